@@ -29,6 +29,8 @@
 //        rules, indexed by their `triggerName`.
 //      - It uses a finalizer to ensure that when a rule is deleted from the cluster,
 //        it is also cleanly removed from the in-memory cache.
+//      - It validates the rule's actions and reports the result through the
+//        standard `Ready`/`Progressing`/`Degraded` conditions.
 //
 //   2. Webhook Server (using `warp`):
 //      - Exposes an HTTP endpoint (`/webhook`) to receive POST requests from an
@@ -44,10 +46,14 @@
 //        (`autoheal-runbooks`) and receives alert context as environment variables.
 //      - After creating the Job, it updates the `phAutoHealRule` status with the
 //        execution timestamp, enabling the cooldown logic for subsequent alerts.
+//      - Every remediation (and every rejected rule) is announced with a
+//        Kubernetes Event on the rule.
 //
+use crate::controllers::conditions::{self, ResourceState};
+use crate::controllers::events;
 use crate::crds::{
     phAutoHealRule, phAutoHealRuleStatus, ActionSpec, HealState, NotifyAction, RedeployAction,
-    RunbookSpec, ScaleUpAction, SnapshotAction, StatusCondition,
+    RunbookSpec, ScaleUpAction, SnapshotAction,
 };
 use anyhow::{anyhow, Context, Result as AnyhowResult};
 use chrono::{DateTime, Utc};
@@ -63,6 +69,7 @@ use kube::{
     client::Client,
     runtime::{
         controller::{Action, Controller},
+        events::Recorder,
         finalizer::{finalizer, Event as FinalizerEvent},
     },
     Resource, ResourceExt,
@@ -109,8 +116,14 @@ struct Context {
     client: Client,
     /// In-memory cache of auto-heal rules, indexed by trigger name for fast lookups.
     rules_cache: Arc<RwLock<HashMap<String, phAutoHealRule>>>,
+    /// Publishes Kubernetes Events about auto-heal rules.
+    recorder: Recorder,
 }
 
+/// Shared state for the webhook handlers: the rule cache, the client and the
+/// event recorder.
+type WebhookContext = Arc<(Arc<RwLock<HashMap<String, phAutoHealRule>>>, Client, Recorder)>;
+
 // --- Alertmanager Webhook Structures ---
 
 /// Represents the top-level payload received from Alertmanager.
@@ -136,11 +149,13 @@ pub async fn run(client: Client) {
     // The shared cache is wrapped in Arc<RwLock<...>> to allow safe concurrent
     // access from both the reconciler loop and the webhook server threads.
     let rules_cache = Arc::new(RwLock::new(HashMap::new()));
+    let recorder = events::recorder(client.clone(), "ph-autoheal-controller");
 
     // Spawn the webhook server as a separate, long-running task.
     let webhook_task = tokio::spawn(run_webhook_server(
         rules_cache.clone(),
         client.clone(),
+        recorder.clone(),
     ));
 
     // Configure and run the main controller loop.
@@ -151,6 +166,7 @@ pub async fn run(client: Client) {
             Arc::new(Context {
                 client,
                 rules_cache,
+                recorder,
             }),
         )
         .for_each(|res| async move {
@@ -184,10 +200,13 @@ async fn reconcile(rule: Arc<phAutoHealRule>, ctx: Arc<Context>) -> Result<Actio
         match event {
             // On resource creation or update, add/update the rule in the cache.
             FinalizerEvent::Apply(rule) => {
-                let mut cache = ctx.rules_cache.write().await;
-                let trigger_name = rule.spec.trigger_name.clone();
-                info!(trigger = %trigger_name, "Updating rule in cache");
-                cache.insert(trigger_name, rule.as_ref().clone());
+                {
+                    let mut cache = ctx.rules_cache.write().await;
+                    let trigger_name = rule.spec.trigger_name.clone();
+                    info!(trigger = %trigger_name, "Updating rule in cache");
+                    cache.insert(trigger_name, rule.as_ref().clone());
+                }
+                report_rule_validity(&rule, &ctx).await?;
                 Ok(Action::requeue(Duration::from_secs(3600)))
             }
             // On resource deletion, remove the rule from the cache.
@@ -204,6 +223,33 @@ async fn reconcile(rule: Arc<phAutoHealRule>, ctx: Arc<Context>) -> Result<Actio
     .map_err(|e| Error::FinalizerError(e.into()))
 }
 
+/// Validates the actions of a rule and reports the result through its
+/// conditions. A rule that already reports the right state is left untouched,
+/// so the remediation outcome written by the webhook is kept.
+async fn report_rule_validity(rule: &phAutoHealRule, ctx: &Context) -> Result<(), Error> {
+    let mut rule_conditions = rule.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default();
+    let current = conditions::current_state(&rule_conditions);
+    match parse_actions_str(&rule.spec.actions_str) {
+        Ok(_) if current == Some(ResourceState::Ready) => return Ok(()),
+        Ok(actions) => {
+            let message = format!("Watching for alert '{}' with {} action(s).", rule.spec.trigger_name, actions.len());
+            conditions::set_state(&mut rule_conditions, ResourceState::Ready, "RuleActive", &message, rule.metadata.generation, Utc::now());
+        }
+        Err(_) if current == Some(ResourceState::Degraded) => return Ok(()),
+        Err(e) => {
+            let message = format!("Failed to parse actions_str: {}", e);
+            events::warning(&ctx.recorder, rule, "InvalidSpec", "Validate", message.clone()).await;
+            conditions::set_state(&mut rule_conditions, ResourceState::Degraded, "InvalidSpec", &message, rule.metadata.generation, Utc::now());
+        }
+    }
+
+    let ns = rule.namespace().ok_or(Error::MissingObjectKey("namespace"))?;
+    let api: Api<phAutoHealRule> = Api::namespaced(ctx.client.clone(), &ns);
+    let patch = json!({ "status": { "conditions": rule_conditions } });
+    api.patch_status(&rule.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    Ok(())
+}
+
 /// Defines the action to take when reconciliation fails.
 fn error_policy(_rule: Arc<phAutoHealRule>, error: &Error, _ctx: Arc<Context>) -> Action {
     warn!("Reconciliation failed: {}", error);
@@ -215,8 +261,8 @@ fn error_policy(_rule: Arc<phAutoHealRule>, error: &Error, _ctx: Arc<Context>) -
 
 /// A helper function to inject the shared context into warp filters.
 fn with_context(
-    ctx: Arc<(Arc<RwLock<HashMap<String, phAutoHealRule>>>, Client)>,
-) -> impl Filter<Extract = (Arc<(Arc<RwLock<HashMap<String, phAutoHealRule>>>, Client)>,), Error = std::convert::Infallible> + Clone {
+    ctx: WebhookContext,
+) -> impl Filter<Extract = (WebhookContext,), Error = std::convert::Infallible> + Clone {
     warp::any().map(move || ctx.clone())
 }
 
@@ -224,8 +270,9 @@ fn with_context(
 async fn run_webhook_server(
     rules_cache: Arc<RwLock<HashMap<String, phAutoHealRule>>>,
     client: Client,
+    recorder: Recorder,
 ) {
-    let context = Arc::new((rules_cache, client));
+    let context = Arc::new((rules_cache, client, recorder));
 
     let webhook_route = warp::post()
         .and(warp::path("webhook"))
@@ -241,9 +288,9 @@ async fn run_webhook_server(
 #[instrument(skip(payload, ctx))]
 async fn handle_webhook(
     payload: AlertmanagerPayload,
-    ctx: Arc<(Arc<RwLock<HashMap<String, phAutoHealRule>>>, Client)>,
+    ctx: WebhookContext,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let (rules_cache, client) = &**ctx;
+    let (rules_cache, client, recorder) = &**ctx;
     info!("Received {} alert(s) from Alertmanager", payload.alerts.len());
 
     for alert in payload.alerts {
@@ -262,12 +309,13 @@ async fn handle_webhook(
             // To avoid holding the read lock for too long, we clone the necessary data.
             let rule_clone = rule.clone();
             let client_clone = client.clone();
+            let recorder_clone = recorder.clone();
             let alert_clone = alert.clone();
 
             // Spawn a new task to handle the rule execution asynchronously.
             // This allows the webhook to respond quickly to Alertmanager.
             tokio::spawn(async move {
-                if let Err(e) = process_rule(rule_clone, alert_clone, client_clone).await {
+                if let Err(e) = process_rule(rule_clone, alert_clone, client_clone, recorder_clone).await {
                     error!(error = %e, "Failed to process auto-heal rule");
                 }
             });
@@ -283,7 +331,7 @@ async fn handle_webhook(
 // --- Rule Processing and Action Execution ---
 
 /// Processes a single rule: checks cooldown and executes the defined actions if applicable.
-async fn process_rule(rule: phAutoHealRule, alert: Alert, client: Client) -> Result<(), Error> {
+async fn process_rule(rule: phAutoHealRule, alert: Alert, client: Client, recorder: Recorder) -> Result<(), Error> {
     // 1. Parse actions string from the spec.
     let actions = match parse_actions_str(&rule.spec.actions_str) {
         Ok(actions) => actions,
@@ -291,6 +339,7 @@ async fn process_rule(rule: phAutoHealRule, alert: Alert, client: Client) -> Res
             // If parsing fails, update the CRD status with an error and stop.
             let error_message = format!("Failed to parse actions_str: {}", e);
             error!(rule = %rule.name_any(), error = %error_message, "Invalid rule spec");
+            events::warning(&recorder, &rule, "InvalidSpec", "Remediate", error_message.clone()).await;
             update_status_with_error(&rule, &client, &error_message).await?;
             // Return Ok here because the error is with the resource, not the controller.
             // The controller has done its job by reporting the invalid spec.
@@ -314,28 +363,42 @@ async fn process_rule(rule: phAutoHealRule, alert: Alert, client: Client) -> Res
 
     // 3. Execute all defined actions sequentially.
     info!(rule = %rule.name_any(), "Executing {} action(s) for rule", actions.len());
+    events::normal(
+        &recorder,
+        &rule,
+        "RemediationStarted",
+        "Remediate",
+        format!("Alert '{}' fired; executing {} action(s).", rule.spec.trigger_name, actions.len()),
+    )
+    .await;
+    let mut failed_actions = Vec::new();
     for (i, action) in actions.iter().enumerate() {
         info!(action_index = i + 1, "Executing action");
         if let Some(redeploy) = &action.redeploy {
             if let Err(e) = execute_redeploy_action(&rule, &alert, &client, redeploy).await {
                 error!(error = %e, "Redeploy action failed");
+                failed_actions.push(format!("redeploy: {}", e));
                 // Optionally, decide if we should stop processing further actions on failure
             }
         } else if let Some(scale_up) = &action.scale_up {
             if let Err(e) = execute_scale_up_action(&rule, &alert, &client, scale_up).await {
                 error!(error = %e, "Scale-up action failed");
+                failed_actions.push(format!("scale-up: {}", e));
             }
         } else if let Some(runbook) = &action.runbook {
             if let Err(e) = execute_runbook_action(&rule, &alert, &client, runbook).await {
                 error!(error = %e, "Runbook action failed");
+                failed_actions.push(format!("runbook: {}", e));
             }
         } else if let Some(notify) = &action.notify {
             if let Err(e) = execute_notify_action(&rule, &alert, &client, notify).await {
                 error!(error = %e, "Notify action failed");
+                failed_actions.push(format!("notify: {}", e));
             }
         } else if let Some(snapshot) = &action.snapshot {
             if let Err(e) = execute_snapshot_action(&rule, &alert, &client, snapshot).await {
                 error!(error = %e, "Snapshot action failed");
+                failed_actions.push(format!("snapshot: {}", e));
             }
         } else {
             warn!("Action at index {} is empty or has an unknown type.", i);
@@ -343,6 +406,18 @@ async fn process_rule(rule: phAutoHealRule, alert: Alert, client: Client) -> Res
     }
 
     // 4. Update Status after all actions are attempted.
+    if failed_actions.is_empty() {
+        events::normal(&recorder, &rule, "Healed", "Remediate", "Auto-heal actions executed successfully.").await;
+    } else {
+        events::warning(
+            &recorder,
+            &rule,
+            "ActionFailed",
+            "Remediate",
+            format!("{} action(s) failed: {}", failed_actions.len(), failed_actions.join("; ")),
+        )
+        .await;
+    }
     update_status_after_success(&rule, &client).await?;
 
     Ok(())
@@ -616,15 +691,20 @@ async fn update_status_after_success(rule: &phAutoHealRule, client: &Client) ->
     let ns = rule.namespace().ok_or(Error::MissingObjectKey("namespace"))?;
     let api: Api<phAutoHealRule> = Api::namespaced(client.clone(), &ns);
 
-    let new_status = phAutoHealRuleStatus {
+    let mut new_status = phAutoHealRuleStatus {
         state: Some(HealState::Cooldown),
         last_execution_time: Some(Utc::now().to_rfc3339()),
         executions_count: Some(rule.status.as_ref().and_then(|s| s.executions_count).unwrap_or(0) + 1),
-        conditions: vec![StatusCondition {
-            type_: "Succeeded".to_string(),
-            message: "Auto-heal actions executed successfully.".to_string(),
-        }],
+        conditions: rule.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default(),
     };
+    conditions::set_state(
+        &mut new_status.conditions,
+        ResourceState::Ready,
+        "Healed",
+        "Auto-heal actions executed successfully.",
+        rule.metadata.generation,
+        Utc::now(),
+    );
 
     let patch = Patch::Apply(json!({ "status": new_status }));
     let ps = PatchParams::apply("ph-operator-autoheal-controller").force();
@@ -638,15 +718,20 @@ async fn update_status_with_error(rule: &phAutoHealRule, client: &Client, error_
     let ns = rule.namespace().ok_or(Error::MissingObjectKey("namespace"))?;
     let api: Api<phAutoHealRule> = Api::namespaced(client.clone(), &ns);
 
-    let new_status = phAutoHealRuleStatus {
+    let mut new_status = phAutoHealRuleStatus {
         state: Some(HealState::Failed),
         last_execution_time: None, // No execution happened
         executions_count: rule.status.as_ref().and_then(|s| s.executions_count),
-        conditions: vec![StatusCondition {
-            type_: "InvalidSpec".to_string(),
-            message: error_message.to_string(),
-        }],
+        conditions: rule.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default(),
     };
+    conditions::set_state(
+        &mut new_status.conditions,
+        ResourceState::Degraded,
+        "InvalidSpec",
+        error_message,
+        rule.metadata.generation,
+        Utc::now(),
+    );
 
     let patch = Patch::Apply(json!({ "status": new_status }));
     let ps = PatchParams::apply("ph-operator-autoheal-controller").force();
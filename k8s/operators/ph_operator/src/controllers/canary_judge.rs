@@ -0,0 +1,266 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/canary_judge.rs
+*
+* This file implements the baseline-vs-canary judge used by release analysis.
+* Instead of checking a metric against an absolute threshold, the judge samples
+* the same metric from the stable (baseline) and canary pods over a lookback
+* window and decides whether the canary is a real regression or just noise
+* that affects both versions equally (e.g. a traffic peak).
+*
+* Architecture:
+* - `mann_whitney_u` runs a two-sided Mann-Whitney U test (normal
+*   approximation with tie and continuity correction) on the two samples.
+* - `judge_metric` fails a metric only when the difference is statistically
+*   significant, points in the regression `direction`, and the relative
+*   difference of the medians exceeds the `tolerance`.
+* - `overall_score` combines the per-metric scores (`100` pass, `0` fail) into a
+*   weighted 0-100 score, and `classify_score` maps it onto an
+*   `AnalysisResult` using the `ScoreThresholds`.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::crds::{BaselineComparison, MetricClassification, MetricScore, RegressionDirection, ScoreThresholds};
+use crate::metrics_analyzer::AnalysisResult;
+
+const DEFAULT_TOLERANCE: f64 = 0.1;
+const DEFAULT_SIGNIFICANCE: f64 = 0.05;
+
+/// The outcome of a Mann-Whitney U test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MannWhitneyResult {
+    /// The U statistic of the canary sample.
+    pub u: f64,
+    pub z: f64,
+    /// Two-sided p-value.
+    pub p_value: f64,
+}
+
+/// Runs a two-sided Mann-Whitney U test. Returns `None` if either sample is empty.
+pub fn mann_whitney_u(baseline: &[f64], canary: &[f64]) -> Option<MannWhitneyResult> {
+    if baseline.is_empty() || canary.is_empty() {
+        return None;
+    }
+
+    let n1 = canary.len() as f64;
+    let n2 = baseline.len() as f64;
+    let n = n1 + n2;
+
+    // Rank the pooled samples, averaging the ranks of ties.
+    let mut pooled: Vec<(f64, bool)> = canary
+        .iter()
+        .map(|v| (*v, true))
+        .chain(baseline.iter().map(|v| (*v, false)))
+        .collect();
+    pooled.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut canary_rank_sum = 0.0;
+    let mut tie_term = 0.0;
+    let mut i = 0;
+    while i < pooled.len() {
+        let mut j = i;
+        while j + 1 < pooled.len() && pooled[j + 1].0 == pooled[i].0 {
+            j += 1;
+        }
+        let average_rank = (i + j) as f64 / 2.0 + 1.0;
+        let ties = (j - i + 1) as f64;
+        tie_term += ties.powi(3) - ties;
+        canary_rank_sum += pooled[i..=j].iter().filter(|(_, is_canary)| *is_canary).count() as f64 * average_rank;
+        i = j + 1;
+    }
+
+    let u = canary_rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        // Every value is identical: there is no evidence of a difference.
+        return Some(MannWhitneyResult { u, z: 0.0, p_value: 1.0 });
+    }
+
+    let continuity = if u > mean { -0.5 } else if u < mean { 0.5 } else { 0.0 };
+    let z = (u - mean + continuity) / variance.sqrt();
+    let p_value = erfc(z.abs() / std::f64::consts::SQRT_2).min(1.0);
+    Some(MannWhitneyResult { u, z, p_value })
+}
+
+/// Judges a single metric from its baseline and canary samples.
+pub fn judge_metric(name: &str, comparison: &BaselineComparison, baseline: &[f64], canary: &[f64]) -> MetricScore {
+    let (Some(test), Some(baseline_median), Some(canary_median)) =
+        (mann_whitney_u(baseline, canary), median(baseline), median(canary))
+    else {
+        return MetricScore {
+            name: name.to_string(),
+            classification: MetricClassification::NoData,
+            score: None,
+            p_value: None,
+            relative_difference: None,
+        };
+    };
+
+    let relative_difference = relative_difference(baseline_median, canary_median);
+    let regressed = match comparison.direction {
+        RegressionDirection::Increase => canary_median > baseline_median,
+        RegressionDirection::Decrease => canary_median < baseline_median,
+        RegressionDirection::Either => canary_median != baseline_median,
+    };
+    let significant = test.p_value < comparison.significance.unwrap_or(DEFAULT_SIGNIFICANCE);
+    let beyond_tolerance = relative_difference.abs() > comparison.tolerance.unwrap_or(DEFAULT_TOLERANCE);
+
+    let classification = if regressed && significant && beyond_tolerance {
+        MetricClassification::Fail
+    } else {
+        MetricClassification::Pass
+    };
+    MetricScore {
+        name: name.to_string(),
+        score: Some(if classification == MetricClassification::Pass { 100.0 } else { 0.0 }),
+        classification,
+        p_value: Some(test.p_value),
+        // JSON cannot carry infinities, so a change from a zero baseline is capped.
+        relative_difference: Some(relative_difference.clamp(-f64::MAX, f64::MAX)),
+    }
+}
+
+/// Combines per-metric scores into a weighted 0-100 score, ignoring metrics without data.
+/// Returns `None` if no metric had data.
+pub fn overall_score(scores: &[(MetricScore, f64)]) -> Option<f64> {
+    let (weighted, total_weight) = scores
+        .iter()
+        .filter_map(|(metric, weight)| metric.score.map(|score| (score * weight, *weight)))
+        .fold((0.0, 0.0), |(sum, total), (score, weight)| (sum + score, total + weight));
+    (total_weight > 0.0).then(|| weighted / total_weight)
+}
+
+/// Maps an overall score onto an analysis outcome.
+pub fn classify_score(score: Option<f64>, thresholds: &ScoreThresholds) -> AnalysisResult {
+    match score {
+        Some(score) if score >= thresholds.pass => AnalysisResult::Success,
+        Some(score) if score < thresholds.marginal => AnalysisResult::Failure,
+        _ => AnalysisResult::Inconclusive,
+    }
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    Some(if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2.0 } else { sorted[mid] })
+}
+
+fn relative_difference(baseline: f64, canary: f64) -> f64 {
+    if baseline == canary {
+        0.0
+    } else if baseline == 0.0 {
+        f64::INFINITY.copysign(canary)
+    } else {
+        (canary - baseline) / baseline.abs()
+    }
+}
+
+/// Complementary error function (Numerical Recipes `erfcc`, fractional error below 1.2e-7).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t * (-z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+        .exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison(direction: RegressionDirection) -> BaselineComparison {
+        BaselineComparison {
+            query: "rate(errors{version_id=\"{{track}}\"}[1m])".to_string(),
+            direction,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_mann_whitney_u() {
+        // Reference p-value from scipy.stats.mannwhitneyu(..., method="asymptotic").
+        let baseline = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let canary = [6.0, 7.0, 8.0, 9.0, 10.0];
+        let result = mann_whitney_u(&baseline, &canary).unwrap();
+        assert_eq!(result.u, 25.0);
+        assert!((result.p_value - 0.01219).abs() < 1e-3, "p = {}", result.p_value);
+
+        let same = mann_whitney_u(&baseline, &baseline).unwrap();
+        assert!(same.p_value > 0.9);
+
+        let constant = mann_whitney_u(&[1.0, 1.0], &[1.0, 1.0]).unwrap();
+        assert_eq!(constant.p_value, 1.0);
+
+        assert!(mann_whitney_u(&[], &canary).is_none());
+    }
+
+    #[test]
+    fn test_judge_metric_flags_significant_regression() {
+        let baseline = [0.010, 0.011, 0.009, 0.010, 0.012, 0.010, 0.011, 0.009];
+        let canary = [0.030, 0.028, 0.031, 0.029, 0.033, 0.030, 0.032, 0.027];
+
+        let score = judge_metric("errors", &comparison(RegressionDirection::Increase), &baseline, &canary);
+        assert_eq!(score.classification, MetricClassification::Fail);
+        assert_eq!(score.score, Some(0.0));
+        assert!(score.relative_difference.unwrap() > 1.0);
+
+        // The same shift is an improvement when lower values are worse.
+        let score = judge_metric("throughput", &comparison(RegressionDirection::Decrease), &baseline, &canary);
+        assert_eq!(score.classification, MetricClassification::Pass);
+    }
+
+    #[test]
+    fn test_judge_metric_tolerates_small_or_noisy_differences() {
+        // Both versions see the same traffic peak: no significant difference.
+        let baseline = [100.0, 250.0, 400.0, 120.0, 380.0, 90.0];
+        let canary = [110.0, 240.0, 390.0, 130.0, 370.0, 95.0];
+        let score = judge_metric("latency", &comparison(RegressionDirection::Increase), &baseline, &canary);
+        assert_eq!(score.classification, MetricClassification::Pass);
+
+        // A consistent but small (5%) increase stays within the default 10% tolerance.
+        let baseline = [100.0, 101.0, 99.0, 100.0, 102.0, 98.0, 100.0, 101.0];
+        let canary: Vec<f64> = baseline.iter().map(|v| v * 1.05).collect();
+        let score = judge_metric("latency", &comparison(RegressionDirection::Increase), &baseline, &canary);
+        assert_eq!(score.classification, MetricClassification::Pass);
+
+        let score = judge_metric("latency", &comparison(RegressionDirection::Increase), &[], &canary);
+        assert_eq!(score.classification, MetricClassification::NoData);
+        assert_eq!(score.score, None);
+    }
+
+    #[test]
+    fn test_overall_score_and_classification() {
+        let metric = |score: Option<f64>| MetricScore {
+            name: "m".to_string(),
+            classification: MetricClassification::Pass,
+            score,
+            p_value: None,
+            relative_difference: None,
+        };
+        let scores = vec![(metric(Some(100.0)), 3.0), (metric(Some(0.0)), 1.0), (metric(None), 5.0)];
+        assert_eq!(overall_score(&scores), Some(75.0));
+        assert_eq!(overall_score(&[(metric(None), 1.0)]), None);
+
+        let thresholds = ScoreThresholds::default();
+        assert_eq!(classify_score(Some(100.0), &thresholds), AnalysisResult::Success);
+        assert_eq!(classify_score(Some(80.0), &thresholds), AnalysisResult::Inconclusive);
+        assert_eq!(classify_score(Some(50.0), &thresholds), AnalysisResult::Failure);
+        assert_eq!(classify_score(None, &thresholds), AnalysisResult::Inconclusive);
+    }
+}
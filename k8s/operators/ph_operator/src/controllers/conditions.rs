@@ -0,0 +1,145 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/conditions.rs
+*
+* This file maintains the standard status conditions shared by all ph CRDs.
+* Conditions follow the `metav1.Condition` conventions, so `kubectl describe`
+* lists them and `kubectl wait --for=condition=Ready` works on our resources.
+*
+* Architecture:
+* - Every resource reports the same three condition types: `Ready` (the
+*   desired state was reached), `Progressing` (the controller is working
+*   towards it) and `Degraded` (it failed or is unhealthy).
+* - `set_state` keeps the three in sync: exactly one of them is `True`.
+* - `set_condition` only moves `lastTransitionTime` when the status of a
+*   condition actually changes, as the conventions require.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::crds::StatusCondition;
+use chrono::{DateTime, Utc};
+
+pub const READY: &str = "Ready";
+pub const PROGRESSING: &str = "Progressing";
+pub const DEGRADED: &str = "Degraded";
+
+/// The overall state of a resource, as reported by its standard conditions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceState {
+    Ready,
+    Progressing,
+    Degraded,
+}
+
+impl ResourceState {
+    fn condition_type(self) -> &'static str {
+        match self {
+            ResourceState::Ready => READY,
+            ResourceState::Progressing => PROGRESSING,
+            ResourceState::Degraded => DEGRADED,
+        }
+    }
+}
+
+/// Inserts `condition`, replacing any condition of the same type. The previous
+/// `lastTransitionTime` is kept when the status did not change.
+pub fn set_condition(conditions: &mut Vec<StatusCondition>, mut condition: StatusCondition) {
+    match conditions.iter_mut().find(|c| c.type_ == condition.type_) {
+        Some(existing) => {
+            if existing.status == condition.status && !existing.last_transition_time.is_empty() {
+                condition.last_transition_time = existing.last_transition_time.clone();
+            }
+            *existing = condition;
+        }
+        None => conditions.push(condition),
+    }
+}
+
+/// Sets the `Ready`, `Progressing` and `Degraded` conditions for a resource in
+/// `state`. All three carry `reason` and `message`, so a `False` condition
+/// still explains why.
+pub fn set_state(
+    conditions: &mut Vec<StatusCondition>,
+    state: ResourceState,
+    reason: &str,
+    message: &str,
+    observed_generation: Option<i64>,
+    now: DateTime<Utc>,
+) {
+    for candidate in [ResourceState::Ready, ResourceState::Progressing, ResourceState::Degraded] {
+        set_condition(
+            conditions,
+            StatusCondition {
+                type_: candidate.condition_type().to_string(),
+                status: if candidate == state { "True" } else { "False" }.to_string(),
+                reason: reason.to_string(),
+                message: message.to_string(),
+                last_transition_time: now.to_rfc3339(),
+                observed_generation,
+            },
+        );
+    }
+}
+
+/// Returns the state whose condition is `True`, if any.
+pub fn current_state(conditions: &[StatusCondition]) -> Option<ResourceState> {
+    [ResourceState::Ready, ResourceState::Progressing, ResourceState::Degraded]
+        .into_iter()
+        .find(|state| {
+            conditions
+                .iter()
+                .any(|c| c.type_ == state.condition_type() && c.status == "True")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(conditions: &'a [StatusCondition], type_: &str) -> &'a StatusCondition {
+        conditions.iter().find(|c| c.type_ == type_).unwrap()
+    }
+
+    #[test]
+    fn test_set_state() {
+        let start = Utc::now();
+        let later = start + chrono::Duration::minutes(5);
+        let mut conditions = Vec::new();
+
+        set_state(&mut conditions, ResourceState::Progressing, "Deploying", "Applying manifests", Some(1), start);
+        assert_eq!(conditions.len(), 3);
+        assert_eq!(current_state(&conditions), Some(ResourceState::Progressing));
+        assert_eq!(find(&conditions, READY).status, "False");
+        assert_eq!(find(&conditions, READY).observed_generation, Some(1));
+
+        set_state(&mut conditions, ResourceState::Ready, "Deployed", "All resources are healthy", Some(2), later);
+        assert_eq!(conditions.len(), 3);
+        assert_eq!(current_state(&conditions), Some(ResourceState::Ready));
+        let ready = find(&conditions, READY);
+        assert_eq!((ready.status.as_str(), ready.reason.as_str()), ("True", "Deployed"));
+        assert_eq!(ready.last_transition_time, later.to_rfc3339());
+        // Degraded stayed False, so its transition time is unchanged.
+        let degraded = find(&conditions, DEGRADED);
+        assert_eq!(degraded.last_transition_time, start.to_rfc3339());
+        assert_eq!(degraded.reason, "Deployed");
+    }
+
+    #[test]
+    fn test_set_condition_without_transition_time() {
+        // Conditions written before transition times were recorded get one.
+        let mut conditions = vec![StatusCondition {
+            type_: READY.to_string(),
+            status: "True".to_string(),
+            reason: String::new(),
+            message: String::new(),
+            last_transition_time: String::new(),
+            observed_generation: None,
+        }];
+        let now = Utc::now();
+        set_state(&mut conditions, ResourceState::Ready, "Reconciled", "", None, now);
+        assert_eq!(find(&conditions, READY).last_transition_time, now.to_rfc3339());
+        assert_eq!(current_state(&[]), None);
+    }
+}
@@ -26,13 +26,19 @@
 * - Failed: If any step in the failover process fails, the state transitions
 *   to Failed, requiring manual investigation.
 *
+* Every state is mirrored in the standard `Ready`/`Progressing`/`Degraded`
+* conditions, and each transition is announced with a Kubernetes Event.
+*
 * SPDX-License-Identifier: Apache-2.0
 */
 
+use crate::controllers::conditions::{self, ResourceState};
+use crate::controllers::events;
 use crate::controllers::utils::{replicate_configmaps, replicate_secrets};
 use crate::crds::{
     ActiveCluster, DRState, PhgitDisasterRecovery, PhgitDisasterRecoveryStatus,
 };
+use crate::expression::{Expression, Variables};
 use crate::metrics_analyzer::{AnalysisResult, PrometheusClient};
 use anyhow::Result;
 use chrono::Utc;
@@ -41,7 +47,7 @@ use k8s_openapi::api::core::v1::{ConfigMap, Secret};
 use kube::{
     api::{Api, ListParams, Patch, PatchParams},
     client::Client,
-    runtime::controller::Action,
+    runtime::{controller::Action, events::Recorder},
     Config, Resource, ResourceExt,
 };
 use reqwest;
@@ -73,6 +79,8 @@ pub enum Error {
 pub struct Context {
     pub client: Client, // This is the client for the operator's own cluster
     pub prometheus_client: PrometheusClient,
+    /// Publishes Kubernetes Events about DR resources.
+    pub recorder: Recorder,
 }
 
 /// Creates a Kubernetes client for a remote cluster using a kubeconfig from a Secret.
@@ -139,21 +147,28 @@ pub async fn reconcile(
                         .success_condition
                         .as_deref()
                         .unwrap_or("value > 0"); // Default for backward compatibility
-                    
-                    let expression = success_condition.replace("value", &metric_value.to_string());
 
-                    match evaluate_simple_expression(&expression) {
+                    // `value` is kept for existing policies; `result` matches release analysis.
+                    let variables = Variables::from([
+                        ("value".to_string(), metric_value),
+                        ("result".to_string(), metric_value),
+                    ]);
+
+                    match Expression::parse(success_condition)
+                        .and_then(|expression| expression.evaluate_condition(&variables))
+                    {
                         Ok(is_success) => {
                             check_is_successful = is_success;
                             println!(
-                                "Health check for '{}': expression '{}' evaluated to {}",
+                                "Health check for '{}': condition '{}' with value {} evaluated to {}",
                                 dr_resource.name_any(),
-                                expression,
+                                success_condition,
+                                metric_value,
                                 is_success
                             );
                         }
                         Err(e) => {
-                            eprintln!("Failed to evaluate health check expression '{}': {}", expression, e);
+                            eprintln!("Failed to evaluate health check condition '{}': {}", success_condition, e);
                             check_is_successful = false; // Treat evaluation error as failure
                         }
                     }
@@ -179,10 +194,21 @@ pub async fn reconcile(
                 if new_status.consecutive_failures >= health_policy.failure_threshold {
                     println!("Failure threshold reached. Moving to Degraded state.");
                     new_status.state = Some(DRState::Degraded);
+                    events::warning(
+                        &ctx.recorder,
+                        &*dr_resource,
+                        "HealthCheckFailed",
+                        "Monitor",
+                        format!(
+                            "Health check failed {} consecutive times; the application is degraded.",
+                            new_status.consecutive_failures
+                        ),
+                    )
+                    .await;
                 }
             }
             
-            update_status(&dr_api, &dr_resource.name_any(), new_status).await?;
+            update_status(&dr_api, &dr_resource, new_status).await?;
             Ok(Action::requeue(interval))
         }
         DRState::Degraded => {
@@ -205,11 +231,19 @@ pub async fn reconcile(
             }
 
             if should_failover {
+                events::normal(
+                    &ctx.recorder,
+                    &*dr_resource,
+                    "FailoverStarted",
+                    "Failover",
+                    format!("Failing over '{}' to the DR cluster.", spec.target_application.deployment_name),
+                )
+                .await;
                 let new_status = PhgitDisasterRecoveryStatus {
                     state: Some(DRState::FailingOver),
                     ..status
                 };
-                update_status(&dr_api, &dr_resource.name_any(), new_status).await?;
+                update_status(&dr_api, &dr_resource, new_status).await?;
                 Ok(Action::requeue(Duration::from_secs(1))) // Requeue immediately
             } else {
                 Ok(Action::requeue(Duration::from_secs(30))) // Wait for annotation
@@ -271,8 +305,16 @@ pub async fn reconcile(
                 active_cluster: Some(ActiveCluster::DR),
                 ..status
             };
-            update_status(&dr_api, &dr_resource.name_any(), new_status).await?;
+            update_status(&dr_api, &dr_resource, new_status).await?;
             println!("Failover complete for '{}'. Application is now active on DR cluster.", dr_resource.name_any());
+            events::normal(
+                &ctx.recorder,
+                &*dr_resource,
+                "FailoverSucceeded",
+                "Failover",
+                format!("'{}' is now running on the DR cluster with {} replicas.", app_name, replicas),
+            )
+            .await;
             
             // 5. Send notification if configured
             if let Some(notification) = &spec.policy.notification {
@@ -308,55 +350,64 @@ fn parse_duration_str(s: &str) -> Result<Duration, Error> {
     }
 }
 
-use anyhow::Context;
-
-/// Evaluates simple comparison expressions.
-fn evaluate_simple_expression(expression: &str) -> Result<bool, anyhow::Error> {
-    let expression = expression.trim();
-    if let Some(pos) = expression.find("&&") {
-        let left_expr = expression[..pos].trim();
-        let right_expr = expression[pos + 2..].trim();
-        let left_result = evaluate_simple_expression(left_expr)?;
-        let right_result = evaluate_simple_expression(right_expr)?;
-        return Ok(left_result && right_result);
-    }
-    if let Some(pos) = expression.find("||") {
-        let left_expr = expression[..pos].trim();
-        let right_expr = expression[pos + 2..].trim();
-        let left_result = evaluate_simple_expression(left_expr)?;
-        let right_result = evaluate_simple_expression(right_expr)?;
-        return Ok(left_result || right_result);
-    }
-    let operators = ["<=", ">=", "==", "!=", "<", ">"];
-    for op in &operators {
-        if let Some(pos) = expression.find(op) {
-            let left_str = expression[..pos].trim();
-            let right_str = expression[pos + op.len()..].trim();
-            let left: f64 = left_str.parse().with_context(|| format!("Failed to parse left operand '{}' as number", left_str))?;
-            let right: f64 = right_str.parse().with_context(|| format!("Failed to parse right operand '{}' as number", right_str))?;
-            let result = match *op {
-                "<" => left < right,
-                "<=" => left <= right,
-                ">" => left > right,
-                ">=" => left >= right,
-                "==" => (left - right).abs() < f64::EPSILON,
-                "!=" => (left - right).abs() >= f64::EPSILON,
-                _ => unreachable!(),
-            };
-            return Ok(result);
+/// Derives the standard conditions from the DR state. A `Degraded` condition
+/// set for a failure keeps its more specific reason and message.
+fn sync_conditions(dr_resource: &PhgitDisasterRecovery, status: &mut PhgitDisasterRecoveryStatus) {
+    let (state, reason, message) = match status.state.clone().unwrap_or(DRState::Monitoring) {
+        DRState::Monitoring if status.consecutive_failures == 0 => (
+            ResourceState::Ready,
+            "Healthy",
+            "The application is healthy on the primary cluster".to_string(),
+        ),
+        DRState::Monitoring => (
+            ResourceState::Ready,
+            "HealthCheckFailing",
+            format!("{} consecutive health check failures", status.consecutive_failures),
+        ),
+        DRState::Degraded => (
+            ResourceState::Degraded,
+            "HealthCheckFailed",
+            format!(
+                "Health check failed {} consecutive times; awaiting failover",
+                status.consecutive_failures
+            ),
+        ),
+        DRState::FailingOver => (
+            ResourceState::Progressing,
+            "FailingOver",
+            "Failing over to the DR cluster".to_string(),
+        ),
+        DRState::ActiveOnDR => (
+            ResourceState::Ready,
+            "ActiveOnDR",
+            "The application is active on the DR cluster".to_string(),
+        ),
+        DRState::Failed => {
+            if conditions::current_state(&status.conditions) == Some(ResourceState::Degraded) {
+                return;
+            }
+            (ResourceState::Degraded, "FailoverFailed", "The failover failed".to_string())
         }
-    }
-    Err(anyhow!("Unsupported expression format: '{}'", expression))
+    };
+    conditions::set_state(
+        &mut status.conditions,
+        state,
+        reason,
+        &message,
+        dr_resource.metadata.generation,
+        Utc::now(),
+    );
 }
 
 /// A helper to patch the status subresource of a PhgitDisasterRecovery.
 async fn update_status(
     api: &Api<PhgitDisasterRecovery>,
-    name: &str,
-    status: PhgitDisasterRecoveryStatus,
+    dr_resource: &PhgitDisasterRecovery,
+    mut status: PhgitDisasterRecoveryStatus,
 ) -> Result<(), Error> {
+    sync_conditions(dr_resource, &mut status);
     let patch = Patch::Apply(json!({ "status": status }));
-    api.patch_status(name, &PatchParams::apply("ph-dr-controller"), &patch)
+    api.patch_status(&dr_resource.name_any(), &PatchParams::apply("ph-dr-controller"), &patch)
         .await?;
     Ok(())
 }
@@ -375,12 +426,21 @@ pub async fn on_error(
     let ns = dr_resource.namespace().unwrap();
     let api: Api<PhgitDisasterRecovery> = Api::namespaced(ctx.client.clone(), &ns);
 
-    let status = PhgitDisasterRecoveryStatus {
+    events::warning(&ctx.recorder, &*dr_resource, "ReconcileError", "Reconcile", error.to_string()).await;
+    let mut status = PhgitDisasterRecoveryStatus {
         state: Some(DRState::Failed),
         ..dr_resource.status.as_ref().cloned().unwrap_or_default()
     };
+    conditions::set_state(
+        &mut status.conditions,
+        ResourceState::Degraded,
+        "ReconcileError",
+        &error.to_string(),
+        dr_resource.metadata.generation,
+        Utc::now(),
+    );
 
-    if let Err(e) = update_status(&api, &dr_resource.name_any(), status).await {
+    if let Err(e) = update_status(&api, &dr_resource, status).await {
         eprintln!("Failed to update status on error: {}", e);
     }
 
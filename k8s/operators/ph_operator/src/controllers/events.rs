@@ -0,0 +1,80 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/events.rs
+*
+* This file publishes Kubernetes Events about the ph CRDs. Events show up in
+* `kubectl describe` and `kubectl get events`, so the reason a resource changed
+* state can be found without reading the operator logs.
+*
+* Architecture:
+* - Each controller owns a `Recorder` (created with `recorder`) in its
+*   context, reporting as its own component (e.g. `ph-release-controller`).
+* - `normal` and `warning` publish an event about any resource. Publishing is
+*   best-effort: a failure is logged and never fails a reconciliation.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use kube::{
+    runtime::events::{Event, EventType, Recorder, Reporter},
+    Client, Resource, ResourceExt,
+};
+use tracing::warn;
+
+/// The API server rejects event notes longer than 1 KiB.
+const MAX_NOTE_LEN: usize = 1024;
+
+/// Creates a recorder that reports events as `controller`. The pod name, when
+/// set through the downward API, identifies the operator instance.
+pub fn recorder(client: Client, controller: &str) -> Recorder {
+    let reporter = Reporter {
+        controller: controller.to_string(),
+        instance: std::env::var("POD_NAME").ok(),
+    };
+    Recorder::new(client, reporter)
+}
+
+/// Publishes a `Normal` event about `resource`.
+pub async fn normal<K>(recorder: &Recorder, resource: &K, reason: &str, action: &str, note: impl Into<String>)
+where
+    K: Resource<DynamicType = ()>,
+{
+    publish(recorder, resource, EventType::Normal, reason, action, note.into()).await
+}
+
+/// Publishes a `Warning` event about `resource`.
+pub async fn warning<K>(recorder: &Recorder, resource: &K, reason: &str, action: &str, note: impl Into<String>)
+where
+    K: Resource<DynamicType = ()>,
+{
+    publish(recorder, resource, EventType::Warning, reason, action, note.into()).await
+}
+
+async fn publish<K>(recorder: &Recorder, resource: &K, type_: EventType, reason: &str, action: &str, note: String)
+where
+    K: Resource<DynamicType = ()>,
+{
+    let event = Event {
+        type_,
+        reason: reason.to_string(),
+        note: Some(truncate(note, MAX_NOTE_LEN)),
+        action: action.to_string(),
+        secondary: None,
+    };
+    if let Err(e) = recorder.publish(&event, &resource.object_ref(&())).await {
+        warn!("Failed to publish {} event for '{}': {}", reason, resource.name_any(), e);
+    }
+}
+
+/// Shortens `note` to at most `max` bytes without splitting a character.
+fn truncate(mut note: String, max: usize) -> String {
+    if note.len() > max {
+        let mut end = max;
+        while !note.is_char_boundary(end) {
+            end -= 1;
+        }
+        note.truncate(end);
+    }
+    note
+}
@@ -0,0 +1,533 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/expression.rs
+*
+* This file implements the expression language used for success conditions,
+* shared by release analysis (`Metric.onSuccess`) and disaster recovery health
+* checks (`HealthCheckPolicy.successCondition`).
+*
+* Language:
+* - Numbers (`0.95`, `1e-3`), booleans (`true`, `false`) and variables bound
+*   by the caller (`result`, `result.p99`, `value`).
+* - Arithmetic: `+`, `-`, `*`, `/`, `%` and unary `-`.
+* - Comparisons: `<`, `<=`, `>`, `>=`, `==`, `!=`. Number equality uses
+*   `f64::EPSILON` to avoid precision issues.
+* - Logic: `&&`, `||` (short-circuiting) and `!`, with parentheses for grouping.
+* - Functions: `isNaN(x)`, `isInf(x)`, `abs(x)`, `min(a, b)`, `max(a, b)` and
+*   `default(x, fallback)`, which yields `fallback` when `x` is NaN or refers
+*   to a variable that is not bound.
+*
+* Comparing against NaN is an evaluation error rather than silently `false`, so
+* a metric without data is reported as inconclusive unless the condition
+* handles it explicitly with `isNaN` or `default`.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// The variables an expression is evaluated against, keyed by name (e.g. `result.p99`).
+pub type Variables = BTreeMap<String, f64>;
+
+/// Errors raised while parsing or evaluating an expression.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ExpressionError {
+    #[error("unexpected character '{ch}' at position {position}")]
+    UnexpectedCharacter { ch: char, position: usize },
+
+    #[error("invalid number '{0}'")]
+    InvalidNumber(String),
+
+    #[error("unexpected token '{token}' at position {position}")]
+    UnexpectedToken { token: String, position: usize },
+
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+
+    #[error("unknown variable '{0}'")]
+    UnknownVariable(String),
+
+    #[error("unknown function '{0}'")]
+    UnknownFunction(String),
+
+    #[error("function '{name}' expects {expected} argument(s), got {found}")]
+    WrongArity { name: String, expected: usize, found: usize },
+
+    #[error("type mismatch: expected a {expected}, found a {found}")]
+    TypeMismatch { expected: &'static str, found: &'static str },
+
+    #[error("comparison with NaN; guard the operand with isNaN() or default()")]
+    NaNComparison,
+
+    #[error("expression evaluates to a number, not a condition")]
+    NotACondition,
+}
+
+/// The value an expression evaluates to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Bool(_) => "boolean",
+        }
+    }
+
+    fn as_number(self) -> Result<f64, ExpressionError> {
+        match self {
+            Value::Number(n) => Ok(n),
+            other => Err(ExpressionError::TypeMismatch { expected: "number", found: other.type_name() }),
+        }
+    }
+
+    fn as_bool(self) -> Result<bool, ExpressionError> {
+        match self {
+            Value::Bool(b) => Ok(b),
+            other => Err(ExpressionError::TypeMismatch { expected: "boolean", found: other.type_name() }),
+        }
+    }
+}
+
+/// A parsed expression that can be evaluated repeatedly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    root: Expr,
+}
+
+impl Expression {
+    /// Parses an expression, reporting syntax errors without evaluating it.
+    pub fn parse(source: &str) -> Result<Self, ExpressionError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let root = parser.parse_expression(0)?;
+        if let Some((token, position)) = parser.tokens.get(parser.position) {
+            return Err(ExpressionError::UnexpectedToken { token: token.to_string(), position: *position });
+        }
+        Ok(Self { root })
+    }
+
+    /// Evaluates the expression against the given variables.
+    pub fn evaluate(&self, variables: &Variables) -> Result<Value, ExpressionError> {
+        self.root.evaluate(variables)
+    }
+
+    /// Evaluates the expression, requiring it to produce a boolean.
+    pub fn evaluate_condition(&self, variables: &Variables) -> Result<bool, ExpressionError> {
+        match self.evaluate(variables)? {
+            Value::Bool(b) => Ok(b),
+            Value::Number(_) => Err(ExpressionError::NotACondition),
+        }
+    }
+}
+
+/// Parses and evaluates a success condition in one go.
+pub fn evaluate_condition(source: &str, variables: &Variables) -> Result<bool, ExpressionError> {
+    Expression::parse(source)?.evaluate_condition(variables)
+}
+
+// --- Lexer ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Op(op) => write!(f, "{}", op),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+const OPERATORS: [&str; 14] = ["&&", "||", "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "%", "!"];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ExpressionError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())) {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // Optional exponent, e.g. `1e-3`.
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text.parse().map_err(|_| ExpressionError::InvalidNumber(text.clone()))?;
+            tokens.push((Token::Number(number), start));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), start));
+        } else if c == '(' {
+            tokens.push((Token::LParen, start));
+            i += 1;
+        } else if c == ')' {
+            tokens.push((Token::RParen, start));
+            i += 1;
+        } else if c == ',' {
+            tokens.push((Token::Comma, start));
+            i += 1;
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or(ExpressionError::UnexpectedCharacter { ch: c, position: start })?;
+            tokens.push((Token::Op(op), start));
+            i += op.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+// --- Parser ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Bool(bool),
+    Variable(String),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+/// Binding power of binary operators; higher binds tighter.
+fn binding_power(op: &str) -> Option<u8> {
+    match op {
+        "||" => Some(1),
+        "&&" => Some(2),
+        "==" | "!=" => Some(3),
+        "<" | "<=" | ">" | ">=" => Some(4),
+        "+" | "-" => Some(5),
+        "*" | "/" | "%" => Some(6),
+        _ => None,
+    }
+}
+
+const UNARY_BINDING_POWER: u8 = 7;
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<(Token, usize), ExpressionError> {
+        let token = self.tokens.get(self.position).cloned().ok_or(ExpressionError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ExpressionError> {
+        let (token, position) = self.next()?;
+        if token != expected {
+            return Err(ExpressionError::UnexpectedToken { token: token.to_string(), position });
+        }
+        Ok(())
+    }
+
+    fn parse_expression(&mut self, min_power: u8) -> Result<Expr, ExpressionError> {
+        let mut lhs = self.parse_prefix()?;
+
+        while let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            let Some(power) = binding_power(op) else { break };
+            if power <= min_power {
+                break;
+            }
+            self.position += 1;
+            let rhs = self.parse_expression(power)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_prefix(&mut self) -> Result<Expr, ExpressionError> {
+        let (token, position) = self.next()?;
+        match token {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Op("-") => Ok(Expr::Negate(Box::new(self.parse_expression(UNARY_BINDING_POWER)?))),
+            Token::Op("!") => Ok(Expr::Not(Box::new(self.parse_expression(UNARY_BINDING_POWER)?))),
+            Token::LParen => {
+                let inner = self.parse_expression(0)?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            Token::Ident(name) if name == "true" => Ok(Expr::Bool(true)),
+            Token::Ident(name) if name == "false" => Ok(Expr::Bool(false)),
+            Token::Ident(name) if self.peek() == Some(&Token::LParen) => {
+                self.position += 1;
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::RParen) {
+                    loop {
+                        args.push(self.parse_expression(0)?);
+                        if self.peek() == Some(&Token::Comma) {
+                            self.position += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                self.expect(Token::RParen)?;
+                Ok(Expr::Call(name, args))
+            }
+            Token::Ident(name) => Ok(Expr::Variable(name)),
+            other => Err(ExpressionError::UnexpectedToken { token: other.to_string(), position }),
+        }
+    }
+}
+
+// --- Evaluator ---
+
+impl Expr {
+    fn evaluate(&self, variables: &Variables) -> Result<Value, ExpressionError> {
+        match self {
+            Expr::Number(n) => Ok(Value::Number(*n)),
+            Expr::Bool(b) => Ok(Value::Bool(*b)),
+            Expr::Variable(name) => variables
+                .get(name)
+                .map(|v| Value::Number(*v))
+                .ok_or_else(|| ExpressionError::UnknownVariable(name.clone())),
+            Expr::Negate(inner) => Ok(Value::Number(-inner.evaluate(variables)?.as_number()?)),
+            Expr::Not(inner) => Ok(Value::Bool(!inner.evaluate(variables)?.as_bool()?)),
+            Expr::Binary(op, lhs, rhs) => evaluate_binary(op, lhs, rhs, variables),
+            Expr::Call(name, args) => evaluate_call(name, args, variables),
+        }
+    }
+}
+
+fn evaluate_binary(op: &str, lhs: &Expr, rhs: &Expr, variables: &Variables) -> Result<Value, ExpressionError> {
+    // Logical operators short-circuit so guards like `isNaN(result) || result < 1` work.
+    match op {
+        "&&" => {
+            return Ok(Value::Bool(
+                lhs.evaluate(variables)?.as_bool()? && rhs.evaluate(variables)?.as_bool()?,
+            ))
+        }
+        "||" => {
+            return Ok(Value::Bool(
+                lhs.evaluate(variables)?.as_bool()? || rhs.evaluate(variables)?.as_bool()?,
+            ))
+        }
+        _ => {}
+    }
+
+    let left = lhs.evaluate(variables)?;
+    let right = rhs.evaluate(variables)?;
+
+    if let (Value::Bool(a), Value::Bool(b)) = (left, right) {
+        return match op {
+            "==" => Ok(Value::Bool(a == b)),
+            "!=" => Ok(Value::Bool(a != b)),
+            _ => Err(ExpressionError::TypeMismatch { expected: "number", found: "boolean" }),
+        };
+    }
+
+    let (a, b) = (left.as_number()?, right.as_number()?);
+    let is_comparison = matches!(op, "<" | "<=" | ">" | ">=" | "==" | "!=");
+    if is_comparison && (a.is_nan() || b.is_nan()) {
+        return Err(ExpressionError::NaNComparison);
+    }
+
+    Ok(match op {
+        "+" => Value::Number(a + b),
+        "-" => Value::Number(a - b),
+        "*" => Value::Number(a * b),
+        "/" => Value::Number(a / b),
+        "%" => Value::Number(a % b),
+        "<" => Value::Bool(a < b),
+        "<=" => Value::Bool(a <= b),
+        ">" => Value::Bool(a > b),
+        ">=" => Value::Bool(a >= b),
+        "==" => Value::Bool(a == b || (a - b).abs() < f64::EPSILON),
+        "!=" => Value::Bool(a != b && (a - b).abs() >= f64::EPSILON),
+        _ => unreachable!("binding_power only admits known operators"),
+    })
+}
+
+fn evaluate_call(name: &str, args: &[Expr], variables: &Variables) -> Result<Value, ExpressionError> {
+    let expected = match name {
+        "isNaN" | "isInf" | "abs" => 1,
+        "min" | "max" | "default" => 2,
+        _ => return Err(ExpressionError::UnknownFunction(name.to_string())),
+    };
+    if args.len() != expected {
+        return Err(ExpressionError::WrongArity { name: name.to_string(), expected, found: args.len() });
+    }
+
+    if name == "default" {
+        return match args[0].evaluate(variables) {
+            Ok(Value::Number(n)) if n.is_nan() => args[1].evaluate(variables),
+            Err(ExpressionError::UnknownVariable(_)) => args[1].evaluate(variables),
+            other => other,
+        };
+    }
+
+    let x = args[0].evaluate(variables)?.as_number()?;
+    Ok(match name {
+        "isNaN" => Value::Bool(x.is_nan()),
+        "isInf" => Value::Bool(x.is_infinite()),
+        "abs" => Value::Number(x.abs()),
+        "min" => Value::Number(x.min(args[1].evaluate(variables)?.as_number()?)),
+        "max" => Value::Number(x.max(args[1].evaluate(variables)?.as_number()?)),
+        _ => unreachable!("arity check only admits known functions"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, f64)]) -> Variables {
+        pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    fn eval(source: &str) -> bool {
+        evaluate_condition(source, &Variables::new()).unwrap()
+    }
+
+    #[test]
+    fn test_comparison_and_logical_operators() {
+        assert!(eval("0.85 < 0.90"));
+        assert!(!eval("0.95 < 0.90"));
+        assert!(eval("0.95 > 0.90"));
+        assert!(!eval("0.85 > 0.90"));
+        assert!(eval("0.90 <= 0.90"));
+        assert!(eval("0.90 >= 0.90"));
+        assert!(eval("0.90 == 0.90"));
+        assert!(eval("0.85 != 0.90"));
+
+        assert!(eval("0.85 < 0.90 && 0.95 > 0.90"));
+        assert!(eval("0.95 < 0.90 || 0.85 < 0.90"));
+        assert!(!eval("0.95 < 0.90 && 0.85 > 0.90"));
+        assert!(eval("!(1 > 2)"));
+    }
+
+    #[test]
+    fn test_precedence_and_arithmetic() {
+        assert!(eval("1 + 2 * 3 == 7"));
+        assert!(eval("(1 + 2) * 3 == 9"));
+        assert!(eval("-2 * -3 == 6"));
+        assert!(eval("10 % 4 == 2"));
+        assert!(eval("1e-3 < 0.01"));
+        // `&&` binds tighter than `||`.
+        assert!(eval("1 > 2 && 1 > 2 || 1 < 2"));
+        assert!(!eval("1 > 2 && (1 > 2 || 1 < 2)"));
+    }
+
+    #[test]
+    fn test_variables_and_named_results() {
+        let v = vars(&[("result", 0.85), ("result.p99", 250.0), ("result.errors", 0.002)]);
+        assert!(evaluate_condition("result > 0.9 || result < 1.0", &v).unwrap());
+        assert!(evaluate_condition("result.p99 < 300 && result.errors < 0.01", &v).unwrap());
+        assert!(!evaluate_condition("result.p99 / 1000 > 0.3", &v).unwrap());
+        assert_eq!(
+            evaluate_condition("result.p50 < 100", &v),
+            Err(ExpressionError::UnknownVariable("result.p50".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_success_conditions() {
+        let check = |source: &str, result: f64| evaluate_condition(source, &vars(&[("result", result)])).unwrap();
+        assert!(check("result < 0.95", 0.85));
+        assert!(!check("result < 0.95", 0.98));
+        assert!(check("result <= 500.0", 450.0));
+        assert!(!check("result <= 500.0", 600.0));
+        assert!(check("result > 0.9 && result < 1.0", 0.95));
+        assert!(check("result < 0.1 || result > 0.9", 0.05));
+        assert!(check("result < 1", f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_functions() {
+        let v = vars(&[("result", f64::NAN), ("latency", -120.0)]);
+        assert!(evaluate_condition("isNaN(result)", &v).unwrap());
+        assert!(evaluate_condition("isNaN(result) || result < 1", &v).unwrap());
+        assert!(evaluate_condition("default(result, 0) < 1", &v).unwrap());
+        assert!(evaluate_condition("default(result.p99, 5) == 5", &v).unwrap());
+        assert!(evaluate_condition("abs(latency) == 120", &v).unwrap());
+        assert!(evaluate_condition("min(1, 2) == 1 && max(1, 2) == 2", &v).unwrap());
+        assert!(!evaluate_condition("isInf(latency)", &v).unwrap());
+    }
+
+    #[test]
+    fn test_evaluation_errors() {
+        let nan = vars(&[("result", f64::NAN)]);
+        assert_eq!(evaluate_condition("result < 0.05", &nan), Err(ExpressionError::NaNComparison));
+        assert_eq!(evaluate_condition("1 + 2", &nan), Err(ExpressionError::NotACondition));
+        assert_eq!(
+            evaluate_condition("median(result)", &nan),
+            Err(ExpressionError::UnknownFunction("median".to_string()))
+        );
+        assert!(matches!(
+            evaluate_condition("abs(1, 2) > 0", &nan),
+            Err(ExpressionError::WrongArity { expected: 1, found: 2, .. })
+        ));
+        assert!(matches!(
+            evaluate_condition("true + 1 > 0", &nan),
+            Err(ExpressionError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Expression::parse("1 <"), Err(ExpressionError::UnexpectedEnd));
+        assert!(matches!(Expression::parse("(1 < 2"), Err(ExpressionError::UnexpectedEnd)));
+        assert!(matches!(
+            Expression::parse("1 < 2 )"),
+            Err(ExpressionError::UnexpectedToken { position: 6, .. })
+        ));
+        assert!(matches!(
+            Expression::parse("result # 2"),
+            Err(ExpressionError::UnexpectedCharacter { ch: '#', .. })
+        ));
+    }
+}
@@ -4,15 +4,19 @@
 //
 // This file implements the reconciliation logic for the PhgitSyncJob custom resource.
 // It is responsible for orchestrating the synchronization of manifests from a
-// Git repository to a target cluster.
+// Git repository to a target cluster. The outcome of each sync is reported
+// through the standard `Ready`/`Progressing`/`Degraded` conditions and a
+// Kubernetes Event.
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::controllers::conditions::{self, ResourceState};
+use crate::controllers::events;
 use crate::crds::{PhgitSyncJob, PhgitSyncJobStatus, SyncJobPhase, StatusCondition};
 use kube::{
     api::{Api, Patch, PatchParams},
     client::Client,
-    runtime::controller::Action,
+    runtime::{controller::Action, events::Recorder},
     Resource, ResourceExt,
 };
 use std::sync::Arc;
@@ -28,6 +32,8 @@ pub enum Error {
 
 pub struct Context {
     pub client: Client,
+    /// Publishes Kubernetes Events about sync jobs.
+    pub recorder: Recorder,
 }
 
 use k8s_openapi::api::apps::v1::Deployment;
@@ -42,13 +48,16 @@ pub async fn reconcile(job: Arc<PhgitSyncJob>, ctx: Arc<Context>) -> Result<Acti
     let job_name = job.name_any();
 
     // Set status to Syncing
-    let new_status = PhgitSyncJobStatus {
+    let mut new_status = PhgitSyncJobStatus {
         phase: Some(SyncJobPhase::Syncing),
         start_time: Some(chrono::Utc::now().to_rfc3339()),
+        conditions: job.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default(),
         ..Default::default()
     };
+    set_state(&job, &mut new_status.conditions, ResourceState::Progressing, "Syncing", "Applying manifests");
     let patch = Patch::Apply(json!({ "status": new_status }));
     api.patch_status(&job_name, &PatchParams::apply("ph-gitsync-controller"), &patch).await?;
+    let conditions = new_status.conditions;
 
     tracing::info!(job = %job_name, "Syncing manifests from path: {}", job.spec.path);
 
@@ -57,7 +66,8 @@ pub async fn reconcile(job: Arc<PhgitSyncJob>, ctx: Arc<Context>) -> Result<Acti
         Ok(m) => m,
         Err(e) => {
             let error_message = format!("Failed to read manifests from path {}: {}", job.spec.path, e);
-            update_status_with_error(&api, &job_name, &error_message).await?;
+            events::warning(&ctx.recorder, &*job, "SyncFailed", "Sync", error_message.clone()).await;
+            update_status_with_error(&api, &job, conditions, &error_message).await?;
             return Ok(Action::await_change());
         }
     };
@@ -66,11 +76,13 @@ pub async fn reconcile(job: Arc<PhgitSyncJob>, ctx: Arc<Context>) -> Result<Acti
     match execute_apply(ctx.client.clone(), &manifests, &BTreeMap::new()).await {
         Ok(applied_resources) => {
             let success_message = format!("Successfully applied {} resources.", applied_resources.len());
-            update_status_with_success(&api, &job_name, &success_message).await?;
+            events::normal(&ctx.recorder, &*job, "Synced", "Sync", success_message.clone()).await;
+            update_status_with_success(&api, &job, conditions, &success_message).await?;
         }
         Err(e) => {
             let error_message = format!("Failed to apply manifests: {}", e);
-            update_status_with_error(&api, &job_name, &error_message).await?;
+            events::warning(&ctx.recorder, &*job, "SyncFailed", "Sync", error_message.clone()).await;
+            update_status_with_error(&api, &job, conditions, &error_message).await?;
         }
     }
 
@@ -122,33 +134,43 @@ async fn execute_apply(
     Ok(applied_resources)
 }
 
-async fn update_status_with_error(api: &Api<PhgitSyncJob>, name: &str, error_message: &str) -> Result<(), Error> {
+fn set_state(job: &PhgitSyncJob, conditions: &mut Vec<StatusCondition>, state: ResourceState, reason: &str, message: &str) {
+    conditions::set_state(conditions, state, reason, message, job.metadata.generation, chrono::Utc::now());
+}
+
+async fn update_status_with_error(
+    api: &Api<PhgitSyncJob>,
+    job: &PhgitSyncJob,
+    mut conditions: Vec<StatusCondition>,
+    error_message: &str,
+) -> Result<(), Error> {
+    set_state(job, &mut conditions, ResourceState::Degraded, "SyncFailed", error_message);
     let new_status = PhgitSyncJobStatus {
         phase: Some(SyncJobPhase::Failed),
         completion_time: Some(chrono::Utc::now().to_rfc3339()),
-        conditions: vec![StatusCondition {
-            type_: "Failed".to_string(),
-            message: error_message.to_string(),
-        }],
+        conditions,
         ..Default::default()
     };
     let patch = Patch::Apply(json!({ "status": new_status }));
-    api.patch_status(name, &PatchParams::apply("ph-gitsync-controller"), &patch).await?;
+    api.patch_status(&job.name_any(), &PatchParams::apply("ph-gitsync-controller"), &patch).await?;
     Ok(())
 }
 
-async fn update_status_with_success(api: &Api<PhgitSyncJob>, name: &str, success_message: &str) -> Result<(), Error> {
+async fn update_status_with_success(
+    api: &Api<PhgitSyncJob>,
+    job: &PhgitSyncJob,
+    mut conditions: Vec<StatusCondition>,
+    success_message: &str,
+) -> Result<(), Error> {
+    set_state(job, &mut conditions, ResourceState::Ready, "Synced", success_message);
     let new_status = PhgitSyncJobStatus {
         phase: Some(SyncJobPhase::Succeeded),
         completion_time: Some(chrono::Utc::now().to_rfc3339()),
-        conditions: vec![StatusCondition {
-            type_: "Succeeded".to_string(),
-            message: success_message.to_string(),
-        }],
+        conditions,
         ..Default::default()
     };
     let patch = Patch::Apply(json!({ "status": new_status }));
-    api.patch_status(name, &PatchParams::apply("ph-gitsync-controller"), &patch).await?;
+    api.patch_status(&job.name_any(), &PatchParams::apply("ph-gitsync-controller"), &patch).await?;
     Ok(())
 }
 
@@ -0,0 +1,439 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/metric_providers.rs
+*
+* This file contains the metric providers used by the `metrics_analyzer` to
+* measure a `Metric` from a `phRelease` analysis. Each provider turns a metric
+* definition into a numerical `result`, plus optional named results
+* (`result.<name>`), that the success condition is evaluated against.
+*
+* Providers:
+* - `prometheus`: Runs a PromQL query (handled by `PrometheusClient`).
+* - `web`: Fetches a JSON document over HTTP and reads a value with a JSONPath.
+* - `job`: Runs a Kubernetes Job to completion. The result is `1` if the Job
+*   succeeded and `0` if it failed.
+*
+* A Job cannot be awaited inside a reconcile, so the `job` provider creates the
+* Job on the first measurement and reports `Measurement::Pending` until it has
+* finished. The finished Job is deleted once its outcome has been read, so the
+* next analysis interval starts a fresh run.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::crds::{JobMetricProvider, Metric, WebMetricProvider};
+use crate::expression::Variables;
+use anyhow::{anyhow, Context, Result};
+use k8s_openapi::api::batch::v1::Job;
+use kube::{
+    api::{Api, DeleteParams, PostParams},
+    Client,
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const DEFAULT_WEB_TIMEOUT_SECS: u64 = 10;
+const JOB_SUCCESS_CONDITION: &str = "result == 1";
+static NO_NAMED_QUERIES: BTreeMap<String, String> = BTreeMap::new();
+
+/// The provider a metric resolves to, borrowed from its spec.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedProvider<'a> {
+    Prometheus {
+        address: Option<&'a str>,
+        query: &'a str,
+        queries: &'a BTreeMap<String, String>,
+    },
+    Web(&'a WebMetricProvider),
+    Job(&'a JobMetricProvider),
+}
+
+/// The values produced by one measurement: the primary `result` plus any named results.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MetricResults {
+    pub result: f64,
+    pub named: BTreeMap<String, f64>,
+}
+
+impl MetricResults {
+    pub fn single(result: f64) -> Self {
+        Self { result, named: BTreeMap::new() }
+    }
+
+    /// Binds the results as expression variables: `result` and `result.<name>`.
+    pub fn variables(&self) -> Variables {
+        let mut variables: Variables = self
+            .named
+            .iter()
+            .map(|(name, value)| (format!("result.{}", name), *value))
+            .collect();
+        variables.insert("result".to_string(), self.result);
+        variables
+    }
+}
+
+/// The outcome of a single measurement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Measurement {
+    /// The provider produced its results.
+    Value(MetricResults),
+    /// The provider has started a measurement that has not finished yet.
+    Pending,
+}
+
+/// Identifies the release a measurement is taken for.
+pub struct MetricTarget {
+    pub namespace: String,
+    pub release_name: String,
+    pub release_uid: Option<String>,
+}
+
+/// Resolves which provider a metric uses, falling back to Prometheus with `metric.query`.
+pub fn resolve_provider(metric: &Metric) -> Result<ResolvedProvider<'_>> {
+    let Some(provider) = &metric.provider else {
+        if metric.query.trim().is_empty() {
+            return Err(anyhow!("Metric '{}' has neither a provider nor a query", metric.name));
+        }
+        return Ok(ResolvedProvider::Prometheus {
+            address: None,
+            query: &metric.query,
+            queries: &NO_NAMED_QUERIES,
+        });
+    };
+
+    match (&provider.prometheus, &provider.web, &provider.job) {
+        (Some(prometheus), None, None) => Ok(ResolvedProvider::Prometheus {
+            address: prometheus.address.as_deref(),
+            query: &prometheus.query,
+            queries: &prometheus.queries,
+        }),
+        (None, Some(web), None) => Ok(ResolvedProvider::Web(web)),
+        (None, None, Some(job)) => Ok(ResolvedProvider::Job(job)),
+        _ => Err(anyhow!(
+            "Metric '{}' must set exactly one of 'prometheus', 'web' or 'job' in its provider",
+            metric.name
+        )),
+    }
+}
+
+/// Returns the success condition for a metric, applying the provider's default when unset.
+pub fn success_condition<'a>(metric: &'a Metric, provider: &ResolvedProvider<'_>) -> Result<&'a str> {
+    match (metric.on_success.trim(), provider) {
+        ("", ResolvedProvider::Job(_)) => Ok(JOB_SUCCESS_CONDITION),
+        ("", _) => Err(anyhow!("Metric '{}' has no 'onSuccess' condition", metric.name)),
+        (condition, _) => Ok(condition),
+    }
+}
+
+/// Measures metrics exposed as JSON over HTTP.
+pub struct WebProvider {
+    client: reqwest::Client,
+}
+
+impl Default for WebProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebProvider {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Fetches `spec.url` and extracts the value at `spec.json_path`, plus any named `json_paths`.
+    pub async fn measure(&self, spec: &WebMetricProvider) -> Result<MetricResults> {
+        let timeout = Duration::from_secs(spec.timeout_seconds.unwrap_or(DEFAULT_WEB_TIMEOUT_SECS));
+        let mut request = self
+            .client
+            .get(&spec.url)
+            .timeout(timeout)
+            .header("Accept", "application/json");
+        for (name, value) in &spec.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to send request to '{}'", spec.url))?;
+        let status_code = response.status();
+        if !status_code.is_success() {
+            return Err(anyhow!("Request to '{}' failed with status {}", spec.url, status_code));
+        }
+
+        let body: Value = response
+            .json()
+            .await
+            .with_context(|| format!("Response from '{}' is not valid JSON", spec.url))?;
+        let mut results = MetricResults::single(select_number(&body, &spec.json_path)?);
+        for (name, path) in &spec.json_paths {
+            results.named.insert(name.clone(), select_number(&body, path)?);
+        }
+        Ok(results)
+    }
+}
+
+/// Measures metrics by running a Kubernetes Job and reading its outcome.
+pub struct JobProvider {
+    client: Client,
+}
+
+impl JobProvider {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Starts the analysis Job if needed and reports its outcome once it has finished.
+    pub async fn measure(
+        &self,
+        metric_name: &str,
+        spec: &JobMetricProvider,
+        target: &MetricTarget,
+    ) -> Result<Measurement> {
+        let jobs: Api<Job> = Api::namespaced(self.client.clone(), &target.namespace);
+        let job_name = analysis_job_name(&target.release_name, metric_name);
+
+        let Some(job) = jobs.get_opt(&job_name).await? else {
+            log::debug!("Starting analysis Job '{}' for metric '{}'", job_name, metric_name);
+            let job = build_analysis_job(&job_name, metric_name, spec, target)?;
+            jobs.create(&PostParams::default(), &job)
+                .await
+                .with_context(|| format!("Failed to create analysis Job '{}'", job_name))?;
+            return Ok(Measurement::Pending);
+        };
+
+        let status = job.status.unwrap_or_default();
+        let outcome = if status.succeeded.unwrap_or(0) > 0 {
+            1.0
+        } else if status.failed.unwrap_or(0) > 0 {
+            0.0
+        } else {
+            return Ok(Measurement::Pending);
+        };
+
+        // Remove the finished Job so the next interval measures again.
+        jobs.delete(&job_name, &DeleteParams::background())
+            .await
+            .with_context(|| format!("Failed to delete analysis Job '{}'", job_name))?;
+        Ok(Measurement::Value(MetricResults::single(outcome)))
+    }
+}
+
+/// Builds a deterministic, DNS-1123 compliant name for a metric's analysis Job.
+fn analysis_job_name(release_name: &str, metric_name: &str) -> String {
+    let raw = format!("{}-analysis-{}", release_name, metric_name).to_lowercase();
+    let sanitized: String = raw
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .take(63)
+        .collect();
+    sanitized.trim_matches('-').to_string()
+}
+
+fn build_analysis_job(
+    job_name: &str,
+    metric_name: &str,
+    spec: &JobMetricProvider,
+    target: &MetricTarget,
+) -> Result<Job> {
+    let owner_references = match &target.release_uid {
+        Some(uid) => json!([{
+            "apiVersion": "ph.io/v1alpha1",
+            "kind": "phRelease",
+            "name": target.release_name,
+            "uid": uid,
+            "controller": true,
+        }]),
+        None => json!([]),
+    };
+
+    let job_json = json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": {
+            "name": job_name,
+            "labels": {
+                "ph.io/release": target.release_name,
+            },
+            "annotations": {
+                "ph.io/analysis-metric": metric_name,
+            },
+            "ownerReferences": owner_references,
+        },
+        "spec": {
+            "template": {
+                "spec": {
+                    "containers": [{
+                        "name": "analysis",
+                        "image": spec.image,
+                        "command": spec.command,
+                        "args": spec.args,
+                    }],
+                    "restartPolicy": "Never"
+                }
+            },
+            "backoffLimit": 0 // A single failed run is a failed measurement.
+        }
+    });
+
+    serde_json::from_value(job_json).context("Failed to build analysis Job")
+}
+
+/// Selects a value from a JSON document with a JSONPath expression.
+///
+/// Supports the subset needed to address a single value: the root `$`,
+/// dotted keys (`.data.rate`), bracketed keys (`['error-rate']`) and array
+/// indices (`[0]`).
+pub fn select_json_path<'a>(document: &'a Value, path: &str) -> Result<&'a Value> {
+    let path = path.trim();
+    let mut rest = path
+        .strip_prefix('$')
+        .ok_or_else(|| anyhow!("JSONPath '{}' must start with '$'", path))?;
+    let mut current = document;
+
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            let key = &after_dot[..end];
+            if key.is_empty() {
+                return Err(anyhow!("JSONPath '{}' has an empty key", path));
+            }
+            current = current
+                .get(key)
+                .ok_or_else(|| anyhow!("JSONPath '{}': key '{}' not found", path, key))?;
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket
+                .find(']')
+                .ok_or_else(|| anyhow!("JSONPath '{}' has an unclosed '['", path))?;
+            let selector = after_bracket[..end].trim();
+            current = if let Some(key) = selector
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| selector.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+            {
+                current
+                    .get(key)
+                    .ok_or_else(|| anyhow!("JSONPath '{}': key '{}' not found", path, key))?
+            } else {
+                let index: usize = selector
+                    .parse()
+                    .with_context(|| format!("JSONPath '{}': invalid index '{}'", path, selector))?;
+                current
+                    .get(index)
+                    .ok_or_else(|| anyhow!("JSONPath '{}': index {} out of bounds", path, index))?
+            };
+            rest = &after_bracket[end + 1..];
+        } else {
+            return Err(anyhow!("JSONPath '{}' is malformed near '{}'", path, rest));
+        }
+    }
+
+    Ok(current)
+}
+
+/// Selects the value at `path` and interprets it as a number.
+fn select_number(document: &Value, path: &str) -> Result<f64> {
+    let value = select_json_path(document, path)?;
+    json_value_as_f64(value).ok_or_else(|| anyhow!("Value at '{}' is not numeric: {}", path, value))
+}
+
+/// Interprets a JSON value as a number. Booleans map to `1`/`0` and numeric strings are parsed.
+fn json_value_as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crds::MetricProvider;
+
+    fn metric(query: &str, on_success: &str, provider: Option<MetricProvider>) -> Metric {
+        Metric {
+            name: "smoke".to_string(),
+            query: query.to_string(),
+            on_success: on_success.to_string(),
+            provider,
+            predictive_analysis: None,
+            comparison: None,
+        }
+    }
+
+    #[test]
+    fn test_select_json_path() {
+        let document = json!({
+            "data": { "errorRate": 0.02, "error-count": "3" },
+            "checks": [{ "latency": 120 }, { "healthy": true }]
+        });
+
+        assert_eq!(select_json_path(&document, "$.data.errorRate").unwrap(), &json!(0.02));
+        assert_eq!(select_json_path(&document, "$['data']['error-count']").unwrap(), &json!("3"));
+        assert_eq!(select_json_path(&document, "$.checks[0].latency").unwrap(), &json!(120));
+        assert_eq!(select_json_path(&document, "$.checks[1].healthy").unwrap(), &json!(true));
+        assert!(select_json_path(&document, "$.checks[5]").is_err());
+        assert!(select_json_path(&document, "$.missing").is_err());
+        assert!(select_json_path(&document, "data.errorRate").is_err());
+
+        assert_eq!(json_value_as_f64(&json!("3")), Some(3.0));
+        assert_eq!(json_value_as_f64(&json!(true)), Some(1.0));
+        assert_eq!(json_value_as_f64(&json!({})), None);
+    }
+
+    #[test]
+    fn test_resolve_provider() {
+        let legacy = metric("up", "result > 0", None);
+        assert_eq!(
+            resolve_provider(&legacy).unwrap(),
+            ResolvedProvider::Prometheus { address: None, query: "up", queries: &BTreeMap::new() }
+        );
+        assert!(resolve_provider(&metric("", "result > 0", None)).is_err());
+
+        let job = metric(
+            "",
+            "",
+            Some(MetricProvider {
+                job: Some(JobMetricProvider { image: "smoke:latest".to_string(), ..Default::default() }),
+                ..Default::default()
+            }),
+        );
+        let provider = resolve_provider(&job).unwrap();
+        assert!(matches!(provider, ResolvedProvider::Job(_)));
+        assert_eq!(success_condition(&job, &provider).unwrap(), JOB_SUCCESS_CONDITION);
+
+        let ambiguous = metric(
+            "",
+            "result < 1",
+            Some(MetricProvider {
+                web: Some(WebMetricProvider::default()),
+                job: Some(JobMetricProvider::default()),
+                ..Default::default()
+            }),
+        );
+        assert!(resolve_provider(&ambiguous).is_err());
+    }
+
+    #[test]
+    fn test_metric_results_variables() {
+        let mut results = MetricResults::single(0.5);
+        results.named.insert("p99".to_string(), 280.0);
+        let variables = results.variables();
+        assert_eq!(variables.get("result"), Some(&0.5));
+        assert_eq!(variables.get("result.p99"), Some(&280.0));
+    }
+
+    #[test]
+    fn test_analysis_job_name() {
+        assert_eq!(analysis_job_name("checkout", "error_rate"), "checkout-analysis-error-rate");
+        assert!(analysis_job_name(&"a".repeat(80), "smoke").len() <= 63);
+    }
+}
@@ -10,11 +10,8 @@
 //   mocked/empty implementation.
 // - The implementation correctly parses the Prometheus JSON response, extracts the metric
 //   value, and handles special float values like "NaN", "+Inf", and "-Inf".
-// - Implemented `evaluate_simple_expression` to parse and evaluate success conditions.
-//   The parser supports comparison operators (<, <=, >, >=, ==, !=) and logical
-//   operators (&&, ||).
-// - Floating-point equality checks (`==`, `!=`) are handled safely using `f64::EPSILON`
-//   to prevent precision issues.
+// - Success conditions are evaluated with the shared expression language in
+//   `expression.rs`, which is also used by the DR controller's health checks.
 // - All in-code comments within the changed blocks have been translated to English for
 //   consistency and clarity.
 
@@ -31,15 +28,27 @@
 // - The `PrometheusClient` struct encapsulates the necessary details for
 //   communicating with a Prometheus instance, including its endpoint and an
 //   HTTP client.
-// - The primary public function, `analyze`, takes a `Metric` definition from a
-//   `phRelease` custom resource. It is responsible for executing the metric's
-//   query and evaluating the result against the specified success condition.
+// - The `MetricsAnalyzer` struct owns one client per metric provider
+//   (Prometheus, HTTP/JSON and Kubernetes Job, see `metric_providers`). Its
+//   `analyze` function takes a `Metric` definition from a `phRelease` custom
+//   resource, measures it with the provider the metric selects and evaluates the
+//   result against the specified success condition.
+// - Metrics with a `comparison` are not evaluated individually. `judge` samples
+//   them from the stable and canary pods and scores them with the
+//   baseline-vs-canary judge in `canary_judge.rs`.
 // - The `AnalysisResult` enum provides a clear, strongly-typed outcome for each
 //   metric evaluation, which the calling controller can use to make decisions
 //   (e.g., promote, rollback, or continue waiting).
 //
-use crate::crds::Metric;
+use crate::canary_judge::{classify_score, judge_metric, overall_score};
+use crate::crds::{BaselineComparison, Metric, MetricScore, ScoreThresholds};
+use crate::expression::{Expression, ExpressionError};
+use crate::metric_providers::{
+    resolve_provider, success_condition, JobProvider, Measurement, MetricResults, MetricTarget,
+    ResolvedProvider, WebProvider,
+};
 use anyhow::{anyhow, Context, Result};
+use kube::Client;
 use serde_json::Value;
 use std::collections::HashMap;
 
@@ -64,8 +73,28 @@ pub struct HistoricalValue {
     pub value: f64,
 }
 
+const DEFAULT_COMPARISON_LOOKBACK: &str = "10m";
+const DEFAULT_COMPARISON_STEP: &str = "30s";
+
+/// The judge's verdict on the metrics compared against the baseline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JudgeReport {
+    pub result: AnalysisResult,
+    /// The overall 0-100 score, or `None` if no compared metric had data.
+    pub score: Option<f64>,
+    pub metric_scores: Vec<MetricScore>,
+}
+
+/// Measures and evaluates release metrics with whichever provider each metric selects.
+pub struct MetricsAnalyzer {
+    prometheus: PrometheusClient,
+    web: WebProvider,
+    job: JobProvider,
+}
+
 /// A client for interacting with a Prometheus API endpoint.
 /// It is responsible for executing PromQL queries and returning the results.
+#[derive(Clone)]
 pub struct PrometheusClient {
     /// An asynchronous HTTP client for making requests to the Prometheus API.
     client: reqwest::Client,
@@ -100,60 +129,65 @@ struct PrometheusResult {
     values: Option<Vec<(f64, String)>>,
 }
 
-impl PrometheusClient {
-    /// Constructs a new `PrometheusClient`.
+impl MetricsAnalyzer {
+    /// Constructs a new `MetricsAnalyzer`.
     ///
     /// # Arguments
     ///
-    /// * `endpoint` - The base URL of the Prometheus API (e.g., "http://localhost:9090").
-    pub fn new(endpoint: &str) -> Self {
+    /// * `client` - The Kubernetes client used by the Job provider.
+    /// * `prometheus_endpoint` - The default Prometheus API base URL.
+    pub fn new(client: Client, prometheus_endpoint: &str) -> Self {
         Self {
-            client: reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(30))
-                .build()
-                .expect("Failed to create HTTP client"),
-            endpoint: endpoint.trim_end_matches('/').to_string(),
+            prometheus: PrometheusClient::new(prometheus_endpoint),
+            web: WebProvider::new(),
+            job: JobProvider::new(client),
         }
     }
 
-    /// Analyzes a given metric by querying Prometheus and evaluating the result.
+    /// Analyzes a given metric by measuring it and evaluating the result.
     ///
-    /// This is the main entry point for the analyzer. It orchestrates the query
-    /// execution and the evaluation of the success condition.
+    /// This is the main entry point for the analyzer. It orchestrates the
+    /// measurement with the metric's provider and the evaluation of the
+    /// success condition.
     ///
     /// # Arguments
     ///
     /// * `metric` - A reference to the `Metric` struct from the `phRelease` CRD.
+    /// * `target` - The release the metric is measured for.
     /// * `history` - A slice of historical values for this metric.
+    ///
+    /// Returns the result and the measured value. Inconclusive results have
+    /// no value, so that they are not mistaken for measurements.
     pub async fn analyze(
         &self,
         metric: &Metric,
+        target: &MetricTarget,
         history: &[HistoricalValue],
-    ) -> Result<(AnalysisResult, f64)> {
+    ) -> Result<(AnalysisResult, Option<f64>)> {
+        let provider = resolve_provider(metric)?;
+        let condition = success_condition(metric, &provider)?;
         log::debug!("Analyzing metric: {}", metric.name);
-        log::debug!("  - Executing Query: {}", metric.query);
-        log::debug!("  - Success Condition: {}", metric.on_success);
-
-        // Execute the PromQL query against Prometheus
-        let metric_value = match self.execute_prometheus_query(&metric.query).await {
-            Ok(value) => {
-                log::debug!("  - Query Result: {}", value);
-                value
+        log::debug!("  - Provider: {:?}", provider);
+        log::debug!("  - Success Condition: {}", condition);
+
+        let results = match self.measure(metric, &provider, target).await {
+            Ok(Measurement::Value(results)) => {
+                log::debug!("  - Measured Results: {:?}", results);
+                results
+            }
+            Ok(Measurement::Pending) => {
+                log::debug!("  - Measurement for metric '{}' is still running", metric.name);
+                return Ok((AnalysisResult::Inconclusive, None));
             }
             Err(e) => {
-                log::warn!(
-                    "Failed to execute Prometheus query for metric '{}': {}",
-                    metric.name,
-                    e
-                );
-                return Ok((AnalysisResult::Inconclusive, 0.0));
+                log::warn!("Failed to measure metric '{}': {}", metric.name, e);
+                return Ok((AnalysisResult::Inconclusive, None));
             }
         };
 
-        // Evaluate the success condition with the retrieved metric value
-        let success_result = self
-            .evaluate_success_condition(&metric.on_success, metric_value)
-            .await;
+        // Evaluate the success condition with the retrieved metric results
+        let metric_value = results.result;
+        let success_result = evaluate_success(condition, &results);
 
         match success_result {
             Ok(true) => {
@@ -161,14 +195,14 @@ impl PrometheusClient {
                 if let Some(pa) = &metric.predictive_analysis {
                     if pa.enabled {
                         let trend_threshold = pa.trend_threshold.unwrap_or(0.1); // Default slope threshold
-                        if let Some(slope) = self.analyze_trend(history) {
+                        if let Some(slope) = self.prometheus.analyze_trend(history) {
                             if slope > trend_threshold {
                                 log::warn!(
                                     "~ Metric '{}' is trending worse (slope: {:.4})",
                                     metric.name,
                                     slope
                                 );
-                                return Ok((AnalysisResult::TrendingWorse, metric_value));
+                                return Ok((AnalysisResult::TrendingWorse, Some(metric_value)));
                             }
                         }
                     }
@@ -178,18 +212,18 @@ impl PrometheusClient {
                     "✓ Metric '{}' passed: {} (condition: {})",
                     metric.name,
                     metric_value,
-                    metric.on_success
+                    condition
                 );
-                Ok((AnalysisResult::Success, metric_value))
+                Ok((AnalysisResult::Success, Some(metric_value)))
             }
             Ok(false) => {
                 log::warn!(
                     "✗ Metric '{}' failed: {} (condition: {})",
                     metric.name,
                     metric_value,
-                    metric.on_success
+                    condition
                 );
-                Ok((AnalysisResult::Failure, metric_value))
+                Ok((AnalysisResult::Failure, Some(metric_value)))
             }
             Err(e) => {
                 log::error!(
@@ -197,8 +231,112 @@ impl PrometheusClient {
                     metric.name,
                     e
                 );
-                Ok((AnalysisResult::Inconclusive, metric_value))
+                Ok((AnalysisResult::Inconclusive, None))
+            }
+        }
+    }
+
+    /// Judges the metrics that declare a `comparison` against the stable baseline.
+    ///
+    /// Each metric is sampled from both tracks over its lookback window and
+    /// scored individually; the weighted overall score is then mapped onto an
+    /// `AnalysisResult` with the given thresholds.
+    pub async fn judge(&self, metrics: &[Metric], thresholds: &ScoreThresholds) -> JudgeReport {
+        let mut weighted_scores = Vec::new();
+
+        for metric in metrics {
+            let Some(comparison) = &metric.comparison else { continue };
+            let client = match metric.provider.as_ref().and_then(|p| p.prometheus.as_ref()).and_then(|p| p.address.as_deref()) {
+                Some(address) => PrometheusClient::new(address),
+                None => self.prometheus.clone(),
+            };
+
+            let (baseline_query, canary_query) = (comparison_query(comparison, "stable"), comparison_query(comparison, "canary"));
+            let (baseline, canary) = tokio::join!(
+                client.execute_prometheus_samples(&baseline_query),
+                client.execute_prometheus_samples(&canary_query),
+            );
+            let (baseline, canary) = match (baseline, canary) {
+                (Ok(baseline), Ok(canary)) => (baseline, canary),
+                (Err(e), _) | (_, Err(e)) => {
+                    log::warn!("Failed to sample metric '{}' for comparison: {}", metric.name, e);
+                    (Vec::new(), Vec::new())
+                }
+            };
+
+            let score = judge_metric(&metric.name, comparison, &baseline, &canary);
+            log::info!(
+                "Judged metric '{}': {:?} (p-value: {:?}, relative difference: {:?})",
+                metric.name,
+                score.classification,
+                score.p_value,
+                score.relative_difference
+            );
+            weighted_scores.push((score, comparison.weight.unwrap_or(1.0)));
+        }
+
+        let score = overall_score(&weighted_scores);
+        JudgeReport {
+            result: classify_score(score, thresholds),
+            score,
+            metric_scores: weighted_scores.into_iter().map(|(score, _)| score).collect(),
+        }
+    }
+
+    /// Takes a single measurement of the metric with its resolved provider.
+    async fn measure(
+        &self,
+        metric: &Metric,
+        provider: &ResolvedProvider<'_>,
+        target: &MetricTarget,
+    ) -> Result<Measurement> {
+        match provider {
+            ResolvedProvider::Prometheus { address, query, queries } => {
+                let dedicated_client = address.map(PrometheusClient::new);
+                let client = dedicated_client.as_ref().unwrap_or(&self.prometheus);
+                let mut results = MetricResults::single(client.execute_prometheus_query(query).await?);
+                for (name, named_query) in queries.iter() {
+                    let value = client.execute_prometheus_query(named_query).await?;
+                    results.named.insert(name.clone(), value);
+                }
+                Ok(Measurement::Value(results))
             }
+            ResolvedProvider::Web(spec) => self.web.measure(spec).await.map(Measurement::Value),
+            ResolvedProvider::Job(spec) => self.job.measure(&metric.name, spec, target).await,
+        }
+    }
+}
+
+/// Evaluates a metric's success condition against the results of one
+/// measurement, bound as `result` and `result.<name>`.
+fn evaluate_success(condition: &str, results: &MetricResults) -> Result<bool, ExpressionError> {
+    Expression::parse(condition).and_then(|expression| expression.evaluate_condition(&results.variables()))
+}
+
+/// Builds the PromQL subquery that samples a compared metric for one track
+/// (`stable` or `canary`) over its lookback window.
+fn comparison_query(comparison: &BaselineComparison, track: &str) -> String {
+    format!(
+        "({})[{}:{}]",
+        comparison.query.replace("{{track}}", track),
+        comparison.lookback.as_deref().unwrap_or(DEFAULT_COMPARISON_LOOKBACK),
+        comparison.step.as_deref().unwrap_or(DEFAULT_COMPARISON_STEP)
+    )
+}
+
+impl PrometheusClient {
+    /// Constructs a new `PrometheusClient`.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The base URL of the Prometheus API (e.g., "http://localhost:9090").
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(30))
+                .build()
+                .expect("Failed to create HTTP client"),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
         }
     }
 
@@ -212,7 +350,7 @@ impl PrometheusClient {
     ///
     /// The numerical value from the first result of the query, or an error if the query fails
     /// or returns no data.
-    async fn execute_prometheus_query(&self, query: &str) -> Result<f64> {
+    pub async fn execute_prometheus_query(&self, query: &str) -> Result<f64> {
         /* BEGIN CHANGE: Implement Prometheus query execution. */
         // This section implements the logic to connect to Prometheus, execute the query,
         // and extract the numerical value from the response, as requested.
@@ -284,9 +422,10 @@ impl PrometheusClient {
             }
         };
 
-        // Handle special Prometheus float string values
+        // Handle special Prometheus float string values. NaN is passed through so
+        // that success conditions can handle it with `isNaN` or `default`.
         let parsed_value = match value_str.as_str() {
-            "NaN" => return Err(anyhow!("Prometheus returned NaN - metric may not have valid data")),
+            "NaN" => f64::NAN,
             "+Inf" => f64::INFINITY,
             "-Inf" => f64::NEG_INFINITY,
             _ => value_str
@@ -299,115 +438,40 @@ impl PrometheusClient {
         /* END CHANGE */
     }
 
-    /// Evaluates a success condition expression with the given metric value.
-    ///
-    /// This function parses and evaluates expressions like "result < 0.95" where
-    /// "result" is replaced with the actual metric value.
-    ///
-    /// # Arguments
-    ///
-    /// * `condition` - The success condition expression (e.g., "result < 0.95")
-    /// * `metric_value` - The numerical value obtained from Prometheus
-    ///
-    /// # Returns
-    ///
-    /// A boolean indicating whether the condition is satisfied.
-    async fn evaluate_success_condition(&self, condition: &str, metric_value: f64) -> Result<bool> {
-        // Handle infinite values before attempting evaluation
-        if metric_value.is_infinite() {
-            return Err(anyhow!("Cannot evaluate condition with infinite metric value"));
-        }
-
-        let expression = condition.replace("result", &metric_value.to_string());
-
-        log::debug!(
-            "  - Evaluating: {} (substituted: {})",
-            condition,
-            expression
-        );
-
-        // Simple expression evaluator for basic comparisons
-        self.evaluate_simple_expression(&expression)
-    }
-
-    /// Evaluates simple comparison expressions.
-    ///
-    /// Supports the following operators: <, <=, >, >=, ==, !=
-    /// Also supports logical operators: &&, ||
-    ///
-    /// # Arguments
-    ///
-    /// * `expression` - The mathematical expression to evaluate (e.g., "0.95 < 0.90")
-    ///
-    /// # Returns
+    /// Executes a PromQL query and returns every finite sample of every series.
     ///
-    /// A boolean result of the expression evaluation.
-    fn evaluate_simple_expression(&self, expression: &str) -> Result<bool> {
-        /* BEGIN CHANGE: Implement success condition expression evaluator. */
-        // This section implements the parser and evaluator for the success condition.
-        // - The function assumes the "result" keyword has already been substituted.
-        // - The parser handles logical (`&&`, `||`) and comparison (`<`, `<=`, `>`, `>=`, `==`, `!=`) operators.
-        // - The implementation is recursive for logical operators, allowing for
-        //   compound conditions like "result > 0.9 && result < 1.0".
-        // - Floating-point comparison for `==` and `!=` is handled safely
-        //   using `f64::EPSILON` to avoid precision issues.
-        let expression = expression.trim();
-
-        // Handle logical operators first (recursively)
-        if let Some(pos) = expression.find("&&") {
-            let left_expr = expression[..pos].trim();
-            let right_expr = expression[pos + 2..].trim();
-            let left_result = self.evaluate_simple_expression(left_expr)?;
-            let right_result = self.evaluate_simple_expression(right_expr)?;
-            return Ok(left_result && right_result);
-        }
+    /// Used with range subqueries to collect the samples the canary judge compares,
+    /// treating each pod's series as additional observations.
+    pub async fn execute_prometheus_samples(&self, query: &str) -> Result<Vec<f64>> {
+        let url = format!("{}/api/v1/query", self.endpoint);
+        let response = self
+            .client
+            .get(&url)
+            .query(&[("query", query)])
+            .header("Accept", "application/json")
+            .send()
+            .await
+            .context("Failed to send request to Prometheus")?;
 
-        if let Some(pos) = expression.find("||") {
-            let left_expr = expression[..pos].trim();
-            let right_expr = expression[pos + 2..].trim();
-            let left_result = self.evaluate_simple_expression(left_expr)?;
-            let right_result = self.evaluate_simple_expression(right_expr)?;
-            return Ok(left_result || right_result);
+        let status_code = response.status();
+        if !status_code.is_success() {
+            let error_body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Prometheus query failed with status {}: {}", status_code, error_body));
         }
 
-        // Handle comparison operators. Check for 2-char operators first.
-        let operators = ["<=", ">=", "==", "!=", "<", ">"];
-
-        for op in &operators {
-            if let Some(pos) = expression.find(op) {
-                let left_str = expression[..pos].trim();
-                let right_str = expression[pos + op.len()..].trim();
-
-                let left: f64 = left_str.parse().with_context(|| {
-                    format!("Failed to parse left operand '{}' as number", left_str)
-                })?;
-                let right: f64 = right_str.parse().with_context(|| {
-                    format!("Failed to parse right operand '{}' as number", right_str)
-                })?;
-
-                let result = match *op {
-                    "<" => left < right,
-                    "<=" => left <= right,
-                    ">" => left > right,
-                    ">=" => left >= right,
-                    "==" => (left - right).abs() < f64::EPSILON, // Handle floating point equality
-                    "!=" => (left - right).abs() >= f64::EPSILON,
-                    _ => unreachable!(),
-                };
-
-                log::debug!(
-                    "  - Expression evaluation: {} {} {} = {}",
-                    left,
-                    op,
-                    right,
-                    result
-                );
-                return Ok(result);
-            }
+        let prometheus_response: PrometheusResponse = response
+            .json()
+            .await
+            .context("Failed to parse Prometheus response JSON")?;
+        if prometheus_response.status != "success" {
+            let error_msg = prometheus_response.error.unwrap_or_else(|| "Unknown error".to_string());
+            return Err(anyhow!("Prometheus returned error: {}", error_msg));
         }
 
-        Err(anyhow!("Unsupported expression format: '{}'. Expected format: 'number operator number' or logical combinations", expression))
-        /* END CHANGE */
+        let data = prometheus_response
+            .data
+            .ok_or_else(|| anyhow!("Prometheus response missing data field"))?;
+        Ok(collect_samples(&data))
     }
 
     /// Gets the health status of the Prometheus connection.
@@ -472,6 +536,16 @@ impl PrometheusClient {
     }
 }
 
+/// Collects the finite sample values of every series in a Prometheus result.
+fn collect_samples(data: &PrometheusData) -> Vec<f64> {
+    data.result
+        .iter()
+        .flat_map(|series| series.value.iter().chain(series.values.iter().flatten()))
+        .filter_map(|(_, value)| value.parse::<f64>().ok())
+        .filter(|value| value.is_finite())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -481,66 +555,85 @@ mod tests {
             name: name.to_string(),
             query: query.to_string(),
             on_success: condition.to_string(),
+            provider: None,
+            predictive_analysis: None,
+            comparison: None,
         }
     }
 
-    #[tokio::test]
-    async fn test_simple_expression_evaluation() {
-        let client = PrometheusClient::new("http://localhost:9090");
-
-        // Test comparison operators
-        assert!(client.evaluate_simple_expression("0.85 < 0.90").unwrap());
-        assert!(!client.evaluate_simple_expression("0.95 < 0.90").unwrap());
-        assert!(client.evaluate_simple_expression("0.95 > 0.90").unwrap());
-        assert!(!client.evaluate_simple_expression("0.85 > 0.90").unwrap());
-        assert!(client.evaluate_simple_expression("0.90 <= 0.90").unwrap());
-        assert!(client.evaluate_simple_expression("0.90 >= 0.90").unwrap());
-        assert!(client.evaluate_simple_expression("0.90 == 0.90").unwrap());
-        assert!(client.evaluate_simple_expression("0.85 != 0.90").unwrap());
-
-        // Test logical operators
-        assert!(client
-            .evaluate_simple_expression("0.85 < 0.90 && 0.95 > 0.90")
-            .unwrap());
-        assert!(client
-            .evaluate_simple_expression("0.95 < 0.90 || 0.85 < 0.90")
-            .unwrap());
-        assert!(!client
-            .evaluate_simple_expression("0.95 < 0.90 && 0.85 > 0.90")
-            .unwrap());
+    #[test]
+    fn test_success_condition_bindings() {
+        // The measured value is bound as `result`, named results as `result.<name>`.
+        let mut results = MetricResults::single(0.002);
+        results.named.insert("p99".to_string(), 250.0);
+        assert!(evaluate_success("result.p99 < 300 && result < 0.01", &results).unwrap());
+        assert!(!evaluate_success("result.p99 < 200", &results).unwrap());
+        assert!(evaluate_success("result.p50 < 300", &results).is_err());
+
+        // NaN from Prometheus reaches the condition, which must guard it.
+        let nan = MetricResults::single(f64::NAN);
+        assert!(evaluate_success("result < 0.01", &nan).is_err());
+        assert!(evaluate_success("isNaN(result) || result < 0.01", &nan).unwrap());
+    }
+
+    /// Serves `/api/v1/query` like Prometheus, answering each query with the
+    /// value `values` maps it to, and returns the endpoint.
+    async fn fake_prometheus(values: &'static [(&'static str, &'static str)]) -> String {
+        use warp::Filter;
+        let route = warp::path!("api" / "v1" / "query")
+            .and(warp::query::<std::collections::HashMap<String, String>>())
+            .map(move |params: std::collections::HashMap<String, String>| {
+                let result: Vec<_> = values
+                    .iter()
+                    .filter(|(query, _)| params.get("query").map(String::as_str) == Some(*query))
+                    .map(|(_, value)| serde_json::json!({ "metric": {}, "value": [1717322400, value] }))
+                    .collect();
+                warp::reply::json(&serde_json::json!({ "status": "success", "data": { "resultType": "vector", "result": result } }))
+            });
+        let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        format!("http://{}", address)
     }
 
     #[tokio::test]
-    async fn test_success_condition_evaluation() {
-        let client = PrometheusClient::new("http://localhost:9090");
+    async fn test_analyze_against_prometheus() {
+        let endpoint = fake_prometheus(&[("errors", "0.01"), ("timeouts", "0.2"), ("idle", "NaN")]).await;
+        let (service, _handle) = tower_test::mock::pair::<http::Request<kube::client::Body>, http::Response<kube::client::Body>>();
+        let analyzer = MetricsAnalyzer::new(Client::new(service, "default"), &endpoint);
+        let target = MetricTarget {
+            namespace: "default".to_string(),
+            release_name: "shop".to_string(),
+            release_uid: None,
+        };
+        let analyze = |query: &'static str, condition: &'static str| {
+            let metric = create_test_metric(query, query, condition);
+            let analyzer = &analyzer;
+            let target = &target;
+            async move { analyzer.analyze(&metric, target, &[]).await.unwrap() }
+        };
 
-        // Test with typical conditions
-        assert!(client
-            .evaluate_success_condition("result < 0.95", 0.85)
-            .await
-            .unwrap());
-        assert!(!client
-            .evaluate_success_condition("result < 0.95", 0.98)
-            .await
-            .unwrap());
-        assert!(client
-            .evaluate_success_condition("result <= 500.0", 450.0)
-            .await
-            .unwrap());
-        assert!(!client
-            .evaluate_success_condition("result <= 500.0", 600.0)
-            .await
-            .unwrap());
+        assert_eq!(analyze("errors", "result < 0.05").await, (AnalysisResult::Success, Some(0.01)));
+        assert_eq!(analyze("timeouts", "result < 0.05").await, (AnalysisResult::Failure, Some(0.2)));
+        // A condition that cannot be evaluated, or a query without data, is
+        // inconclusive and has no value.
+        assert_eq!(analyze("idle", "result < 0.05").await, (AnalysisResult::Inconclusive, None));
+        assert_eq!(analyze("missing", "result < 0.05").await, (AnalysisResult::Inconclusive, None));
+    }
 
-        // Test complex conditions
-        assert!(client
-            .evaluate_success_condition("result > 0.9 && result < 1.0", 0.95)
-            .await
-            .unwrap());
-        assert!(client
-            .evaluate_success_condition("result < 0.1 || result > 0.9", 0.05)
-            .await
-            .unwrap());
+    #[tokio::test]
+    async fn test_failed_query_has_no_value() {
+        let (service, _handle) = tower_test::mock::pair::<http::Request<kube::client::Body>, http::Response<kube::client::Body>>();
+        // Nothing listens on port 1, so the query fails.
+        let analyzer = MetricsAnalyzer::new(Client::new(service, "default"), "http://127.0.0.1:1");
+        let metric = create_test_metric("error_rate", "rate(errors[5m])", "result < 0.05");
+        let target = MetricTarget {
+            namespace: "default".to_string(),
+            release_name: "shop".to_string(),
+            release_uid: None,
+        };
+        let (result, value) = analyzer.analyze(&metric, &target, &[]).await.unwrap();
+        assert!(matches!(result, AnalysisResult::Inconclusive));
+        assert_eq!(value, None);
     }
 
     #[tokio::test]
@@ -595,4 +688,32 @@ mod tests {
         assert!(result.value.is_some());
         assert_eq!(result.value.as_ref().unwrap().1, "1");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_comparison_sampling() {
+        let comparison = BaselineComparison {
+            query: "rate(http_errors_total{app=\"checkout\",version_id=\"{{track}}\"}[1m])".to_string(),
+            lookback: Some("5m".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            comparison_query(&comparison, "canary"),
+            "(rate(http_errors_total{app=\"checkout\",version_id=\"canary\"}[1m]))[5m:30s]"
+        );
+
+        let json_response = r#"
+        {
+            "status": "success",
+            "data": {
+                "resultType": "matrix",
+                "result": [
+                    { "metric": { "pod": "a" }, "values": [[1609459200, "0.1"], [1609459230, "NaN"]] },
+                    { "metric": { "pod": "b" }, "values": [[1609459200, "0.3"], [1609459230, "0.2"]] }
+                ]
+            }
+        }
+        "#;
+        let parsed: PrometheusResponse = serde_json::from_str(json_response).unwrap();
+        assert_eq!(collect_samples(&parsed.data.unwrap()), vec![0.1, 0.3, 0.2]);
+    }
+}
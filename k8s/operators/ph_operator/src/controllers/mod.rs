@@ -19,9 +19,30 @@
 
 pub mod pipeline_controller;
 pub mod preview_controller;
+pub mod preview_guardrails;
+pub mod preview_render;
+pub mod preview_report;
+pub mod preview_schedule;
+pub mod preview_waker;
 pub mod release_controller;
 pub mod utils;
 pub mod metrics_analyzer; 
+pub mod metric_providers;
+pub mod expression;
+pub mod canary_judge;
 pub mod autoheal_controller;
 pub mod dr_controller;
-pub mod gitsync_controller;
\ No newline at end of file
+pub mod gitsync_controller;
+pub mod conditions;
+pub mod events;
+pub mod pipeline_dag;
+pub mod pipeline_workspace;
+pub mod pipeline_cache;
+pub mod pipeline_matrix;
+pub mod pipeline_when;
+pub mod pipeline_template;
+pub mod pipeline_run_controller;
+pub mod vcs_events;
+pub mod trigger_controller;
+pub mod preview_trigger_controller;
+pub mod vcs_webhook;
\ No newline at end of file
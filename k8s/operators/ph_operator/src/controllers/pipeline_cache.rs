@@ -0,0 +1,443 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/pipeline_cache.rs
+*
+* This file lets phPipeline steps with a `cache` be skipped when a previous
+* run had the same inputs.
+*
+* Architecture:
+* - The controller hashes what it knows of a step (`base_key`): its image,
+*   command, arguments, environment and cache `params`, with references to
+*   earlier outputs resolved. The files of the workspace can only be read in
+*   a pod, so before the step runs a small lookup Job (`probe_container`)
+*   adds the content of the files matching the cache `files` globs to the
+*   hash. That gives the key of the cache entry.
+* - On a hit, the lookup restores the step's output directory and its cached
+*   `paths` into the workspace and reports the recorded output values; the
+*   step's Job is never created. On a miss, the step runs with an extra
+*   container (`save_container`) that stores the same files under the key
+*   once the step succeeded.
+* - Entries live in a claim mounted at `/cache` or in an S3-compatible bucket.
+*   An entry is complete once its `results` file exists, which is written
+*   last.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::controllers::pipeline_workspace::{
+    self, shell_quote, WorkspaceError, HELPER_IMAGE, S3_IMAGE, WORKSPACE_MOUNT,
+};
+use crate::crds::{phPipelineSpec, PipelineCache, PipelineStep, WorkspaceS3};
+use pipeline_rules::names::content_hash;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// The container of the lookup Job; its termination message carries the
+/// result.
+pub const PROBE_CONTAINER: &str = "ph-cache";
+
+const SAVE_CONTAINER: &str = "ph-cache-save";
+const CACHE_VOLUME: &str = "cache";
+const CACHE_MOUNT: &str = "/cache";
+const KEY_RESULT: &str = "ph.cache.key";
+const HIT_RESULT: &str = "ph.cache.hit";
+/// Where the helper containers collect the output values before copying
+/// them into an entry.
+const RESULTS_FILE: &str = "/tmp/ph-cache-results";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CacheError {
+    #[error("step '{0}' has a cache, but the pipeline sets no 'cache' store")]
+    MissingStore(String),
+    #[error("cache must set exactly one of 'volume' and 's3'")]
+    InvalidStore,
+    #[error("step '{0}' has a cache, which needs a volume workspace")]
+    NeedsVolumeWorkspace(String),
+    #[error("cache path '{path}' of step '{step}' must be inside /workspace")]
+    PathOutsideWorkspace { step: String, path: String },
+}
+
+/// Checks the cache store and the cache of every step.
+pub fn validate(spec: &phPipelineSpec) -> Result<(), CacheError> {
+    if let Some(cache) = &spec.cache {
+        if cache.volume.is_some() == cache.s3.is_some() {
+            return Err(CacheError::InvalidStore);
+        }
+    }
+    let volume_workspace = spec.workspace.as_ref().is_some_and(|w| w.volume.is_some());
+    for step in spec.stages.iter().flat_map(|stage| &stage.steps).chain(&spec.finally) {
+        let Some(cache) = &step.cache else { continue };
+        if spec.cache.is_none() {
+            return Err(CacheError::MissingStore(step.name.clone()));
+        }
+        if !volume_workspace {
+            return Err(CacheError::NeedsVolumeWorkspace(step.name.clone()));
+        }
+        for path in cache.files.iter().chain(&cache.paths) {
+            workspace_path(path).ok_or_else(|| CacheError::PathOutsideWorkspace {
+                step: step.name.clone(),
+                path: path.clone(),
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns `path` relative to the workspace, if it is inside it.
+fn workspace_path(path: &str) -> Option<&str> {
+    let relative = match path.strip_prefix('/') {
+        Some(_) => path.strip_prefix(WORKSPACE_MOUNT)?.strip_prefix('/')?,
+        None => path,
+    };
+    let relative = relative.trim_end_matches('/');
+    let escapes = relative.split('/').any(|part| part == "..");
+    (!relative.is_empty() && !escapes).then_some(relative)
+}
+
+/// Hashes the inputs of a step that the controller knows: everything but the
+/// workspace files.
+pub fn base_key(
+    step: &PipelineStep,
+    outputs: &HashMap<String, BTreeMap<String, String>>,
+) -> Result<String, WorkspaceError> {
+    let resolve = |values: &[String]| {
+        values
+            .iter()
+            .map(|value| pipeline_workspace::substitute(value, outputs))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let env = step
+        .env
+        .iter()
+        .map(|var| {
+            let value = pipeline_workspace::substitute(&var.value, outputs)?;
+            Ok(json!({ "name": var.name, "value": value, "valueFrom": var.value_from }))
+        })
+        .collect::<Result<Vec<_>, WorkspaceError>>()?;
+    let cache = step.cache.clone().unwrap_or_default();
+    let inputs = json!({
+        "step": step.name,
+        "stepType": step.step_type,
+        "image": step.image,
+        "command": resolve(&step.command)?,
+        "args": resolve(&step.args)?,
+        "env": env,
+        "outputs": step.outputs,
+        "params": resolve(&cache.params)?,
+        "files": cache.files,
+        "paths": cache.paths,
+    });
+    Ok(content_hash(&serde_json::to_vec(&inputs).unwrap_or_default()))
+}
+
+/// The result of a lookup Job.
+#[derive(Debug, Default, PartialEq)]
+pub struct Lookup {
+    pub key: Option<String>,
+    pub hit: bool,
+    /// The recorded output values, on a hit.
+    pub outputs: BTreeMap<String, String>,
+}
+
+/// Parses the termination message of a lookup Job.
+pub fn parse_lookup(message: &str) -> Lookup {
+    let mut results = pipeline_workspace::parse_results(message);
+    let key = results.remove(KEY_RESULT).filter(|key| !key.is_empty());
+    let hit = results.remove(HIT_RESULT).as_deref() == Some("true");
+    Lookup {
+        hit: hit && key.is_some(),
+        key,
+        outputs: if hit { results } else { BTreeMap::new() },
+    }
+}
+
+/// Turns a cache glob into a `find -path` pattern, in which `*` also matches
+/// `/`.
+fn find_pattern(glob: &str) -> String {
+    let glob = workspace_path(glob).unwrap_or(glob);
+    format!("./{}", glob.replace("**/", "*").replace("**", "*"))
+}
+
+/// The pod-level pieces of the cache store of a pipeline.
+pub struct CacheStore<'a> {
+    cache: &'a PipelineCache,
+    namespace: String,
+}
+
+impl<'a> CacheStore<'a> {
+    /// Describes the cache store of a pipeline, if it has one.
+    pub fn new(spec: &'a phPipelineSpec, namespace: &str) -> Option<Self> {
+        Some(Self {
+            cache: spec.cache.as_ref()?,
+            namespace: namespace.to_string(),
+        })
+    }
+
+    fn s3(&self) -> Option<&WorkspaceS3> {
+        self.cache.s3.as_ref()
+    }
+
+    /// The volume holding the entries, for a claim store.
+    pub fn volume(&self) -> Option<Value> {
+        let volume = self.cache.volume.as_ref()?;
+        Some(json!({ "name": CACHE_VOLUME, "persistentVolumeClaim": { "claimName": volume.claim_name } }))
+    }
+
+    /// The location of the entry named by `$PH_CACHE_KEY`, quoted for a shell.
+    fn entry(&self) -> String {
+        let base = match self.s3() {
+            Some(s3) => {
+                let prefix = s3.prefix.as_deref().map(|p| p.trim_matches('/')).filter(|p| !p.is_empty());
+                match prefix {
+                    Some(prefix) => format!("s3://{}/{}/{}/", s3.bucket, prefix, self.namespace),
+                    None => format!("s3://{}/{}/", s3.bucket, self.namespace),
+                }
+            }
+            None => format!("{}/", CACHE_MOUNT),
+        };
+        format!("{}\"$PH_CACHE_KEY\"", shell_quote(&base))
+    }
+
+    /// Copies the directory `from` to `to`, on the volume or in the bucket.
+    fn copy(&self, from: &str, to: &str) -> String {
+        match self.s3() {
+            Some(_) => format!("aws --endpoint-url \"$S3_ENDPOINT\" s3 cp --recursive {}/ {}/", from, to),
+            None => format!("mkdir -p {to} && cp -a {}/. {to}/", from, to = to),
+        }
+    }
+
+    /// The container of the lookup Job of `step`: it computes the key from
+    /// `base_key` and the workspace files and restores a matching entry.
+    pub fn probe_container(&self, step: &PipelineStep, base_key: &str) -> Value {
+        let cache = step.cache.clone().unwrap_or_default();
+        let mut script = vec![format!("cd {}", WORKSPACE_MOUNT)];
+        if cache.files.is_empty() {
+            script.push(format!("PH_CACHE_KEY=$(echo {} | sha256sum | cut -c 1-64)", base_key));
+        } else {
+            let patterns: Vec<String> = cache
+                .files
+                .iter()
+                .map(|glob| format!("-path {}", shell_quote(&find_pattern(glob))))
+                .collect();
+            script.push(format!(
+                "PH_CACHE_KEY=$({{ echo {}; find . -type f \\( {} \\) -exec sha256sum {{}} + | LC_ALL=C sort; }} | sha256sum | cut -c 1-64)",
+                base_key,
+                patterns.join(" -o "),
+            ));
+        }
+        script.push(format!("echo \"{}=$PH_CACHE_KEY\" >> /dev/termination-log", KEY_RESULT));
+
+        let entry = self.entry();
+        let found = match self.s3() {
+            Some(_) => {
+                script.push("aws configure set default.s3.addressing_style path".to_string());
+                format!(
+                    "aws --endpoint-url \"$S3_ENDPOINT\" s3 cp {}/results {} > /dev/null 2>&1",
+                    entry, RESULTS_FILE
+                )
+            }
+            None => format!("[ -f {}/results ] && cp {}/results {}", entry, entry, RESULTS_FILE),
+        };
+        let output_dir = shell_quote(&pipeline_workspace::step_output_dir(&step.name));
+        script.push(format!("if {}; then", found));
+        script.push(format!("rm -rf {}", output_dir));
+        script.push(self.copy(&format!("{}/outputs", entry), &output_dir));
+        for (i, path) in cache.paths.iter().enumerate() {
+            // `validate` rejected paths outside the workspace.
+            let Some(path) = workspace_path(path) else { continue };
+            let path = shell_quote(&format!("{}/{}", WORKSPACE_MOUNT, path));
+            script.push(format!("rm -rf {}", path));
+            script.push(self.copy(&format!("{}/paths/{}", entry, i), &path));
+        }
+        script.push(format!("echo {}=true >> /dev/termination-log", HIT_RESULT));
+        script.push(format!("cat {} >> /dev/termination-log", RESULTS_FILE));
+        script.push("else".to_string());
+        script.push(format!("echo {}=false >> /dev/termination-log", HIT_RESULT));
+        script.push("fi".to_string());
+        self.container(PROBE_CONTAINER, &format!("set -e\n{}", script.join("\n")))
+    }
+
+    /// The container that stores the results of `step` under `key` after it
+    /// succeeded. Failing to store them does not fail the step.
+    pub fn save_container(&self, step: &PipelineStep, key: &str) -> Value {
+        let cache = step.cache.clone().unwrap_or_default();
+        let mut script = vec![format!(": > {}", RESULTS_FILE)];
+        script.extend(pipeline_workspace::results_script(step, RESULTS_FILE));
+        let output_dir = shell_quote(&pipeline_workspace::step_output_dir(&step.name));
+        match self.s3() {
+            Some(_) => {
+                let entry = self.entry();
+                script.push("aws configure set default.s3.addressing_style path".to_string());
+                script.push(self.copy(&output_dir, &format!("{}/outputs", entry)));
+                for (i, path) in cache.paths.iter().enumerate() {
+                    let Some(path) = workspace_path(path) else { continue };
+                    let path = shell_quote(&format!("{}/{}", WORKSPACE_MOUNT, path));
+                    script.push(format!(
+                        "if [ -d {} ]; then {}; fi",
+                        path,
+                        self.copy(&path, &format!("{}/paths/{}", entry, i))
+                    ));
+                }
+                script.push(format!(
+                    "aws --endpoint-url \"$S3_ENDPOINT\" s3 cp {} {}/results",
+                    RESULTS_FILE, entry
+                ));
+            }
+            None => {
+                // The entry is assembled next to its final place and then
+                // renamed, so a lookup never sees half of it.
+                let staging = format!("{}/.tmp-\"$PH_CACHE_KEY\"-\"$HOSTNAME\"", CACHE_MOUNT);
+                script.push(format!("rm -rf {}", staging));
+                script.push(self.copy(&output_dir, &format!("{}/outputs", staging)));
+                for (i, path) in cache.paths.iter().enumerate() {
+                    let Some(path) = workspace_path(path) else { continue };
+                    let path = shell_quote(&format!("{}/{}", WORKSPACE_MOUNT, path));
+                    let target = format!("{}/paths/{}", staging, i);
+                    script.push(format!(
+                        "if [ -d {} ]; then {}; else mkdir -p {}; fi",
+                        path,
+                        self.copy(&path, &target),
+                        target
+                    ));
+                }
+                script.push(format!("cp {} {}/results", RESULTS_FILE, staging));
+                let entry = self.entry();
+                script.push(format!(
+                    "if [ -e {entry} ]; then rm -rf {staging}; else mv {staging} {entry}; fi",
+                    entry = entry,
+                    staging = staging
+                ));
+            }
+        }
+        let command = format!(
+            "(\nset -e\n{}\n) || echo 'Could not save the cache entry.' >&2",
+            script.join("\n")
+        );
+        let mut container = self.container(SAVE_CONTAINER, &command);
+        if let Some(env) = container["env"].as_array_mut() {
+            env.push(json!({ "name": "PH_CACHE_KEY", "value": key }));
+        } else {
+            container["env"] = json!([{ "name": "PH_CACHE_KEY", "value": key }]);
+        }
+        container
+    }
+
+    fn container(&self, name: &str, script: &str) -> Value {
+        let mut mounts = vec![json!({ "name": "workspace", "mountPath": WORKSPACE_MOUNT })];
+        if self.cache.volume.is_some() {
+            mounts.push(json!({ "name": CACHE_VOLUME, "mountPath": CACHE_MOUNT }));
+        }
+        let mut container = json!({
+            "name": name,
+            "image": if self.s3().is_some() { S3_IMAGE } else { HELPER_IMAGE },
+            "command": ["sh", "-c", script],
+            "volumeMounts": mounts,
+        });
+        if let Some(s3) = self.s3() {
+            pipeline_workspace::add_s3_access(&mut container, s3);
+        }
+        container
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crds::{CacheVolume, PipelineStage, PipelineWorkspace, WorkspaceVolume};
+
+    fn cached_step(files: &[&str], paths: &[&str]) -> PipelineStep {
+        serde_json::from_value(json!({
+            "name": "deps",
+            "image": "node:20@sha256:0123",
+            "command": ["npm", "ci"],
+            "env": [{ "name": "REGISTRY", "value": "$(steps.setup.outputs.registry)" }],
+            "cache": { "files": files, "paths": paths, "params": ["$(steps.setup.outputs.node)"] }
+        }))
+        .unwrap()
+    }
+
+    fn spec(step: PipelineStep, workspace: Option<PipelineWorkspace>, cache: Option<PipelineCache>) -> phPipelineSpec {
+        phPipelineSpec {
+            stages: vec![PipelineStage {
+                name: "build".to_string(),
+                steps: vec![step],
+            }],
+            max_parallel: None,
+            workspace,
+            finally: Vec::new(),
+            cache,
+            changed_paths: None,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let workspace = PipelineWorkspace {
+            volume: Some(WorkspaceVolume::default()),
+            s3: None,
+        };
+        let store = PipelineCache {
+            volume: Some(CacheVolume {
+                claim_name: "ci-cache".to_string(),
+            }),
+            s3: None,
+        };
+        let step = cached_step(&["package-lock.json"], &["node_modules", "/workspace/.npm/"]);
+        assert!(validate(&spec(step.clone(), Some(workspace.clone()), Some(store.clone()))).is_ok());
+        assert_eq!(
+            validate(&spec(step.clone(), Some(workspace.clone()), None)),
+            Err(CacheError::MissingStore("deps".to_string()))
+        );
+        assert_eq!(
+            validate(&spec(step, None, Some(store.clone()))),
+            Err(CacheError::NeedsVolumeWorkspace("deps".to_string()))
+        );
+        let escaping = cached_step(&["../secrets"], &[]);
+        assert!(matches!(
+            validate(&spec(escaping, Some(workspace.clone()), Some(store))),
+            Err(CacheError::PathOutsideWorkspace { .. })
+        ));
+        assert_eq!(
+            validate(&spec(cached_step(&[], &[]), Some(workspace), Some(PipelineCache::default()))),
+            Err(CacheError::InvalidStore)
+        );
+    }
+
+    #[test]
+    fn test_base_key() {
+        let outputs = |node: &str| {
+            HashMap::from([(
+                "setup".to_string(),
+                BTreeMap::from([
+                    ("registry".to_string(), "https://npm.local".to_string()),
+                    ("node".to_string(), node.to_string()),
+                ]),
+            )])
+        };
+        let step = cached_step(&["package-lock.json"], &["node_modules"]);
+        let key = base_key(&step, &outputs("20.11")).unwrap();
+        assert_eq!(key, base_key(&step, &outputs("20.11")).unwrap());
+        // A different parameter or image gives a different key.
+        assert_ne!(key, base_key(&step, &outputs("20.12")).unwrap());
+        let mut other_image = step.clone();
+        other_image.image = "node:20@sha256:4567".to_string();
+        assert_ne!(key, base_key(&other_image, &outputs("20.11")).unwrap());
+        assert!(base_key(&step, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_lookup() {
+        let hit = parse_lookup("ph.cache.key=ab12\nph.cache.hit=true\nimage=registry.local/app:1\n");
+        assert_eq!(hit.key.as_deref(), Some("ab12"));
+        assert!(hit.hit);
+        assert_eq!(hit.outputs["image"], "registry.local/app:1");
+
+        let miss = parse_lookup("ph.cache.key=ab12\nph.cache.hit=false\n");
+        assert!(!miss.hit);
+        assert!(miss.outputs.is_empty());
+        assert_eq!(parse_lookup(""), Lookup::default());
+        assert_eq!(find_pattern("/workspace/src/**/*.go"), "./src/*.go");
+    }
+}
@@ -6,8 +6,8 @@
  * This file implements the reconciliation logic for the phPipeline custom resource.
  * It functions as a resilient, state-driven orchestrator for CI/CD pipelines
  * defined declaratively within the Kubernetes cluster. The controller's primary
- * responsibility is to execute a sequence of stages and steps, represented as
- * Kubernetes Jobs, ensuring sequential execution and accurate status reporting.
+ * responsibility is to execute the steps of a pipeline, represented as Kubernetes
+ * Jobs, in dependency order and with accurate status reporting.
  *
  * Architecture:
  * The controller's architecture is a refined state machine, where the state of the
@@ -23,14 +23,38 @@
  * `cleanup_pipeline` functions.
  * - `apply_pipeline`: This function acts as the main dispatcher for the state machine.
  * It inspects the `status.phase` field and takes action accordingly:
- * - `Pending` (or None): Initializes the pipeline. It sets the status to `Running`,
- * records the start time, and sets the current stage/step indices to 0. This
- * is the initial state for any new pipeline.
- * - `Running`: The core processing state. It identifies the current step, creates
- * a Kubernetes Job for it if one doesn't exist, and then monitors the Job's
- * status. Upon Job success, it advances the step/stage indices. On failure,
- * it transitions the pipeline to the `Failed` phase.
- * - `Succeeded` / `Failed`: These are terminal states. No further action is taken,
+ * - `Pending` (or None): Initializes the pipeline. It sets the status to `Running`
+ * and records the start time. This is the initial state for any new pipeline.
+ * - `Running`: The core processing state. The steps form a dependency graph
+ * (see `pipeline_dag`): a step waits for the steps it `needs`, or for the whole
+ * previous stage. Every step whose dependencies succeeded gets a Job, up to
+ * `maxParallel` at a time, and `status.steps` tracks each step. When a step
+ * fails, the pending steps are skipped, and the pipeline turns `Failed` once
+ * the running ones finished. Only the steps whose `when` conditions check the
+ * status of another step still run, e.g. to react to the failure. A step
+ * with `continueOnError` never fails the pipeline.
+ * - Timeouts and retries: a step's `timeout` becomes the deadline of its Job.
+ * A failed or timed-out attempt is retried `retries` times, each time in a new
+ * Job and after an exponential backoff (`retryBackoff`).
+ * - `finally` steps run once all other steps finished, whatever the outcome,
+ * and fail the pipeline when they fail.
+ * - Cancellation: setting the `ph.io/cancel` annotation to "true" deletes the
+ * running Jobs and skips the pending steps; the `finally` steps still run and
+ * the pipeline ends `Cancelled`.
+ * - Workspace and outputs: every step pod mounts the run's workspace (see
+ * `pipeline_workspace`). When a step succeeds, the values of its outputs are
+ * stored in `status.steps`, and later steps receive them wherever they write
+ * `$(steps.<step>.outputs.<name>)`.
+ * - Matrix and conditions: a step with a `matrix` runs once per combination
+ * of its parameters (see `pipeline_matrix`), and `status.steps` records the
+ * combination of each. A step whose `when` conditions do not hold is skipped
+ * when its turn comes (see `pipeline_when`); skipped steps do not block the
+ * steps that need them.
+ * - Caching: a step with a `cache` first runs a lookup Job that hashes its
+ * inputs (see `pipeline_cache`). On a hit the recorded outputs and cached
+ * directories are restored and the step succeeds without running; on a miss
+ * the step runs and its results are saved under the key.
+ * - `Succeeded` / `Failed` / `Cancelled`: These are terminal states. No further action is taken,
  * and the reconciliation loop effectively stops for this resource until it is
  * updated or deleted.
  * - `handle_running_pipeline`: A dedicated function to manage the logic when a pipeline
@@ -41,6 +65,11 @@
  * handle the primary garbage collection, this hook is preserved for any future,
  * more complex cleanup needs (e.g., sending notifications, cleaning external
  * resources).
+ * - Conditions and Events: Every phase change is mirrored in the standard
+ * `Ready`/`Progressing`/`Degraded` conditions and announced with a Kubernetes
+ * Event (`JobCreated`, `StepSucceeded`, `StepCached`, `StepRetrying`,
+ * `StepTimedOut`, `StepSkipped`, `JobFailed`, `CacheLookupFailed`,
+ * `PipelineCancelled`, `PipelineSucceeded`).
  * - `update_status`: A robust, centralized function for patching the status
  * subresource using a server-side `Patch::Apply`. This is the modern, preferred
  * way to update status, preventing race conditions.
@@ -51,13 +80,15 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::{PersistentVolumeClaim, Pod};
 use kube::{
-    api::{Api, Patch, PatchParams, PostParams, ResourceExt},
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams, PostParams, ResourceExt},
     client::Client,
     runtime::{
         controller::Action,
+        events::Recorder,
         finalizer::{finalizer, Event as FinalizerEvent},
     },
     Error as KubeError,
@@ -67,10 +98,25 @@ use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 
-use crate::crds::{phPipeline, phPipelineStatus, PipelinePhase};
+use crate::controllers::conditions::{self, ResourceState};
+use crate::controllers::events;
+use crate::controllers::pipeline_cache::{self, CacheStore};
+use crate::controllers::pipeline_dag::{self, DagStep, DEFAULT_MAX_PARALLEL};
+use crate::controllers::pipeline_matrix;
+use crate::controllers::pipeline_when::{self, WhenContext};
+use crate::controllers::pipeline_workspace::{self, RunWorkspace};
+use crate::crds::{
+    phPipeline, phPipelineStatus, CacheResult, PipelinePhase, PipelineStepStatus, StepCacheStatus, StepPhase,
+};
+use pipeline_rules::duration::parse_duration;
+use pipeline_rules::names::bounded_name;
+use std::collections::{BTreeMap, HashMap};
 
 // The unique identifier for our controller's finalizer.
 const PIPELINE_FINALIZER: &str = "ph.io/pipeline-finalizer";
+/// Cancels a running pipeline when set to "true": the running Jobs are
+/// deleted, the pending steps skipped, and the `finally` steps still run.
+const CANCEL_ANNOTATION: &str = "ph.io/cancel";
 
 // Custom error types for the pipeline controller.
 #[derive(Debug, Error)]
@@ -83,11 +129,21 @@ pub enum Error {
 
     #[error("Failed to update resource status: {0}")]
     StatusUpdateError(String),
+
+    #[error("Invalid step: {0}")]
+    InvalidStep(String),
 }
 
 /// The context required by the reconciler.
 pub struct Context {
     pub client: Client,
+    /// Publishes Kubernetes Events about pipelines.
+    pub recorder: Recorder,
+}
+
+/// Sets the standard conditions of the pipeline status to `state`.
+fn set_state(pipeline: &phPipeline, status: &mut phPipelineStatus, state: ResourceState, reason: &str, message: &str) {
+    conditions::set_state(&mut status.conditions, state, reason, message, pipeline.metadata.generation, Utc::now());
 }
 
 /// Main reconciliation function for the phPipeline resource.
@@ -120,13 +176,13 @@ async fn apply_pipeline(pipeline: Arc<phPipeline>, ctx: Arc<Context>) -> Result<
         // State: Pending/None. This is a new pipeline. Let's initialize it.
         None | Some(PipelinePhase::Pending) => {
             println!("Pipeline '{}' is new. Initializing status.", pipeline.name_any());
-            let initial_status = phPipelineStatus {
+            let mut initial_status = phPipelineStatus {
                 phase: Some(PipelinePhase::Running),
                 start_time: Some(Utc::now().to_rfc3339()),
-                current_stage_index: Some(0),
-                current_step_index: Some(0),
+                conditions: pipeline.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default(),
                 ..Default::default()
             };
+            set_state(&pipeline, &mut initial_status, ResourceState::Progressing, "Started", "Pipeline started");
             update_status(pipeline, ctx.client.clone(), initial_status).await?;
             // Requeue immediately to begin the first step.
             Ok(Action::requeue(Duration::from_millis(100)))
@@ -134,172 +190,678 @@ async fn apply_pipeline(pipeline: Arc<phPipeline>, ctx: Arc<Context>) -> Result<
         // State: Running. This is where the core logic happens.
         Some(PipelinePhase::Running) => handle_running_pipeline(pipeline, ctx).await,
         // Terminal states: Do nothing further.
-        Some(PipelinePhase::Succeeded) | Some(PipelinePhase::Failed) => {
+        Some(PipelinePhase::Succeeded) | Some(PipelinePhase::Failed) | Some(PipelinePhase::Cancelled) => {
             Ok(Action::await_change())
         }
     }
 }
 
-/// Handles the core logic when a pipeline is in the "Running" state.
+/// Returns the status of every step of `dag`, in order, keeping the recorded
+/// state of the steps that already have one. `combinations` holds the matrix
+/// combination of the steps expanded from a matrix.
+fn step_statuses(
+    dag: &[DagStep<'_>],
+    combinations: &HashMap<String, BTreeMap<String, String>>,
+    previous: Vec<PipelineStepStatus>,
+) -> Vec<PipelineStepStatus> {
+    let mut previous: HashMap<String, PipelineStepStatus> =
+        previous.into_iter().map(|step| (step.name.clone(), step)).collect();
+    dag.iter()
+        .map(|node| {
+            previous.remove(&node.step.name).unwrap_or_else(|| PipelineStepStatus {
+                name: node.step.name.clone(),
+                stage: node.stage_name.to_string(),
+                phase: StepPhase::Pending,
+                job_name: None,
+                start_time: None,
+                completion_time: None,
+                message: None,
+                outputs: BTreeMap::new(),
+                attempts: 0,
+                next_attempt_time: None,
+                cache: None,
+                matrix: combinations.get(&node.step.name).cloned().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// The name of the Job that runs attempt `attempt` (counted from 1) of a step,
+/// shortened to the length Kubernetes allows.
+fn step_job_name(pipeline: &phPipeline, node: &DagStep<'_>, attempt: u32) -> String {
+    let name = format!(
+        "{}-s{}-{}",
+        pipeline.name_any(),
+        node.stage_index,
+        node.step.name.replace('_', "-")
+    );
+    if attempt > 1 {
+        bounded_name(&format!("{}-r{}", name, attempt - 1))
+    } else {
+        bounded_name(&name)
+    }
+}
+
+/// Handles the core logic when a pipeline is in the "Running" state: it
+/// refreshes the running steps from their Jobs, then starts every step whose
+/// dependencies succeeded, up to the concurrency limit.
 async fn handle_running_pipeline(pipeline: Arc<phPipeline>, ctx: Arc<Context>) -> Result<Action, Error> {
     let client = &ctx.client;
     let ns = pipeline.namespace().unwrap();
     let spec = pipeline.spec.as_ref().ok_or(Error::MissingSpec)?;
     let mut status = pipeline.status.as_ref().cloned().unwrap_or_default(); // Should always exist here.
 
-    let stage_index = status.current_stage_index.unwrap_or(0);
-    let step_index = status.current_step_index.unwrap_or(0);
+    // --- 1. Expand the matrix steps and build the dependency graph ---
+    let expanded = pipeline_matrix::expand(spec).map_err(|e| e.to_string());
+    let validated = expanded.as_ref().map_err(Clone::clone).and_then(|expansion| {
+        let spec = &expansion.spec;
+        let dag = pipeline_dag::build(spec, &expansion.groups).map_err(|e| e.to_string())?;
+        pipeline_workspace::validate(spec).map_err(|e| e.to_string())?;
+        pipeline_cache::validate(spec).map_err(|e| e.to_string())?;
+        pipeline_when::validate(spec).map_err(|e| e.to_string())?;
+        Ok((dag, expansion))
+    });
+    let (dag, expansion) = match validated {
+        Ok(validated) => validated,
+        Err(e) => {
+            let message = format!("Invalid pipeline: {}", e);
+            eprintln!("Pipeline '{}' is invalid: {}", pipeline.name_any(), e);
+            events::warning(&ctx.recorder, &*pipeline, "InvalidSpec", "Validate", message.clone()).await;
+            status.phase = Some(PipelinePhase::Failed);
+            status.completion_time = Some(Utc::now().to_rfc3339());
+            set_state(&pipeline, &mut status, ResourceState::Degraded, "InvalidSpec", &message);
+            update_status(pipeline.clone(), client.clone(), status).await?;
+            return Ok(Action::await_change());
+        }
+    };
+    let spec = &expansion.spec;
+    status.steps = step_statuses(&dag, &expansion.combinations, std::mem::take(&mut status.steps));
 
-    // --- 1. Check for pipeline completion ---
-    if stage_index >= spec.stages.len() {
-        println!("Pipeline '{}' completed successfully.", pipeline.name_any());
-        status.phase = Some(PipelinePhase::Succeeded);
-        status.completion_time = Some(Utc::now().to_rfc3339());
-        update_status(pipeline, client.clone(), status).await?;
-        return Ok(Action::await_change());
+    // --- 2. Refresh the running steps from their Jobs ---
+    let jobs: Api<Job> = Api::namespaced(client.clone(), &ns);
+    for (node, step_status) in dag.iter().zip(status.steps.iter_mut()) {
+        if step_status.phase != StepPhase::Running {
+            continue;
+        }
+        let job_name = step_status
+            .job_name
+            .clone()
+            .unwrap_or_else(|| step_job_name(&pipeline, node, step_status.attempts.max(1)));
+        let looking_up = step_status.cache.as_ref().map(|cache| cache.result) == Some(CacheResult::Checking);
+        let Some(job) = jobs.get_opt(&job_name).await? else {
+            // The Job was deleted while the step ran: run the step again.
+            step_status.phase = StepPhase::Pending;
+            if looking_up {
+                step_status.cache = None;
+            }
+            continue;
+        };
+        let job_status = job.status.unwrap_or_default();
+        if looking_up {
+            if job_status.succeeded.unwrap_or(0) > 0 {
+                let message = read_termination_message(client, &ns, &job_name, pipeline_cache::PROBE_CONTAINER).await?;
+                let lookup = pipeline_cache::parse_lookup(&message.unwrap_or_default());
+                if lookup.hit {
+                    println!("Step '{}' of pipeline '{}' was restored from the cache.", node.step.name, pipeline.name_any());
+                    events::normal(
+                        &ctx.recorder,
+                        &*pipeline,
+                        "StepCached",
+                        "Run",
+                        format!(
+                            "Step '{}' of stage '{}' was restored from the cache (key {}).",
+                            node.step.name,
+                            node.stage_name,
+                            lookup.key.as_deref().unwrap_or_default()
+                        ),
+                    )
+                    .await;
+                    step_status.phase = StepPhase::Succeeded;
+                    step_status.completion_time = Some(Utc::now().to_rfc3339());
+                    step_status.outputs = lookup.outputs;
+                    step_status.cache = Some(StepCacheStatus { result: CacheResult::Hit, key: lookup.key });
+                } else {
+                    // The step runs as soon as it is picked again.
+                    step_status.phase = StepPhase::Pending;
+                    step_status.cache = Some(StepCacheStatus { result: CacheResult::Miss, key: lookup.key });
+                }
+            } else if job_status.failed.unwrap_or(0) > 0 {
+                // Without a key the step still runs, but its results are not saved.
+                let message = format!(
+                    "Cache lookup for step '{}' failed (Job '{}'); running the step without the cache.",
+                    node.step.name, job_name
+                );
+                events::warning(&ctx.recorder, &*pipeline, "CacheLookupFailed", "Run", message).await;
+                step_status.phase = StepPhase::Pending;
+                step_status.cache = Some(StepCacheStatus { result: CacheResult::Miss, key: None });
+            }
+            continue;
+        }
+        let timed_out = job_status.conditions.iter().flatten().any(|condition| {
+            condition.type_ == "Failed" && condition.status == "True" && condition.reason.as_deref() == Some("DeadlineExceeded")
+        });
+        if job_status.succeeded.unwrap_or(0) > 0 {
+            println!("Job '{}' succeeded for pipeline '{}'.", job_name, pipeline.name_any());
+            events::normal(
+                &ctx.recorder,
+                &*pipeline,
+                "StepSucceeded",
+                "Run",
+                format!("Step '{}' of stage '{}' succeeded (Job '{}').", node.step.name, node.stage_name, job_name),
+            )
+            .await;
+            step_status.phase = StepPhase::Succeeded;
+            step_status.completion_time = Some(Utc::now().to_rfc3339());
+            step_status.outputs = read_step_outputs(client, &ns, &job_name).await?;
+        } else if timed_out || job_status.failed.unwrap_or(0) > 0 {
+            eprintln!("Job '{}' failed for pipeline '{}'.", job_name, pipeline.name_any());
+            let outcome = match (&node.step.timeout, timed_out) {
+                (Some(timeout), true) => format!("timed out after {}", timeout),
+                _ => "failed".to_string(),
+            };
+            let message = format!(
+                "Step '{}' of stage '{}' {} (Job '{}', attempt {}).",
+                node.step.name, node.stage_name, outcome, job_name, step_status.attempts.max(1)
+            );
+            step_status.message = Some(message.clone());
+            let attempts = step_status.attempts.max(1);
+            if attempts <= node.step.retries {
+                let delay = pipeline_dag::retry_delay(node.step, attempts);
+                let note = format!("{} Retrying in {}s.", message, delay.as_secs());
+                events::warning(&ctx.recorder, &*pipeline, "StepRetrying", "Run", note).await;
+                step_status.phase = StepPhase::Pending;
+                step_status.next_attempt_time =
+                    Some((Utc::now() + chrono::Duration::seconds(delay.as_secs() as i64)).to_rfc3339());
+            } else {
+                let reason = if timed_out { "StepTimedOut" } else { "JobFailed" };
+                events::warning(&ctx.recorder, &*pipeline, reason, "Run", message).await;
+                step_status.phase = StepPhase::Failed;
+                step_status.completion_time = Some(Utc::now().to_rfc3339());
+            }
+        }
     }
 
-    // --- 2. Identify current step and construct Job name ---
-    let current_stage = &spec.stages[stage_index];
-    let current_step = &current_stage.steps[step_index];
-    let job_name = format!(
-        "{}-s{}-{}",
-        pipeline.name_any(),
-        stage_index,
-        current_step.name.replace('_', "-")
-    );
-
-    // --- 3. Find or create the Job for the current step ---
-    let jobs: Api<Job> = Api::namespaced(client.clone(), &ns);
-    match jobs.get(&job_name).await {
-        // Job exists, let's check its status.
-        Ok(job) => {
-            if let Some(job_status) = job.status {
-                if job_status.succeeded.unwrap_or(0) > 0 {
-                    // --- Job Succeeded: Advance to next step/stage ---
-                    println!("Job '{}' succeeded for pipeline '{}'.", job_name, pipeline.name_any());
-                    if step_index + 1 >= current_stage.steps.len() {
-                        status.current_stage_index = Some(stage_index + 1);
-                        status.current_step_index = Some(0);
-                    } else {
-                        status.current_step_index = Some(step_index + 1);
+    // --- 3. Cancellation kills the running steps and skips the pending ones ---
+    let cancelled = pipeline.annotations().get(CANCEL_ANNOTATION).map(String::as_str) == Some("true");
+    if cancelled {
+        let mut stopped = Vec::new();
+        for (node, step_status) in dag.iter().zip(status.steps.iter_mut()) {
+            if node.finally {
+                continue;
+            }
+            match step_status.phase {
+                StepPhase::Running => {
+                    if let Some(job_name) = &step_status.job_name {
+                        delete_job(&jobs, job_name).await?;
                     }
-                    update_status(pipeline, client.clone(), status).await?;
-                    Ok(Action::requeue(Duration::from_millis(100))) // Requeue immediately for next step.
+                    step_status.phase = StepPhase::Cancelled;
+                    step_status.completion_time = Some(Utc::now().to_rfc3339());
+                    step_status.message = Some("Cancelled while running.".to_string());
+                    stopped.push(node.step.name.as_str());
+                }
+                StepPhase::Pending => {
+                    step_status.phase = StepPhase::Skipped;
+                    step_status.message = Some("Skipped because the pipeline was cancelled.".to_string());
+                }
+                _ => {}
+            }
+        }
+        if !stopped.is_empty() {
+            println!("Pipeline '{}' cancelled; stopped steps: {}.", pipeline.name_any(), stopped.join(", "));
+            events::normal(
+                &ctx.recorder,
+                &*pipeline,
+                "PipelineCancelled",
+                "Cancel",
+                format!("Cancelled through '{}'; stopped steps: {}.", CANCEL_ANNOTATION, stopped.join(", ")),
+            )
+            .await;
+        }
+    }
 
-                } else if job_status.failed.unwrap_or(0) > 0 {
-                    // --- Job Failed: Terminate the pipeline ---
-                    eprintln!("Job '{}' failed for pipeline '{}'.", job_name, pipeline.name_any());
-                    status.phase = Some(PipelinePhase::Failed);
-                    status.completion_time = Some(Utc::now().to_rfc3339());
-                    update_status(pipeline, client.clone(), status).await?;
-                    Ok(Action::await_change())
+    // --- 4. A failed step skips the steps that have not started yet, except
+    // those whose conditions check the status of other steps and may still
+    // start ---
+    let failure = dag
+        .iter()
+        .zip(&status.steps)
+        .find(|(node, step)| !node.finally && step.phase == StepPhase::Failed && !node.step.continue_on_error)
+        .map(|(_, step)| (step.name.clone(), step.message.clone()));
+    if let Some((failed, _)) = &failure {
+        let skipped = format!("Skipped because step '{}' failed.", failed);
+        let phases: Vec<StepPhase> = status.steps.iter().map(|step| step.phase).collect();
+        for (index, (node, step)) in dag.iter().zip(status.steps.iter_mut()).enumerate() {
+            let waits = !node.watches.is_empty() && !pipeline_dag::is_blocked(&dag, &phases, index);
+            if !node.finally && !waits && step.phase == StepPhase::Pending {
+                step.phase = StepPhase::Skipped;
+                step.message = Some(skipped.clone());
+            }
+        }
+    }
+    // What the `finally` steps are told about the rest of the pipeline.
+    let outcome = if cancelled {
+        PipelinePhase::Cancelled
+    } else if failure.is_some() {
+        PipelinePhase::Failed
+    } else {
+        PipelinePhase::Succeeded
+    };
 
-                } else {
-                    // --- Job still running: Check again later ---
-                    println!("Job '{}' is still running for pipeline '{}'.", job_name, pipeline.name_any());
-                    Ok(Action::requeue(Duration::from_secs(15)))
+    // --- 5. Check for pipeline completion ---
+    if status.steps.iter().all(|step| pipeline_dag::is_finished(step.phase)) {
+        // A failed `finally` step fails the pipeline as well.
+        let failure = failure.or_else(|| {
+            dag.iter()
+                .zip(&status.steps)
+                .find(|(node, step)| step.phase == StepPhase::Failed && !node.step.continue_on_error)
+                .map(|(_, step)| (step.name.clone(), step.message.clone()))
+        });
+        status.completion_time = Some(Utc::now().to_rfc3339());
+        if cancelled {
+            println!("Pipeline '{}' was cancelled.", pipeline.name_any());
+            status.phase = Some(PipelinePhase::Cancelled);
+            set_state(&pipeline, &mut status, ResourceState::Degraded, "Cancelled", "The pipeline was cancelled");
+        } else if let Some((failed, message)) = failure {
+            let message = message.unwrap_or_else(|| format!("Step '{}' failed.", failed));
+            status.phase = Some(PipelinePhase::Failed);
+            set_state(&pipeline, &mut status, ResourceState::Degraded, "JobFailed", &message);
+        } else {
+            println!("Pipeline '{}' completed successfully.", pipeline.name_any());
+            status.phase = Some(PipelinePhase::Succeeded);
+            set_state(&pipeline, &mut status, ResourceState::Ready, "Succeeded", "All stages completed successfully");
+            events::normal(&ctx.recorder, &*pipeline, "PipelineSucceeded", "Run", "All stages completed successfully.").await;
+        }
+        update_status(pipeline.clone(), client.clone(), status).await?;
+        return Ok(Action::await_change());
+    }
+
+    // --- 6. Start every step whose dependencies succeeded ---
+    let now = Utc::now();
+    let retry_at = |step: &PipelineStepStatus| {
+        step.next_attempt_time
+            .as_deref()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc))
+            .filter(|&time| step.phase == StepPhase::Pending && time > now)
+    };
+    // A step waiting for its next attempt keeps its parallel slot.
+    let phases: Vec<StepPhase> = status
+        .steps
+        .iter()
+        .map(|step| if retry_at(step).is_some() { StepPhase::Running } else { step.phase })
+        .collect();
+    let max_parallel = spec.max_parallel.unwrap_or(DEFAULT_MAX_PARALLEL);
+    let ready = pipeline_dag::ready_steps(&dag, &phases, max_parallel);
+    if !ready.is_empty() {
+        ensure_workspace_claim(&pipeline, client, &ns).await?;
+    }
+    let run_id = pipeline.uid().unwrap_or_default();
+    let workspace = RunWorkspace::new(spec, &ns, &pipeline.name_any(), &run_id);
+    let cache_store = CacheStore::new(spec, &ns);
+    let outputs: HashMap<String, BTreeMap<String, String>> = status
+        .steps
+        .iter()
+        .map(|step| (step.name.clone(), step.outputs.clone()))
+        .collect();
+    let when_context = WhenContext {
+        phases: dag.iter().zip(&status.steps).map(|(node, step)| (node.step.name.as_str(), step.phase)).collect(),
+        groups: &expansion.groups,
+        outputs: &outputs,
+        changed_paths: spec.changed_paths.as_deref(),
+    };
+    let mut requeue_now = false;
+    for index in ready {
+        let node = &dag[index];
+        // A step whose conditions do not hold is skipped instead of run.
+        match pipeline_when::evaluate(node.step, &when_context) {
+            Ok(None) => {}
+            Ok(Some(reason)) => {
+                println!("Step '{}' of pipeline '{}' is skipped.", node.step.name, pipeline.name_any());
+                events::normal(&ctx.recorder, &*pipeline, "StepSkipped", "Run", reason.clone()).await;
+                let step_status = &mut status.steps[index];
+                step_status.phase = StepPhase::Skipped;
+                step_status.message = Some(reason);
+                requeue_now = true;
+                continue;
+            }
+            Err(e) => {
+                let message = e.to_string();
+                events::warning(&ctx.recorder, &*pipeline, "JobFailed", "Run", message.clone()).await;
+                let step_status = &mut status.steps[index];
+                step_status.phase = StepPhase::Failed;
+                step_status.completion_time = Some(Utc::now().to_rfc3339());
+                step_status.message = Some(message);
+                requeue_now = true;
+                continue;
+            }
+        }
+        // A step with a cache is looked up before its first attempt.
+        let lookup = match &cache_store {
+            Some(store) if node.step.cache.is_some() && status.steps[index].cache.is_none() => Some(store),
+            _ => None,
+        };
+        if let Some(store) = lookup {
+            let job_name = format!("{}-cache", step_job_name(&pipeline, node, 1));
+            let job_def = match create_cache_lookup_job(&pipeline, &job_name, node, &workspace, store, &outputs) {
+                Ok(job_def) => job_def,
+                Err(Error::InvalidStep(message)) => {
+                    events::warning(&ctx.recorder, &*pipeline, "JobFailed", "Run", message.clone()).await;
+                    let step_status = &mut status.steps[index];
+                    step_status.phase = StepPhase::Failed;
+                    step_status.completion_time = Some(Utc::now().to_rfc3339());
+                    step_status.message = Some(message);
+                    requeue_now = true;
+                    continue;
                 }
-            } else {
-                // Job status not yet available.
-                Ok(Action::requeue(Duration::from_secs(5)))
+                Err(e) => return Err(e),
+            };
+            println!("Creating cache lookup Job '{}' for pipeline '{}'", job_name, pipeline.name_any());
+            match jobs.create(&PostParams::default(), &job_def).await {
+                Ok(_) => {}
+                Err(KubeError::Api(e)) if e.code == 409 => {}
+                Err(e) => return Err(e.into()),
             }
+            let step_status = &mut status.steps[index];
+            step_status.phase = StepPhase::Running;
+            step_status.job_name = Some(job_name);
+            step_status.start_time = Some(Utc::now().to_rfc3339());
+            step_status.cache = Some(StepCacheStatus { result: CacheResult::Checking, key: None });
+            continue;
         }
-        // Job does not exist, create it.
-        Err(KubeError::Api(e)) if e.code == 404 => {
-            println!("Creating Job '{}' for pipeline '{}'", job_name, pipeline.name_any());
-            let job_def = create_job_for_step(&pipeline, &job_name, current_step)?;
-            jobs.create(&PostParams::default(), &job_def).await?;
-            Ok(Action::requeue(Duration::from_secs(10)))
+        let attempt = status.steps[index].attempts + 1;
+        let job_name = step_job_name(&pipeline, node, attempt);
+        let needs: Vec<&str> = node.needs.iter().map(|&need| dag[need].step.name.as_str()).collect();
+        // On a miss, the results are saved under the key the lookup computed.
+        let save = cache_store
+            .as_ref()
+            .zip(status.steps[index].cache.as_ref().and_then(|cache| cache.key.as_deref()));
+        println!("Creating Job '{}' for pipeline '{}'", job_name, pipeline.name_any());
+        let job_def = match create_job_for_step(&pipeline, &job_name, node, &needs, &workspace, save, &outputs, &outcome) {
+            Ok(job_def) => job_def,
+            Err(Error::InvalidStep(message)) => {
+                // The step can never run; fail it like a failed Job.
+                events::warning(&ctx.recorder, &*pipeline, "JobFailed", "Run", message.clone()).await;
+                let step_status = &mut status.steps[index];
+                step_status.phase = StepPhase::Failed;
+                step_status.completion_time = Some(Utc::now().to_rfc3339());
+                step_status.message = Some(message);
+                requeue_now = true;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        match jobs.create(&PostParams::default(), &job_def).await {
+            Ok(_) => {}
+            // The Job was created before a status update failed; keep watching it.
+            Err(KubeError::Api(e)) if e.code == 409 => {}
+            Err(e) => return Err(e.into()),
         }
-        // Another Kubernetes API error occurred.
-        Err(e) => Err(e.into()),
+        events::normal(
+            &ctx.recorder,
+            &*pipeline,
+            "JobCreated",
+            "Run",
+            format!("Created Job '{}' for step '{}' of stage '{}'.", job_name, node.step.name, node.stage_name),
+        )
+        .await;
+        let step_status = &mut status.steps[index];
+        step_status.phase = StepPhase::Running;
+        step_status.job_name = Some(job_name);
+        step_status.start_time = Some(Utc::now().to_rfc3339());
+        step_status.attempts = attempt;
+        step_status.next_attempt_time = None;
+        step_status.message = None;
+    }
+
+    let running: Vec<&str> = status
+        .steps
+        .iter()
+        .filter(|step| step.phase == StepPhase::Running)
+        .map(|step| step.name.as_str())
+        .collect();
+    let message = format!("Running steps: {}.", running.join(", "));
+    match (&failure, cancelled) {
+        (_, true) => set_state(&pipeline, &mut status, ResourceState::Degraded, "Cancelled", &message),
+        (Some((failed, step_message)), false) => {
+            let step_message = step_message.clone().unwrap_or_else(|| format!("Step '{}' failed.", failed));
+            set_state(&pipeline, &mut status, ResourceState::Degraded, "JobFailed", &step_message)
+        }
+        (None, false) => set_state(&pipeline, &mut status, ResourceState::Progressing, "Running", &message),
     }
+    // A step that could not be started or was skipped is handled on the next
+    // pass; a step waiting for a retry is started as soon as its backoff
+    // elapsed.
+    let requeue = if requeue_now {
+        Duration::from_millis(100)
+    } else {
+        status
+            .steps
+            .iter()
+            .filter_map(retry_at)
+            .filter_map(|time| (time - now).to_std().ok())
+            .fold(Duration::from_secs(10), Duration::min)
+    };
+    update_status(pipeline.clone(), client.clone(), status).await?;
+    Ok(Action::requeue(requeue))
 }
 
-/// Constructs a Kubernetes Job object for a specific pipeline step.
-fn create_job_for_step(pipeline: &phPipeline, job_name: &str, step: &crate::crds::PipelineStep) -> Result<Job, Error> {
+/// Constructs a Kubernetes Job object for a specific pipeline step. The
+/// step runs after the workspace was prepared and before its outputs are
+/// collected (see `pipeline_workspace`). References to the outputs of earlier
+/// steps are replaced with their values. A `finally` step is told the
+/// `outcome` of the other steps. With `save`, the results of the step are
+/// stored in the cache under the given key.
+#[allow(clippy::too_many_arguments)]
+fn create_job_for_step(
+    pipeline: &phPipeline,
+    job_name: &str,
+    node: &DagStep<'_>,
+    needs: &[&str],
+    workspace: &RunWorkspace<'_>,
+    save: Option<(&CacheStore<'_>, &str)>,
+    outputs: &HashMap<String, BTreeMap<String, String>>,
+    outcome: &PipelinePhase,
+) -> Result<Job, Error> {
+    let step = node.step;
+    let resolve = |text: &String| {
+        pipeline_workspace::substitute(text, outputs).map_err(|e| Error::InvalidStep(e.to_string()))
+    };
+    let command = step.command.iter().map(resolve).collect::<Result<Vec<_>, _>>()?;
+    let args = step.args.iter().map(resolve).collect::<Result<Vec<_>, _>>()?;
+    let mut env = workspace.step_env(&step.name);
+    for var in &step.env {
+        let Some(source) = &var.value_from else {
+            env.push(json!({ "name": var.name, "value": resolve(&var.value)? }));
+            continue;
+        };
+        let value_from = match (&source.secret_key_ref, &source.config_map_key_ref) {
+            (Some(secret), None) => json!({ "secretKeyRef": { "name": secret.name, "key": secret.key } }),
+            (None, Some(config_map)) => json!({ "configMapKeyRef": { "name": config_map.name, "key": config_map.key } }),
+            _ => {
+                return Err(Error::InvalidStep(format!(
+                    "env '{}' of step '{}' must set exactly one of 'secretKeyRef' and 'configMapKeyRef'",
+                    var.name, step.name
+                )))
+            }
+        };
+        env.push(json!({ "name": var.name, "valueFrom": value_from }));
+    }
+    if node.finally {
+        env.push(json!({ "name": "PH_PIPELINE_RESULT", "value": outcome }));
+    }
+    let timeout = step.timeout.as_deref().map(|timeout| {
+        parse_duration(timeout)
+            .map(|timeout| timeout.as_secs())
+            .ok_or_else(|| Error::InvalidStep(format!("step '{}' has an invalid timeout '{}'", step.name, timeout)))
+    });
+    let timeout = timeout.transpose()?;
+
     // Check for specialized step types
-    if let Some(step_type) = &step.step_type {
-        if step_type == "generate-sbom" {
-            println!("Step '{}' is of type 'generate-sbom'. Creating specialized Job.", step.name);
-            // This is a placeholder for a more complex logic that would find the image
-            // from a previous step. For now, we use a placeholder image to scan.
-            let image_to_scan = "nginx:latest";
-            let output_path = "/artifacts/sbom.json";
-
-            let job_json = json!({
-                "apiVersion": "batch/v1",
-                "kind": "Job",
-                "metadata": {
-                    "name": job_name,
-                    "ownerReferences": [{
-                        "apiVersion": "ph.io/v1alpha1",
-                        "kind": "phPipeline",
-                        "name": pipeline.name_any(),
-                        "uid": pipeline.uid().ok_or_else(|| KubeError::Request(http::Error::new("Missing UID")))?,
-                        "controller": true,
-                    }]
-                },
+    let container = if step.step_type.as_deref() == Some("generate-sbom") {
+        println!("Step '{}' is of type 'generate-sbom'. Creating specialized Job.", step.name);
+        // The image to scan is the first argument, usually the output of the
+        // step that built it, e.g. `$(steps.build.outputs.image)`.
+        let image_to_scan = args.first().ok_or_else(|| {
+            Error::InvalidStep(format!(
+                "step '{}' of type 'generate-sbom' needs the image to scan as its first argument",
+                step.name
+            ))
+        })?;
+        let output_path = format!("{}/sbom.json", pipeline_workspace::step_output_dir(&step.name));
+        json!({
+            "name": step.name.clone(),
+            "image": "anchore/syft:latest",
+            "command": ["syft", "packages", image_to_scan, "-o", "spdx-json", "--file", output_path],
+            "env": env,
+            "volumeMounts": [workspace.volume_mount()],
+        })
+    } else {
+        // Default behavior for generic steps
+        json!({
+            "name": step.name.clone(),
+            "image": step.image.clone(),
+            "command": command,
+            "args": args,
+            "env": env,
+            "volumeMounts": [workspace.volume_mount()],
+        })
+    };
+
+    let mut containers = vec![workspace.collect_container(step)];
+    let mut volumes = vec![workspace.volume()];
+    if let Some((store, key)) = save {
+        containers.push(store.save_container(step, key));
+        volumes.extend(store.volume());
+    }
+
+    let job_json = json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": {
+            "name": job_name,
+            "ownerReferences": [owner_reference(pipeline)?]
+        },
+        "spec": {
+            "template": {
                 "spec": {
-                    "template": {
-                        "spec": {
-                            "containers": [{
-                                "name": "sbom-generator",
-                                "image": "anchore/syft:latest",
-                                "command": ["syft", "packages", image_to_scan, "-o", "spdx-json", "--file", output_path],
-                                "volumeMounts": [{
-                                    "name": "artifacts",
-                                    "mountPath": "/artifacts"
-                                }]
-                            }],
-                            "volumes": [{
-                                "name": "artifacts",
-                                "emptyDir": {}
-                            }],
-                            "restartPolicy": "Never"
-                        }
-                    },
-                    "backoffLimit": 1
+                    "initContainers": [workspace.prepare_container(&step.name, needs), container],
+                    "containers": containers,
+                    "volumes": volumes,
+                    "restartPolicy": "Never"
                 }
-            });
-            return serde_json::from_value(job_json).map_err(|e| Error::KubeError(KubeError::SerdeError(e)));
+            },
+            "activeDeadlineSeconds": timeout,
+            "backoffLimit": 0 // Retries are run by the controller as new Jobs.
         }
-    }
+    });
 
-    // Default behavior for generic steps
+    serde_json::from_value(job_json).map_err(|e| Error::KubeError(KubeError::SerdeError(e)))
+}
+
+/// Constructs the Job that looks a cached step up: it hashes the step's
+/// inputs and restores a matching entry into the workspace.
+fn create_cache_lookup_job(
+    pipeline: &phPipeline,
+    job_name: &str,
+    node: &DagStep<'_>,
+    workspace: &RunWorkspace<'_>,
+    store: &CacheStore<'_>,
+    outputs: &HashMap<String, BTreeMap<String, String>>,
+) -> Result<Job, Error> {
+    let base_key =
+        pipeline_cache::base_key(node.step, outputs).map_err(|e| Error::InvalidStep(e.to_string()))?;
+    let mut volumes = vec![workspace.volume()];
+    volumes.extend(store.volume());
     let job_json = json!({
         "apiVersion": "batch/v1",
         "kind": "Job",
         "metadata": {
             "name": job_name,
-            "ownerReferences": [{
-                "apiVersion": "ph.io/v1alpha1",
-                "kind": "phPipeline",
-                "name": pipeline.name_any(),
-                "uid": pipeline.uid().ok_or_else(|| KubeError::Request(http::Error::new("Missing UID")))?,
-                "controller": true,
-            }]
+            "ownerReferences": [owner_reference(pipeline)?]
         },
         "spec": {
             "template": {
                 "spec": {
-                    "containers": [{
-                        "name": step.name.clone(),
-                        "image": step.image.clone(),
-                        "command": step.command,
-                        "args": step.args,
-                    }],
+                    "containers": [store.probe_container(node.step, &base_key)],
+                    "volumes": volumes,
                     "restartPolicy": "Never"
                 }
             },
-            "backoffLimit": 1 // Do not retry failed jobs within the pipeline.
+            "backoffLimit": 0
         }
     });
 
     serde_json::from_value(job_json).map_err(|e| Error::KubeError(KubeError::SerdeError(e)))
 }
 
+/// The reference that makes the pipeline own a Job or claim.
+fn owner_reference(pipeline: &phPipeline) -> Result<serde_json::Value, Error> {
+    Ok(json!({
+        "apiVersion": "ph.io/v1alpha1",
+        "kind": "phPipeline",
+        "name": pipeline.name_any(),
+        "uid": pipeline.uid().ok_or_else(|| KubeError::Request(http::Error::new("Missing UID")))?,
+        "controller": true,
+    }))
+}
+
+/// Deletes the Job of a cancelled step together with its pods.
+async fn delete_job(jobs: &Api<Job>, job_name: &str) -> Result<(), Error> {
+    match jobs.delete(job_name, &DeleteParams::background()).await {
+        Ok(_) => Ok(()),
+        Err(KubeError::Api(e)) if e.code == 404 => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reads the output values a finished step reported through the termination
+/// message of its collector container.
+async fn read_step_outputs(client: &Client, ns: &str, job_name: &str) -> Result<BTreeMap<String, String>, Error> {
+    let message = read_termination_message(client, ns, job_name, pipeline_workspace::COLLECT_CONTAINER).await?;
+    Ok(message.map(|m| pipeline_workspace::parse_results(&m)).unwrap_or_default())
+}
+
+/// Reads the termination message of `container` in the pod of a finished Job.
+async fn read_termination_message(
+    client: &Client,
+    ns: &str,
+    job_name: &str,
+    container: &str,
+) -> Result<Option<String>, Error> {
+    let pods: Api<Pod> = Api::namespaced(client.clone(), ns);
+    let pods = pods
+        .list(&ListParams::default().labels(&format!("job-name={}", job_name)))
+        .await?;
+    let message = pods
+        .items
+        .iter()
+        .filter_map(|pod| pod.status.as_ref()?.container_statuses.as_ref())
+        .flatten()
+        .filter(|status| status.name == container)
+        .find_map(|status| status.state.as_ref()?.terminated.as_ref()?.message.clone());
+    Ok(message)
+}
+
+/// Creates the claim of a volume workspace unless it already exists. The
+/// claim is owned by the pipeline, so it is deleted together with it.
+async fn ensure_workspace_claim(pipeline: &phPipeline, client: &Client, ns: &str) -> Result<(), Error> {
+    let Some(workspace) = pipeline.spec.as_ref().and_then(|spec| spec.workspace.as_ref()) else {
+        return Ok(());
+    };
+    let Some(mut manifest) = pipeline_workspace::claim_manifest(&pipeline.name_any(), workspace) else {
+        return Ok(());
+    };
+    manifest["metadata"]["ownerReferences"] = json!([owner_reference(pipeline)?]);
+    let claim: PersistentVolumeClaim =
+        serde_json::from_value(manifest).map_err(|e| Error::KubeError(KubeError::SerdeError(e)))?;
+    let claims: Api<PersistentVolumeClaim> = Api::namespaced(client.clone(), ns);
+    match claims.create(&PostParams::default(), &claim).await {
+        Ok(_) => Ok(()),
+        Err(KubeError::Api(e)) if e.code == 409 => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
 
 /// The "cleanup" branch of the reconciliation loop.
 async fn cleanup_pipeline(pipeline: Arc<phPipeline>, _ctx: Arc<Context>) -> Result<Action, Error> {
@@ -335,11 +897,13 @@ async fn update_status(pipeline: Arc<phPipeline>, client: Client, status: phPipe
 pub async fn on_error(pipeline: Arc<phPipeline>, error: &Error, ctx: Arc<Context>) -> Action {
     eprintln!("Reconciliation error for phPipeline '{}': {:?}", pipeline.name_any(), error);
 
-    let failed_status = phPipelineStatus {
+    events::warning(&ctx.recorder, &*pipeline, "ReconcileError", "Reconcile", error.to_string()).await;
+    let mut failed_status = phPipelineStatus {
         phase: Some(PipelinePhase::Failed),
         completion_time: Some(Utc::now().to_rfc3339()),
         ..pipeline.status.clone().unwrap_or_default()
     };
+    set_state(&pipeline, &mut failed_status, ResourceState::Degraded, "ReconcileError", &error.to_string());
 
     if let Err(e) = update_status(pipeline, ctx.client.clone(), failed_status).await {
         eprintln!("Failed to update status on error: {}", e);
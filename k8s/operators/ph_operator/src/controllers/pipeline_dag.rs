@@ -0,0 +1,458 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/pipeline_dag.rs
+*
+* This file turns the stages and steps of a phPipeline into a dependency graph
+* and decides which steps may start. It holds no Kubernetes logic, so the
+* scheduling rules can be tested on their own.
+*
+* Architecture:
+* - `build` flattens the stages into a list of `DagStep`s. A step depends on
+*   the steps named in its `needs` (or `runAfter`) field or, when it has none,
+*   on every step of the previous stage, and on the steps its `when`
+*   conditions refer to. The `finally` steps come last. The graph is
+*   validated: step names must be unique, dependencies must exist, there must
+*   be no cycle and durations must parse.
+* - `ready_steps` returns the pending steps whose dependencies all succeeded
+*   (or were skipped, or failed with `continueOnError`), limited by the number
+*   of free parallel slots. A dependency whose status a `when` condition
+*   checks only has to finish, whatever the outcome. `finally` steps wait
+*   until every other step finished.
+* - `is_blocked` tells which steps a failure keeps from ever starting.
+* - `retry_delay` computes the exponential backoff between attempts.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::controllers::pipeline_when;
+use crate::crds::{phPipelineSpec, PipelineStep, StepPhase};
+use pipeline_rules::duration::{self, parse_duration};
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+
+/// The number of steps that may run at the same time when the pipeline does
+/// not set `maxParallel`.
+pub const DEFAULT_MAX_PARALLEL: u32 = 4;
+/// The stage name reported for `finally` steps.
+pub const FINALLY_STAGE: &str = "finally";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DagError {
+    #[error("step name '{0}' is used more than once")]
+    DuplicateStep(String),
+    #[error("step '{step}' needs unknown step '{need}'")]
+    UnknownDependency { step: String, need: String },
+    #[error("steps form a dependency cycle: {0}")]
+    Cycle(String),
+    #[error("step '{step}' cannot need finally step '{need}'")]
+    NeedsFinallyStep { step: String, need: String },
+    #[error("step '{step}' has an invalid duration '{value}' (expected e.g. \"30s\", \"10m\" or \"1h\")")]
+    InvalidDuration { step: String, value: String },
+}
+
+/// A step of the pipeline together with the steps it waits for.
+#[derive(Debug)]
+pub struct DagStep<'a> {
+    pub stage_index: usize,
+    pub stage_name: &'a str,
+    pub step: &'a PipelineStep,
+    /// Positions (in the flattened step list) of the steps this one needs.
+    pub needs: Vec<usize>,
+    /// The needs whose status the `when` conditions check. They only have to
+    /// finish, in any phase.
+    pub watches: Vec<usize>,
+    /// Whether this is a `finally` step.
+    pub finally: bool,
+}
+
+/// Builds and validates the dependency graph of a pipeline. Steps keep the
+/// order in which they are declared, followed by the `finally` steps.
+/// `groups` lists the steps expanded from every matrix step; a `when`
+/// condition referring to a matrix step waits for all of them.
+pub fn build<'a>(
+    spec: &'a phPipelineSpec,
+    groups: &HashMap<String, Vec<String>>,
+) -> Result<Vec<DagStep<'a>>, DagError> {
+    let mut positions = HashMap::new();
+    let mut stage_members: Vec<Vec<usize>> = Vec::with_capacity(spec.stages.len());
+    let mut position = 0;
+    for stage in &spec.stages {
+        let mut members = Vec::with_capacity(stage.steps.len());
+        for step in &stage.steps {
+            if positions.insert(step.name.as_str(), position).is_some() {
+                return Err(DagError::DuplicateStep(step.name.clone()));
+            }
+            members.push(position);
+            position += 1;
+        }
+        stage_members.push(members);
+    }
+    let first_finally = position;
+    for step in &spec.finally {
+        if positions.insert(step.name.as_str(), position).is_some() {
+            return Err(DagError::DuplicateStep(step.name.clone()));
+        }
+        position += 1;
+    }
+
+    let position_of = |step: &PipelineStep, need: &str| {
+        positions.get(need).copied().ok_or_else(|| DagError::UnknownDependency {
+            step: step.name.clone(),
+            need: need.to_string(),
+        })
+    };
+    let resolve = |step: &PipelineStep| -> Result<Vec<usize>, DagError> {
+        step.needs.iter().map(|need| position_of(step, need)).collect()
+    };
+    // A matrix step stands for all the steps expanded from it.
+    let positions_of = |step: &PipelineStep, reference: &str| -> Result<Vec<usize>, DagError> {
+        match groups.get(reference) {
+            Some(members) => members.iter().map(|member| position_of(step, member)).collect(),
+            None => Ok(vec![position_of(step, reference)?]),
+        }
+    };
+    // The steps a `when` condition refers to have to finish first.
+    let add_references = |step: &PipelineStep, needs: &mut Vec<usize>| -> Result<(), DagError> {
+        for reference in pipeline_when::references(step) {
+            for position in positions_of(step, reference)? {
+                if !needs.contains(&position) {
+                    needs.push(position);
+                }
+            }
+        }
+        Ok(())
+    };
+    let watches = |step: &PipelineStep| -> Result<Vec<usize>, DagError> {
+        let mut watches = Vec::new();
+        for reference in pipeline_when::status_references(step) {
+            watches.extend(positions_of(step, reference)?);
+        }
+        Ok(watches)
+    };
+
+    let mut dag = Vec::with_capacity(position);
+    for (stage_index, stage) in spec.stages.iter().enumerate() {
+        for step in &stage.steps {
+            let mut needs = if step.needs.is_empty() {
+                stage_index
+                    .checked_sub(1)
+                    .map(|previous| stage_members[previous].clone())
+                    .unwrap_or_default()
+            } else {
+                resolve(step)?
+            };
+            add_references(step, &mut needs)?;
+            if let Some(&need) = needs.iter().find(|&&need| need >= first_finally) {
+                return Err(DagError::NeedsFinallyStep {
+                    step: step.name.clone(),
+                    need: spec.finally[need - first_finally].name.clone(),
+                });
+            }
+            dag.push(DagStep {
+                stage_index,
+                stage_name: &stage.name,
+                step,
+                needs,
+                watches: watches(step)?,
+                finally: false,
+            });
+        }
+    }
+    for step in &spec.finally {
+        let mut needs = resolve(step)?;
+        add_references(step, &mut needs)?;
+        dag.push(DagStep {
+            stage_index: spec.stages.len(),
+            stage_name: FINALLY_STAGE,
+            step,
+            needs,
+            watches: watches(step)?,
+            finally: true,
+        });
+    }
+
+    for node in &dag {
+        for value in node.step.timeout.iter().chain(&node.step.retry_backoff) {
+            parse_duration(value).ok_or_else(|| DagError::InvalidDuration {
+                step: node.step.name.clone(),
+                value: value.clone(),
+            })?;
+        }
+    }
+    check_acyclic(&dag)?;
+    Ok(dag)
+}
+
+/// Rejects graphs with a cycle, naming the steps that can never start.
+fn check_acyclic(dag: &[DagStep<'_>]) -> Result<(), DagError> {
+    let mut remaining: Vec<usize> = dag.iter().map(|step| step.needs.len()).collect();
+    let mut done = vec![false; dag.len()];
+    let mut progressed = true;
+    while progressed {
+        progressed = false;
+        for i in 0..dag.len() {
+            if !done[i] && remaining[i] == 0 {
+                done[i] = true;
+                progressed = true;
+                for (j, step) in dag.iter().enumerate() {
+                    remaining[j] -= step.needs.iter().filter(|&&need| need == i).count();
+                }
+            }
+        }
+    }
+
+    let blocked: Vec<&str> = dag
+        .iter()
+        .zip(&done)
+        .filter(|(_, done)| !**done)
+        .map(|(step, _)| step.step.name.as_str())
+        .collect();
+    if blocked.is_empty() {
+        Ok(())
+    } else {
+        Err(DagError::Cycle(blocked.join(", ")))
+    }
+}
+
+/// Whether a step in `phase` will not change any more.
+pub fn is_finished(phase: StepPhase) -> bool {
+    !matches!(phase, StepPhase::Pending | StepPhase::Running)
+}
+
+/// Returns the positions of the pending steps that can start now: all their
+/// dependencies succeeded or were skipped, and fewer than `max_parallel` steps
+/// are running.
+/// The dependencies a step watches, and all those of a `finally` step, only
+/// have to finish. A `finally` step also waits for the other steps to finish.
+/// `phases` holds the phase of every step of `dag`, in the same order.
+pub fn ready_steps(dag: &[DagStep<'_>], phases: &[StepPhase], max_parallel: u32) -> Vec<usize> {
+    let running = phases.iter().filter(|&&phase| phase == StepPhase::Running).count();
+    let free = (max_parallel.max(1) as usize).saturating_sub(running);
+    let satisfied = |step: &DagStep<'_>, need: usize| match phases[need] {
+        StepPhase::Succeeded | StepPhase::Skipped => true,
+        phase if step.watches.contains(&need) => is_finished(phase),
+        StepPhase::Failed => dag[need].step.continue_on_error,
+        _ => false,
+    };
+    let others_finished = dag
+        .iter()
+        .zip(phases)
+        .all(|(step, &phase)| step.finally || is_finished(phase));
+    dag.iter()
+        .enumerate()
+        .filter(|(i, step)| {
+            phases[*i] == StepPhase::Pending
+                && if step.finally {
+                    others_finished && step.needs.iter().all(|&need| is_finished(phases[need]))
+                } else {
+                    step.needs.iter().all(|&need| satisfied(step, need))
+                }
+        })
+        .map(|(i, _)| i)
+        .take(free)
+        .collect()
+}
+
+/// Whether step `index` can never start: a dependency it does not watch
+/// failed.
+pub fn is_blocked(dag: &[DagStep<'_>], phases: &[StepPhase], index: usize) -> bool {
+    let step = &dag[index];
+    step.needs.iter().any(|&need| {
+        !step.watches.contains(&need)
+            && matches!(phases[need], StepPhase::Failed | StepPhase::Cancelled)
+            && !dag[need].step.continue_on_error
+    })
+}
+
+/// The wait before attempt `attempt + 1` of a step, after `attempt` failed
+/// ones (see `pipeline_rules::duration::retry_delay`).
+pub fn retry_delay(step: &PipelineStep, attempt: u32) -> Duration {
+    duration::retry_delay(step.retry_backoff.as_deref(), attempt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crds::PipelineStage;
+
+    fn step(name: &str, needs: &[&str]) -> PipelineStep {
+        PipelineStep {
+            name: name.to_string(),
+            step_type: None,
+            image: "alpine".to_string(),
+            command: Vec::new(),
+            args: Vec::new(),
+            env: Vec::new(),
+            outputs: None,
+            needs: needs.iter().map(|need| need.to_string()).collect(),
+            timeout: None,
+            retries: 0,
+            retry_backoff: None,
+            continue_on_error: false,
+            cache: None,
+            matrix: None,
+            when: Vec::new(),
+        }
+    }
+
+    fn spec(stages: Vec<(&str, Vec<PipelineStep>)>) -> phPipelineSpec {
+        phPipelineSpec {
+            stages: stages
+                .into_iter()
+                .map(|(name, steps)| PipelineStage {
+                    name: name.to_string(),
+                    steps,
+                })
+                .collect(),
+            max_parallel: None,
+            workspace: None,
+            finally: Vec::new(),
+            cache: None,
+            changed_paths: None,
+        }
+    }
+
+    #[test]
+    fn test_build_dependencies() {
+        let spec = spec(vec![
+            ("check", vec![step("lint", &[]), step("unit-test", &[])]),
+            ("build", vec![step("image", &[]), step("sbom", &["image"])]),
+            ("deploy", vec![step("deploy", &[]), step("notify", &["lint"])]),
+        ]);
+        let dag = build(&spec, &HashMap::new()).unwrap();
+        let needs: Vec<&[usize]> = dag.iter().map(|step| step.needs.as_slice()).collect();
+        assert_eq!(needs, vec![&[][..], &[], &[0, 1], &[2], &[2, 3], &[0]]);
+        assert_eq!(dag[3].stage_name, "build");
+    }
+
+    #[test]
+    fn test_build_rejects_invalid_graphs() {
+        let duplicate = spec(vec![("a", vec![step("x", &[])]), ("b", vec![step("x", &[])])]);
+        assert_eq!(build(&duplicate, &HashMap::new()).unwrap_err(), DagError::DuplicateStep("x".to_string()));
+
+        let unknown = spec(vec![("a", vec![step("x", &["missing"])])]);
+        assert!(matches!(build(&unknown, &HashMap::new()), Err(DagError::UnknownDependency { .. })));
+
+        let cycle = spec(vec![("a", vec![step("x", &["y"]), step("y", &["x"]), step("z", &[])])]);
+        assert_eq!(build(&cycle, &HashMap::new()).unwrap_err(), DagError::Cycle("x, y".to_string()));
+    }
+
+    #[test]
+    fn test_ready_steps() {
+        let spec = spec(vec![
+            ("check", vec![step("lint", &[]), step("unit-test", &[]), step("image", &[])]),
+            ("publish", vec![step("push", &["image"])]),
+        ]);
+        let dag = build(&spec, &HashMap::new()).unwrap();
+        use StepPhase::*;
+
+        // Independent steps start together, up to the parallel limit.
+        assert_eq!(ready_steps(&dag, &[Pending, Pending, Pending, Pending], 4), vec![0, 1, 2]);
+        assert_eq!(ready_steps(&dag, &[Pending, Pending, Pending, Pending], 2), vec![0, 1]);
+        assert_eq!(ready_steps(&dag, &[Running, Running, Pending, Pending], 2), Vec::<usize>::new());
+
+        // A step starts as soon as the steps it needs succeeded.
+        assert_eq!(ready_steps(&dag, &[Running, Running, Succeeded, Pending], 4), vec![3]);
+        assert_eq!(ready_steps(&dag, &[Running, Failed, Running, Pending], 4), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_continue_on_error_and_finally() {
+        let mut flaky = step("integration", &[]);
+        flaky.continue_on_error = true;
+        let mut spec = spec(vec![
+            ("test", vec![flaky, step("unit", &[])]),
+            ("report", vec![step("report", &[])]),
+        ]);
+        spec.finally = vec![step("cleanup", &[]), step("notify", &["cleanup", "report"])];
+        let dag = build(&spec, &HashMap::new()).unwrap();
+        assert_eq!(dag[3].stage_name, FINALLY_STAGE);
+        assert_eq!(dag[4].needs, vec![3, 2]);
+        use StepPhase::*;
+
+        // A step that may fail does not block the steps that need it.
+        assert_eq!(ready_steps(&dag, &[Failed, Succeeded, Pending, Pending, Pending], 4), vec![2]);
+        // Finally steps run once everything else finished, even after a failure.
+        assert_eq!(ready_steps(&dag, &[Succeeded, Failed, Running, Pending, Pending], 4), Vec::<usize>::new());
+        assert_eq!(ready_steps(&dag, &[Succeeded, Failed, Skipped, Pending, Pending], 4), vec![3]);
+        assert_eq!(ready_steps(&dag, &[Succeeded, Failed, Skipped, Failed, Pending], 4), vec![4]);
+
+        let mut invalid = spec.clone();
+        invalid.stages[1].steps[0].needs = vec!["cleanup".to_string()];
+        assert!(matches!(build(&invalid, &HashMap::new()), Err(DagError::NeedsFinallyStep { .. })));
+    }
+
+    #[test]
+    fn test_when_references_and_skipped_steps() {
+        let mut deploy = step("deploy", &["build"]);
+        deploy.when = vec![crate::crds::WhenCondition {
+            input: Some("$(steps.plan.outputs.changes)".to_string()),
+            values: vec!["true".to_string()],
+            ..Default::default()
+        }];
+        let spec = spec(vec![("all", vec![step("plan", &[]), step("build", &[]), deploy])]);
+        let dag = build(&spec, &HashMap::new()).unwrap();
+        assert_eq!(dag[2].needs, vec![1, 0]);
+        use StepPhase::*;
+
+        // A skipped step does not block the steps that need it.
+        assert_eq!(ready_steps(&dag, &[Succeeded, Skipped, Pending], 4), vec![2]);
+        assert_eq!(ready_steps(&dag, &[Running, Succeeded, Pending], 4), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_status_conditions() {
+        let on_failure = |mut step: PipelineStep| {
+            step.when = vec![crate::crds::WhenCondition {
+                input: Some("$(steps.build.status)".to_string()),
+                values: vec!["Failed".to_string()],
+                ..Default::default()
+            }];
+            step
+        };
+        let spec = spec(vec![
+            ("build", vec![step("build", &[]), step("lint", &[])]),
+            ("after", vec![step("package", &["build"]), on_failure(step("rollback", &["build"])), on_failure(step("report", &["lint"]))]),
+        ]);
+        let dag = build(&spec, &HashMap::new()).unwrap();
+        assert_eq!(dag[3].watches, vec![0]);
+        assert_eq!(dag[4].needs, vec![1, 0]);
+        use StepPhase::*;
+
+        // A step checking the status of a failed dependency still starts...
+        let phases = [Failed, Succeeded, Pending, Pending, Pending];
+        assert_eq!(ready_steps(&dag, &phases, 4), vec![3, 4]);
+        assert!(is_blocked(&dag, &phases, 2));
+        assert!(!is_blocked(&dag, &phases, 3));
+        // ...and runs, since its condition holds.
+        let outputs = HashMap::new();
+        let groups = HashMap::new();
+        let context = pipeline_when::WhenContext {
+            phases: dag.iter().zip(phases).map(|(node, phase)| (node.step.name.as_str(), phase)).collect(),
+            groups: &groups,
+            outputs: &outputs,
+            changed_paths: None,
+        };
+        assert_eq!(pipeline_when::evaluate(dag[3].step, &context), Ok(None));
+
+        // It waits until the dependency finished, and an unwatched failure
+        // still blocks it.
+        assert_eq!(ready_steps(&dag, &[Running, Succeeded, Pending, Pending, Pending], 4), Vec::<usize>::new());
+        assert!(is_blocked(&dag, &[Succeeded, Failed, Pending, Pending, Pending], 4));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let mut flaky = step("flaky", &[]);
+        assert_eq!(retry_delay(&flaky, 1), Duration::from_secs(10));
+        assert_eq!(retry_delay(&flaky, 3), Duration::from_secs(40));
+        flaky.retry_backoff = Some("5m".to_string());
+        assert_eq!(retry_delay(&flaky, 2), duration::MAX_RETRY_BACKOFF);
+
+        flaky.timeout = Some("forever".to_string());
+        let invalid = spec(vec![("test", vec![flaky])]);
+        assert!(matches!(build(&invalid, &HashMap::new()), Err(DagError::InvalidDuration { .. })));
+    }
+}
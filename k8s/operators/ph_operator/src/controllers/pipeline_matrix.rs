@@ -0,0 +1,166 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/pipeline_matrix.rs
+*
+* This file expands the `matrix` of phPipeline steps into one step per
+* combination of the matrix parameters. It holds no Kubernetes logic, so the
+* expansion rules can be tested on their own.
+*
+* Architecture:
+* - The combinations of a matrix, the names of their steps and the
+*   substitution of `$(matrix.<name>)` follow `pipeline_rules::matrix`, which
+*   `ph ci run` uses as well.
+* - `expand` replaces every matrix step with its combinations before the
+*   dependency graph is built. Each combination is a copy of the step named
+*   after its values, with `$(matrix.<name>)` replaced everywhere. A step that
+*   needs the matrix step needs all of its combinations instead, and `when`
+*   conditions referring to the matrix step see the combinations as one step
+*   (see `Expansion::groups`).
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::crds::phPipelineSpec;
+use pipeline_rules::matrix::{self, MatrixError};
+use std::collections::{BTreeMap, HashMap};
+
+/// A pipeline with its matrix steps expanded.
+pub struct Expansion {
+    pub spec: phPipelineSpec,
+    /// The combination of every step created from a matrix, by step name.
+    pub combinations: HashMap<String, BTreeMap<String, String>>,
+    /// The steps created from every matrix step, by the matrix step's name.
+    pub groups: HashMap<String, Vec<String>>,
+}
+
+/// Replaces the matrix steps of `spec` with one step per combination.
+pub fn expand(spec: &phPipelineSpec) -> Result<Expansion, MatrixError> {
+    let mut expansion = Expansion {
+        spec: spec.clone(),
+        combinations: HashMap::new(),
+        groups: HashMap::new(),
+    };
+    let stages = expansion.spec.stages.iter_mut().map(|stage| &mut stage.steps);
+    for steps in stages.chain(std::iter::once(&mut expansion.spec.finally)) {
+        let mut result = Vec::with_capacity(steps.len());
+        for step in steps.drain(..) {
+            let Some(matrix) = &step.matrix else {
+                result.push(step);
+                continue;
+            };
+            let mut template = step.clone();
+            template.matrix = None;
+            let instances = matrix::instances(&step.name, matrix)?;
+            let mut names = Vec::with_capacity(instances.len());
+            for (name, combination) in instances {
+                let mut instance = matrix::instantiate(&template, &step.name, &combination)?;
+                instance.name = name;
+                names.push(instance.name.clone());
+                expansion.combinations.insert(instance.name.clone(), combination);
+                result.push(instance);
+            }
+            expansion.groups.insert(step.name.clone(), names);
+        }
+        *steps = result;
+    }
+
+    if !expansion.groups.is_empty() {
+        let stages = expansion.spec.stages.iter_mut().flat_map(|stage| &mut stage.steps);
+        for step in stages.chain(&mut expansion.spec.finally) {
+            step.needs = step
+                .needs
+                .iter()
+                .flat_map(|need| expansion.groups.get(need).cloned().unwrap_or_else(|| vec![need.clone()]))
+                .collect();
+        }
+    }
+    Ok(expansion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controllers::{pipeline_dag, pipeline_when};
+    use crate::crds::StepPhase;
+
+    fn combination(values: &[(&str, &str)]) -> BTreeMap<String, String> {
+        values.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_expand() {
+        let spec: phPipelineSpec = serde_json::from_value(serde_json::json!({
+            "stages": [
+                { "name": "test", "steps": [{
+                    "name": "test",
+                    "image": "rust:$(matrix.toolchain)",
+                    "command": ["cargo", "test", "--features", "$(matrix.features)"],
+                    "matrix": { "params": { "toolchain": ["1.79", "nightly"], "features": ["default"] } }
+                }] },
+                { "name": "report", "steps": [{ "name": "report", "image": "alpine", "needs": ["test"] }] }
+            ]
+        }))
+        .unwrap();
+        let expansion = expand(&spec).unwrap();
+        let steps = &expansion.spec.stages[0].steps;
+        let names: Vec<&str> = steps.iter().map(|step| step.name.as_str()).collect();
+        assert_eq!(names, vec!["test-default-1-79", "test-default-nightly"]);
+        assert_eq!(steps[1].image, "rust:nightly");
+        assert_eq!(steps[1].command[3], "default");
+        assert!(steps[1].matrix.is_none());
+        assert_eq!(
+            expansion.combinations["test-default-1-79"],
+            combination(&[("features", "default"), ("toolchain", "1.79")])
+        );
+        assert_eq!(expansion.spec.stages[1].steps[0].needs, names);
+
+        let mut unknown = spec.clone();
+        unknown.stages[0].steps[0].args = vec!["$(matrix.os)".to_string()];
+        assert!(matches!(expand(&unknown), Err(MatrixError::UnresolvedReference { .. })));
+    }
+
+    #[test]
+    fn test_when_on_matrix_step() {
+        let spec: phPipelineSpec = serde_json::from_value(serde_json::json!({
+            "stages": [{ "name": "build", "steps": [
+                {
+                    "name": "build",
+                    "image": "golang",
+                    "matrix": { "params": { "arch": ["amd64", "arm64"] } }
+                },
+                {
+                    "name": "notify",
+                    "image": "alpine",
+                    "when": [{ "input": "$(steps.build.status)", "operator": "in", "values": ["Failed"] }]
+                }
+            ] }]
+        }))
+        .unwrap();
+        let expansion = expand(&spec).unwrap();
+        assert_eq!(expansion.groups["build"], vec!["build-amd64", "build-arm64"]);
+        // The condition waits for every combination instead of failing on an unknown step.
+        let dag = pipeline_dag::build(&expansion.spec, &expansion.groups).unwrap();
+        assert_eq!(dag[2].step.name, "notify");
+        assert_eq!(dag[2].needs, vec![0, 1]);
+        assert_eq!(dag[2].watches, vec![0, 1]);
+
+        // Once a combination failed and the other one finished, the step runs.
+        use StepPhase::*;
+        let phases = [Failed, Succeeded, Pending];
+        assert_eq!(pipeline_dag::ready_steps(&dag, &phases, 4), vec![2]);
+        assert_eq!(pipeline_dag::ready_steps(&dag, &[Failed, Running, Pending], 4), Vec::<usize>::new());
+        let outputs = HashMap::new();
+        let context = pipeline_when::WhenContext {
+            phases: dag.iter().zip(phases).map(|(node, phase)| (node.step.name.as_str(), phase)).collect(),
+            groups: &expansion.groups,
+            outputs: &outputs,
+            changed_paths: None,
+        };
+        assert_eq!(pipeline_when::evaluate(dag[2].step, &context), Ok(None));
+        assert!(matches!(
+            pipeline_dag::build(&expansion.spec, &HashMap::new()),
+            Err(pipeline_dag::DagError::UnknownDependency { .. })
+        ));
+    }
+}
@@ -0,0 +1,320 @@
+/*
+ * Copyright (C) 2025 Pedro Henrique / phkaiser13
+ *
+ * File: pipeline_run_controller.rs
+ *
+ * This file implements the reconciliation logic for the phPipelineRun custom
+ * resource. A run is one execution of a phPipelineTemplate: the controller
+ * renders the template with the run's parameter values into a phPipeline and
+ * lets the pipeline controller execute it.
+ *
+ * Architecture:
+ * - Start: the template is looked up in the run's namespace, its parameters
+ * are resolved and the pipeline is rendered (see `pipeline_template`). The
+ * resulting phPipeline has the name of the run and is owned by it, so it is
+ * deleted together with the run.
+ * - Monitoring: the run watches the pipeline it owns and mirrors its phase,
+ * steps and conditions into its own status. The run also records the
+ * parameter values and the template generation it used, so it still
+ * documents the execution after the template changed.
+ * - Cancellation: the `ph.io/cancel` annotation on a run is passed on to its
+ * pipeline.
+ * - Retention: when a run finishes, the oldest finished runs of the same
+ * template beyond its `runHistoryLimit` are deleted.
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use chrono::Utc;
+use kube::{
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams, PostParams, ResourceExt},
+    client::Client,
+    runtime::{controller::Action, events::Recorder},
+    Error as KubeError,
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::controllers::conditions::{self, ResourceState};
+use crate::controllers::events;
+use crate::controllers::pipeline_template;
+use crate::crds::{phPipeline, phPipelineRun, phPipelineRunStatus, phPipelineTemplate, PipelinePhase, StatusCondition};
+
+/// Cancels a run; the annotation is copied to the run's pipeline.
+const CANCEL_ANNOTATION: &str = "ph.io/cancel";
+/// Labels of the pipelines created for runs.
+const RUN_LABEL: &str = "ph.io/pipeline-run";
+const TEMPLATE_LABEL: &str = "ph.io/pipeline-template";
+const DEFAULT_RUN_HISTORY_LIMIT: u32 = 10;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Kubernetes API error: {0}")]
+    KubeError(#[from] KubeError),
+
+    #[error("phPipelineRun has no namespace")]
+    MissingNamespace,
+
+    #[error("Failed to update resource status: {0}")]
+    StatusUpdateError(String),
+
+    #[error("Failed to build the pipeline: {0}")]
+    SerializationError(#[from] serde_json::Error),
+}
+
+/// The context required by the reconciler.
+pub struct Context {
+    pub client: Client,
+    /// Publishes Kubernetes Events about pipeline runs.
+    pub recorder: Recorder,
+}
+
+fn is_finished(phase: &Option<PipelinePhase>) -> bool {
+    matches!(
+        phase,
+        Some(PipelinePhase::Succeeded) | Some(PipelinePhase::Failed) | Some(PipelinePhase::Cancelled)
+    )
+}
+
+/// Sets the standard conditions of the run status to `state`.
+fn set_state(run: &phPipelineRun, status: &mut phPipelineRunStatus, state: ResourceState, reason: &str, message: &str) {
+    conditions::set_state(&mut status.conditions, state, reason, message, run.metadata.generation, Utc::now());
+}
+
+/// Main reconciliation function for the phPipelineRun resource.
+pub async fn reconcile(run: Arc<phPipelineRun>, ctx: Arc<Context>) -> Result<Action, Error> {
+    let ns = run.namespace().ok_or(Error::MissingNamespace)?;
+    let status = run.status.clone().unwrap_or_default();
+    if is_finished(&status.phase) {
+        return Ok(Action::await_change());
+    }
+    match status.pipeline_name.clone() {
+        None => start_run(&run, &ctx, &ns, status).await,
+        Some(pipeline_name) => monitor_run(&run, &ctx, &ns, &pipeline_name, status).await,
+    }
+}
+
+/// Renders the template of a new run and creates its pipeline.
+async fn start_run(
+    run: &phPipelineRun,
+    ctx: &Context,
+    ns: &str,
+    mut status: phPipelineRunStatus,
+) -> Result<Action, Error> {
+    let run_name = run.name_any();
+    let template_name = &run.spec.template_ref;
+    let templates: Api<phPipelineTemplate> = Api::namespaced(ctx.client.clone(), ns);
+
+    let Some(template) = templates.get_opt(template_name).await? else {
+        // The template may still be on its way; keep waiting for it.
+        let message = format!("phPipelineTemplate '{}' was not found", template_name);
+        if conditions::current_state(&status.conditions) != Some(ResourceState::Degraded) {
+            events::warning(&ctx.recorder, run, "TemplateNotFound", "Start", message.clone()).await;
+        }
+        status.phase = Some(PipelinePhase::Pending);
+        set_state(run, &mut status, ResourceState::Degraded, "TemplateNotFound", &message);
+        update_status(run, &ctx.client, status).await?;
+        return Ok(Action::requeue(Duration::from_secs(30)));
+    };
+
+    let rendered = pipeline_template::resolve_params(&template.spec.params, &run.spec.params).and_then(|values| {
+        pipeline_template::instantiate(&template.spec, &values).map(|spec| (values, spec))
+    });
+    let (values, mut spec) = match rendered {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            let message = format!("Cannot run template '{}': {}", template_name, e);
+            eprintln!("Pipeline run '{}' is invalid: {}", run_name, e);
+            events::warning(&ctx.recorder, run, "InvalidParams", "Start", message.clone()).await;
+            status.phase = Some(PipelinePhase::Failed);
+            status.completion_time = Some(Utc::now().to_rfc3339());
+            set_state(run, &mut status, ResourceState::Degraded, "InvalidParams", &message);
+            update_status(run, &ctx.client, status).await?;
+            return Ok(Action::await_change());
+        }
+    };
+    if run.spec.changed_paths.is_some() {
+        spec.changed_paths = run.spec.changed_paths.clone();
+    }
+
+    let pipeline: phPipeline = serde_json::from_value(json!({
+        "apiVersion": "ph.io/v1alpha1",
+        "kind": "phPipeline",
+        "metadata": {
+            "name": run_name,
+            "labels": {
+                RUN_LABEL: run_name,
+                TEMPLATE_LABEL: template_name,
+            },
+            "ownerReferences": [{
+                "apiVersion": "ph.io/v1alpha1",
+                "kind": "phPipelineRun",
+                "name": run_name,
+                "uid": run.uid().unwrap_or_default(),
+                "controller": true,
+            }]
+        },
+        "spec": spec,
+    }))?;
+    let pipelines: Api<phPipeline> = Api::namespaced(ctx.client.clone(), ns);
+    match pipelines.create(&PostParams::default(), &pipeline).await {
+        Ok(_) => {}
+        Err(KubeError::Api(e)) if e.code == 409 => {
+            // Either created by an earlier pass of this run, or a pipeline of
+            // the same name that the run must not take over.
+            let existing = pipelines.get(&run_name).await?;
+            let owned = existing
+                .owner_references()
+                .iter()
+                .any(|owner| Some(&owner.uid) == run.uid().as_ref());
+            if !owned {
+                let message = format!("A phPipeline named '{}' already exists and is not owned by this run", run_name);
+                events::warning(&ctx.recorder, run, "PipelineExists", "Start", message.clone()).await;
+                status.phase = Some(PipelinePhase::Failed);
+                status.completion_time = Some(Utc::now().to_rfc3339());
+                set_state(run, &mut status, ResourceState::Degraded, "PipelineExists", &message);
+                update_status(run, &ctx.client, status).await?;
+                return Ok(Action::await_change());
+            }
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    println!("Pipeline run '{}' started from template '{}'.", run_name, template_name);
+    events::normal(
+        &ctx.recorder,
+        run,
+        "PipelineCreated",
+        "Start",
+        format!("Created phPipeline '{}' from template '{}'.", run_name, template_name),
+    )
+    .await;
+    status.phase = Some(PipelinePhase::Running);
+    status.pipeline_name = Some(run_name);
+    status.template_generation = template.metadata.generation;
+    status.params = values;
+    status.start_time = Some(Utc::now().to_rfc3339());
+    set_state(run, &mut status, ResourceState::Progressing, "Started", "Pipeline started");
+    update_status(run, &ctx.client, status).await?;
+    Ok(Action::requeue(Duration::from_secs(30)))
+}
+
+/// Mirrors the state of the run's pipeline into the run.
+async fn monitor_run(
+    run: &phPipelineRun,
+    ctx: &Context,
+    ns: &str,
+    pipeline_name: &str,
+    mut status: phPipelineRunStatus,
+) -> Result<Action, Error> {
+    let pipelines: Api<phPipeline> = Api::namespaced(ctx.client.clone(), ns);
+    let Some(pipeline) = pipelines.get_opt(pipeline_name).await? else {
+        let message = format!("phPipeline '{}' was deleted before the run finished", pipeline_name);
+        events::warning(&ctx.recorder, run, "PipelineDeleted", "Run", message.clone()).await;
+        status.phase = Some(PipelinePhase::Failed);
+        status.completion_time = Some(Utc::now().to_rfc3339());
+        set_state(run, &mut status, ResourceState::Degraded, "PipelineDeleted", &message);
+        update_status(run, &ctx.client, status).await?;
+        return Ok(Action::await_change());
+    };
+
+    let cancel = run.annotations().get(CANCEL_ANNOTATION);
+    if cancel.is_some() && pipeline.annotations().get(CANCEL_ANNOTATION) != cancel {
+        println!("Cancelling pipeline '{}' of run '{}'.", pipeline_name, run.name_any());
+        let patch = Patch::Merge(json!({ "metadata": { "annotations": { CANCEL_ANNOTATION: cancel } } }));
+        pipelines.patch(pipeline_name, &PatchParams::default(), &patch).await?;
+    }
+
+    let pipeline_status = pipeline.status.clone().unwrap_or_default();
+    status.phase = match pipeline_status.phase {
+        None | Some(PipelinePhase::Pending) => Some(PipelinePhase::Running),
+        phase => phase,
+    };
+    status.steps = pipeline_status.steps;
+    for condition in pipeline_status.conditions {
+        conditions::set_condition(
+            &mut status.conditions,
+            StatusCondition {
+                observed_generation: run.metadata.generation,
+                ..condition
+            },
+        );
+    }
+    if !is_finished(&status.phase) {
+        update_status(run, &ctx.client, status).await?;
+        return Ok(Action::requeue(Duration::from_secs(30)));
+    }
+
+    status.completion_time = pipeline_status.completion_time.or_else(|| Some(Utc::now().to_rfc3339()));
+    let note = format!("Pipeline '{}' finished: {:?}.", pipeline_name, status.phase.clone().unwrap_or(PipelinePhase::Failed));
+    match status.phase {
+        Some(PipelinePhase::Succeeded) => events::normal(&ctx.recorder, run, "RunSucceeded", "Run", note).await,
+        Some(PipelinePhase::Cancelled) => events::normal(&ctx.recorder, run, "RunCancelled", "Run", note).await,
+        _ => events::warning(&ctx.recorder, run, "RunFailed", "Run", note).await,
+    }
+    update_status(run, &ctx.client, status).await?;
+    prune_history(ctx, ns, &run.spec.template_ref).await?;
+    Ok(Action::await_change())
+}
+
+/// Deletes the oldest finished runs of a template beyond its history limit.
+async fn prune_history(ctx: &Context, ns: &str, template_name: &str) -> Result<(), Error> {
+    let templates: Api<phPipelineTemplate> = Api::namespaced(ctx.client.clone(), ns);
+    let limit = templates
+        .get_opt(template_name)
+        .await?
+        .and_then(|template| template.spec.run_history_limit)
+        .unwrap_or(DEFAULT_RUN_HISTORY_LIMIT) as usize;
+
+    let runs: Api<phPipelineRun> = Api::namespaced(ctx.client.clone(), ns);
+    let mut finished: Vec<phPipelineRun> = runs
+        .list(&ListParams::default())
+        .await?
+        .items
+        .into_iter()
+        .filter(|run| run.spec.template_ref == template_name)
+        .filter(|run| is_finished(&run.status.as_ref().and_then(|s| s.phase.clone())))
+        .collect();
+    // Newest first; timestamps are RFC 3339 in UTC, so they sort as strings.
+    finished.sort_by_key(|run| std::cmp::Reverse(run.status.as_ref().and_then(|s| s.completion_time.clone())));
+    for expired in finished.iter().skip(limit) {
+        println!("Deleting run '{}' of template '{}' beyond the history limit.", expired.name_any(), template_name);
+        match runs.delete(&expired.name_any(), &DeleteParams::background()).await {
+            Ok(_) => {}
+            Err(KubeError::Api(e)) if e.code == 404 => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Patches the status subresource of the phPipelineRun using Server-Side Apply.
+async fn update_status(run: &phPipelineRun, client: &Client, status: phPipelineRunStatus) -> Result<(), Error> {
+    let ns = run.namespace().ok_or(Error::MissingNamespace)?;
+    let runs: Api<phPipelineRun> = Api::namespaced(client.clone(), &ns);
+    let patch = Patch::Apply(json!({
+        "apiVersion": "ph.io/v1alpha1",
+        "kind": "phPipelineRun",
+        "status": status,
+    }));
+    let ps = PatchParams::apply("ph-pipeline-run-controller").force();
+    runs.patch_status(&run.name_any(), &ps, &patch)
+        .await
+        .map_err(|e| Error::StatusUpdateError(e.to_string()))?;
+    Ok(())
+}
+
+/// Error handling function for the reconciliation loop. Errors are usually
+/// transient, so the run is marked `Degraded` but not failed.
+pub async fn on_error(run: Arc<phPipelineRun>, error: &Error, ctx: Arc<Context>) -> Action {
+    eprintln!("Reconciliation error for phPipelineRun '{}': {:?}", run.name_any(), error);
+    events::warning(&ctx.recorder, &*run, "ReconcileError", "Reconcile", error.to_string()).await;
+    let mut status = run.status.clone().unwrap_or_default();
+    set_state(&run, &mut status, ResourceState::Degraded, "ReconcileError", &error.to_string());
+    if let Err(e) = update_status(&run, &ctx.client, status).await {
+        eprintln!("Failed to update status on error: {}", e);
+    }
+    Action::requeue(Duration::from_secs(30))
+}
@@ -0,0 +1,168 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/pipeline_template.rs
+*
+* This file renders a phPipelineTemplate into the spec of a phPipeline, using
+* the parameter values of a phPipelineRun. It holds no Kubernetes logic, so
+* the rendering rules can be tested on their own.
+*
+* Architecture:
+* - `resolve_params` combines the values of a run with the defaults of the
+*   template. Every parameter must get a value of its declared type, and a run
+*   may not set parameters the template does not declare.
+* - `instantiate` replaces every `$(params.<name>)` in the text fields of the
+*   template (images, commands, arguments, environment values, ...) with its
+*   value. Other `$(...)` expressions, such as step output references, are
+*   left for the pipeline controller.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::crds::{phPipelineSpec, phPipelineTemplateSpec, ParamType, PipelineParam};
+use pipeline_rules::references::substitute_references_in;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TemplateError {
+    #[error("parameter '{0}' is declared more than once")]
+    DuplicateParam(String),
+    #[error("parameter '{0}' has no default and was not set")]
+    MissingParam(String),
+    #[error("parameter '{0}' is not declared by the template")]
+    UnknownParam(String),
+    #[error("parameter '{param}' must be {expected:?}, got '{value}'")]
+    InvalidValue {
+        param: String,
+        expected: ParamType,
+        value: String,
+    },
+    #[error("'$(params.{0})' refers to an undeclared parameter")]
+    UnresolvedReference(String),
+    #[error("rendered pipeline is invalid: {0}")]
+    InvalidPipeline(String),
+}
+
+/// Returns the value of every declared parameter: the one set by the run, or
+/// else the default.
+pub fn resolve_params(
+    declared: &[PipelineParam],
+    provided: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, TemplateError> {
+    if let Some(unknown) = provided.keys().find(|name| !declared.iter().any(|p| &p.name == *name)) {
+        return Err(TemplateError::UnknownParam(unknown.clone()));
+    }
+    let mut values = BTreeMap::new();
+    for param in declared {
+        let value = provided
+            .get(&param.name)
+            .or(param.default.as_ref())
+            .ok_or_else(|| TemplateError::MissingParam(param.name.clone()))?;
+        let valid = match param.param_type {
+            ParamType::String => true,
+            ParamType::Integer => value.trim().parse::<i64>().is_ok(),
+            ParamType::Boolean => matches!(value.as_str(), "true" | "false"),
+        };
+        if !valid {
+            return Err(TemplateError::InvalidValue {
+                param: param.name.clone(),
+                expected: param.param_type,
+                value: value.clone(),
+            });
+        }
+        if values.insert(param.name.clone(), value.clone()).is_some() {
+            return Err(TemplateError::DuplicateParam(param.name.clone()));
+        }
+    }
+    Ok(values)
+}
+
+/// Renders the pipeline of `template` with the resolved parameter `values`.
+pub fn instantiate(
+    template: &phPipelineTemplateSpec,
+    values: &BTreeMap<String, String>,
+) -> Result<phPipelineSpec, TemplateError> {
+    let mut pipeline =
+        serde_json::to_value(&template.pipeline).map_err(|e| TemplateError::InvalidPipeline(e.to_string()))?;
+    substitute_references_in(&mut pipeline, "params.", &mut |name| {
+        values
+            .get(name)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| TemplateError::UnresolvedReference(name.to_string()))
+    })?;
+    serde_json::from_value(pipeline).map_err(|e| TemplateError::InvalidPipeline(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, param_type: ParamType, default: Option<&str>) -> PipelineParam {
+        PipelineParam {
+            name: name.to_string(),
+            param_type,
+            default: default.map(str::to_string),
+            description: None,
+        }
+    }
+
+    fn provided(values: &[(&str, &str)]) -> BTreeMap<String, String> {
+        values.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_resolve_params() {
+        let declared = vec![
+            param("branch", ParamType::String, None),
+            param("replicas", ParamType::Integer, Some("2")),
+            param("push", ParamType::Boolean, Some("false")),
+        ];
+
+        let values = resolve_params(&declared, &provided(&[("branch", "main"), ("push", "true")])).unwrap();
+        assert_eq!(values, provided(&[("branch", "main"), ("push", "true"), ("replicas", "2")]));
+
+        assert_eq!(
+            resolve_params(&declared, &provided(&[])),
+            Err(TemplateError::MissingParam("branch".to_string()))
+        );
+        assert_eq!(
+            resolve_params(&declared, &provided(&[("branch", "main"), ("tag", "v1")])),
+            Err(TemplateError::UnknownParam("tag".to_string()))
+        );
+        assert!(matches!(
+            resolve_params(&declared, &provided(&[("branch", "main"), ("replicas", "two")])),
+            Err(TemplateError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_instantiate() {
+        let template: phPipelineTemplateSpec = serde_json::from_value(serde_json::json!({
+            "params": [{ "name": "branch" }],
+            "stages": [{
+                "name": "build",
+                "steps": [{
+                    "name": "build",
+                    "image": "golang:1.22",
+                    "command": ["make", "BRANCH=$(params.branch)", "IMAGE=$(steps.build.outputs.image)"],
+                    "env": [{ "name": "TOKEN", "valueFrom": { "secretKeyRef": { "name": "ci", "key": "token" } } }]
+                }]
+            }]
+        }))
+        .unwrap();
+
+        let spec = instantiate(&template, &provided(&[("branch", "release/1.2")])).unwrap();
+        let step = &spec.stages[0].steps[0];
+        assert_eq!(step.command[1], "BRANCH=release/1.2");
+        // References to step outputs are resolved later, by the pipeline controller.
+        assert_eq!(step.command[2], "IMAGE=$(steps.build.outputs.image)");
+        assert!(step.env[0].value_from.is_some());
+
+        assert_eq!(
+            instantiate(&template, &provided(&[])).unwrap_err(),
+            TemplateError::UnresolvedReference("branch".to_string())
+        );
+    }
+}
@@ -0,0 +1,52 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/pipeline_when.rs
+*
+* This file evaluates the `when` conditions that decide whether a phPipeline
+* step runs or is skipped. It holds no Kubernetes logic, so the rules can be
+* tested on their own.
+*
+* Architecture:
+* - The conditions follow `pipeline_rules::when`, which `ph ci run` uses as
+*   well: a condition either matches its `input` against glob `values`, or
+*   checks that a changed file matches one of its `changedPaths`. The status
+*   of a matrix step sums up the steps expanded from it.
+* - `validate` checks the conditions of every step when the pipeline starts.
+* - `references` lists the steps a step's conditions refer to, so the
+*   dependency graph makes the step wait for them. A step whose status a
+*   condition checks only has to finish, so that e.g. a step can run
+*   because another one failed (`status_references`).
+* - `evaluate` is called once the step may start and returns why it is
+*   skipped, if a condition does not hold.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::crds::{phPipelineSpec, PipelineStep};
+use pipeline_rules::when;
+
+pub use pipeline_rules::when::{WhenContext, WhenError};
+
+/// Checks that every condition sets exactly one of its two forms.
+pub fn validate(spec: &phPipelineSpec) -> Result<(), WhenError> {
+    for step in spec.stages.iter().flat_map(|stage| &stage.steps).chain(&spec.finally) {
+        when::validate(&step.name, &step.when)?;
+    }
+    Ok(())
+}
+
+/// The steps whose status or outputs the conditions of `step` refer to.
+pub fn references(step: &PipelineStep) -> Vec<&str> {
+    when::references(&step.when)
+}
+
+/// The steps whose status the conditions of `step` check.
+pub fn status_references(step: &PipelineStep) -> Vec<&str> {
+    when::status_references(&step.when)
+}
+
+/// Returns why `step` is skipped, or `None` when all its conditions hold.
+pub fn evaluate(step: &PipelineStep, context: &WhenContext<'_>) -> Result<Option<String>, WhenError> {
+    when::evaluate(&step.name, &step.when, context)
+}
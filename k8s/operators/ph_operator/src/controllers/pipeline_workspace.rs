@@ -0,0 +1,395 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/pipeline_workspace.rs
+*
+* This file gives every phPipeline run a workspace and passes step outputs
+* from one step to the next.
+*
+* Architecture:
+* - Every step pod mounts the workspace at `/workspace`. It is a shared
+*   PersistentVolumeClaim, an S3-compatible bucket (synchronized by helper
+*   containers) or, without configuration, an emptyDir of the pod.
+* - A step pod runs three containers in order: `ph-prepare` creates the step's
+*   output directory (and, for S3, downloads the outputs of the steps it
+*   needs), the step itself runs as the next init container, and `ph-collect`
+*   reads the declared outputs once it succeeded. The collector writes the
+*   first line of each output file to its termination message, where the
+*   controller picks the values up (`parse_results`), and uploads the files
+*   when the workspace is a bucket.
+* - Captured values are substituted into later steps wherever they write
+*   `$(steps.<step>.outputs.<name>)` (`substitute`).
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::crds::{phPipelineSpec, PipelineStep, PipelineWorkspace, WorkspaceS3};
+use pipeline_rules::references::{substitute_outputs, UnresolvedOutput};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// Where the workspace is mounted in every container of a step pod.
+pub const WORKSPACE_MOUNT: &str = "/workspace";
+/// The container whose termination message carries the output values.
+pub const COLLECT_CONTAINER: &str = "ph-collect";
+
+const WORKSPACE_VOLUME: &str = "workspace";
+pub const HELPER_IMAGE: &str = "busybox:1.36";
+pub const S3_IMAGE: &str = "amazon/aws-cli:2.15.0";
+const DEFAULT_CLAIM_SIZE: &str = "1Gi";
+const DEFAULT_ACCESS_MODE: &str = "ReadWriteMany";
+const DEFAULT_S3_REGION: &str = "us-east-1";
+/// Output values are cut to this many bytes; the whole termination message
+/// of a container is limited to 4 KiB.
+const MAX_VALUE_LEN: usize = 1024;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum WorkspaceError {
+    #[error("workspace must set exactly one of 'volume' and 's3'")]
+    InvalidWorkspace,
+    #[error("output name '{0}' may only contain letters, digits, '-' and '_'")]
+    InvalidOutputName(String),
+    #[error("output '{output}' of step '{step}' must be inside /workspace")]
+    OutputOutsideWorkspace { step: String, output: String },
+    #[error(transparent)]
+    UnresolvedReference(#[from] UnresolvedOutput),
+}
+
+/// Checks the workspace and the declared outputs of every step.
+pub fn validate(spec: &phPipelineSpec) -> Result<(), WorkspaceError> {
+    if let Some(workspace) = &spec.workspace {
+        if workspace.volume.is_some() == workspace.s3.is_some() {
+            return Err(WorkspaceError::InvalidWorkspace);
+        }
+    }
+    for step in spec.stages.iter().flat_map(|stage| &stage.steps).chain(&spec.finally) {
+        for output in step.outputs.iter().flatten() {
+            if output.name.is_empty()
+                || !output.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(WorkspaceError::InvalidOutputName(output.name.clone()));
+            }
+            output_file(&step.name, &output.name, &output.path)?;
+        }
+    }
+    Ok(())
+}
+
+/// The directory where a step writes its outputs.
+pub fn step_output_dir(step: &str) -> String {
+    format!("{}/steps/{}", WORKSPACE_MOUNT, step)
+}
+
+/// Resolves the path of an output against the step's output directory.
+fn output_file(step: &str, output: &str, path: &str) -> Result<String, WorkspaceError> {
+    let file = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", step_output_dir(step), path)
+    };
+    let inside = file
+        .strip_prefix(WORKSPACE_MOUNT)
+        .is_some_and(|rest| rest.starts_with('/'));
+    if inside && !file.split('/').any(|part| part == "..") {
+        Ok(file)
+    } else {
+        Err(WorkspaceError::OutputOutsideWorkspace {
+            step: step.to_string(),
+            output: output.to_string(),
+        })
+    }
+}
+
+/// Replaces every `$(steps.<step>.outputs.<name>)` in `text` with the captured
+/// value. Other `$(...)` expressions, such as Kubernetes variable references,
+/// are left alone.
+pub fn substitute(
+    text: &str,
+    outputs: &HashMap<String, BTreeMap<String, String>>,
+) -> Result<String, WorkspaceError> {
+    Ok(substitute_outputs(text, outputs)?)
+}
+
+/// Parses the `name=value` lines the collector writes to its termination
+/// message.
+pub fn parse_results(message: &str) -> BTreeMap<String, String> {
+    message
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, value)| (name.to_string(), value.trim_end().to_string()))
+        .collect()
+}
+
+/// The name of the claim used as the workspace, when it is a volume.
+pub fn claim_name(pipeline_name: &str, workspace: Option<&PipelineWorkspace>) -> Option<String> {
+    let volume = workspace?.volume.as_ref()?;
+    Some(
+        volume
+            .claim_name
+            .clone()
+            .unwrap_or_else(|| format!("{}-workspace", pipeline_name)),
+    )
+}
+
+/// The claim the operator creates when the workspace volume names none. It is
+/// `ReadWriteMany` unless the volume asks otherwise, since the pods of steps
+/// running in parallel may land on different nodes.
+pub fn claim_manifest(pipeline_name: &str, workspace: &PipelineWorkspace) -> Option<Value> {
+    let volume = workspace.volume.as_ref()?;
+    if volume.claim_name.is_some() {
+        return None;
+    }
+    let mut spec = json!({
+        "accessModes": [volume.access_mode.as_deref().unwrap_or(DEFAULT_ACCESS_MODE)],
+        "resources": { "requests": { "storage": volume.size.as_deref().unwrap_or(DEFAULT_CLAIM_SIZE) } },
+    });
+    if let Some(class) = &volume.storage_class_name {
+        spec["storageClassName"] = json!(class);
+    }
+    Some(json!({
+        "apiVersion": "v1",
+        "kind": "PersistentVolumeClaim",
+        "metadata": { "name": format!("{}-workspace", pipeline_name) },
+        "spec": spec,
+    }))
+}
+
+/// The pod-level pieces shared by all step Jobs of a run.
+pub struct RunWorkspace<'a> {
+    pub workspace: Option<&'a PipelineWorkspace>,
+    /// The claim to mount, for a volume workspace.
+    pub claim_name: Option<String>,
+    /// The key prefix of this run, for a bucket workspace.
+    pub run_prefix: String,
+}
+
+impl<'a> RunWorkspace<'a> {
+    /// Describes the workspace of one run of a pipeline. `run_id` tells runs
+    /// with the same name apart in the bucket.
+    pub fn new(spec: &'a phPipelineSpec, namespace: &str, pipeline_name: &str, run_id: &str) -> Self {
+        let workspace = spec.workspace.as_ref();
+        let prefix = workspace
+            .and_then(|w| w.s3.as_ref())
+            .and_then(|s3| s3.prefix.as_deref())
+            .map(|prefix| prefix.trim_matches('/'))
+            .filter(|prefix| !prefix.is_empty());
+        let run = format!("{}/{}/{}", namespace, pipeline_name, run_id);
+        Self {
+            workspace,
+            claim_name: claim_name(pipeline_name, workspace),
+            run_prefix: match prefix {
+                Some(prefix) => format!("{}/{}", prefix, run),
+                None => run,
+            },
+        }
+    }
+
+    fn s3(&self) -> Option<&WorkspaceS3> {
+        self.workspace.and_then(|w| w.s3.as_ref())
+    }
+
+    /// The workspace volume of a step pod.
+    pub fn volume(&self) -> Value {
+        match &self.claim_name {
+            Some(claim) => json!({ "name": WORKSPACE_VOLUME, "persistentVolumeClaim": { "claimName": claim } }),
+            None => json!({ "name": WORKSPACE_VOLUME, "emptyDir": {} }),
+        }
+    }
+
+    pub fn volume_mount(&self) -> Value {
+        json!({ "name": WORKSPACE_VOLUME, "mountPath": WORKSPACE_MOUNT })
+    }
+
+    /// The environment every step gets to find its workspace.
+    pub fn step_env(&self, step: &str) -> Vec<Value> {
+        vec![
+            json!({ "name": "PH_WORKSPACE", "value": WORKSPACE_MOUNT }),
+            json!({ "name": "PH_OUTPUTS", "value": step_output_dir(step) }),
+        ]
+    }
+
+    /// The init container that prepares the workspace before `step` runs.
+    /// With a bucket, it downloads the outputs of the steps in `needs`.
+    pub fn prepare_container(&self, step: &str, needs: &[&str]) -> Value {
+        let mut script = vec![format!("mkdir -p {}", shell_quote(&step_output_dir(step)))];
+        match self.s3() {
+            Some(s3) => {
+                script.insert(0, "aws configure set default.s3.addressing_style path".to_string());
+                for need in needs {
+                    script.push(format!(
+                        "aws --endpoint-url \"$S3_ENDPOINT\" s3 cp --recursive {} {}",
+                        shell_quote(&self.s3_url(s3, need)),
+                        shell_quote(&format!("{}/", step_output_dir(need))),
+                    ));
+                }
+                self.helper_container("ph-prepare", S3_IMAGE, &script, Some(s3))
+            }
+            None => self.helper_container("ph-prepare", HELPER_IMAGE, &script, None),
+        }
+    }
+
+    /// The container that captures the outputs of `step` after it succeeded.
+    pub fn collect_container(&self, step: &PipelineStep) -> Value {
+        let mut script = results_script(step, "/dev/termination-log");
+        match self.s3() {
+            Some(s3) => {
+                script.push("aws configure set default.s3.addressing_style path".to_string());
+                script.push(format!(
+                    "aws --endpoint-url \"$S3_ENDPOINT\" s3 cp --recursive {} {}",
+                    shell_quote(&format!("{}/", step_output_dir(&step.name))),
+                    shell_quote(&self.s3_url(s3, &step.name)),
+                ));
+                self.helper_container(COLLECT_CONTAINER, S3_IMAGE, &script, Some(s3))
+            }
+            None => {
+                if script.is_empty() {
+                    script.push("true".to_string());
+                }
+                self.helper_container(COLLECT_CONTAINER, HELPER_IMAGE, &script, None)
+            }
+        }
+    }
+
+    fn s3_url(&self, s3: &WorkspaceS3, step: &str) -> String {
+        format!("s3://{}/{}/steps/{}/", s3.bucket, self.run_prefix, step)
+    }
+
+    fn helper_container(&self, name: &str, image: &str, script: &[String], s3: Option<&WorkspaceS3>) -> Value {
+        let mut container = json!({
+            "name": name,
+            "image": image,
+            "command": ["sh", "-c", format!("set -e\n{}", script.join("\n"))],
+            "volumeMounts": [self.volume_mount()],
+        });
+        if let Some(s3) = s3 {
+            add_s3_access(&mut container, s3);
+        }
+        container
+    }
+}
+
+/// Gives a helper container the endpoint, region and credentials of a
+/// bucket, as `S3_ENDPOINT` and the variables of the AWS CLI.
+pub fn add_s3_access(container: &mut Value, s3: &WorkspaceS3) {
+    container["env"] = json!([
+        { "name": "S3_ENDPOINT", "value": s3.endpoint },
+        { "name": "AWS_DEFAULT_REGION", "value": s3.region.as_deref().unwrap_or(DEFAULT_S3_REGION) },
+    ]);
+    container["envFrom"] = json!([{ "secretRef": { "name": s3.credentials_secret } }]);
+}
+
+/// The commands that append the `name=value` line of every output of `step`
+/// to `target`.
+pub fn results_script(step: &PipelineStep, target: &str) -> Vec<String> {
+    let target = shell_quote(target);
+    let mut script = Vec::new();
+    for output in step.outputs.iter().flatten() {
+        // `validate` rejected outputs outside the workspace.
+        let Ok(file) = output_file(&step.name, &output.name, &output.path) else { continue };
+        let file = shell_quote(&file);
+        script.push(format!(
+            "if [ -f {file} ]; then printf '%s=' {name} >> {target}; head -n 1 {file} | head -c {max} >> {target}; echo >> {target}; fi",
+            name = shell_quote(&output.name),
+            max = MAX_VALUE_LEN,
+        ));
+    }
+    script
+}
+
+/// Quotes `value` for a POSIX shell.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crds::{PipelineStage, PipelineStepOutput, WorkspaceVolume};
+
+    fn outputs() -> HashMap<String, BTreeMap<String, String>> {
+        HashMap::from([(
+            "build".to_string(),
+            BTreeMap::from([("image".to_string(), "registry.local/app:1f2e3d".to_string())]),
+        )])
+    }
+
+    #[test]
+    fn test_substitute() {
+        let outputs = outputs();
+        assert_eq!(
+            substitute("scan $(steps.build.outputs.image) --env=$(HOME)", &outputs).unwrap(),
+            "scan registry.local/app:1f2e3d --env=$(HOME)"
+        );
+        assert_eq!(substitute("no references", &outputs).unwrap(), "no references");
+        assert_eq!(
+            substitute("$(steps.build.outputs.digest)", &outputs),
+            Err(WorkspaceError::UnresolvedReference(UnresolvedOutput {
+                step: "build".to_string(),
+                output: "digest".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_results() {
+        let results = parse_results("image=registry.local/app:1f2e3d\n\ndigest=sha256:abc=\n");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results["digest"], "sha256:abc=");
+    }
+
+    #[test]
+    fn test_validate() {
+        let step = |path: &str| PipelineStep {
+            name: "build".to_string(),
+            step_type: None,
+            image: "alpine".to_string(),
+            command: Vec::new(),
+            args: Vec::new(),
+            env: Vec::new(),
+            outputs: Some(vec![PipelineStepOutput {
+                name: "image".to_string(),
+                path: path.to_string(),
+            }]),
+            needs: Vec::new(),
+            timeout: None,
+            retries: 0,
+            retry_backoff: None,
+            continue_on_error: false,
+            cache: None,
+            matrix: None,
+            when: Vec::new(),
+        };
+        let spec = |path: &str, workspace: Option<PipelineWorkspace>| phPipelineSpec {
+            stages: vec![PipelineStage {
+                name: "build".to_string(),
+                steps: vec![step(path)],
+            }],
+            max_parallel: None,
+            workspace,
+            finally: Vec::new(),
+            cache: None,
+            changed_paths: None,
+        };
+
+        assert!(validate(&spec("image.txt", None)).is_ok());
+        assert!(validate(&spec("/workspace/image.txt", None)).is_ok());
+        assert!(validate(&spec("/tmp/image.txt", None)).is_err());
+        assert!(validate(&spec("/workspace/../etc/passwd", None)).is_err());
+        assert!(validate(&spec("../../../etc/passwd", None)).is_err());
+        assert!(validate(&spec("reports/../image.txt", None)).is_err());
+        assert_eq!(
+            validate(&spec("image.txt", Some(PipelineWorkspace::default()))),
+            Err(WorkspaceError::InvalidWorkspace)
+        );
+        let volume = PipelineWorkspace {
+            volume: Some(WorkspaceVolume::default()),
+            s3: None,
+        };
+        assert!(validate(&spec("image.txt", Some(volume.clone()))).is_ok());
+        assert_eq!(claim_name("ci", Some(&volume)).as_deref(), Some("ci-workspace"));
+        let claim = claim_manifest("ci", &volume).unwrap();
+        assert_eq!(claim["spec"]["accessModes"], json!(["ReadWriteMany"]));
+    }
+}
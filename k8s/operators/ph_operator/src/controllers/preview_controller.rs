@@ -28,19 +28,44 @@
  * cleanup is the finalizer removed, allowing Kubernetes to complete the deletion.
  * - `apply_preview`: This function contains the logic to create a preview environment. It
  * performs the following steps:
- * 1. Creates a unique, temporary namespace for the preview, derived from the resource's UID.
- * 2. Clones the specified Git repository at a given revision.
- * 3. Locates Kubernetes manifest files (YAML) within the cloned repository.
- * 4. Parses and applies each manifest to the newly created namespace.
+ * 0. Looks up the `phPreviewClass` of the preview (`sizeClass`, or the default class)
+ * and, until the preview holds a namespace, checks the caps of the class on
+ * concurrent previews (see `preview_guardrails`): over a cap, the preview is
+ * `Queued` (and checked again every 30 seconds) or rejected (`LimitExceeded`).
+ * 1. Creates a unique, temporary namespace for the preview, derived from the resource's UID,
+ * and stamps the guardrails of the class into it: a ResourceQuota, a LimitRange and a
+ * default-deny NetworkPolicy.
+ * 2. Clones the specified Git repository at a given revision: the pinned `commitSha`
+ * when set (previews of pull requests), otherwise the head of `branch`.
+ * 3. Renders the manifests under `manifestPath` (see `preview_render`): every YAML
+ * file, recursively, or a kustomization built in-process, or a Helm chart
+ * through `helm template`, with the image overrides of `render.images`.
+ * 4. Applies each rendered object to the preview namespace with server-side
+ * apply, through the dynamic API, labelled with the preview's name.
  * 5. Patches the `phPreview` resource's status subresource to reflect the outcome,
- * setting conditions like `Deployed` and recording the `namespace` and `url`.
+ * setting conditions like `Deployed` and recording the `namespace` and the
+ * `deployedCommit`. When `commitSha` moves (a push to the pull request), the
+ * preview reports `Updating` until the new commit is deployed.
+ * - Pull request reporting: every status update also records the preview URL
+ * (from the ingresses of the namespace) and the expiry, and, when the preview
+ * asks for it with `report`, is reported to its pull request as a commit
+ * status and a sticky comment (see `preview_report`). A failed report is
+ * published as an Event and does not fail the reconcile.
+ * - Expiry and working hours (see `preview_schedule`): a preview past its `ttl`
+ * is deleted. With an `idleSchedule`, a deployed preview sleeps outside its
+ * working hours: its Deployments and StatefulSets are scaled to zero (their
+ * replicas recorded in `ph.io/awake-replicas`) and its ingresses send their
+ * requests to the preview waker (see `preview_waker`), which sets
+ * `ph.io/wake-up` on the preview. The preview then stays awake for
+ * `wakeFor` (`status.awakeUntil`), and its workloads are restored.
  * - `cleanup_preview`: This function handles the teardown of the preview environment. It is
  * responsible for deleting the entire namespace, which garbage-collects all associated
  * resources.
  * - Status Updates: The controller now provides detailed status updates via the
- * `.status` subresource of the `phPreview` CRD. It reports the current phase
- * (e.g., `Creating`, `Deployed`, `Failed`, `Terminating`) and the name of the
- * managed namespace. This feedback is critical for user observability.
+ * `.status` subresource of the `phPreview` CRD. It reports the standard `Ready`,
+ * `Progressing` and `Degraded` conditions (with reasons such as `Creating`,
+ * `Deployed`, `Unhealthy` and `Terminating`) and the name of the managed
+ * namespace, and publishes a Kubernetes Event for each of these transitions.
  *
  * The implementation leverages `kube-rs` for all Kubernetes API interactions, `tokio` for
  * asynchronous operations, and external commands (`git`, `kubectl`) for environment setup.
@@ -50,14 +75,26 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
-use crate::crds::{phPreview, phPreviewStatus, StatusCondition};
+use crate::controllers::conditions::{self, ResourceState};
+use crate::controllers::events;
+use crate::controllers::preview_guardrails::{self, Admission};
+use crate::controllers::preview_render;
+use crate::controllers::preview_report::{self, PodHealth, PreviewSummary};
+use crate::controllers::preview_schedule::{self, WorkingHours, SLEEPING_REASON};
+use crate::controllers::preview_waker;
+use crate::crds::{phPreview, phPreviewClass, phPreviewClassSpec, phPreviewSpec, phPreviewStatus, StatusCondition};
 use crate::metrics;
-use k8s_openapi::api::core::v1::Pod;
+use chrono::{DateTime, Utc};
+use k8s_openapi::api::apps::v1::{Deployment, StatefulSet};
+use k8s_openapi::api::core::v1::{LimitRange, Pod, ResourceQuota, Service};
+use k8s_openapi::api::networking::v1::{Ingress, NetworkPolicy};
 use kube::{
-    api::{Api, DeleteParams, ListParams, Patch, PatchParams, PostParams, ResourceExt},
+    api::{Api, DeleteParams, DynamicObject, GroupVersionKind, ListParams, Patch, PatchParams, PostParams, ResourceExt},
     client::Client,
+    discovery::{self, Scope},
     runtime::{
         controller::Action,
+        events::Recorder,
         finalizer::{finalizer, Event as FinalizerEvent},
     },
     Error as KubeError,
@@ -67,16 +104,34 @@ use opentelemetry::{
     propagation::{Extractor, TextMapPropagator},
     sdk::propagation::TraceContextPropagator,
 };
+use serde_json::json;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::process::Command;
-use tracing::{info_span, Instrument};
+use tracing::{info_span, warn, Instrument};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 // The unique identifier for our controller's finalizer.
 const PREVIEW_FINALIZER: &str = "ph.io/finalizer";
+// Labels every object applied for a preview with the preview's name.
+const PREVIEW_LABEL: &str = "ph.io/preview";
+// The field manager of the server-side applies.
+const FIELD_MANAGER: &str = "ph-preview-controller";
+// Asks a sleeping preview to wake up; set by the preview waker.
+pub const WAKE_ANNOTATION: &str = "ph.io/wake-up";
+// Records the replicas of a workload scaled to zero while its preview sleeps.
+const AWAKE_REPLICAS_ANNOTATION: &str = "ph.io/awake-replicas";
+// The Service of a sleeping preview's namespace that forwards to the waker.
+const WAKER_SERVICE: &str = "ph-preview-waker";
+// The host of the waker, unless `PH_PREVIEW_WAKER_HOST` is set.
+const DEFAULT_WAKER_HOST: &str = "ph-operator-waker.ph-operator-system.svc.cluster.local";
+// The ingress-nginx annotations sending the requests of a sleeping preview to the waker.
+const DEFAULT_BACKEND_ANNOTATION: &str = "nginx.ingress.kubernetes.io/default-backend";
+const CUSTOM_ERRORS_ANNOTATION: &str = "nginx.ingress.kubernetes.io/custom-http-errors";
+// How often a preview is re-applied.
+const REAPPLY_INTERVAL: Duration = Duration::from_secs(600);
 
 // Custom error types for the controller for better diagnostics and status reporting.
 #[derive(Debug, Error)]
@@ -84,12 +139,24 @@ pub enum PreviewError {
     #[error("Failed to apply Kubernetes manifests: {0}")]
     ManifestApplyError(String),
 
+    #[error("Failed to render Kubernetes manifests: {0}")]
+    ManifestRenderError(String),
+
     #[error("Failed to clone Git repository: {0}")]
     GitCloneError(String),
 
     #[error("Failed to create namespace: {0}")]
     NamespaceCreationError(String),
 
+    #[error("Failed to apply the guardrails of the namespace: {0}")]
+    GuardrailError(String),
+
+    #[error("phPreviewClass '{0}' not found")]
+    SizeClassNotFound(String),
+
+    #[error("Invalid schedule: {0}")]
+    InvalidSchedule(String),
+
     #[error("Failed to update resource status: {0}")]
     StatusUpdateError(String),
 
@@ -103,6 +170,8 @@ pub enum PreviewError {
 /// The context required by the reconciler. It holds the Kubernetes client.
 pub struct Context {
     pub client: Client,
+    /// Publishes Kubernetes Events about previews.
+    pub recorder: Recorder,
 }
 
 /// Generates the unique namespace name for a given preview resource.
@@ -123,13 +192,14 @@ fn generate_namespace_name(preview: &phPreview) -> Result<String, PreviewError>
 
     // The name is constructed to be unique and descriptive.
     // Format: preview-<branch>-<app_name>-<uid_prefix>
-    // The branch name has slashes replaced to be a valid DNS label.
-    Ok(format!(
-        "preview-{}-{}-{}",
-        spec.branch.replace('/', "-"),
-        spec.app_name,
-        &uid[..6]
-    ))
+    // The branch name has slashes replaced to be a valid DNS label. Previews
+    // of pull requests use `pr-<number>` instead, as head branches are often
+    // long or not valid in a DNS label.
+    let source = match spec.pr_number {
+        Some(number) => format!("pr-{}", number),
+        None => spec.branch.replace('/', "-"),
+    };
+    Ok(format!("preview-{}-{}-{}", source, spec.app_name, &uid[..6]))
 }
 
 /// Updates the status subresource of the phPreview custom resource.
@@ -167,6 +237,79 @@ async fn update_status(
     Ok(())
 }
 
+/// Builds a status for the preview in `state`, carrying over its existing
+/// conditions so that unchanged conditions keep their transition time, and
+/// the rest of its status (deployed commit, URL, last report).
+fn preview_status(
+    preview: &phPreview,
+    conditions: &[StatusCondition],
+    namespace: Option<String>,
+    state: ResourceState,
+    reason: &str,
+    message: &str,
+) -> phPreviewStatus {
+    let mut conditions = conditions.to_vec();
+    conditions::set_state(&mut conditions, state, reason, message, preview.metadata.generation, Utc::now());
+    let expires_at = preview.creation_timestamp().and_then(|created| {
+        preview_schedule::expiry(preview.spec.as_ref()?, created.0).ok().flatten().map(|at| at.to_rfc3339())
+    });
+    phPreviewStatus {
+        namespace,
+        expires_at,
+        conditions,
+        ..preview.status.clone().unwrap_or_default()
+    }
+}
+
+/// Records the URL of the preview in `status` and, if the preview asks for
+/// it, reports `status` to its pull request. The report is skipped while it
+/// is unchanged; a failed report is published as a warning Event only.
+async fn report_status(ctx: &Context, preview: &phPreview, mut status: phPreviewStatus, deleted: bool) -> phPreviewStatus {
+    let mut pods = PodHealth::default();
+    if let (Some(namespace), false) = (&status.namespace, deleted) {
+        match preview_report::observe_namespace(&ctx.client, namespace).await {
+            Ok((url, health)) => {
+                status.url = url;
+                pods = health;
+            }
+            Err(e) => warn!(namespace = %namespace, error = %e, "Failed to observe the preview namespace"),
+        }
+    }
+
+    let Some(spec) = preview.spec.as_ref() else {
+        return status;
+    };
+    let (Some(report), Some(_)) = (&spec.report, spec.pr_number) else {
+        return status;
+    };
+    let Some(ready) = status.conditions.iter().find(|c| c.type_ == conditions::READY) else {
+        return status;
+    };
+    let summary = PreviewSummary {
+        preview: format!("{}/{}", preview.namespace().unwrap_or_default(), preview.name_any()),
+        namespace: status.namespace.clone(),
+        url: status.url.clone(),
+        state: conditions::current_state(&status.conditions).unwrap_or(ResourceState::Progressing),
+        reason: ready.reason.clone(),
+        message: ready.message.clone(),
+        // While a new commit is deployed, report on the new one.
+        commit: spec.commit_sha.clone().or_else(|| status.deployed_commit.clone()),
+        pods,
+        expires_at: status.expires_at.as_deref().and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok()).map(|at| at.with_timezone(&Utc)),
+        deleted,
+    };
+
+    let digest = preview_report::report_digest(&summary, report);
+    if status.reported.as_deref() == Some(digest.as_str()) {
+        return status;
+    }
+    match preview_report::report(&ctx.client, preview, report, &summary).await {
+        Ok(()) => status.reported = Some(digest),
+        Err(e) => events::warning(&ctx.recorder, preview, "ReportFailed", "Report", e.to_string()).await,
+    }
+    status
+}
+
 /// Main reconciliation function for the phPreview resource.
 /// This function is the entry point of the controller's reconciliation loop.
 /// It uses the `kube_rs::runtime::finalizer` helper to manage cleanup logic.
@@ -240,17 +383,111 @@ pub async fn reconcile(preview: Arc<phPreview>, ctx: Arc<Context>) -> Result<Act
 async fn apply_preview(preview: Arc<phPreview>, ctx: Arc<Context>) -> Result<Action, PreviewError> {
     let client = ctx.client.clone();
     let ns_name = generate_namespace_name(&preview)?;
+    let now = Utc::now();
+    let Some((preview, hours)) = check_schedule(&ctx, preview, now).await? else {
+        return Ok(Action::await_change());
+    };
     let spec = preview.spec.as_ref().ok_or(PreviewError::MissingSpec)?;
+    let expires_at = preview.creation_timestamp().and_then(|created| preview_schedule::expiry(spec, created.0).ok().flatten());
+    let awake_until = preview
+        .status
+        .as_ref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s.awake_until.as_deref()?).ok())
+        .map(|at| at.with_timezone(&Utc));
+    let next_change = hours.as_ref().and_then(|h| h.next_change(now));
+
+    let mut current_conditions = preview.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default();
+
+    // --- 0. Look up the size class and admit the preview ---
+    // A preview holds a slot once it has a namespace; until then, the caps of
+    // its class decide whether it may start.
+    let class = preview_class(&client, spec).await?;
+    let holds_slot = preview.status.as_ref().is_some_and(|s| s.namespace.is_some());
+    if let (Some(concurrency), false) = (class.as_ref().and_then(|c| c.spec.concurrency.as_ref()), holds_slot) {
+        let all_previews = Api::<phPreview>::all(client.clone()).list(&ListParams::default()).await?.items;
+        let refusal = match preview_guardrails::admit(&preview, &all_previews, concurrency) {
+            Admission::Admitted => None,
+            Admission::Queued(message) => Some((
+                ResourceState::Progressing,
+                preview_guardrails::QUEUED_REASON,
+                message,
+                Action::requeue(Duration::from_secs(30)),
+            )),
+            Admission::Rejected(message) => Some((ResourceState::Degraded, "LimitExceeded", message, Action::await_change())),
+        };
+        if let Some((state, reason, message, action)) = refusal {
+            let current_reason = current_conditions.iter().find(|c| c.type_ == conditions::READY).map(|c| c.reason.as_str());
+            if current_reason != Some(reason) {
+                warn!(preview = %preview.name_any(), reason, "Preview not admitted: {}", message);
+                events::warning(&ctx.recorder, &*preview, reason, "Admit", message.clone()).await;
+            }
+            let status = preview_status(&preview, &current_conditions, None, state, reason, &message);
+            let status = report_status(&ctx, &preview, status, false).await;
+            update_status(preview.clone(), client, status).await?;
+            return Ok(action);
+        }
+    }
 
-    // --- 1. Update Status to "Creating" ---
-    let initial_status = phPreviewStatus {
-        namespace: Some(ns_name.clone()),
-        conditions: vec![StatusCondition::new(
-            "Creating".to_string(),
-            "Reconciliation started".to_string(),
-        )],
-    };
-    update_status(preview.clone(), client.clone(), initial_status).await?;
+    // --- Sleep outside the working hours ---
+    // Only a deployed preview sleeps; it is woken up before it is re-applied.
+    let deployed = preview.status.as_ref().is_some_and(|s| s.deployed_commit.is_some());
+    let asleep = hours.as_ref().is_some_and(|h| !preview_schedule::is_awake(h, awake_until, now));
+    if deployed && asleep {
+        sleep_namespace(&client, &ns_name).await?;
+        let current_reason = current_conditions.iter().find(|c| c.type_ == conditions::READY).map(|c| c.reason.as_str());
+        if current_reason != Some(SLEEPING_REASON) {
+            let note = format!("Scaled namespace '{}' to zero outside working hours.", ns_name);
+            events::normal(&ctx.recorder, &*preview, SLEEPING_REASON, "Sleep", note).await;
+        }
+        let status = preview_status(
+            &preview,
+            &current_conditions,
+            Some(ns_name),
+            ResourceState::Ready,
+            SLEEPING_REASON,
+            "Scaled to zero outside working hours; open the preview to wake it up",
+        );
+        let status = report_status(&ctx, &preview, status, false).await;
+        update_status(preview.clone(), client, status).await?;
+        return Ok(Action::requeue(preview_schedule::requeue_after(now, &[expires_at, next_change], REAPPLY_INTERVAL)));
+    }
+    if deployed && wake_namespace(&client, &ns_name).await? {
+        events::normal(&ctx.recorder, &*preview, "Waking", "Wake", format!("Restored the workloads of namespace '{}'.", ns_name)).await;
+    }
+
+    // --- 1. Update Status to "Creating" or "Updating" ---
+    // A healthy preview stays Ready while it is periodically re-applied, until
+    // its pinned commit moves.
+    let deployed_commit = preview.status.as_ref().and_then(|s| s.deployed_commit.as_deref());
+    let new_commit = spec.commit_sha.as_deref().filter(|sha| deployed_commit.is_some_and(|deployed| deployed != *sha));
+    if let Some(sha) = new_commit {
+        let updating_status = preview_status(
+            &preview,
+            &current_conditions,
+            Some(ns_name.clone()),
+            ResourceState::Progressing,
+            "Updating",
+            &format!("Deploying commit {}", sha),
+        );
+        events::normal(&ctx.recorder, &*preview, "Updating", "Deploy", format!("Deploying commit {} into namespace '{}'.", sha, ns_name)).await;
+        current_conditions = updating_status.conditions.clone();
+        let updating_status = report_status(&ctx, &preview, updating_status, false).await;
+        update_status(preview.clone(), client.clone(), updating_status).await?;
+    } else if conditions::current_state(&current_conditions) != Some(ResourceState::Ready) {
+        let initial_status = preview_status(
+            &preview,
+            &current_conditions,
+            Some(ns_name.clone()),
+            ResourceState::Progressing,
+            "Creating",
+            "Creating the preview environment",
+        );
+        let revision = spec.commit_sha.as_ref().map_or_else(|| format!("branch '{}'", spec.branch), |sha| format!("commit {}", sha));
+        events::normal(&ctx.recorder, &*preview, "Creating", "Deploy", format!("Deploying {} into namespace '{}'.", revision, ns_name)).await;
+        current_conditions = initial_status.conditions.clone();
+        let initial_status = report_status(&ctx, &preview, initial_status, false).await;
+        update_status(preview.clone(), client.clone(), initial_status).await?;
+    }
 
     // --- 2. Create the Kubernetes Namespace ---
     async {
@@ -266,50 +503,41 @@ async fn apply_preview(preview: Arc<phPreview>, ctx: Arc<Context>) -> Result<Act
         }
     }.instrument(info_span!("create_namespace", "ph.namespace" = ns_name.as_str())).await?;
 
+    // Stamp the guardrails before any workload is applied, so that the
+    // defaults of the LimitRange apply to it.
+    if let Some(class) = &class {
+        apply_guardrails(&client, &ns_name, &class.spec)
+            .instrument(info_span!("apply_guardrails", "ph.size_class" = class.name_any().as_str()))
+            .await?;
+    }
+
     // --- 3. Clone the Git repository ---
-    let temp_dir = async {
+    let (temp_dir, commit) = async {
         let temp_dir = tempfile::Builder::new()
             .prefix("ph-preview-")
             .tempdir()
             .map_err(|e| PreviewError::GitCloneError(e.to_string()))?;
         let repo_path = temp_dir.path().to_str().unwrap();
 
-        let git_clone_status = Command::new("git")
-            .args(["clone", "--branch", &spec.branch, "--depth", "1", &spec.repo_url, repo_path])
-            .status().await.map_err(|e| PreviewError::GitCloneError(e.to_string()))?;
-
-        if !git_clone_status.success() {
-            return Err(PreviewError::GitCloneError("Git clone command failed".to_string()));
+        match &spec.commit_sha {
+            // A single commit cannot be cloned; fetch it into an empty repository.
+            Some(sha) => {
+                run_git(&["init", "--quiet", repo_path]).await?;
+                run_git(&["-C", repo_path, "fetch", "--quiet", "--depth", "1", &spec.repo_url, sha]).await?;
+                run_git(&["-C", repo_path, "checkout", "--quiet", "FETCH_HEAD"]).await?;
+            }
+            None => {
+                run_git(&["clone", "--branch", &spec.branch, "--depth", "1", &spec.repo_url, repo_path]).await?;
+            }
         }
-        Ok(temp_dir)
+        let head = run_git(&["-C", repo_path, "rev-parse", "HEAD"]).await?;
+        Ok::<_, PreviewError>((temp_dir, head))
     }.instrument(info_span!("clone_repository", "ph.repo_url" = spec.repo_url.as_str())).await?;
 
-    // --- 4. Find and apply Kubernetes manifests ---
+    // --- 4. Render the manifests and apply them ---
     async {
-        let manifest_dir = temp_dir.path().join(&spec.manifest_path);
-        if !manifest_dir.is_dir() {
-            return Err(PreviewError::ManifestApplyError(format!("Manifest path '{}' not found", manifest_dir.display())));
-        }
-
-        for entry in std::fs::read_dir(&manifest_dir).map_err(|e| PreviewError::ManifestApplyError(e.to_string()))? {
-            let path = entry.map_err(|e| PreviewError::ManifestApplyError(e.to_string()))?.path();
-            if path.is_file() && (path.extension() == Some("yaml".as_ref()) || path.extension() == Some("yml".as_ref())) {
-                let manifest_content = std::fs::read_to_string(&path)
-                    .map_err(|e| PreviewError::ManifestApplyError(format!("Failed to read manifest {}: {}", path.display(), e)))?;
-                
-                let mut child = Command::new("kubectl").args(["apply", "-n", &ns_name, "-f", "-"])
-                    .stdin(std::process::Stdio::piped()).spawn().map_err(|e| PreviewError::ManifestApplyError(e.to_string()))?;
-                
-                let mut stdin = child.stdin.take().expect("Failed to open stdin");
-                tokio::spawn(async move { stdin.write_all(manifest_content.as_bytes()).await });
-                
-                let output = child.wait_with_output().await.map_err(|e| PreviewError::ManifestApplyError(e.to_string()))?;
-                if !output.status.success() {
-                    return Err(PreviewError::ManifestApplyError(format!("kubectl apply failed for {}: {}", path.display(), String::from_utf8_lossy(&output.stderr))));
-                }
-            }
-        }
-        Ok(())
+        let objects = render_manifests(spec, temp_dir.path(), &ns_name, &commit).await?;
+        apply_objects(&client, &ns_name, &preview.name_any(), objects).await
     }.instrument(info_span!("apply_manifests", "ph.manifest_path" = spec.manifest_path.as_str())).await?;
 
     // --- 5. Monitor Health and Update Status ---
@@ -320,26 +548,299 @@ async fn apply_preview(preview: Arc<phPreview>, ctx: Arc<Context>) -> Result<Act
             println!("Preview '{}' is healthy.", preview.name_any());
             metrics::PHGIT_PREVIEW_CREATED_TOTAL.inc();
             metrics::PHGIT_PREVIEW_ACTIVE.inc();
-            phPreviewStatus {
-                namespace: Some(ns_name),
-                conditions: vec![StatusCondition::new(
-                    "Deployed".to_string(),
-                    "All manifests applied and resources are healthy".to_string(),
-                )],
-            }
+            events::normal(&ctx.recorder, &*preview, "Deployed", "Deploy", format!("Preview is healthy in namespace '{}'.", ns_name)).await;
+            preview_status(
+                &preview,
+                &current_conditions,
+                Some(ns_name),
+                ResourceState::Ready,
+                "Deployed",
+                &format!("Commit {} applied and resources are healthy", commit),
+            )
         }
         Err(e) => {
             println!("Preview '{}' is unhealthy: {}", preview.name_any(), e);
-            phPreviewStatus {
-                namespace: Some(ns_name),
-                conditions: vec![StatusCondition::new("Failed".to_string(), e.to_string())],
-            }
+            events::warning(&ctx.recorder, &*preview, "Unhealthy", "Deploy", e.clone()).await;
+            preview_status(&preview, &current_conditions, Some(ns_name), ResourceState::Degraded, "Unhealthy", &e)
         }
     };
-    
+    // The manifests of `commit` were applied, whatever the health of the pods.
+    let final_status = phPreviewStatus {
+        deployed_commit: Some(commit),
+        size_class: class.as_ref().map(|c| c.name_any()),
+        ..final_status
+    };
+    let final_status = report_status(&ctx, &preview, final_status, false).await;
+
     update_status(preview, client, final_status).await?;
 
-    Ok(Action::requeue(Duration::from_secs(600)))
+    // Come back in time to expire the preview or put it to sleep.
+    Ok(Action::requeue(preview_schedule::requeue_after(now, &[expires_at, next_change, awake_until], REAPPLY_INTERVAL)))
+}
+
+/// Deletes the preview once it has expired, and takes in a request to wake
+/// it up (`ph.io/wake-up`). Returns the preview, with its new `awakeUntil`
+/// when woken up, and its working hours; `None` once it has been deleted.
+async fn check_schedule(
+    ctx: &Context,
+    preview: Arc<phPreview>,
+    now: DateTime<Utc>,
+) -> Result<Option<(Arc<phPreview>, Option<WorkingHours>)>, PreviewError> {
+    let spec = preview.spec.as_ref().ok_or(PreviewError::MissingSpec)?;
+    let previews: Api<phPreview> = Api::namespaced(ctx.client.clone(), &preview.namespace().unwrap_or_default());
+    let created = preview.creation_timestamp().map_or(now, |created| created.0);
+    let expired = preview_schedule::expiry(spec, created).map_err(PreviewError::InvalidSchedule)?.filter(|at| *at <= now);
+    if let Some(expired) = expired {
+        let note = format!("The preview expired at {}.", expired.to_rfc3339());
+        events::normal(&ctx.recorder, &*preview, "Expired", "Delete", note).await;
+        // The finalizer removes the namespace.
+        previews.delete(&preview.name_any(), &DeleteParams::default()).await?;
+        return Ok(None);
+    }
+
+    let hours = spec.idle_schedule.as_ref().map(WorkingHours::parse).transpose().map_err(PreviewError::InvalidSchedule)?;
+    if !preview.annotations().contains_key(WAKE_ANNOTATION) {
+        return Ok(Some((preview, hours)));
+    }
+    let patch = json!({ "metadata": { "annotations": { WAKE_ANNOTATION: null } } });
+    previews.patch(&preview.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    let Some(hours) = hours else {
+        return Ok(Some((preview, None)));
+    };
+    let awake_until = (now + hours.wake_for).to_rfc3339();
+    events::normal(&ctx.recorder, &*preview, "WakeUp", "Wake", format!("Woken up until {}.", awake_until)).await;
+    let mut woken = (*preview).clone();
+    woken.status.get_or_insert_with(Default::default).awake_until = Some(awake_until);
+    Ok(Some((Arc::new(woken), Some(hours))))
+}
+
+/// Puts a preview namespace to sleep: scales its Deployments and
+/// StatefulSets to zero and sends the requests of its ingresses to the
+/// preview waker.
+async fn sleep_namespace(client: &Client, ns_name: &str) -> Result<(), PreviewError> {
+    scale_to_zero(&Api::<Deployment>::namespaced(client.clone(), ns_name)).await?;
+    scale_to_zero(&Api::<StatefulSet>::namespaced(client.clone(), ns_name)).await?;
+
+    let waker_host = std::env::var("PH_PREVIEW_WAKER_HOST").unwrap_or_else(|_| DEFAULT_WAKER_HOST.to_string());
+    let waker: Service = serde_json::from_value(json!({
+        "apiVersion": "v1", "kind": "Service", "metadata": { "name": WAKER_SERVICE },
+        "spec": { "type": "ExternalName", "externalName": waker_host, "ports": [{ "port": preview_waker::WAKER_PORT }] }
+    }))
+    .expect("Static service definition should not fail");
+    let services: Api<Service> = Api::namespaced(client.clone(), ns_name);
+    services.patch(WAKER_SERVICE, &PatchParams::apply(FIELD_MANAGER).force(), &Patch::Apply(&waker)).await?;
+    route_to_waker(client, ns_name, true).await
+}
+
+/// Wakes a preview namespace up. Returns whether any workload was scaled
+/// back up.
+async fn wake_namespace(client: &Client, ns_name: &str) -> Result<bool, PreviewError> {
+    let deployments = restore_replicas(&Api::<Deployment>::namespaced(client.clone(), ns_name)).await?;
+    let stateful_sets = restore_replicas(&Api::<StatefulSet>::namespaced(client.clone(), ns_name)).await?;
+    route_to_waker(client, ns_name, false).await?;
+    Ok(deployments || stateful_sets)
+}
+
+/// Scales the workloads of `api` to zero, recording their replicas.
+async fn scale_to_zero<K>(api: &Api<K>) -> Result<(), PreviewError>
+where
+    K: kube::Resource<DynamicType = ()> + Clone + std::fmt::Debug + serde::Serialize + serde::de::DeserializeOwned,
+{
+    for workload in api.list(&ListParams::default()).await? {
+        let replicas = serde_json::to_value(&workload).ok().and_then(|w| w["spec"]["replicas"].as_i64()).unwrap_or(1);
+        if replicas == 0 {
+            continue;
+        }
+        let patch = json!({
+            "metadata": { "annotations": { AWAKE_REPLICAS_ANNOTATION: replicas.to_string() } },
+            "spec": { "replicas": 0 }
+        });
+        api.patch(&workload.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    }
+    Ok(())
+}
+
+/// Scales the workloads of `api` back to their recorded replicas. Returns
+/// whether there were any.
+async fn restore_replicas<K>(api: &Api<K>) -> Result<bool, PreviewError>
+where
+    K: kube::Resource<DynamicType = ()> + Clone + std::fmt::Debug + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let mut restored = false;
+    for workload in api.list(&ListParams::default()).await? {
+        let Some(replicas) = workload.annotations().get(AWAKE_REPLICAS_ANNOTATION).and_then(|r| r.parse::<i64>().ok()) else {
+            continue;
+        };
+        let patch = json!({
+            "metadata": { "annotations": { AWAKE_REPLICAS_ANNOTATION: null } },
+            "spec": { "replicas": replicas }
+        });
+        api.patch(&workload.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await?;
+        restored = true;
+    }
+    Ok(restored)
+}
+
+/// Sends the requests that the ingresses of a namespace cannot serve to the
+/// waker Service, or stops doing so.
+async fn route_to_waker(client: &Client, ns_name: &str, enabled: bool) -> Result<(), PreviewError> {
+    let ingresses: Api<Ingress> = Api::namespaced(client.clone(), ns_name);
+    let annotations = if enabled {
+        json!({ DEFAULT_BACKEND_ANNOTATION: WAKER_SERVICE, CUSTOM_ERRORS_ANNOTATION: "502,503,504" })
+    } else {
+        json!({ DEFAULT_BACKEND_ANNOTATION: null, CUSTOM_ERRORS_ANNOTATION: null })
+    };
+    let patch = json!({ "metadata": { "annotations": annotations } });
+    for ingress in ingresses.list(&ListParams::default()).await? {
+        let routed = ingress.annotations().get(DEFAULT_BACKEND_ANNOTATION).is_some_and(|backend| backend == WAKER_SERVICE);
+        if routed != enabled {
+            ingresses.patch(&ingress.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Looks up the phPreviewClass of a preview: `sizeClass`, or else the class
+/// annotated as the default, if any.
+async fn preview_class(client: &Client, spec: &phPreviewSpec) -> Result<Option<phPreviewClass>, PreviewError> {
+    let classes: Api<phPreviewClass> = Api::all(client.clone());
+    match &spec.size_class {
+        Some(name) => match classes.get_opt(name).await? {
+            Some(class) => Ok(Some(class)),
+            None => Err(PreviewError::SizeClassNotFound(name.clone())),
+        },
+        None => Ok(classes.list(&ListParams::default()).await?.items.into_iter().find(|class| {
+            class.annotations().get(preview_guardrails::DEFAULT_CLASS_ANNOTATION).is_some_and(|value| value == "true")
+        })),
+    }
+}
+
+/// Stamps the guardrails of `class` into the preview namespace, and removes
+/// the quota and limit range when the class no longer sets them.
+async fn apply_guardrails(client: &Client, ns_name: &str, class: &phPreviewClassSpec) -> Result<(), PreviewError> {
+    let guardrails = preview_guardrails::guardrails(class, ns_name);
+    let params = PatchParams::apply(FIELD_MANAGER).force();
+    let quotas: Api<ResourceQuota> = Api::namespaced(client.clone(), ns_name);
+    apply_or_delete(&quotas, preview_guardrails::QUOTA_NAME, guardrails.quota, &params).await?;
+    let limit_ranges: Api<LimitRange> = Api::namespaced(client.clone(), ns_name);
+    apply_or_delete(&limit_ranges, preview_guardrails::LIMIT_RANGE_NAME, guardrails.limit_range, &params).await?;
+    let policies: Api<NetworkPolicy> = Api::namespaced(client.clone(), ns_name);
+    apply_or_delete(&policies, preview_guardrails::NETWORK_POLICY_NAME, Some(guardrails.network_policy), &params).await
+}
+
+/// Applies `object` under `name`, or deletes the object named `name` when
+/// there is none.
+async fn apply_or_delete<K>(api: &Api<K>, name: &str, object: Option<K>, params: &PatchParams) -> Result<(), PreviewError>
+where
+    K: kube::Resource<DynamicType = ()> + Clone + std::fmt::Debug + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let result = match object {
+        Some(object) => api.patch(name, params, &Patch::Apply(&object)).await.map(|_| ()),
+        None => match api.delete(name, &DeleteParams::default()).await {
+            Err(KubeError::Api(ae)) if ae.code == 404 => Ok(()),
+            result => result.map(|_| ()),
+        },
+    };
+    result.map_err(|e| PreviewError::GuardrailError(format!("{} '{}': {}", K::kind(&()), name, e)))
+}
+
+/// Renders the objects of a preview from the repository checked out in
+/// `repo_dir`, according to `spec.render`.
+async fn render_manifests(
+    spec: &phPreviewSpec,
+    repo_dir: &std::path::Path,
+    ns_name: &str,
+    commit: &str,
+) -> Result<Vec<serde_json::Value>, PreviewError> {
+    let render_error = |e: preview_render::RenderError| PreviewError::ManifestRenderError(e.to_string());
+    let render = spec.render.clone().unwrap_or_default();
+    let mut objects = match (&render.helm, render.kustomize) {
+        (Some(_), true) => {
+            return Err(PreviewError::ManifestRenderError("Only one of 'kustomize' and 'helm' may be set".to_string()));
+        }
+        (Some(helm), false) => {
+            let args = preview_render::helm_template_args(helm, repo_dir, &spec.manifest_path, &spec.app_name, ns_name, commit)
+                .map_err(render_error)?;
+            let output = Command::new("helm")
+                .args(&args)
+                .current_dir(repo_dir)
+                .output()
+                .await
+                .map_err(|e| PreviewError::ManifestRenderError(format!("Failed to run helm: {}", e)))?;
+            if !output.status.success() {
+                return Err(PreviewError::ManifestRenderError(format!(
+                    "helm template failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            preview_render::parse_documents(&String::from_utf8_lossy(&output.stdout), "helm template").map_err(render_error)?
+        }
+        (None, kustomize) => {
+            let manifest_dir = repo_dir.join(&spec.manifest_path);
+            if !manifest_dir.is_dir() {
+                return Err(PreviewError::ManifestRenderError(format!("Manifest path '{}' not found", spec.manifest_path)));
+            }
+            if kustomize {
+                preview_render::build_kustomization(&manifest_dir, repo_dir).map_err(render_error)?
+            } else {
+                preview_render::read_plain(&manifest_dir).map_err(render_error)?
+            }
+        }
+    };
+    preview_render::set_images(&mut objects, &render.images, commit);
+    if objects.is_empty() {
+        return Err(PreviewError::ManifestRenderError("The manifests define no objects".to_string()));
+    }
+    Ok(objects)
+}
+
+/// Applies `objects` to the preview namespace with server-side apply.
+/// Namespaces in the manifests are skipped, as the preview brings its own,
+/// and other cluster-scoped objects are refused.
+async fn apply_objects(
+    client: &Client,
+    ns_name: &str,
+    preview_name: &str,
+    objects: Vec<serde_json::Value>,
+) -> Result<(), PreviewError> {
+    let apply_error = |message: String| PreviewError::ManifestApplyError(message);
+    let params = PatchParams::apply(FIELD_MANAGER).force();
+    for object in objects {
+        let mut object: DynamicObject = serde_json::from_value(object).map_err(|e| apply_error(e.to_string()))?;
+        let types = object.types.clone().ok_or_else(|| apply_error("An object has no apiVersion or kind".to_string()))?;
+        let gvk = GroupVersionKind::try_from(&types).map_err(|e| apply_error(e.to_string()))?;
+        let name = object.name_any();
+        if gvk.kind == "Namespace" {
+            continue;
+        }
+        let (resource, capabilities) = discovery::pinned_kind(client, &gvk)
+            .await
+            .map_err(|e| apply_error(format!("Unknown kind {} of '{}': {}", gvk.kind, name, e)))?;
+        if capabilities.scope != Scope::Namespaced {
+            return Err(apply_error(format!("{} '{}' is cluster-scoped and cannot be part of a preview", gvk.kind, name)));
+        }
+
+        object.metadata.namespace = Some(ns_name.to_string());
+        object.labels_mut().insert(PREVIEW_LABEL.to_string(), preview_name.to_string());
+        let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), ns_name, &resource);
+        api.patch(&name, &params, &Patch::Apply(&object))
+            .await
+            .map_err(|e| apply_error(format!("{} '{}': {}", gvk.kind, name, e)))?;
+    }
+    Ok(())
+}
+
+/// Runs `git` with `args` and returns its trimmed stdout.
+async fn run_git(args: &[&str]) -> Result<String, PreviewError> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| PreviewError::GitCloneError(e.to_string()))?;
+    if !output.status.success() {
+        return Err(PreviewError::GitCloneError(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
 /// Cleans up the resources created for a preview environment.
@@ -360,13 +861,16 @@ async fn cleanup_preview(preview: Arc<phPreview>, ctx: Arc<Context>) -> Result<A
     let ns_name = generate_namespace_name(&preview)?;
 
     // --- 2. Update Status to "Terminating" ---
-    let status = phPreviewStatus {
-        namespace: Some(ns_name.clone()),
-        conditions: vec![StatusCondition::new(
-            "Terminating".to_string(),
-            "Deleting preview environment namespace".to_string(),
-        )],
-    };
+    let status = preview_status(
+        &preview,
+        preview.status.as_ref().map(|s| s.conditions.as_slice()).unwrap_or_default(),
+        Some(ns_name.clone()),
+        ResourceState::Progressing,
+        "Terminating",
+        "Deleting preview environment namespace",
+    );
+    events::normal(&ctx.recorder, &*preview, "Terminating", "Delete", format!("Deleting namespace '{}'.", ns_name)).await;
+    let status = report_status(&ctx, &preview, status, true).await;
     update_status(preview.clone(), client.clone(), status).await?;
 
     // --- 2. Delete the Namespace ---
@@ -459,14 +963,17 @@ async fn monitor_preview_health(client: &Client, ns_name: &str) -> Result<(), St
 pub async fn on_error(preview: Arc<phPreview>, error: &PreviewError, ctx: Arc<Context>) -> Action {
     eprintln!("Reconciliation error for phPreview '{}': {:?}", preview.name_any(), error);
 
-    // When an error occurs, update the status to "Failed" with a descriptive message.
-    let failed_status = phPreviewStatus {
-        namespace: preview.status.as_ref().and_then(|s| s.namespace.clone()),
-        conditions: vec![StatusCondition::new(
-            "Failed".to_string(),
-            error.to_string(),
-        )],
-    };
+    // When an error occurs, mark the preview Degraded with a descriptive message.
+    events::warning(&ctx.recorder, &*preview, "ReconcileError", "Reconcile", error.to_string()).await;
+    let failed_status = preview_status(
+        &preview,
+        preview.status.as_ref().map(|s| s.conditions.as_slice()).unwrap_or_default(),
+        preview.status.as_ref().and_then(|s| s.namespace.clone()),
+        ResourceState::Degraded,
+        "ReconcileError",
+        &error.to_string(),
+    );
+    let failed_status = report_status(&ctx, &preview, failed_status, false).await;
 
     if let Err(e) = update_status(preview.clone(), ctx.client.clone(), failed_status).await {
         eprintln!("Failed to update status on error: {}", e);
@@ -0,0 +1,307 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/preview_guardrails.rs
+*
+* This file holds the guardrails of preview environments, set by the
+* `phPreviewClass` of each preview.
+*
+* Architecture:
+* - `guardrails` builds the objects stamped into a preview namespace: a
+*   ResourceQuota with the class's hard limits, a LimitRange with the default
+*   and maximum resources of containers, and a default-deny NetworkPolicy
+*   that only lets the pods of the namespace talk to each other and to the
+*   cluster DNS, plus the namespaces and CIDRs the class allows.
+* - `admit` decides whether a preview may start, from the previews of the
+*   whole cluster. A preview counts against the caps of its class once it
+*   has a namespace (`status.namespace`), so running previews are never
+*   evicted when a cap is lowered. Over a cap, a preview is queued, and
+*   waiting previews are admitted in order of creation, or it is rejected.
+* - Admission is decided on the state read at reconcile time, so previews
+*   created at the same instant may briefly exceed a cap.
+* - Both are pure functions; the preview controller applies their result.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::controllers::conditions;
+use crate::crds::{phPreview, phPreviewClassSpec, LimitPolicy, PreviewConcurrency};
+use k8s_openapi::api::core::v1::{LimitRange, ResourceQuota};
+use k8s_openapi::api::networking::v1::NetworkPolicy;
+use kube::ResourceExt;
+use serde_json::{json, Value};
+
+pub const QUOTA_NAME: &str = "ph-preview-quota";
+pub const LIMIT_RANGE_NAME: &str = "ph-preview-limits";
+pub const NETWORK_POLICY_NAME: &str = "ph-preview-default-deny";
+/// Marks the default phPreviewClass, as for StorageClasses.
+pub const DEFAULT_CLASS_ANNOTATION: &str = "ph.io/default-class";
+/// The reason of the conditions of a queued preview.
+pub const QUEUED_REASON: &str = "Queued";
+
+/// The objects stamped into a preview namespace. The quota and the limit
+/// range are only created when the class sets limits.
+#[derive(Debug, Clone)]
+pub struct Guardrails {
+    pub quota: Option<ResourceQuota>,
+    pub limit_range: Option<LimitRange>,
+    pub network_policy: NetworkPolicy,
+}
+
+/// Whether a preview may start.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Admission {
+    Admitted,
+    /// Waits for a slot; the message tells which limit is reached.
+    Queued(String),
+    Rejected(String),
+}
+
+/// Builds the guardrails of `namespace` for a preview of `class`.
+pub fn guardrails(class: &phPreviewClassSpec, namespace: &str) -> Guardrails {
+    let metadata = |name: &str| json!({ "name": name, "namespace": namespace });
+
+    let quota = (!class.quota.is_empty()).then(|| {
+        serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "ResourceQuota",
+            "metadata": metadata(QUOTA_NAME),
+            "spec": { "hard": class.quota },
+        }))
+        .expect("Static quota definition should not fail")
+    });
+
+    let limit_range = class.container_limits.as_ref().map(|limits| {
+        // `ContainerLimits` has the shape of a LimitRange item.
+        let mut item = serde_json::to_value(limits).expect("Limits serialize to JSON");
+        item["type"] = Value::from("Container");
+        serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "LimitRange",
+            "metadata": metadata(LIMIT_RANGE_NAME),
+            "spec": { "limits": [item] },
+        }))
+        .expect("Static limit range definition should not fail")
+    });
+
+    let mut ingress_from = vec![json!({ "podSelector": {} })];
+    for labels in &class.network.allow_from_namespaces {
+        ingress_from.push(json!({ "namespaceSelector": { "matchLabels": labels } }));
+    }
+    let mut egress = vec![
+        json!({ "to": [{ "podSelector": {} }] }),
+        json!({
+            "to": [{ "namespaceSelector": {}, "podSelector": { "matchLabels": { "k8s-app": "kube-dns" } } }],
+            "ports": [{ "protocol": "UDP", "port": 53 }, { "protocol": "TCP", "port": 53 }],
+        }),
+    ];
+    if !class.network.allow_egress_cidrs.is_empty() {
+        let blocks: Vec<Value> = class.network.allow_egress_cidrs.iter().map(|cidr| json!({ "ipBlock": { "cidr": cidr } })).collect();
+        egress.push(json!({ "to": blocks }));
+    }
+    let network_policy = serde_json::from_value(json!({
+        "apiVersion": "networking.k8s.io/v1",
+        "kind": "NetworkPolicy",
+        "metadata": metadata(NETWORK_POLICY_NAME),
+        "spec": {
+            "podSelector": {},
+            "policyTypes": ["Ingress", "Egress"],
+            "ingress": [{ "from": ingress_from }],
+            "egress": egress,
+        },
+    }))
+    .expect("Static network policy definition should not fail");
+
+    Guardrails { quota, limit_range, network_policy }
+}
+
+/// Identifies a repository whatever the form of its URL, e.g.
+/// `github.com/acme/shop` for `https://github.com/Acme/shop.git` and
+/// `git@github.com:acme/shop`.
+pub fn repository_key(repo_url: &str) -> String {
+    let url = repo_url.trim().to_lowercase();
+    let url = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    let url = url.split_once('@').map_or(url, |(_, rest)| rest);
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url).replacen(':', "/", 1)
+}
+
+/// Whether `preview` holds a slot: it has a namespace and is not deleted.
+fn is_running(preview: &phPreview) -> bool {
+    preview.metadata.deletion_timestamp.is_none() && preview.status.as_ref().is_some_and(|s| s.namespace.is_some())
+}
+
+/// Whether `preview` waits in the queue.
+fn is_queued(preview: &phPreview) -> bool {
+    preview.metadata.deletion_timestamp.is_none()
+        && preview
+            .status
+            .as_ref()
+            .and_then(|s| s.conditions.iter().find(|c| c.type_ == conditions::READY))
+            .is_some_and(|ready| ready.reason == QUEUED_REASON)
+}
+
+/// Whether `a` was created before `b`, by name for previews created in the
+/// same second.
+fn created_before(a: &phPreview, b: &phPreview) -> bool {
+    (a.creation_timestamp(), a.name_any()) < (b.creation_timestamp(), b.name_any())
+}
+
+/// Decides whether `candidate` may start, given every preview of the
+/// cluster (`previews` may include `candidate` itself).
+pub fn admit(candidate: &phPreview, previews: &[phPreview], concurrency: &PreviewConcurrency) -> Admission {
+    let Some(spec) = candidate.spec.as_ref() else {
+        return Admission::Admitted;
+    };
+    let others: Vec<&phPreview> = previews.iter().filter(|p| p.uid() != candidate.uid()).collect();
+
+    let repository = repository_key(&spec.repo_url);
+    let same_repository = |p: &phPreview| p.spec.as_ref().is_some_and(|s| repository_key(&s.repo_url) == repository);
+    let same_team = |p: &phPreview| p.spec.as_ref().and_then(|s| s.team.as_ref()).is_some_and(|t| Some(t) == spec.team.as_ref());
+
+    let limits = [
+        (concurrency.max_per_repository, format!("repository {}", repository), &same_repository as &dyn Fn(&phPreview) -> bool),
+        (
+            concurrency.max_per_team.filter(|_| spec.team.is_some()),
+            format!("team {}", spec.team.clone().unwrap_or_default()),
+            &same_team,
+        ),
+    ];
+    for (max, scope, matches) in limits {
+        let Some(max) = max else {
+            continue;
+        };
+        let running = others.iter().filter(|p| matches(p) && is_running(p)).count();
+        // Queued previews created earlier go first.
+        let ahead = match concurrency.when_exceeded {
+            LimitPolicy::Queue => others.iter().filter(|p| matches(p) && is_queued(p) && created_before(p, candidate)).count(),
+            LimitPolicy::Reject => 0,
+        };
+        if running + ahead < max as usize {
+            continue;
+        }
+        return match concurrency.when_exceeded {
+            LimitPolicy::Queue => Admission::Queued(format!(
+                "{} of {} previews of {} are running and {} are queued ahead; waiting for a slot",
+                running, max, scope, ahead
+            )),
+            LimitPolicy::Reject => {
+                Admission::Rejected(format!("{} of {} previews of {} are already running", running, max, scope))
+            }
+        };
+    }
+    Admission::Admitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preview(name: &str, created: &str, repo_url: &str, team: Option<&str>, status: Value) -> phPreview {
+        serde_json::from_value(json!({
+            "apiVersion": "ph.io/v1alpha1",
+            "kind": "phPreview",
+            "metadata": { "name": name, "namespace": "default", "uid": name, "creationTimestamp": created },
+            "spec": {
+                "repoUrl": repo_url,
+                "branch": "main",
+                "manifestPath": "k8s",
+                "appName": "shop",
+                "team": team,
+            },
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    fn running() -> Value {
+        json!({ "namespace": "preview-ns" })
+    }
+
+    fn queued() -> Value {
+        json!({ "conditions": [{ "type": "Ready", "status": "False", "reason": QUEUED_REASON }] })
+    }
+
+    #[test]
+    fn test_repository_key() {
+        assert_eq!(repository_key("https://github.com/Acme/shop.git"), "github.com/acme/shop");
+        assert_eq!(repository_key("git@github.com:acme/shop.git"), "github.com/acme/shop");
+        assert_eq!(repository_key("ssh://git@github.com/acme/shop/"), "github.com/acme/shop");
+    }
+
+    #[test]
+    fn test_guardrails() {
+        let class: phPreviewClassSpec = serde_json::from_value(json!({
+            "quota": { "requests.cpu": "2", "pods": "10" },
+            "containerLimits": { "default": { "cpu": "500m" }, "defaultRequest": { "cpu": "100m" } },
+            "network": {
+                "allowFromNamespaces": [{ "kubernetes.io/metadata.name": "ingress-nginx" }],
+                "allowEgressCidrs": ["0.0.0.0/0"]
+            }
+        }))
+        .unwrap();
+        let guardrails = guardrails(&class, "preview-pr-7-shop-1a2b3c");
+
+        let quota = serde_json::to_value(guardrails.quota.unwrap()).unwrap();
+        assert_eq!(quota["metadata"]["namespace"], "preview-pr-7-shop-1a2b3c");
+        assert_eq!(quota["spec"]["hard"], json!({ "requests.cpu": "2", "pods": "10" }));
+        let limit_range = serde_json::to_value(guardrails.limit_range.unwrap()).unwrap();
+        assert_eq!(
+            limit_range["spec"]["limits"][0],
+            json!({ "type": "Container", "default": { "cpu": "500m" }, "defaultRequest": { "cpu": "100m" } })
+        );
+
+        let policy = serde_json::to_value(guardrails.network_policy).unwrap();
+        assert_eq!(policy["spec"]["podSelector"], json!({}));
+        assert_eq!(policy["spec"]["policyTypes"], json!(["Ingress", "Egress"]));
+        assert_eq!(
+            policy["spec"]["ingress"][0]["from"],
+            json!([{ "podSelector": {} }, { "namespaceSelector": { "matchLabels": { "kubernetes.io/metadata.name": "ingress-nginx" } } }])
+        );
+        assert_eq!(policy["spec"]["egress"].as_array().unwrap().len(), 3);
+        assert_eq!(policy["spec"]["egress"][2]["to"], json!([{ "ipBlock": { "cidr": "0.0.0.0/0" } }]));
+
+        // Without limits, only the network policy is created.
+        let bare = super::guardrails(&phPreviewClassSpec::default(), "ns");
+        assert!(bare.quota.is_none() && bare.limit_range.is_none());
+        assert_eq!(serde_json::to_value(bare.network_policy).unwrap()["spec"]["egress"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_admit() {
+        let shop = "https://github.com/acme/shop.git";
+        let cap = |policy| PreviewConcurrency { max_per_repository: Some(2), max_per_team: None, when_exceeded: policy };
+        let previews = vec![
+            preview("a", "2025-06-01T10:00:00Z", shop, None, running()),
+            preview("b", "2025-06-01T10:01:00Z", "git@github.com:acme/shop", None, running()),
+            preview("c", "2025-06-01T10:02:00Z", "https://github.com/acme/other.git", None, running()),
+            preview("d", "2025-06-01T10:03:00Z", shop, None, queued()),
+            preview("e", "2025-06-01T10:04:00Z", shop, None, json!({})),
+        ];
+
+        // Two previews of the repository run, so the others wait, in order.
+        assert!(matches!(admit(&previews[3], &previews, &cap(LimitPolicy::Queue)), Admission::Queued(m) if m.contains("2 of 2 previews of repository github.com/acme/shop are running and 0")));
+        assert!(matches!(admit(&previews[4], &previews, &cap(LimitPolicy::Queue)), Admission::Queued(m) if m.contains("1 are queued ahead")));
+        assert!(matches!(admit(&previews[4], &previews, &cap(LimitPolicy::Reject)), Admission::Rejected(_)));
+        // Running previews keep their slot.
+        assert_eq!(admit(&previews[0], &previews, &cap(LimitPolicy::Queue)), Admission::Admitted);
+
+        // Once a running preview is deleted, the oldest queued one starts first.
+        let mut freed = previews.clone();
+        freed[0].metadata.deletion_timestamp = freed[2].metadata.creation_timestamp.clone();
+        assert_eq!(admit(&freed[3], &freed, &cap(LimitPolicy::Queue)), Admission::Admitted);
+        assert!(matches!(admit(&freed[4], &freed, &cap(LimitPolicy::Queue)), Admission::Queued(_)));
+
+        // Team caps count the previews of the team, across repositories.
+        let team = PreviewConcurrency { max_per_repository: None, max_per_team: Some(1), when_exceeded: LimitPolicy::Reject };
+        let teams = vec![
+            preview("a", "2025-06-01T10:00:00Z", shop, Some("payments"), running()),
+            preview("b", "2025-06-01T10:01:00Z", "https://github.com/acme/other.git", Some("payments"), json!({})),
+            preview("c", "2025-06-01T10:02:00Z", shop, Some("search"), json!({})),
+            preview("d", "2025-06-01T10:03:00Z", shop, None, json!({})),
+        ];
+        assert!(matches!(admit(&teams[1], &teams, &team), Admission::Rejected(m) if m == "1 of 1 previews of team payments are already running"));
+        assert_eq!(admit(&teams[2], &teams, &team), Admission::Admitted);
+        assert_eq!(admit(&teams[3], &teams, &team), Admission::Admitted);
+    }
+}
@@ -0,0 +1,1265 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/preview_render.rs
+*
+* This file turns the manifests of a preview's repository into the list of
+* Kubernetes objects the preview controller applies. It holds no Kubernetes
+* API logic, so the rendering rules can be tested on their own.
+*
+* Architecture:
+* - `read_plain` reads every YAML file under a directory, recursively, in
+*   path order. Multi-document files and `List` objects are flattened.
+* - `build_kustomization` builds a kustomization in-process. It supports the
+*   commonly used subset of kustomize:
+*     - `resources` (files and directories holding a kustomization; `bases`
+*       is accepted as an alias);
+*     - `configMapGenerator` and `secretGenerator` (literals, files and env
+*       files), with the content hash suffix unless disabled;
+*     - `patchesStrategicMerge`, `patches` and `patchesJson6902`: a patch is
+*       a JSON 6902 operation list or a strategic merge patch, in which lists
+*       of objects are merged by `name` (or `mountPath`, `containerPort`,
+*       `port`) and `$patch: delete` removes an item;
+*     - `namespace`, `namePrefix`, `nameSuffix`, `commonLabels`, `labels`,
+*       `commonAnnotations`, `images` and `replicas`.
+*   Renamed ConfigMaps, Secrets, Services, claims and service accounts are
+*   renamed in the references to them. Unknown fields and remote resources
+*   are rejected rather than ignored, and no file outside the repository can
+*   be read.
+* - `helm_template_args` builds the `helm template` command for a chart; the
+*   controller runs it and parses its output with `parse_documents`. Local
+*   charts with dependencies get them fetched, and `values` are escaped so
+*   that commas and backslashes reach the chart as written.
+* - `set_images` applies the image overrides of a preview, substituting
+*   `$(commitSha)` and `$(shortSha)` with the deployed commit.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::crds::{HelmRender, ImageOverride};
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The names a kustomization file may have.
+const KUSTOMIZATION_FILES: [&str; 3] = ["kustomization.yaml", "kustomization.yml", "Kustomization"];
+
+/// Kinds without a namespace, which `namespace` leaves alone.
+const CLUSTER_SCOPED_KINDS: [&str; 11] = [
+    "APIService",
+    "ClusterRole",
+    "ClusterRoleBinding",
+    "CustomResourceDefinition",
+    "IngressClass",
+    "MutatingWebhookConfiguration",
+    "Namespace",
+    "PersistentVolume",
+    "PriorityClass",
+    "StorageClass",
+    "ValidatingWebhookConfiguration",
+];
+
+/// Kinds whose pods are described by `spec.template`.
+const WORKLOAD_KINDS: [&str; 6] = ["Deployment", "StatefulSet", "DaemonSet", "ReplicaSet", "ReplicationController", "Job"];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RenderError {
+    #[error("failed to read '{path}': {message}")]
+    Read { path: String, message: String },
+
+    #[error("invalid manifest in '{origin}': {message}")]
+    InvalidManifest { origin: String, message: String },
+
+    #[error("kustomization '{path}': {message}")]
+    Kustomize { path: String, message: String },
+
+    #[error("'{0}' is outside of the repository")]
+    OutsideRepository(String),
+}
+
+/// Parses the YAML documents of `text`; `origin` names it in errors.
+pub fn parse_documents(text: &str, origin: &str) -> Result<Vec<Value>, RenderError> {
+    let invalid = |message: String| RenderError::InvalidManifest { origin: origin.to_string(), message };
+    let mut objects = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(text) {
+        let value = Value::deserialize(document).map_err(|e| invalid(e.to_string()))?;
+        match value {
+            Value::Null => {}
+            Value::Object(ref object) if object.contains_key("apiVersion") && object.contains_key("kind") => {
+                let is_list = object.get("kind").and_then(Value::as_str).is_some_and(|kind| kind.ends_with("List"));
+                match object.get("items") {
+                    Some(Value::Array(items)) if is_list => objects.extend(items.iter().cloned()),
+                    _ => objects.push(value),
+                }
+            }
+            _ => return Err(invalid("a document is not a Kubernetes object".to_string())),
+        }
+    }
+    Ok(objects)
+}
+
+/// Reads the objects of every YAML file under `dir`, in path order.
+/// Kustomization files are not objects and are skipped.
+pub fn read_plain(dir: &Path) -> Result<Vec<Value>, RenderError> {
+    let mut files = Vec::new();
+    collect_yaml_files(dir, &mut files)?;
+    files.sort();
+    let mut objects = Vec::new();
+    for file in files {
+        objects.extend(parse_documents(&read_file(&file)?, &file.display().to_string())?);
+    }
+    Ok(objects)
+}
+
+fn collect_yaml_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), RenderError> {
+    let entries = std::fs::read_dir(dir).map_err(|e| read_error(dir, e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| read_error(dir, e))?.path();
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if name.starts_with('.') || KUSTOMIZATION_FILES.contains(&name) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_yaml_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_error(path: &Path, e: std::io::Error) -> RenderError {
+    RenderError::Read { path: path.display().to_string(), message: e.to_string() }
+}
+
+fn read_file(path: &Path) -> Result<String, RenderError> {
+    std::fs::read_to_string(path).map_err(|e| read_error(path, e))
+}
+
+/// Resolves `relative` against `base`, refusing anything outside `root`
+/// (symbolic links included).
+fn resolve(root: &Path, base: &Path, relative: &str) -> Result<PathBuf, RenderError> {
+    let path = base.join(relative);
+    let canonical = path.canonicalize().map_err(|e| read_error(&path, e))?;
+    let root = root.canonicalize().map_err(|e| read_error(root, e))?;
+    if !canonical.starts_with(&root) {
+        return Err(RenderError::OutsideRepository(relative.to_string()));
+    }
+    Ok(canonical)
+}
+
+// --- Kustomize ---
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Kustomization {
+    #[serde(default, rename = "apiVersion")]
+    _api_version: Option<String>,
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    resources: Vec<String>,
+    #[serde(default)]
+    bases: Vec<String>,
+    #[serde(default)]
+    namespace: Option<String>,
+    #[serde(default)]
+    name_prefix: Option<String>,
+    #[serde(default)]
+    name_suffix: Option<String>,
+    #[serde(default)]
+    common_labels: BTreeMap<String, String>,
+    #[serde(default)]
+    labels: Vec<LabelSet>,
+    #[serde(default)]
+    common_annotations: BTreeMap<String, String>,
+    #[serde(default)]
+    images: Vec<KustomizeImage>,
+    #[serde(default)]
+    replicas: Vec<ReplicaCount>,
+    #[serde(default)]
+    patches_strategic_merge: Vec<String>,
+    #[serde(default)]
+    patches: Vec<KustomizePatch>,
+    #[serde(default, rename = "patchesJson6902")]
+    patches_json6902: Vec<KustomizePatch>,
+    #[serde(default)]
+    config_map_generator: Vec<Generator>,
+    #[serde(default)]
+    secret_generator: Vec<Generator>,
+    #[serde(default)]
+    generator_options: Option<GeneratorOptions>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct LabelSet {
+    pairs: BTreeMap<String, String>,
+    #[serde(default)]
+    include_selectors: bool,
+    #[serde(default)]
+    include_templates: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct KustomizeImage {
+    name: String,
+    #[serde(default)]
+    new_name: Option<String>,
+    #[serde(default)]
+    new_tag: Option<String>,
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReplicaCount {
+    name: String,
+    count: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KustomizePatch {
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    patch: Option<String>,
+    #[serde(default)]
+    target: Option<PatchTarget>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PatchTarget {
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    namespace: Option<String>,
+    #[serde(default)]
+    label_selector: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Generator {
+    name: String,
+    #[serde(default)]
+    namespace: Option<String>,
+    #[serde(default)]
+    behavior: Option<String>,
+    #[serde(default)]
+    literals: Vec<String>,
+    #[serde(default)]
+    files: Vec<String>,
+    #[serde(default)]
+    envs: Vec<String>,
+    #[serde(default, rename = "type")]
+    type_: Option<String>,
+    #[serde(default)]
+    options: Option<GeneratorOptions>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct GeneratorOptions {
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+    #[serde(default)]
+    disable_name_suffix_hash: bool,
+}
+
+/// A name change made by the build, applied to the references to the
+/// object.
+struct Rename {
+    kind: String,
+    from: String,
+    to: String,
+}
+
+/// Builds the kustomization in `dir`. Files may only be read below `root`.
+pub fn build_kustomization(dir: &Path, root: &Path) -> Result<Vec<Value>, RenderError> {
+    let mut stack = Vec::new();
+    let (objects, _) = build(dir, root, &mut stack)?;
+    Ok(objects)
+}
+
+fn build(dir: &Path, root: &Path, stack: &mut Vec<PathBuf>) -> Result<(Vec<Value>, Vec<Rename>), RenderError> {
+    let dir = resolve(root, dir, ".")?;
+    let error = |message: String| RenderError::Kustomize { path: dir.display().to_string(), message };
+    if stack.contains(&dir) {
+        return Err(error("the kustomization includes itself".to_string()));
+    }
+    let Some(file) = KUSTOMIZATION_FILES.iter().map(|name| dir.join(name)).find(|path| path.is_file()) else {
+        return Err(error("no kustomization.yaml found".to_string()));
+    };
+    let kustomization: Kustomization =
+        serde_yaml::from_str(&read_file(&file)?).map_err(|e| error(e.to_string()))?;
+    if kustomization.kind.as_deref().is_some_and(|kind| kind != "Kustomization") {
+        return Err(error(format!("kind '{}' is not supported", kustomization.kind.unwrap_or_default())));
+    }
+
+    stack.push(dir.clone());
+    let mut objects = Vec::new();
+    let mut renames = Vec::new();
+    for resource in kustomization.bases.iter().chain(&kustomization.resources) {
+        if resource.contains("://") || resource.starts_with("github.com/") || resource.starts_with("git@") {
+            return Err(error(format!("remote resource '{}' is not supported", resource)));
+        }
+        let path = resolve(root, &dir, resource)?;
+        if path.is_dir() {
+            let (built, built_renames) = build(&path, root, stack)?;
+            objects.extend(built);
+            renames.extend(built_renames);
+        } else {
+            objects.extend(parse_documents(&read_file(&path)?, resource)?);
+        }
+    }
+    stack.pop();
+
+    let defaults = kustomization.generator_options.clone().unwrap_or_default();
+    for (generator, kind) in kustomization
+        .config_map_generator
+        .iter()
+        .map(|g| (g, "ConfigMap"))
+        .chain(kustomization.secret_generator.iter().map(|g| (g, "Secret")))
+    {
+        let (object, rename) = generate(generator, kind, &defaults, root, &dir)?;
+        objects.push(object);
+        renames.extend(rename);
+    }
+
+    for patch_file in &kustomization.patches_strategic_merge {
+        let path = resolve(root, &dir, patch_file)?;
+        for patch in parse_patch(&read_file(&path)?, patch_file)? {
+            apply_patch(&mut objects, &patch, None).map_err(error)?;
+        }
+    }
+    for patch in kustomization.patches.iter().chain(&kustomization.patches_json6902) {
+        let (text, origin) = match (&patch.path, &patch.patch) {
+            (Some(path), None) => (read_file(&resolve(root, &dir, path)?)?, path.as_str()),
+            (None, Some(inline)) => (inline.clone(), "inline patch"),
+            _ => return Err(error("a patch needs exactly one of 'path' and 'patch'".to_string())),
+        };
+        for document in parse_patch(&text, origin)? {
+            apply_patch(&mut objects, &document, patch.target.as_ref()).map_err(error)?;
+        }
+    }
+
+    // Replica counts name the resources before the prefix and suffix.
+    for replica in &kustomization.replicas {
+        for object in objects.iter_mut().filter(|object| name_of(object) == replica.name) {
+            if matches!(kind_of(object), "Deployment" | "StatefulSet" | "ReplicaSet" | "ReplicationController") {
+                object["spec"]["replicas"] = Value::from(replica.count);
+            }
+        }
+    }
+    if let Some(namespace) = &kustomization.namespace {
+        for object in objects.iter_mut().filter(|object| !CLUSTER_SCOPED_KINDS.contains(&kind_of(object))) {
+            object["metadata"]["namespace"] = Value::from(namespace.as_str());
+        }
+    }
+    let prefix = kustomization.name_prefix.as_deref().unwrap_or_default();
+    let suffix = kustomization.name_suffix.as_deref().unwrap_or_default();
+    if !prefix.is_empty() || !suffix.is_empty() {
+        for object in objects.iter_mut().filter(|object| kind_of(object) != "Namespace") {
+            let name = name_of(object).to_string();
+            let renamed = format!("{}{}{}", prefix, name, suffix);
+            renames.push(Rename { kind: kind_of(object).to_string(), from: name, to: renamed.clone() });
+            object["metadata"]["name"] = Value::from(renamed);
+        }
+    }
+    let mut label_sets: Vec<(&BTreeMap<String, String>, bool, bool)> = kustomization
+        .labels
+        .iter()
+        .map(|set| (&set.pairs, set.include_selectors, set.include_templates || set.include_selectors))
+        .collect();
+    label_sets.push((&kustomization.common_labels, true, true));
+    for (labels, selectors, templates) in label_sets {
+        for object in &mut objects {
+            add_labels(object, labels, selectors, templates);
+        }
+    }
+    for object in &mut objects {
+        add_annotations(object, &kustomization.common_annotations);
+    }
+    let images: Vec<ImageRule> = kustomization
+        .images
+        .iter()
+        .map(|image| ImageRule {
+            name: image.name.clone(),
+            new_name: image.new_name.clone(),
+            new_tag: image.new_tag.clone(),
+            digest: image.digest.clone(),
+        })
+        .collect();
+    for object in &mut objects {
+        replace_images(object, &images);
+    }
+
+    for rename in &renames {
+        for object in &mut objects {
+            rename_references(object, rename);
+        }
+    }
+    check_duplicates(&objects).map_err(error)?;
+    Ok((objects, renames))
+}
+
+fn kind_of(object: &Value) -> &str {
+    object.get("kind").and_then(Value::as_str).unwrap_or_default()
+}
+
+fn name_of(object: &Value) -> &str {
+    object.pointer("/metadata/name").and_then(Value::as_str).unwrap_or_default()
+}
+
+fn check_duplicates(objects: &[Value]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for object in objects {
+        let namespace = object.pointer("/metadata/namespace").and_then(Value::as_str).unwrap_or_default();
+        if !seen.insert((kind_of(object), namespace, name_of(object))) {
+            return Err(format!("{} '{}' is defined more than once", kind_of(object), name_of(object)));
+        }
+    }
+    Ok(())
+}
+
+/// Creates the ConfigMap or Secret of a generator.
+fn generate(
+    generator: &Generator,
+    kind: &str,
+    defaults: &GeneratorOptions,
+    root: &Path,
+    dir: &Path,
+) -> Result<(Value, Option<Rename>), RenderError> {
+    let error = |message: String| RenderError::Kustomize { path: dir.display().to_string(), message };
+    if generator.behavior.as_deref().is_some_and(|behavior| behavior != "create") {
+        return Err(error(format!("generator '{}': only the 'create' behavior is supported", generator.name)));
+    }
+    let mut data = BTreeMap::new();
+    let mut pair = |entry: &str, origin: &str| -> Result<(), RenderError> {
+        let (key, value) = entry
+            .split_once('=')
+            .ok_or_else(|| error(format!("'{}' in {} is not a key=value pair", entry, origin)))?;
+        data.insert(key.trim().to_string(), value.trim_matches('"').to_string());
+        Ok(())
+    };
+    for literal in &generator.literals {
+        pair(literal, "literals")?;
+    }
+    for env in &generator.envs {
+        let text = read_file(&resolve(root, dir, env)?)?;
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            pair(line, env)?;
+        }
+    }
+    for file in &generator.files {
+        let (key, path) = match file.split_once('=') {
+            Some((key, path)) => (key.to_string(), path),
+            None => (Path::new(file).file_name().unwrap_or_default().to_string_lossy().into_owned(), file.as_str()),
+        };
+        data.insert(key, read_file(&resolve(root, dir, path)?)?);
+    }
+
+    let mut object = serde_json::json!({
+        "apiVersion": "v1",
+        "kind": kind,
+        "metadata": { "name": generator.name },
+    });
+    if let Some(namespace) = &generator.namespace {
+        object["metadata"]["namespace"] = Value::from(namespace.as_str());
+    }
+    if kind == "Secret" {
+        let encoded: Map<String, Value> = data
+            .into_iter()
+            .map(|(key, value)| (key, Value::from(base64::engine::general_purpose::STANDARD.encode(value))))
+            .collect();
+        object["type"] = Value::from(generator.type_.as_deref().unwrap_or("Opaque"));
+        object["data"] = Value::Object(encoded);
+    } else {
+        object["data"] = serde_json::to_value(data).unwrap_or_default();
+    }
+    let options = generator.options.as_ref().unwrap_or(defaults);
+    let labels = defaults.labels.iter().chain(&options.labels).map(|(k, v)| (k.clone(), v.clone())).collect();
+    let annotations = defaults.annotations.iter().chain(&options.annotations).map(|(k, v)| (k.clone(), v.clone())).collect();
+    add_labels(&mut object, &labels, false, false);
+    add_annotations(&mut object, &annotations);
+
+    if options.disable_name_suffix_hash || defaults.disable_name_suffix_hash {
+        return Ok((object, None));
+    }
+    // A new content gives a new name, so that workloads roll out.
+    let digest = Sha256::digest(object.to_string().as_bytes());
+    let hash: String = digest.iter().take(5).map(|byte| format!("{:02x}", byte)).collect();
+    let name = format!("{}-{}", generator.name, hash);
+    object["metadata"]["name"] = Value::from(name.clone());
+    Ok((object, Some(Rename { kind: kind.to_string(), from: generator.name.clone(), to: name })))
+}
+
+/// Parses the documents of a patch file; an operation list is one document.
+fn parse_patch(text: &str, origin: &str) -> Result<Vec<Value>, RenderError> {
+    let invalid = |message: String| RenderError::InvalidManifest { origin: origin.to_string(), message };
+    let mut documents = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(text) {
+        match Value::deserialize(document).map_err(|e| invalid(e.to_string()))? {
+            Value::Null => {}
+            value @ (Value::Object(_) | Value::Array(_)) => documents.push(value),
+            _ => return Err(invalid("a patch must be an object or a list of operations".to_string())),
+        }
+    }
+    Ok(documents)
+}
+
+/// Applies a strategic merge patch or a JSON 6902 operation list to the
+/// objects it targets.
+fn apply_patch(objects: &mut Vec<Value>, patch: &Value, target: Option<&PatchTarget>) -> Result<(), String> {
+    let own_target;
+    let target = match (target, patch) {
+        (Some(target), _) => target,
+        (None, Value::Object(_)) => {
+            own_target = PatchTarget {
+                kind: Some(kind_of(patch).to_string()),
+                name: Some(name_of(patch).to_string()),
+                ..Default::default()
+            };
+            &own_target
+        }
+        (None, _) => return Err("a JSON 6902 patch needs a target".to_string()),
+    };
+    let mut matched = false;
+    let mut deleted = Vec::new();
+    for (index, object) in objects.iter_mut().enumerate().filter(|(_, object)| target_matches(target, object)) {
+        matched = true;
+        match patch {
+            Value::Array(operations) => {
+                for operation in operations {
+                    apply_operation(object, operation)?;
+                }
+            }
+            _ => {
+                if strategic_merge(object, patch) {
+                    deleted.push(index);
+                }
+            }
+        }
+    }
+    if !matched {
+        return Err(format!(
+            "no resource matches the patch target {}/{}",
+            target.kind.as_deref().unwrap_or("*"),
+            target.name.as_deref().unwrap_or("*")
+        ));
+    }
+    for index in deleted.into_iter().rev() {
+        objects.remove(index);
+    }
+    Ok(())
+}
+
+fn target_matches(target: &PatchTarget, object: &Value) -> bool {
+    let api_version = object.get("apiVersion").and_then(Value::as_str).unwrap_or_default();
+    let (group, version) = api_version.rsplit_once('/').unwrap_or(("", api_version));
+    let namespace = object.pointer("/metadata/namespace").and_then(Value::as_str).unwrap_or_default();
+    let labels = object.pointer("/metadata/labels").and_then(Value::as_object);
+    let selector_matches = target.label_selector.as_deref().is_none_or(|selector| {
+        selector.split(',').map(str::trim).filter(|term| !term.is_empty()).all(|term| match term.split_once('=') {
+            Some((key, value)) => {
+                labels.and_then(|labels| labels.get(key.trim())).and_then(Value::as_str) == Some(value.trim())
+            }
+            None => labels.is_some_and(|labels| labels.contains_key(term)),
+        })
+    });
+    target.group.as_deref().is_none_or(|g| g == group)
+        && target.version.as_deref().is_none_or(|v| v == version)
+        && target.kind.as_deref().is_none_or(|k| k == kind_of(object))
+        && target.name.as_deref().is_none_or(|n| n == name_of(object))
+        && target.namespace.as_deref().is_none_or(|n| n == namespace)
+        && selector_matches
+}
+
+/// Merges `patch` into `target`. Returns true when the patch deletes the
+/// target (`$patch: delete`).
+fn strategic_merge(target: &mut Value, patch: &Value) -> bool {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return false;
+    };
+    if patch.get("$patch").and_then(Value::as_str) == Some("delete") {
+        return true;
+    }
+    if patch.get("$patch").and_then(Value::as_str) == Some("replace") {
+        let mut replacement = patch.clone();
+        replacement.remove("$patch");
+        *target = Value::Object(replacement);
+        return false;
+    }
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(object) = target else { unreachable!() };
+    for (key, value) in patch.iter().filter(|(key, _)| !key.starts_with('$')) {
+        match (object.get_mut(key), value) {
+            (_, Value::Null) => {
+                object.remove(key);
+            }
+            (Some(Value::Array(items)), Value::Array(patches)) => merge_lists(items, patches),
+            (Some(existing), _) => {
+                if strategic_merge(existing, value) {
+                    object.remove(key);
+                }
+            }
+            (None, _) => {
+                object.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    false
+}
+
+/// Merges two lists by the first merge key all patch items carry; other
+/// lists are replaced.
+fn merge_lists(items: &mut Vec<Value>, patches: &[Value]) {
+    let key = ["name", "mountPath", "containerPort", "port"]
+        .into_iter()
+        .find(|key| !patches.is_empty() && patches.iter().all(|patch| patch.get(key).is_some()));
+    let Some(key) = key else {
+        *items = patches.to_vec();
+        return;
+    };
+    for patch in patches {
+        match items.iter().position(|item| item.get(key) == patch.get(key)) {
+            Some(index) if strategic_merge(&mut items[index], patch) => {
+                items.remove(index);
+            }
+            Some(_) => {}
+            None if patch.get("$patch").is_none() => items.push(patch.clone()),
+            None => {}
+        }
+    }
+}
+
+/// Applies one JSON 6902 operation.
+fn apply_operation(object: &mut Value, operation: &Value) -> Result<(), String> {
+    let op = operation.get("op").and_then(Value::as_str).unwrap_or_default();
+    let path = operation.get("path").and_then(Value::as_str).ok_or("a patch operation has no 'path'")?;
+    let value = || operation.get("value").cloned().ok_or(format!("'{}' at '{}' needs a 'value'", op, path));
+    let from = || operation.get("from").and_then(Value::as_str).ok_or(format!("'{}' at '{}' needs 'from'", op, path));
+    match op {
+        "add" => pointer_add(object, path, value()?),
+        "remove" => pointer_remove(object, path).map(|_| ()),
+        "replace" => {
+            let slot = object.pointer_mut(path).ok_or(format!("path '{}' does not exist", path))?;
+            *slot = value()?;
+            Ok(())
+        }
+        "move" => {
+            let moved = pointer_remove(object, from()?)?;
+            pointer_add(object, path, moved)
+        }
+        "copy" => {
+            let copied = object.pointer(from()?).cloned().ok_or(format!("path '{}' does not exist", from()?))?;
+            pointer_add(object, path, copied)
+        }
+        "test" => match object.pointer(path) {
+            Some(current) if *current == value()? => Ok(()),
+            _ => Err(format!("test of '{}' failed", path)),
+        },
+        _ => Err(format!("unknown patch operation '{}'", op)),
+    }
+}
+
+/// Splits a JSON pointer into its parent pointer and unescaped last token.
+fn split_pointer(path: &str) -> Result<(&str, String), String> {
+    let (parent, token) = path.rsplit_once('/').ok_or(format!("invalid path '{}'", path))?;
+    Ok((parent, token.replace("~1", "/").replace("~0", "~")))
+}
+
+fn pointer_add(object: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    let (parent, token) = split_pointer(path)?;
+    match object.pointer_mut(parent) {
+        Some(Value::Object(map)) => {
+            map.insert(token, value);
+            Ok(())
+        }
+        Some(Value::Array(items)) if token == "-" => {
+            items.push(value);
+            Ok(())
+        }
+        Some(Value::Array(items)) => match token.parse::<usize>() {
+            Ok(index) if index <= items.len() => {
+                items.insert(index, value);
+                Ok(())
+            }
+            _ => Err(format!("invalid index in '{}'", path)),
+        },
+        _ => Err(format!("path '{}' does not exist", parent)),
+    }
+}
+
+fn pointer_remove(object: &mut Value, path: &str) -> Result<Value, String> {
+    let (parent, token) = split_pointer(path)?;
+    let removed = match object.pointer_mut(parent) {
+        Some(Value::Object(map)) => map.remove(&token),
+        Some(Value::Array(items)) => token.parse::<usize>().ok().filter(|&i| i < items.len()).map(|i| items.remove(i)),
+        _ => None,
+    };
+    removed.ok_or(format!("path '{}' does not exist", path))
+}
+
+/// Sets `labels` on an object and, for workloads and services, on the pod
+/// templates and selectors.
+fn add_labels(object: &mut Value, labels: &BTreeMap<String, String>, selectors: bool, templates: bool) {
+    if labels.is_empty() {
+        return;
+    }
+    let kind = kind_of(object).to_string();
+    let mut paths = vec!["/metadata/labels"];
+    if WORKLOAD_KINDS.contains(&kind.as_str()) {
+        if templates {
+            paths.push("/spec/template/metadata/labels");
+        }
+        // A Job's selector is generated by the API server.
+        if selectors && kind != "Job" {
+            paths.push("/spec/selector/matchLabels");
+        }
+    }
+    if kind == "CronJob" && templates {
+        paths.push("/spec/jobTemplate/spec/template/metadata/labels");
+    }
+    if kind == "Service" && selectors {
+        paths.push("/spec/selector");
+    }
+    for path in paths {
+        merge_string_map(object, path, labels);
+    }
+}
+
+fn add_annotations(object: &mut Value, annotations: &BTreeMap<String, String>) {
+    if annotations.is_empty() {
+        return;
+    }
+    merge_string_map(object, "/metadata/annotations", annotations);
+    if WORKLOAD_KINDS.contains(&kind_of(object)) {
+        merge_string_map(object, "/spec/template/metadata/annotations", annotations);
+    }
+}
+
+/// Adds `entries` to the map at `path`, creating the path if needed.
+fn merge_string_map(object: &mut Value, path: &str, entries: &BTreeMap<String, String>) {
+    let mut slot = &mut *object;
+    for token in path.split('/').skip(1) {
+        if !slot.is_object() {
+            *slot = Value::Object(Map::new());
+        }
+        slot = slot.as_object_mut().expect("just made an object").entry(token).or_insert(Value::Null);
+    }
+    if !slot.is_object() {
+        *slot = Value::Object(Map::new());
+    }
+    let map = slot.as_object_mut().expect("just made an object");
+    for (key, value) in entries {
+        map.insert(key.clone(), Value::from(value.as_str()));
+    }
+}
+
+/// Points the references to a renamed object at its new name.
+fn rename_references(object: &mut Value, rename: &Rename) {
+    // (kind, object key, name field) of every reference rewritten.
+    const REFERENCES: [(&str, &str, &str); 11] = [
+        ("ConfigMap", "configMap", "name"),
+        ("ConfigMap", "configMapRef", "name"),
+        ("ConfigMap", "configMapKeyRef", "name"),
+        ("Secret", "secret", "secretName"),
+        ("Secret", "secret", "name"),
+        ("Secret", "secretRef", "name"),
+        ("Secret", "secretKeyRef", "name"),
+        ("Service", "service", "name"),
+        ("PersistentVolumeClaim", "persistentVolumeClaim", "claimName"),
+        ("ServiceAccount", "subjects", "name"),
+        ("Secret", "imagePullSecrets", "name"),
+    ];
+    match object {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                for (kind, reference_key, field) in REFERENCES {
+                    if kind != rename.kind || key != reference_key {
+                        continue;
+                    }
+                    let targets: Vec<&mut Value> = match &mut *child {
+                        Value::Array(items) => items.iter_mut().collect(),
+                        other => vec![other],
+                    };
+                    for target in targets {
+                        if let Some(name) = target.get_mut(field) {
+                            if name.as_str() == Some(rename.from.as_str()) {
+                                *name = Value::from(rename.to.as_str());
+                            }
+                        }
+                    }
+                }
+                let direct = match key.as_str() {
+                    "serviceAccountName" => rename.kind == "ServiceAccount",
+                    "serviceName" => rename.kind == "Service",
+                    _ => false,
+                };
+                if direct && child.as_str() == Some(rename.from.as_str()) {
+                    *child = Value::from(rename.to.as_str());
+                } else {
+                    rename_references(child, rename);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                rename_references(item, rename);
+            }
+        }
+        _ => {}
+    }
+}
+
+// --- Images ---
+
+/// A change to the image of containers, from kustomize `images` or a
+/// preview's image overrides.
+struct ImageRule {
+    name: String,
+    new_name: Option<String>,
+    new_tag: Option<String>,
+    digest: Option<String>,
+}
+
+/// Splits an image reference into its name, tag and digest.
+fn split_image(image: &str) -> (&str, Option<&str>, Option<&str>) {
+    let (rest, digest) = match image.split_once('@') {
+        Some((rest, digest)) => (rest, Some(digest)),
+        None => (image, None),
+    };
+    match rest.rfind(':') {
+        // A colon before the last slash belongs to a registry port.
+        Some(colon) if !rest[colon..].contains('/') => (&rest[..colon], Some(&rest[colon + 1..]), digest),
+        _ => (rest, None, digest),
+    }
+}
+
+fn replace_images(object: &mut Value, rules: &[ImageRule]) {
+    if rules.is_empty() {
+        return;
+    }
+    match object {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let is_containers = matches!(key.as_str(), "containers" | "initContainers" | "ephemeralContainers");
+                if let (true, Value::Array(containers)) = (is_containers, &mut *child) {
+                    for container in containers.iter_mut() {
+                        if let Some(Value::String(image)) = container.get_mut("image") {
+                            *image = replace_image(image, rules);
+                        }
+                    }
+                }
+                replace_images(child, rules);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                replace_images(item, rules);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn replace_image(image: &str, rules: &[ImageRule]) -> String {
+    let (name, tag, digest) = split_image(image);
+    let Some(rule) = rules.iter().find(|rule| rule.name == name) else {
+        return image.to_string();
+    };
+    let name = rule.new_name.as_deref().unwrap_or(name);
+    if let Some(digest) = &rule.digest {
+        return format!("{}@{}", name, digest);
+    }
+    if let Some(tag) = &rule.new_tag {
+        return format!("{}:{}", name, tag);
+    }
+    // Only the name changes; the tag and digest stay.
+    let tag = tag.map(|tag| format!(":{}", tag)).unwrap_or_default();
+    let digest = digest.map(|digest| format!("@{}", digest)).unwrap_or_default();
+    format!("{}{}{}", name, tag, digest)
+}
+
+/// Replaces `$(commitSha)` and `$(shortSha)` in `text`.
+pub fn substitute_commit(text: &str, commit: &str) -> String {
+    let short = &commit[..commit.len().min(7)];
+    text.replace("$(commitSha)", commit).replace("$(shortSha)", short)
+}
+
+/// Applies the image overrides of a preview to the rendered objects.
+pub fn set_images(objects: &mut [Value], overrides: &[ImageOverride], commit: &str) {
+    let rules: Vec<ImageRule> = overrides
+        .iter()
+        .map(|image| ImageRule {
+            name: image.name.clone(),
+            new_name: image.new_name.clone(),
+            new_tag: Some(substitute_commit(image.new_tag.as_deref().unwrap_or("$(commitSha)"), commit)),
+            digest: None,
+        })
+        .collect();
+    for object in objects {
+        replace_images(object, &rules);
+    }
+}
+
+// --- Helm ---
+
+/// The parts of a `Chart.yaml` that change how a chart is rendered.
+#[derive(Deserialize, Default)]
+struct ChartFile {
+    #[serde(default)]
+    dependencies: Vec<Value>,
+}
+
+/// Whether the local chart in `dir` declares dependencies, in its
+/// `Chart.yaml` or, for `apiVersion: v1` charts, in `requirements.yaml`.
+fn has_dependencies(dir: &Path) -> Result<bool, RenderError> {
+    if dir.join("requirements.yaml").is_file() {
+        return Ok(true);
+    }
+    let path = dir.join("Chart.yaml");
+    if !path.is_file() {
+        // Not a chart; `helm template` reports it.
+        return Ok(false);
+    }
+    let chart: Option<ChartFile> = serde_yaml::from_str(&read_file(&path)?).map_err(|e| RenderError::InvalidManifest {
+        origin: path.display().to_string(),
+        message: e.to_string(),
+    })?;
+    Ok(chart.is_some_and(|chart| !chart.dependencies.is_empty()))
+}
+
+/// Escapes a value for `helm --set`, which splits assignments at commas and
+/// treats backslashes as escapes.
+fn escape_set_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,")
+}
+
+/// The arguments of the `helm template` command rendering `helm` into
+/// `namespace`. Local charts and values files must be in `root`; their
+/// dependencies are fetched first when missing.
+pub fn helm_template_args(
+    helm: &HelmRender,
+    root: &Path,
+    manifest_path: &str,
+    app_name: &str,
+    namespace: &str,
+    commit: &str,
+) -> Result<Vec<String>, RenderError> {
+    let release = helm.release_name.as_deref().unwrap_or(app_name);
+    let mut dependency_update = false;
+    let chart = match (&helm.repo, &helm.chart) {
+        (Some(_), Some(chart)) => chart.clone(),
+        (Some(_), None) => {
+            return Err(RenderError::InvalidManifest {
+                origin: "helm".to_string(),
+                message: "a chart of a repository needs 'chart'".to_string(),
+            })
+        }
+        (None, chart) => {
+            let dir = resolve(root, root, chart.as_deref().unwrap_or(manifest_path))?;
+            dependency_update = has_dependencies(&dir)?;
+            dir.display().to_string()
+        }
+    };
+    let mut args = vec![
+        "template".to_string(),
+        release.to_string(),
+        chart,
+        "--namespace".to_string(),
+        namespace.to_string(),
+    ];
+    if let Some(repo) = &helm.repo {
+        args.extend(["--repo".to_string(), repo.clone()]);
+    }
+    if let Some(version) = &helm.version {
+        args.extend(["--version".to_string(), version.clone()]);
+    }
+    if dependency_update {
+        args.push("--dependency-update".to_string());
+    }
+    for values_file in &helm.values_files {
+        args.extend(["--values".to_string(), resolve(root, root, values_file)?.display().to_string()]);
+    }
+    for (key, value) in &helm.values {
+        args.extend(["--set".to_string(), format!("{}={}", key, escape_set_value(&substitute_commit(value, commit)))]);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write(dir: &Path, path: &str, content: &str) {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    const DEPLOYMENT: &str = "apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+spec:
+  replicas: 3
+  selector:
+    matchLabels:
+      app: web
+  template:
+    metadata:
+      labels:
+        app: web
+    spec:
+      serviceAccountName: web
+      containers:
+        - name: web
+          image: ghcr.io/acme/web:1.0
+          envFrom:
+            - configMapRef:
+                name: settings
+        - name: proxy
+          image: envoyproxy/envoy:v1.29
+";
+
+    #[test]
+    fn test_parse_documents() {
+        let text = "---\napiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: a\n---\n\
+                    apiVersion: v1\nkind: List\nitems:\n  - apiVersion: v1\n    kind: Secret\n    metadata:\n      name: b\n";
+        let objects = parse_documents(text, "test").unwrap();
+        assert_eq!(objects.iter().map(name_of).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert!(parse_documents("just text", "test").is_err());
+    }
+
+    #[test]
+    fn test_read_plain_is_recursive() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "deployment.yaml", DEPLOYMENT);
+        write(dir.path(), "nested/service.yml", "apiVersion: v1\nkind: Service\nmetadata:\n  name: web\n");
+        write(dir.path(), "nested/kustomization.yaml", "resources: []\n");
+        write(dir.path(), "README.md", "not a manifest");
+        let objects = read_plain(dir.path()).unwrap();
+        assert_eq!(objects.iter().map(kind_of).collect::<Vec<_>>(), vec!["Deployment", "Service"]);
+    }
+
+    #[test]
+    fn test_build_kustomization() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "base/deployment.yaml", DEPLOYMENT);
+        write(dir.path(), "base/kustomization.yaml", "resources:\n  - deployment.yaml\n");
+        write(dir.path(), "overlays/preview/app.env", "# comment\nLOG_LEVEL=debug\n");
+        write(
+            dir.path(),
+            "overlays/preview/kustomization.yaml",
+            "resources:
+  - ../../base
+namePrefix: pr-
+commonLabels:
+  env: preview
+commonAnnotations:
+  owner: team-a
+configMapGenerator:
+  - name: settings
+    envs: [app.env]
+    literals: [MODE=preview]
+images:
+  - name: ghcr.io/acme/web
+    newTag: abc123
+replicas:
+  - name: web
+    count: 1
+patches:
+  - target:
+      kind: Deployment
+      name: web
+    patch: |
+      - op: add
+        path: /spec/template/spec/containers/0/args
+        value: [--verbose]
+  - patch: |
+      apiVersion: apps/v1
+      kind: Deployment
+      metadata:
+        name: web
+      spec:
+        template:
+          spec:
+            containers:
+              - name: proxy
+                $patch: delete
+              - name: web
+                resources:
+                  limits:
+                    memory: 256Mi
+",
+        );
+        let objects = build_kustomization(&dir.path().join("overlays/preview"), dir.path()).unwrap();
+        assert_eq!(objects.len(), 2);
+
+        let deployment = objects.iter().find(|o| kind_of(o) == "Deployment").unwrap();
+        assert_eq!(name_of(deployment), "pr-web");
+        assert_eq!(deployment["spec"]["replicas"], json!(1));
+        assert_eq!(deployment["metadata"]["labels"]["env"], json!("preview"));
+        assert_eq!(deployment["metadata"]["annotations"]["owner"], json!("team-a"));
+        assert_eq!(deployment["spec"]["selector"]["matchLabels"], json!({ "app": "web", "env": "preview" }));
+        assert_eq!(deployment["spec"]["template"]["metadata"]["labels"]["env"], json!("preview"));
+        let containers = deployment["spec"]["template"]["spec"]["containers"].as_array().unwrap();
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0]["image"], json!("ghcr.io/acme/web:abc123"));
+        assert_eq!(containers[0]["args"], json!(["--verbose"]));
+        assert_eq!(containers[0]["resources"]["limits"]["memory"], json!("256Mi"));
+
+        let config_map = objects.iter().find(|o| kind_of(o) == "ConfigMap").unwrap();
+        let name = name_of(config_map);
+        assert!(name.starts_with("pr-settings-"), "{}", name);
+        assert_eq!(config_map["data"], json!({ "LOG_LEVEL": "debug", "MODE": "preview" }));
+        assert_eq!(containers[0]["envFrom"][0]["configMapRef"]["name"], json!(name));
+    }
+
+    #[test]
+    fn test_kustomization_is_confined() {
+        let outside = tempfile::tempdir().unwrap();
+        write(outside.path(), "token", "s3cret");
+        let dir = tempfile::tempdir().unwrap();
+        let token = outside.path().join("token");
+        write(
+            dir.path(),
+            "kustomization.yaml",
+            &format!("secretGenerator:\n  - name: leak\n    files: [{}]\n", token.display()),
+        );
+        assert_eq!(
+            build_kustomization(dir.path(), dir.path()),
+            Err(RenderError::OutsideRepository(token.display().to_string()))
+        );
+
+        write(dir.path(), "kustomization.yaml", "resources:\n  - https://github.com/acme/base\n");
+        assert!(build_kustomization(dir.path(), dir.path()).is_err());
+        write(dir.path(), "kustomization.yaml", "helmCharts: []\n");
+        assert!(build_kustomization(dir.path(), dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_set_images() {
+        let mut objects = parse_documents(DEPLOYMENT, "test").unwrap();
+        let overrides = vec![ImageOverride {
+            name: "ghcr.io/acme/web".to_string(),
+            new_name: None,
+            new_tag: Some("sha-$(shortSha)".to_string()),
+        }];
+        set_images(&mut objects, &overrides, "0123456789abcdef");
+        let containers = &objects[0]["spec"]["template"]["spec"]["containers"];
+        assert_eq!(containers[0]["image"], json!("ghcr.io/acme/web:sha-0123456"));
+        assert_eq!(containers[1]["image"], json!("envoyproxy/envoy:v1.29"));
+
+        assert_eq!(split_image("localhost:5000/web:1.0"), ("localhost:5000/web", Some("1.0"), None));
+        assert_eq!(split_image("localhost:5000/web"), ("localhost:5000/web", None, None));
+        assert_eq!(split_image("web@sha256:ff"), ("web", None, Some("sha256:ff")));
+    }
+
+    #[test]
+    fn test_helm_template_args() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "charts/shop/Chart.yaml", "name: shop\n");
+        write(dir.path(), "charts/shop/values-preview.yaml", "replicas: 1\n");
+        let helm = HelmRender {
+            values_files: vec!["charts/shop/values-preview.yaml".to_string()],
+            values: BTreeMap::from([("image.tag".to_string(), "$(commitSha)".to_string())]),
+            ..Default::default()
+        };
+        let args = helm_template_args(&helm, dir.path(), "charts/shop", "shop", "preview-pr-7", "abc123").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "template".to_string(),
+                "shop".to_string(),
+                root.join("charts/shop").display().to_string(),
+                "--namespace".to_string(),
+                "preview-pr-7".to_string(),
+                "--values".to_string(),
+                root.join("charts/shop/values-preview.yaml").display().to_string(),
+                "--set".to_string(),
+                "image.tag=abc123".to_string(),
+            ]
+        );
+        let remote = HelmRender { repo: Some("https://charts.acme.dev".to_string()), ..Default::default() };
+        assert!(helm_template_args(&remote, dir.path(), "k8s", "shop", "ns", "abc").is_err());
+    }
+
+    #[test]
+    fn test_helm_chart_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "charts/shop/Chart.yaml",
+            "apiVersion: v2\nname: shop\ndependencies:\n  - name: redis\n    version: 18.x\n    repository: https://charts.bitnami.com/bitnami\n",
+        );
+        write(dir.path(), "charts/legacy/Chart.yaml", "apiVersion: v1\nname: legacy\n");
+        write(dir.path(), "charts/legacy/requirements.yaml", "dependencies:\n  - name: redis\n");
+        write(dir.path(), "charts/plain/Chart.yaml", "apiVersion: v2\nname: plain\n");
+        let helm = HelmRender::default();
+        let dependency_update =
+            |chart: &str| helm_template_args(&helm, dir.path(), chart, "shop", "ns", "abc").unwrap().contains(&"--dependency-update".to_string());
+        assert!(dependency_update("charts/shop"));
+        assert!(dependency_update("charts/legacy"));
+        assert!(!dependency_update("charts/plain"));
+
+        // A chart of a repository brings its dependencies.
+        let remote = HelmRender {
+            repo: Some("https://charts.acme.dev".to_string()),
+            chart: Some("shop".to_string()),
+            ..Default::default()
+        };
+        let args = helm_template_args(&remote, dir.path(), "k8s", "shop", "ns", "abc").unwrap();
+        assert!(!args.contains(&"--dependency-update".to_string()));
+    }
+
+    #[test]
+    fn test_helm_set_values() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "chart/Chart.yaml", "apiVersion: v2\nname: shop\n");
+        let helm = HelmRender {
+            values: BTreeMap::from([
+                ("env.ALLOWED_ORIGINS".to_string(), "https://a.acme.dev,https://b.acme.dev".to_string()),
+                ("env.SHARE".to_string(), "\\\\files\\$(shortSha)".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let args = helm_template_args(&helm, dir.path(), "chart", "shop", "ns", "0123456789").unwrap();
+        let sets: Vec<&str> = args.iter().skip_while(|arg| *arg != "--set").map(String::as_str).collect();
+        // Commas do not split the value into several assignments, and
+        // backslashes are kept.
+        assert_eq!(
+            sets,
+            vec![
+                "--set",
+                "env.ALLOWED_ORIGINS=https://a.acme.dev\\,https://b.acme.dev",
+                "--set",
+                "env.SHARE=\\\\\\\\files\\\\0123456",
+            ]
+        );
+    }
+}
@@ -0,0 +1,398 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/preview_report.rs
+*
+* This file reports the state of a phPreview back to its pull request, so that
+* reviewers find the preview from the pull request itself.
+*
+* Architecture:
+* - A preview with `spec.report` and a `prNumber` is reported through the Git
+*   provider API of the `api_client` module: a commit status on the deployed
+*   commit (pending, success or failure, linking to the preview URL) and a
+*   single "sticky" comment on the pull request, edited in place, with the
+*   URL, the state, the health of the pods and the expiry of the preview.
+* - `report.apiUrl` selects the server, which lets a GitHub Enterprise server
+*   or a local mock server stand in for github.com. The token is read from
+*   the Secret key `report.tokenSecretRef`.
+* - The preview controller reports on every reconcile. A digest of the last
+*   report is kept in `status.reported`, and nothing is posted while the
+*   report is unchanged.
+* - The summary is built from plain data (pods, ingresses) by pure functions,
+*   separately from the API calls.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::controllers::conditions::ResourceState;
+use crate::controllers::preview_schedule::SLEEPING_REASON;
+use crate::crds::{phPreview, PreviewReport};
+use api_client::github_handler::{ApiProvider, CommitState, CommitStatus, GitHubHandler};
+use chrono::{DateTime, Utc};
+use k8s_openapi::api::core::v1::{Pod, Secret};
+use k8s_openapi::api::networking::v1::Ingress;
+use kube::{
+    api::{Api, ListParams},
+    client::Client,
+    ResourceExt,
+};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// The API of github.com.
+const DEFAULT_API_URL: &str = "https://api.github.com";
+/// The default context of the commit status.
+pub const DEFAULT_CONTEXT: &str = "ph/preview";
+
+#[derive(Debug, Error)]
+pub enum ReportError {
+    #[error("Cannot tell the repository of '{0}'; set report.repository")]
+    UnknownRepository(String),
+
+    #[error("Key '{key}' of Secret '{name}' was not found")]
+    TokenNotFound { name: String, key: String },
+
+    #[error("Kubernetes API error: {0}")]
+    Kube(#[from] kube::Error),
+
+    #[error("Git provider API error: {0}")]
+    Provider(String),
+}
+
+/// The health of the pods of a preview.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PodHealth {
+    pub ready: usize,
+    pub total: usize,
+    /// The pods that are not ready, with the reason when known.
+    pub failing: Vec<String>,
+}
+
+/// Everything reported about a preview.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewSummary {
+    /// `<namespace>/<name>` of the phPreview.
+    pub preview: String,
+    pub namespace: Option<String>,
+    pub url: Option<String>,
+    pub state: ResourceState,
+    pub reason: String,
+    pub message: String,
+    pub commit: Option<String>,
+    pub pods: PodHealth,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The preview is being deleted.
+    pub deleted: bool,
+}
+
+/// Reads `owner/name` from the URL of a repository, for HTTPS
+/// (`https://github.com/acme/shop.git`) and SSH (`git@github.com:acme/shop.git`)
+/// URLs.
+pub fn parse_repository(repo_url: &str) -> Option<(String, String)> {
+    let path = match repo_url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        None => repo_url.split_once(':')?.1,
+    };
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    match path.split('/').collect::<Vec<_>>().as_slice() {
+        [owner, name] if !owner.is_empty() && !name.is_empty() => Some((owner.to_string(), name.to_string())),
+        _ => None,
+    }
+}
+
+/// The URL of the first host of the ingresses, over HTTPS when the host has
+/// TLS configured.
+pub fn ingress_url(ingresses: &[Ingress]) -> Option<String> {
+    ingresses.iter().filter_map(|ingress| ingress.spec.as_ref()).find_map(|spec| {
+        let host = spec.rules.as_ref()?.iter().find_map(|rule| rule.host.clone())?;
+        let tls = spec.tls.iter().flatten().any(|tls| tls.hosts.iter().flatten().any(|h| *h == host));
+        Some(format!("{}://{}", if tls { "https" } else { "http" }, host))
+    })
+}
+
+/// Counts the ready pods. Pods that completed successfully are not counted.
+pub fn pod_health(pods: &[Pod]) -> PodHealth {
+    let mut health = PodHealth::default();
+    for pod in pods {
+        let status = pod.status.clone().unwrap_or_default();
+        if status.phase.as_deref() == Some("Succeeded") {
+            continue;
+        }
+        health.total += 1;
+        let ready = status
+            .conditions
+            .iter()
+            .flatten()
+            .any(|condition| condition.type_ == "Ready" && condition.status == "True");
+        if ready {
+            health.ready += 1;
+            continue;
+        }
+        let reason = status
+            .container_statuses
+            .iter()
+            .flatten()
+            .filter(|cs| !cs.ready)
+            .find_map(|cs| {
+                let state = cs.state.as_ref()?;
+                state
+                    .waiting
+                    .as_ref()
+                    .and_then(|w| w.reason.clone())
+                    .or_else(|| state.terminated.as_ref().and_then(|t| t.reason.clone()))
+            })
+            .or(status.reason)
+            .or(status.phase);
+        health.failing.push(match reason {
+            Some(reason) => format!("{} ({})", pod.name_any(), reason),
+            None => pod.name_any(),
+        });
+    }
+    health
+}
+
+/// The hidden marker identifying the comment of a preview.
+pub fn comment_marker(preview: &str) -> String {
+    format!("<!-- ph-preview:{} -->", preview)
+}
+
+/// The commit status of a preview.
+pub fn commit_status(summary: &PreviewSummary, context: &str) -> CommitStatus {
+    let state = match summary.state {
+        ResourceState::Ready => CommitState::Success,
+        ResourceState::Progressing => CommitState::Pending,
+        ResourceState::Degraded => CommitState::Failure,
+    };
+    let description = match (summary.state, &summary.url) {
+        (ResourceState::Ready, Some(url)) => format!("Preview ready at {}", url),
+        (ResourceState::Ready, None) => "Preview ready".to_string(),
+        _ => format!("{}: {}", summary.reason, summary.message),
+    };
+    CommitStatus {
+        state,
+        context: context.to_string(),
+        description,
+        target_url: summary.url.clone(),
+    }
+}
+
+/// The body of the sticky comment of a preview. It holds no timestamp other
+/// than the expiry, so that an unchanged preview yields the same comment.
+pub fn comment_body(summary: &PreviewSummary) -> String {
+    let name = summary.preview.rsplit('/').next().unwrap_or(&summary.preview);
+    let state = match (summary.deleted, summary.state) {
+        (true, _) => "🗑️ Deleted".to_string(),
+        (false, ResourceState::Ready) if summary.reason == SLEEPING_REASON => "💤 Sleeping".to_string(),
+        (false, ResourceState::Ready) => format!("✅ Ready ({})", summary.reason),
+        (false, ResourceState::Progressing) => format!("⏳ In progress ({})", summary.reason),
+        (false, ResourceState::Degraded) => format!("❌ Failing ({})", summary.reason),
+    };
+    let mut rows = vec![("Status", state)];
+    if !summary.deleted {
+        rows.push(("URL", summary.url.clone().unwrap_or_else(|| "_No ingress yet_".to_string())));
+    }
+    if let Some(commit) = &summary.commit {
+        rows.push(("Commit", format!("`{}`", commit)));
+    }
+    if let Some(namespace) = &summary.namespace {
+        rows.push(("Namespace", format!("`{}`", namespace)));
+    }
+    if !summary.deleted {
+        rows.push(("Pods", format!("{}/{} ready", summary.pods.ready, summary.pods.total)));
+        if let Some(expires_at) = summary.expires_at {
+            rows.push(("Expires", expires_at.format("%Y-%m-%d %H:%M UTC").to_string()));
+        }
+    }
+
+    let mut body = format!("{}\n### Preview environment `{}`\n\n| | |\n|---|---|\n", comment_marker(&summary.preview), name);
+    for (label, value) in rows {
+        body.push_str(&format!("| **{}** | {} |\n", label, value));
+    }
+    if !summary.deleted && !summary.message.is_empty() {
+        body.push_str(&format!("\n> {}\n", summary.message.replace('\n', " ")));
+    }
+    if !summary.deleted && !summary.pods.failing.is_empty() {
+        body.push_str("\nPods not ready:\n");
+        for pod in &summary.pods.failing {
+            body.push_str(&format!("- `{}`\n", pod));
+        }
+    }
+    body
+}
+
+/// A digest of what `report` posts, to skip unchanged reports.
+pub fn report_digest(summary: &PreviewSummary, report: &PreviewReport) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(comment_body(summary));
+    if !summary.deleted {
+        let status = commit_status(summary, report.context.as_deref().unwrap_or(DEFAULT_CONTEXT));
+        hasher.update(format!("{:?}", status));
+    }
+    let digest = hasher.finalize();
+    digest.iter().take(12).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Reads the URL and the pod health of the preview namespace.
+pub async fn observe_namespace(client: &Client, namespace: &str) -> Result<(Option<String>, PodHealth), ReportError> {
+    let ingresses: Api<Ingress> = Api::namespaced(client.clone(), namespace);
+    let pods: Api<Pod> = Api::namespaced(client.clone(), namespace);
+    let url = ingress_url(&ingresses.list(&ListParams::default()).await?.items);
+    let health = pod_health(&pods.list(&ListParams::default()).await?.items);
+    Ok((url, health))
+}
+
+/// Posts `summary` to the pull request of `preview`: the commit status (unless
+/// the preview is being deleted) and the sticky comment.
+pub async fn report(client: &Client, preview: &phPreview, report: &PreviewReport, summary: &PreviewSummary) -> Result<(), ReportError> {
+    let Some(spec) = preview.spec.as_ref() else {
+        return Ok(());
+    };
+    let Some(number) = spec.pr_number else {
+        return Ok(());
+    };
+    let (owner, repo) = match &report.repository {
+        Some(repository) => repository.split_once('/').map(|(o, r)| (o.to_string(), r.to_string())),
+        None => parse_repository(&spec.repo_url),
+    }
+    .ok_or_else(|| ReportError::UnknownRepository(report.repository.clone().unwrap_or_else(|| spec.repo_url.clone())))?;
+
+    let ns = preview.namespace().unwrap_or_default();
+    let secrets: Api<Secret> = Api::namespaced(client.clone(), &ns);
+    let token_ref = &report.token_secret_ref;
+    let token = secrets
+        .get_opt(&token_ref.name)
+        .await?
+        .and_then(|secret| secret.data?.remove(&token_ref.key))
+        .map(|value| String::from_utf8_lossy(&value.0).trim().to_string())
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| ReportError::TokenNotFound { name: token_ref.name.clone(), key: token_ref.key.clone() })?;
+
+    let provider: Box<dyn ApiProvider> =
+        Box::new(GitHubHandler::with_endpoint(report.api_url.as_deref().unwrap_or(DEFAULT_API_URL), Some(token)));
+    let provider_error = |e: api_client::github_handler::ProviderError| ReportError::Provider(e.to_string());
+
+    if let (false, Some(commit)) = (summary.deleted, &summary.commit) {
+        let status = commit_status(summary, report.context.as_deref().unwrap_or(DEFAULT_CONTEXT));
+        provider.set_commit_status(&owner, &repo, commit, &status).await.map_err(provider_error)?;
+    }
+    provider
+        .upsert_pull_request_comment(&owner, &repo, number, &comment_marker(&summary.preview), &comment_body(summary))
+        .await
+        .map_err(provider_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn summary() -> PreviewSummary {
+        PreviewSummary {
+            preview: "default/shop-pr-7".to_string(),
+            namespace: Some("preview-pr-7-shop-1a2b3c".to_string()),
+            url: Some("https://shop-pr-7.preview.example.com".to_string()),
+            state: ResourceState::Ready,
+            reason: "Deployed".to_string(),
+            message: "Commit abc123 applied and resources are healthy".to_string(),
+            commit: Some("abc123".to_string()),
+            pods: PodHealth { ready: 2, total: 2, failing: Vec::new() },
+            expires_at: Some(DateTime::parse_from_rfc3339("2025-06-02T10:30:00Z").unwrap().with_timezone(&Utc)),
+            deleted: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_repository() {
+        let parsed = |url| parse_repository(url).map(|(o, r)| format!("{}/{}", o, r));
+        assert_eq!(parsed("https://github.com/acme/shop.git").as_deref(), Some("acme/shop"));
+        assert_eq!(parsed("https://github.example.com/acme/shop").as_deref(), Some("acme/shop"));
+        assert_eq!(parsed("git@github.com:acme/shop.git").as_deref(), Some("acme/shop"));
+        assert_eq!(parsed("ssh://git@github.com/acme/shop.git").as_deref(), Some("acme/shop"));
+        assert_eq!(parsed("https://gitlab.com/group/sub/shop.git"), None);
+        assert_eq!(parsed("shop"), None);
+    }
+
+    #[test]
+    fn test_ingress_url_and_pod_health() {
+        let ingresses: Vec<Ingress> = serde_json::from_value(json!([
+            { "spec": { "rules": [{ "http": {} }] } },
+            { "spec": {
+                "rules": [{ "host": "shop.preview.example.com" }],
+                "tls": [{ "hosts": ["shop.preview.example.com"] }]
+            } }
+        ]))
+        .unwrap();
+        assert_eq!(ingress_url(&ingresses).as_deref(), Some("https://shop.preview.example.com"));
+        let plain: Vec<Ingress> = serde_json::from_value(json!([{ "spec": { "rules": [{ "host": "shop.local" }] } }])).unwrap();
+        assert_eq!(ingress_url(&plain).as_deref(), Some("http://shop.local"));
+        assert_eq!(ingress_url(&[]), None);
+
+        let pods: Vec<Pod> = serde_json::from_value(json!([
+            { "metadata": { "name": "web" }, "status": { "phase": "Running",
+                "conditions": [{ "type": "Ready", "status": "True" }] } },
+            { "metadata": { "name": "worker" }, "status": { "phase": "Running",
+                "conditions": [{ "type": "Ready", "status": "False" }],
+                "containerStatuses": [{ "name": "worker", "image": "worker", "imageID": "", "ready": false,
+                    "restartCount": 3, "state": { "waiting": { "reason": "CrashLoopBackOff" } } }] } },
+            { "metadata": { "name": "migrate" }, "status": { "phase": "Succeeded" } },
+            { "metadata": { "name": "db" }, "status": { "phase": "Pending" } }
+        ]))
+        .unwrap();
+        assert_eq!(
+            pod_health(&pods),
+            PodHealth { ready: 1, total: 3, failing: vec!["worker (CrashLoopBackOff)".to_string(), "db (Pending)".to_string()] }
+        );
+    }
+
+    #[test]
+    fn test_report_contents() {
+        let ready = summary();
+        let status = commit_status(&ready, DEFAULT_CONTEXT);
+        assert_eq!(status.state, CommitState::Success);
+        assert_eq!(status.description, "Preview ready at https://shop-pr-7.preview.example.com");
+        assert_eq!(status.target_url.as_deref(), Some("https://shop-pr-7.preview.example.com"));
+
+        let body = comment_body(&ready);
+        assert!(body.starts_with("<!-- ph-preview:default/shop-pr-7 -->\n### Preview environment `shop-pr-7`"));
+        assert!(body.contains("| **Status** | ✅ Ready (Deployed) |"));
+        assert!(body.contains("| **URL** | https://shop-pr-7.preview.example.com |"));
+        assert!(body.contains("| **Pods** | 2/2 ready |"));
+        assert!(body.contains("| **Expires** | 2025-06-02 10:30 UTC |"));
+
+        let failing = PreviewSummary {
+            state: ResourceState::Degraded,
+            reason: "Unhealthy".to_string(),
+            message: "Pod 'worker' is not ready. Reason: CrashLoopBackOff".to_string(),
+            pods: PodHealth { ready: 1, total: 2, failing: vec!["worker (CrashLoopBackOff)".to_string()] },
+            ..summary()
+        };
+        assert_eq!(commit_status(&failing, "ci/preview").state, CommitState::Failure);
+        assert_eq!(commit_status(&failing, "ci/preview").context, "ci/preview");
+        let body = comment_body(&failing);
+        assert!(body.contains("| **Status** | ❌ Failing (Unhealthy) |"));
+        assert!(body.contains("> Pod 'worker' is not ready. Reason: CrashLoopBackOff"));
+        assert!(body.contains("- `worker (CrashLoopBackOff)`"));
+
+        let deleted = comment_body(&PreviewSummary { deleted: true, ..summary() });
+        assert!(deleted.contains("🗑️ Deleted"));
+        assert!(!deleted.contains("**URL**"));
+
+        let report: PreviewReport = serde_json::from_value(json!({
+            "tokenSecretRef": { "name": "github", "key": "token" }
+        }))
+        .unwrap();
+        assert_eq!(report_digest(&ready, &report), report_digest(&summary(), &report));
+        assert_ne!(report_digest(&ready, &report), report_digest(&failing, &report));
+
+        let sleeping = PreviewSummary {
+            reason: SLEEPING_REASON.to_string(),
+            message: "Scaled to zero outside working hours".to_string(),
+            pods: PodHealth::default(),
+            ..summary()
+        };
+        assert!(comment_body(&sleeping).contains("| **Status** | 💤 Sleeping |"));
+        assert_eq!(commit_status(&sleeping, DEFAULT_CONTEXT).state, CommitState::Success);
+    }
+}
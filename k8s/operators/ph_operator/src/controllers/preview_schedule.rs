@@ -0,0 +1,214 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/preview_schedule.rs
+*
+* This file holds the time rules of preview environments: their expiry and
+* their working hours.
+*
+* Architecture:
+* - `expiry` computes when a preview expires: at `expiresAt` when it is set
+*   (`ph preview extend` sets it), else after `ttl` ("36h", "7d") or the
+*   older `ttlHours`, counted from the creation of the preview. The preview
+*   controller deletes expired previews.
+* - `WorkingHours` is a validated `idleSchedule`: working days, a start and
+*   an end time in an IANA time zone. Outside the working hours a preview
+*   sleeps, unless it was woken up (`status.awakeUntil`).
+* - `WorkingHours::next_change` tells the controller when to look at the
+*   preview again, so that it goes to sleep and wakes up on time.
+* - All functions are pure and take the current time as an argument.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::crds::{phPreviewSpec, IdleSchedule};
+use chrono::{DateTime, Datelike, Duration, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use pipeline_rules::duration::parse_duration;
+
+/// The reason of the `Ready` condition of a sleeping preview.
+pub const SLEEPING_REASON: &str = "Sleeping";
+/// How long a preview woken up outside its working hours stays awake.
+const DEFAULT_WAKE_FOR: Duration = Duration::hours(2);
+const DEFAULT_DAYS: [Weekday; 5] = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+
+/// When a preview created at `created` expires: `expiresAt` if set, else
+/// `ttl`, else `ttlHours`. Fails on an invalid `expiresAt` or `ttl`.
+pub fn expiry(spec: &phPreviewSpec, created: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, String> {
+    if let Some(at) = &spec.expires_at {
+        let at = DateTime::parse_from_rfc3339(at)
+            .map_err(|_| format!("Invalid expiresAt '{}'; use e.g. \"2025-06-05T10:00:00Z\"", at))?;
+        return Ok(Some(at.with_timezone(&Utc)));
+    }
+    match (&spec.ttl, spec.ttl_hours) {
+        (Some(ttl), _) => {
+            let ttl = parse_duration(ttl).ok_or_else(|| format!("Invalid ttl '{}'; use e.g. \"36h\" or \"7d\"", ttl))?;
+            let ttl = Duration::from_std(ttl).map_err(|e| format!("Invalid ttl: {}", e))?;
+            Ok(Some(created + ttl))
+        }
+        (None, Some(hours)) => Ok(Some(created + Duration::hours(i64::from(hours)))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// A validated `idleSchedule`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkingHours {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+    time_zone: Tz,
+    /// How long a woken up preview stays awake.
+    pub wake_for: Duration,
+}
+
+impl WorkingHours {
+    pub fn parse(schedule: &IdleSchedule) -> Result<Self, String> {
+        let days = if schedule.days.is_empty() {
+            DEFAULT_DAYS.to_vec()
+        } else {
+            schedule
+                .days
+                .iter()
+                .map(|day| day.parse::<Weekday>().map_err(|_| format!("Invalid day '{}'; use Mon to Sun", day)))
+                .collect::<Result<_, _>>()?
+        };
+        let time = |value: &str| {
+            NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| format!("Invalid time '{}'; use HH:MM", value))
+        };
+        let time_zone = match &schedule.time_zone {
+            Some(name) => name.parse::<Tz>().map_err(|_| format!("Unknown time zone '{}'", name))?,
+            None => Tz::UTC,
+        };
+        let wake_for = match &schedule.wake_for {
+            Some(value) => parse_duration(value)
+                .and_then(|d| Duration::from_std(d).ok())
+                .ok_or_else(|| format!("Invalid wakeFor '{}'; use e.g. \"90m\" or \"2h\"", value))?,
+            None => DEFAULT_WAKE_FOR,
+        };
+        Ok(Self { days, start: time(&schedule.start)?, end: time(&schedule.end)?, time_zone, wake_for })
+    }
+
+    /// Whether `now` is within the working hours. A window ending before it
+    /// starts spans midnight and belongs to the day it starts.
+    pub fn is_working_time(&self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.time_zone);
+        let time = local.time();
+        if self.start <= self.end {
+            self.days.contains(&local.weekday()) && self.start <= time && time < self.end
+        } else {
+            (self.days.contains(&local.weekday()) && time >= self.start)
+                || (self.days.contains(&local.weekday().pred()) && time < self.end)
+        }
+    }
+
+    /// The next instant after `now` at which the preview goes to sleep or
+    /// wakes up.
+    pub fn next_change(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let working = self.is_working_time(now);
+        let today = now.with_timezone(&self.time_zone).date_naive();
+        let mut boundaries: Vec<DateTime<Utc>> = (0..=8)
+            .flat_map(|offset| [self.start, self.end].map(move |time| (today + Duration::days(offset)).and_time(time)))
+            // A time skipped by a daylight saving change has no instant.
+            .filter_map(|local| self.time_zone.from_local_datetime(&local).earliest())
+            .map(|at| at.with_timezone(&Utc))
+            .filter(|at| *at > now)
+            .collect();
+        boundaries.sort();
+        boundaries.into_iter().find(|at| self.is_working_time(*at) != working)
+    }
+}
+
+/// Whether a preview should be awake at `now`: within its working hours, or
+/// woken up until after `now`.
+pub fn is_awake(hours: &WorkingHours, awake_until: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    hours.is_working_time(now) || awake_until.is_some_and(|until| until > now)
+}
+
+/// How long to wait before looking at a preview again: until the first of
+/// `instants` after `now`, at most `max`.
+pub fn requeue_after(now: DateTime<Utc>, instants: &[Option<DateTime<Utc>>], max: std::time::Duration) -> std::time::Duration {
+    instants
+        .iter()
+        .flatten()
+        .filter(|at| **at > now)
+        .filter_map(|at| (*at - now).to_std().ok())
+        // A second late, so that the change has happened.
+        .map(|wait| wait + std::time::Duration::from_secs(1))
+        .fold(max, std::cmp::min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn hours(schedule: serde_json::Value) -> WorkingHours {
+        WorkingHours::parse(&serde_json::from_value(schedule).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_expiry() {
+        let spec = |extra: serde_json::Value| -> phPreviewSpec {
+            let mut spec = json!({ "repoUrl": "https://github.com/acme/shop.git", "branch": "main", "manifestPath": "k8s", "appName": "shop" });
+            spec.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            serde_json::from_value(spec).unwrap()
+        };
+        let created = at("2025-06-02T10:00:00Z");
+        assert_eq!(expiry(&spec(json!({ "ttl": "3d", "ttlHours": 24 })), created), Ok(Some(at("2025-06-05T10:00:00Z"))));
+        assert_eq!(expiry(&spec(json!({ "ttlHours": 24 })), created), Ok(Some(at("2025-06-03T10:00:00Z"))));
+        assert_eq!(expiry(&spec(json!({})), created), Ok(None));
+        // An explicit expiry, e.g. from `ph preview extend`, wins.
+        let extended = json!({ "ttl": "3d", "expiresAt": "2025-06-09T12:30:00+02:00" });
+        assert_eq!(expiry(&spec(extended), created), Ok(Some(at("2025-06-09T10:30:00Z"))));
+        assert!(expiry(&spec(json!({ "expiresAt": "next week" })), created).unwrap_err().contains("Invalid expiresAt"));
+        assert!(expiry(&spec(json!({ "ttl": "soon" })), created).unwrap_err().contains("Invalid ttl 'soon'"));
+    }
+
+    #[test]
+    fn test_working_hours() {
+        // 2025-06-02 is a Monday; Lisbon is at UTC+1 in summer.
+        let office = hours(json!({ "start": "08:00", "end": "20:00", "timeZone": "Europe/Lisbon" }));
+        assert!(office.is_working_time(at("2025-06-02T07:00:00Z")));
+        assert!(!office.is_working_time(at("2025-06-02T06:59:00Z")));
+        assert!(!office.is_working_time(at("2025-06-02T19:00:00Z")));
+        assert!(!office.is_working_time(at("2025-06-07T12:00:00Z")));
+        assert_eq!(office.wake_for, Duration::hours(2));
+
+        // Friday evening: asleep until Monday morning.
+        assert_eq!(office.next_change(at("2025-06-06T18:00:00Z")), Some(at("2025-06-06T19:00:00Z")));
+        assert_eq!(office.next_change(at("2025-06-06T19:00:00Z")), Some(at("2025-06-09T07:00:00Z")));
+
+        // A night shift spans midnight and belongs to the day it starts.
+        let night = hours(json!({ "days": ["Fri"], "start": "22:00", "end": "06:00", "wakeFor": "30m" }));
+        assert!(night.is_working_time(at("2025-06-06T23:00:00Z")));
+        assert!(night.is_working_time(at("2025-06-07T05:00:00Z")));
+        assert!(!night.is_working_time(at("2025-06-05T23:00:00Z")));
+        assert_eq!(night.next_change(at("2025-06-07T05:00:00Z")), Some(at("2025-06-07T06:00:00Z")));
+        assert_eq!(night.wake_for, Duration::minutes(30));
+
+        // Woken up previews stay awake until `awakeUntil`.
+        let saturday = at("2025-06-07T12:00:00Z");
+        assert!(!is_awake(&office, None, saturday));
+        assert!(is_awake(&office, Some(at("2025-06-07T13:00:00Z")), saturday));
+        assert!(!is_awake(&office, Some(at("2025-06-07T11:00:00Z")), saturday));
+
+        let max = std::time::Duration::from_secs(600);
+        let soon = Some(at("2025-06-07T12:02:00Z"));
+        assert_eq!(requeue_after(saturday, &[None, soon, office.next_change(saturday)], max).as_secs(), 121);
+        assert_eq!(requeue_after(saturday, &[Some(at("2025-06-07T11:00:00Z")), None], max), max);
+    }
+
+    #[test]
+    fn test_invalid_schedules() {
+        let parse = |schedule: serde_json::Value| WorkingHours::parse(&serde_json::from_value(schedule).unwrap()).unwrap_err();
+        assert_eq!(parse(json!({ "start": "8am", "end": "20:00" })), "Invalid time '8am'; use HH:MM");
+        assert_eq!(parse(json!({ "days": ["Funday"], "start": "08:00", "end": "20:00" })), "Invalid day 'Funday'; use Mon to Sun");
+        assert_eq!(parse(json!({ "start": "08:00", "end": "20:00", "timeZone": "Mars/Olympus" })), "Unknown time zone 'Mars/Olympus'");
+        assert!(parse(json!({ "start": "08:00", "end": "20:00", "wakeFor": "1w" })).starts_with("Invalid wakeFor"));
+    }
+}
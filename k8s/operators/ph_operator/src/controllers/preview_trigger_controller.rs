@@ -0,0 +1,540 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/preview_trigger_controller.rs
+*
+* This file implements the controller for the `phPreviewTrigger` custom
+* resource, which keeps a preview environment for every open pull request of
+* a repository.
+*
+* Architecture:
+* - Reconciler: checks that the webhook secret of every trigger exists and
+*   reports the result through the standard `Ready`/`Progressing`/`Degraded`
+*   conditions, like the phPipelineTrigger controller.
+* - Webhook server (see `vcs_webhook`): receives `pull_request` deliveries
+*   at `POST /previews/<namespace>/<trigger>` on port 8082. A delivery is
+*   only accepted with a valid `X-Hub-Signature-256` signature made with the
+*   trigger's secret.
+* - Each pull request owns one `phPreview`, named `<trigger>-pr-<number>`:
+*     - opening or reopening the pull request creates it from the trigger's
+*       template, pinned to the head commit;
+*     - a push to the pull request moves `commitSha` to the new head, which
+*       makes the preview controller deploy it;
+*     - closing or merging the pull request deletes it, and the preview
+*       controller's finalizer removes its namespace.
+*   The previews are owned by the trigger, so deleting the trigger deletes
+*   them too. A redelivery of the last accepted delivery is ignored, and
+*   the preview records the delivery that last changed it in an annotation.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::controllers::conditions::{self, ResourceState};
+use crate::controllers::events;
+use crate::controllers::vcs_events::{self, PreviewAction, PullRequestEvent};
+use crate::controllers::vcs_webhook::{self, Authentication, Delivery};
+use crate::crds::{phPreview, phPreviewTrigger};
+use chrono::Utc;
+use futures::stream::StreamExt;
+use kube::{
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams, PostParams},
+    client::Client,
+    runtime::{
+        controller::{Action, Controller},
+        events::Recorder,
+    },
+    Resource, ResourceExt,
+};
+use serde_json::json;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+use warp::{http::StatusCode, hyper::body::Bytes};
+
+/// The port of the pull request webhook server.
+const WEBHOOK_PORT: u16 = 8082;
+const TRIGGER_LABEL: &str = "ph.io/preview-trigger";
+const PULL_REQUEST_LABEL: &str = "ph.io/pull-request";
+const DELIVERY_ANNOTATION: &str = "ph.io/git-delivery";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Kubernetes API error: {0}")]
+    KubeError(#[from] kube::Error),
+
+    #[error("Missing object key '{0}' in resource")]
+    MissingObjectKey(&'static str),
+
+    #[error("JSON serialization/deserialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+}
+
+/// Shared state for the reconciler and the webhook handlers.
+struct Context {
+    client: Client,
+    /// Publishes Kubernetes Events about triggers.
+    recorder: Recorder,
+}
+
+/// Runs the preview trigger controller and its webhook server.
+pub async fn run(client: Client) {
+    let triggers: Api<phPreviewTrigger> = Api::all(client.clone());
+    let ctx = Arc::new(Context {
+        recorder: events::recorder(client.clone(), "ph-preview-trigger-controller"),
+        client,
+    });
+
+    let webhook_ctx = ctx.clone();
+    let webhook_task = tokio::spawn(vcs_webhook::serve("previews", WEBHOOK_PORT, move |ns, name, delivery, body| {
+        handle_delivery(webhook_ctx.clone(), ns, name, delivery, body)
+    }));
+    let controller = Controller::new(triggers, ListParams::default())
+        .run(reconcile, error_policy, ctx)
+        .for_each(|res| async move {
+            match res {
+                Ok(o) => info!("Reconciled phPreviewTrigger: {:?}", o),
+                Err(e) => warn!("phPreviewTrigger reconcile error: {}", e),
+            }
+        });
+
+    tokio::select! {
+        _ = webhook_task => warn!("Pull request webhook server task has unexpectedly exited."),
+        _ = controller => warn!("Preview trigger reconciliation task has unexpectedly exited."),
+    }
+}
+
+/// The name of the phPreview of pull request `number`.
+fn preview_name(trigger: &str, number: u64) -> String {
+    format!("{}-pr-{}", trigger, number)
+}
+
+// --- Reconciler Implementation ---
+
+/// Reports whether the webhook secret of a trigger is available.
+async fn reconcile(trigger: Arc<phPreviewTrigger>, ctx: Arc<Context>) -> Result<Action, Error> {
+    let ns = trigger.namespace().ok_or(Error::MissingObjectKey("namespace"))?;
+    let name = trigger.name_any();
+    let secret_ref = &trigger.spec.secret_ref;
+
+    let (state, reason, message) = match vcs_webhook::webhook_secret(&ctx.client, &ns, &*trigger).await? {
+        Some(_) => (
+            ResourceState::Ready,
+            "Listening",
+            format!("Receiving deliveries at /previews/{}/{}.", ns, name),
+        ),
+        None => (
+            ResourceState::Degraded,
+            "SecretNotFound",
+            format!("Key '{}' of Secret '{}' was not found.", secret_ref.key, secret_ref.name),
+        ),
+    };
+
+    let mut trigger_conditions = trigger.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default();
+    if conditions::current_state(&trigger_conditions) != Some(state) {
+        if state == ResourceState::Degraded {
+            events::warning(&ctx.recorder, &*trigger, reason, "Validate", message.clone()).await;
+        }
+        conditions::set_state(&mut trigger_conditions, state, reason, &message, trigger.metadata.generation, Utc::now());
+        let api: Api<phPreviewTrigger> = Api::namespaced(ctx.client.clone(), &ns);
+        let patch = json!({ "status": { "conditions": trigger_conditions } });
+        api.patch_status(&name, &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    }
+    // The secret is not watched; look at it again from time to time.
+    Ok(Action::requeue(Duration::from_secs(300)))
+}
+
+fn error_policy(_trigger: Arc<phPreviewTrigger>, error: &Error, _ctx: Arc<Context>) -> Action {
+    warn!("Reconciliation failed: {}", error);
+    Action::requeue(Duration::from_secs(15))
+}
+
+// --- Webhook Server Implementation ---
+
+async fn handle_delivery(
+    ctx: Arc<Context>,
+    ns: String,
+    name: String,
+    delivery: Delivery,
+    body: Bytes,
+) -> (StatusCode, String) {
+    match process_delivery(&ctx, &ns, &name, delivery, &body).await {
+        Ok(reply) => reply,
+        Err(e) => {
+            error!(trigger = %name, namespace = %ns, error = %e, "Failed to process pull request delivery");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        }
+    }
+}
+
+/// Authenticates a delivery and creates, updates or deletes the preview of
+/// its pull request.
+async fn process_delivery(
+    ctx: &Context,
+    ns: &str,
+    name: &str,
+    delivery: Delivery,
+    body: &[u8],
+) -> Result<(StatusCode, String), Error> {
+    let authentication = vcs_webhook::authenticate(&ctx.client, &ctx.recorder, ns, name, &delivery, body).await?;
+    let (trigger, event_type): (phPreviewTrigger, _) = match authentication {
+        Authentication::Accepted { trigger, event } => (trigger, event),
+        Authentication::Rejected(status, message) => return Ok((status, message)),
+    };
+    let event = match vcs_events::parse_pull_request_event(&event_type, body) {
+        Ok(Some(event)) => event,
+        Ok(None) => return Ok((StatusCode::ACCEPTED, format!("Ignored '{}' event.", event_type))),
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e.to_string())),
+    };
+    if !vcs_events::preview_matches(&trigger.spec, &event) {
+        return Ok((
+            StatusCode::ACCEPTED,
+            format!("Ignored pull request of {} into '{}'.", event.repository, event.base_branch),
+        ));
+    }
+    if delivery.is_redelivery(&trigger) {
+        return Ok((StatusCode::ACCEPTED, "Delivery was already processed.".to_string()));
+    }
+
+    let preview = preview_name(name, event.number);
+    let change = match event.action {
+        PreviewAction::Deploy => deploy_preview(ctx, ns, &trigger, &preview, &event, delivery.id.as_deref()).await?,
+        PreviewAction::Remove => remove_preview(ctx, ns, &preview, &event).await?,
+    };
+    if let Some((reason, message)) = &change {
+        events::normal(&ctx.recorder, &trigger, reason, "Preview", message.clone()).await;
+    }
+
+    info!(trigger = %name, namespace = %ns, preview = %preview, action = ?event.action, "Pull request event handled");
+    let triggers: Api<phPreviewTrigger> = Api::namespaced(ctx.client.clone(), ns);
+    let patch = json!({ "status": {
+        "lastDelivery": delivery.id,
+        "lastEventTime": Utc::now().to_rfc3339(),
+        "lastPreview": preview,
+    } });
+    triggers.patch_status(name, &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    let reply = change.map_or_else(|| format!("phPreview '{}' is up to date.", preview), |(_, message)| message);
+    Ok((StatusCode::ACCEPTED, reply))
+}
+
+/// Creates the preview of a pull request, or points it at the new head
+/// commit. Returns the reason and message of the Event to publish, if the
+/// preview changed.
+async fn deploy_preview(
+    ctx: &Context,
+    ns: &str,
+    trigger: &phPreviewTrigger,
+    name: &str,
+    event: &PullRequestEvent,
+    delivery_id: Option<&str>,
+) -> Result<Option<(&'static str, String)>, Error> {
+    let previews: Api<phPreview> = Api::namespaced(ctx.client.clone(), ns);
+    if let Some(existing) = previews.get_opt(name).await? {
+        if existing.spec.commit_sha.as_deref() == Some(event.head_sha.as_str()) {
+            return Ok(None);
+        }
+        // In a merge patch, a null ID removes the annotation of an earlier
+        // delivery.
+        let patch = json!({
+            "metadata": { "annotations": { DELIVERY_ANNOTATION: delivery_id } },
+            "spec": { "branch": event.head_branch, "commitSha": event.head_sha },
+        });
+        previews.patch(name, &PatchParams::default(), &Patch::Merge(&patch)).await?;
+        return Ok(Some((
+            "PreviewUpdated",
+            format!("Deploying {} of pull request #{} to phPreview '{}'.", event.head_sha, event.number, name),
+        )));
+    }
+
+    let preview: phPreview = serde_json::from_value(preview_manifest(trigger, name, event, delivery_id))?;
+    previews.create(&PostParams::default(), &preview).await?;
+    Ok(Some((
+        "PreviewCreated",
+        format!("Created phPreview '{}' for pull request #{} at {}.", name, event.number, event.head_sha),
+    )))
+}
+
+/// The phPreview trigger `trigger` creates for pull request `event`.
+/// Deliveries without an ID get no delivery annotation.
+fn preview_manifest(
+    trigger: &phPreviewTrigger,
+    name: &str,
+    event: &PullRequestEvent,
+    delivery_id: Option<&str>,
+) -> serde_json::Value {
+    let template = &trigger.spec.template;
+    let repo_url = template.repo_url.as_deref().unwrap_or(&event.clone_url);
+    let mut annotations = json!({});
+    if let Some(id) = delivery_id {
+        annotations[DELIVERY_ANNOTATION] = json!(id);
+    }
+    json!({
+        "apiVersion": "ph.io/v1alpha1",
+        "kind": "phPreview",
+        "metadata": {
+            "name": name,
+            "labels": {
+                TRIGGER_LABEL: trigger.name_any(),
+                PULL_REQUEST_LABEL: event.number.to_string(),
+            },
+            "annotations": annotations,
+            "ownerReferences": trigger.controller_owner_ref(&()).into_iter().collect::<Vec<_>>(),
+        },
+        "spec": {
+            "repoUrl": repo_url,
+            "branch": event.head_branch,
+            "manifestPath": template.manifest_path,
+            "appName": template.app_name,
+            "prNumber": event.number,
+            "commitSha": event.head_sha,
+            "render": template.render,
+            "report": template.report,
+            "sizeClass": template.size_class,
+            "team": template.team,
+            "ttl": template.ttl,
+            "idleSchedule": template.idle_schedule,
+        }
+    })
+}
+
+/// Deletes the preview of a closed pull request.
+async fn remove_preview(
+    ctx: &Context,
+    ns: &str,
+    name: &str,
+    event: &PullRequestEvent,
+) -> Result<Option<(&'static str, String)>, Error> {
+    let previews: Api<phPreview> = Api::namespaced(ctx.client.clone(), ns);
+    match previews.delete(name, &DeleteParams::default()).await {
+        Ok(_) => {
+            let how = if event.merged { "merged" } else { "closed" };
+            Ok(Some((
+                "PreviewDeleted",
+                format!("Deleted phPreview '{}': pull request #{} was {}.", name, event.number, how),
+            )))
+        }
+        Err(kube::Error::Api(ae)) if ae.code == 404 => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use hmac::{Hmac, Mac};
+    use http::{Request, Response};
+    use kube::client::Body;
+    use serde_json::Value;
+    use sha2::Sha256;
+    use std::sync::Mutex;
+
+    const SECRET: &[u8] = b"It's a Secret to Everybody";
+    const PREVIEWS: &str = "/apis/ph.io/v1alpha1/namespaces/previews/phpreviews";
+
+    /// The requests the mocked API server received: method, path and body.
+    type Requests = Arc<Mutex<Vec<(String, String, Value)>>>;
+
+    fn trigger() -> phPreviewTrigger {
+        serde_json::from_value(json!({
+            "apiVersion": "ph.io/v1alpha1",
+            "kind": "phPreviewTrigger",
+            "metadata": { "name": "shop", "namespace": "previews", "uid": "3f9a" },
+            "spec": {
+                "secretRef": { "name": "webhook", "key": "secret" },
+                "repository": "acme/shop",
+                "template": { "manifestPath": "k8s", "appName": "shop", "ttl": "48h" }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn pull_request_body(action: &str, repository: &str, sha: &str) -> Vec<u8> {
+        json!({
+            "action": action,
+            "number": 7,
+            "pull_request": {
+                "head": { "ref": "feature/login", "sha": sha },
+                "base": { "ref": "main", "sha": "def456" }
+            },
+            "repository": { "full_name": repository, "clone_url": "https://github.com/acme/shop.git" }
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn event() -> PullRequestEvent {
+        let body = pull_request_body("opened", "acme/shop", "abc123");
+        vcs_events::parse_pull_request_event("pull_request", &body).unwrap().unwrap()
+    }
+
+    fn sign(body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET).unwrap();
+        mac.update(body);
+        let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("sha256={}", digest)
+    }
+
+    /// A client of an API server holding `trigger()`, its webhook secret and,
+    /// if given, the phPreview `shop-pr-7`, which can be created, patched and
+    /// deleted. Anything else is not found.
+    fn mock_context(existing: Option<Value>) -> (Arc<Context>, Requests) {
+        let (service, mut handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        let requests = Requests::default();
+        let received = requests.clone();
+        tokio::spawn(async move {
+            let preview = format!("{}/shop-pr-7", PREVIEWS);
+            while let Some((request, send)) = handle.next_request().await {
+                let (parts, body) = request.into_parts();
+                let body: Value = serde_json::from_slice(&body.collect_bytes().await.unwrap()).unwrap_or(Value::Null);
+                let (method, path) = (parts.method.to_string(), parts.uri.path().to_string());
+                let reply = match (method.as_str(), path.as_str()) {
+                    ("GET", "/apis/ph.io/v1alpha1/namespaces/previews/phpreviewtriggers/shop")
+                    | ("PATCH", "/apis/ph.io/v1alpha1/namespaces/previews/phpreviewtriggers/shop/status") => {
+                        Some((200, serde_json::to_value(trigger()).unwrap()))
+                    }
+                    ("GET", "/api/v1/namespaces/previews/secrets/webhook") => Some((
+                        200,
+                        json!({
+                            "apiVersion": "v1",
+                            "kind": "Secret",
+                            "metadata": { "name": "webhook", "namespace": "previews" },
+                            "data": { "secret": STANDARD.encode(SECRET) }
+                        }),
+                    )),
+                    ("POST", PREVIEWS) => Some((201, body.clone())),
+                    ("GET" | "PATCH" | "DELETE", path) if path == preview => existing.clone().map(|preview| (200, preview)),
+                    _ => None,
+                };
+                let (status, reply) = reply.unwrap_or_else(|| {
+                    (
+                        404,
+                        json!({ "apiVersion": "v1", "kind": "Status", "status": "Failure", "reason": "NotFound", "message": "not found", "code": 404 }),
+                    )
+                });
+                received.lock().unwrap().push((method, path, body));
+                send.send_response(Response::builder().status(status).body(Body::from(reply.to_string().into_bytes())).unwrap());
+            }
+        });
+        let client = Client::new(service, "default");
+        let ctx = Arc::new(Context {
+            recorder: events::recorder(client.clone(), "ph-preview-trigger-controller"),
+            client,
+        });
+        (ctx, requests)
+    }
+
+    /// The phPreview of pull request 7, deployed at `sha`.
+    fn existing_preview(sha: &str) -> Value {
+        let mut preview = preview_manifest(&trigger(), "shop-pr-7", &event(), Some("d-0"));
+        preview["metadata"]["namespace"] = json!("previews");
+        preview["spec"]["commitSha"] = json!(sha);
+        preview
+    }
+
+    /// The requests made for phPreviews, except reads: method, path and body.
+    fn preview_changes(requests: &Requests) -> Vec<(String, String, Value)> {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(method, path, _)| method != "GET" && path.starts_with(PREVIEWS))
+            .cloned()
+            .collect()
+    }
+
+    async fn deliver(ctx: Arc<Context>, body: Vec<u8>) -> (StatusCode, String) {
+        let delivery = Delivery {
+            signature: Some(sign(&body)),
+            event: Some("pull_request".to_string()),
+            id: Some("d-1".to_string()),
+        };
+        handle_delivery(ctx, "previews".to_string(), "shop".to_string(), delivery, Bytes::from(body)).await
+    }
+
+    #[tokio::test]
+    async fn test_opened_pull_request() {
+        let (ctx, requests) = mock_context(None);
+        let (status, reply) = deliver(ctx, pull_request_body("opened", "acme/shop", "abc123")).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(reply, "Created phPreview 'shop-pr-7' for pull request #7 at abc123.");
+
+        let changes = preview_changes(&requests);
+        assert_eq!(changes.len(), 1);
+        let (method, _, preview) = &changes[0];
+        assert_eq!(method, "POST");
+        assert_eq!(preview["metadata"]["name"], "shop-pr-7");
+        assert_eq!(preview["spec"]["commitSha"], "abc123");
+        // The trigger owns the preview, so deleting it deletes the preview.
+        let owner = &preview["metadata"]["ownerReferences"][0];
+        assert_eq!(owner["kind"], "phPreviewTrigger");
+        assert_eq!(owner["name"], "shop");
+        assert_eq!(owner["uid"], "3f9a");
+        assert_eq!(owner["controller"], true);
+    }
+
+    #[tokio::test]
+    async fn test_synchronized_pull_request() {
+        let (ctx, requests) = mock_context(Some(existing_preview("abc123")));
+        let (status, reply) = deliver(ctx, pull_request_body("synchronize", "acme/shop", "fed987")).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(reply, "Deploying fed987 of pull request #7 to phPreview 'shop-pr-7'.");
+
+        let changes = preview_changes(&requests);
+        assert_eq!(changes.len(), 1);
+        let (method, path, patch) = &changes[0];
+        assert_eq!((method.as_str(), path.as_str()), ("PATCH", format!("{}/shop-pr-7", PREVIEWS).as_str()));
+        assert_eq!(patch["spec"]["commitSha"], "fed987");
+        assert_eq!(patch["metadata"]["annotations"][DELIVERY_ANNOTATION], "d-1");
+
+        // A preview already at the head commit is left alone.
+        let (ctx, requests) = mock_context(Some(existing_preview("fed987")));
+        let (status, reply) = deliver(ctx, pull_request_body("synchronize", "acme/shop", "fed987")).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(reply, "phPreview 'shop-pr-7' is up to date.");
+        assert!(preview_changes(&requests).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_closed_pull_request() {
+        let (ctx, requests) = mock_context(Some(existing_preview("abc123")));
+        let (status, reply) = deliver(ctx, pull_request_body("closed", "acme/shop", "abc123")).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(reply, "Deleted phPreview 'shop-pr-7': pull request #7 was closed.");
+        let changes = preview_changes(&requests);
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].0.as_str(), changes[0].1.as_str()), ("DELETE", format!("{}/shop-pr-7", PREVIEWS).as_str()));
+
+        // A preview that is already gone is not an error.
+        let (ctx, _) = mock_context(None);
+        let (status, reply) = deliver(ctx, pull_request_body("closed", "acme/shop", "abc123")).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(reply, "phPreview 'shop-pr-7' is up to date.");
+    }
+
+    #[tokio::test]
+    async fn test_pull_request_of_another_repository() {
+        let (ctx, requests) = mock_context(Some(existing_preview("abc123")));
+        let (status, reply) = deliver(ctx, pull_request_body("closed", "acme/other", "abc123")).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(reply, "Ignored pull request of acme/other into 'main'.");
+        assert!(requests.lock().unwrap().iter().all(|(_, path, _)| !path.starts_with(PREVIEWS)));
+    }
+
+    #[test]
+    fn test_preview_manifest() {
+        let trigger = trigger();
+        let event = event();
+        let manifest = preview_manifest(&trigger, "shop-pr-7", &event, Some("d-1"));
+        assert_eq!(manifest["metadata"]["annotations"][DELIVERY_ANNOTATION], "d-1");
+        assert_eq!(manifest["metadata"]["labels"][PULL_REQUEST_LABEL], "7");
+        let preview: phPreview = serde_json::from_value(manifest).unwrap();
+        assert_eq!(preview.spec.repo_url, "https://github.com/acme/shop.git");
+        assert_eq!(preview.spec.commit_sha.as_deref(), Some("abc123"));
+
+        // Without an ID, the annotation is left out rather than null.
+        let manifest = preview_manifest(&trigger, "shop-pr-7", &event, None);
+        assert!(manifest["metadata"]["annotations"].get(DELIVERY_ANNOTATION).is_none());
+        assert!(serde_json::from_value::<phPreview>(manifest).is_ok());
+    }
+}
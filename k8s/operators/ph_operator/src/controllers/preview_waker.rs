@@ -0,0 +1,237 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/preview_waker.rs
+*
+* This file implements the preview waker, the HTTP server that wakes up the
+* previews sleeping outside their working hours.
+*
+* Architecture:
+* - While a preview sleeps, the preview controller points the ingresses of
+*   its namespace at the waker (port 8083): ingress-nginx sends it the
+*   requests that the scaled down workloads cannot serve, with the namespace
+*   of the ingress in the `X-Namespace` header.
+* - `GET /wake/<namespace>/<name>` wakes up a preview by name, e.g. from a
+*   link in the pull request.
+* - Waking up sets the `ph.io/wake-up` annotation on the sleeping phPreview;
+*   the preview controller then restores its workloads and keeps it awake
+*   for `idleSchedule.wakeFor`.
+* - Meanwhile, the waker answers 503 with a page that reloads itself, so the
+*   visitor lands on the preview once it is up.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::controllers::conditions;
+use crate::controllers::preview_controller::WAKE_ANNOTATION;
+use crate::controllers::preview_schedule::SLEEPING_REASON;
+use crate::crds::phPreview;
+use chrono::Utc;
+use kube::{
+    api::{Api, ListParams, Patch, PatchParams},
+    client::Client,
+    ResourceExt,
+};
+use serde_json::json;
+use tracing::{error, info};
+use warp::{http::StatusCode, reply::Response, Filter, Reply};
+
+/// The port of the waker.
+pub const WAKER_PORT: u16 = 8083;
+/// How long the waking page waits before reloading.
+const RETRY_AFTER_SECONDS: u32 = 15;
+
+/// Runs the waker server.
+pub async fn run(client: Client) {
+    info!("Starting preview waker on 0.0.0.0:{}", WAKER_PORT);
+    warp::serve(routes(client)).run(([0, 0, 0, 0], WAKER_PORT)).await;
+}
+
+fn routes(client: Client) -> impl Filter<Extract = (Response,), Error = warp::Rejection> + Clone {
+    let with_client = warp::any().map(move || client.clone());
+    let wake = warp::get()
+        .and(warp::path!("wake" / String / String))
+        .and(with_client.clone())
+        .and_then(handle_wake);
+    // ingress-nginx sends the failed requests of any path as the default backend.
+    let default_backend = warp::header::optional::<String>("x-namespace")
+        .and(with_client)
+        .and_then(handle_default_backend);
+    wake.or(default_backend).unify()
+}
+
+async fn handle_wake(ns: String, name: String, client: Client) -> Result<Response, warp::Rejection> {
+    let previews: Api<phPreview> = Api::namespaced(client.clone(), &ns);
+    Ok(match previews.get_opt(&name).await {
+        Ok(preview) => wake_up(&client, preview).await,
+        Err(e) => failure(&ns, e),
+    })
+}
+
+async fn handle_default_backend(namespace: Option<String>, client: Client) -> Result<Response, warp::Rejection> {
+    let Some(namespace) = namespace else {
+        return Ok(warp::reply::with_status("Not a preview.", StatusCode::NOT_FOUND).into_response());
+    };
+    Ok(match preview_of_namespace(&client, &namespace).await {
+        Ok(preview) => wake_up(&client, preview).await,
+        Err(e) => failure(&namespace, e),
+    })
+}
+
+/// Finds the preview deployed into namespace `ns_name`.
+async fn preview_of_namespace(client: &Client, ns_name: &str) -> Result<Option<phPreview>, kube::Error> {
+    let previews: Api<phPreview> = Api::all(client.clone());
+    Ok(previews
+        .list(&ListParams::default())
+        .await?
+        .items
+        .into_iter()
+        .find(|preview| preview.status.as_ref().and_then(|s| s.namespace.as_deref()) == Some(ns_name)))
+}
+
+/// Asks a sleeping preview to wake up, once, and replies with the waking
+/// page. Previews that are already awake may still be starting; they get
+/// the page only.
+async fn wake_up(client: &Client, preview: Option<phPreview>) -> Response {
+    let Some(preview) = preview else {
+        return warp::reply::with_status("No such preview.", StatusCode::NOT_FOUND).into_response();
+    };
+    let ns = preview.namespace().unwrap_or_default();
+    let name = preview.name_any();
+    let sleeping = preview
+        .status
+        .iter()
+        .flat_map(|s| &s.conditions)
+        .any(|c| c.type_ == conditions::READY && c.reason == SLEEPING_REASON);
+    if sleeping && !preview.annotations().contains_key(WAKE_ANNOTATION) {
+        let previews: Api<phPreview> = Api::namespaced(client.clone(), &ns);
+        let patch = json!({ "metadata": { "annotations": { WAKE_ANNOTATION: Utc::now().to_rfc3339() } } });
+        if let Err(e) = previews.patch(&name, &PatchParams::default(), &Patch::Merge(&patch)).await {
+            return failure(&ns, e);
+        }
+        info!(preview = %name, namespace = %ns, "Waking up preview");
+    }
+
+    let page = format!(
+        "<!DOCTYPE html>\n<html><head><meta http-equiv=\"refresh\" content=\"{seconds}\"><title>Waking up {name}</title></head>\n\
+         <body><p>The preview <code>{name}</code> is waking up. This page reloads in {seconds} seconds.</p></body></html>\n",
+        seconds = RETRY_AFTER_SECONDS,
+        name = name,
+    );
+    let reply = warp::reply::with_status(warp::reply::html(page), StatusCode::SERVICE_UNAVAILABLE);
+    let reply = warp::reply::with_header(reply, "Retry-After", RETRY_AFTER_SECONDS.to_string());
+    warp::reply::with_header(reply, "Cache-Control", "no-store").into_response()
+}
+
+fn failure(ns: &str, e: kube::Error) -> Response {
+    error!(namespace = %ns, error = %e, "Failed to wake up preview");
+    warp::reply::with_status("Failed to wake up the preview.", StatusCode::INTERNAL_SERVER_ERROR).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::{Request, Response};
+    use kube::client::Body;
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    /// The requests the mocked API server received: method and path.
+    type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+    fn preview(name: &str, namespace: &str, reason: &str) -> Value {
+        json!({
+            "apiVersion": "ph.io/v1alpha1",
+            "kind": "phPreview",
+            "metadata": { "name": name, "namespace": "previews" },
+            "spec": { "repoUrl": "https://github.com/acme/shop.git", "branch": "main", "manifestPath": "k8s", "appName": "shop" },
+            "status": {
+                "namespace": namespace,
+                "conditions": [{ "type": "Ready", "status": "False", "reason": reason }]
+            }
+        })
+    }
+
+    /// A client of an API server holding a sleeping preview deployed into
+    /// `preview-shop-pr-7` and an awake one deployed into `preview-shop-pr-8`.
+    fn mock_client() -> (Client, Requests) {
+        let (service, mut handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        let requests = Requests::default();
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Some((request, send)) = handle.next_request().await {
+                let (method, path) = (request.method().to_string(), request.uri().path().to_string());
+                let sleeping = preview("shop-pr-7", "preview-shop-pr-7", SLEEPING_REASON);
+                let (status, reply) = match (method.as_str(), path.as_str()) {
+                    ("GET", "/apis/ph.io/v1alpha1/phpreviews") => (
+                        200,
+                        json!({
+                            "apiVersion": "ph.io/v1alpha1",
+                            "kind": "phPreviewList",
+                            "metadata": {},
+                            "items": [sleeping, preview("shop-pr-8", "preview-shop-pr-8", "Deployed")]
+                        }),
+                    ),
+                    ("GET", "/apis/ph.io/v1alpha1/namespaces/previews/phpreviews/shop-pr-7")
+                    | ("PATCH", "/apis/ph.io/v1alpha1/namespaces/previews/phpreviews/shop-pr-7") => (200, sleeping),
+                    _ => (
+                        404,
+                        json!({ "apiVersion": "v1", "kind": "Status", "status": "Failure", "reason": "NotFound", "message": "not found", "code": 404 }),
+                    ),
+                };
+                received.lock().unwrap().push((method, path));
+                send.send_response(Response::builder().status(status).body(Body::from(reply.to_string().into_bytes())).unwrap());
+            }
+        });
+        (Client::new(service, "default"), requests)
+    }
+
+    fn wake_ups(requests: &Requests) -> Vec<String> {
+        requests.lock().unwrap().iter().filter(|(method, _)| method == "PATCH").map(|(_, path)| path.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_wake_up_by_namespace() {
+        let (client, requests) = mock_client();
+        let routes = routes(client);
+
+        // A request to the ingress of a sleeping preview wakes it up.
+        let reply = warp::test::request().path("/cart").header("x-namespace", "preview-shop-pr-7").reply(&routes).await;
+        assert_eq!(reply.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(reply.headers()["retry-after"], RETRY_AFTER_SECONDS.to_string());
+        assert!(String::from_utf8_lossy(reply.body()).contains("<code>shop-pr-7</code> is waking up"));
+        assert_eq!(wake_ups(&requests), vec!["/apis/ph.io/v1alpha1/namespaces/previews/phpreviews/shop-pr-7"]);
+
+        // An awake preview that is still starting gets the page only.
+        let reply = warp::test::request().header("x-namespace", "preview-shop-pr-8").reply(&routes).await;
+        assert_eq!(reply.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(String::from_utf8_lossy(reply.body()).contains("<code>shop-pr-8</code>"));
+        assert_eq!(wake_ups(&requests).len(), 1);
+
+        // So is a preview woken up by name.
+        let reply = warp::test::request().path("/wake/previews/shop-pr-7").reply(&routes).await;
+        assert_eq!(reply.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(wake_ups(&requests).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_hosts() {
+        let (client, requests) = mock_client();
+        let routes = routes(client);
+
+        // Requests without the namespace of an ingress are not for a preview.
+        let reply = warp::test::request().path("/").reply(&routes).await;
+        assert_eq!(reply.status(), StatusCode::NOT_FOUND);
+        assert_eq!(reply.body(), "Not a preview.");
+
+        // Nor are requests to a namespace no preview is deployed into.
+        let reply = warp::test::request().header("x-namespace", "default").reply(&routes).await;
+        assert_eq!(reply.status(), StatusCode::NOT_FOUND);
+        assert_eq!(reply.body(), "No such preview.");
+
+        let reply = warp::test::request().path("/wake/previews/shop-pr-9").reply(&routes).await;
+        assert_eq!(reply.status(), StatusCode::NOT_FOUND);
+        assert_eq!(reply.body(), "No such preview.");
+        assert!(wake_ups(&requests).is_empty());
+    }
+}
@@ -14,16 +14,23 @@
 *   pre-defined `ClusterRole`.
 * - A finalizer ensures that when a `PhgitRbacPolicy` is deleted, the associated
 *   `RoleBinding` is also garbage collected, preventing orphaned permissions.
+* - The outcome is reported through the standard `Ready`/`Progressing`/`Degraded`
+*   conditions and a Kubernetes Event.
 *
 * SPDX-License-Identifier: Apache-2.0
 */
 
-use crate::crds::{PhgitRbacPolicy, PhgitRbacPolicyStatus, StatusCondition};
+use crate::controllers::conditions::{self, ResourceState};
+use crate::controllers::events;
+use crate::crds::{PhgitRbacPolicy, PhgitRbacPolicyStatus};
 use chrono::Utc;
 use kube::{
     api::{Api, ObjectMeta, Patch, PatchParams, PostParams, Resource, finalizer},
     client::Client,
-    runtime::controller::{Action, Controller},
+    runtime::{
+        controller::{Action, Controller},
+        events::Recorder,
+    },
 };
 use k8s_openapi::api::rbac::v1 as rbac;
 use serde_json::json;
@@ -51,6 +58,7 @@ pub enum Error {
 
 struct Context {
     client: Client,
+    recorder: Recorder,
 }
 
 static ROLE_MAP: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|| {
@@ -66,7 +74,14 @@ static ROLE_MAP: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|
 pub async fn run(client: Client) {
     let api = Api::<PhgitRbacPolicy>::all(client.clone());
     Controller::new(api, Default::default())
-        .run(reconcile, error_policy, Arc::new(Context { client }))
+        .run(
+            reconcile,
+            error_policy,
+            Arc::new(Context {
+                recorder: events::recorder(client.clone(), "ph-rbac-policy-controller"),
+                client,
+            }),
+        )
         .await;
 }
 
@@ -129,15 +144,28 @@ async fn reconcile_policy(policy: &PhgitRbacPolicy, ctx: Arc<Context>) -> Result
         .await?;
 
     // After successfully creating/updating the binding, update the policy status.
-    let status = PhgitRbacPolicyStatus {
-        binding_name: Some(binding_name),
-        conditions: vec![StatusCondition {
-            type_: "Ready".to_string(),
-            status: "True".to_string(),
-            last_transition_time: Utc::now().to_rfc3339(),
-            message: "RoleBinding is reconciled successfully.".to_string(),
-        }],
+    let mut status = PhgitRbacPolicyStatus {
+        binding_name: Some(binding_name.clone()),
+        conditions: policy.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default(),
     };
+    if conditions::current_state(&status.conditions) != Some(ResourceState::Ready) {
+        events::normal(
+            &ctx.recorder,
+            policy,
+            "BindingReconciled",
+            "Reconcile",
+            format!("RoleBinding '{}' grants ClusterRole '{}'.", binding_name, k8s_role_name),
+        )
+        .await;
+    }
+    conditions::set_state(
+        &mut status.conditions,
+        ResourceState::Ready,
+        "BindingReconciled",
+        "RoleBinding is reconciled successfully.",
+        policy.metadata.generation,
+        Utc::now(),
+    );
     update_status(policy, ctx.client.clone(), status).await?;
 
     Ok(Action::requeue(Duration::from_secs(3600)))
@@ -177,21 +205,29 @@ fn error_policy(policy: Arc<PhgitRbacPolicy>, error: &Error, ctx: Arc<Context>)
     warn!("Reconciliation failed: {}", error);
 
     // Update the status to reflect the error condition.
-    let status = PhgitRbacPolicyStatus {
+    let reason = match error {
+        Error::InvalidRole(_) => "InvalidRole",
+        _ => "ReconcileError",
+    };
+    let message = format!("Reconciliation failed: {}", error);
+    let mut status = PhgitRbacPolicyStatus {
         binding_name: policy.status.as_ref().and_then(|s| s.binding_name.clone()),
-        conditions: vec![StatusCondition {
-            type_: "Ready".to_string(),
-            status: "False".to_string(),
-            last_transition_time: Utc::now().to_rfc3339(),
-            message: format!("Reconciliation failed: {}", error),
-        }],
+        conditions: policy.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default(),
     };
+    conditions::set_state(
+        &mut status.conditions,
+        ResourceState::Degraded,
+        reason,
+        &message,
+        policy.metadata.generation,
+        Utc::now(),
+    );
 
     // We need a separate async block to update status, as error_policy is sync.
     // This is a common pattern in kube-rs controllers.
-    let client = ctx.client.clone();
     tokio::spawn(async move {
-        if let Err(e) = update_status(&policy, client, status).await {
+        events::warning(&ctx.recorder, &*policy, reason, "Reconcile", message).await;
+        if let Err(e) = update_status(&policy, ctx.client.clone(), status).await {
             warn!("Failed to update status on error: {}", e);
         }
     });
@@ -18,27 +18,34 @@
 * - **Initial State (No Phase)**: On first reconciliation, the controller
 *   creates the necessary `stable` and `canary` Deployments with the initial
 *   traffic split (based on replica counts) and sets the phase to `Progressing`.
-* - **`Progressing` Phase**: This is the main active state.
-*   - If `analysis` is configured in the spec, the controller enters an
-*     analysis loop.
-*   - It periodically calls the `metrics_analyzer` module to evaluate the
-*     health of the canary version against user-defined Prometheus metrics.
-*   - It updates the `analysisRun` status with success or failure counts.
-*   - Based on these counts, it decides the next state:
-*     - On reaching the success `threshold`, it transitions to `Promoting` (if
-*       `autoPromote` is true) or `Paused` (if manual promotion is required).
-*     - On reaching the `maxFailures` count, it transitions to `RollingBack`.
-*   - If no `analysis` is configured, it remains in `Progressing`, awaiting
-*     manual changes to the `phRelease` spec by the user.
+* - **`Progressing` Phase**: This is the main active state. The controller
+*   walks the typed canary plan (`spec.strategy.canary.steps`), executing the
+*   step at `status.currentStep` and advancing once it completes:
+*   - `setWeight` shifts the given share of traffic to the canary.
+*   - `pause` waits for a fixed duration, or moves the release to `Paused`
+*     until it is resumed when no duration is given.
+*   - `analysis` periodically calls the `metrics_analyzer` module and updates
+*     the `analysisRun` status. Reaching the success `threshold` completes the
+*     step; reaching `maxFailures` transitions to `RollingBack`. Metrics
+*     trending worse pause the release; resuming it runs the analysis again.
+*   - `setHeaderRoute` installs a header-based route to the canary.
+*   Once every step has run, the release transitions to `Promoting` (if
+*   `autoPromote` is true) or `Paused` (if manual promotion is required). When
+*   no steps are declared, a default plan of `setWeight: trafficPercent`
+*   followed by an analysis step (or an indefinite pause) is used.
 * - **`Promoting` Phase**: A terminal action state. It sets the canary
 *   deployment's traffic to 100% and the stable to 0%, then transitions the
 *   phase to `Succeeded`.
 * - **`RollingBack` Phase**: A terminal action state. It sets the canary
 *   deployment's traffic to 0% and the stable to 100%, then transitions the
-*   phase to `Failed`.
+*   phase to `Failed`. A rollback to an earlier revision also redeploys that
+*   revision's version on the stable track.
 * - **`Succeeded`, `Failed`, `Paused`**: Terminal states where the controller
 *   takes no further action and waits for changes to the resource.
 *
+* Every spec change starts a new revision, recorded with its version, spec hash,
+* timestamps and outcome in the bounded `status.revisions` history.
+*
 * This state-driven approach makes the release process robust, observable, and
 * fully automated, turning a simple deployment tool into a true progressive
 * delivery orchestrator.
@@ -46,10 +53,12 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
-use crate::crds::{phRelease, phReleaseStatus, AnalysisRunStatus, ReleasePhase, StrategyType};
-use crate::mesh::{self, TrafficManagerClient, TrafficSplit as MeshTrafficSplit};
+use crate::crds::{phRelease, phReleaseSpec, phReleaseStatus, AnalysisRunStatus, GatewayApiTrafficConfig, ReleasePhase, StrategyType, TrafficManagement, TrafficManagerKind};
+use crate::mesh::{self, RouteMatch, StringMatch, TrafficManagerClient, TrafficMirror as MeshTrafficMirror, TrafficSplit as MeshTrafficSplit};
 use crate::metrics;
-use crate::metrics_analyzer::{AnalysisResult, PrometheusClient};
+use crate::expression::Expression;
+use crate::metric_providers::{resolve_provider, success_condition, MetricTarget};
+use crate::metrics_analyzer::{AnalysisResult, MetricsAnalyzer};
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
 use k8s_openapi::api::apps::v1::Deployment;
@@ -60,6 +69,7 @@ use kube::{
     client::Client,
     runtime::{
         controller::Action,
+        events::Recorder,
         finalizer::{finalizer, Event as FinalizerEvent},
     },
     Error as KubeError, ResourceExt,
@@ -76,11 +86,22 @@ use thiserror::Error;
 use tracing::{info_span, Instrument};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use crate::crds::{CanaryStep, Metric as CrdMetric};
+use crate::crds::{Analysis, ApprovalRecord, BaselineComparison, CanaryStep, Experiment, Metric as CrdMetric, PauseReason, ReleaseRevision, RevisionOutcome, RolloutAction, SetHeaderRoute};
+use crate::controllers::conditions::{self, ResourceState};
+use crate::controllers::events;
+use pipeline_rules::names::content_hash;
 
 // The unique identifier for our controller's finalizer.
 const RELEASE_FINALIZER: &str = "ph.io/release-finalizer";
 const SKIP_SIG_CHECK_ANNOTATION: &str = "ph.io/skip-sig-check";
+/// Requests a manual action (`resume`, `promote`, `abort` or `rollback`) on a
+/// release. The controller applies it once and removes the annotation.
+const ROLLOUT_ACTION_ANNOTATION: &str = "ph.io/rollout-action";
+/// The person requesting the action, recorded in `status.approvals`.
+const ROLLOUT_ACTOR_ANNOTATION: &str = "ph.io/rollout-actor";
+/// The revision a `rollback` action restores. Defaults to the previous successful revision.
+const ROLLOUT_REVISION_ANNOTATION: &str = "ph.io/rollout-revision";
+const DEFAULT_REVISION_HISTORY_LIMIT: u32 = 10;
 const DEFAULT_REPLICAS: i32 = 5; // Default total replicas for the application.
 
 // Custom error types for the controller for better diagnostics.
@@ -106,19 +127,28 @@ pub enum Error {
 
     #[error("Image signature verification failed: {0}")]
     SignatureVerificationFailed(String),
+
+    #[error("Traffic manager '{0}' was requested but its CRD is not installed")]
+    TrafficManagerUnavailable(String),
+
+    #[error("Traffic mirroring is unavailable: {0}")]
+    MirroringUnavailable(String),
 }
 
 /// The context required by the reconciler.
 pub struct Context {
     pub client: Client,
-    pub prometheus_client: PrometheusClient,
+    pub metrics_analyzer: MetricsAnalyzer,
+    /// Publishes Kubernetes Events about releases.
+    pub recorder: Recorder,
 }
 
 impl Context {
     pub fn new(client: Client, prometheus_endpoint: &str) -> Self {
         Self {
+            metrics_analyzer: MetricsAnalyzer::new(client.clone(), prometheus_endpoint),
+            recorder: events::recorder(client.clone(), "ph-release-controller"),
             client,
-            prometheus_client: PrometheusClient::new(prometheus_endpoint),
         }
     }
 }
@@ -171,14 +201,39 @@ pub async fn reconcile(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Act
 /// The core logic for creating and managing a Canary release, now a state machine.
 use tracing::field;
 
-/// A validated and structured representation of the canary strategy, parsed from the raw spec.
+/// A single, validated step of the canary plan.
+#[derive(Debug, Clone, PartialEq)]
+enum PlanStep {
+    /// Shift the given percentage of traffic to the canary.
+    SetWeight(u8),
+    /// Pause for a fixed duration, or until resumed if `None`.
+    Pause(Option<Duration>),
+    /// Run the analysis for the named metrics (all metrics if empty).
+    Analysis(Vec<String>),
+    /// Install a header-based route to the canary.
+    SetHeaderRoute(SetHeaderRoute),
+    /// Wait for a manual approval, showing the optional message.
+    Approval(Option<String>),
+}
+
+/// A validated and structured representation of the canary (or shadow) strategy, resolved from the spec.
 struct ValidatedCanaryStrategy {
-    steps: Vec<CanaryStep>,
+    steps: Vec<PlanStep>,
     analysis: Option<Analysis>,
     auto_promote: bool,
+    experiment: Option<Experiment>,
 }
 
-/// Parses the raw string fields from the phRelease spec into a validated, structured representation.
+/// Resolves the plan for the release's strategy type.
+fn parse_and_validate_release_plan(release: &phRelease) -> Result<ValidatedCanaryStrategy, anyhow::Error> {
+    let spec = release.spec.as_ref().ok_or_else(|| anyhow!("Missing spec"))?;
+    match spec.strategy.strategy_type {
+        StrategyType::Shadow => parse_and_validate_shadow_spec(release),
+        _ => parse_and_validate_canary_spec(release),
+    }
+}
+
+/// Resolves the typed canary plan from the phRelease spec, validating every step.
 fn parse_and_validate_canary_spec(
     release: &phRelease,
 ) -> Result<ValidatedCanaryStrategy, anyhow::Error> {
@@ -189,53 +244,252 @@ fn parse_and_validate_canary_spec(
         .as_ref()
         .ok_or_else(|| anyhow!("Canary strategy not defined in spec"))?;
 
-    // --- Parse Steps ---
-    let mut steps = Vec::new();
-    if let Some(steps_str) = &canary_spec.steps_str {
-        for s in steps_str.split(',') {
-            let weight = s
-                .trim()
-                .parse::<i32>()
-                .context(format!("Invalid step value: '{}'", s))?;
-            steps.push(CanaryStep {
-                set_weight: weight,
-                // The analysis_window concept is simplified; we use one window for the whole analysis.
-            });
-        }
-    } else {
-        // Default to a single step of 100% if --steps is not provided
-        steps.push(CanaryStep { set_weight: 100 });
-    }
-
-    // --- Parse Metrics and build Analysis ---
-    let mut metrics = Vec::new();
-    if let Some(metric_str) = &canary_spec.metric_str {
-        let metric: CrdMetric = serde_json::from_str(metric_str)
-            .context("Failed to parse --metric flag. It must be a valid JSON string.")?;
-        metrics.push(metric);
-    }
-
-    let analysis = if !metrics.is_empty() {
-        Some(Analysis {
-            interval: canary_spec
-                .analysis_window_str
-                .clone()
-                .unwrap_or_else(|| "5m".to_string()),
-            threshold: 5,    // Default value
-            max_failures: 2, // Default value
-            metrics,
-        })
+    let analysis = validate_analysis(canary_spec.analysis.as_ref())?;
+
+    if let Some(experiment) = &canary_spec.experiment {
+        validate_experiment(experiment)?;
+    }
+
+    let steps = if canary_spec.steps.is_empty() {
+        default_plan(canary_spec.traffic_percent, analysis.is_some())
     } else {
-        None
+        canary_spec
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| resolve_step(index, step, analysis.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?
     };
 
     Ok(ValidatedCanaryStrategy {
         steps,
         analysis,
         auto_promote: canary_spec.auto_promote,
+        experiment: canary_spec.experiment.clone(),
     })
 }
 
+/// Resolves the plan of a shadow release: mirror for `duration`, then analyse
+/// the shadow pods. No real traffic moves until the release is promoted.
+fn parse_and_validate_shadow_spec(release: &phRelease) -> Result<ValidatedCanaryStrategy, anyhow::Error> {
+    let spec = release.spec.as_ref().ok_or_else(|| anyhow!("Missing spec"))?;
+    let shadow_spec = spec
+        .strategy
+        .shadow
+        .as_ref()
+        .ok_or_else(|| anyhow!("Shadow strategy not defined in spec"))?;
+
+    if !(1..=100).contains(&shadow_spec.mirror_percent) {
+        return Err(anyhow!("mirrorPercent must be between 1 and 100, got {}", shadow_spec.mirror_percent));
+    }
+    let analysis = validate_analysis(shadow_spec.analysis.as_ref())?;
+
+    let mut steps = Vec::new();
+    if let Some(duration) = &shadow_spec.duration {
+        steps.push(PlanStep::Pause(Some(parse_duration_str(duration)?)));
+    }
+    if analysis.is_some() {
+        steps.push(PlanStep::Analysis(Vec::new()));
+    }
+    if steps.is_empty() {
+        return Err(anyhow!("Shadow strategy requires a duration or an analysis"));
+    }
+
+    Ok(ValidatedCanaryStrategy {
+        steps,
+        analysis,
+        auto_promote: shadow_spec.auto_promote,
+        experiment: None,
+    })
+}
+
+/// Validates an analysis section. An analysis without metrics has nothing to
+/// evaluate and is treated as absent.
+fn validate_analysis(analysis: Option<&Analysis>) -> Result<Option<Analysis>, anyhow::Error> {
+    let analysis = analysis.cloned().filter(|analysis| !analysis.metrics.is_empty());
+    if let Some(analysis) = &analysis {
+        parse_duration_str(&analysis.interval).map_err(|e| anyhow!("Invalid analysis interval: {}", e))?;
+        if let Some(thresholds) = &analysis.score_thresholds {
+            if !(0.0..=100.0).contains(&thresholds.marginal) || thresholds.marginal > thresholds.pass {
+                return Err(anyhow!("scoreThresholds must satisfy 0 <= marginal <= pass <= 100"));
+            }
+        }
+        for metric in &analysis.metrics {
+            if let Some(comparison) = &metric.comparison {
+                validate_comparison(&metric.name, comparison)?;
+                continue;
+            }
+            let provider = resolve_provider(metric)?;
+            let condition = success_condition(metric, &provider)?;
+            Expression::parse(condition)
+                .map_err(|e| anyhow!("Invalid onSuccess for metric '{}': {}", metric.name, e))?;
+        }
+    }
+    Ok(analysis)
+}
+
+/// Validates the match rules of an experiment.
+fn validate_experiment(experiment: &Experiment) -> Result<(), anyhow::Error> {
+    for (index, request_match) in experiment.matches.iter().enumerate() {
+        if request_match.headers.is_empty() && request_match.cookies.is_empty() && request_match.query_params.is_empty() {
+            return Err(anyhow!("Experiment match {} must set at least one header, cookie or query parameter", index));
+        }
+        // Cookies are matched through the `cookie` header, which a match can only constrain once.
+        let cookie_header = request_match.headers.iter().any(|h| h.header_name.eq_ignore_ascii_case("cookie"));
+        if request_match.cookies.len() > 1 || (cookie_header && !request_match.cookies.is_empty()) {
+            return Err(anyhow!("Experiment match {} can constrain the cookie header only once", index));
+        }
+        let mut names = request_match
+            .headers
+            .iter()
+            .map(|h| &h.header_name)
+            .chain(request_match.cookies.iter().map(|c| &c.name))
+            .chain(request_match.query_params.iter().map(|q| &q.name));
+        if names.any(|name| name.is_empty()) {
+            return Err(anyhow!("Experiment match {} has an empty name", index));
+        }
+    }
+    Ok(())
+}
+
+/// Collects the match rules that send requests to the canary: the experiment's
+/// matches followed by the header routes installed by `setHeaderRoute` steps.
+fn canary_route_matches(experiment: Option<&Experiment>, header_routes: &[SetHeaderRoute]) -> Vec<RouteMatch> {
+    let experiment_matches = experiment.into_iter().flat_map(|e| &e.matches).map(|request_match| {
+        let mut route = RouteMatch {
+            headers: request_match
+                .headers
+                .iter()
+                .map(|h| (h.header_name.to_lowercase(), StringMatch::Exact(h.header_value.clone())))
+                .collect(),
+            query_params: request_match
+                .query_params
+                .iter()
+                .map(|q| (q.name.clone(), StringMatch::Exact(q.value.clone())))
+                .collect(),
+        };
+        for cookie in &request_match.cookies {
+            route = route.with_cookie(&cookie.name, &cookie.value);
+        }
+        route
+    });
+    let header_route_matches = header_routes.iter().filter(|r| !r.matches.is_empty()).map(|r| RouteMatch {
+        headers: r
+            .matches
+            .iter()
+            .map(|h| (h.header_name.to_lowercase(), StringMatch::Exact(h.header_value.clone())))
+            .collect(),
+        query_params: Vec::new(),
+    });
+    experiment_matches.chain(header_route_matches).collect()
+}
+
+/// Installs `route` in the list of active header routes, replacing any route with
+/// the same name. A route without matches removes the named route.
+fn upsert_header_route(header_routes: &[SetHeaderRoute], route: &SetHeaderRoute) -> Vec<SetHeaderRoute> {
+    let mut routes: Vec<SetHeaderRoute> = header_routes.iter().filter(|r| r.name != route.name).cloned().collect();
+    if !route.matches.is_empty() {
+        routes.push(route.clone());
+    }
+    routes
+}
+
+/// Validates a metric's baseline comparison.
+fn validate_comparison(metric_name: &str, comparison: &BaselineComparison) -> Result<(), anyhow::Error> {
+    if !comparison.query.contains("{{track}}") {
+        return Err(anyhow!(
+            "Comparison query of metric '{}' must contain '{{{{track}}}}' to select the stable and canary pods",
+            metric_name
+        ));
+    }
+    for window in [&comparison.lookback, &comparison.step].into_iter().flatten() {
+        parse_duration_str(window).map_err(|e| anyhow!("Metric '{}': {}", metric_name, e))?;
+    }
+    if comparison.significance.is_some_and(|alpha| !(alpha > 0.0 && alpha < 1.0)) {
+        return Err(anyhow!("Metric '{}': significance must be between 0 and 1", metric_name));
+    }
+    if comparison.tolerance.is_some_and(|tolerance| tolerance < 0.0)
+        || comparison.weight.is_some_and(|weight| weight <= 0.0)
+    {
+        return Err(anyhow!("Metric '{}': tolerance must be non-negative and weight positive", metric_name));
+    }
+    Ok(())
+}
+
+/// The plan used when the spec declares no steps: shift `trafficPercent` to the
+/// canary, then either analyse it or wait for a manual decision.
+fn default_plan(traffic_percent: u8, has_analysis: bool) -> Vec<PlanStep> {
+    let gate = if has_analysis {
+        PlanStep::Analysis(Vec::new())
+    } else {
+        PlanStep::Pause(None)
+    };
+    vec![PlanStep::SetWeight(traffic_percent), gate]
+}
+
+/// Validates a single `CanaryStep` from the spec and resolves it into a `PlanStep`.
+fn resolve_step(
+    index: usize,
+    step: &CanaryStep,
+    analysis: Option<&Analysis>,
+) -> Result<PlanStep, anyhow::Error> {
+    let fields_set = [
+        step.set_weight.is_some(),
+        step.pause.is_some(),
+        step.analysis.is_some(),
+        step.set_header_route.is_some(),
+        step.approval.is_some(),
+    ]
+    .iter()
+    .filter(|is_set| **is_set)
+    .count();
+    if fields_set != 1 {
+        return Err(anyhow!(
+            "Step {} must set exactly one of setWeight, pause, analysis, setHeaderRoute or approval",
+            index
+        ));
+    }
+
+    if let Some(weight) = step.set_weight {
+        if weight > 100 {
+            return Err(anyhow!("Step {}: setWeight must be between 0 and 100, got {}", index, weight));
+        }
+        return Ok(PlanStep::SetWeight(weight));
+    }
+
+    if let Some(pause) = &step.pause {
+        let duration = pause
+            .duration
+            .as_deref()
+            .map(parse_duration_str)
+            .transpose()
+            .map_err(|e| anyhow!("Step {}: {}", index, e))?;
+        return Ok(PlanStep::Pause(duration));
+    }
+
+    if let Some(analysis_step) = &step.analysis {
+        let analysis = analysis.ok_or_else(|| {
+            anyhow!("Step {} runs an analysis but no analysis metrics are configured", index)
+        })?;
+        for name in &analysis_step.metrics {
+            if !analysis.metrics.iter().any(|metric| &metric.name == name) {
+                return Err(anyhow!("Step {} references unknown metric '{}'", index, name));
+            }
+        }
+        return Ok(PlanStep::Analysis(analysis_step.metrics.clone()));
+    }
+
+    if let Some(approval) = &step.approval {
+        return Ok(PlanStep::Approval(approval.message.clone()));
+    }
+
+    let route = step.set_header_route.clone().unwrap_or_default();
+    if route.name.is_empty() {
+        return Err(anyhow!("Step {}: setHeaderRoute requires a name", index));
+    }
+    Ok(PlanStep::SetHeaderRoute(route))
+}
+
 async fn apply_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Action, Error> {
     let span = info_span!(
         "apply_release",
@@ -245,7 +499,10 @@ async fn apply_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Act
         let client = ctx.client.clone();
         let ns = release.namespace().unwrap();
     let spec = release.spec.as_ref().ok_or(Error::MissingSpec)?;
-    let status = release.status.as_ref().cloned().unwrap_or_default();
+    let status = phReleaseStatus {
+        observed_generation: release.metadata.generation,
+        ..release.status.as_ref().cloned().unwrap_or_default()
+    };
     let releases: Api<phRelease> = Api::namespaced(client.clone(), &ns);
     let release_name = release.name_any();
 
@@ -257,7 +514,14 @@ async fn apply_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Act
 
     if skip_check {
         println!("⚠️ Signature verification was skipped for release '{}' via annotation.", release_name);
-        // In a real system, we'd create a Kubernetes Event here for auditing.
+        events::warning(
+            &ctx.recorder,
+            &*release,
+            "SignatureCheckSkipped",
+            "VerifySignature",
+            format!("Signature verification was skipped via the '{}' annotation.", SKIP_SIG_CHECK_ANNOTATION),
+        )
+        .await;
     } else {
         // Verification is not skipped, so it's mandatory.
         let verification_config = spec.security.as_ref().and_then(|s| s.signature_verification.as_ref());
@@ -265,11 +529,12 @@ async fn apply_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Act
         if verification_config.is_none() {
             // Config is missing, fail the release.
             println!("❌ Release '{}' failed: Signature verification is mandatory but not configured. To bypass, annotate the resource with 'ph.io/skip-sig-check: \"true\"'.", release_name);
-            let new_status = phReleaseStatus {
-                phase: Some(ReleasePhase::Failed),
-                traffic_split: Some("Error: Signature verification is mandatory but not configured.".to_string()),
-                ..status.clone()
-            };
+            let message = format!(
+                "Signature verification is mandatory but not configured. To bypass, annotate the resource with '{}: \"true\"'.",
+                SKIP_SIG_CHECK_ANNOTATION
+            );
+            events::warning(&ctx.recorder, &*release, "SignatureVerificationNotConfigured", "VerifySignature", message.clone()).await;
+            let new_status = failed_status(&status, "SignatureVerificationNotConfigured", &message);
             update_status(&releases, &release_name, new_status).await?;
             return Ok(Action::await_change());
         }
@@ -285,11 +550,9 @@ async fn apply_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Act
             }
             Err(e) => {
                 println!("❌ Signature verification failed for '{}': {}", release_name, e);
-                let new_status = phReleaseStatus {
-                    phase: Some(ReleasePhase::Failed),
-                    traffic_split: Some(format!("Error: {}", e)),
-                    ..status.clone()
-                };
+                let message = format!("Signature verification failed: {}", e);
+                events::warning(&ctx.recorder, &*release, "SignatureVerificationFailed", "VerifySignature", message.clone()).await;
+                let new_status = failed_status(&status, "SignatureVerificationFailed", &message);
                 update_status(&releases, &release_name, new_status).await?;
                 return Ok(Action::await_change());
             }
@@ -297,25 +560,101 @@ async fn apply_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Act
     }
 
     // --- 2. PARSE AND VALIDATE SPEC ---
-    let validated_canary_strategy = match parse_and_validate_canary_spec(&release) {
+    let validated_canary_strategy = match parse_and_validate_release_plan(&release) {
         Ok(validated_spec) => validated_spec,
         Err(e) => {
-            let error_message = format!("Invalid canary spec: {}", e);
+            let error_message = format!("Invalid release spec: {}", e);
             println!("❌ Release '{}' failed: {}", release_name, error_message);
-            let new_status = phReleaseStatus {
-                phase: Some(ReleasePhase::Failed),
-                traffic_split: Some(format!("Error: {}", error_message)),
-                ..status.clone()
-            };
+            events::warning(&ctx.recorder, &*release, "InvalidSpec", "Validate", error_message.clone()).await;
+            let new_status = failed_status(&status, "InvalidSpec", &error_message);
             update_status(&releases, &release_name, new_status).await?;
             return Ok(Action::await_change());
         }
     };
 
+    // --- 3. MANUAL ROLLOUT ACTIONS ---
+    if let Some(requested) = release.annotations().get(ROLLOUT_ACTION_ANNOTATION) {
+        let actor = release
+            .annotations()
+            .get(ROLLOUT_ACTOR_ANNOTATION)
+            .cloned()
+            .unwrap_or_else(|| "unknown".to_string());
+        let revision = release.annotations().get(ROLLOUT_REVISION_ANNOTATION).cloned();
+        // Clear the request first so that it is never applied twice.
+        let clear = Patch::Merge(json!({
+            "metadata": { "annotations": {
+                ROLLOUT_ACTION_ANNOTATION: null,
+                ROLLOUT_ACTOR_ANNOTATION: null,
+                ROLLOUT_REVISION_ANNOTATION: null
+            } }
+        }));
+        releases.patch(&release_name, &PatchParams::default(), &clear).await?;
+
+        let Some(action) = parse_rollout_action(requested) else {
+            println!("Ignoring unknown rollout action '{}' on release '{}'.", requested, release_name);
+            return Ok(Action::requeue(Duration::from_secs(1)));
+        };
+        let revision = match revision.map(|r| r.trim().parse::<u32>()) {
+            Some(Ok(revision)) => Some(revision),
+            Some(Err(_)) => {
+                println!("Ignoring {:?} on release '{}': invalid '{}' annotation.", action, release_name, ROLLOUT_REVISION_ANNOTATION);
+                return Ok(Action::requeue(Duration::from_secs(1)));
+            }
+            None => None,
+        };
+        match apply_rollout_action(&status, validated_canary_strategy.steps.len(), action, &actor, revision, Utc::now()) {
+            Some(new_status) => {
+                println!("Applying {:?} requested by '{}' to release '{}'.", action, actor, release_name);
+                let note = match new_status.rollback_to_revision {
+                    Some(revision) => format!("{:?} to revision {} requested by '{}'.", action, revision, actor),
+                    None => format!("{:?} requested by '{}'.", action, actor),
+                };
+                events::normal(&ctx.recorder, &*release, "RolloutAction", &format!("{:?}", action), note).await;
+                update_status(&releases, &release_name, new_status).await?;
+            }
+            None => {
+                println!(
+                    "Ignoring {:?} on release '{}': not applicable in phase {:?}.",
+                    action, release_name, status.phase
+                );
+                events::warning(
+                    &ctx.recorder,
+                    &*release,
+                    "RolloutActionIgnored",
+                    &format!("{:?}", action),
+                    format!("{:?} requested by '{}' does not apply in phase {:?}.", action, actor, status.phase),
+                )
+                .await;
+            }
+        }
+        return Ok(Action::requeue(Duration::from_secs(1)));
+    }
+
     // Determine the current phase, defaulting to Progressing if not set.
     let current_phase = status.phase.clone().unwrap_or(ReleasePhase::Progressing);
     span.record("ph.release.phase", &serde_json::to_string(&current_phase).unwrap_or_default());
 
+    // --- 4. REVISIONS ---
+    // A changed spec starts a new revision, unless a promotion or rollback is
+    // still being carried out.
+    let spec_hash = release_spec_hash(spec);
+    let spec_changed = status.revisions.last().is_none_or(|r| r.spec_hash != spec_hash);
+    if spec_changed && !matches!(current_phase, ReleasePhase::Promoting | ReleasePhase::RollingBack) {
+        if status.revisions.is_empty() && status.phase.is_some() {
+            // Release created before revisions were tracked: adopt it as revision 1.
+            let new_status = phReleaseStatus {
+                revisions: record_revision(&[], &spec_hash, &spec.version, revision_history_limit(spec), Utc::now()),
+                ..status.clone()
+            };
+            update_status(&releases, &release_name, new_status).await?;
+            return Ok(Action::requeue(Duration::from_secs(1)));
+        }
+        if status.phase.is_some() {
+            println!("Spec of release '{}' changed. Starting a new revision.", release_name);
+        }
+        return initial_setup(release, ctx).instrument(info_span!("initial_setup")).await;
+    }
+
     // --- STATE MACHINE ---
     match current_phase {
         ReleasePhase::Progressing => {
@@ -331,162 +670,521 @@ async fn apply_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Act
                 return initial_setup(release, ctx).instrument(info_span!("initial_setup")).await;
             }
 
-            // Deployments exist, proceed with the analysis loop if configured.
-            let canary_spec = validated_canary_strategy;
-
-            /* BEGIN CHANGE: Implement automated metrics analysis loop.
-             * This block contains the core logic for integrating the metrics_analyzer.
-             * It checks if analysis is enabled, manages the check interval, invokes
-             * the analyzer, updates the phRelease status with results, and decides
-             * whether to promote, rollback, or continue the analysis based on configured
-             * thresholds.
-             */
-            if let Some(analysis_config) = &canary_spec.analysis {
-                // --- AUTOMATED ANALYSIS LOGIC ---
-                let interval = parse_duration_str(&analysis_config.interval)?;
-                let now = Utc::now();
-
-                // Check if it's time for a new analysis based on lastCheck timestamp.
-                let should_analyze = match status.analysis_run.as_ref().and_then(|ar| ar.last_check.as_ref()) {
-                    Some(last_check_str) => {
-                        let last_check_time = DateTime::parse_from_rfc3339(last_check_str)
-                            .map_err(|e| Error::StatusUpdateError(format!("Invalid lastCheck timestamp: {}", e)))?
-                            .with_timezone(&Utc);
-                        now >= last_check_time + chrono::Duration::from_std(interval).unwrap()
-                    }
-                    None => true, // First time, analyze immediately.
-                };
+            // Deployments exist, walk the canary plan from the recorded step.
+            execute_canary_step(&release, &ctx, &releases, status, &validated_canary_strategy)
+                .instrument(info_span!("execute_canary_step"))
+                .await
+        }
+        ReleasePhase::Promoting => {
+            println!("Reconciling release '{}' in Promoting phase.", release_name);
+            promote_release(release, ctx).instrument(info_span!("promote_release")).await
+        }
+        ReleasePhase::RollingBack => {
+            println!("Reconciling release '{}' in RollingBack phase.", release_name);
+            rollback_release(release, ctx).instrument(info_span!("rollback_release")).await
+        }
+        ReleasePhase::Paused => {
+            println!(
+                "Release '{}' is paused. Annotate it with '{}: resume|promote|abort|rollback' to continue.",
+                release_name, ROLLOUT_ACTION_ANNOTATION
+            );
+            Ok(Action::await_change())
+        }
+        ReleasePhase::Succeeded | ReleasePhase::Failed => {
+            println!("Reconciliation for '{}' is complete (Phase: {:?}). No action needed.", release_name, status.phase);
+            Ok(Action::await_change())
+        }
+    }
+    }.instrument(span).await
+}
 
-                if !should_analyze {
-                    let last_check_time = DateTime::parse_from_rfc3339(
-                        status.analysis_run.as_ref().unwrap().last_check.as_ref().unwrap()
-                    ).unwrap().with_timezone(&Utc);
-                    let next_run_time = last_check_time + chrono::Duration::from_std(interval).unwrap();
-                    let requeue_duration = (next_run_time - now).to_std().unwrap_or(Duration::from_secs(1));
-                    println!("Next analysis for '{}' scheduled in {:?}", release_name, requeue_duration);
-                    return Ok(Action::requeue(requeue_duration));
+/// Executes the canary step at `status.currentStep`, advancing the plan once
+/// the step has completed. When every step has run, the release moves on to
+/// `Promoting` (if `autoPromote` is set) or `Paused` for manual promotion.
+async fn execute_canary_step(
+    release: &phRelease,
+    ctx: &Context,
+    releases: &Api<phRelease>,
+    status: phReleaseStatus,
+    plan: &ValidatedCanaryStrategy,
+) -> Result<Action, Error> {
+    let release_name = release.name_any();
+    let ns = release.namespace().unwrap();
+    let spec = release.spec.as_ref().ok_or(Error::MissingSpec)?;
+    let step_index = status.current_step.unwrap_or(0) as usize;
+
+    let step = match plan.steps.get(step_index) {
+        Some(step) => step,
+        None => {
+            // --- Plan complete ---
+            observe_rollout_latency(&status, "promotion");
+            let mut new_status = status.clone();
+            new_status.phase = if plan.auto_promote {
+                println!("All {} canary steps completed for '{}'. Promoting automatically.", plan.steps.len(), release_name);
+                Some(ReleasePhase::Promoting)
+            } else {
+                println!("All {} canary steps completed for '{}'. Pausing for manual promotion.", plan.steps.len(), release_name);
+                events::normal(&ctx.recorder, release, "AwaitingPromotion", "Pause", "All canary steps completed. Waiting for manual promotion.").await;
+                new_status.pause_reason = Some(PauseReason::Step);
+                Some(ReleasePhase::Paused)
+            };
+            update_status(releases, &release_name, new_status).await?;
+            return Ok(Action::requeue(Duration::from_secs(1)));
+        }
+    };
+
+    match step {
+        PlanStep::SetWeight(weight) => {
+            println!("Step {}: shifting {}% of traffic to the canary for '{}'.", step_index, weight, release_name);
+            let matches = canary_route_matches(plan.experiment.as_ref(), &status.header_routes);
+            set_canary_weight(ctx.client.clone(), &ns, spec, *weight, matches).await?;
+            events::normal(&ctx.recorder, release, "TrafficShifted", "SetWeight", format!("Step {}: {}% of traffic goes to the canary.", step_index, weight)).await;
+            let mut new_status = advance_step(&status, step_index);
+            new_status.traffic_split = Some(format!("stable: {}%, canary: {}%", 100 - weight, weight));
+            new_status.canary_weight = Some(*weight);
+            update_status(releases, &release_name, new_status).await?;
+            Ok(Action::requeue(Duration::from_secs(1)))
+        }
+        PlanStep::Pause(Some(duration)) => {
+            let now = Utc::now();
+            let started_at = status
+                .step_start_time
+                .as_deref()
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|t| t.with_timezone(&Utc));
+
+            match started_at {
+                None => {
+                    println!("Step {}: pausing release '{}' for {:?}.", step_index, release_name, duration);
+                    let new_status = phReleaseStatus {
+                        step_start_time: Some(now.to_rfc3339()),
+                        ..status.clone()
+                    };
+                    update_status(releases, &release_name, new_status).await?;
+                    Ok(Action::requeue(*duration))
                 }
+                Some(started_at) => {
+                    let resume_at = started_at + chrono::Duration::from_std(*duration).unwrap_or_else(|_| chrono::Duration::zero());
+                    if now >= resume_at {
+                        println!("Step {}: pause elapsed for release '{}'.", step_index, release_name);
+                        update_status(releases, &release_name, advance_step(&status, step_index)).await?;
+                        Ok(Action::requeue(Duration::from_secs(1)))
+                    } else {
+                        Ok(Action::requeue((resume_at - now).to_std().unwrap_or(Duration::from_secs(1))))
+                    }
+                }
+            }
+        }
+        PlanStep::Pause(None) => {
+            println!("Step {}: pausing release '{}' until it is resumed.", step_index, release_name);
+            events::normal(&ctx.recorder, release, "Paused", "Pause", format!("Step {}: paused until the release is resumed.", step_index)).await;
+            let new_status = phReleaseStatus {
+                phase: Some(ReleasePhase::Paused),
+                pause_reason: Some(PauseReason::Step),
+                step_start_time: Some(Utc::now().to_rfc3339()),
+                ..status.clone()
+            };
+            update_status(releases, &release_name, new_status).await?;
+            Ok(Action::await_change())
+        }
+        PlanStep::Approval(message) => {
+            let note = format!(
+                "Step {}: waiting for approval{}.",
+                step_index,
+                message.as_deref().map(|m| format!(": {}", m)).unwrap_or_default()
+            );
+            println!("Release '{}': {}", release_name, note);
+            events::normal(&ctx.recorder, release, "AwaitingApproval", "Approval", note).await;
+            let new_status = phReleaseStatus {
+                phase: Some(ReleasePhase::Paused),
+                pause_reason: Some(PauseReason::Step),
+                step_start_time: Some(Utc::now().to_rfc3339()),
+                ..status.clone()
+            };
+            update_status(releases, &release_name, new_status).await?;
+            Ok(Action::await_change())
+        }
+        PlanStep::Analysis(metric_names) => {
+            // Validation guarantees an analysis config exists for analysis steps.
+            let analysis_config = plan.analysis.as_ref().ok_or(Error::MissingSpec)?;
+            let target = MetricTarget {
+                namespace: ns.clone(),
+                release_name: release_name.clone(),
+                release_uid: release.uid(),
+            };
+            run_analysis_step(release, &target, ctx, releases, status, analysis_config, metric_names, step_index)
+                .instrument(info_span!("run_analysis_step"))
+                .await
+        }
+        PlanStep::SetHeaderRoute(route) => {
+            if route.matches.is_empty() {
+                println!("Step {}: removing header route '{}' for '{}'.", step_index, route.name, release_name);
+            } else {
+                println!("Step {}: installing header route '{}' for '{}'.", step_index, route.name, release_name);
+            }
+            let header_routes = upsert_header_route(&status.header_routes, route);
+            let weight = status
+                .canary_weight
+                .or_else(|| spec.strategy.canary.as_ref().map(|c| c.traffic_percent))
+                .unwrap_or(0);
+            let matches = canary_route_matches(plan.experiment.as_ref(), &header_routes);
+            set_canary_weight(ctx.client.clone(), &ns, spec, weight, matches).await?;
+            let mut new_status = advance_step(&status, step_index);
+            new_status.header_routes = header_routes;
+            update_status(releases, &release_name, new_status).await?;
+            Ok(Action::requeue(Duration::from_secs(1)))
+        }
+    }
+}
 
-                // It's time to run the analysis.
-                println!("Running analysis for release '{}'...", release_name);
-                
-                // Run analysis for all configured metrics
-                let (analysis_results, new_history) = run_metrics_analysis(
-                    &ctx.prometheus_client,
-                    &analysis_config.metrics,
-                    status.analysis_run.as_ref().and_then(|ar| ar.metric_history.as_ref()),
-                )
-                .instrument(info_span!("run_metrics_analysis"))
-                .await?;
-                let mut analysis_run_status = status.analysis_run.clone().unwrap_or_default();
-                analysis_run_status.metric_history = Some(new_history);
+/// Runs one round of the analysis for an `analysis` step. The step completes once
+/// the success `threshold` is reached; reaching `maxFailures` rolls the release back.
+#[allow(clippy::too_many_arguments)]
+async fn run_analysis_step(
+    release: &phRelease,
+    target: &MetricTarget,
+    ctx: &Context,
+    releases: &Api<phRelease>,
+    status: phReleaseStatus,
+    analysis_config: &Analysis,
+    metric_names: &[String],
+    step_index: usize,
+) -> Result<Action, Error> {
+    let release_name = target.release_name.as_str();
+    let interval = parse_duration_str(&analysis_config.interval)?;
+    let now = Utc::now();
+
+    // Check if it's time for a new analysis based on lastCheck timestamp.
+    let last_check_time = match status.analysis_run.as_ref().and_then(|ar| ar.last_check.as_ref()) {
+        Some(last_check_str) => Some(
+            DateTime::parse_from_rfc3339(last_check_str)
+                .map_err(|e| Error::StatusUpdateError(format!("Invalid lastCheck timestamp: {}", e)))?
+                .with_timezone(&Utc),
+        ),
+        None => None, // First time, analyze immediately.
+    };
 
+    if let Some(last_check_time) = last_check_time {
+        let next_run_time = last_check_time + chrono::Duration::from_std(interval).unwrap();
+        if now < next_run_time {
+            let requeue_duration = (next_run_time - now).to_std().unwrap_or(Duration::from_secs(1));
+            println!("Next analysis for '{}' scheduled in {:?}", release_name, requeue_duration);
+            return Ok(Action::requeue(requeue_duration));
+        }
+    }
 
-                // Evaluate overall analysis result
-                let all_metrics_passed = analysis_results.iter().all(|(_, result)| matches!(result, AnalysisResult::Success));
-                let has_inconclusive = analysis_results.iter().any(|(_, result)| matches!(result, AnalysisResult::Inconclusive));
-                let is_trending_worse = analysis_results.iter().any(|(_, result)| matches!(result, AnalysisResult::TrendingWorse));
+    // It's time to run the analysis.
+    println!("Step {}: running analysis for release '{}'...", step_index, release_name);
+    // Metrics with a `comparison` are judged together against the baseline;
+    // the rest are evaluated individually against their success condition.
+    let (compared_metrics, metrics): (Vec<CrdMetric>, Vec<CrdMetric>) = analysis_config
+        .metrics
+        .iter()
+        .filter(|metric| metric_names.is_empty() || metric_names.contains(&metric.name))
+        .cloned()
+        .partition(|metric| metric.comparison.is_some());
+
+    let (mut analysis_results, new_history) = run_metrics_analysis(
+        &ctx.metrics_analyzer,
+        target,
+        &metrics,
+        status.analysis_run.as_ref().and_then(|ar| ar.metric_history.as_ref()),
+    )
+    .instrument(info_span!("run_metrics_analysis"))
+    .await?;
+    let mut analysis_run_status = status.analysis_run.clone().unwrap_or_default();
+    analysis_run_status.metric_history = Some(new_history);
+
+    if !compared_metrics.is_empty() {
+        let thresholds = analysis_config.score_thresholds.clone().unwrap_or_default();
+        let report = ctx
+            .metrics_analyzer
+            .judge(&compared_metrics, &thresholds)
+            .instrument(info_span!("judge_canary"))
+            .await;
+        println!("Canary judge score for '{}': {:?}", release_name, report.score);
+        analysis_results.push(("canary-judge".to_string(), report.result));
+        analysis_run_status.score = report.score;
+        analysis_run_status.metric_scores = report.metric_scores;
+    }
 
-                // Log individual metric results
-                for (metric_name, result) in &analysis_results {
-                    println!("  - Metric '{}': {:?}", metric_name, result);
-                }
+    // Evaluate overall analysis result
+    let all_metrics_passed = analysis_results.iter().all(|(_, result)| matches!(result, AnalysisResult::Success));
+    let has_inconclusive = analysis_results.iter().any(|(_, result)| matches!(result, AnalysisResult::Inconclusive));
+    let is_trending_worse = analysis_results.iter().any(|(_, result)| matches!(result, AnalysisResult::TrendingWorse));
 
-                // Update success/failure counters based on analysis results
-                if is_trending_worse {
-                    println!("~ Predictive analysis detected a negative trend for '{}'. Pausing release.", release_name);
-                    let mut new_status = status.clone();
-                    new_status.phase = Some(ReleasePhase::Paused);
-                    new_status.analysis_run = Some(analysis_run_status);
-                    // TODO: Add a specific condition for this
-                    update_status(&releases, &release_name, new_status).await?;
-                    return Ok(Action::requeue(interval));
-                } else if all_metrics_passed {
-                    analysis_run_status.success_count += 1;
-                    analysis_run_status.failure_count = 0; // Reset failure count on success
-                    println!("All metrics passed for '{}'. Success count: {}", release_name, analysis_run_status.success_count);
-                } else if has_inconclusive {
-                    // Don't increment failure count for inconclusive results, just log and retry
-                    println!("Some metrics were inconclusive for '{}'. Will retry on next analysis.", release_name);
-                } else {
-                    // At least one metric failed
-                    analysis_run_status.failure_count += 1;
-                    analysis_run_status.success_count = 0; // Reset success count on failure
-                    println!("Some metrics failed for '{}'. Failure count: {}", release_name, analysis_run_status.failure_count);
-                }
+    // Log individual metric results
+    for (metric_name, result) in &analysis_results {
+        println!("  - Metric '{}': {:?}", metric_name, result);
+    }
 
-                analysis_run_status.last_check = Some(now.to_rfc3339());
+    // Update success/failure counters based on analysis results
+    if is_trending_worse {
+        println!("~ Predictive analysis detected a negative trend for '{}'. Pausing release.", release_name);
+        let trending: Vec<&str> = analysis_results
+            .iter()
+            .filter(|(_, result)| matches!(result, AnalysisResult::TrendingWorse))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let message = format!("Step {}: metrics trending worse ({}). Paused for review.", step_index, trending.join(", "));
+        events::warning(&ctx.recorder, release, "NegativeTrend", "Analysis", message.clone()).await;
+        let mut new_status = status.clone();
+        new_status.phase = Some(ReleasePhase::Paused);
+        new_status.pause_reason = Some(PauseReason::Analysis);
+        new_status.analysis_run = Some(analysis_run_status);
+        conditions::set_state(&mut new_status.conditions, ResourceState::Progressing, "NegativeTrend", &message, new_status.observed_generation, now);
+        update_status(releases, release_name, new_status).await?;
+        return Ok(Action::requeue(interval));
+    } else if all_metrics_passed {
+        analysis_run_status.success_count += 1;
+        analysis_run_status.failure_count = 0; // Reset failure count on success
+        println!("All metrics passed for '{}'. Success count: {}", release_name, analysis_run_status.success_count);
+    } else if has_inconclusive {
+        // Don't increment failure count for inconclusive results, just log and retry
+        println!("Some metrics were inconclusive for '{}'. Will retry on next analysis.", release_name);
+    } else {
+        // At least one metric failed
+        analysis_run_status.failure_count += 1;
+        analysis_run_status.success_count = 0; // Reset success count on failure
+        println!("Some metrics failed for '{}'. Failure count: {}", release_name, analysis_run_status.failure_count);
+    }
 
-                let mut new_status = status.clone();
-                new_status.analysis_run = Some(analysis_run_status.clone());
+    analysis_run_status.last_check = Some(now.to_rfc3339());
+
+    // Decide the next step based on thresholds
+    if analysis_run_status.success_count >= analysis_config.threshold {
+        println!("Success threshold ({}) reached for '{}'. Analysis step {} passed.",
+                analysis_config.threshold, release_name, step_index);
+        events::normal(&ctx.recorder, release, "AnalysisSucceeded", "Analysis", format!("Step {}: analysis passed {} checks.", step_index, analysis_config.threshold)).await;
+        // Start the next analysis step from a clean slate, keeping the history for trend analysis.
+        let mut new_status = advance_step(&status, step_index);
+        new_status.analysis_run = Some(AnalysisRunStatus {
+            metric_history: analysis_run_status.metric_history,
+            score: analysis_run_status.score,
+            metric_scores: analysis_run_status.metric_scores,
+            ..Default::default()
+        });
+        update_status(releases, release_name, new_status).await?;
+        return Ok(Action::requeue(Duration::from_secs(1)));
+    }
 
-                // Decide the next phase based on thresholds
-                if analysis_run_status.success_count >= analysis_config.threshold {
-                    // --- Measure Latency on Promotion ---
-                    if let Some(start_time_str) = &status.progressing_start_time {
-                        if let Ok(start_time) = DateTime::parse_from_rfc3339(start_time_str) {
-                            let duration = Utc::now().signed_duration_since(start_time);
-                            metrics::PHGIT_ROLLOUT_STEP_LATENCY_SECONDS.observe(duration.num_seconds() as f64);
-                            println!("Observed promotion latency: {}s", duration.num_seconds());
-                        }
-                    }
-                    new_status.phase = if canary_spec.auto_promote {
-                        println!("Success threshold ({}) reached for '{}'. Promoting automatically.", 
-                                analysis_config.threshold, release_name);
-                        Some(ReleasePhase::Promoting)
-                    } else {
-                        println!("Success threshold ({}) reached for '{}'. Pausing for manual promotion.", 
-                                analysis_config.threshold, release_name);
-                        Some(ReleasePhase::Paused)
-                    };
-                } else if analysis_run_status.failure_count >= analysis_config.max_failures {
-                    // --- Measure Latency on Rollback ---
-                     if let Some(start_time_str) = &status.progressing_start_time {
-                        if let Ok(start_time) = DateTime::parse_from_rfc3339(start_time_str) {
-                            let duration = Utc::now().signed_duration_since(start_time);
-                            metrics::PHGIT_ROLLOUT_STEP_LATENCY_SECONDS.observe(duration.num_seconds() as f64);
-                             println!("Observed rollback latency: {}s", duration.num_seconds());
-                        }
-                    }
-                    println!("Failure threshold ({}) reached for '{}'. Rolling back automatically.", 
-                            analysis_config.max_failures, release_name);
-                    new_status.phase = Some(ReleasePhase::RollingBack);
-                } else {
-                    println!("Analysis for '{}' complete. Successes: {}/{}, Failures: {}/{}. Continuing analysis.", 
-                            release_name, 
-                            analysis_run_status.success_count, analysis_config.threshold,
-                            analysis_run_status.failure_count, analysis_config.max_failures);
-                }
+    let mut new_status = status.clone();
+    if analysis_run_status.failure_count >= analysis_config.max_failures {
+        observe_rollout_latency(&status, "rollback");
+        println!("Failure threshold ({}) reached for '{}'. Rolling back automatically.", 
+                analysis_config.max_failures, release_name);
+        let failed: Vec<&str> = analysis_results
+            .iter()
+            .filter(|(_, result)| matches!(result, AnalysisResult::Failure))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let message = format!(
+            "Step {}: analysis failed {} times (failing metrics: {}). Rolling back.",
+            step_index,
+            analysis_run_status.failure_count,
+            failed.join(", ")
+        );
+        events::warning(&ctx.recorder, release, "AnalysisFailed", "Analysis", message.clone()).await;
+        new_status.phase = Some(ReleasePhase::RollingBack);
+        conditions::set_state(&mut new_status.conditions, ResourceState::Degraded, "AnalysisFailed", &message, new_status.observed_generation, now);
+    } else {
+        println!("Analysis for '{}' complete. Successes: {}/{}, Failures: {}/{}. Continuing analysis.", 
+                release_name, 
+                analysis_run_status.success_count, analysis_config.threshold,
+                analysis_run_status.failure_count, analysis_config.max_failures);
+    }
+    new_status.analysis_run = Some(analysis_run_status);
 
-                update_status(&releases, &release_name, new_status).await?;
-                Ok(Action::requeue(interval))
+    update_status(releases, release_name, new_status).await?;
+    Ok(Action::requeue(interval))
+}
 
-            } else {
-                // No analysis configured, this is a manual canary.
-                println!("Release '{}' is in Progressing phase (manual). Waiting for spec changes.", release_name);
-                Ok(Action::requeue(Duration::from_secs(300)))
+/// Returns a copy of `status` pointing at the step after `step_index`.
+fn advance_step(status: &phReleaseStatus, step_index: usize) -> phReleaseStatus {
+    phReleaseStatus {
+        current_step: Some(step_index as u32 + 1),
+        step_start_time: None,
+        ..status.clone()
+    }
+}
+
+/// The `strategy` label value used for rollout metrics.
+fn strategy_label(strategy_type: &StrategyType) -> &'static str {
+    match strategy_type {
+        StrategyType::Canary => "canary",
+        StrategyType::BlueGreen => "blue_green",
+        StrategyType::Shadow => "shadow",
+    }
+}
+
+/// Parses the value of the `ph.io/rollout-action` annotation.
+fn parse_rollout_action(value: &str) -> Option<RolloutAction> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "resume" | "approve" => Some(RolloutAction::Resume),
+        "promote" => Some(RolloutAction::Promote),
+        "abort" => Some(RolloutAction::Abort),
+        "rollback" => Some(RolloutAction::Rollback),
+        _ => None,
+    }
+}
+
+/// Computes the status after a manual rollout action, recording who requested
+/// it. `revision` is the rollback target, if one was given. Returns `None` if the
+/// action does not apply to the release's phase.
+fn apply_rollout_action(
+    status: &phReleaseStatus,
+    plan_len: usize,
+    action: RolloutAction,
+    actor: &str,
+    revision: Option<u32>,
+    now: DateTime<Utc>,
+) -> Option<phReleaseStatus> {
+    let phase = status.phase.clone().unwrap_or(ReleasePhase::Progressing);
+    let step_index = status.current_step.unwrap_or(0) as usize;
+
+    let mut new_status = match (action, phase) {
+        // Paused by the analysis: run the analysis step again rather than skip it.
+        (RolloutAction::Resume, ReleasePhase::Paused) if status.pause_reason == Some(PauseReason::Analysis) => phReleaseStatus {
+            phase: Some(ReleasePhase::Progressing),
+            analysis_run: status.analysis_run.as_ref().map(|run| AnalysisRunStatus {
+                metric_history: run.metric_history.clone(),
+                ..Default::default()
+            }),
+            ..status.clone()
+        },
+        (RolloutAction::Resume, ReleasePhase::Paused) if step_index < plan_len => phReleaseStatus {
+            phase: Some(ReleasePhase::Progressing),
+            ..advance_step(status, step_index)
+        },
+        // Paused after the last step: the release is waiting for its promotion.
+        (RolloutAction::Resume, ReleasePhase::Paused)
+        | (RolloutAction::Promote, ReleasePhase::Progressing | ReleasePhase::Paused) => phReleaseStatus {
+            phase: Some(ReleasePhase::Promoting),
+            ..status.clone()
+        },
+        (RolloutAction::Abort, ReleasePhase::Progressing | ReleasePhase::Paused) => phReleaseStatus {
+            phase: Some(ReleasePhase::RollingBack),
+            ..status.clone()
+        },
+        (
+            RolloutAction::Rollback,
+            phase @ (ReleasePhase::Progressing | ReleasePhase::Paused | ReleasePhase::Succeeded | ReleasePhase::Failed),
+        ) => {
+            let target = rollback_target(&status.revisions, revision);
+            // Without an earlier revision to restore, only an in-flight canary can
+            // be rolled back (back to the current stable version).
+            let in_flight = matches!(phase, ReleasePhase::Progressing | ReleasePhase::Paused);
+            if target.is_none() && (revision.is_some() || !in_flight) {
+                return None;
+            }
+            phReleaseStatus {
+                phase: Some(ReleasePhase::RollingBack),
+                rollback_to_revision: target,
+                ..status.clone()
             }
-            /* END CHANGE */
-        }
-        ReleasePhase::Promoting => {
-            println!("Reconciling release '{}' in Promoting phase.", release_name);
-            promote_release(release, ctx).instrument(info_span!("promote_release")).await
-        }
-        ReleasePhase::RollingBack => {
-            println!("Reconciling release '{}' in RollingBack phase.", release_name);
-            rollback_release(release, ctx).instrument(info_span!("rollback_release")).await
         }
-        ReleasePhase::Succeeded | ReleasePhase::Failed | ReleasePhase::Paused => {
-            println!("Reconciliation for '{}' is complete (Phase: {:?}). No action needed.", release_name, status.phase);
-            Ok(Action::await_change())
+        _ => return None,
+    };
+    new_status.pause_reason = None;
+
+    new_status.approvals.push(ApprovalRecord {
+        action,
+        approver: actor.to_string(),
+        time: now.to_rfc3339(),
+        step: status.current_step,
+        revision: new_status.rollback_to_revision,
+    });
+    Some(new_status)
+}
+
+/// Resolves the revision a rollback restores: `requested` if it names an earlier
+/// successful revision, otherwise the latest successful revision before the
+/// current one.
+fn rollback_target(revisions: &[ReleaseRevision], requested: Option<u32>) -> Option<u32> {
+    let current = revisions.last()?.revision;
+    revisions
+        .iter()
+        .rev()
+        .filter(|r| r.revision < current && r.outcome == RevisionOutcome::Succeeded)
+        .find(|r| requested.is_none_or(|n| r.revision == n))
+        .map(|r| r.revision)
+}
+
+/// Hash of the spec, used to detect when a new revision starts.
+fn release_spec_hash(spec: &phReleaseSpec) -> String {
+    content_hash(&serde_json::to_vec(spec).unwrap_or_default())
+}
+
+fn revision_history_limit(spec: &phReleaseSpec) -> usize {
+    spec.revision_history_limit.unwrap_or(DEFAULT_REVISION_HISTORY_LIMIT).max(1) as usize
+}
+
+/// Appends a new `Progressing` revision to the history, marking a revision that
+/// was still in flight as `Superseded` and keeping at most `limit` entries.
+fn record_revision(
+    revisions: &[ReleaseRevision],
+    spec_hash: &str,
+    version: &str,
+    limit: usize,
+    now: DateTime<Utc>,
+) -> Vec<ReleaseRevision> {
+    let mut revisions = revisions.to_vec();
+    if let Some(last) = revisions.last_mut().filter(|r| r.outcome == RevisionOutcome::Progressing) {
+        last.outcome = RevisionOutcome::Superseded;
+        last.finished_at = Some(now.to_rfc3339());
+    }
+    let revision = revisions.last().map_or(1, |r| r.revision + 1);
+    revisions.push(ReleaseRevision {
+        revision,
+        version: version.to_string(),
+        spec_hash: spec_hash.to_string(),
+        started_at: now.to_rfc3339(),
+        finished_at: None,
+        outcome: RevisionOutcome::Progressing,
+    });
+    let excess = revisions.len().saturating_sub(limit);
+    revisions.drain(..excess);
+    revisions
+}
+
+/// Records the outcome of the current (latest) revision.
+fn finish_revision(revisions: &mut [ReleaseRevision], outcome: RevisionOutcome, now: DateTime<Utc>) {
+    if let Some(current) = revisions.last_mut() {
+        current.outcome = outcome;
+        current.finished_at = Some(now.to_rfc3339());
+    }
+}
+
+/// Updates the history after a rollback. Without a `target`, the in-flight
+/// revision failed. With one, every successful revision newer than the target
+/// was rolled back; an in-flight revision still counts as failed.
+fn roll_back_revisions(revisions: &mut [ReleaseRevision], target: Option<u32>, now: DateTime<Utc>) {
+    for r in revisions.iter_mut() {
+        let outcome = match r.outcome {
+            RevisionOutcome::Progressing => RevisionOutcome::Failed,
+            RevisionOutcome::Succeeded if target.is_some_and(|t| r.revision > t) => RevisionOutcome::RolledBack,
+            _ => continue,
+        };
+        r.outcome = outcome;
+        r.finished_at = Some(now.to_rfc3339());
+    }
+}
+
+/// Records how long the release spent progressing before a promotion or rollback decision.
+fn observe_rollout_latency(status: &phReleaseStatus, outcome: &str) {
+    if let Some(start_time_str) = &status.progressing_start_time {
+        if let Ok(start_time) = DateTime::parse_from_rfc3339(start_time_str) {
+            let duration = Utc::now().signed_duration_since(start_time);
+            metrics::PHGIT_ROLLOUT_STEP_LATENCY_SECONDS.observe(duration.num_seconds() as f64);
+            println!("Observed {} latency: {}s", outcome, duration.num_seconds());
         }
     }
-    }.instrument(span).await
 }
 
 /// Runs analysis for all configured metrics and returns the results.
 async fn run_metrics_analysis(
-    prometheus_client: &PrometheusClient,
+    metrics_analyzer: &MetricsAnalyzer,
+    target: &MetricTarget,
     metrics: &[crate::crds::Metric],
     history: Option<&Vec<crate::crds::MetricHistory>>,
 ) -> Result<(Vec<(String, AnalysisResult)>, Vec<crate::crds::MetricHistory>), Error> {
@@ -512,35 +1210,38 @@ async fn run_metrics_analysis(
             })
             .unwrap_or_default();
 
-        let (result, value) = prometheus_client
-            .analyze(metric, &metric_history_points)
+        let (result, value) = metrics_analyzer
+            .analyze(metric, target, &metric_history_points)
             .await?;
         results.push((metric.name.clone(), result.clone()));
-
-        // Update history regardless of outcome to have a complete picture.
-        if let Some(h) = new_history.iter_mut().find(|h| h.name == metric.name) {
-            h.values.push(crate::crds::HistoricalValue {
-                timestamp: Utc::now().to_rfc3339(),
-                value,
-            });
-            // Cap history size
-            if h.values.len() > 20 {
-                h.values.remove(0);
-            }
-        } else {
-            new_history.push(crate::crds::MetricHistory {
-                name: metric.name.clone(),
-                values: vec![crate::crds::HistoricalValue {
-                    timestamp: Utc::now().to_rfc3339(),
-                    value,
-                }],
-            });
-        }
+        record_sample(&mut new_history, &metric.name, value, Utc::now().to_rfc3339());
     }
 
     Ok((results, new_history))
 }
 
+/// Appends a measured value to the history of a metric, keeping the last 20.
+/// A result without a measured value leaves the history unchanged, so that
+/// failed or pending queries do not skew trends.
+fn record_sample(history: &mut Vec<crate::crds::MetricHistory>, name: &str, value: Option<f64>, timestamp: String) {
+    let Some(value) = value else {
+        return;
+    };
+    let sample = crate::crds::HistoricalValue { timestamp, value };
+    if let Some(h) = history.iter_mut().find(|h| h.name == name) {
+        h.values.push(sample);
+        // Cap history size
+        if h.values.len() > 20 {
+            h.values.remove(0);
+        }
+    } else {
+        history.push(crate::crds::MetricHistory {
+            name: name.to_string(),
+            values: vec![sample],
+        });
+    }
+}
+
 /// Performs the initial setup of Kubernetes resources for the release.
 async fn initial_setup(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Action, Error> {
     let client = ctx.client.clone();
@@ -550,6 +1251,8 @@ async fn initial_setup(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Act
     let deployments: Api<Deployment> = Api::namespaced(client.clone(), &ns);
     let services: Api<Service> = Api::namespaced(client.clone(), &ns);
 
+    let status = release.status.clone().unwrap_or_default();
+
     println!("Performing initial setup for release '{}'", release.name_any());
 
     let app_name = &spec.app_name;
@@ -557,15 +1260,21 @@ async fn initial_setup(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Act
     let stable_name = "stable".to_string();
     let canary_name = "canary".to_string();
 
-    let canary_strategy = match &spec.strategy.strategy_type {
-        StrategyType::Canary => spec.strategy.canary.as_ref().ok_or(Error::MissingSpec)?,
-        _ => return Err(Error::UnsupportedStrategy),
-    };
+    if !matches!(spec.strategy.strategy_type, StrategyType::Canary | StrategyType::Shadow) {
+        return Err(Error::UnsupportedStrategy);
+    }
 
     // Create or update the root Service.
-    let service = build_service(app_name, app_name);
+    let service = build_service(app_name);
     services.patch(app_name, &PatchParams::apply("ph-release-controller"), &Patch::Apply(&service)).await?;
 
+    // Create or update the versioned Services that SMI and Gateway API backends route to.
+    for version_id in [&stable_name, &canary_name] {
+        let version_service = build_version_service(app_name, version_id);
+        let version_service_name = format!("{}-{}", app_name, version_id);
+        services.patch(&version_service_name, &PatchParams::apply("ph-release-controller"), &Patch::Apply(&version_service)).await?;
+    }
+
     // Determine the stable version from the existing stable deployment, if any.
     let stable_version = deployments.get(&format!("{}-{}", app_name, stable_name)).await
         .ok()
@@ -583,45 +1292,119 @@ async fn initial_setup(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<Act
     deployments.patch(&format!("{}-{}", app_name, canary_name), &PatchParams::apply("ph-release-controller"), &Patch::Apply(&canary_dep_def)).await?;
 
     // Traffic Splitting Logic
-    let traffic_percent = canary_strategy.traffic_percent;
-    if let Some(mesh_client) = get_traffic_manager_client(client.clone()).await? {
+    let (traffic_percent, traffic_split) = if let StrategyType::Shadow = spec.strategy.strategy_type {
+        // A shadow release keeps every user on stable and only mirrors traffic.
+        let shadow_strategy = spec.strategy.shadow.as_ref().ok_or(Error::MissingSpec)?;
+        start_mirroring(client.clone(), &ns, spec, shadow_strategy.mirror_percent).await?;
+        (0, format!("stable: 100%, canary: 0% (mirroring {}%)", shadow_strategy.mirror_percent))
+    } else {
+        let canary_strategy = spec.strategy.canary.as_ref().ok_or(Error::MissingSpec)?;
+        let traffic_percent = canary_strategy.traffic_percent;
+        let matches = canary_route_matches(canary_strategy.experiment.as_ref(), &[]);
+        set_canary_weight(client.clone(), &ns, spec, traffic_percent, matches).await?;
+        (traffic_percent, format!("stable: {}%, canary: {}%", 100 - traffic_percent, traffic_percent))
+    };
+
+    // Set the initial status to Progressing. Only the revision history and the
+    // audit trail carry over from an earlier revision.
+    let now = Utc::now();
+    let spec_hash = release_spec_hash(spec);
+    let revisions = if status.revisions.last().is_some_and(|r| r.spec_hash == spec_hash) {
+        // Same spec, e.g. the canary Deployment was deleted: resume the revision.
+        status.revisions.clone()
+    } else {
+        let revisions = record_revision(&status.revisions, &spec_hash, canary_version, revision_history_limit(spec), now);
+        if let Some(revision) = revisions.last() {
+            events::normal(
+                &ctx.recorder,
+                &*release,
+                "RevisionStarted",
+                "Rollout",
+                format!("Revision {}: rolling out version '{}' next to stable '{}'.", revision.revision, canary_version, stable_version),
+            )
+            .await;
+        }
+        revisions
+    };
+    let new_status = phReleaseStatus {
+        phase: Some(ReleasePhase::Progressing),
+        revisions,
+        approvals: status.approvals.clone(),
+        conditions: status.conditions.clone(),
+        observed_generation: release.metadata.generation,
+        stable_version: Some(stable_version),
+        canary_version: Some(canary_version.clone()),
+        traffic_split: Some(traffic_split),
+        current_step: Some(0),
+        canary_weight: Some(traffic_percent),
+        progressing_start_time: Some(now.to_rfc3339()),
+        ..Default::default()
+    };
+
+    update_status(&releases, &release.name_any(), new_status).await?;
+    println!("Initial setup complete for '{}'. Starting monitoring phase.", release.name_any());
+
+    Ok(Action::requeue(Duration::from_secs(1)))
+}
+
+/// Shifts `weight` percent of traffic to the canary, and sends requests matching
+/// `matches` to the canary regardless of the weight. Uses the detected traffic
+/// manager when one is available, and otherwise approximates the split by scaling
+/// the stable and canary Deployments.
+async fn set_canary_weight(
+    client: Client,
+    ns: &str,
+    spec: &phReleaseSpec,
+    weight: u8,
+    matches: Vec<RouteMatch>,
+) -> Result<(), Error> {
+    let app_name = &spec.app_name;
+    if let Some(mesh_client) = get_traffic_manager_client(client.clone(), spec.traffic_management.as_ref()).await? {
         println!("Traffic manager detected. Shifting traffic via mesh/controller.");
         let split = MeshTrafficSplit {
-            app_name: app_name.clone(),
+            app_name: app_name.to_string(),
             weights: vec![
-                (stable_name.clone(), 100 - traffic_percent),
-                (canary_name.clone(), traffic_percent),
+                ("stable".to_string(), 100 - weight),
+                ("canary".to_string(), weight),
             ],
+            matches,
         };
         mesh_client.update_traffic_split(ns, split).await?;
     } else {
         println!("No service mesh detected. Shifting traffic via replica scaling.");
-        // Fallback to replica scaling if no mesh is found
-        let canary_replicas_scaled = (DEFAULT_REPLICAS as u8 * traffic_percent) / 100;
-        let stable_replicas_scaled = DEFAULT_REPLICAS as u8 - canary_replicas_scaled;
-        
+        if !matches.is_empty() {
+            println!("Replica scaling cannot route by request attributes. Ignoring {} match rule(s).", matches.len());
+        }
+        let deployments: Api<Deployment> = Api::namespaced(client, ns);
+        let canary_replicas = (DEFAULT_REPLICAS * weight as i32) / 100;
+        let stable_replicas = DEFAULT_REPLICAS - canary_replicas;
+
         let patch_params = PatchParams::apply("ph-release-controller");
-        let canary_patch = Patch::Merge(json!({ "spec": { "replicas": canary_replicas_scaled } }));
-        let stable_patch = Patch::Merge(json!({ "spec": { "replicas": stable_replicas_scaled } }));
+        let canary_patch = Patch::Merge(json!({ "spec": { "replicas": canary_replicas } }));
+        let stable_patch = Patch::Merge(json!({ "spec": { "replicas": stable_replicas } }));
 
         deployments.patch(&format!("{}-canary", app_name), &patch_params, &canary_patch).await?;
         deployments.patch(&format!("{}-stable", app_name), &patch_params, &stable_patch).await?;
     }
+    Ok(())
+}
 
-    // Set the initial status to Progressing.
-    let new_status = phReleaseStatus {
-        phase: Some(ReleasePhase::Progressing),
-        stable_version: Some(stable_version),
-        canary_version: Some(canary_version.clone()),
-        traffic_split: Some(format!("stable: {}%, canary: {}%", 100 - traffic_percent, traffic_percent)),
-        progressing_start_time: Some(Utc::now().to_rfc3339()),
-        ..Default::default()
+/// Serves all traffic from the stable version and mirrors `percent` percent of it
+/// to the canary. Unlike weight shifting there is no replica-scaling fallback, as
+/// that would expose users to the new version.
+async fn start_mirroring(client: Client, ns: &str, spec: &phReleaseSpec, percent: u8) -> Result<(), Error> {
+    let traffic_manager = get_traffic_manager_client(client, spec.traffic_management.as_ref())
+        .await?
+        .ok_or_else(|| Error::MirroringUnavailable("no traffic manager is installed".to_string()))?;
+    let mirror = MeshTrafficMirror {
+        app_name: spec.app_name.clone(),
+        mirror_to: "canary".to_string(),
+        percent,
     };
-
-    update_status(&releases, &release.name_any(), new_status).await?;
-    println!("Initial setup complete for '{}'. Starting monitoring phase.", release.name_any());
-
-    Ok(Action::requeue(Duration::from_secs(1)))
+    traffic_manager
+        .update_mirror(ns, mirror)
+        .await
+        .map_err(|e| Error::MirroringUnavailable(e.to_string()))
 }
 
 /// Promotes the canary release to 100% traffic.
@@ -635,7 +1418,7 @@ async fn promote_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<A
     let app_name = &spec.app_name;
     println!("Promoting canary '{}' to stable for release '{}'", spec.version, release.name_any());
 
-    if let Some(traffic_manager) = get_traffic_manager_client(client.clone()).await? {
+    if let Some(traffic_manager) = get_traffic_manager_client(client.clone(), spec.traffic_management.as_ref()).await? {
         println!("Traffic manager detected. Promoting via client.");
         traffic_manager.promote(ns, app_name).await?;
     } else {
@@ -653,16 +1436,21 @@ async fn promote_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<A
         deployments.patch(&format!("{}-canary", app_name), &patch_params, &canary_scale_patch).await?;
     }
 
-    let new_status = phReleaseStatus {
+    let mut new_status = phReleaseStatus {
         phase: Some(ReleasePhase::Succeeded),
         stable_version: Some(spec.version.clone()),
         canary_version: None,
         traffic_split: Some("stable: 100%, canary: 0%".to_string()),
+        canary_weight: None,
+        header_routes: Vec::new(),
+        observed_generation: release.metadata.generation,
         ..release.status.as_ref().cloned().unwrap_or_default()
     };
+    finish_revision(&mut new_status.revisions, RevisionOutcome::Succeeded, Utc::now());
+    events::normal(&ctx.recorder, &*release, "Promoted", "Promote", format!("Version '{}' now serves all traffic.", spec.version)).await;
     update_status(&releases, &release.name_any(), new_status).await?;
 
-    metrics::PHGIT_ROLLOUTS_TOTAL.with_label_values(&["canary", "succeeded"]).inc();
+    metrics::PHGIT_ROLLOUTS_TOTAL.with_label_values(&[strategy_label(&spec.strategy.strategy_type), "succeeded"]).inc();
     println!("Release '{}' promoted successfully.", release.name_any());
     Ok(Action::await_change())
 }
@@ -675,10 +1463,16 @@ async fn rollback_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<
     let releases: Api<phRelease> = Api::namespaced(client.clone(), &ns);
     let deployments: Api<Deployment> = Api::namespaced(client.clone(), &ns);
 
+    let status = release.status.as_ref().cloned().unwrap_or_default();
+    let target = status
+        .rollback_to_revision
+        .and_then(|n| status.revisions.iter().find(|r| r.revision == n))
+        .cloned();
+
     let app_name = &spec.app_name;
     println!("Rolling back canary for release '{}'", release.name_any());
 
-    if let Some(traffic_manager) = get_traffic_manager_client(client.clone()).await? {
+    if let Some(traffic_manager) = get_traffic_manager_client(client.clone(), spec.traffic_management.as_ref()).await? {
         println!("Traffic manager detected. Rolling back via client.");
         traffic_manager.rollback(ns, app_name).await?;
     } else {
@@ -691,14 +1485,37 @@ async fn rollback_release(release: Arc<phRelease>, ctx: Arc<Context>) -> Result<
         deployments.patch(&format!("{}-stable", app_name), &patch_params, &stable_patch).await?;
     }
 
-    let new_status = phReleaseStatus {
+    // Restoring an earlier revision redeploys its version on the stable track.
+    if let Some(target) = &target {
+        println!("Restoring version '{}' of revision {} on the stable track.", target.version, target.revision);
+        let restore_patch = Patch::Merge(json!({
+            "spec": { "template": { "spec": { "containers": [{"name": app_name, "image": format!("nginx:{}", target.version)}] } } }
+        }));
+        deployments.patch(&format!("{}-stable", app_name), &PatchParams::apply("ph-release-controller"), &restore_patch).await?;
+    }
+
+    let mut new_status = phReleaseStatus {
         phase: Some(ReleasePhase::Failed),
         traffic_split: Some("stable: 100%, canary: 0%".to_string()),
-        ..release.status.as_ref().cloned().unwrap_or_default()
+        canary_weight: Some(0),
+        header_routes: Vec::new(),
+        stable_version: target.as_ref().map(|t| t.version.clone()).or(status.stable_version.clone()),
+        rollback_to_revision: None,
+        observed_generation: release.metadata.generation,
+        ..status.clone()
+    };
+    roll_back_revisions(&mut new_status.revisions, target.as_ref().map(|t| t.revision), Utc::now());
+    let note = match &target {
+        Some(target) => format!("Restored version '{}' of revision {}.", target.version, target.revision),
+        None => format!(
+            "Shifted all traffic back to stable version '{}'.",
+            new_status.stable_version.as_deref().unwrap_or("unknown")
+        ),
     };
+    events::warning(&ctx.recorder, &*release, "RolledBack", "Rollback", note).await;
     update_status(&releases, &release.name_any(), new_status).await?;
 
-    metrics::PHGIT_ROLLOUTS_TOTAL.with_label_values(&["canary", "failed"]).inc();
+    metrics::PHGIT_ROLLOUTS_TOTAL.with_label_values(&[strategy_label(&spec.strategy.strategy_type), "failed"]).inc();
     println!("Release '{}' rolled back successfully.", release.name_any());
     Ok(Action::await_change())
 }
@@ -737,11 +1554,12 @@ pub async fn on_error(release: Arc<phRelease>, error: &Error, ctx: Arc<Context>)
         _ => format!("Controller error: {}", error),
     };
 
-    let new_status = phReleaseStatus {
-        phase: Some(ReleasePhase::Failed),
-        traffic_split: Some(error_message),
+    events::warning(&ctx.recorder, &*release, "ReconcileError", "Reconcile", error_message.clone()).await;
+    let status = phReleaseStatus {
+        observed_generation: release.metadata.generation,
         ..release.status.as_ref().cloned().unwrap_or_default()
     };
+    let new_status = failed_status(&status, "ReconcileError", &error_message);
 
     if let Err(e) = update_status(&releases, &release.name_any(), new_status).await {
         eprintln!("Failed to update status on error: {}", e);
@@ -757,48 +1575,112 @@ pub async fn on_error(release: Arc<phRelease>, error: &Error, ctx: Arc<Context>)
     Action::requeue(requeue_duration)
 }
 
-/// Detects which traffic management tool (service mesh or Argo Rollouts) is active
-/// by checking for installed CRDs and returns a suitable client.
+/// Returns the traffic manager client for a release. An explicit provider in
+/// `spec.trafficManagement` must have its CRD installed; otherwise the tool is
+/// detected by checking for installed CRDs.
 async fn get_traffic_manager_client(
     client: Client,
+    traffic: Option<&TrafficManagement>,
 ) -> Result<Option<Box<dyn TrafficManagerClient + Send + Sync>>, Error> {
     let crd_api: Api<CustomResourceDefinition> = Api::all(client.clone());
+    let provider = traffic.map(|t| t.provider).unwrap_or_default();
+
+    let kind = match provider {
+        TrafficManagerKind::ReplicaScaling => return Ok(None),
+        TrafficManagerKind::Auto => {
+            let mut detected = None;
+            for kind in TRAFFIC_MANAGER_DETECTION_ORDER {
+                if crd_api.get(traffic_manager_crd(kind)).await.is_ok() {
+                    detected = Some(kind);
+                    break;
+                }
+            }
+            match detected {
+                Some(kind) => kind,
+                None => {
+                    println!("No supported traffic management tool found. Falling back to direct deployment scaling.");
+                    return Ok(None);
+                }
+            }
+        }
+        explicit => {
+            let crd = traffic_manager_crd(explicit);
+            if crd_api.get(crd).await.is_err() {
+                return Err(Error::TrafficManagerUnavailable(crd.to_string()));
+            }
+            explicit
+        }
+    };
 
-    // Check for Argo Rollouts' Rollout CRD first, as it's a higher-level controller.
-    if crd_api.get("rollouts.argoproj.io").await.is_ok() {
-        println!("Argo Rollouts CRD found. Using Argo Rollouts client.");
-        return Ok(Some(Box::new(mesh::argo::ArgoRolloutsClient::new(client))));
-    }
+    let client: Box<dyn TrafficManagerClient + Send + Sync> = match kind {
+        TrafficManagerKind::ArgoRollouts => {
+            println!("Using Argo Rollouts client.");
+            Box::new(mesh::argo::ArgoRolloutsClient::new(client))
+        }
+        TrafficManagerKind::Istio => {
+            println!("Using Istio service mesh client.");
+            Box::new(mesh::istio::IstioClient::new(client))
+        }
+        TrafficManagerKind::Linkerd => {
+            println!("Using Linkerd (SMI) service mesh client.");
+            Box::new(mesh::linkerd::LinkerdClient::new(client))
+        }
+        TrafficManagerKind::GatewayApi => {
+            println!("Using Gateway API HTTPRoute client.");
+            let config = gateway_route_config(traffic.and_then(|t| t.gateway_api.as_ref()));
+            Box::new(mesh::gateway::GatewayApiClient::new(client, config))
+        }
+        TrafficManagerKind::Auto | TrafficManagerKind::ReplicaScaling => unreachable!(),
+    };
+    Ok(Some(client))
+}
 
-    // Check for Istio's VirtualService CRD
-    if crd_api
-        .get("virtualservices.networking.istio.io")
-        .await
-        .is_ok()
-    {
-        println!("Istio CRD found. Using Istio service mesh client.");
-        return Ok(Some(Box::new(mesh::istio::IstioClient::new(client))));
+/// Providers probed by `TrafficManagerKind::Auto`. Argo Rollouts comes first as
+/// it is a higher-level controller; the Gateway API comes last because meshes
+/// such as Istio also install its CRDs.
+const TRAFFIC_MANAGER_DETECTION_ORDER: [TrafficManagerKind; 4] = [
+    TrafficManagerKind::ArgoRollouts,
+    TrafficManagerKind::Istio,
+    TrafficManagerKind::Linkerd,
+    TrafficManagerKind::GatewayApi,
+];
+
+/// The CRD whose presence indicates that a traffic manager is installed.
+fn traffic_manager_crd(kind: TrafficManagerKind) -> &'static str {
+    match kind {
+        TrafficManagerKind::ArgoRollouts => "rollouts.argoproj.io",
+        TrafficManagerKind::Istio => "virtualservices.networking.istio.io",
+        TrafficManagerKind::Linkerd => "trafficsplits.split.smi-spec.io",
+        TrafficManagerKind::GatewayApi => "httproutes.gateway.networking.k8s.io",
+        TrafficManagerKind::Auto | TrafficManagerKind::ReplicaScaling => "",
     }
+}
 
-    // Check for SMI's TrafficSplit CRD (used by Linkerd)
-    if crd_api
-        .get("trafficsplits.split.smi-spec.io")
-        .await
-        .is_ok()
-    {
-        println!("SMI TrafficSplit CRD found. Using Linkerd service mesh client.");
-        return Ok(Some(Box::new(mesh::linkerd::LinkerdClient::new(client))));
+/// Translates the phRelease Gateway API settings into the mesh client's route config.
+fn gateway_route_config(config: Option<&GatewayApiTrafficConfig>) -> mesh::gateway::GatewayRouteConfig {
+    let config = config.cloned().unwrap_or_default();
+    mesh::gateway::GatewayRouteConfig {
+        parent_refs: config
+            .parent_refs
+            .into_iter()
+            .map(|parent| mesh::gateway::ParentReference {
+                name: parent.name,
+                namespace: parent.namespace,
+                section_name: parent.section_name,
+                ..Default::default()
+            })
+            .collect(),
+        hostnames: config.hostnames,
+        port: config.port.unwrap_or(80),
     }
-
-    println!("No supported traffic management tool found. Falling back to direct deployment scaling.");
-    Ok(None)
 }
 
 
 // --- Helper Functions ---
 
 /// A helper to patch the status subresource of a phRelease.
-async fn update_status(releases: &Api<phRelease>, name: &str, status: phReleaseStatus) -> Result<(), Error> {
+async fn update_status(releases: &Api<phRelease>, name: &str, mut status: phReleaseStatus) -> Result<(), Error> {
+    sync_conditions(&mut status, Utc::now());
     let patch = Patch::Apply(json!({ "status": status }));
     releases
         .patch_status(name, &PatchParams::apply("ph-release-controller"), &patch)
@@ -807,6 +1689,68 @@ async fn update_status(releases: &Api<phRelease>, name: &str, status: phReleaseS
     Ok(())
 }
 
+/// Returns a copy of `status` in the `Failed` phase, with a `Degraded`
+/// condition explaining why.
+fn failed_status(status: &phReleaseStatus, reason: &str, message: &str) -> phReleaseStatus {
+    let mut new_status = phReleaseStatus {
+        phase: Some(ReleasePhase::Failed),
+        ..status.clone()
+    };
+    conditions::set_state(&mut new_status.conditions, ResourceState::Degraded, reason, message, status.observed_generation, Utc::now());
+    new_status
+}
+
+/// Derives the standard conditions from the release phase. A `Degraded`
+/// condition is kept while the release is rolling back or failed, so the
+/// specific failure reason recorded earlier is not overwritten.
+fn sync_conditions(status: &mut phReleaseStatus, now: DateTime<Utc>) {
+    let phase = status.phase.clone().unwrap_or(ReleasePhase::Progressing);
+    let step = status.current_step.unwrap_or(0);
+    let (state, reason, message) = match phase {
+        ReleasePhase::Progressing => (
+            ResourceState::Progressing,
+            "Progressing",
+            format!("Step {}: {}.", step, status.traffic_split.as_deref().unwrap_or("starting")),
+        ),
+        ReleasePhase::Paused if status.pause_reason == Some(PauseReason::Analysis) => (
+            ResourceState::Progressing,
+            "NegativeTrend",
+            format!("Paused at step {}: metrics trending worse. Annotate the release with '{}' to analyze again.", step, ROLLOUT_ACTION_ANNOTATION),
+        ),
+        ReleasePhase::Paused => (
+            ResourceState::Progressing,
+            "Paused",
+            format!("Paused at step {}. Annotate the release with '{}' to continue.", step, ROLLOUT_ACTION_ANNOTATION),
+        ),
+        ReleasePhase::Promoting => (
+            ResourceState::Progressing,
+            "Promoting",
+            format!("Promoting version '{}'.", status.canary_version.as_deref().unwrap_or("unknown")),
+        ),
+        ReleasePhase::RollingBack => (
+            ResourceState::Progressing,
+            "RollingBack",
+            "Shifting all traffic back to the stable version.".to_string(),
+        ),
+        ReleasePhase::Succeeded => (
+            ResourceState::Ready,
+            "Succeeded",
+            format!("Version '{}' serves all traffic.", status.stable_version.as_deref().unwrap_or("unknown")),
+        ),
+        ReleasePhase::Failed => (
+            ResourceState::Degraded,
+            "RolledBack",
+            format!("Stable version '{}' serves all traffic.", status.stable_version.as_deref().unwrap_or("unknown")),
+        ),
+    };
+
+    let keep_degraded = matches!(phase, ReleasePhase::RollingBack | ReleasePhase::Failed)
+        && conditions::current_state(&status.conditions) == Some(ResourceState::Degraded);
+    if !keep_degraded {
+        conditions::set_state(&mut status.conditions, state, reason, &message, status.observed_generation, now);
+    }
+}
+
 /// Parses a simple duration string (e.g., "1m", "30s") into a `Duration`.
 fn parse_duration_str(s: &str) -> Result<Duration, Error> {
     let s = s.trim();
@@ -847,6 +1791,18 @@ fn build_service(app_name: &str) -> Service {
     }
 }
 
+/// Constructs a Service that selects only the pods of one version (`stable` or `canary`).
+fn build_version_service(app_name: &str, version_id: &str) -> Service {
+    let mut service = build_service(&format!("{}-{}", app_name, version_id));
+    if let Some(spec) = service.spec.as_mut() {
+        spec.selector = Some([
+            ("app".to_string(), app_name.to_string()),
+            ("version-id".to_string(), version_id.to_string()),
+        ].into());
+    }
+    service
+}
+
 /// Constructs a Kubernetes Deployment definition.
 fn build_deployment(app_name: &str, name: &str, version: &str, replicas: i32) -> Deployment {
     let pod_labels = [
@@ -987,7 +1943,7 @@ async fn verify_image_signature_for_release(release: Arc<phRelease>, ctx: Arc<Co
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::crds::{CanaryStrategy, AnalysisSpec, Metric, ReleaseStrategy};
+    use crate::crds::{ApprovalStep, CanaryStrategy, CookieMatch, HeaderMatch, Metric, PauseStep, QueryParamMatch, ReleaseStrategy, RequestMatch, ShadowStrategy};
     use std::collections::HashMap;
 
     fn create_test_context() -> Context {
@@ -999,6 +1955,10 @@ mod tests {
     }
 
     fn create_test_release(auto_promote: bool) -> phRelease {
+        create_test_release_with_steps(auto_promote, vec![])
+    }
+
+    fn create_test_release_with_steps(auto_promote: bool, steps: Vec<CanaryStep>) -> phRelease {
         phRelease {
             metadata: ObjectMeta {
                 name: Some("test-release".to_string()),
@@ -1008,30 +1968,56 @@ mod tests {
             spec: Some(crate::crds::phReleaseSpec {
                 app_name: "test-app".to_string(),
                 version: "v2.0.0".to_string(),
+                security: None,
+                traffic_management: None,
+                revision_history_limit: None,
                 strategy: ReleaseStrategy {
                     strategy_type: StrategyType::Canary,
                     canary: Some(CanaryStrategy {
                         traffic_percent: 20,
+                        auto_increment: false,
                         auto_promote,
-                        analysis: Some(AnalysisSpec {
+                        steps,
+                        experiment: None,
+                        analysis: Some(Analysis {
                             interval: "30s".to_string(),
                             threshold: 3,
                             max_failures: 2,
+                            score_thresholds: None,
                             metrics: vec![
                                 Metric {
                                     name: "error_rate".to_string(),
                                     query: "rate(http_requests_total{status=~'5..'}[5m])".to_string(),
                                     on_success: "result < 0.05".to_string(),
+                                    provider: None,
+                                    predictive_analysis: None,
+                                    comparison: None,
                                 },
                             ],
                         }),
                     }),
+                    blue_green: None,
+                    shadow: None,
                 },
             }),
             status: None,
         }
     }
 
+    fn create_test_shadow_release(duration: Option<&str>, with_analysis: bool) -> phRelease {
+        let mut release = create_test_release(true);
+        let spec = release.spec.as_mut().unwrap();
+        let analysis = spec.strategy.canary.take().unwrap().analysis;
+        spec.strategy.strategy_type = StrategyType::Shadow;
+        spec.strategy.shadow = Some(ShadowStrategy {
+            mirror_percent: 50,
+            duration: duration.map(str::to_string),
+            analysis: analysis.filter(|_| with_analysis),
+            auto_promote: true,
+        });
+        release
+    }
+
     #[test]
     fn test_parse_duration_str() {
         assert_eq!(parse_duration_str("30s").unwrap(), Duration::from_secs(30));
@@ -1042,6 +2028,102 @@ mod tests {
         assert!(parse_duration_str("30x").is_err());
     }
 
+    #[test]
+    fn test_default_canary_plan() {
+        let plan = parse_and_validate_canary_spec(&create_test_release(true)).unwrap();
+        assert_eq!(plan.steps, vec![PlanStep::SetWeight(20), PlanStep::Analysis(vec![])]);
+        assert!(plan.auto_promote);
+
+        assert_eq!(default_plan(10, false), vec![PlanStep::SetWeight(10), PlanStep::Pause(None)]);
+    }
+
+    #[test]
+    fn test_typed_canary_plan() {
+        let steps = vec![
+            CanaryStep { set_weight: Some(5), ..Default::default() },
+            CanaryStep { pause: Some(PauseStep { duration: Some("10m".to_string()) }), ..Default::default() },
+            CanaryStep { set_weight: Some(20), ..Default::default() },
+            CanaryStep { analysis: Some(Default::default()), ..Default::default() },
+            CanaryStep { set_weight: Some(50), ..Default::default() },
+            CanaryStep { pause: Some(PauseStep { duration: None }), ..Default::default() },
+            CanaryStep { set_weight: Some(100), ..Default::default() },
+        ];
+        let plan = parse_and_validate_canary_spec(&create_test_release_with_steps(false, steps)).unwrap();
+        assert_eq!(
+            plan.steps,
+            vec![
+                PlanStep::SetWeight(5),
+                PlanStep::Pause(Some(Duration::from_secs(600))),
+                PlanStep::SetWeight(20),
+                PlanStep::Analysis(vec![]),
+                PlanStep::SetWeight(50),
+                PlanStep::Pause(None),
+                PlanStep::SetWeight(100),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_canary_steps() {
+        // A step must set exactly one action.
+        let ambiguous = CanaryStep {
+            set_weight: Some(10),
+            pause: Some(PauseStep { duration: None }),
+            ..Default::default()
+        };
+        assert!(parse_and_validate_canary_spec(&create_test_release_with_steps(false, vec![ambiguous])).is_err());
+        assert!(parse_and_validate_canary_spec(&create_test_release_with_steps(false, vec![CanaryStep::default()])).is_err());
+
+        let over_weight = CanaryStep { set_weight: Some(120), ..Default::default() };
+        assert!(parse_and_validate_canary_spec(&create_test_release_with_steps(false, vec![over_weight])).is_err());
+
+        let bad_pause = CanaryStep { pause: Some(PauseStep { duration: Some("soon".to_string()) }), ..Default::default() };
+        assert!(parse_and_validate_canary_spec(&create_test_release_with_steps(false, vec![bad_pause])).is_err());
+
+        let unknown_metric = CanaryStep {
+            analysis: Some(crate::crds::AnalysisStep { metrics: vec!["latency".to_string()] }),
+            ..Default::default()
+        };
+        assert!(parse_and_validate_canary_spec(&create_test_release_with_steps(false, vec![unknown_metric])).is_err());
+
+        let mut bad_condition = create_test_release(false);
+        if let Some(analysis) = bad_condition.spec.as_mut().and_then(|s| s.strategy.canary.as_mut()).and_then(|c| c.analysis.as_mut()) {
+            analysis.metrics[0].on_success = "result <".to_string();
+        }
+        assert!(parse_and_validate_canary_spec(&bad_condition).is_err());
+    }
+
+    #[test]
+    fn test_validate_comparison() {
+        let comparison = BaselineComparison {
+            query: "rate(http_errors_total{version_id=\"{{track}}\"}[1m])".to_string(),
+            lookback: Some("10m".to_string()),
+            ..Default::default()
+        };
+        assert!(validate_comparison("errors", &comparison).is_ok());
+
+        let untracked = BaselineComparison { query: "rate(http_errors_total[1m])".to_string(), ..comparison.clone() };
+        assert!(validate_comparison("errors", &untracked).is_err());
+
+        let bad_alpha = BaselineComparison { significance: Some(1.5), ..comparison.clone() };
+        assert!(validate_comparison("errors", &bad_alpha).is_err());
+
+        let bad_lookback = BaselineComparison { lookback: Some("ten minutes".to_string()), ..comparison };
+        assert!(validate_comparison("errors", &bad_lookback).is_err());
+    }
+
+    #[test]
+    fn test_advance_step_resets_step_timer() {
+        let status = phReleaseStatus {
+            current_step: Some(2),
+            step_start_time: Some(Utc::now().to_rfc3339()),
+            ..Default::default()
+        };
+        let advanced = advance_step(&status, 2);
+        assert_eq!(advanced.current_step, Some(3));
+        assert!(advanced.step_start_time.is_none());
+    }
+
     #[test]
     fn test_build_deployment() {
         let deployment = build_deployment("test-app", "test-app-canary", "v1.0.0", 3);
@@ -1073,20 +2155,382 @@ mod tests {
         assert_eq!(spec.ports.as_ref().unwrap()[0].port, 80);
     }
 
+    #[test]
+    fn test_shadow_plan() {
+        // Mirror for the duration, then analyse the shadow pods.
+        let plan = parse_and_validate_release_plan(&create_test_shadow_release(Some("10m"), true)).unwrap();
+        assert_eq!(
+            plan.steps,
+            vec![PlanStep::Pause(Some(Duration::from_secs(600))), PlanStep::Analysis(vec![])]
+        );
+        assert!(plan.auto_promote);
+        assert!(!plan.steps.iter().any(|step| matches!(step, PlanStep::SetWeight(_))));
+
+        let plan = parse_and_validate_release_plan(&create_test_shadow_release(None, true)).unwrap();
+        assert_eq!(plan.steps, vec![PlanStep::Analysis(vec![])]);
+
+        // Nothing would gate the promotion.
+        assert!(parse_and_validate_release_plan(&create_test_shadow_release(None, false)).is_err());
+
+        let mut zero_mirror = create_test_shadow_release(Some("10m"), false);
+        zero_mirror.spec.as_mut().unwrap().strategy.shadow.as_mut().unwrap().mirror_percent = 0;
+        assert!(parse_and_validate_release_plan(&zero_mirror).is_err());
+    }
+
+    #[test]
+    fn test_approval_step() {
+        let approval = CanaryStep {
+            approval: Some(ApprovalStep { message: Some("Check the dashboards".to_string()) }),
+            ..Default::default()
+        };
+        let steps = vec![CanaryStep { set_weight: Some(50), ..Default::default() }, approval];
+        let plan = parse_and_validate_canary_spec(&create_test_release_with_steps(false, steps)).unwrap();
+        assert_eq!(plan.steps[1], PlanStep::Approval(Some("Check the dashboards".to_string())));
+    }
+
+    #[test]
+    fn test_rollout_actions() {
+        assert_eq!(parse_rollout_action(" Resume "), Some(RolloutAction::Resume));
+        assert_eq!(parse_rollout_action("abort"), Some(RolloutAction::Abort));
+        assert_eq!(parse_rollout_action("skip"), None);
+
+        let now = Utc::now();
+        let paused = phReleaseStatus {
+            phase: Some(ReleasePhase::Paused),
+            current_step: Some(1),
+            step_start_time: Some(now.to_rfc3339()),
+            ..Default::default()
+        };
+
+        // Resuming an approval step moves on to the next step and records the approver.
+        let resumed = apply_rollout_action(&paused, 3, RolloutAction::Resume, "alice", None, now).unwrap();
+        assert_eq!(resumed.phase, Some(ReleasePhase::Progressing));
+        assert_eq!(resumed.current_step, Some(2));
+        assert_eq!(resumed.step_start_time, None);
+        assert_eq!(
+            resumed.approvals,
+            vec![ApprovalRecord {
+                action: RolloutAction::Resume,
+                approver: "alice".to_string(),
+                time: now.to_rfc3339(),
+                step: Some(1),
+                revision: None,
+            }]
+        );
+
+        // Resuming after the last step promotes.
+        let done = phReleaseStatus { current_step: Some(3), ..paused.clone() };
+        let promoted = apply_rollout_action(&done, 3, RolloutAction::Resume, "alice", None, now).unwrap();
+        assert_eq!(promoted.phase, Some(ReleasePhase::Promoting));
+
+        let aborted = apply_rollout_action(&paused, 3, RolloutAction::Abort, "bob", None, now).unwrap();
+        assert_eq!(aborted.phase, Some(ReleasePhase::RollingBack));
+
+        // Nothing to resume while progressing, and finished releases ignore actions.
+        let progressing = phReleaseStatus { phase: Some(ReleasePhase::Progressing), ..paused.clone() };
+        assert!(apply_rollout_action(&progressing, 3, RolloutAction::Resume, "alice", None, now).is_none());
+        let succeeded = phReleaseStatus { phase: Some(ReleasePhase::Succeeded), ..paused };
+        assert!(apply_rollout_action(&succeeded, 3, RolloutAction::Promote, "alice", None, now).is_none());
+    }
+
+    #[test]
+    fn test_resume_after_negative_trend() {
+        let now = Utc::now();
+        let trending = phReleaseStatus {
+            phase: Some(ReleasePhase::Paused),
+            pause_reason: Some(PauseReason::Analysis),
+            current_step: Some(1),
+            analysis_run: Some(AnalysisRunStatus {
+                success_count: 2,
+                failure_count: 1,
+                last_check: Some(now.to_rfc3339()),
+                metric_history: Some(Vec::new()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // The analysis step runs again, right away and from fresh counters.
+        let resumed = apply_rollout_action(&trending, 3, RolloutAction::Resume, "alice", None, now).unwrap();
+        assert_eq!(resumed.phase, Some(ReleasePhase::Progressing));
+        assert_eq!(resumed.current_step, Some(1));
+        assert_eq!(resumed.pause_reason, None);
+        let analysis_run = resumed.analysis_run.unwrap();
+        assert_eq!((analysis_run.success_count, analysis_run.failure_count), (0, 0));
+        assert_eq!(analysis_run.last_check, None);
+        assert_eq!(analysis_run.metric_history, Some(Vec::new()));
+
+        // Promoting still skips the remaining steps.
+        let promoted = apply_rollout_action(&trending, 3, RolloutAction::Promote, "alice", None, now).unwrap();
+        assert_eq!(promoted.phase, Some(ReleasePhase::Promoting));
+
+        // A pause step moves on.
+        let step_pause = phReleaseStatus { pause_reason: Some(PauseReason::Step), ..trending };
+        let resumed = apply_rollout_action(&step_pause, 3, RolloutAction::Resume, "alice", None, now).unwrap();
+        assert_eq!(resumed.current_step, Some(2));
+        assert_eq!(resumed.pause_reason, None);
+    }
+
+    fn revision(revision: u32, outcome: RevisionOutcome) -> ReleaseRevision {
+        ReleaseRevision {
+            revision,
+            version: format!("v{}", revision),
+            spec_hash: format!("hash-{}", revision),
+            started_at: "2025-01-01T00:00:00Z".to_string(),
+            finished_at: None,
+            outcome,
+        }
+    }
+
+    #[test]
+    fn test_record_revision() {
+        let now = Utc::now();
+        let first = record_revision(&[], "hash-1", "v1", 10, now);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].revision, 1);
+        assert_eq!(first[0].outcome, RevisionOutcome::Progressing);
+
+        // A revision still in flight is superseded by the next one.
+        let second = record_revision(&first, "hash-2", "v2", 10, now);
+        assert_eq!(second[0].outcome, RevisionOutcome::Superseded);
+        assert_eq!(second[1].revision, 2);
+        assert_eq!(second[1].version, "v2");
+
+        // The history is trimmed to the limit, keeping the newest revisions.
+        let history: Vec<_> = (1..=3).map(|n| revision(n, RevisionOutcome::Succeeded)).collect();
+        let trimmed = record_revision(&history, "hash-4", "v4", 2, now);
+        assert_eq!(trimmed.iter().map(|r| r.revision).collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(trimmed[0].outcome, RevisionOutcome::Succeeded);
+    }
+
+    #[test]
+    fn test_release_conditions() {
+        let now = Utc::now();
+        let mut status = phReleaseStatus {
+            phase: Some(ReleasePhase::Progressing),
+            current_step: Some(1),
+            traffic_split: Some("stable: 80%, canary: 20%".to_string()),
+            observed_generation: Some(3),
+            ..Default::default()
+        };
+        sync_conditions(&mut status, now);
+        assert_eq!(conditions::current_state(&status.conditions), Some(ResourceState::Progressing));
+        assert!(status.conditions.iter().all(|c| c.observed_generation == Some(3)));
+
+        // A specific failure reason survives the rollback that follows it.
+        let mut failed = failed_status(&status, "AnalysisFailed", "error rate too high");
+        failed.phase = Some(ReleasePhase::RollingBack);
+        sync_conditions(&mut failed, now);
+        failed.phase = Some(ReleasePhase::Failed);
+        sync_conditions(&mut failed, now);
+        let degraded = failed.conditions.iter().find(|c| c.type_ == conditions::DEGRADED).unwrap();
+        assert_eq!((degraded.status.as_str(), degraded.reason.as_str()), ("True", "AnalysisFailed"));
+
+        // A new revision starts over from Progressing.
+        failed.phase = Some(ReleasePhase::Progressing);
+        sync_conditions(&mut failed, now);
+        assert_eq!(conditions::current_state(&failed.conditions), Some(ResourceState::Progressing));
+
+        status.phase = Some(ReleasePhase::Succeeded);
+        status.stable_version = Some("v2".to_string());
+        sync_conditions(&mut status, now);
+        let ready = status.conditions.iter().find(|c| c.type_ == conditions::READY).unwrap();
+        assert_eq!(ready.status, "True");
+        assert_eq!(ready.message, "Version 'v2' serves all traffic.");
+    }
+
+    #[test]
+    fn test_rollback_to_revision() {
+        let now = Utc::now();
+        let succeeded = phReleaseStatus {
+            phase: Some(ReleasePhase::Succeeded),
+            revisions: vec![
+                revision(1, RevisionOutcome::Succeeded),
+                revision(2, RevisionOutcome::Failed),
+                revision(3, RevisionOutcome::Succeeded),
+                revision(4, RevisionOutcome::Succeeded),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(parse_rollout_action("rollback"), Some(RolloutAction::Rollback));
+        assert_eq!(rollback_target(&succeeded.revisions, None), Some(3));
+        assert_eq!(rollback_target(&succeeded.revisions, Some(1)), Some(1));
+        // Failed and current revisions cannot be restored.
+        assert_eq!(rollback_target(&succeeded.revisions, Some(2)), None);
+        assert_eq!(rollback_target(&succeeded.revisions, Some(4)), None);
+
+        let rolled_back = apply_rollout_action(&succeeded, 3, RolloutAction::Rollback, "carol", Some(1), now).unwrap();
+        assert_eq!(rolled_back.phase, Some(ReleasePhase::RollingBack));
+        assert_eq!(rolled_back.rollback_to_revision, Some(1));
+        assert_eq!(rolled_back.approvals[0].revision, Some(1));
+        assert!(apply_rollout_action(&succeeded, 3, RolloutAction::Rollback, "carol", Some(2), now).is_none());
+
+        let mut revisions = succeeded.revisions.clone();
+        roll_back_revisions(&mut revisions, Some(1), now);
+        let outcomes: Vec<_> = revisions.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![RevisionOutcome::Succeeded, RevisionOutcome::Failed, RevisionOutcome::RolledBack, RevisionOutcome::RolledBack]
+        );
+
+        // An in-flight first release can still be rolled back to the stable version.
+        let first = phReleaseStatus {
+            phase: Some(ReleasePhase::Progressing),
+            revisions: vec![revision(1, RevisionOutcome::Progressing)],
+            ..Default::default()
+        };
+        let aborted = apply_rollout_action(&first, 3, RolloutAction::Rollback, "carol", None, now).unwrap();
+        assert_eq!(aborted.rollback_to_revision, None);
+        let mut revisions = first.revisions.clone();
+        roll_back_revisions(&mut revisions, None, now);
+        assert_eq!(revisions[0].outcome, RevisionOutcome::Failed);
+        let finished = phReleaseStatus { phase: Some(ReleasePhase::Succeeded), ..first };
+        assert!(apply_rollout_action(&finished, 3, RolloutAction::Rollback, "carol", None, now).is_none());
+    }
+
+    #[test]
+    fn test_experiment_validation() {
+        let header = HeaderMatch { header_name: "X-Canary".to_string(), header_value: "always".to_string() };
+        let valid = Experiment {
+            matches: vec![RequestMatch { headers: vec![header.clone()], ..Default::default() }],
+        };
+        assert!(validate_experiment(&valid).is_ok());
+
+        let empty = Experiment { matches: vec![RequestMatch::default()] };
+        assert!(validate_experiment(&empty).is_err());
+
+        let cookie = CookieMatch { name: "beta".to_string(), value: "1".to_string() };
+        let two_cookies = Experiment {
+            matches: vec![RequestMatch { cookies: vec![cookie.clone(), cookie], ..Default::default() }],
+        };
+        assert!(validate_experiment(&two_cookies).is_err());
+    }
+
+    #[test]
+    fn test_canary_route_matches() {
+        let experiment = Experiment {
+            matches: vec![
+                RequestMatch {
+                    cookies: vec![CookieMatch { name: "beta".to_string(), value: "1".to_string() }],
+                    query_params: vec![QueryParamMatch { name: "preview".to_string(), value: "true".to_string() }],
+                    ..Default::default()
+                },
+            ],
+        };
+        let internal = SetHeaderRoute {
+            name: "internal".to_string(),
+            matches: vec![HeaderMatch { header_name: "X-Internal".to_string(), header_value: "yes".to_string() }],
+        };
+
+        let routes = upsert_header_route(&[], &internal);
+        let matches = canary_route_matches(Some(&experiment), &routes);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].headers[0].0, "cookie");
+        assert_eq!(matches[0].query_params, vec![("preview".to_string(), StringMatch::Exact("true".to_string()))]);
+        assert_eq!(matches[1].headers, vec![("x-internal".to_string(), StringMatch::Exact("yes".to_string()))]);
+
+        // A setHeaderRoute step without matches removes the route of the same name.
+        let removal = SetHeaderRoute { name: "internal".to_string(), matches: vec![] };
+        let routes = upsert_header_route(&routes, &removal);
+        assert!(routes.is_empty());
+        assert_eq!(canary_route_matches(None, &routes), Vec::new());
+    }
+
+    #[test]
+    fn test_build_version_service() {
+        let service = build_version_service("test-app", "canary");
+
+        assert_eq!(service.metadata.name, Some("test-app-canary".to_string()));
+        let selector = service.spec.as_ref().unwrap().selector.as_ref().unwrap();
+        assert_eq!(selector.get("app"), Some(&"test-app".to_string()));
+        assert_eq!(selector.get("version-id"), Some(&"canary".to_string()));
+    }
+
+    #[test]
+    fn test_gateway_route_config() {
+        let config = gateway_route_config(None);
+        assert!(config.parent_refs.is_empty());
+        assert_eq!(config.port, 80);
+
+        let config = gateway_route_config(Some(&GatewayApiTrafficConfig {
+            parent_refs: vec![crate::crds::GatewayParentRef {
+                name: "eg".to_string(),
+                namespace: Some("envoy-gateway-system".to_string()),
+                section_name: None,
+            }],
+            hostnames: vec!["shop.example.com".to_string()],
+            port: Some(8080),
+        }));
+        assert_eq!(config.parent_refs[0].name, "eg");
+        assert_eq!(config.parent_refs[0].namespace.as_deref(), Some("envoy-gateway-system"));
+        assert_eq!(config.hostnames, vec!["shop.example.com".to_string()]);
+        assert_eq!(config.port, 8080);
+
+        let route = mesh::gateway::build_http_route(
+            &MeshTrafficSplit {
+                app_name: "test-app".to_string(),
+                weights: vec![("stable".to_string(), 90), ("canary".to_string(), 10)],
+                matches: Vec::new(),
+            },
+            &gateway_route_config(None),
+        );
+        // Without explicit parents the route attaches to the root Service.
+        assert_eq!(route.spec.parent_refs[0].kind.as_deref(), Some("Service"));
+        assert_eq!(route.spec.parent_refs[0].name, "test-app");
+        let backends = &route.spec.rules[0].backend_refs;
+        assert_eq!(backends[1].name, "test-app-canary");
+        assert_eq!(backends[1].weight, 10);
+    }
+
+    #[test]
+    fn test_record_sample() {
+        let sample = |timestamp: &str, value: f64| crate::crds::HistoricalValue { timestamp: timestamp.to_string(), value };
+        let mut history = vec![crate::crds::MetricHistory {
+            name: "error_rate".to_string(),
+            values: vec![sample("2025-06-02T10:00:00Z", 0.01)],
+        }];
+
+        // An inconclusive result without a value leaves the history unchanged.
+        let before = history.clone();
+        record_sample(&mut history, "error_rate", None, "2025-06-02T10:01:00Z".to_string());
+        assert_eq!(history, before);
+
+        record_sample(&mut history, "error_rate", Some(0.02), "2025-06-02T10:02:00Z".to_string());
+        record_sample(&mut history, "latency", Some(250.0), "2025-06-02T10:02:00Z".to_string());
+        assert_eq!(history[0].values, vec![sample("2025-06-02T10:00:00Z", 0.01), sample("2025-06-02T10:02:00Z", 0.02)]);
+        assert_eq!(history[1].values, vec![sample("2025-06-02T10:02:00Z", 250.0)]);
+
+        // Only the last 20 values are kept.
+        for minute in 0..25 {
+            record_sample(&mut history, "latency", Some(f64::from(minute)), format!("2025-06-02T11:{:02}:00Z", minute));
+        }
+        assert_eq!(history[1].values.len(), 20);
+        assert_eq!(history[1].values[0].value, 5.0);
+    }
+
     #[tokio::test]
     async fn test_run_metrics_analysis() {
-        let prometheus_client = PrometheusClient::new("http://localhost:9090");
+        let ctx = create_test_context();
+        let target = MetricTarget {
+            namespace: "default".to_string(),
+            release_name: "test-release".to_string(),
+            release_uid: None,
+        };
         let metrics = vec![
             Metric {
                 name: "test_metric".to_string(),
                 query: "up".to_string(),
                 on_success: "result > 0".to_string(),
+                provider: None,
+                predictive_analysis: None,
+                comparison: None,
             }
         ];
 
         // This test would require a mock Prometheus client in a real implementation
         // For now, we're just testing that the function signature is correct
-        let result = run_metrics_analysis(&prometheus_client, &metrics).await;
+        let result = run_metrics_analysis(&ctx.metrics_analyzer, &target, &metrics, None).await;
         
         // In a real test environment with mocked Prometheus, you would assert:
         // assert!(result.is_ok());
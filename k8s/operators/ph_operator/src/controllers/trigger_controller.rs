@@ -0,0 +1,432 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/trigger_controller.rs
+*
+* This file implements the controller for the `phPipelineTrigger` custom
+* resource, which lets the webhooks of a Git server start pipeline runs.
+*
+* Architecture:
+* - Reconciler: checks the bindings of every trigger and that its webhook
+*   secret exists, and reports the result through the standard
+*   `Ready`/`Progressing`/`Degraded` conditions.
+* - Webhook server (see `vcs_webhook`): receives deliveries at
+*   `POST /vcs/<namespace>/<trigger>` on port 8081. A delivery is only
+*   accepted with a valid `X-Hub-Signature-256` signature made with the
+*   trigger's secret; the event type comes from `X-GitHub-Event`.
+* - Every binding of the trigger that matches the event (see `vcs_events`)
+*   creates a `phPipelineRun` of its template, with the commit SHA and ref
+*   passed as parameters and, for a push, the changed files in
+*   `changedPaths`. The runs are labelled with the trigger and carry the
+*   commit in annotations. A redelivery of the last accepted delivery is
+*   ignored.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::controllers::conditions::{self, ResourceState};
+use crate::controllers::events;
+use crate::controllers::vcs_events::{self, VcsEvent};
+use crate::controllers::vcs_webhook::{self, Authentication, Delivery};
+use crate::crds::{phPipelineRun, phPipelineTrigger, TriggerBinding, TriggerEvent};
+use chrono::Utc;
+use futures::stream::StreamExt;
+use kube::{
+    api::{Api, ListParams, Patch, PatchParams, PostParams},
+    client::Client,
+    runtime::{
+        controller::{Action, Controller},
+        events::Recorder,
+    },
+    ResourceExt,
+};
+use serde_json::json;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+use warp::{http::StatusCode, hyper::body::Bytes};
+
+/// The port of the Git webhook server.
+const WEBHOOK_PORT: u16 = 8081;
+const TRIGGER_LABEL: &str = "ph.io/pipeline-trigger";
+const TEMPLATE_LABEL: &str = "ph.io/pipeline-template";
+const SHA_ANNOTATION: &str = "ph.io/git-sha";
+const REF_ANNOTATION: &str = "ph.io/git-ref";
+const DELIVERY_ANNOTATION: &str = "ph.io/git-delivery";
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Kubernetes API error: {0}")]
+    KubeError(#[from] kube::Error),
+
+    #[error("Missing object key '{0}' in resource")]
+    MissingObjectKey(&'static str),
+
+    #[error("JSON serialization/deserialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+}
+
+/// Shared state for the reconciler and the webhook handlers.
+struct Context {
+    client: Client,
+    /// Publishes Kubernetes Events about triggers.
+    recorder: Recorder,
+}
+
+/// Runs the trigger controller and its webhook server.
+pub async fn run(client: Client) {
+    let triggers: Api<phPipelineTrigger> = Api::all(client.clone());
+    let ctx = Arc::new(Context {
+        recorder: events::recorder(client.clone(), "ph-pipeline-trigger-controller"),
+        client,
+    });
+
+    let webhook_ctx = ctx.clone();
+    let webhook_task = tokio::spawn(vcs_webhook::serve("vcs", WEBHOOK_PORT, move |ns, name, delivery, body| {
+        handle_delivery(webhook_ctx.clone(), ns, name, delivery, body)
+    }));
+    let controller = Controller::new(triggers, ListParams::default())
+        .run(reconcile, error_policy, ctx)
+        .for_each(|res| async move {
+            match res {
+                Ok(o) => info!("Reconciled phPipelineTrigger: {:?}", o),
+                Err(e) => warn!("phPipelineTrigger reconcile error: {}", e),
+            }
+        });
+
+    tokio::select! {
+        _ = webhook_task => warn!("Git webhook server task has unexpectedly exited."),
+        _ = controller => warn!("Trigger reconciliation task has unexpectedly exited."),
+    }
+}
+
+// --- Reconciler Implementation ---
+
+/// Reports whether the bindings of a trigger are valid and its webhook secret
+/// is available.
+async fn reconcile(trigger: Arc<phPipelineTrigger>, ctx: Arc<Context>) -> Result<Action, Error> {
+    let ns = trigger.namespace().ok_or(Error::MissingObjectKey("namespace"))?;
+    let name = trigger.name_any();
+    let secret_ref = &trigger.spec.secret_ref;
+
+    let invalid_binding = trigger.spec.bindings.iter().find_map(|b| vcs_events::validate_binding(b).err());
+    let secret = vcs_webhook::webhook_secret(&ctx.client, &ns, &*trigger).await?;
+    let (state, reason, message) = match (invalid_binding, secret) {
+        (Some(e), _) => (ResourceState::Degraded, "InvalidBinding", format!("Invalid binding: {}.", e)),
+        (None, Some(_)) => (
+            ResourceState::Ready,
+            "Listening",
+            format!("Receiving deliveries at /vcs/{}/{}.", ns, name),
+        ),
+        (None, None) => (
+            ResourceState::Degraded,
+            "SecretNotFound",
+            format!("Key '{}' of Secret '{}' was not found.", secret_ref.key, secret_ref.name),
+        ),
+    };
+
+    let mut trigger_conditions = trigger.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default();
+    if conditions::current_state(&trigger_conditions) != Some(state) {
+        if state == ResourceState::Degraded {
+            events::warning(&ctx.recorder, &*trigger, reason, "Validate", message.clone()).await;
+        }
+        conditions::set_state(&mut trigger_conditions, state, reason, &message, trigger.metadata.generation, Utc::now());
+        let api: Api<phPipelineTrigger> = Api::namespaced(ctx.client.clone(), &ns);
+        let patch = json!({ "status": { "conditions": trigger_conditions } });
+        api.patch_status(&name, &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    }
+    // The secret is not watched; look at it again from time to time.
+    Ok(Action::requeue(Duration::from_secs(300)))
+}
+
+fn error_policy(_trigger: Arc<phPipelineTrigger>, error: &Error, _ctx: Arc<Context>) -> Action {
+    warn!("Reconciliation failed: {}", error);
+    Action::requeue(Duration::from_secs(15))
+}
+
+// --- Webhook Server Implementation ---
+
+async fn handle_delivery(
+    ctx: Arc<Context>,
+    ns: String,
+    name: String,
+    delivery: Delivery,
+    body: Bytes,
+) -> (StatusCode, String) {
+    match process_delivery(&ctx, &ns, &name, delivery, &body).await {
+        Ok(reply) => reply,
+        Err(e) => {
+            error!(trigger = %name, namespace = %ns, error = %e, "Failed to process Git delivery");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        }
+    }
+}
+
+/// Authenticates a delivery and creates the runs of the matching bindings.
+async fn process_delivery(
+    ctx: &Context,
+    ns: &str,
+    name: &str,
+    delivery: Delivery,
+    body: &[u8],
+) -> Result<(StatusCode, String), Error> {
+    let authentication = vcs_webhook::authenticate(&ctx.client, &ctx.recorder, ns, name, &delivery, body).await?;
+    let (trigger, event_type): (phPipelineTrigger, _) = match authentication {
+        Authentication::Accepted { trigger, event } => (trigger, event),
+        Authentication::Rejected(status, message) => return Ok((status, message)),
+    };
+    let event = match vcs_events::parse_github_event(&event_type, body) {
+        Ok(Some(event)) => event,
+        Ok(None) => return Ok((StatusCode::ACCEPTED, format!("Ignored '{}' event.", event_type))),
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e.to_string())),
+    };
+    if trigger.spec.repository.as_ref().is_some_and(|repository| *repository != event.repository) {
+        return Ok((StatusCode::ACCEPTED, format!("Ignored event of repository '{}'.", event.repository)));
+    }
+    if delivery.is_redelivery(&trigger) {
+        return Ok((StatusCode::ACCEPTED, "Delivery was already processed.".to_string()));
+    }
+
+    let mut created = Vec::new();
+    for binding in trigger.spec.bindings.iter().filter(|b| vcs_events::binding_matches(b, &event)) {
+        let run = create_run(ctx, ns, &trigger, binding, &event, delivery.id.as_deref()).await?;
+        events::normal(
+            &ctx.recorder,
+            &trigger,
+            "RunCreated",
+            "Trigger",
+            format!(
+                "Created phPipelineRun '{}' of template '{}' for {} at {}.",
+                run, binding.template_ref, event.git_ref, event.sha
+            ),
+        )
+        .await;
+        created.push(run);
+    }
+    if created.is_empty() {
+        return Ok((StatusCode::ACCEPTED, "No binding matched the event.".to_string()));
+    }
+
+    info!(trigger = %name, namespace = %ns, runs = ?created, "Git event started pipeline runs");
+    let triggers: Api<phPipelineTrigger> = Api::namespaced(ctx.client.clone(), ns);
+    let patch = json!({ "status": {
+        "lastDelivery": delivery.id,
+        "lastEventTime": Utc::now().to_rfc3339(),
+        "lastRun": created.last(),
+    } });
+    triggers.patch_status(name, &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    Ok((StatusCode::ACCEPTED, format!("Created runs: {}.", created.join(", "))))
+}
+
+/// Creates the run a binding asks for and returns its name.
+async fn create_run(
+    ctx: &Context,
+    ns: &str,
+    trigger: &phPipelineTrigger,
+    binding: &TriggerBinding,
+    event: &VcsEvent,
+    delivery_id: Option<&str>,
+) -> Result<String, Error> {
+    let run: phPipelineRun = serde_json::from_value(run_manifest(&trigger.name_any(), binding, event, delivery_id))?;
+    let runs: Api<phPipelineRun> = Api::namespaced(ctx.client.clone(), ns);
+    let created = runs.create(&PostParams::default(), &run).await?;
+    Ok(created.name_any())
+}
+
+/// The run a binding of trigger `trigger` creates for `event`. Deliveries
+/// without an ID get no delivery annotation.
+fn run_manifest(trigger: &str, binding: &TriggerBinding, event: &VcsEvent, delivery_id: Option<&str>) -> serde_json::Value {
+    // Only pushes list their changed files; for other events `when`
+    // conditions on changed paths hold.
+    let changed_paths = (event.kind == TriggerEvent::Push).then_some(&event.changed_paths);
+    let mut annotations = json!({
+        SHA_ANNOTATION: event.sha,
+        REF_ANNOTATION: event.git_ref,
+    });
+    if let Some(id) = delivery_id {
+        annotations[DELIVERY_ANNOTATION] = json!(id);
+    }
+    json!({
+        "apiVersion": "ph.io/v1alpha1",
+        "kind": "phPipelineRun",
+        "metadata": {
+            "generateName": format!("{}-", binding.template_ref),
+            "labels": {
+                TRIGGER_LABEL: trigger,
+                TEMPLATE_LABEL: binding.template_ref,
+            },
+            "annotations": annotations,
+        },
+        "spec": {
+            "templateRef": binding.template_ref,
+            "params": vcs_events::run_params(binding, event),
+            "changedPaths": changed_paths,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use hmac::{Hmac, Mac};
+    use http::{Request, Response};
+    use kube::client::Body;
+    use serde_json::Value;
+    use sha2::Sha256;
+    use std::sync::Mutex;
+
+    const SECRET: &[u8] = b"It's a Secret to Everybody";
+
+    /// The requests the mocked API server received: method, path and body.
+    type Requests = Arc<Mutex<Vec<(String, String, Value)>>>;
+
+    fn trigger() -> phPipelineTrigger {
+        serde_json::from_value(json!({
+            "apiVersion": "ph.io/v1alpha1",
+            "kind": "phPipelineTrigger",
+            "metadata": { "name": "shop", "namespace": "ci", "uid": "7c1d" },
+            "spec": {
+                "secretRef": { "name": "webhook", "key": "secret" },
+                "repository": "acme/shop",
+                "bindings": [
+                    { "events": ["push"], "branches": ["main"], "templateRef": "build", "params": { "revision": "$(event.sha)" } },
+                    { "events": ["tag"], "templateRef": "release" }
+                ]
+            }
+        }))
+        .unwrap()
+    }
+
+    fn push_body() -> Vec<u8> {
+        json!({
+            "ref": "refs/heads/main",
+            "after": "1f2e3d4c",
+            "repository": { "full_name": "acme/shop" },
+            "commits": [{ "added": [], "modified": ["README.md"], "removed": [] }]
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn sign(body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET).unwrap();
+        mac.update(body);
+        let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("sha256={}", digest)
+    }
+
+    /// A client of an API server holding `trigger()` and its webhook secret,
+    /// accepting the runs and status updates of the trigger. Anything else is
+    /// not found.
+    fn mock_context() -> (Arc<Context>, Requests) {
+        let (service, mut handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        let requests = Requests::default();
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Some((request, send)) = handle.next_request().await {
+                let (parts, body) = request.into_parts();
+                let body: Value = serde_json::from_slice(&body.collect_bytes().await.unwrap()).unwrap_or(Value::Null);
+                let (method, path) = (parts.method.to_string(), parts.uri.path().to_string());
+                let (status, reply) = match (method.as_str(), path.as_str()) {
+                    ("GET", "/apis/ph.io/v1alpha1/namespaces/ci/phpipelinetriggers/shop")
+                    | ("PATCH", "/apis/ph.io/v1alpha1/namespaces/ci/phpipelinetriggers/shop/status") => {
+                        (200, serde_json::to_value(trigger()).unwrap())
+                    }
+                    ("GET", "/api/v1/namespaces/ci/secrets/webhook") => (
+                        200,
+                        json!({
+                            "apiVersion": "v1",
+                            "kind": "Secret",
+                            "metadata": { "name": "webhook", "namespace": "ci" },
+                            "data": { "secret": STANDARD.encode(SECRET) }
+                        }),
+                    ),
+                    ("POST", "/apis/ph.io/v1alpha1/namespaces/ci/phpipelineruns") => {
+                        let mut run = body.clone();
+                        run["metadata"]["name"] = json!("build-x7k2p");
+                        (201, run)
+                    }
+                    _ => (
+                        404,
+                        json!({ "apiVersion": "v1", "kind": "Status", "status": "Failure", "reason": "NotFound", "message": "not found", "code": 404 }),
+                    ),
+                };
+                received.lock().unwrap().push((method, path, body));
+                send.send_response(Response::builder().status(status).body(Body::from(reply.to_string().into_bytes())).unwrap());
+            }
+        });
+        let client = Client::new(service, "default");
+        let ctx = Arc::new(Context {
+            recorder: events::recorder(client.clone(), "ph-pipeline-trigger-controller"),
+            client,
+        });
+        (ctx, requests)
+    }
+
+    fn created_runs(requests: &Requests) -> Vec<Value> {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(method, path, _)| method == "POST" && path.ends_with("/phpipelineruns"))
+            .map(|(_, _, body)| body.clone())
+            .collect()
+    }
+
+    async fn deliver(ctx: Arc<Context>, signature: Option<String>, id: Option<&str>, body: Vec<u8>) -> StatusCode {
+        let delivery = Delivery {
+            signature,
+            event: Some("push".to_string()),
+            id: id.map(str::to_string),
+        };
+        let (status, _) = handle_delivery(ctx, "ci".to_string(), "shop".to_string(), delivery, Bytes::from(body)).await;
+        status
+    }
+
+    #[tokio::test]
+    async fn test_delivery_with_invalid_signature() {
+        let (ctx, requests) = mock_context();
+        let body = push_body();
+        assert_eq!(deliver(ctx.clone(), None, Some("d-1"), body.clone()).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(deliver(ctx, Some(sign(b"another body")), Some("d-1"), body).await, StatusCode::UNAUTHORIZED);
+        assert!(created_runs(&requests).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delivery_without_id() {
+        let (ctx, requests) = mock_context();
+        let body = push_body();
+        assert_eq!(deliver(ctx, Some(sign(&body)), None, body).await, StatusCode::ACCEPTED);
+
+        // Only the push binding matched.
+        let runs = created_runs(&requests);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0]["spec"]["templateRef"], "build");
+        assert_eq!(runs[0]["spec"]["params"]["revision"], "1f2e3d4c");
+        assert_eq!(runs[0]["metadata"]["labels"][TRIGGER_LABEL], "shop");
+        let annotations = runs[0]["metadata"]["annotations"].as_object().unwrap();
+        assert_eq!(annotations[SHA_ANNOTATION], "1f2e3d4c");
+        assert!(!annotations.contains_key(DELIVERY_ANNOTATION));
+        assert!(requests.lock().unwrap().iter().any(|(method, path, body)| {
+            method == "PATCH" && path.ends_with("/shop/status") && body["status"]["lastRun"] == "build-x7k2p"
+        }));
+    }
+
+    #[test]
+    fn test_run_manifest() {
+        let trigger = trigger();
+        let event = vcs_events::parse_github_event("push", &push_body()).unwrap().unwrap();
+        let manifest = run_manifest("shop", &trigger.spec.bindings[0], &event, Some("d-1"));
+        assert_eq!(manifest["metadata"]["generateName"], "build-");
+        assert_eq!(manifest["metadata"]["annotations"][DELIVERY_ANNOTATION], "d-1");
+        let run: phPipelineRun = serde_json::from_value(manifest).unwrap();
+        assert_eq!(run.spec.changed_paths, Some(vec!["README.md".to_string()]));
+
+        // Without an ID, the annotation is left out rather than null.
+        let manifest = run_manifest("shop", &trigger.spec.bindings[0], &event, None);
+        assert!(manifest["metadata"]["annotations"].get(DELIVERY_ANNOTATION).is_none());
+        assert!(serde_json::from_value::<phPipelineRun>(manifest).is_ok());
+    }
+}
@@ -11,6 +11,9 @@
 * - `replicate_secrets`: Replicates Secrets from a source to a destination cluster.
 * - `replicate_configmaps`: Replicates ConfigMaps from a source to a destination cluster.
 *
+* The hashing, naming, reference and duration helpers of pipelines live in
+* the `pipeline_rules` crate, shared with `ph ci run`.
+*
 * SPDX-License-Identifier: Apache-2.0
 */
 
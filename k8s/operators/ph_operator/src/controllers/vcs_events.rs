@@ -0,0 +1,480 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/vcs_events.rs
+*
+* This file understands the webhooks of a Git server: it checks their
+* signature, extracts the facts a pipeline needs from the payload and decides
+* which trigger bindings match. It holds no Kubernetes logic, so the rules can
+* be tested on their own.
+*
+* Architecture:
+* - `verify_signature` checks the GitHub-style `X-Hub-Signature-256` header,
+*   an HMAC-SHA256 of the raw body keyed with the webhook secret.
+* - `parse_github_event` turns a `push` or `pull_request` delivery into a
+*   `VcsEvent`. Deliveries that never start a run (pings, deleted branches,
+*   closed pull requests, ...) yield `None`.
+* - `binding_matches` applies the event, branch, tag and path filters of a
+*   binding; filters are globs (see `pipeline_rules::glob`). Only pushes
+*   list their changed files, so `validate_binding` rejects path filters on
+*   bindings of other events.
+* - `run_params` fills the `$(event.*)` references of a binding's parameters.
+* - `parse_pull_request_event` reads the whole lifecycle of a pull request
+*   for preview triggers, closed and merged pull requests included, and
+*   `preview_matches` applies a preview trigger's filters.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::crds::{phPreviewTriggerSpec, TriggerBinding, TriggerEvent};
+use hmac::{Hmac, Mac};
+use pipeline_rules::glob::glob_match;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum VcsError {
+    #[error("invalid '{event}' payload: {message}")]
+    InvalidPayload { event: String, message: String },
+    #[error("binding of template '{0}' filters on paths, which only push events list")]
+    PathsWithoutPush(String),
+}
+
+/// The facts about a Git event that bindings filter on and runs receive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VcsEvent {
+    pub kind: TriggerEvent,
+    /// The repository, as "owner/name".
+    pub repository: String,
+    /// The full ref, e.g. "refs/heads/main" or "refs/pull/12/head".
+    pub git_ref: String,
+    /// The pushed branch, or the base branch of a pull request.
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    /// The commit to build.
+    pub sha: String,
+    pub pull_request: Option<u64>,
+    /// The files changed by a push.
+    pub changed_paths: Vec<String>,
+}
+
+/// What a pull request delivery means for the preview of the pull request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewAction {
+    /// Deploy the head commit: the pull request was opened, reopened or
+    /// received new commits.
+    Deploy,
+    /// Remove the preview: the pull request was closed or merged.
+    Remove,
+}
+
+/// A pull request delivery, as preview triggers see it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PullRequestEvent {
+    pub action: PreviewAction,
+    /// The repository, as "owner/name".
+    pub repository: String,
+    pub clone_url: String,
+    pub number: u64,
+    /// The branch holding the changes.
+    pub head_branch: String,
+    /// The commit to deploy.
+    pub head_sha: String,
+    /// The branch the pull request targets.
+    pub base_branch: String,
+    pub merged: bool,
+}
+
+#[derive(Deserialize)]
+struct Repository {
+    full_name: String,
+    #[serde(default)]
+    clone_url: String,
+}
+
+#[derive(Deserialize)]
+struct PushPayload {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    after: String,
+    #[serde(default)]
+    deleted: bool,
+    repository: Repository,
+    #[serde(default)]
+    commits: Vec<PushCommit>,
+}
+
+#[derive(Deserialize)]
+struct PushCommit {
+    #[serde(default)]
+    added: Vec<String>,
+    #[serde(default)]
+    modified: Vec<String>,
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct PullRequestPayload {
+    action: String,
+    number: u64,
+    pull_request: PullRequest,
+    repository: Repository,
+}
+
+#[derive(Deserialize)]
+struct PullRequest {
+    head: GitPointer,
+    base: GitPointer,
+    #[serde(default)]
+    merged: bool,
+}
+
+#[derive(Deserialize)]
+struct GitPointer {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    sha: String,
+}
+
+/// Checks the `X-Hub-Signature-256` header (`sha256=<hex digest>`) of a
+/// delivery against the webhook secret. The comparison takes constant time.
+pub fn verify_signature(secret: &[u8], body: &[u8], header: &str) -> bool {
+    let Some(signature) = header.trim().strip_prefix("sha256=").and_then(decode_hex) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).ok().filter(|pair| pair.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+/// Parses a GitHub delivery of type `event` (the `X-GitHub-Event` header).
+pub fn parse_github_event(event: &str, body: &[u8]) -> Result<Option<VcsEvent>, VcsError> {
+    let invalid = |e: serde_json::Error| VcsError::InvalidPayload {
+        event: event.to_string(),
+        message: e.to_string(),
+    };
+    match event {
+        "push" => {
+            let payload: PushPayload = serde_json::from_slice(body).map_err(invalid)?;
+            if payload.deleted {
+                return Ok(None);
+            }
+            let (kind, branch, tag) = if let Some(tag) = payload.git_ref.strip_prefix("refs/tags/") {
+                (TriggerEvent::Tag, None, Some(tag.to_string()))
+            } else if let Some(branch) = payload.git_ref.strip_prefix("refs/heads/") {
+                (TriggerEvent::Push, Some(branch.to_string()), None)
+            } else {
+                return Ok(None);
+            };
+            let mut changed_paths: Vec<String> = payload
+                .commits
+                .into_iter()
+                .flat_map(|commit| commit.added.into_iter().chain(commit.modified).chain(commit.removed))
+                .collect();
+            changed_paths.sort();
+            changed_paths.dedup();
+            Ok(Some(VcsEvent {
+                kind,
+                repository: payload.repository.full_name,
+                git_ref: payload.git_ref,
+                branch,
+                tag,
+                sha: payload.after,
+                pull_request: None,
+                changed_paths,
+            }))
+        }
+        "pull_request" => {
+            let payload: PullRequestPayload = serde_json::from_slice(body).map_err(invalid)?;
+            if !matches!(payload.action.as_str(), "opened" | "reopened" | "synchronize") {
+                return Ok(None);
+            }
+            Ok(Some(VcsEvent {
+                kind: TriggerEvent::PullRequest,
+                repository: payload.repository.full_name,
+                git_ref: format!("refs/pull/{}/head", payload.number),
+                branch: Some(payload.pull_request.base.git_ref),
+                tag: None,
+                sha: payload.pull_request.head.sha,
+                pull_request: Some(payload.number),
+                changed_paths: Vec::new(),
+            }))
+        }
+        // Pings and all other events never start a run.
+        _ => Ok(None),
+    }
+}
+
+/// Parses a GitHub delivery of type `event` for preview triggers. Unlike
+/// `parse_github_event`, closing a pull request is an event of interest.
+pub fn parse_pull_request_event(event: &str, body: &[u8]) -> Result<Option<PullRequestEvent>, VcsError> {
+    if event != "pull_request" {
+        return Ok(None);
+    }
+    let payload: PullRequestPayload = serde_json::from_slice(body).map_err(|e| VcsError::InvalidPayload {
+        event: event.to_string(),
+        message: e.to_string(),
+    })?;
+    let action = match payload.action.as_str() {
+        "opened" | "reopened" | "synchronize" => PreviewAction::Deploy,
+        "closed" => PreviewAction::Remove,
+        // Labels, reviews, edits, ... do not change what is deployed.
+        _ => return Ok(None),
+    };
+    Ok(Some(PullRequestEvent {
+        action,
+        repository: payload.repository.full_name,
+        clone_url: payload.repository.clone_url,
+        number: payload.number,
+        head_branch: payload.pull_request.head.git_ref,
+        head_sha: payload.pull_request.head.sha,
+        base_branch: payload.pull_request.base.git_ref,
+        merged: payload.pull_request.merged,
+    }))
+}
+
+/// Whether a preview trigger handles `event`.
+pub fn preview_matches(spec: &phPreviewTriggerSpec, event: &PullRequestEvent) -> bool {
+    spec.repository.as_ref().is_none_or(|repository| *repository == event.repository)
+        && any_glob(&spec.branches, Some(&event.base_branch))
+}
+
+/// Whether `value` matches one of `globs`; an empty list matches anything.
+fn any_glob(globs: &[String], value: Option<&str>) -> bool {
+    globs.is_empty() || value.is_some_and(|value| globs.iter().any(|glob| glob_match(glob, value)))
+}
+
+/// Checks that a binding only filters on what its events carry.
+pub fn validate_binding(binding: &TriggerBinding) -> Result<(), VcsError> {
+    if !binding.paths.is_empty() && binding.events.iter().any(|kind| *kind != TriggerEvent::Push) {
+        return Err(VcsError::PathsWithoutPush(binding.template_ref.clone()));
+    }
+    Ok(())
+}
+
+/// Whether a binding starts a run for `event`. A path filter never matches
+/// an event without changed files.
+pub fn binding_matches(binding: &TriggerBinding, event: &VcsEvent) -> bool {
+    if !binding.events.contains(&event.kind) {
+        return false;
+    }
+    let paths_match = binding.paths.is_empty()
+        || event.changed_paths.iter().any(|path| any_glob(&binding.paths, Some(path)));
+    match event.kind {
+        TriggerEvent::Push | TriggerEvent::PullRequest => {
+            any_glob(&binding.branches, event.branch.as_deref()) && paths_match
+        }
+        TriggerEvent::Tag => any_glob(&binding.tags, event.tag.as_deref()) && paths_match,
+    }
+}
+
+/// The parameters of the run a binding creates for `event`.
+pub fn run_params(binding: &TriggerBinding, event: &VcsEvent) -> BTreeMap<String, String> {
+    let pull_request = event.pull_request.map(|n| n.to_string()).unwrap_or_default();
+    let references = [
+        ("$(event.sha)", event.sha.as_str()),
+        ("$(event.ref)", event.git_ref.as_str()),
+        ("$(event.branch)", event.branch.as_deref().unwrap_or_default()),
+        ("$(event.tag)", event.tag.as_deref().unwrap_or_default()),
+        ("$(event.repository)", event.repository.as_str()),
+        ("$(event.pullRequest)", pull_request.as_str()),
+    ];
+    binding
+        .params
+        .iter()
+        .map(|(name, value)| {
+            let value = references
+                .iter()
+                .fold(value.clone(), |value, (reference, replacement)| value.replace(reference, replacement));
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crds::{KeySelector, PreviewTemplate};
+
+    fn binding(events: &[TriggerEvent], branches: &[&str], paths: &[&str]) -> TriggerBinding {
+        TriggerBinding {
+            events: events.to_vec(),
+            branches: branches.iter().map(|b| b.to_string()).collect(),
+            tags: Vec::new(),
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            template_ref: "ci".to_string(),
+            params: BTreeMap::from([
+                ("revision".to_string(), "$(event.sha)".to_string()),
+                ("ref".to_string(), "$(event.ref)".to_string()),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        // The example from GitHub's webhook documentation.
+        let secret = b"It's a Secret to Everybody";
+        let header = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(verify_signature(secret, b"Hello, World!", header));
+        assert!(!verify_signature(secret, b"Hello, World?", header));
+        assert!(!verify_signature(b"another secret", b"Hello, World!", header));
+        assert!(!verify_signature(secret, b"Hello, World!", "sha1=757107ea"));
+    }
+
+    #[test]
+    fn test_push_event() {
+        let body = serde_json::json!({
+            "ref": "refs/heads/main",
+            "after": "1f2e3d4c",
+            "repository": { "full_name": "acme/shop" },
+            "commits": [
+                { "added": ["services/api/new.rs"], "modified": ["README.md"], "removed": [] },
+                { "added": [], "modified": ["README.md"], "removed": [] }
+            ]
+        });
+        let event = parse_github_event("push", body.to_string().as_bytes()).unwrap().unwrap();
+        assert_eq!(event.kind, TriggerEvent::Push);
+        assert_eq!(event.branch.as_deref(), Some("main"));
+        assert_eq!(event.changed_paths, vec!["README.md", "services/api/new.rs"]);
+
+        assert!(binding_matches(&binding(&[TriggerEvent::Push], &["main"], &["services/api/**"]), &event));
+        assert!(!binding_matches(&binding(&[TriggerEvent::Push], &["release/*"], &[]), &event));
+        assert!(!binding_matches(&binding(&[TriggerEvent::Push], &[], &["services/web/**"]), &event));
+        assert!(!binding_matches(&binding(&[TriggerEvent::Tag], &[], &[]), &event));
+
+        let params = run_params(&binding(&[TriggerEvent::Push], &[], &[]), &event);
+        assert_eq!(params["revision"], "1f2e3d4c");
+        assert_eq!(params["ref"], "refs/heads/main");
+
+        assert_eq!(parse_github_event("ping", b"{}").unwrap(), None);
+        assert!(parse_github_event("push", b"not json").is_err());
+    }
+
+    #[test]
+    fn test_pull_request_event() {
+        let payload = |action: &str| {
+            serde_json::json!({
+                "action": action,
+                "number": 42,
+                "pull_request": {
+                    "head": { "ref": "feature/login", "sha": "abc123" },
+                    "base": { "ref": "main", "sha": "def456" }
+                },
+                "repository": { "full_name": "acme/shop" }
+            })
+            .to_string()
+        };
+        let event = parse_github_event("pull_request", payload("synchronize").as_bytes()).unwrap().unwrap();
+        assert_eq!(event.sha, "abc123");
+        assert_eq!(event.git_ref, "refs/pull/42/head");
+        assert!(binding_matches(&binding(&[TriggerEvent::PullRequest], &["main"], &[]), &event));
+        assert!(!binding_matches(&binding(&[TriggerEvent::PullRequest], &["release/*"], &[]), &event));
+        // Pull request deliveries do not list their changed files.
+        assert!(!binding_matches(&binding(&[TriggerEvent::PullRequest], &["main"], &["docs/**"]), &event));
+        assert_eq!(parse_github_event("pull_request", payload("closed").as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_validate_binding() {
+        assert!(validate_binding(&binding(&[TriggerEvent::Push], &["main"], &["docs/**"])).is_ok());
+        assert!(validate_binding(&binding(&[TriggerEvent::PullRequest, TriggerEvent::Tag], &[], &[])).is_ok());
+        assert_eq!(
+            validate_binding(&binding(&[TriggerEvent::Push, TriggerEvent::PullRequest], &[], &["docs/**"])),
+            Err(VcsError::PathsWithoutPush("ci".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_pull_request_lifecycle() {
+        let payload = |action: &str, merged: bool| {
+            serde_json::json!({
+                "action": action,
+                "number": 7,
+                "pull_request": {
+                    "head": { "ref": "feature/login", "sha": "abc123" },
+                    "base": { "ref": "main", "sha": "def456" },
+                    "merged": merged
+                },
+                "repository": { "full_name": "acme/shop", "clone_url": "https://github.com/acme/shop.git" }
+            })
+            .to_string()
+        };
+        let parse = |action: &str, merged: bool| {
+            parse_pull_request_event("pull_request", payload(action, merged).as_bytes()).unwrap()
+        };
+
+        let opened = parse("opened", false).unwrap();
+        assert_eq!(opened.action, PreviewAction::Deploy);
+        assert_eq!(opened.number, 7);
+        assert_eq!(opened.head_branch, "feature/login");
+        assert_eq!(opened.head_sha, "abc123");
+        assert_eq!(opened.base_branch, "main");
+        assert_eq!(opened.clone_url, "https://github.com/acme/shop.git");
+        assert_eq!(parse("synchronize", false).unwrap().action, PreviewAction::Deploy);
+        assert_eq!(parse("reopened", false).unwrap().action, PreviewAction::Deploy);
+
+        let merged = parse("closed", true).unwrap();
+        assert_eq!(merged.action, PreviewAction::Remove);
+        assert!(merged.merged);
+        assert_eq!(parse("closed", false).unwrap().action, PreviewAction::Remove);
+
+        assert_eq!(parse("labeled", false), None);
+        assert_eq!(parse_pull_request_event("push", b"{}").unwrap(), None);
+        assert!(parse_pull_request_event("pull_request", b"{}").is_err());
+    }
+
+    #[test]
+    fn test_preview_matches() {
+        let event = parse_pull_request_event(
+            "pull_request",
+            serde_json::json!({
+                "action": "opened",
+                "number": 7,
+                "pull_request": {
+                    "head": { "ref": "feature/login", "sha": "abc123" },
+                    "base": { "ref": "release/1.2", "sha": "def456" }
+                },
+                "repository": { "full_name": "acme/shop" }
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap()
+        .unwrap();
+        let spec = |repository: Option<&str>, branches: &[&str]| phPreviewTriggerSpec {
+            secret_ref: KeySelector { name: "webhook".to_string(), key: "secret".to_string() },
+            repository: repository.map(str::to_string),
+            branches: branches.iter().map(|b| b.to_string()).collect(),
+            template: PreviewTemplate {
+                repo_url: None,
+                manifest_path: "k8s".to_string(),
+                app_name: "shop".to_string(),
+                render: None,
+                report: None,
+                size_class: None,
+                team: None,
+                ttl: None,
+                idle_schedule: None,
+            },
+        };
+        assert!(preview_matches(&spec(None, &[]), &event));
+        assert!(preview_matches(&spec(Some("acme/shop"), &["release/*"]), &event));
+        assert!(!preview_matches(&spec(Some("acme/blog"), &[]), &event));
+        assert!(!preview_matches(&spec(None, &["main"]), &event));
+    }
+}
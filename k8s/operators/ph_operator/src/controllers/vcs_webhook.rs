@@ -0,0 +1,179 @@
+/*
+* Copyright (C) 2025 Pedro Henrique / phkaiser13
+*
+* File: k8s/operators/ph_operator/src/controllers/vcs_webhook.rs
+*
+* This file holds the webhook plumbing shared by the controllers that react
+* to Git server deliveries (`trigger_controller` for phPipelineTriggers and
+* `preview_trigger_controller` for phPreviewTriggers).
+*
+* Architecture:
+* - `serve` runs a `warp` server receiving `POST /<path>/<namespace>/<trigger>`
+*   with the `X-Hub-Signature-256`, `X-GitHub-Event` and `X-GitHub-Delivery`
+*   headers, and hands every delivery to the controller's handler.
+* - `authenticate` looks up the trigger a delivery is addressed to and checks
+*   its signature with the trigger's webhook secret (see `vcs_events`). A
+*   rejected delivery is logged and published as an Event on the trigger.
+* - `WebhookTrigger` is what both trigger kinds have in common: the Secret key
+*   of their webhook secret and the last delivery they accepted.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::controllers::events;
+use crate::controllers::vcs_events;
+use crate::crds::{phPipelineTrigger, phPreviewTrigger, KeySelector};
+use k8s_openapi::{api::core::v1::Secret, NamespaceResourceScope};
+use kube::{api::Api, client::Client, runtime::events::Recorder, Resource};
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+use std::future::Future;
+use tracing::{info, warn};
+use warp::{http::StatusCode, hyper::body::Bytes, Filter};
+
+/// GitHub limits payloads to 25 MB.
+const MAX_PAYLOAD_BYTES: u64 = 25 * 1024 * 1024;
+
+/// A trigger that receives signed Git webhook deliveries.
+pub trait WebhookTrigger:
+    Resource<DynamicType = (), Scope = NamespaceResourceScope> + Clone + DeserializeOwned + Debug
+{
+    /// The key of the Secret holding the webhook secret.
+    fn secret_ref(&self) -> &KeySelector;
+    /// The ID of the last accepted delivery.
+    fn last_delivery(&self) -> Option<&str>;
+}
+
+impl WebhookTrigger for phPipelineTrigger {
+    fn secret_ref(&self) -> &KeySelector {
+        &self.spec.secret_ref
+    }
+
+    fn last_delivery(&self) -> Option<&str> {
+        self.status.as_ref()?.last_delivery.as_deref()
+    }
+}
+
+impl WebhookTrigger for phPreviewTrigger {
+    fn secret_ref(&self) -> &KeySelector {
+        &self.spec.secret_ref
+    }
+
+    fn last_delivery(&self) -> Option<&str> {
+        self.status.as_ref()?.last_delivery.as_deref()
+    }
+}
+
+/// The headers of a delivery that the handlers need.
+pub struct Delivery {
+    pub signature: Option<String>,
+    pub event: Option<String>,
+    pub id: Option<String>,
+}
+
+impl Delivery {
+    /// Whether this is a redelivery of the last delivery `trigger` accepted.
+    pub fn is_redelivery(&self, trigger: &impl WebhookTrigger) -> bool {
+        self.id.is_some() && self.id.as_deref() == trigger.last_delivery()
+    }
+}
+
+/// The outcome of `authenticate`.
+pub enum Authentication<K> {
+    /// The delivery is signed by the trigger's secret.
+    Accepted { trigger: K, event: String },
+    /// The reply to send instead of processing the delivery.
+    Rejected(StatusCode, String),
+}
+
+/// Runs the server handing the deliveries at `/<path>/<namespace>/<trigger>`
+/// to `handler`, which returns the status and message of the reply.
+pub async fn serve<F, Fut>(path: &'static str, port: u16, handler: F)
+where
+    F: Fn(String, String, Delivery, Bytes) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = (StatusCode, String)> + Send,
+{
+    let route = warp::post()
+        .and(warp::path(path))
+        .and(warp::path::param::<String>())
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::header::optional::<String>("x-hub-signature-256"))
+        .and(warp::header::optional::<String>("x-github-event"))
+        .and(warp::header::optional::<String>("x-github-delivery"))
+        .and(warp::body::content_length_limit(MAX_PAYLOAD_BYTES))
+        .and(warp::body::bytes())
+        .then(move |ns, name, signature, event, id, body| {
+            let delivery = Delivery { signature, event, id };
+            let handler = handler.clone();
+            async move {
+                let (status, message) = handler(ns, name, delivery, body).await;
+                warp::reply::with_status(message, status)
+            }
+        });
+
+    info!("Starting Git webhook server for /{} on 0.0.0.0:{}", path, port);
+    warp::serve(route).run(([0, 0, 0, 0], port)).await;
+}
+
+/// Reads the webhook secret of a trigger, if it exists.
+pub async fn webhook_secret<K: WebhookTrigger>(
+    client: &Client,
+    ns: &str,
+    trigger: &K,
+) -> Result<Option<Vec<u8>>, kube::Error> {
+    let secrets: Api<Secret> = Api::namespaced(client.clone(), ns);
+    let secret_ref = trigger.secret_ref();
+    Ok(secrets
+        .get_opt(&secret_ref.name)
+        .await?
+        .and_then(|secret| secret.data?.remove(&secret_ref.key))
+        .map(|value| value.0)
+        .filter(|value| !value.is_empty()))
+}
+
+/// Looks up trigger `ns/name` and checks that `delivery` is signed with its
+/// webhook secret and names its event type.
+pub async fn authenticate<K: WebhookTrigger>(
+    client: &Client,
+    recorder: &Recorder,
+    ns: &str,
+    name: &str,
+    delivery: &Delivery,
+    body: &[u8],
+) -> Result<Authentication<K>, kube::Error> {
+    let triggers: Api<K> = Api::namespaced(client.clone(), ns);
+    let Some(trigger) = triggers.get_opt(name).await? else {
+        let message = format!("No {} '{}/{}'.", K::kind(&()), ns, name);
+        return Ok(Authentication::Rejected(StatusCode::NOT_FOUND, message));
+    };
+
+    let Some(secret) = webhook_secret(client, ns, &trigger).await? else {
+        let message = "The webhook secret is not available.".to_string();
+        return Ok(Authentication::Rejected(StatusCode::SERVICE_UNAVAILABLE, message));
+    };
+    let signed = delivery
+        .signature
+        .as_deref()
+        .is_some_and(|signature| vcs_events::verify_signature(&secret, body, signature));
+    if !signed {
+        warn!(trigger = %name, namespace = %ns, "Rejected Git delivery with an invalid signature");
+        events::warning(
+            recorder,
+            &trigger,
+            "InvalidSignature",
+            "Authenticate",
+            "Rejected a delivery without a valid X-Hub-Signature-256 signature.",
+        )
+        .await;
+        return Ok(Authentication::Rejected(StatusCode::UNAUTHORIZED, "Invalid signature.".to_string()));
+    }
+
+    match &delivery.event {
+        Some(event) => Ok(Authentication::Accepted { trigger, event: event.clone() }),
+        None => Ok(Authentication::Rejected(
+            StatusCode::BAD_REQUEST,
+            "Missing X-GitHub-Event header.".to_string(),
+        )),
+    }
+}
@@ -23,13 +23,34 @@
 * - The `status` for `phRelease` has also been enriched with `AnalysisRunStatus`
 *   to track the progress of these health checks, enabling automated promotion
 *   or rollback logic within the release_controller.
+* - `CanaryStrategy` carries a typed `steps` plan (`setWeight`, `pause`,
+*   `analysis`, `setHeaderRoute`) that the release_controller walks through,
+*   recording its position in `status.currentStep`.
+* - Each `Metric` may name a `provider` (`prometheus`, `web` or `job`) so that
+*   analysis is not limited to PromQL queries, or a `comparison` that judges
+*   the canary against the stable baseline and records scores in the status.
+* - `phPipelineTemplate` holds a parameterized pipeline definition and
+*   `phPipelineRun` instantiates it, so that every execution keeps its own
+*   status and the runs form the template's history.
+* - `phPipelineTrigger` binds the push, tag and pull request webhooks of a
+*   Git server to runs of pipeline templates.
+* - `phPreviewTrigger` keeps a `phPreview` for every open pull request of a
+*   repository, pinned to the head commit of the pull request.
+* - `phPreviewClass` (cluster-scoped) sizes preview namespaces with a
+*   ResourceQuota, a LimitRange and a default-deny NetworkPolicy, and caps
+*   the previews running at once per repository or team.
 * - A new `phAutoHealRule` CRD is introduced to define auto-healing policies.
 *   This allows the operator to react to Prometheus alerts by executing predefined
 *   runbooks, creating a closed-loop remediation system.
+* - Every status carries standard `conditions` (`Ready`, `Progressing`,
+*   `Degraded`) in the `metav1.Condition` format, maintained through the
+*   `conditions` controller module.
 * - `serde` attributes are used to map between idiomatic Rust `snake_case` and
 *   idiomatic Kubernetes `camelCase`.
 * - `schemars` is leveraged to automatically generate an OpenAPI v3 schema from the
 *   Rust types, which is embedded into the CRD manifest for server-side validation.
+* - The step matrix, `when` conditions and step phases of `phPipeline` come
+*   from the `pipeline_rules` crate, which `ph ci run` uses as well.
 *
 * SPDX-License-Identifier: Apache-2.0
 */
@@ -37,6 +58,11 @@
 use kube::CustomResource;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// The pipeline types the rules of `pipeline_rules` work on, shared with
+// `ph ci run`.
+pub use pipeline_rules::{StepMatrix, StepPhase, WhenCondition, WhenOperator};
 
 // --- phPreview Custom Resource Definition ---
 
@@ -47,8 +73,12 @@ use serde::{Deserialize, Serialize};
     kind = "phPreview",
     namespaced,
     status = "phPreviewStatus",
-    printcolumn = r#"{"name":"Status", "type":"string", "jsonPath":".status.conditions[-1:].type"}"#,
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Reason", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].reason"}"#,
     printcolumn = r#"{"name":"Namespace", "type":"string", "jsonPath":".status.namespace"}"#,
+    printcolumn = r#"{"name":"Commit", "type":"string", "jsonPath":".status.deployedCommit"}"#,
+    printcolumn = r#"{"name":"URL", "type":"string", "jsonPath":".status.url"}"#,
+    printcolumn = r#"{"name":"Class", "type":"string", "jsonPath":".status.sizeClass"}"#,
     printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#,
     shortname = "pgprv"
 )]
@@ -58,6 +88,147 @@ pub struct phPreviewSpec {
     pub branch: String,
     pub manifest_path: String,
     pub app_name: String,
+    /// The pull request the preview belongs to. Set by phPreviewTrigger.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pr_number: Option<u64>,
+    /// The commit to deploy. When unset, the head of `branch` is deployed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_sha: Option<String>,
+    /// How the manifests are rendered. Without it, every YAML file under
+    /// `manifestPath` is applied as it is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub render: Option<PreviewRender>,
+    /// Time-to-live in hours, counted from the creation of the preview.
+    /// Superseded by `ttl`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_hours: Option<u32>,
+    /// How long the preview lives, counted from its creation, e.g. "36h" or
+    /// "7d". The operator deletes the preview once it expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
+    /// When the preview expires (RFC 3339), set e.g. by `ph preview extend`.
+    /// Takes precedence over `ttl` and `ttlHours`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    /// The working hours of the preview. Outside them, its Deployments and
+    /// StatefulSets are scaled to zero until they are woken up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_schedule: Option<IdleSchedule>,
+    /// Reports the preview to its pull request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<PreviewReport>,
+    /// The phPreviewClass setting the guardrails of the preview namespace.
+    /// Defaults to the class annotated `ph.io/default-class: "true"`, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_class: Option<String>,
+    /// The team owning the preview, for the per-team limit of concurrent
+    /// previews.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+}
+
+/// The working hours of a preview, e.g. Monday to Friday from "08:00" to
+/// "20:00" in "Europe/Lisbon". A preview asleep outside them is woken up by
+/// the `ph.io/wake-up` annotation, or by the first request to its ingresses,
+/// and then stays awake for `wakeFor`.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IdleSchedule {
+    /// The working days, as "Mon" to "Sun". Defaults to Monday to Friday.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<String>,
+    /// The start of the working hours, "HH:MM".
+    pub start: String,
+    /// The end of the working hours, "HH:MM". An end before the start spans
+    /// midnight.
+    pub end: String,
+    /// The IANA time zone of `start` and `end`. Defaults to UTC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+    /// How long a preview woken up outside the working hours stays awake.
+    /// Defaults to "2h".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wake_for: Option<String>,
+}
+
+/// Reports a preview to its pull request (`prNumber`): a commit status on the
+/// deployed commit and a comment kept up to date with the preview URL, the
+/// state and pod health, and the expiry.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewReport {
+    /// The key of the Secret, in the preview's namespace, holding the API
+    /// token.
+    pub token_secret_ref: KeySelector,
+    /// The root of the GitHub REST API. Defaults to https://api.github.com.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    /// The repository, as `owner/name`. Defaults to the repository of
+    /// `repoUrl`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// The context of the commit status. Defaults to "ph/preview".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+/// Renders the manifests of a preview before they are applied. At most one
+/// of `kustomize` and `helm` may be used.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewRender {
+    /// Builds `manifestPath` as a kustomization (a directory holding a
+    /// `kustomization.yaml`). The build runs in the operator.
+    #[serde(default)]
+    pub kustomize: bool,
+    /// Renders a Helm chart with `helm template`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub helm: Option<HelmRender>,
+    /// Images whose tag is replaced in the rendered workloads, typically with
+    /// the deployed commit.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageOverride>,
+}
+
+/// A Helm chart rendered for a preview.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HelmRender {
+    /// The chart: a path in the repository, or the name of a chart of
+    /// `repo`. Defaults to `manifestPath`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chart: Option<String>,
+    /// The URL of the chart repository, for charts not kept in the
+    /// repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The release name. Defaults to `appName`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_name: Option<String>,
+    /// Values files, relative to the repository root, applied in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values_files: Vec<String>,
+    /// Values set last, as with `helm --set`. Values may use `$(commitSha)`
+    /// and `$(shortSha)`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub values: BTreeMap<String, String>,
+}
+
+/// Replaces the image of the containers running `name`.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageOverride {
+    /// The image to replace, without tag, e.g. "ghcr.io/acme/shop".
+    pub name: String,
+    /// A replacement for the image name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_name: Option<String>,
+    /// The new tag. `$(commitSha)` and `$(shortSha)` stand for the deployed
+    /// commit. Defaults to `$(commitSha)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_tag: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
@@ -65,23 +236,134 @@ pub struct phPreviewSpec {
 pub struct phPreviewStatus {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub namespace: Option<String>,
+    /// The commit whose manifests were last applied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployed_commit: Option<String>,
+    /// The URL of the preview, from the ingresses of its namespace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// When the preview expires, from `expiresAt`, `ttl` or `ttlHours`
+    /// (RFC 3339).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    /// A digest of the last report posted to the pull request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reported: Option<String>,
+    /// The phPreviewClass applied to the namespace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_class: Option<String>,
+    /// Until when a preview woken up outside its working hours stays awake
+    /// (RFC 3339).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub awake_until: Option<String>,
+    #[serde(default)]
     pub conditions: Vec<StatusCondition>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+/// A status condition following the `metav1.Condition` conventions.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct StatusCondition {
     #[serde(rename = "type")]
     pub type_: String,
+    /// `True`, `False` or `Unknown`.
+    pub status: String,
+    /// A CamelCase, machine-readable reason for the last transition.
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
     pub message: String,
+    /// RFC 3339 timestamp of when `status` last changed.
+    #[serde(default)]
+    pub last_transition_time: String,
+    /// The `metadata.generation` the condition was computed from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
 }
 
-impl StatusCondition {
-    pub fn new(type_: String, message: String) -> Self {
-        Self { type_, message }
-    }
+// --- phPreviewClass Custom Resource Definition ---
+
+/// A size class of previews: the guardrails stamped into the namespace of
+/// each preview using it, and the limits on concurrent previews.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[kube(
+    group = "ph.io",
+    version = "v1alpha1",
+    kind = "phPreviewClass",
+    printcolumn = r#"{"name":"Per Repository", "type":"integer", "jsonPath":".spec.concurrency.maxPerRepository"}"#,
+    printcolumn = r#"{"name":"Per Team", "type":"integer", "jsonPath":".spec.concurrency.maxPerTeam"}"#,
+    printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#,
+    shortname = "pgprvcls"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct phPreviewClassSpec {
+    /// The hard limits of the namespace's ResourceQuota, e.g.
+    /// `{"requests.cpu": "2", "limits.memory": "4Gi", "pods": "20"}`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub quota: BTreeMap<String, String>,
+    /// The namespace's LimitRange for containers. With a CPU or memory
+    /// quota, the defaults let pods without resources be admitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_limits: Option<ContainerLimits>,
+    /// The exceptions to the default-deny NetworkPolicy of the namespace.
+    #[serde(default)]
+    pub network: PreviewNetwork,
+    /// Limits on the previews running at the same time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<PreviewConcurrency>,
 }
 
+/// The resources of the containers of a preview, as in a LimitRange item.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerLimits {
+    /// The limits of containers that set none.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub default: BTreeMap<String, String>,
+    /// The requests of containers that set none.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub default_request: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub max: BTreeMap<String, String>,
+}
+
+/// The traffic allowed in and out of a preview namespace. Pods may always
+/// reach each other and the cluster DNS.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewNetwork {
+    /// Label selectors of the namespaces allowed to reach the preview, e.g.
+    /// `{"kubernetes.io/metadata.name": "ingress-nginx"}`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_from_namespaces: Vec<BTreeMap<String, String>>,
+    /// CIDRs the preview may reach, e.g. "0.0.0.0/0" for the internet.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_egress_cidrs: Vec<String>,
+}
+
+/// Caps on the previews running at the same time across the cluster, per
+/// repository and per team (`spec.team`).
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewConcurrency {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_per_repository: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_per_team: Option<u32>,
+    /// What happens to a preview over a limit.
+    #[serde(default)]
+    pub when_exceeded: LimitPolicy,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, Default, PartialEq, Eq)]
+pub enum LimitPolicy {
+    /// The preview waits, in order of creation, until a running preview is
+    /// deleted.
+    #[default]
+    Queue,
+    /// The preview is refused until its spec changes.
+    Reject,
+}
 
 // --- phRelease Custom Resource Definition ---
 
@@ -119,6 +401,7 @@ pub struct PublicKeySecretRef {
     kind = "phRelease",
     namespaced,
     status = "phReleaseStatus",
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
     shortname = "pgrls"
 )]
 #[serde(rename_all = "camelCase")]
@@ -129,6 +412,66 @@ pub struct phReleaseSpec {
     /// Security-related configurations for the release.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub security: Option<Security>,
+    /// Selects the traffic manager used to shift traffic. When omitted, the
+    /// controller detects one from the CRDs installed in the cluster.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traffic_management: Option<TrafficManagement>,
+    /// How many revisions to keep in `status.revisions`. Defaults to 10.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision_history_limit: Option<u32>,
+}
+
+/// Traffic manager selection for a phRelease.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficManagement {
+    #[serde(default)]
+    pub provider: TrafficManagerKind,
+    /// Settings for the `GatewayAPI` provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway_api: Option<GatewayApiTrafficConfig>,
+}
+
+/// The tool that shifts traffic between the stable and canary versions.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, Default, PartialEq, Eq)]
+pub enum TrafficManagerKind {
+    /// Detect the tool from the installed CRDs, in the order Argo Rollouts,
+    /// Istio, SMI (Linkerd), Gateway API, falling back to replica scaling.
+    #[default]
+    Auto,
+    ArgoRollouts,
+    Istio,
+    Linkerd,
+    #[serde(rename = "GatewayAPI")]
+    GatewayApi,
+    /// Approximate the split by scaling the stable and canary Deployments.
+    ReplicaScaling,
+}
+
+/// Describes the `HTTPRoute` managed by the `GatewayAPI` provider.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GatewayApiTrafficConfig {
+    /// The Gateways the route attaches to. When empty, the route attaches to
+    /// the application's root Service (east-west routing).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parent_refs: Vec<GatewayParentRef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hostnames: Vec<String>,
+    /// The port of the versioned Services. Defaults to 80.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+}
+
+/// A reference to a Gateway an `HTTPRoute` attaches to.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GatewayParentRef {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section_name: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
@@ -138,6 +481,8 @@ pub struct ReleaseStrategy {
     pub strategy_type: StrategyType,
     pub canary: Option<CanaryStrategy>,
     pub blue_green: Option<BlueGreenStrategy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow: Option<ShadowStrategy>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
@@ -145,11 +490,18 @@ pub struct ReleaseStrategy {
 pub enum StrategyType {
     Canary,
     BlueGreen,
+    /// Dark launch: mirror live traffic to the new version and analyse it
+    /// before any user-facing traffic moves.
+    Shadow,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CanaryStrategy {
+    /// The initial canary weight applied when the release is first set up.
+    /// Ignored once `steps` starts issuing `setWeight` steps.
+    #[serde(default)]
+    #[schemars(range(max = 100))]
     pub traffic_percent: u8,
     #[serde(default)]
     pub auto_increment: bool,
@@ -157,14 +509,125 @@ pub struct CanaryStrategy {
     pub analysis: Option<Analysis>,
     #[serde(default)]
     pub auto_promote: bool,
+    /// The ordered plan the controller walks through. When empty, a default
+    /// plan of `setWeight: trafficPercent` followed by an analysis step (or an
+    /// indefinite pause if no analysis is configured) is used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<CanaryStep>,
+    /// Sends selected requests to the canary for the whole rollout, regardless
+    /// of the traffic weight.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub experiment: Option<Experiment>,
+}
 
-    // Raw strings from CLI, to be parsed by controller
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub steps_str: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub metric_str: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub analysis_window_str: Option<String>,
+/// Match-based routing to the canary, e.g. so internal users or users who opt
+/// in with a header hit the new version before any public traffic does.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Experiment {
+    /// A request matching any entry is sent to the canary.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<RequestMatch>,
+}
+
+/// Request conditions that must all hold for a request to match.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestMatch {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<HeaderMatch>,
+    /// At most one cookie condition is supported per match.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cookies: Vec<CookieMatch>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query_params: Vec<QueryParamMatch>,
+}
+
+/// An exact-value match on a request cookie.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CookieMatch {
+    pub name: String,
+    pub value: String,
+}
+
+/// An exact-value match on a query parameter.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryParamMatch {
+    pub name: String,
+    pub value: String,
+}
+
+/// A single step of a canary plan. Exactly one of the fields must be set.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CanaryStep {
+    /// Shifts the given percentage of traffic to the canary.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(max = 100))]
+    pub set_weight: Option<u8>,
+    /// Halts the rollout for a fixed duration or until it is resumed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pause: Option<PauseStep>,
+    /// Runs the release analysis and blocks until it passes or fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<AnalysisStep>,
+    /// Routes requests carrying the given headers to the canary, regardless of weight.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub set_header_route: Option<SetHeaderRoute>,
+    /// Halts the rollout until someone approves it with the `resume` or `promote` action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval: Option<ApprovalStep>,
+}
+
+/// Parameters for an `approval` canary step.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalStep {
+    /// A note for the approvers, e.g. what to check before signing off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Parameters for a `pause` canary step.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PauseStep {
+    /// How long to pause (e.g., "30s", "10m"). If omitted, the release stays
+    /// paused until it is manually resumed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+}
+
+/// Parameters for an `analysis` canary step.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisStep {
+    /// Names of the metrics from `analysis.metrics` to evaluate at this step.
+    /// All configured metrics are evaluated when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub metrics: Vec<String>,
+}
+
+/// Parameters for a `setHeaderRoute` canary step.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetHeaderRoute {
+    /// A name identifying the route in the traffic manager.
+    pub name: String,
+    /// The header matches that send a request to the canary. An empty list
+    /// removes a previously installed route.
+    #[serde(default, rename = "match", skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<HeaderMatch>,
+}
+
+/// An exact-value match on a request header.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderMatch {
+    pub header_name: String,
+    pub header_value: String,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
@@ -173,19 +636,140 @@ pub struct Analysis {
     pub interval: String,
     pub threshold: u32,
     pub max_failures: u32,
-    // This will be populated by the controller after parsing metric_str
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub metrics: Vec<Metric>,
+    /// Score thresholds for metrics judged against the baseline. Defaults to
+    /// `pass: 95`, `marginal: 75`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_thresholds: Option<ScoreThresholds>,
+}
+
+/// Maps the overall baseline-vs-canary score (0-100) to an analysis outcome:
+/// at least `pass` succeeds, below `marginal` fails, anything in between is inconclusive.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreThresholds {
+    pub pass: f64,
+    pub marginal: f64,
+}
+
+impl Default for ScoreThresholds {
+    fn default() -> Self {
+        Self { pass: 95.0, marginal: 75.0 }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Metric {
     pub name: String,
+    /// PromQL query for the default Prometheus provider. Ignored when `provider` is set.
+    #[serde(default)]
     pub query: String,
+    /// Success condition evaluated against the measured `result` (and any named
+    /// `result.<name>` values), e.g. `result.p99 < 300 && result.errors < 0.01`.
+    /// Defaults to `result == 1` for the `job` provider.
+    #[serde(default)]
     pub on_success: String,
+    /// Where the metric's value comes from. Defaults to Prometheus with `query`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<MetricProvider>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub predictive_analysis: Option<PredictiveAnalysis>,
+    /// Judges the canary against the stable baseline instead of `onSuccess`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comparison: Option<BaselineComparison>,
+}
+
+/// Compares a metric sampled from the stable (baseline) and canary pods with a
+/// Mann-Whitney U test and a relative-difference tolerance.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BaselineComparison {
+    /// PromQL range query in which `{{track}}` is replaced with `stable` or
+    /// `canary`, matching the pods' `version-id` label.
+    pub query: String,
+    /// Which change of the canary counts as a regression. Defaults to `Increase`.
+    #[serde(default)]
+    pub direction: RegressionDirection,
+    /// Relative difference of the medians tolerated even when it is significant. Defaults to `0.1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f64>,
+    /// Significance level of the Mann-Whitney U test. Defaults to `0.05`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub significance: Option<f64>,
+    /// How far back both versions are sampled. Defaults to `10m`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookback: Option<String>,
+    /// Resolution of the samples. Defaults to `30s`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<String>,
+    /// Weight of this metric in the overall score. Defaults to `1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub enum RegressionDirection {
+    /// Higher canary values are worse (e.g. error rate, latency).
+    #[default]
+    Increase,
+    /// Lower canary values are worse (e.g. throughput, success rate).
+    Decrease,
+    /// Any significant difference is a regression.
+    Either,
+}
+
+/// Selects the backend a metric is measured with. Exactly one field must be set.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricProvider {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prometheus: Option<PrometheusMetricProvider>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web: Option<WebMetricProvider>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<JobMetricProvider>,
+}
+
+/// Runs a PromQL query, optionally against a Prometheus other than the operator's default.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PrometheusMetricProvider {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    pub query: String,
+    /// Additional named queries, exposed to `onSuccess` as `result.<name>`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub queries: BTreeMap<String, String>,
+}
+
+/// Fetches a JSON document over HTTP and reads a number out of it.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebMetricProvider {
+    pub url: String,
+    /// JSONPath to the value, e.g. `$.data.errorRate` or `$.checks[0].latency`.
+    pub json_path: String,
+    /// Additional named JSONPaths, exposed to `onSuccess` as `result.<name>`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub json_paths: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+}
+
+/// Runs a container to completion. The result is `1` if it exits successfully and `0` otherwise.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobMetricProvider {
+    pub image: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
@@ -203,6 +787,27 @@ pub struct BlueGreenStrategy {
     pub auto_promote: bool,
 }
 
+/// A shadow release serves every request from the stable version while a copy
+/// of `mirrorPercent` percent of the live traffic is sent to the new version.
+/// Responses of the new version are discarded. Requires Istio.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowStrategy {
+    /// The percentage of live traffic to mirror to the new version.
+    #[schemars(range(min = 1, max = 100))]
+    pub mirror_percent: u8,
+    /// How long to mirror before the analysis starts (e.g., "10m").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+    /// The analysis run against the shadow pods. Without it, the release waits
+    /// for a manual promotion once `duration` has elapsed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<Analysis>,
+    /// Promote automatically once the shadow analysis succeeds.
+    #[serde(default)]
+    pub auto_promote: bool,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
 #[serde(rename_all = "PascalCase")]
 pub enum ReleasePhase {
@@ -214,6 +819,16 @@ pub enum ReleasePhase {
     RollingBack,
 }
 
+/// Why a release is `Paused`.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub enum PauseReason {
+    /// A `pause` or `approval` step, or the wait for a manual promotion.
+    Step,
+    /// The analysis of the current step saw metrics trending worse.
+    Analysis,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct phReleaseStatus {
@@ -225,12 +840,103 @@ pub struct phReleaseStatus {
     pub canary_version: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub traffic_split: Option<String>,
+    /// The index of the canary step currently being executed.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub current_step: Option<u32>,
+    /// When the current step started. Used to time `pause` steps.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step_start_time: Option<String>,
+    /// Why the release is paused. Resuming after an analysis pause re-runs the
+    /// analysis step instead of skipping it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pause_reason: Option<PauseReason>,
+    /// The percentage of traffic currently sent to the canary.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canary_weight: Option<u8>,
+    /// Header routes installed by `setHeaderRoute` steps, kept until the release ends.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub header_routes: Vec<SetHeaderRoute>,
+    /// Manual rollout actions (approvals, promotions, aborts) in the order they were applied.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approvals: Vec<ApprovalRecord>,
+    /// The releases of this phRelease, oldest first. A new revision starts
+    /// whenever the spec changes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revisions: Vec<ReleaseRevision>,
+    /// The revision a pending rollback restores, if it targets a specific revision.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback_to_revision: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub analysis_run: Option<AnalysisRunStatus>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub progressing_start_time: Option<String>,
+    /// The `metadata.generation` of the spec this status reflects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<StatusCondition>,
+}
+
+/// One release of a phRelease.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseRevision {
+    pub revision: u32,
+    /// The version (image tag) rolled out by this revision.
+    pub version: String,
+    /// Hash of the spec that produced this revision.
+    pub spec_hash: String,
+    /// RFC 3339 timestamp of when the revision started.
+    pub started_at: String,
+    /// RFC 3339 timestamp of when the revision reached its outcome.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    pub outcome: RevisionOutcome,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub enum RevisionOutcome {
+    Progressing,
+    /// Promoted to stable.
+    Succeeded,
+    /// Failed its analysis or was aborted before promotion.
+    Failed,
+    /// Promoted, then replaced by a rollback to an earlier revision.
+    RolledBack,
+    /// The spec changed before the revision finished.
+    Superseded,
+}
+
+/// A manual action requested through the `ph.io/rollout-action` annotation.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub enum RolloutAction {
+    /// Continue past the current pause or approval step.
+    Resume,
+    /// Skip the remaining steps and promote the canary.
+    Promote,
+    /// Stop the rollout and roll the canary back.
+    Abort,
+    /// Restore the version of an earlier successful revision (the previous one
+    /// unless `ph.io/rollout-revision` names another), even after promotion.
+    Rollback,
+}
+
+/// Who requested a manual rollout action, and when.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalRecord {
+    pub action: RolloutAction,
+    pub approver: String,
+    /// RFC 3339 timestamp of when the action was applied.
+    pub time: String,
+    /// The plan step the release was on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<u32>,
+    /// The revision restored by a `Rollback`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
@@ -242,6 +948,36 @@ pub struct AnalysisRunStatus {
     pub last_check: Option<String>, // Using String to align with Kubernetes API conventions for timestamps
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub metric_history: Option<Vec<MetricHistory>>,
+    /// Overall baseline-vs-canary score (0-100) of the last judged analysis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// Per-metric results of the last judged analysis.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub metric_scores: Vec<MetricScore>,
+}
+
+/// The judge's verdict for a single compared metric.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricScore {
+    pub name: String,
+    pub classification: MetricClassification,
+    /// `100` for `Pass`, `0` for `Fail`; absent for `NoData`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p_value: Option<f64>,
+    /// `(canary - baseline) / baseline` of the sample medians.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_difference: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub enum MetricClassification {
+    Pass,
+    Fail,
+    NoData,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
@@ -273,16 +1009,108 @@ pub struct HistoricalValue {
     namespaced,
     status = "phPipelineStatus",
     printcolumn = r#"{"name":"Status", "type":"string", "jsonPath":".status.phase"}"#,
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
     printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#,
     shortname = "pgpipe"
 )]
 #[serde(rename_all = "camelCase")]
 pub struct phPipelineSpec {
-    /// A list of stages to be executed sequentially.
+    /// A list of stages. Unless a step declares `needs`, it waits for every
+    /// step of the previous stage, so stages still run one after another.
     pub stages: Vec<PipelineStage>,
+    /// The maximum number of steps running at the same time. Defaults to 4.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_parallel: Option<u32>,
+    /// Where the steps of the pipeline share files. Without it, every step
+    /// gets an empty scratch workspace and only output values are passed on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<PipelineWorkspace>,
+    /// Steps that run once all other steps finished, whether the pipeline
+    /// succeeded, failed or was cancelled, e.g. for cleanup or notifications.
+    /// They see the outcome in `PH_PIPELINE_RESULT`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub finally: Vec<PipelineStep>,
+    /// Where the results of steps with a `cache` are kept. Needed by any
+    /// step that declares one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<PipelineCache>,
+    /// The files changed by the commit being built, for `when` conditions on
+    /// `changedPaths`. Runs started by a phPipelineTrigger for a push set it;
+    /// when it is unknown, those conditions hold.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_paths: Option<Vec<String>>,
 }
 
-/// A single stage in the pipeline, containing one or more steps.
+/// The storage shared by the steps of a pipeline, mounted at `/workspace`.
+/// Exactly one of `volume` and `s3` must be set.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineWorkspace {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<WorkspaceVolume>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3: Option<WorkspaceS3>,
+}
+
+/// A PersistentVolumeClaim mounted by every step.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceVolume {
+    /// An existing claim to use. When unset, the operator creates
+    /// `<pipeline>-workspace`, which is deleted with the pipeline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claim_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_class_name: Option<String>,
+    /// The size of the created claim. Defaults to "1Gi".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+    /// The access mode of the created claim. Defaults to "ReadWriteMany",
+    /// which steps running in parallel on several nodes need. With
+    /// "ReadWriteOnce", set `maxParallel: 1` or keep the steps on one node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_mode: Option<String>,
+}
+
+/// An S3-compatible bucket (AWS S3, MinIO, ...). Each step downloads the
+/// outputs of the steps it needs and uploads its own.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceS3 {
+    /// The endpoint URL, e.g. "http://minio.ci.svc:9000".
+    pub endpoint: String,
+    pub bucket: String,
+    /// A key prefix under which the runs are stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// A Secret holding `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+    pub credentials_secret: String,
+}
+
+/// The store of cached step results, shared by all runs. Exactly one of
+/// `volume` and `s3` must be set. The operator never deletes entries.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineCache {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<CacheVolume>,
+    /// A bucket; the entries are stored under `<prefix>/<namespace>/`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3: Option<WorkspaceS3>,
+}
+
+/// An existing PersistentVolumeClaim holding the cache entries. It must
+/// outlive the pipelines, so the operator does not create it.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheVolume {
+    pub claim_name: String,
+}
+
+/// A single stage in the pipeline, containing one or more steps that run in
+/// parallel.
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct PipelineStage {
@@ -309,9 +1137,98 @@ pub struct PipelineStep {
     /// The arguments to pass to the command.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub args: Vec<String>,
-    /// Artifacts produced by this step.
+    /// Environment variables to set in the container.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<PipelineEnvVar>,
+    /// Artifacts produced by this step. Later steps can use their values as
+    /// `$(steps.<step>.outputs.<name>)` in `command`, `args` and `env`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub outputs: Option<Vec<PipelineStepOutput>>,
+    /// The names of the steps (in any stage) that must succeed before this
+    /// one starts. When set, it replaces the implicit dependency on the
+    /// previous stage; an empty list is the same as not setting it.
+    #[serde(default, alias = "runAfter", skip_serializing_if = "Vec::is_empty")]
+    pub needs: Vec<String>,
+    /// The maximum duration of one attempt, e.g. "90s", "10m" or "1h". The
+    /// Job is killed and the attempt fails when it is exceeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    /// How many times a failed attempt is retried. Defaults to 0.
+    #[serde(default)]
+    pub retries: u32,
+    /// The wait before the first retry, doubled for every further one (up to
+    /// 10 minutes). Defaults to "10s".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_backoff: Option<String>,
+    /// When set, a failure of this step does not fail the pipeline and the
+    /// steps that need it still run.
+    #[serde(default)]
+    pub continue_on_error: bool,
+    /// Skips the step when a previous run had the same inputs, restoring its
+    /// outputs and cached directories instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<StepCache>,
+    /// Runs the step once for every combination of the matrix parameters,
+    /// e.g. os × toolchain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<StepMatrix>,
+    /// Conditions that must all hold for the step to run. Otherwise it is
+    /// skipped, which still lets the steps that need it run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub when: Vec<WhenCondition>,
+}
+
+/// The inputs of a cached step. The key of an entry is the hash of these
+/// inputs together with the step's image, command, arguments and
+/// environment. The image is part of the key as written, so refer to it by
+/// digest (`image@sha256:...`) for a pushed tag to invalidate the entries.
+/// Caching needs a volume workspace.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StepCache {
+    /// Globs of workspace files whose content is part of the key, e.g.
+    /// "go.sum" or "services/api/**". A `*` also matches a `/`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+    /// Values that are part of the key, usually the `$(params.<name>)` of a
+    /// template. References to step outputs are resolved first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<String>,
+    /// Workspace directories saved after the step succeeded and restored on
+    /// a cache hit, e.g. "node_modules". The step's outputs are always saved.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+}
+
+/// An environment variable of a pipeline step. Its value is either given
+/// inline or read from a Secret or ConfigMap of the pipeline's namespace.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineEnvVar {
+    pub name: String,
+    #[serde(default)]
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_from: Option<EnvVarSource>,
+}
+
+/// Where the value of an environment variable comes from. Exactly one of the
+/// fields must be set.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvVarSource {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_key_ref: Option<KeySelector>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_map_key_ref: Option<KeySelector>,
+}
+
+/// A key of a Secret or ConfigMap.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KeySelector {
+    pub name: String,
+    pub key: String,
 }
 
 /// Defines an output artifact from a pipeline step.
@@ -320,7 +1237,10 @@ pub struct PipelineStep {
 pub struct PipelineStepOutput {
     /// The logical name of the output artifact.
     pub name: String,
-    /// The path within the container where the artifact can be found.
+    /// Where the step writes the artifact. A relative path is resolved against
+    /// the step's output directory (`$PH_OUTPUTS`); an absolute path must be
+    /// inside `/workspace`. The first line of a file (up to 1 KiB) becomes the
+    /// output's value.
     pub path: String,
 }
 
@@ -337,12 +1257,69 @@ pub struct phPipelineStatus {
     /// The timestamp when the pipeline completed or failed.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub completion_time: Option<String>,
-    /// The index of the current stage being executed.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub current_stage_index: Option<usize>,
-    /// The index of the current step being executed within the stage.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub current_step_index: Option<usize>,
+    /// The state of every step of the pipeline.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<PipelineStepStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<StatusCondition>,
+}
+
+/// The observed state of a single pipeline step.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineStepStatus {
+    /// The name of the step.
+    pub name: String,
+    /// The name of the stage the step belongs to.
+    pub stage: String,
+    pub phase: StepPhase,
+    /// The Job running the step, once it was created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_time: Option<String>,
+    /// Why the step failed or was skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The values of the step's outputs, captured when it succeeded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub outputs: BTreeMap<String, String>,
+    /// The number of attempts started so far.
+    #[serde(default)]
+    pub attempts: u32,
+    /// When a failed step is retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_attempt_time: Option<String>,
+    /// The cache lookup of a step with a `cache`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<StepCacheStatus>,
+    /// The matrix combination this step runs for.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub matrix: BTreeMap<String, String>,
+}
+
+/// The cache lookup of a step.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StepCacheStatus {
+    pub result: CacheResult,
+    /// The hash of the step's inputs, once it was computed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+/// The outcome of a cache lookup.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub enum CacheResult {
+    /// The inputs are being hashed and looked up.
+    Checking,
+    /// The step was restored from the cache and did not run.
+    Hit,
+    /// The step runs, and its results are saved under `key`.
+    Miss,
 }
 
 /// An enum representing the possible phases of a pipeline's lifecycle.
@@ -353,6 +1330,284 @@ pub enum PipelinePhase {
     Running,
     Succeeded,
     Failed,
+    Cancelled,
+}
+
+// --- phPipelineTemplate Custom Resource Definition ---
+
+/// # phPipelineTemplate
+/// A reusable pipeline definition with parameters. A template never runs by
+/// itself: every `phPipelineRun` instantiates it with concrete values.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(
+    group = "ph.io",
+    version = "v1alpha1",
+    kind = "phPipelineTemplate",
+    namespaced,
+    printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#,
+    shortname = "pgpipetpl"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct phPipelineTemplateSpec {
+    /// The parameters of the template. Steps use their values as
+    /// `$(params.<name>)` in any text field.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<PipelineParam>,
+    /// How many finished runs of the template are kept; older ones are
+    /// deleted. Defaults to 10.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_history_limit: Option<u32>,
+    /// The pipeline itself, in the same format as a phPipeline spec.
+    #[serde(flatten)]
+    pub pipeline: phPipelineSpec,
+}
+
+/// A parameter of a pipeline template.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineParam {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub param_type: ParamType,
+    /// The value used when a run does not set the parameter. A parameter
+    /// without a default is required.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// The type a parameter value must have. Values are always written as strings.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    #[default]
+    String,
+    Integer,
+    Boolean,
+}
+
+// --- phPipelineRun Custom Resource Definition ---
+
+/// # phPipelineRun
+/// One execution of a `phPipelineTemplate`. The run renders the template with
+/// its parameter values into a phPipeline that it owns, and reports the
+/// progress of that pipeline in its own status. Finished runs are kept as the
+/// history of the template, up to its `runHistoryLimit`.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(
+    group = "ph.io",
+    version = "v1alpha1",
+    kind = "phPipelineRun",
+    namespaced,
+    status = "phPipelineRunStatus",
+    printcolumn = r#"{"name":"Template", "type":"string", "jsonPath":".spec.templateRef"}"#,
+    printcolumn = r#"{"name":"Status", "type":"string", "jsonPath":".status.phase"}"#,
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#,
+    shortname = "pgpiperun"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct phPipelineRunSpec {
+    /// The name of the phPipelineTemplate to run, in the run's namespace.
+    pub template_ref: String,
+    /// The values of the template's parameters. Parameters with a default may
+    /// be left out.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+    /// The files changed by the commit being built, passed on to the
+    /// pipeline's `when` conditions. Set by phPipelineTrigger for pushes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_paths: Option<Vec<String>>,
+}
+
+/// The observed state of a phPipelineRun.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct phPipelineRunStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<PipelinePhase>,
+    /// The phPipeline executing the run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline_name: Option<String>,
+    /// The generation of the template the run was rendered from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_generation: Option<i64>,
+    /// The parameter values of the run, defaults included.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_time: Option<String>,
+    /// The state of every step, as reported by the pipeline.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<PipelineStepStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<StatusCondition>,
+}
+
+// --- phPipelineTrigger Custom Resource Definition ---
+
+/// # phPipelineTrigger
+/// Starts pipeline runs from the webhooks of a Git server. The operator
+/// receives the deliveries for a trigger at `/vcs/<namespace>/<name>` and
+/// checks their GitHub-style HMAC signature (`X-Hub-Signature-256`) with the
+/// trigger's secret. Every binding matching the event creates a
+/// `phPipelineRun`.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(
+    group = "ph.io",
+    version = "v1alpha1",
+    kind = "phPipelineTrigger",
+    namespaced,
+    status = "phPipelineTriggerStatus",
+    printcolumn = r#"{"name":"Last Run", "type":"string", "jsonPath":".status.lastRun"}"#,
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#,
+    shortname = "pgpipetrig"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct phPipelineTriggerSpec {
+    /// The key of a Secret holding the webhook secret configured on the Git
+    /// server.
+    pub secret_ref: KeySelector,
+    /// Only accept events of this repository ("owner/name").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    pub bindings: Vec<TriggerBinding>,
+}
+
+/// Maps matching Git events to runs of a pipeline template.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerBinding {
+    /// The events that start a run.
+    pub events: Vec<TriggerEvent>,
+    /// Globs the branch must match (pushes and the base branch of pull
+    /// requests), e.g. "main" or "release/*". Empty matches any branch.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<String>,
+    /// Globs the tag must match, e.g. "v*". Empty matches any tag.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Globs of which at least one changed file must match, e.g.
+    /// "services/api/**". Only pushes list their changed files, so bindings
+    /// with paths may only react to pushes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    /// The template to run, in the trigger's namespace.
+    pub template_ref: String,
+    /// The parameters of the run. Values may use `$(event.sha)`,
+    /// `$(event.ref)`, `$(event.branch)`, `$(event.tag)`,
+    /// `$(event.repository)` and `$(event.pullRequest)`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+}
+
+/// The kinds of Git events a binding reacts to.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TriggerEvent {
+    /// A push to a branch.
+    Push,
+    /// A pull request was opened, reopened or received new commits.
+    PullRequest,
+    /// A tag was pushed.
+    Tag,
+}
+
+/// The observed state of a phPipelineTrigger.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct phPipelineTriggerStatus {
+    /// The ID of the last accepted delivery (`X-GitHub-Delivery`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_delivery: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_event_time: Option<String>,
+    /// The last phPipelineRun the trigger created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<StatusCondition>,
+}
+
+// --- phPreviewTrigger Custom Resource Definition ---
+
+/// # phPreviewTrigger
+/// Keeps a `phPreview` for every open pull request of a repository. The
+/// operator receives the `pull_request` deliveries for a trigger at
+/// `/previews/<namespace>/<name>`, signed like those of a phPipelineTrigger.
+/// Opening or reopening a pull request creates its preview, new commits
+/// re-deploy it at the new head and closing or merging the pull request
+/// deletes it.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(
+    group = "ph.io",
+    version = "v1alpha1",
+    kind = "phPreviewTrigger",
+    namespaced,
+    status = "phPreviewTriggerStatus",
+    printcolumn = r#"{"name":"Last Preview", "type":"string", "jsonPath":".status.lastPreview"}"#,
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#,
+    shortname = "pgprvtrig"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct phPreviewTriggerSpec {
+    /// The key of a Secret holding the webhook secret configured on the Git
+    /// server.
+    pub secret_ref: KeySelector,
+    /// Only accept events of this repository ("owner/name").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// Globs the base branch of a pull request must match, e.g. "main".
+    /// Empty matches any branch.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<String>,
+    /// The preview created for each pull request.
+    pub template: PreviewTemplate,
+}
+
+/// The parts of a phPreview that do not come from the pull request.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewTemplate {
+    /// The repository to clone. Defaults to the clone URL of the repository
+    /// in the event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo_url: Option<String>,
+    pub manifest_path: String,
+    pub app_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub render: Option<PreviewRender>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<PreviewReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_class: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_schedule: Option<IdleSchedule>,
+}
+
+/// The observed state of a phPreviewTrigger.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct phPreviewTriggerStatus {
+    /// The ID of the last accepted delivery (`X-GitHub-Delivery`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_delivery: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_event_time: Option<String>,
+    /// The last phPreview the trigger created, updated or deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_preview: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<StatusCondition>,
 }
 
 // --- phAutoHealRule Custom Resource Definition ---
@@ -370,6 +1625,7 @@ pub enum PipelinePhase {
     status = "phAutoHealRuleStatus",
     printcolumn = r#"{"name":"Trigger", "type":"string", "jsonPath":".spec.triggerName"}"#,
     printcolumn = r#"{"name":"Status", "type":"string", "jsonPath":".status.state"}"#,
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
     printcolumn = r#"{"name":"Last Execution", "type":"date", "jsonPath":".status.lastExecutionTime"}"#,
     printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#,
     shortname = "phahr"
@@ -525,6 +1781,7 @@ pub struct phAutoHealRuleStatus {
     kind = "PhgitSyncJob",
     namespaced,
     status = "PhgitSyncJobStatus",
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
     shortname = "pgsj"
 )]
 #[serde(rename_all = "camelCase")]
@@ -618,6 +1875,7 @@ pub struct Target {
     kind = "PhgitDisasterRecovery",
     namespaced,
     status = "PhgitDisasterRecoveryStatus",
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
     shortname = "phdr"
 )]
 #[serde(rename_all = "camelCase")]
@@ -652,6 +1910,10 @@ pub struct DRPolicy {
 #[serde(rename_all = "camelCase")]
 pub struct HealthCheckPolicy {
     pub prometheus_query: String,
+    /// Condition evaluated against the query result, bound as `value` (and `result`).
+    /// Defaults to `value > 0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_condition: Option<String>,
     pub interval: String,
     pub failure_threshold: u32,
 }
@@ -707,7 +1969,7 @@ pub enum DRState {
     printcolumn = r#"{"name":"Role", "type":"string", "jsonPath":".spec.role"}"#,
     printcolumn = r#"{"name":"Subject Kind", "type":"string", "jsonPath":".spec.subject.kind"}"#,
     printcolumn = r#"{"name":"Subject Name", "type":"string", "jsonPath":".spec.subject.name"}"#,
-    printcolumn = r#"{"name":"Status", "type":"string", "jsonPath":".status.conditions[-1:].type"}"#,
+    printcolumn = r#"{"name":"Ready", "type":"string", "jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
     shortname = "phrbac"
 )]
 #[serde(rename_all = "camelCase")]
@@ -12,13 +12,19 @@
 * setting up `tracing` for structured logging.
 * 2.  **CRD Registration**: The `main` function discovers all Custom
 * Resource Definitions (CRDs) that this operator manages: `phPreview`,
-* `phRelease`, `phPipeline`, and the new `phAutoHealRule`.
+* `phRelease`, `phPipeline`, `phPipelineRun`, and the new `phAutoHealRule`.
 * 3.  **Controller Manager**:
 *     - For `phPreview`, `phRelease`, and `phPipeline`, a standard `Controller`
 *       from `kube-rs` is instantiated to manage the watch and reconcile loop.
 *     - For `phAutoHealRule`, a dedicated `run` function is called. This function
 *       encapsulates both the CRD reconciler (for managing an in-memory cache)
 *       and an embedded HTTP webhook server (for receiving alerts from Alertmanager).
+*     - `phPipelineTrigger` works the same way: its `run` function hosts the
+*       reconciler and the server receiving Git webhook deliveries.
+*     - So does `phPreviewTrigger`, whose server receives the pull request
+*       deliveries that create, update and delete previews.
+*     - The preview waker is a plain server: it receives the requests sent to
+*       previews sleeping outside their working hours and wakes them up.
 * 4.  **Shared Context**: A shared `Context` object, containing the Kubernetes
 * client, is created for the traditional controllers. The auto-heal controller
 * manages its own state internally.
@@ -51,17 +57,35 @@ mod metrics;
 mod controllers {
     pub mod audit_controller;
     pub mod autoheal_controller; // New controller for auto-healing logic
+    pub mod conditions; // Standard Ready/Progressing/Degraded conditions
     pub mod dr_controller;
+    pub mod events; // Kubernetes Event recording
     pub mod gitsync_controller;
+    pub mod pipeline_cache; // Content-addressed step caching of phPipelines
     pub mod pipeline_controller;
+    pub mod pipeline_dag; // Step dependency graph of phPipelines
+    pub mod pipeline_matrix; // Matrix expansion of phPipeline steps
+    pub mod pipeline_run_controller; // Runs of phPipelineTemplates
+    pub mod pipeline_template; // Parameter rendering of phPipelineTemplates
+    pub mod pipeline_when; // `when` conditions of phPipeline steps
+    pub mod pipeline_workspace; // Shared workspace and step outputs of phPipelines
     pub mod preview_controller;
+    pub mod preview_guardrails; // Size classes and concurrency caps of phPreviews
+    pub mod preview_render; // Kustomize and Helm rendering of phPreview manifests
+    pub mod preview_report; // Commit statuses and comments on the pull requests of phPreviews
+    pub mod preview_schedule; // TTL expiry and working hours of phPreviews
+    pub mod preview_waker; // Wakes up sleeping phPreviews on their first request
+    pub mod preview_trigger_controller; // Pull request webhooks driving phPreviews
     pub mod rbac_policy_controller;
     pub mod release_controller;
+    pub mod trigger_controller; // Git webhook triggers of phPipelineRuns
+    pub mod vcs_events; // Signature check and parsing of Git webhook events
+    pub mod vcs_webhook; // Webhook server and signature checks shared by the Git triggers
 }
 
 // Re-exporting the CRDs for easier access.
 use crds::{
-    phAutoHealRule, phPipeline, phPreview, phRelease, PhgitDisasterRecovery, PhgitSyncJob,
+    phAutoHealRule, phPipeline, phPipelineRun, phPreview, phRelease, PhgitDisasterRecovery, PhgitSyncJob,
 };
 
 // The shared context struct passed to the traditional controllers.
@@ -69,6 +93,10 @@ pub struct Context {
     pub client: Client,
     // Add the prometheus client to the shared context
     pub prometheus_client: controllers::metrics_analyzer::PrometheusClient,
+    // Measures release analysis metrics with their configured provider.
+    pub metrics_analyzer: controllers::metrics_analyzer::MetricsAnalyzer,
+    // Publishes Kubernetes Events about the reconciled resources.
+    pub recorder: kube::runtime::events::Recorder,
 }
 
 /// Initializes the OpenTelemetry pipeline for Jaeger.
@@ -131,6 +159,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let previews = kube::Api::<phPreview>::all(client.clone());
     let releases = kube::Api::<phRelease>::all(client.clone());
     let pipelines = kube::Api::<phPipeline>::all(client.clone());
+    let pipeline_runs = kube::Api::<phPipelineRun>::all(client.clone());
     let dr_resources = kube::Api::<PhgitDisasterRecovery>::all(client.clone());
     let gitsyncjobs = kube::Api::<PhgitSyncJob>::all(client.clone());
     
@@ -142,6 +171,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let context = Arc::new(Context {
         client: client.clone(),
         prometheus_client: controllers::metrics_analyzer::PrometheusClient::new(&prometheus_endpoint),
+        metrics_analyzer: controllers::metrics_analyzer::MetricsAnalyzer::new(client.clone(), &prometheus_endpoint),
+        recorder: controllers::events::recorder(client.clone(), "ph-operator"),
     });
 
     // 4. Initialize metrics registry
@@ -158,6 +189,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         // --- Auto-Heal Controller and Webhook Server ---
         controllers::autoheal_controller::run(client.clone()),
 
+        // --- Pipeline Trigger Controller and Git Webhook Server ---
+        controllers::trigger_controller::run(client.clone()),
+
+        // --- Preview Trigger Controller and Pull Request Webhook Server ---
+        controllers::preview_trigger_controller::run(client.clone()),
+
+        // --- Preview Waker ---
+        controllers::preview_waker::run(client.clone()),
+
         // --- Preview Controller ---
         Controller::new(previews, Default::default())
             .run(
@@ -200,6 +240,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }),
 
+        // --- Pipeline Run Controller ---
+        // Also watches the pipelines created for runs to mirror their status.
+        Controller::new(pipeline_runs, Default::default())
+            .owns(kube::Api::<phPipeline>::all(client.clone()), Default::default())
+            .run(
+                controllers::pipeline_run_controller::reconcile,
+                controllers::pipeline_run_controller::on_error,
+                context.clone(),
+            )
+            .for_each(|res| async move {
+                match res {
+                    Ok(o) => info!("Reconciled phPipelineRun: {:?}", o),
+                    Err(e) => tracing::error!("phPipelineRun reconcile error: {}", e),
+                }
+            }),
+
         // --- DR Controller ---
         Controller::new(dr_resources, Default::default())
             .run(
@@ -15,7 +15,7 @@
  * complex business logic (like Kubernetes API interaction, Git operations, or
  * release orchestration) to the safer, more expressive Rust ecosystem.
  *
- * This version adds support for the `rollout promote` and `rollout rollback`
+ * This version adds support for the `rollout promote`, `rollout resume`, `rollout abort` and `rollout rollback`
  * subcommands, enabling manual control over the progressive delivery lifecycle.
  *
  * SPDX-License-Identifier: Apache-2.0 */
@@ -108,7 +108,7 @@ static void print_kube_usage(void) {
                    "  info            Display information about the current or a specific cluster.\n\n"
                    "GitOps & Deployments:\n"
                    "  sync            Sync manifests from a Git repo to a cluster. Can detect drift, create PRs, or apply directly.\n"
-                   "  rollout         Manage application rollouts with advanced strategies (start, promote, rollback).\n\n"
+                   "  rollout         Manage application rollouts with advanced strategies (start, promote, resume, abort, rollback).\n\n"
                    "Access Control (RBAC):\n"
                    "  grant           Grant a predefined role to a user or group.\n"
                    "  revoke          Revoke a role from a user or group.\n\n"
@@ -361,14 +361,20 @@ static phStatus handle_sync_command(int argc, const char** argv) {
  * This function parses the arguments for the rollout command, constructs a JSON
  * payload based on the specified action, and invokes the Rust release orchestrator.
  * - 'start': Initiates a new release. Requires --type, --app, and --image.
- * - 'promote': Manually promotes an ongoing release. Requires --app.
- * - 'rollback': Manually rolls back an ongoing release. Requires --app.
+ * - 'promote': Manually promotes an ongoing release. Requires --id.
+ * - 'resume': Approves a paused release so it continues its plan. Requires --id.
+ * - 'abort': Stops an ongoing release and rolls the canary back. Requires --id.
+ * - 'rollback': Rolls back an ongoing release, or restores an earlier revision
+ *   of a finished one with '--to-revision N'. Requires --id.
+ * These actions also accept the release id as a positional argument
+ * ('ph rollout rollback <app> --to-revision N') and '--by <name>' to record
+ * the approver.
  * The Rust backend interprets the 'type' field in the JSON to determine the
  * appropriate action to take on the corresponding phRelease custom resource.
  */
 static phStatus handle_rollout_command(int argc, const char** argv) {
     if (argc < 1) {
-        tui_print_error("Subcommand required for 'rollout'. Use 'start', 'promote', or 'rollback'.");
+        tui_print_error("Subcommand required for 'rollout'. Use 'start', 'promote', 'resume', 'abort', or 'rollback'.");
         return ph_ERROR_INVALID_ARGS;
     }
 
@@ -377,6 +383,7 @@ static phStatus handle_rollout_command(int argc, const char** argv) {
 
     if (strcmp(action, "start") == 0) {
         const char* type = NULL, *app = NULL, *image = NULL, *steps = NULL, *metric = NULL, *analysis_window = NULL;
+        const char* approve_after = NULL;
         const char* public_key_file = NULL;
         bool skip_sig_check = false;
 
@@ -387,6 +394,7 @@ static phStatus handle_rollout_command(int argc, const char** argv) {
             else if (strcmp(argv[i], "--steps") == 0 && i + 1 < argc) steps = argv[++i];
             else if (strcmp(argv[i], "--metric") == 0 && i + 1 < argc) metric = argv[++i];
             else if (strcmp(argv[i], "--analysis-window") == 0 && i + 1 < argc) analysis_window = argv[++i];
+            else if (strcmp(argv[i], "--approve-after") == 0 && i + 1 < argc) approve_after = argv[++i];
             else if (strcmp(argv[i], "--public-key-file") == 0 && i + 1 < argc) public_key_file = argv[++i];
             else if (strcmp(argv[i], "--skip-sig-check") == 0) skip_sig_check = true;
         }
@@ -431,6 +439,9 @@ static phStatus handle_rollout_command(int argc, const char** argv) {
         if (analysis_window) {
             ptr += snprintf(ptr, end - ptr, ",\"analysisWindow\":\"%s\"", analysis_window);
         }
+        if (approve_after) {
+            ptr += snprintf(ptr, end - ptr, ",\"approveAfter\":\"%s\"", approve_after);
+        }
 
         snprintf(ptr, end - ptr, "}"); // Close the JSON object
 
@@ -502,21 +513,27 @@ static phStatus handle_rollout_command(int argc, const char** argv) {
         }
         return ph_SUCCESS;
 
-    } else if (strcmp(action, "promote") == 0 || strcmp(action, "rollback") == 0) {
+    } else if (strcmp(action, "promote") == 0 || strcmp(action, "resume") == 0 ||
+               strcmp(action, "abort") == 0 || strcmp(action, "rollback") == 0) {
         const char* id = NULL;
         const char* to_revision_str = NULL;
+        const char* approver = NULL;
 
         for (int i = 1; i < argc; ++i) {
             if (strcmp(argv[i], "--id") == 0 && i + 1 < argc) {
                 id = argv[++i];
             } else if (strcmp(action, "rollback") == 0 && strcmp(argv[i], "--to-revision") == 0 && i + 1 < argc) {
                 to_revision_str = argv[++i];
+            } else if (strcmp(argv[i], "--by") == 0 && i + 1 < argc) {
+                approver = argv[++i];
+            } else if (!id && argv[i][0] != '-') {
+                id = argv[i];
             }
         }
 
         if (!id) {
             char error_msg[128];
-            snprintf(error_msg, sizeof(error_msg), "--id is required for 'rollout %s'.", action);
+            snprintf(error_msg, sizeof(error_msg), "A release id (<app> or --id) is required for 'rollout %s'.", action);
             tui_print_error(error_msg);
             return ph_ERROR_INVALID_ARGS;
         }
@@ -528,21 +545,30 @@ static phStatus handle_rollout_command(int argc, const char** argv) {
         ptr += snprintf(ptr, end - ptr, "{\"type\":\"%s\",\"id\":\"%s\"", action, id);
 
         if (to_revision_str) {
-            // Basic validation: check if it's a number.
+            // Basic validation: check if it's a positive number.
+            bool valid = *to_revision_str != '\0' && strlen(to_revision_str) < 10;
             for (const char* p = to_revision_str; *p; p++) {
-                if (*p < '0' || *p > '9') {
-                    tui_print_error("--to-revision must be a positive integer.");
-                    return ph_ERROR_INVALID_ARGS;
-                }
+                if (*p < '0' || *p > '9') valid = false;
+            }
+            if (!valid || atoi(to_revision_str) < 1) {
+                tui_print_error("--to-revision must be a positive integer.");
+                return ph_ERROR_INVALID_ARGS;
             }
             ptr += snprintf(ptr, end - ptr, ",\"toRevision\":%s", to_revision_str);
         }
+        if (approver) {
+            char* escaped_approver = json_escape(approver);
+            if (escaped_approver) {
+                ptr += snprintf(ptr, end - ptr, ",\"approver\":\"%s\"", escaped_approver);
+                free(escaped_approver);
+            }
+        }
 
         snprintf(ptr, end - ptr, "}");
 
         logger_log_fmt(LOG_LEVEL_DEBUG, "KubeHandler", "Calling 'run_release_orchestrator' with payload: %s", payload_buffer);
         unsigned char error_buffer[1024] = {0};
-        int result = run_release_orchestrator(json_payload, error_buffer, sizeof(error_buffer));
+        int result = run_release_orchestrator(payload_buffer, error_buffer, sizeof(error_buffer));
         if (result != 0) {
             char error_title[128];
             snprintf(error_title, sizeof(error_title), "Rollout %s command failed.", action);
@@ -552,7 +578,7 @@ static phStatus handle_rollout_command(int argc, const char** argv) {
         return ph_SUCCESS;
 
     } else {
-        tui_print_error("Unknown action for 'rollout'. Use 'start', 'status', 'plan', 'promote', or 'rollback'.");
+        tui_print_error("Unknown action for 'rollout'. Use 'start', 'status', 'plan', 'promote', 'resume', 'abort', or 'rollback'.");
         return ph_ERROR_NOT_FOUND;
     }
 }
@@ -10,6 +10,13 @@
 * JSON parsing, which are the idiomatic choices in the Rust ecosystem.
 * The error handling uses `Box<dyn std::error::Error>` to be flexible.
 *
+* Besides reading repositories, a provider reports on pull requests: commit
+* statuses and "sticky" comments, which are edited in place rather than
+* posted again. The operator uses them to publish preview environments.
+* `GitHubHandler::with_endpoint` points the handler at any server that speaks
+* the GitHub REST API (GitHub Enterprise, or a mock server in tests); `new`
+* honours the `GITHUB_API_URL` and `GITHUB_TOKEN` environment variables.
+*
 * SPDX-License-Identifier: Apache-2.0 */
 
 use serde::Deserialize;
@@ -19,7 +26,7 @@ use crate::{log_to_core, LogLevel};
 
 // --- Generic API Provider Contracts ---
 
-//// A generic, provider-agnostic representation of a repository.
+/// A generic, provider-agnostic representation of a repository.
 #[derive(Debug)]
 pub struct RepoInfo {
     pub full_name: String,
@@ -30,11 +37,62 @@ pub struct RepoInfo {
     pub default_branch: String,
 }
 
+/// The errors of the pull request reporting calls. They are `Send` so that
+/// the calls can be made from multi-threaded async tasks.
+pub type ProviderError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The state of a commit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitState {
+    Pending,
+    Success,
+    Failure,
+    Error,
+}
+
+impl CommitState {
+    fn as_str(self) -> &'static str {
+        match self {
+            CommitState::Pending => "pending",
+            CommitState::Success => "success",
+            CommitState::Failure => "failure",
+            CommitState::Error => "error",
+        }
+    }
+}
+
+/// A status attached to a commit, shown on the pull requests containing it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitStatus {
+    pub state: CommitState,
+    /// Identifies the status; a new status replaces the previous one of the
+    /// same context.
+    pub context: String,
+    pub description: String,
+    /// The link of the status, e.g. the URL of a preview.
+    pub target_url: Option<String>,
+}
+
 /// Defines the contract for any Git API service.
 #[async_trait::async_trait]
 pub trait ApiProvider: Send + Sync { // Adicionado Send + Sync para segurança de thread
     /// Fetches repository information asynchronously.
     async fn fetch_repo_info(&self, user: &str, repo: &str) -> Result<RepoInfo, Box<dyn std::error::Error>>;
+
+    /// Sets a status on commit `sha`.
+    async fn set_commit_status(&self, user: &str, repo: &str, sha: &str, status: &CommitStatus) -> Result<(), ProviderError>;
+
+    /// Creates or edits the comment of pull request `number` containing
+    /// `marker`, so that a single comment carries `body` (which must contain
+    /// `marker`). Returns the ID of the comment.
+    async fn upsert_pull_request_comment(
+        &self,
+        user: &str,
+        repo: &str,
+        number: u64,
+        marker: &str,
+        body: &str,
+    ) -> Result<u64, ProviderError>;
 }
 
 // --- GitHub API Implementation ---
@@ -52,30 +110,83 @@ struct GitHubRepoResponse {
     default_branch: String,
 }
 
-const GITHUB_API_ENDPOINT: &str = "https://api.github.com/repos";
+#[derive(Deserialize, Debug)]
+struct GitHubComment {
+    id: u64,
+    #[serde(default)]
+    body: String,
+}
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+// The page size of listings; the maximum GitHub allows.
+const PAGE_SIZE: usize = 100;
 
 pub struct GitHubHandler {
     client: reqwest::Client,
+    /// The root of the REST API, without trailing slash.
+    api_url: String,
+    token: Option<String>,
 }
 
 impl GitHubHandler {
+    /// A handler for `GITHUB_API_URL` (github.com by default), authenticated
+    /// with `GITHUB_TOKEN` when it is set.
     pub fn new() -> Self {
+        let api_url = std::env::var("GITHUB_API_URL").unwrap_or_else(|_| GITHUB_API_URL.to_string());
+        let token = std::env::var("GITHUB_TOKEN").ok().filter(|token| !token.is_empty());
+        Self::with_endpoint(&api_url, token)
+    }
+
+    /// A handler for the GitHub REST API served at `api_url`.
+    pub fn with_endpoint(api_url: &str, token: Option<String>) -> Self {
         Self {
             client: reqwest::Client::new(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}{}", self.api_url, path);
+        log_to_core(LogLevel::Debug, &format!("Querying GitHub API: {} {}", method, url));
+        let request = self
+            .client
+            .request(method, url)
+            .header("User-Agent", "ph-rust-client")
+            .header("Accept", "application/vnd.github+json");
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
         }
     }
 }
 
+impl Default for GitHubHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Turns an unsuccessful response into an error carrying GitHub's message.
+async fn check_response(response: reqwest::Response, what: &str) -> Result<reqwest::Response, ProviderError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|value| value.get("message")?.as_str().map(str::to_string))
+        .unwrap_or(body);
+    Err(format!("Failed to {} (status: {}): {}", what, status, message).into())
+}
+
 #[async_trait::async_trait]
 impl ApiProvider for GitHubHandler {
     /// Performs the API call to GitHub and maps the response to our generic RepoInfo struct.
     async fn fetch_repo_info(&self, user: &str, repo: &str) -> Result<RepoInfo, Box<dyn std::error::Error>> {
-        let url = format!("{}/{}/{}", GITHUB_API_ENDPOINT, user, repo);
-        log_to_core(LogLevel::Debug, &format!("Querying GitHub API: {}", url));
-
-        let response = self.client
-            .get(&url)
-            .header("User-Agent", "ph-rust-client")
+        let response = self
+            .request(reqwest::Method::GET, &format!("/repos/{}/{}", user, repo))
             .send()
             .await?;
 
@@ -96,6 +207,62 @@ impl ApiProvider for GitHubHandler {
 
         Ok(repo_info)
     }
+
+    async fn set_commit_status(&self, user: &str, repo: &str, sha: &str, status: &CommitStatus) -> Result<(), ProviderError> {
+        let mut payload = serde_json::json!({
+            "state": status.state.as_str(),
+            "context": status.context,
+            // GitHub rejects descriptions longer than 140 characters.
+            "description": status.description.chars().take(140).collect::<String>(),
+        });
+        if let Some(target_url) = &status.target_url {
+            payload["target_url"] = serde_json::Value::from(target_url.as_str());
+        }
+        let response = self
+            .request(reqwest::Method::POST, &format!("/repos/{}/{}/statuses/{}", user, repo, sha))
+            .json(&payload)
+            .send()
+            .await?;
+        check_response(response, "set the commit status").await?;
+        Ok(())
+    }
+
+    async fn upsert_pull_request_comment(
+        &self,
+        user: &str,
+        repo: &str,
+        number: u64,
+        marker: &str,
+        body: &str,
+    ) -> Result<u64, ProviderError> {
+        // Look for the comment through every page of the conversation.
+        let mut existing = None;
+        for page in 1.. {
+            let path = format!("/repos/{}/{}/issues/{}/comments?per_page={}&page={}", user, repo, number, PAGE_SIZE, page);
+            let response = self.request(reqwest::Method::GET, &path).send().await?;
+            let comments: Vec<GitHubComment> = check_response(response, "list the comments").await?.json().await?;
+            let last_page = comments.len() < PAGE_SIZE;
+            existing = comments.into_iter().find(|comment| comment.body.contains(marker));
+            if existing.is_some() || last_page {
+                break;
+            }
+        }
+
+        let payload = serde_json::json!({ "body": body });
+        let response = match existing {
+            Some(comment) if comment.body == body => return Ok(comment.id),
+            Some(comment) => {
+                let path = format!("/repos/{}/{}/issues/comments/{}", user, repo, comment.id);
+                self.request(reqwest::Method::PATCH, &path).json(&payload).send().await?
+            }
+            None => {
+                let path = format!("/repos/{}/{}/issues/{}/comments", user, repo, number);
+                self.request(reqwest::Method::POST, &path).json(&payload).send().await?
+            }
+        };
+        let comment: GitHubComment = check_response(response, "write the comment").await?.json().await?;
+        Ok(comment.id)
+    }
 }
 
 // --- Command Logic ---
@@ -13,7 +13,9 @@
 * SPDX-License-Identifier: Apache-2.0 */
 
 // We declare the github_handler module, making its public items available.
-mod github_handler;
+// It is also the Rust API of the crate, used by the operator to report on
+// pull requests.
+pub mod github_handler;
 
 use github_handler::{set_repository, ApiProvider, GitHubHandler};
 use libc::{c_char, c_int};
@@ -142,6 +144,8 @@ pub extern "C" fn module_get_info() -> *const ModuleInfo {
 }
 
 /// Initializes the module, receiving the context from the core.
+// The pointers come from the C core, which guarantees their validity.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[no_mangle]
 pub extern "C" fn module_init(context: *const CoreContext) -> Status {
     if context.is_null() {
@@ -155,6 +159,7 @@ pub extern "C" fn module_init(context: *const CoreContext) -> Status {
 }
 
 /// Executes a command passed from the core.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[no_mangle]
 pub extern "C" fn module_exec(argc: c_int, argv: *const *const c_char) -> Status {
     let args = unsafe { c_args_to_vec(argc, argv) };
@@ -0,0 +1,203 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* tests/github_reporting.rs - Pull request reporting against a mock GitHub.
+*
+* Runs the GitHub handler against a small in-process server that implements
+* the few endpoints of the GitHub REST API used to report on pull requests,
+* and checks the requests it receives.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use api_client::github_handler::{ApiProvider, CommitState, CommitStatus, GitHubHandler};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request received by the mock server.
+#[derive(Debug, Clone)]
+struct Received {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: Value,
+}
+
+#[derive(Default)]
+struct MockState {
+    requests: Vec<Received>,
+    /// The comments of the pull request, as (id, body).
+    comments: Vec<(u64, String)>,
+    next_id: u64,
+}
+
+/// Starts the mock server and returns its URL.
+async fn start_mock(state: Arc<Mutex<MockState>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(serve_connection(stream, state.clone()));
+        }
+    });
+    url
+}
+
+async fn serve_connection(stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+            return;
+        }
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+
+        let mut content_length = 0;
+        let mut authorization = None;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line.split_once(':').unwrap();
+            match name.to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().unwrap(),
+                "authorization" => authorization = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).await.unwrap();
+        let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+        let received = Received { method, path, authorization, body };
+        let (status, response) = respond(&mut state.lock().unwrap(), &received);
+        state.lock().unwrap().requests.push(received);
+
+        let response = response.to_string();
+        let reply = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            response.len(),
+            response
+        );
+        reader.get_mut().write_all(reply.as_bytes()).await.unwrap();
+    }
+}
+
+/// Serves the statuses and issue comments endpoints of repository acme/shop.
+fn respond(state: &mut MockState, request: &Received) -> (&'static str, Value) {
+    let (path, query) = request.path.split_once('?').unwrap_or((&request.path, ""));
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["repos", "acme", "shop", "statuses", _]) => ("201 Created", json!({ "id": 1 })),
+        ("GET", ["repos", "acme", "shop", "issues", "7", "comments"]) => {
+            let param = |name: &str| {
+                query
+                    .split('&')
+                    .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+                    .and_then(|value| value.parse::<usize>().ok())
+            };
+            let (per_page, page) = (param("per_page").unwrap_or(30), param("page").unwrap_or(1));
+            let page: Vec<Value> = state
+                .comments
+                .iter()
+                .skip(per_page * (page - 1))
+                .take(per_page)
+                .map(|(id, body)| json!({ "id": id, "body": body }))
+                .collect();
+            ("200 OK", Value::from(page))
+        }
+        ("POST", ["repos", "acme", "shop", "issues", "7", "comments"]) => {
+            state.next_id += 1;
+            let body = request.body["body"].as_str().unwrap().to_string();
+            state.comments.push((state.next_id, body.clone()));
+            ("201 Created", json!({ "id": state.next_id, "body": body }))
+        }
+        ("PATCH", ["repos", "acme", "shop", "issues", "comments", id]) => {
+            let id: u64 = id.parse().unwrap();
+            let body = request.body["body"].as_str().unwrap().to_string();
+            match state.comments.iter_mut().find(|(comment_id, _)| *comment_id == id) {
+                Some(comment) => {
+                    comment.1 = body.clone();
+                    ("200 OK", json!({ "id": id, "body": body }))
+                }
+                None => ("404 Not Found", json!({ "message": "Not Found" })),
+            }
+        }
+        _ => ("404 Not Found", json!({ "message": "Not Found" })),
+    }
+}
+
+fn requests(state: &Arc<Mutex<MockState>>) -> Vec<(String, String)> {
+    state.lock().unwrap().requests.iter().map(|r| (r.method.clone(), r.path.clone())).collect()
+}
+
+#[tokio::test]
+async fn test_commit_status() {
+    let state = Arc::new(Mutex::new(MockState::default()));
+    let handler = GitHubHandler::with_endpoint(&start_mock(state.clone()).await, Some("s3cr3t".to_string()));
+
+    let status = CommitStatus {
+        state: CommitState::Success,
+        context: "ph/preview".to_string(),
+        description: "x".repeat(200),
+        target_url: Some("https://shop-pr-7.preview.example.com".to_string()),
+    };
+    handler.set_commit_status("acme", "shop", "abc123", &status).await.unwrap();
+
+    let received = state.lock().unwrap().requests[0].clone();
+    assert_eq!((received.method.as_str(), received.path.as_str()), ("POST", "/repos/acme/shop/statuses/abc123"));
+    assert_eq!(received.authorization.as_deref(), Some("Bearer s3cr3t"));
+    assert_eq!(received.body["state"], "success");
+    assert_eq!(received.body["context"], "ph/preview");
+    assert_eq!(received.body["description"].as_str().unwrap().len(), 140);
+    assert_eq!(received.body["target_url"], "https://shop-pr-7.preview.example.com");
+
+    // Errors carry the message of the server.
+    let error = handler.set_commit_status("acme", "other", "abc123", &status).await.unwrap_err();
+    assert!(error.to_string().contains("Not Found"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_sticky_comment() {
+    let state = Arc::new(Mutex::new(MockState::default()));
+    // Enough unrelated comments to push the sticky comment to a second page.
+    state.lock().unwrap().comments = (1..=120).map(|id| (id, format!("comment {}", id))).collect();
+    state.lock().unwrap().next_id = 120;
+    let handler = GitHubHandler::with_endpoint(&start_mock(state.clone()).await, None);
+    let marker = "<!-- ph-preview:default/shop-pr-7 -->";
+
+    // The first report creates the comment.
+    let id = handler
+        .upsert_pull_request_comment("acme", "shop", 7, marker, &format!("{}\nDeploying", marker))
+        .await
+        .unwrap();
+    assert_eq!(id, 121);
+    assert_eq!(requests(&state).last().unwrap(), &("POST".to_string(), "/repos/acme/shop/issues/7/comments".to_string()));
+    assert!(state.lock().unwrap().requests[0].authorization.is_none());
+
+    // Later reports edit it in place.
+    state.lock().unwrap().requests.clear();
+    let body = format!("{}\nReady", marker);
+    assert_eq!(handler.upsert_pull_request_comment("acme", "shop", 7, marker, &body).await.unwrap(), 121);
+    assert_eq!(
+        requests(&state),
+        vec![
+            ("GET".to_string(), "/repos/acme/shop/issues/7/comments?per_page=100&page=1".to_string()),
+            ("GET".to_string(), "/repos/acme/shop/issues/7/comments?per_page=100&page=2".to_string()),
+            ("PATCH".to_string(), "/repos/acme/shop/issues/comments/121".to_string()),
+        ]
+    );
+    assert_eq!(state.lock().unwrap().comments.len(), 121);
+    assert_eq!(state.lock().unwrap().comments[120].1, body);
+
+    // An unchanged comment is not written again.
+    state.lock().unwrap().requests.clear();
+    assert_eq!(handler.upsert_pull_request_comment("acme", "shop", 7, marker, &body).await.unwrap(), 121);
+    assert!(requests(&state).iter().all(|(method, _)| method == "GET"));
+}
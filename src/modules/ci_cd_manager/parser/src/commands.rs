@@ -0,0 +1,275 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/commands.rs - Implementation of the `ph ci` subcommands.
+*
+* `module_exec` in `lib.rs` routes `ph ci <subcommand>` here. Each handler
+* parses its own flags, reads the workflow with `parse_workflow` and prints
+* its result:
+* - `convert <file>` writes the phPipeline manifest of a workflow as YAML
+*   (or, with `--format json`, the manifest and its warnings as JSON) and
+*   lists everything that was not translated on stderr.
+* - `run <file>` runs a workflow (converted first) or a phPipeline manifest
+*   with the local Docker engine and prints the outcome of every step. It
+*   fails when the pipeline does.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use crate::convert::{self, Options, WarningKind};
+use crate::parse_workflow;
+use crate::pipeline::{self, Manifest};
+use crate::runner::{self, StepPhase};
+use std::fs;
+use std::path::PathBuf;
+
+/// A flexible error type for command logic failures.
+type CommandResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const CI_USAGE: &str = "Usage: ph ci <subcommand> [options]
+
+Subcommands:
+  convert <file>   Convert a GitHub Actions or GitLab CI file into a phPipeline manifest.
+  run <file>       Run a workflow file or a phPipeline manifest with the local Docker engine.";
+
+const CONVERT_USAGE: &str = "Usage: ph ci convert <workflow-file> [options]
+
+Options:
+  --name <name>         Name of the phPipeline (default: the workflow name)
+  --namespace <ns>      Namespace written into the manifest
+  --repo <url>          Clone this repository into the workspace before the first step
+  --image <image>       Image of jobs that name none (default: ubuntu:24.04)
+  --secret <name>       Secret holding the workflow secrets (default: <name>-secrets)
+  --output <file>       Write the manifest to a file instead of stdout
+  --format yaml|json    yaml prints the manifest; json adds the warnings (default: yaml)";
+
+const RUN_USAGE: &str = "Usage: ph ci run <workflow-or-manifest> [options]
+
+Runs every step in a container of the local Docker engine. A GitHub Actions
+or GitLab CI file is converted as by 'ph ci convert' first.
+
+Options:
+  --workspace <dir>     Mount this directory (e.g. the checkout) as /workspace instead of a volume
+  --keep-workspace      Keep the volumes of the run for inspection
+  --set <key>=<value>   Value of a Secret or ConfigMap key (default: the variable <key> of the environment)
+  --repo <url>          Workflows only: clone this repository into the workspace first
+  --image <image>       Workflows only: image of jobs that name none (default: ubuntu:24.04)";
+
+/// Routes `ph ci <subcommand>`.
+pub fn handle_ci(args: &[String]) -> CommandResult<()> {
+    let Some(subcommand) = args.first() else {
+        return Err(format!("missing subcommand\n\n{}", CI_USAGE).into());
+    };
+    match subcommand.as_str() {
+        "convert" => handle_convert(&args[1..]),
+        "run" => handle_run(&args[1..]),
+        "help" | "--help" | "-h" => {
+            println!("{}", CI_USAGE);
+            Ok(())
+        }
+        other => Err(format!("unknown subcommand '{}'\n\n{}", other, CI_USAGE).into()),
+    }
+}
+
+fn handle_convert(args: &[String]) -> CommandResult<()> {
+    let mut options = Options::default();
+    let mut file = None;
+    let mut output = None;
+    let mut json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} requires a value\n\n{}", flag, CONVERT_USAGE))
+        };
+        match arg.as_str() {
+            "--name" => options.name = Some(value(arg)?),
+            "--namespace" => options.namespace = Some(value(arg)?),
+            "--repo" => options.repository = Some(value(arg)?),
+            "--image" => options.default_image = value(arg)?,
+            "--secret" => options.secret_name = Some(value(arg)?),
+            "--output" | "-o" => output = Some(value(arg)?),
+            "--format" => {
+                json = match value(arg)?.as_str() {
+                    "yaml" => false,
+                    "json" => true,
+                    other => {
+                        return Err(
+                            format!("unknown format '{}', expected yaml or json", other).into()
+                        )
+                    }
+                }
+            }
+            "--help" | "-h" => {
+                println!("{}", CONVERT_USAGE);
+                return Ok(());
+            }
+            flag if flag.starts_with('-') => {
+                return Err(format!("unknown option '{}'\n\n{}", flag, CONVERT_USAGE).into())
+            }
+            path if file.is_none() => file = Some(path.to_string()),
+            extra => {
+                return Err(format!("unexpected argument '{}'\n\n{}", extra, CONVERT_USAGE).into())
+            }
+        }
+    }
+    let Some(file) = file else {
+        return Err(format!("missing workflow file\n\n{}", CONVERT_USAGE).into());
+    };
+
+    let source = fs::read_to_string(&file).map_err(|e| format!("cannot read '{}': {}", file, e))?;
+    let workflow = parse_workflow(&file, &source).map_err(|e| e.in_file(&file))?;
+    let conversion = convert::convert(&workflow, &options);
+
+    let text = if json {
+        serde_json::to_string_pretty(&conversion)? + "\n"
+    } else {
+        serde_yaml::to_string(&conversion.manifest)?
+    };
+    match &output {
+        Some(path) => {
+            fs::write(path, text).map_err(|e| format!("cannot write '{}': {}", path, e))?
+        }
+        None => print!("{}", text),
+    }
+
+    for warning in &conversion.warnings {
+        eprintln!("warning{}", warning);
+    }
+    let steps: usize = conversion
+        .manifest
+        .spec
+        .stages
+        .iter()
+        .map(|stage| stage.steps.len())
+        .sum();
+    eprintln!(
+        "Converted '{}' into phPipeline '{}': {} stage(s), {} step(s), {} warning(s).",
+        file,
+        conversion.manifest.metadata.name,
+        conversion.manifest.spec.stages.len(),
+        steps,
+        conversion.warnings.len()
+    );
+    if let Some(path) = output {
+        eprintln!("Manifest written to '{}'.", path);
+    }
+    Ok(())
+}
+
+fn handle_run(args: &[String]) -> CommandResult<()> {
+    let mut options = runner::Options::default();
+    let mut convert_options = Options::default();
+    let mut file = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} requires a value\n\n{}", flag, RUN_USAGE))
+        };
+        match arg.as_str() {
+            "--workspace" => options.workspace = Some(PathBuf::from(value(arg)?)),
+            "--keep-workspace" => options.keep_workspace = true,
+            "--set" => {
+                let setting = value(arg)?;
+                let Some((key, value)) = setting.split_once('=') else {
+                    return Err(format!("--set expects <key>=<value>, got '{}'", setting).into());
+                };
+                options.values.insert(key.to_string(), value.to_string());
+            }
+            "--repo" => convert_options.repository = Some(value(arg)?),
+            "--image" => convert_options.default_image = value(arg)?,
+            "--help" | "-h" => {
+                println!("{}", RUN_USAGE);
+                return Ok(());
+            }
+            flag if flag.starts_with('-') => {
+                return Err(format!("unknown option '{}'\n\n{}", flag, RUN_USAGE).into())
+            }
+            path if file.is_none() => file = Some(path.to_string()),
+            extra => return Err(format!("unexpected argument '{}'\n\n{}", extra, RUN_USAGE).into()),
+        }
+    }
+    let Some(file) = file else {
+        return Err(format!("missing workflow or manifest file\n\n{}", RUN_USAGE).into());
+    };
+
+    let source = fs::read_to_string(&file).map_err(|e| format!("cannot read '{}': {}", file, e))?;
+    let manifest = load_pipeline(&file, &source, &convert_options, &options)?;
+    let report = runner::run(&manifest, &options)?;
+
+    let name_width = report.steps.iter().map(|step| step.name.len()).max();
+    let name_width = name_width.unwrap_or_default().max(4);
+    let stage_width = report.steps.iter().map(|step| step.stage.len()).max();
+    let stage_width = stage_width.unwrap_or_default().max(5);
+    println!(
+        "\nPipeline '{}' {} in {}:",
+        report.pipeline,
+        report.phase,
+        runner::format_duration(report.duration)
+    );
+    println!(
+        "  {:<name_width$}  {:<stage_width$}  {:<9}  {:>8}  {:>8}  MESSAGE",
+        "STEP", "STAGE", "PHASE", "ATTEMPTS", "DURATION"
+    );
+    for step in &report.steps {
+        println!(
+            "  {:<name_width$}  {:<stage_width$}  {:<9}  {:>8}  {:>8}  {}",
+            step.name,
+            step.stage,
+            step.phase.to_string(),
+            step.attempts,
+            runner::format_duration(step.duration),
+            step.message
+        );
+    }
+
+    match (report.phase, report.failed_step) {
+        (StepPhase::Failed, Some(step)) => {
+            Err(format!("pipeline '{}' failed at step '{}'", report.pipeline, step).into())
+        }
+        (StepPhase::Failed, None) => Err(format!("pipeline '{}' failed", report.pipeline).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Reads `source` as a phPipeline manifest when it declares a `kind`, and
+/// converts it as a workflow file otherwise.
+fn load_pipeline(
+    file: &str,
+    source: &str,
+    convert_options: &Options,
+    options: &runner::Options,
+) -> CommandResult<Manifest> {
+    let document: serde_yaml::Value =
+        serde_yaml::from_str(source).map_err(|e| format!("cannot read '{}': {}", file, e))?;
+    match document.get("kind").and_then(serde_yaml::Value::as_str) {
+        Some(pipeline::KIND) => {
+            let manifest: Manifest = serde_yaml::from_value(document)
+                .map_err(|e| format!("'{}' is not a valid phPipeline: {}", file, e))?;
+            return Ok(manifest);
+        }
+        Some(kind) => {
+            return Err(format!(
+                "'{}' is a {}; ph ci run takes a {} manifest or a workflow file",
+                file,
+                kind,
+                pipeline::KIND
+            )
+            .into())
+        }
+        None => {}
+    }
+
+    let workflow = parse_workflow(file, source).map_err(|e| e.in_file(file))?;
+    let conversion = convert::convert(&workflow, convert_options);
+    for warning in &conversion.warnings {
+        // A mounted directory already holds the checkout the jobs expect.
+        let checked_out = options.workspace.is_some()
+            && warning.kind == WarningKind::Checkout
+            && warning.job.is_empty();
+        if !checked_out {
+            eprintln!("warning{}", warning);
+        }
+    }
+    Ok(conversion.manifest)
+}
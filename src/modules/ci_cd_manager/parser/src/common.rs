@@ -0,0 +1,177 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/common.rs - Value shapes shared by the workflow front ends.
+*
+* Both GitHub Actions and GitLab CI accept several spellings for many keys:
+* a single string or a list, a bare scalar where a string is meant
+* (`node-version: 18`), or an expression where a boolean is expected. The
+* helpers in this file normalize those spellings while the YAML is
+* deserialized, so the schema structs stay close to the documented keywords.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+
+/// A scalar written where a string is meant. Numbers and booleans keep the
+/// text YAML gives them, e.g. `18` or `true`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Scalar(pub String);
+
+impl<'de> Deserialize<'de> for Scalar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Bool(bool),
+            Int(i64),
+            Float(f64),
+            String(String),
+            Null(()),
+        }
+        Ok(Scalar(
+            match Repr::deserialize(deserializer)
+                .map_err(|_| D::Error::custom("expected a string, number or boolean"))?
+            {
+                Repr::Bool(value) => value.to_string(),
+                Repr::Int(value) => value.to_string(),
+                Repr::Float(value) => value.to_string(),
+                Repr::String(value) => value,
+                Repr::Null(()) => String::new(),
+            },
+        ))
+    }
+}
+
+/// A key that takes either one string or a list of strings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StringList(pub Vec<String>);
+
+impl<'de> Deserialize<'de> for StringList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            One(Scalar),
+            Many(Vec<Scalar>),
+        }
+        Ok(StringList(
+            match Repr::deserialize(deserializer)
+                .map_err(|_| D::Error::custom("expected a string or a list of strings"))?
+            {
+                Repr::One(Scalar(value)) => vec![value],
+                Repr::Many(values) => values.into_iter().map(|Scalar(value)| value).collect(),
+            },
+        ))
+    }
+}
+
+/// A flag that may also be an expression evaluated at run time, such as
+/// `continue-on-error: ${{ matrix.experimental }}`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flag {
+    Value(bool),
+    Expression(String),
+}
+
+impl Default for Flag {
+    fn default() -> Self {
+        Flag::Value(false)
+    }
+}
+
+impl<'de> Deserialize<'de> for Flag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Value(bool),
+            Expression(String),
+        }
+        Ok(
+            match Repr::deserialize(deserializer)
+                .map_err(|_| D::Error::custom("expected a boolean or an expression"))?
+            {
+                Repr::Value(value) => Flag::Value(value),
+                Repr::Expression(text) => match text.trim() {
+                    "true" => Flag::Value(true),
+                    "false" => Flag::Value(false),
+                    _ => Flag::Expression(text),
+                },
+            },
+        )
+    }
+}
+
+impl Flag {
+    /// The value of the flag, `false` when it is only known at run time.
+    pub fn is_set(&self) -> bool {
+        matches!(self, Flag::Value(true))
+    }
+}
+
+/// A count, such as `timeout-minutes` or `max-parallel`, that may also be an
+/// expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Count {
+    Value(u32),
+    Expression(String),
+}
+
+impl<'de> Deserialize<'de> for Count {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Value(u32),
+            Fraction(f64),
+            Expression(String),
+        }
+        Ok(
+            match Repr::deserialize(deserializer)
+                .map_err(|_| D::Error::custom("expected a number or an expression"))?
+            {
+                Repr::Value(value) => Count::Value(value),
+                Repr::Fraction(value) => Count::Value(value.ceil() as u32),
+                Repr::Expression(text) => match text.trim().parse() {
+                    Ok(value) => Count::Value(value),
+                    Err(_) => Count::Expression(text),
+                },
+            },
+        )
+    }
+}
+
+impl Count {
+    /// The count, 0 when it is only known at run time.
+    pub fn value(&self) -> u32 {
+        match self {
+            Count::Value(value) => *value,
+            Count::Expression(_) => 0,
+        }
+    }
+}
+
+/// A map of names to scalar values, such as `env` or `with`.
+pub type ScalarMap = BTreeMap<String, Scalar>;
+
+/// Flattens a `ScalarMap` into plain strings.
+pub fn strings(map: ScalarMap) -> BTreeMap<String, String> {
+    map.into_iter()
+        .map(|(key, Scalar(value))| (key, value))
+        .collect()
+}
+
+/// The text of a YAML value given where a string is expected. Mappings and
+/// sequences, e.g. object values of a matrix, are kept as JSON.
+pub fn value_text(value: &serde_yaml::Value) -> String {
+    use serde_yaml::Value;
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(value) => value.to_string(),
+        Value::Number(value) => value.to_string(),
+        Value::String(value) => value.clone(),
+        Value::Tagged(tagged) => value_text(&tagged.value),
+        Value::Sequence(_) | Value::Mapping(_) => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
@@ -0,0 +1,1204 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/convert.rs - Converts a parsed workflow into a phPipeline manifest.
+*
+* This is the logic behind `ph ci convert`. It takes the `model::Workflow`
+* produced by either front end and writes the phPipeline the operator would
+* run for it:
+* - Every `run` step (or GitLab script) becomes a pipeline step running the
+*   script with the step's shell, from `/workspace` or its working
+*   directory. All steps share a workspace volume, like the steps of a
+*   hosted job share a checkout.
+* - The image of a step is the job's container, else the image of the last
+*   `actions/setup-*` step (e.g. `node:20`), else the image matching the
+*   `runs-on` label (`ubuntu-22.04` runs in `ubuntu:22.04`). Steps using a
+*   `docker://` action run that image.
+* - The steps of a job run one after another through `needs`; the first one
+*   needs the last step of every job the job needs. Jobs are placed in the
+*   stages of their depth in that graph, named after the GitLab stage when
+*   there is one.
+* - A matrix becomes a step matrix, and `${{ matrix.x }}`, `${{ env.x }}`
+*   and `${{ secrets.x }}` become `$(matrix.x)`, variables and Secret keys.
+*
+* Whatever has no counterpart (triggers, conditions, services, most
+* actions, other expressions, ...) is left out or kept verbatim and
+* reported as a `Warning`, so a migration can be reviewed item by item.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use crate::model::{self, WorkflowSource};
+use crate::pipeline::{
+    self, EnvVar, EnvVarSource, KeySelector, Manifest, Metadata, Spec, Stage, Step,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/// Where every step finds the shared files.
+const WORKSPACE: &str = "/workspace";
+/// The image of the step that clones the repository.
+const GIT_IMAGE: &str = "alpine/git:latest";
+/// The longest step name kept before matrix values are appended, so the
+/// Job names the operator derives stay within 63 characters.
+const MAX_STEP_NAME: usize = 40;
+
+/// Settings of a conversion.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The name of the phPipeline. Defaults to the workflow name.
+    pub name: Option<String>,
+    pub namespace: Option<String>,
+    /// The image of jobs that name none and run on no known runner.
+    pub default_image: String,
+    /// The repository cloned into the workspace before the first step.
+    pub repository: Option<String>,
+    /// The Secret holding the values of `${{ secrets.x }}`. Defaults to
+    /// `<pipeline>-secrets`.
+    pub secret_name: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            name: None,
+            namespace: None,
+            default_image: "ubuntu:24.04".to_string(),
+            repository: None,
+            secret_name: None,
+        }
+    }
+}
+
+/// What a warning is about.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarningKind {
+    Trigger,
+    Include,
+    Checkout,
+    Runner,
+    Action,
+    ReusableWorkflow,
+    Condition,
+    Service,
+    Output,
+    Environment,
+    Matrix,
+    Shell,
+    Expression,
+    Secret,
+}
+
+impl WarningKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WarningKind::Trigger => "trigger",
+            WarningKind::Include => "include",
+            WarningKind::Checkout => "checkout",
+            WarningKind::Runner => "runner",
+            WarningKind::Action => "action",
+            WarningKind::ReusableWorkflow => "reusable-workflow",
+            WarningKind::Condition => "condition",
+            WarningKind::Service => "service",
+            WarningKind::Output => "output",
+            WarningKind::Environment => "environment",
+            WarningKind::Matrix => "matrix",
+            WarningKind::Shell => "shell",
+            WarningKind::Expression => "expression",
+            WarningKind::Secret => "secret",
+        }
+    }
+}
+
+/// Something of the workflow that was not translated, or only partly.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    /// The job it is about; empty for the whole workflow.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub job: String,
+    /// The step it is about, by name; empty for the whole job.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub step: String,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.kind.as_str())?;
+        if !self.job.is_empty() {
+            write!(f, " job '{}'", self.job)?;
+        }
+        if !self.step.is_empty() {
+            write!(f, " step '{}'", self.step)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The manifest, and what could not be translated into it.
+#[derive(Serialize, Debug, Clone)]
+pub struct Conversion {
+    pub manifest: Manifest,
+    pub warnings: Vec<Warning>,
+}
+
+/// Converts `workflow` into a phPipeline.
+pub fn convert(workflow: &model::Workflow, options: &Options) -> Conversion {
+    let name = options
+        .name
+        .clone()
+        .unwrap_or_else(|| match dns_label(&workflow.name, 63) {
+            name if name.is_empty() => "workflow".to_string(),
+            name => name,
+        });
+    let mut converter = Converter {
+        workflow,
+        options,
+        secret_name: options
+            .secret_name
+            .clone()
+            .unwrap_or_else(|| format!("{}-secrets", name)),
+        warnings: Vec::new(),
+        secrets: BTreeSet::new(),
+        checks_out: workflow.source == WorkflowSource::GitlabCi,
+        step_names: BTreeSet::new(),
+    };
+
+    if !workflow.triggers.is_empty() {
+        let events: Vec<&str> = workflow
+            .triggers
+            .iter()
+            .map(|trigger| trigger.event.as_str())
+            .collect();
+        converter.warn(
+            WarningKind::Trigger,
+            "",
+            "",
+            format!(
+                "the triggers ({}) are not converted; start runs with a phPipelineTrigger",
+                events.join(", ")
+            ),
+        );
+    }
+    if !workflow.includes.is_empty() {
+        converter.warn(
+            WarningKind::Include,
+            "",
+            "",
+            format!(
+                "included files are not read, convert them separately: {}",
+                workflow.includes.join(", ")
+            ),
+        );
+    }
+
+    let jobs: Vec<Vec<Step>> = workflow.jobs.iter().map(|job| converter.job(job)).collect();
+    let stages = converter.stages(jobs);
+
+    if !converter.secrets.is_empty() {
+        let keys: Vec<&str> = converter.secrets.iter().map(String::as_str).collect();
+        let message = format!(
+            "create the Secret '{}' with the keys {} before running the pipeline",
+            converter.secret_name,
+            keys.join(", ")
+        );
+        converter.warn(WarningKind::Secret, "", "", message);
+    }
+
+    Conversion {
+        manifest: Manifest {
+            api_version: pipeline::API_VERSION.to_string(),
+            kind: pipeline::KIND.to_string(),
+            metadata: Metadata {
+                name,
+                namespace: options.namespace.clone(),
+                annotations: BTreeMap::from([(
+                    "ph.io/converted-from".to_string(),
+                    source_name(workflow.source),
+                )]),
+            },
+            spec: Spec {
+                stages,
+                workspace: Some(pipeline::Workspace::default()),
+                ..Default::default()
+            },
+        },
+        warnings: converter.warnings,
+    }
+}
+
+fn source_name(source: WorkflowSource) -> String {
+    match source {
+        WorkflowSource::GithubActions => "github-actions",
+        WorkflowSource::GitlabCi => "gitlab-ci",
+        WorkflowSource::WorkflowSourceUnspecified => "unknown",
+    }
+    .to_string()
+}
+
+/// Reduces `text` to a lowercase DNS label of at most `max` characters.
+fn dns_label(text: &str, max: usize) -> String {
+    let mut label = String::with_capacity(text.len());
+    for c in text.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            label.push(c);
+        } else if !label.is_empty() && !label.ends_with('-') {
+            label.push('-');
+        }
+    }
+    label.truncate(max);
+    label.trim_end_matches('-').to_string()
+}
+
+/// Which variables an expression is translated into.
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    /// A shell script: `${NAME}`.
+    Script,
+    /// The value of a container variable: `$(NAME)`.
+    Env,
+}
+
+struct Converter<'a> {
+    workflow: &'a model::Workflow,
+    options: &'a Options,
+    secret_name: String,
+    warnings: Vec<Warning>,
+    /// The Secret keys the steps read.
+    secrets: BTreeSet<String>,
+    /// Whether a job works on a checkout of the repository.
+    checks_out: bool,
+    step_names: BTreeSet<String>,
+}
+
+impl Converter<'_> {
+    fn warn(&mut self, kind: WarningKind, job: &str, step: &str, message: String) {
+        self.warnings.push(Warning {
+            kind,
+            job: job.to_string(),
+            step: step.to_string(),
+            message,
+        });
+    }
+
+    /// Returns the steps of `job`, chained with `needs`.
+    fn job(&mut self, job: &model::Job) -> Vec<Step> {
+        let id = job.name.as_str();
+        if !job.uses.is_empty() {
+            let message = format!(
+                "'{}' runs another pipeline, which is not converted; convert it separately",
+                job.uses
+            );
+            self.warn(WarningKind::ReusableWorkflow, id, "", message);
+        }
+        if !job.condition.is_empty() {
+            let message = format!(
+                "the condition '{}' is not converted; the job always runs",
+                job.condition
+            );
+            self.warn(WarningKind::Condition, id, "", message);
+        }
+        if !job.when.is_empty() {
+            let message = format!(
+                "'when: {}' is not converted; the job runs with the others",
+                job.when
+            );
+            self.warn(WarningKind::Condition, id, "", message);
+        }
+        for service in &job.services {
+            let message = format!(
+                "the service '{}' ({}) is not started; deploy it next to the pipeline",
+                service.name, service.image
+            );
+            self.warn(WarningKind::Service, id, "", message);
+        }
+        if !job.outputs.is_empty() {
+            let names: Vec<&str> = job.outputs.keys().map(String::as_str).collect();
+            let message = format!(
+                "the outputs {} are not converted; declare them as step outputs",
+                names.join(", ")
+            );
+            self.warn(WarningKind::Output, id, "", message);
+        }
+        if !job.environment.is_empty() {
+            let message = format!(
+                "the deployment environment '{}' and its protection rules are not converted",
+                job.environment
+            );
+            self.warn(WarningKind::Environment, id, "", message);
+        }
+
+        let matrix = self.matrix(job);
+        let mut image = self.image(job);
+        let mut env: Vec<(String, String)> = Vec::new();
+        match self.workflow.source {
+            WorkflowSource::GitlabCi => {
+                env.push(("CI".to_string(), "true".to_string()));
+                env.push(("CI_PROJECT_DIR".to_string(), WORKSPACE.to_string()));
+                env.push(("CI_JOB_NAME".to_string(), id.to_string()));
+                // GitLab exposes the matrix values as variables.
+                if let Some(matrix) = &matrix {
+                    let names: BTreeSet<&String> = matrix
+                        .params
+                        .keys()
+                        .chain(matrix.include.iter().flat_map(|values| values.keys()))
+                        .collect();
+                    for name in names {
+                        env.push((name.clone(), format!("$(matrix.{})", name)));
+                    }
+                }
+                if job.parallel > 1 {
+                    env.push(("CI_NODE_TOTAL".to_string(), job.parallel.to_string()));
+                }
+            }
+            _ => {
+                env.push(("CI".to_string(), "true".to_string()));
+                env.push(("GITHUB_WORKSPACE".to_string(), WORKSPACE.to_string()));
+                env.push(("GITHUB_JOB".to_string(), id.to_string()));
+            }
+        }
+        let workflow_env = self.workflow.env.iter().chain(&job.env);
+        let workflow_env: Vec<(String, String)> = workflow_env
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        env.extend(workflow_env);
+
+        // (label, step) for every converted step; named once all are known.
+        let mut steps: Vec<(String, Step)> = Vec::new();
+        // GitLab runs `before_script` and `script` in one shell, so variables
+        // set by the first are seen by the second.
+        let mut job_steps = job.steps.clone();
+        if self.workflow.source == WorkflowSource::GitlabCi
+            && job_steps.len() > 1
+            && job_steps[0].name == "before_script"
+            && job_steps[1].name == "script"
+        {
+            let before = job_steps.remove(0);
+            job_steps[0].run_command =
+                format!("{}\n{}", before.run_command, job_steps[0].run_command);
+        }
+        for (index, source) in job_steps.iter().enumerate() {
+            let label = match (&source.id, &source.name) {
+                (id, _) if !id.is_empty() => id.clone(),
+                (_, name) if !name.is_empty() => name.clone(),
+                _ => (index + 1).to_string(),
+            };
+            if !source.condition.is_empty() {
+                let message = match source.condition.as_str() {
+                    "always()" => "it runs only when the steps before it succeed".to_string(),
+                    condition => format!(
+                        "the condition '{}' is not converted; the step always runs",
+                        condition
+                    ),
+                };
+                self.warn(WarningKind::Condition, id, &source.name, message);
+            }
+            let mut secrets = BTreeSet::new();
+            let (image, command, args) = if !source.uses.is_empty() {
+                match self.action(job, source, &mut image, &mut secrets) {
+                    Some(step) => step,
+                    None => continue,
+                }
+            } else {
+                let command = self.run(job, source, &mut secrets);
+                (image.clone(), command, Vec::new())
+            };
+            let step_env = source
+                .env
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()));
+            let step_env: Vec<(String, String)> = env.iter().cloned().chain(step_env).collect();
+            let mut step = Step {
+                name: String::new(),
+                image,
+                command,
+                args,
+                env: self.env(id, &source.name, step_env, &mut secrets),
+                needs: Vec::new(),
+                timeout: match (source.timeout_minutes, job.timeout_minutes) {
+                    (0, 0) => None,
+                    (0, minutes) | (minutes, _) => Some(format!("{}m", minutes)),
+                },
+                continue_on_error: source.continue_on_error || job.continue_on_error,
+                matrix: matrix.clone(),
+                ..Default::default()
+            };
+            // Secrets read by the script come first, so that other variables
+            // can refer to them.
+            let secret_env = secrets.into_iter().map(|key| EnvVar {
+                name: key.clone(),
+                value: String::new(),
+                value_from: Some(EnvVarSource {
+                    secret_key_ref: Some(KeySelector {
+                        name: self.secret_name.clone(),
+                        key: key.clone(),
+                    }),
+                    ..Default::default()
+                }),
+            });
+            let secret_env: Vec<EnvVar> = secret_env
+                .filter(|secret| step.env.iter().all(|var| var.name != secret.name))
+                .collect();
+            step.env.splice(0..0, secret_env);
+            steps.push((label, step));
+        }
+
+        // A job with a single step gives it its name.
+        let single = steps.len() == 1;
+        let mut named = Vec::with_capacity(steps.len());
+        let mut previous: Option<String> = None;
+        for (label, mut step) in steps {
+            let job_label = dns_label(id, MAX_STEP_NAME);
+            let name = if single {
+                job_label
+            } else {
+                let room = MAX_STEP_NAME.saturating_sub(job_label.len() + 1).max(8);
+                format!("{}-{}", job_label, dns_label(&label, room))
+            };
+            step.name = self.unique_name(name);
+            step.needs = previous.iter().cloned().collect();
+            previous = Some(step.name.clone());
+            named.push(step);
+        }
+        named
+    }
+
+    fn unique_name(&mut self, name: String) -> String {
+        let name = if name.is_empty() {
+            "step".to_string()
+        } else {
+            name
+        };
+        let mut candidate = name.clone();
+        let mut suffix = 2;
+        while !self.step_names.insert(candidate.clone()) {
+            candidate = format!("{}-{}", name, suffix);
+            suffix += 1;
+        }
+        candidate
+    }
+
+    /// The image a job runs in before any setup action.
+    fn image(&mut self, job: &model::Job) -> String {
+        let id = job.name.as_str();
+        if let Some(container) = &job.container {
+            if !container.options.is_empty()
+                || !container.volumes.is_empty()
+                || !container.ports.is_empty()
+            {
+                let message =
+                    "the container's options, volumes and ports are not converted".to_string();
+                self.warn(WarningKind::Runner, id, "", message);
+            }
+            let mut secrets = BTreeSet::new();
+            let image = self.translate(id, "", &container.image, Target::Script, &mut secrets);
+            return image;
+        }
+        let labels: Vec<&str> = if job.runner_labels.is_empty() {
+            job.runs_on
+                .split(',')
+                .map(str::trim)
+                .filter(|label| !label.is_empty())
+                .collect()
+        } else {
+            job.runner_labels.iter().map(String::as_str).collect()
+        };
+        for label in &labels {
+            if *label == "ubuntu-latest" {
+                return "ubuntu:24.04".to_string();
+            }
+            if let Some(version) = label.strip_prefix("ubuntu-") {
+                return format!("ubuntu:{}", version);
+            }
+        }
+        let default_image = self.options.default_image.clone();
+        if job.uses.is_empty() && !job.steps.is_empty() {
+            let message = if labels.is_empty() {
+                format!("the job names no image; it runs in '{}'", default_image)
+            } else {
+                format!(
+                    "the runner '{}' has no matching image; the job runs in '{}'",
+                    labels.join(", "),
+                    default_image
+                )
+            };
+            self.warn(WarningKind::Runner, id, "", message);
+        }
+        default_image
+    }
+
+    /// The matrix of the steps of a job.
+    fn matrix(&mut self, job: &model::Job) -> Option<pipeline::StepMatrix> {
+        let id = job.name.as_str();
+        let Some(matrix) = &job.matrix else {
+            // GitLab's `parallel: N` runs N copies told apart by CI_NODE_INDEX.
+            if job.parallel > 1 {
+                let indexes = (1..=job.parallel).map(|index| index.to_string()).collect();
+                return Some(pipeline::StepMatrix {
+                    params: BTreeMap::from([("CI_NODE_INDEX".to_string(), indexes)]),
+                    ..Default::default()
+                });
+            }
+            return None;
+        };
+        if !matrix.expression.is_empty() {
+            let message = format!(
+                "the matrix is computed by '{}', which is not converted; the job runs once",
+                matrix.expression
+            );
+            self.warn(WarningKind::Matrix, id, "", message);
+            return None;
+        }
+        let params: BTreeMap<String, Vec<String>> = matrix
+            .axes
+            .iter()
+            .map(|(name, axis)| (name.clone(), axis.values.clone()))
+            .collect();
+        let mut include = Vec::new();
+        for combination in &matrix.include {
+            let values = &combination.values;
+            // GitHub adds an `include` entry that matches a combination to
+            // that combination; phPipeline only adds new ones.
+            let matches_axes = !params.is_empty()
+                && params
+                    .iter()
+                    .all(|(name, axis)| values.get(name).is_some_and(|value| axis.contains(value)));
+            let names_all = params.keys().all(|name| values.contains_key(name));
+            if matches_axes || !names_all {
+                let message = format!(
+                    "the include entry {:?} extends existing combinations, which is not converted",
+                    values
+                );
+                self.warn(WarningKind::Matrix, id, "", message);
+                continue;
+            }
+            include.push(values.clone());
+        }
+        let exclude = matrix
+            .exclude
+            .iter()
+            .map(|combination| combination.values.clone())
+            .collect();
+        if params.is_empty() && include.is_empty() {
+            return None;
+        }
+        Some(pipeline::StepMatrix {
+            params,
+            exclude,
+            include,
+        })
+    }
+
+    /// Converts a step using an action. Returns the image, command and
+    /// arguments of the pipeline step, or `None` when the action needs no
+    /// step of its own.
+    fn action(
+        &mut self,
+        job: &model::Job,
+        step: &model::Step,
+        image: &mut String,
+        secrets: &mut BTreeSet<String>,
+    ) -> Option<(String, Vec<String>, Vec<String>)> {
+        let id = job.name.as_str();
+        let uses = step.uses.as_str();
+        if let Some(docker_image) = uses.strip_prefix("docker://") {
+            let command = step
+                .with
+                .get("entrypoint")
+                .map(|entrypoint| {
+                    vec![self.translate(id, &step.name, entrypoint, Target::Script, secrets)]
+                })
+                .unwrap_or_default();
+            let args = step
+                .with
+                .get("args")
+                .map(|args| self.translate(id, &step.name, args, Target::Script, secrets))
+                .map(|args| args.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default();
+            return Some((docker_image.to_string(), command, args));
+        }
+        let (action, _) = uses.split_once('@').unwrap_or((uses, ""));
+        let setup = match action {
+            "actions/checkout" => {
+                self.checks_out = true;
+                if step
+                    .with
+                    .keys()
+                    .any(|input| matches!(input.as_str(), "repository" | "ref" | "path"))
+                {
+                    let message =
+                        "the inputs 'repository', 'ref' and 'path' are not converted".to_string();
+                    self.warn(WarningKind::Checkout, id, &step.name, message);
+                }
+                return None;
+            }
+            "actions/setup-node" => Some(("node", "node-version", "lts")),
+            "actions/setup-python" => Some(("python", "python-version", "3")),
+            "actions/setup-go" => Some(("golang", "go-version", "1")),
+            "actions/setup-java" => Some(("eclipse-temurin", "java-version", "21")),
+            "actions/setup-dotnet" => {
+                Some(("mcr.microsoft.com/dotnet/sdk", "dotnet-version", "8.0"))
+            }
+            _ => None,
+        };
+        if let Some((repository, input, default_tag)) = setup {
+            if job.container.is_some() {
+                let message = format!(
+                    "'{}' is left out; the steps run in the job's container",
+                    uses
+                );
+                self.warn(WarningKind::Action, id, &step.name, message);
+                return None;
+            }
+            let tag = match step.with.get(input).map(String::as_str) {
+                Some(version) if !version.is_empty() && !version.contains("${{") => version
+                    .trim_start_matches('v')
+                    .trim_end_matches(".x")
+                    .to_string(),
+                Some(version) if !version.is_empty() => {
+                    let message = format!(
+                        "the version '{}' is not converted; using '{}'",
+                        version, default_tag
+                    );
+                    self.warn(WarningKind::Action, id, &step.name, message);
+                    default_tag.to_string()
+                }
+                _ => default_tag.to_string(),
+            };
+            // The later steps of the job run in the toolchain's image.
+            *image = format!("{}:{}", repository, tag);
+            return None;
+        }
+        let message = match action {
+            "actions/cache" => {
+                "is left out; give the step that builds the cached files a 'cache'".to_string()
+            }
+            "actions/upload-artifact" | "actions/download-artifact" => {
+                "is left out; the steps share the workspace, so files are passed on without it"
+                    .to_string()
+            }
+            _ => "has no phPipeline counterpart and is left out".to_string(),
+        };
+        self.warn(
+            WarningKind::Action,
+            id,
+            &step.name,
+            format!("'{}' {}", uses, message),
+        );
+        None
+    }
+
+    /// The command of a `run` step.
+    fn run(
+        &mut self,
+        job: &model::Job,
+        step: &model::Step,
+        secrets: &mut BTreeSet<String>,
+    ) -> Vec<String> {
+        let id = job.name.as_str();
+        let script = self.translate(id, &step.name, &step.run_command, Target::Script, secrets);
+        let directory = match step.working_directory.as_str() {
+            "" => WORKSPACE.to_string(),
+            directory if directory.starts_with('/') => directory.to_string(),
+            directory => format!("{}/{}", WORKSPACE, directory.trim_start_matches("./")),
+        };
+        let default_shell = match self.workflow.source {
+            WorkflowSource::GitlabCi => "sh",
+            _ => "bash",
+        };
+        let shell = match step.shell.as_str() {
+            "" => default_shell,
+            shell => shell,
+        };
+        let (command, prologue): (&[&str], String) = match shell {
+            "bash" => (
+                &["bash", "-eo", "pipefail", "-c"],
+                format!("cd \"{}\"\n", directory),
+            ),
+            "sh" => (&["sh", "-e", "-c"], format!("cd \"{}\"\n", directory)),
+            "pwsh" | "powershell" => (
+                &["pwsh", "-Command"],
+                format!("Set-Location \"{}\"\n", directory),
+            ),
+            "python" => (
+                &["python", "-c"],
+                format!("import os\nos.chdir(\"{}\")\n", directory),
+            ),
+            other => {
+                let message = format!(
+                    "the shell '{}' is not converted; the script runs with bash",
+                    other
+                );
+                self.warn(WarningKind::Shell, id, &step.name, message);
+                (
+                    &["bash", "-eo", "pipefail", "-c"],
+                    format!("cd \"{}\"\n", directory),
+                )
+            }
+        };
+        let script = format!("{}{}", prologue, script);
+        let mut command: Vec<String> = command.iter().map(|part| part.to_string()).collect();
+        command.push(script);
+        command
+    }
+
+    /// The variables of a step, with `${{ secrets.x }}` values read from the
+    /// Secret. Later entries override earlier ones of the same name.
+    fn env(
+        &mut self,
+        job: &str,
+        step: &str,
+        variables: Vec<(String, String)>,
+        secrets: &mut BTreeSet<String>,
+    ) -> Vec<EnvVar> {
+        let mut env: Vec<EnvVar> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for (name, value) in variables {
+            let trimmed = value.trim();
+            let secret = trimmed
+                .strip_prefix("${{")
+                .and_then(|rest| rest.strip_suffix("}}"))
+                .and_then(|inner| inner.trim().strip_prefix("secrets."))
+                .filter(|key| !key.contains(char::is_whitespace));
+            let var = match secret {
+                Some(key) => {
+                    self.secrets.insert(key.to_string());
+                    EnvVar {
+                        name: name.clone(),
+                        value: String::new(),
+                        value_from: Some(EnvVarSource {
+                            secret_key_ref: Some(KeySelector {
+                                name: self.secret_name.clone(),
+                                key: key.to_string(),
+                            }),
+                            ..Default::default()
+                        }),
+                    }
+                }
+                None => EnvVar {
+                    name: name.clone(),
+                    value: self.translate(job, step, &value, Target::Env, secrets),
+                    value_from: None,
+                },
+            };
+            match positions.get(&name) {
+                Some(&position) => env[position] = var,
+                None => {
+                    positions.insert(name, env.len());
+                    env.push(var);
+                }
+            }
+        }
+        env
+    }
+
+    /// Replaces the GitHub expressions of `text` that have a counterpart;
+    /// the others are kept and reported.
+    fn translate(
+        &mut self,
+        job: &str,
+        step: &str,
+        text: &str,
+        target: Target,
+        secrets: &mut BTreeSet<String>,
+    ) -> String {
+        let variable = |name: &str| match target {
+            Target::Script => format!("${{{}}}", name),
+            Target::Env => format!("$({})", name),
+        };
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${{") {
+            let Some(length) = rest[start..].find("}}") else {
+                break;
+            };
+            result.push_str(&rest[..start]);
+            let original = &rest[start..start + length + 2];
+            let inner = rest[start + 3..start + length].trim();
+            rest = &rest[start + length + 2..];
+            let simple = |prefix: &str| {
+                inner.strip_prefix(prefix).filter(|name| {
+                    !name.is_empty()
+                        && name
+                            .chars()
+                            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+                })
+            };
+            if let Some(name) = simple("matrix.") {
+                result.push_str(&format!("$(matrix.{})", name));
+            } else if let Some(name) = simple("env.") {
+                result.push_str(&variable(name));
+            } else if let Some(key) = simple("secrets.") {
+                self.secrets.insert(key.to_string());
+                secrets.insert(key.to_string());
+                result.push_str(&variable(key));
+            } else if inner == "github.workspace" {
+                result.push_str(WORKSPACE);
+            } else if inner == "runner.os" {
+                result.push_str("Linux");
+            } else {
+                let message = format!(
+                    "the expression '{}' is not converted and kept as written",
+                    original
+                );
+                self.warn(WarningKind::Expression, job, step, message);
+                result.push_str(original);
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// Chains the jobs with `needs` and places them in stages.
+    fn stages(&mut self, jobs: Vec<Vec<Step>>) -> Vec<Stage> {
+        let workflow = self.workflow;
+        let index: HashMap<&str, usize> = workflow
+            .jobs
+            .iter()
+            .enumerate()
+            .map(|(position, job)| (job.name.as_str(), position))
+            .collect();
+
+        // The jobs with steps each job waits for, looking through jobs that
+        // have none, and the depth of every job in that graph.
+        let mut waits_for: Vec<Option<BTreeSet<usize>>> = vec![None; jobs.len()];
+        let mut depth: Vec<usize> = vec![0; jobs.len()];
+        fn resolve(
+            job: usize,
+            workflow: &model::Workflow,
+            index: &HashMap<&str, usize>,
+            jobs: &[Vec<Step>],
+            waits_for: &mut Vec<Option<BTreeSet<usize>>>,
+            depth: &mut Vec<usize>,
+            visiting: &mut BTreeSet<usize>,
+        ) {
+            if waits_for[job].is_some() || !visiting.insert(job) {
+                return;
+            }
+            let mut set = BTreeSet::new();
+            let mut level = 0;
+            for need in &workflow.jobs[job].needs {
+                let Some(&need) = index.get(need.as_str()) else {
+                    continue;
+                };
+                resolve(need, workflow, index, jobs, waits_for, depth, visiting);
+                let Some(need_waits) = waits_for[need].clone() else {
+                    continue;
+                };
+                if jobs[need].is_empty() {
+                    level = level.max(depth[need]);
+                    set.extend(need_waits);
+                } else {
+                    level = level.max(depth[need] + 1);
+                    set.insert(need);
+                }
+            }
+            visiting.remove(&job);
+            waits_for[job] = Some(set);
+            depth[job] = level;
+        }
+        for job in 0..jobs.len() {
+            resolve(
+                job,
+                workflow,
+                &index,
+                &jobs,
+                &mut waits_for,
+                &mut depth,
+                &mut BTreeSet::new(),
+            );
+        }
+
+        let checkout = self.checkout();
+        let mut levels: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for (job, steps) in jobs.iter().enumerate() {
+            if !steps.is_empty() {
+                levels.entry(depth[job]).or_default().push(job);
+            }
+        }
+        let mut stages = Vec::new();
+        let mut stage_names = BTreeSet::new();
+        let mut jobs = jobs;
+        for (number, members) in levels.values().enumerate() {
+            let gitlab_stages: BTreeSet<&str> = members
+                .iter()
+                .map(|&job| workflow.jobs[job].stage.as_str())
+                .collect();
+            let mut name = match (gitlab_stages.len(), members.len()) {
+                (1, _) if !gitlab_stages.contains("") => gitlab_stages
+                    .into_iter()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+                (_, 1) => workflow.jobs[members[0]].name.clone(),
+                _ => format!("stage-{}", number + 1),
+            };
+            name = dns_label(&name, 63);
+            if !stage_names.insert(name.clone()) {
+                name = format!("{}-{}", name, number + 1);
+                stage_names.insert(name.clone());
+            }
+            let mut steps = Vec::new();
+            for &job in members {
+                let needs: Vec<String> = waits_for[job]
+                    .iter()
+                    .flatten()
+                    .filter_map(|&need| jobs[need].last().map(|step| step.name.clone()))
+                    .collect();
+                let first = &mut jobs[job][0];
+                first.needs = needs;
+                if first.needs.is_empty() {
+                    first
+                        .needs
+                        .extend(checkout.iter().map(|step| step.name.clone()));
+                }
+                steps.extend(jobs[job].iter().cloned());
+            }
+            stages.push(Stage { name, steps });
+        }
+        if let (Some(checkout), Some(first)) = (checkout, stages.first_mut()) {
+            first.steps.insert(0, checkout);
+        }
+        stages
+    }
+
+    /// The step cloning the repository into the workspace, when a job
+    /// expects a checkout.
+    fn checkout(&mut self) -> Option<Step> {
+        if !self.checks_out {
+            return None;
+        }
+        let Some(repository) = self.options.repository.clone() else {
+            let message =
+                "the jobs expect a checkout of the repository; pass --repo to clone it into the \
+                           workspace first"
+                    .to_string();
+            self.warn(WarningKind::Checkout, "", "", message);
+            return None;
+        };
+        // The volume may already hold files (e.g. lost+found), so the
+        // repository is fetched into it rather than cloned.
+        let script =
+            "git init -q . && git remote add origin \"$1\" && git fetch -q --depth 1 origin HEAD \
+                      && git checkout -q FETCH_HEAD";
+        Some(Step {
+            name: self.unique_name("checkout".to_string()),
+            image: GIT_IMAGE.to_string(),
+            command: vec![
+                "sh".to_string(),
+                "-ec".to_string(),
+                format!("cd \"{}\"\n{}", WORKSPACE, script),
+            ],
+            args: vec!["checkout".to_string(), repository],
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn github(source: &str) -> model::Workflow {
+        crate::github::parse(source, "ci").unwrap()
+    }
+
+    fn step<'a>(manifest: &'a Manifest, name: &str) -> &'a Step {
+        manifest
+            .spec
+            .stages
+            .iter()
+            .flat_map(|stage| &stage.steps)
+            .find(|step| step.name == name)
+            .unwrap_or_else(|| panic!("no step '{}'", name))
+    }
+
+    fn kinds(warnings: &[Warning]) -> Vec<WarningKind> {
+        warnings.iter().map(|warning| warning.kind).collect()
+    }
+
+    const WORKFLOW: &str = r#"
+name: CI
+on: [push]
+jobs:
+  build:
+    runs-on: ubuntu-22.04
+    steps:
+      - uses: actions/checkout@v4
+      - uses: actions/setup-node@v4
+        with:
+          node-version: 20.x
+      - name: Install
+        run: npm ci
+      - name: Test
+        run: npm test
+        continue-on-error: true
+        timeout-minutes: 5
+  lint:
+    runs-on: ubuntu-latest
+    steps:
+      - run: npm run lint
+  deploy:
+    needs: [build, lint]
+    runs-on: ubuntu-latest
+    steps:
+      - run: ./deploy.sh
+"#;
+
+    #[test]
+    fn test_step_order() {
+        let options = Options {
+            repository: Some("https://example.com/app.git".to_string()),
+            ..Default::default()
+        };
+        let conversion = convert(&github(WORKFLOW), &options);
+        let manifest = &conversion.manifest;
+        assert_eq!(manifest.metadata.name, "ci");
+        let stages: Vec<(&str, Vec<&str>)> = manifest
+            .spec
+            .stages
+            .iter()
+            .map(|stage| {
+                let steps = stage.steps.iter().map(|step| step.name.as_str()).collect();
+                (stage.name.as_str(), steps)
+            })
+            .collect();
+        assert_eq!(
+            stages,
+            vec![
+                (
+                    "stage-1",
+                    vec!["checkout", "build-install", "build-test", "lint"]
+                ),
+                ("deploy", vec!["deploy"]),
+            ]
+        );
+        // The steps of a job run one after another, after the checkout.
+        assert_eq!(step(manifest, "build-install").needs, vec!["checkout"]);
+        assert_eq!(step(manifest, "build-test").needs, vec!["build-install"]);
+        assert_eq!(step(manifest, "lint").needs, vec!["checkout"]);
+        // A job waits for the last step of every job it needs.
+        assert_eq!(step(manifest, "deploy").needs, vec!["build-test", "lint"]);
+
+        let test = step(manifest, "build-test");
+        assert_eq!(test.image, "node:20");
+        assert_eq!(test.command[..4], ["bash", "-eo", "pipefail", "-c"]);
+        assert_eq!(test.command[4], "cd \"/workspace\"\nnpm test");
+        assert!(test.continue_on_error);
+        assert_eq!(test.timeout.as_deref(), Some("5m"));
+        assert_eq!(step(manifest, "lint").image, "ubuntu:24.04");
+        assert_eq!(kinds(&conversion.warnings), vec![WarningKind::Trigger]);
+    }
+
+    #[test]
+    fn test_checkout_and_conditions() {
+        // Without a repository to clone, the checkout is reported instead.
+        let conversion = convert(&github(WORKFLOW), &Options::default());
+        let manifest = &conversion.manifest;
+        assert_eq!(manifest.spec.stages[0].steps[0].name, "build-install");
+        assert!(step(manifest, "build-install").needs.is_empty());
+        assert_eq!(
+            kinds(&conversion.warnings),
+            vec![WarningKind::Trigger, WarningKind::Checkout]
+        );
+
+        // Conditions on the outcome of earlier steps are not converted: a
+        // failing step stops the job and the steps after it.
+        let conditional = github(
+            r#"
+jobs:
+  build:
+    runs-on: ubuntu-latest
+    steps:
+      - run: make
+      - name: Upload logs
+        if: failure()
+        run: ./upload-logs.sh
+      - name: Clean up
+        if: always()
+        run: make clean
+"#,
+        );
+        let conversion = convert(&conditional, &Options::default());
+        let messages: Vec<String> = conversion
+            .warnings
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "[condition] job 'build' step 'Upload logs': the condition 'failure()' is not \
+                 converted; the step always runs",
+                "[condition] job 'build' step 'Clean up': it runs only when the steps before it \
+                 succeed",
+            ]
+        );
+        assert_eq!(
+            step(&conversion.manifest, "build-clean-up").needs,
+            vec!["build-upload-logs"]
+        );
+    }
+
+    #[test]
+    fn test_env_and_secrets() {
+        let workflow = github(
+            r#"
+name: Release
+env:
+  REGISTRY: ghcr.io
+  IMAGE: app
+jobs:
+  publish:
+    runs-on: ubuntu-latest
+    env:
+      IMAGE: service
+      TAG: ${{ env.REGISTRY }}/${{ env.IMAGE }}
+      TOKEN: ${{ secrets.REGISTRY_TOKEN }}
+    steps:
+      - run: docker login -p "${{ secrets.PASSWORD }}" && echo ${{ github.sha }}
+        env:
+          TAG: latest
+"#,
+        );
+        let conversion = convert(&workflow, &Options::default());
+        let publish = &conversion.manifest.spec.stages[0].steps[0];
+        let env: Vec<(&str, &str)> = publish
+            .env
+            .iter()
+            .map(|var| (var.name.as_str(), var.value.as_str()))
+            .collect();
+        assert_eq!(
+            env,
+            vec![
+                ("PASSWORD", ""),
+                ("CI", "true"),
+                ("GITHUB_WORKSPACE", "/workspace"),
+                ("GITHUB_JOB", "publish"),
+                ("IMAGE", "service"),
+                ("REGISTRY", "ghcr.io"),
+                ("TAG", "latest"),
+                ("TOKEN", ""),
+            ]
+        );
+        let secret = |name: &str| {
+            let var = publish.env.iter().find(|var| var.name == name).unwrap();
+            var.value_from.clone().unwrap().secret_key_ref.unwrap()
+        };
+        assert_eq!(secret("PASSWORD").name, "release-secrets");
+        assert_eq!(secret("TOKEN").key, "REGISTRY_TOKEN");
+        assert_eq!(
+            publish.command[4],
+            "cd \"/workspace\"\ndocker login -p \"${PASSWORD}\" && echo ${{ github.sha }}"
+        );
+        assert_eq!(
+            kinds(&conversion.warnings),
+            vec![WarningKind::Expression, WarningKind::Secret]
+        );
+        assert_eq!(
+            conversion.warnings[1].message,
+            "create the Secret 'release-secrets' with the keys PASSWORD, REGISTRY_TOKEN before \
+             running the pipeline"
+        );
+    }
+}
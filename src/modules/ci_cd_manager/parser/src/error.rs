@@ -0,0 +1,270 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/error.rs - Parse errors with their position in the workflow file.
+*
+* Every error the parser reports names the line and column it refers to, so
+* an editor or a terminal can point at the offending key. Syntax and type
+* errors come with the position `serde_yaml` found them at. Errors that are
+* only detected after deserializing (an unknown job in `needs`, a step with
+* neither `run` nor `uses`, ...) are placed with `locate`, which finds a key
+* path such as `jobs.build.steps[2]` in the source text.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use serde::Serialize;
+use std::fmt;
+
+/// Mirrors the `ParseError` message: a problem at a 1-based line and column.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// An error at a known position.
+    pub fn at(line: usize, column: usize, message: impl Into<String>) -> Self {
+        ParseError {
+            file: String::new(),
+            line: line as u32,
+            column: column as u32,
+            message: message.into(),
+        }
+    }
+
+    /// An error about the key at `path`, placed with `locate`.
+    pub fn at_path(source: &str, path: &[Key<'_>], message: impl Into<String>) -> Self {
+        let (line, column) = locate(source, path);
+        ParseError::at(line, column, message)
+    }
+
+    /// Converts a `serde_yaml` error. Without a position, the error is put at
+    /// the key at `fallback`.
+    pub fn from_yaml(source: &str, fallback: &[Key<'_>], error: serde_yaml::Error) -> Self {
+        // serde_yaml prefixes the position to the message; keep only the reason.
+        let message = error.to_string();
+        let message = match message.find(" at line ") {
+            Some(index) => message[..index].to_string(),
+            None => message,
+        };
+        match error.location() {
+            Some(location) => ParseError::at(location.line(), location.column(), message),
+            None => ParseError::at_path(source, fallback, message),
+        }
+    }
+
+    /// Sets the file the error is reported for.
+    pub fn in_file(mut self, file: &str) -> Self {
+        self.file = file.to_string();
+        self
+    }
+}
+
+/// One step of a path through the YAML document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key<'a> {
+    /// A key of a mapping.
+    Name(&'a str),
+    /// An item of a sequence, counted from 0.
+    Index(usize),
+}
+
+/// Finds the line and column of the key at `path` in block-style YAML. When
+/// the path cannot be followed to the end, the deepest key found is
+/// returned, or the start of the file.
+pub fn locate(source: &str, path: &[Key<'_>]) -> (usize, usize) {
+    let mut found = (1, 1);
+    if path.is_empty() {
+        return found;
+    }
+    let mut depth = 0;
+    // The column of the last key found; its block ends at a line that is not
+    // indented more.
+    let mut parent: Option<usize> = None;
+    // The column of the children of the last key found, set by the first one.
+    let mut children: Option<usize> = None;
+    let mut items_seen = 0;
+
+    for (number, line) in source.lines().enumerate() {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - content.len();
+        let is_item = content == "-" || content.starts_with("- ");
+        // Sequence items may sit at the indentation of their key.
+        let item_wanted = is_item && matches!(path[depth], Key::Index(_));
+        let ended =
+            parent.is_some_and(|parent| indent < parent || (indent == parent && !item_wanted));
+        if ended {
+            break;
+        }
+        if *children.get_or_insert(indent) != indent {
+            continue;
+        }
+        let (mut column, mut rest) = (indent, content);
+        if let Key::Index(index) = path[depth] {
+            if !is_item {
+                continue;
+            }
+            if items_seen < index {
+                items_seen += 1;
+                continue;
+            }
+            found = (number + 1, indent + 1);
+            depth += 1;
+            items_seen = 0;
+            // The first key of the item may follow the dash: "- name: x".
+            rest = rest[1..].trim_start();
+            column = indent + (content.len() - rest.len());
+            parent = Some(indent);
+            children = (!rest.is_empty()).then_some(column);
+            if depth == path.len() {
+                break;
+            }
+            if rest.is_empty() {
+                continue;
+            }
+        }
+        if let Key::Name(name) = path[depth] {
+            if key_of(rest) == Some(name) {
+                found = (number + 1, column + 1);
+                depth += 1;
+                parent = Some(column);
+                children = None;
+                if depth == path.len() {
+                    break;
+                }
+            }
+        }
+    }
+    found
+}
+
+/// The key a line defines, e.g. `build` for `build:` or `"on": push`.
+fn key_of(line: &str) -> Option<&str> {
+    let (key, rest) = if let Some(quoted) = line.strip_prefix('"') {
+        let end = quoted.find('"')?;
+        (&quoted[..end], &quoted[end + 1..])
+    } else if let Some(quoted) = line.strip_prefix('\'') {
+        let end = quoted.find('\'')?;
+        (&quoted[..end], &quoted[end + 1..])
+    } else {
+        let end = line.find(':')?;
+        (line[..end].trim_end(), &line[end..])
+    };
+    let rest = rest.trim_start();
+    (rest.starts_with(':') && (rest.len() == 1 || rest[1..].starts_with(char::is_whitespace)))
+        .then_some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKFLOW: &str = "\
+name: CI
+# The jobs.
+jobs:
+  build:
+    runs-on: ubuntu-latest
+    steps:
+      - name: Checkout
+        uses: actions/checkout@v4
+      - run: make
+  \"deploy\":
+    needs: build
+";
+
+    #[test]
+    fn test_locate() {
+        let at = |path: &[Key<'_>]| locate(WORKFLOW, path);
+        assert_eq!(at(&[Key::Name("jobs")]), (3, 1));
+        assert_eq!(at(&[Key::Name("jobs"), Key::Name("build")]), (4, 3));
+        let steps = [Key::Name("jobs"), Key::Name("build"), Key::Name("steps")];
+        assert_eq!(at(&[&steps[..], &[Key::Index(0)]].concat()), (7, 7));
+        assert_eq!(at(&[&steps[..], &[Key::Index(1)]].concat()), (9, 7));
+        assert_eq!(
+            at(&[&steps[..], &[Key::Index(0), Key::Name("uses")]].concat()),
+            (8, 9)
+        );
+        assert_eq!(
+            at(&[Key::Name("jobs"), Key::Name("deploy"), Key::Name("needs")]),
+            (11, 5)
+        );
+        // A path that cannot be followed ends at the deepest key found.
+        assert_eq!(at(&[Key::Name("jobs"), Key::Name("test")]), (3, 1));
+        assert_eq!(at(&[&steps[..], &[Key::Index(5)]].concat()), (6, 5));
+        assert_eq!(at(&[Key::Name("on")]), (1, 1));
+    }
+
+    #[test]
+    fn test_malformed_yaml() {
+        let error =
+            crate::parse_workflow("ci.yml", "jobs:\n  build:\n    steps: [run\n").unwrap_err();
+        assert_eq!((error.line, error.column), (4, 1));
+        assert!(
+            error
+                .message
+                .starts_with("did not find expected ',' or ']'"),
+            "{}",
+            error.message
+        );
+        assert!(!error.message.contains(" at line "), "{}", error.message);
+
+        // Type errors keep the position serde_yaml found them at.
+        let source = "jobs:\n  build:\n    runs-on: ubuntu-latest\n    steps: make\n";
+        let error = crate::parse_workflow("ci.yml", source)
+            .unwrap_err()
+            .in_file("ci.yml");
+        assert_eq!((error.line, error.column), (4, 12));
+        assert_eq!(
+            error.to_string(),
+            "ci.yml:4:12: jobs.build.steps: invalid type: string \"make\", expected a sequence"
+        );
+    }
+
+    #[test]
+    fn test_semantic_errors() {
+        let error = ParseError::at_path(
+            WORKFLOW,
+            &[Key::Name("jobs"), Key::Name("deploy"), Key::Name("needs")],
+            "job 'deploy' needs unknown job 'build'",
+        )
+        .in_file(".github/workflows/ci.yml");
+        assert_eq!(
+            error,
+            ParseError {
+                file: ".github/workflows/ci.yml".to_string(),
+                line: 11,
+                column: 5,
+                message: "job 'deploy' needs unknown job 'build'".to_string(),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            ".github/workflows/ci.yml:11:5: job 'deploy' needs unknown job 'build'"
+        );
+
+        let source = "jobs:\n  build:\n    runs-on: ubuntu-latest\n    steps:\n      - run: make\n      - name: Empty\n";
+        let error = crate::parse_workflow("ci.yml", source).unwrap_err();
+        assert_eq!((error.line, error.column), (6, 7));
+        assert_eq!(
+            error.message,
+            "step 2 of job 'build' must have exactly one of 'run' and 'uses'"
+        );
+    }
+}
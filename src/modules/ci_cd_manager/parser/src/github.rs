@@ -0,0 +1,747 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/github.rs - GitHub Actions front end of the workflow parser.
+*
+* The structs below model the GitHub Actions workflow syntax: triggers
+* (`on`), workflow and job `env`, `defaults`, `permissions`, `concurrency`,
+* and for every job `needs`, `if`, `runs-on`, `strategy.matrix`,
+* `container`, `services`, `outputs`, `environment`, reusable workflows
+* (`uses`/`with`/`secrets`) and steps with `run` or `uses`. They are
+* deserialized straight from the source text, so type errors keep their
+* line and column.
+*
+* `convert` then checks what serde cannot (every job has steps or calls a
+* workflow, every step has exactly one of `run` and `uses`, `needs` names
+* existing jobs) and maps the result into the `model` structs mirroring
+* `rpc_data.proto`. Default shells and working directories are applied to
+* the steps; `permissions` and `concurrency` are accepted but have no
+* counterpart in the model. A key the syntax does not define, such as a
+* misspelt `run-on`, is an error rather than silently dropped.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use crate::common::{strings, value_text, Count, Flag, Scalar, ScalarMap, StringList};
+use crate::error::{Key, ParseError};
+use crate::model;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+/// A GitHub Actions workflow file.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Workflow {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub run_name: Option<String>,
+    #[serde(default, rename = "on")]
+    pub on: Option<On>,
+    #[serde(default)]
+    pub permissions: Option<Permissions>,
+    #[serde(default)]
+    pub env: ScalarMap,
+    #[serde(default)]
+    pub defaults: Option<Defaults>,
+    #[serde(default)]
+    pub concurrency: Option<Concurrency>,
+    pub jobs: IndexMap<String, Job>,
+}
+
+/// The events that start a workflow.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum On {
+    Event(String),
+    Events(Vec<String>),
+    Configured(IndexMap<String, Option<EventConfig>>),
+}
+
+/// The settings of one event.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum EventConfig {
+    /// `schedule` takes a list of cron entries.
+    Schedule(Vec<Cron>),
+    Filters(Box<EventFilters>),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Cron {
+    pub cron: String,
+}
+
+/// The filters of an event, and the inputs of `workflow_dispatch` and
+/// `workflow_call`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct EventFilters {
+    #[serde(default)]
+    pub branches: StringList,
+    #[serde(default)]
+    pub branches_ignore: StringList,
+    #[serde(default)]
+    pub tags: StringList,
+    #[serde(default)]
+    pub tags_ignore: StringList,
+    #[serde(default)]
+    pub paths: StringList,
+    #[serde(default)]
+    pub paths_ignore: StringList,
+    #[serde(default)]
+    pub types: StringList,
+    /// The workflows whose runs trigger `workflow_run`.
+    #[serde(default)]
+    pub workflows: StringList,
+    #[serde(default)]
+    pub inputs: IndexMap<String, Value>,
+    #[serde(default)]
+    pub outputs: IndexMap<String, Value>,
+    #[serde(default)]
+    pub secrets: IndexMap<String, Value>,
+}
+
+/// `permissions: read-all` or a map of scopes to access levels.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Permissions {
+    All(String),
+    Scopes(IndexMap<String, String>),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Concurrency {
+    Group(String),
+    Configured {
+        group: String,
+        #[serde(default, rename = "cancel-in-progress")]
+        cancel_in_progress: Flag,
+    },
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    #[serde(default)]
+    pub run: RunDefaults,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RunDefaults {
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub working_directory: Option<String>,
+}
+
+/// A job of the workflow.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Job {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub needs: StringList,
+    #[serde(default)]
+    pub runs_on: Option<RunsOn>,
+    #[serde(default)]
+    pub permissions: Option<Permissions>,
+    #[serde(default)]
+    pub environment: Option<Environment>,
+    #[serde(default)]
+    pub concurrency: Option<Concurrency>,
+    #[serde(default)]
+    pub outputs: ScalarMap,
+    #[serde(default)]
+    pub env: ScalarMap,
+    #[serde(default)]
+    pub defaults: Option<Defaults>,
+    #[serde(default, rename = "if")]
+    pub condition: Option<Scalar>,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+    #[serde(default)]
+    pub timeout_minutes: Option<Count>,
+    #[serde(default)]
+    pub strategy: Option<Strategy>,
+    #[serde(default)]
+    pub continue_on_error: Flag,
+    #[serde(default)]
+    pub container: Option<ContainerSpec>,
+    #[serde(default)]
+    pub services: IndexMap<String, ContainerSpec>,
+    /// A reusable workflow called instead of running steps.
+    #[serde(default)]
+    pub uses: Option<String>,
+    #[serde(default)]
+    pub with: ScalarMap,
+    #[serde(default)]
+    pub secrets: Option<Secrets>,
+}
+
+/// A runner label, a list of labels, or a runner group.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum RunsOn {
+    Labels(StringList),
+    Group {
+        #[serde(default)]
+        group: Option<String>,
+        #[serde(default)]
+        labels: StringList,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Environment {
+    Name(String),
+    Configured {
+        name: String,
+        #[serde(default)]
+        url: Option<String>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Strategy {
+    #[serde(default)]
+    pub matrix: Option<MatrixSpec>,
+    #[serde(default)]
+    pub fail_fast: Option<Flag>,
+    #[serde(default)]
+    pub max_parallel: Option<Count>,
+}
+
+/// A matrix, or an expression that computes one.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum MatrixSpec {
+    Expression(String),
+    Axes(IndexMap<String, Value>),
+}
+
+/// A container image, or its full settings.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ContainerSpec {
+    Image(String),
+    Configured(ContainerOptions),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ContainerOptions {
+    pub image: String,
+    #[serde(default)]
+    pub credentials: Option<Value>,
+    #[serde(default)]
+    pub env: ScalarMap,
+    #[serde(default)]
+    pub ports: Vec<Scalar>,
+    #[serde(default)]
+    pub volumes: Vec<String>,
+    #[serde(default)]
+    pub options: Option<String>,
+}
+
+/// `secrets: inherit` or the secrets passed to a reusable workflow.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Secrets {
+    Inherit(String),
+    Passed(ScalarMap),
+}
+
+/// A step of a job.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Step {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default, rename = "if")]
+    pub condition: Option<Scalar>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub uses: Option<String>,
+    #[serde(default)]
+    pub run: Option<String>,
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub with: ScalarMap,
+    #[serde(default)]
+    pub env: ScalarMap,
+    #[serde(default)]
+    pub continue_on_error: Flag,
+    #[serde(default)]
+    pub timeout_minutes: Option<Count>,
+    #[serde(default)]
+    pub working_directory: Option<String>,
+}
+
+/// Parses a GitHub Actions workflow. `default_name` names a workflow
+/// without `name`, as GitHub uses the file path.
+pub fn parse(source: &str, default_name: &str) -> Result<model::Workflow, ParseError> {
+    let workflow: Workflow =
+        serde_yaml::from_str(source).map_err(|e| ParseError::from_yaml(source, &[], e))?;
+    convert(source, workflow, default_name)
+}
+
+/// Checks a deserialized workflow and maps it into the model.
+pub fn convert(
+    source: &str,
+    workflow: Workflow,
+    default_name: &str,
+) -> Result<model::Workflow, ParseError> {
+    let workflow_defaults = workflow
+        .defaults
+        .map(|defaults| defaults.run)
+        .unwrap_or_default();
+    let mut jobs = Vec::with_capacity(workflow.jobs.len());
+    for (id, job) in &workflow.jobs {
+        let at = |path: &[Key<'_>], message: String| {
+            let mut full = vec![Key::Name("jobs"), Key::Name(id)];
+            full.extend_from_slice(path);
+            ParseError::at_path(source, &full, message)
+        };
+        match (job.steps.is_empty(), &job.uses) {
+            (true, None) => {
+                return Err(at(
+                    &[],
+                    format!("job '{}' needs either 'steps' or 'uses'", id),
+                ))
+            }
+            (false, Some(_)) => {
+                return Err(at(
+                    &[Key::Name("uses")],
+                    format!("job '{}' cannot have both 'steps' and 'uses'", id),
+                ))
+            }
+            _ => {}
+        }
+        if job.runs_on.is_none() && job.uses.is_none() {
+            return Err(at(&[], format!("job '{}' is missing 'runs-on'", id)));
+        }
+        if let Some(need) = job
+            .needs
+            .0
+            .iter()
+            .find(|need| !workflow.jobs.contains_key(need.as_str()))
+        {
+            return Err(at(
+                &[Key::Name("needs")],
+                format!("job '{}' needs unknown job '{}'", id, need),
+            ));
+        }
+        let job_defaults = job.defaults.as_ref().map(|defaults| &defaults.run);
+        let shell = job_defaults
+            .and_then(|run| run.shell.clone())
+            .or_else(|| workflow_defaults.shell.clone());
+        let working_directory = job_defaults
+            .and_then(|run| run.working_directory.clone())
+            .or_else(|| workflow_defaults.working_directory.clone());
+
+        let mut steps = Vec::with_capacity(job.steps.len());
+        for (index, step) in job.steps.iter().enumerate() {
+            let (run, uses) = match (&step.run, &step.uses) {
+                (Some(run), None) => (run.clone(), String::new()),
+                (None, Some(uses)) => (String::new(), uses.clone()),
+                _ => {
+                    return Err(at(
+                        &[Key::Name("steps"), Key::Index(index)],
+                        format!(
+                            "step {} of job '{}' must have exactly one of 'run' and 'uses'",
+                            index + 1,
+                            id
+                        ),
+                    ))
+                }
+            };
+            let runs_command = !run.is_empty();
+            steps.push(model::Step {
+                name: step
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| default_step_name(&run, &uses)),
+                run_command: run,
+                uses,
+                with: strings(step.with.clone()),
+                env: strings(step.env.clone()),
+                condition: step
+                    .condition
+                    .clone()
+                    .map(|Scalar(text)| text)
+                    .unwrap_or_default(),
+                id: step.id.clone().unwrap_or_default(),
+                shell: step
+                    .shell
+                    .clone()
+                    .or_else(|| shell.clone().filter(|_| runs_command))
+                    .unwrap_or_default(),
+                working_directory: step
+                    .working_directory
+                    .clone()
+                    .or_else(|| working_directory.clone().filter(|_| runs_command))
+                    .unwrap_or_default(),
+                continue_on_error: step.continue_on_error.is_set(),
+                timeout_minutes: step.timeout_minutes.as_ref().map(Count::value).unwrap_or(0),
+            });
+        }
+
+        let labels = match &job.runs_on {
+            Some(RunsOn::Labels(labels)) => labels.0.clone(),
+            Some(RunsOn::Group { group, labels }) => {
+                group.iter().chain(&labels.0).cloned().collect()
+            }
+            None => Vec::new(),
+        };
+        let strategy = job.strategy.as_ref();
+        jobs.push(model::Job {
+            name: id.clone(),
+            runs_on: labels.join(", "),
+            steps,
+            needs: job.needs.0.clone(),
+            env: strings(job.env.clone()),
+            condition: job
+                .condition
+                .clone()
+                .map(|Scalar(text)| text)
+                .unwrap_or_default(),
+            matrix: strategy.and_then(|strategy| {
+                let spec = strategy.matrix.as_ref()?;
+                let mut matrix = matrix(spec);
+                // Combinations are cancelled on a failure unless disabled.
+                matrix.fail_fast = strategy.fail_fast.as_ref().is_none_or(Flag::is_set);
+                matrix.max_parallel = strategy
+                    .max_parallel
+                    .as_ref()
+                    .map(Count::value)
+                    .unwrap_or(0);
+                Some(matrix)
+            }),
+            container: job
+                .container
+                .as_ref()
+                .map(|spec| container(String::new(), spec)),
+            services: job
+                .services
+                .iter()
+                .map(|(name, spec)| container(name.clone(), spec))
+                .collect(),
+            outputs: strings(job.outputs.clone()),
+            timeout_minutes: job.timeout_minutes.as_ref().map(Count::value).unwrap_or(0),
+            continue_on_error: job.continue_on_error.is_set(),
+            environment: match &job.environment {
+                Some(Environment::Name(name)) | Some(Environment::Configured { name, .. }) => {
+                    name.clone()
+                }
+                None => String::new(),
+            },
+            uses: job.uses.clone().unwrap_or_default(),
+            with: strings(job.with.clone()),
+            runner_labels: labels,
+            display_name: job.name.clone().unwrap_or_default(),
+            ..Default::default()
+        });
+    }
+
+    Ok(model::Workflow {
+        name: workflow.name.unwrap_or_else(|| default_name.to_string()),
+        jobs,
+        source: model::WorkflowSource::GithubActions,
+        env: strings(workflow.env),
+        triggers: workflow.on.map(triggers).unwrap_or_default(),
+        ..Default::default()
+    })
+}
+
+/// The name GitHub shows for a step without one.
+fn default_step_name(run: &str, uses: &str) -> String {
+    if uses.is_empty() {
+        format!("Run {}", run.lines().next().unwrap_or_default().trim())
+    } else {
+        format!("Run {}", uses)
+    }
+}
+
+fn triggers(on: On) -> Vec<model::Trigger> {
+    let event = |name: String| model::Trigger {
+        event: name,
+        ..Default::default()
+    };
+    match on {
+        On::Event(name) => vec![event(name)],
+        On::Events(names) => names.into_iter().map(event).collect(),
+        On::Configured(events) => events
+            .into_iter()
+            .map(|(name, config)| match config {
+                None => event(name),
+                Some(EventConfig::Schedule(crons)) => model::Trigger {
+                    schedules: crons.into_iter().map(|entry| entry.cron).collect(),
+                    ..event(name)
+                },
+                Some(EventConfig::Filters(filters)) => {
+                    let filters = *filters;
+                    model::Trigger {
+                        branches: filters.branches.0,
+                        branches_ignore: filters.branches_ignore.0,
+                        tags: filters.tags.0,
+                        tags_ignore: filters.tags_ignore.0,
+                        paths: filters.paths.0,
+                        paths_ignore: filters.paths_ignore.0,
+                        types: filters.types.0,
+                        ..event(name)
+                    }
+                }
+            })
+            .collect(),
+    }
+}
+
+fn matrix(spec: &MatrixSpec) -> model::Matrix {
+    let axes = match spec {
+        MatrixSpec::Expression(expression) => {
+            return model::Matrix {
+                expression: expression.clone(),
+                ..Default::default()
+            }
+        }
+        MatrixSpec::Axes(axes) => axes,
+    };
+    let combinations = |value: &Value| -> Vec<model::MatrixCombination> {
+        value
+            .as_sequence()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_mapping)
+            .map(|entry| model::MatrixCombination {
+                values: entry
+                    .iter()
+                    .map(|(key, value)| (value_text(key), value_text(value)))
+                    .collect(),
+            })
+            .collect()
+    };
+    let mut matrix = model::Matrix::default();
+    for (name, value) in axes {
+        match name.as_str() {
+            "include" => matrix.include = combinations(value),
+            "exclude" => matrix.exclude = combinations(value),
+            _ => {
+                // An axis is a list of values, or an expression producing one.
+                let values = match value.as_sequence() {
+                    Some(values) => values.iter().map(value_text).collect(),
+                    None => vec![value_text(value)],
+                };
+                matrix
+                    .axes
+                    .insert(name.clone(), model::MatrixAxis { values });
+            }
+        }
+    }
+    matrix
+}
+
+fn container(name: String, spec: &ContainerSpec) -> model::Container {
+    match spec {
+        ContainerSpec::Image(image) => model::Container {
+            name,
+            image: image.clone(),
+            ..Default::default()
+        },
+        ContainerSpec::Configured(options) => model::Container {
+            name,
+            image: options.image.clone(),
+            env: strings(options.env.clone()),
+            ports: options
+                .ports
+                .iter()
+                .map(|Scalar(port)| port.clone())
+                .collect(),
+            volumes: options.volumes.clone(),
+            options: options.options.clone().unwrap_or_default(),
+            ..Default::default()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    const BUILD: &str = include_str!("../tests/fixtures/github/build.yml");
+
+    fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_jobs_and_needs() {
+        let workflow = parse(BUILD, "build").unwrap();
+        assert_eq!(workflow.name, "Build");
+        assert_eq!(workflow.source, model::WorkflowSource::GithubActions);
+        assert_eq!(workflow.env, map(&[("GO_VERSION", "1.22")]));
+        let jobs: Vec<(&str, &[String])> = workflow
+            .jobs
+            .iter()
+            .map(|job| (job.name.as_str(), job.needs.as_slice()))
+            .collect();
+        assert_eq!(
+            jobs,
+            vec![
+                ("lint", &[][..]),
+                ("test", &["lint".to_string()][..]),
+                ("release", &["lint".to_string(), "test".to_string()][..]),
+            ]
+        );
+
+        let lint = &workflow.jobs[0];
+        assert_eq!(lint.runs_on, "ubuntu-latest");
+        // The run defaults apply to `run` steps only.
+        assert_eq!(lint.steps[0].name, "Run actions/checkout@v4");
+        assert_eq!(lint.steps[0].shell, "");
+        assert_eq!(lint.steps[1].name, "Run make lint");
+        assert_eq!(lint.steps[1].shell, "bash");
+        assert_eq!(lint.steps[1].working_directory, "src");
+
+        let test = &workflow.jobs[1];
+        assert_eq!(test.condition, "github.event_name == 'push'");
+        assert_eq!(test.runs_on, "self-hosted, linux");
+        assert_eq!(test.steps[0].env, map(&[("GOFLAGS", "-race")]));
+        assert_eq!(test.steps[0].timeout_minutes, 10);
+
+        let release = &workflow.jobs[2];
+        assert_eq!(release.uses, "./.github/workflows/release.yml");
+        assert_eq!(release.with, map(&[("version", "${{ github.ref_name }}")]));
+        assert!(release.steps.is_empty());
+    }
+
+    #[test]
+    fn test_matrix() {
+        let workflow = parse(BUILD, "build").unwrap();
+        let matrix = workflow.jobs[1].matrix.as_ref().unwrap();
+        let axes: Vec<(&str, &[String])> = matrix
+            .axes
+            .iter()
+            .map(|(name, axis)| (name.as_str(), axis.values.as_slice()))
+            .collect();
+        assert_eq!(
+            axes,
+            vec![
+                ("go", &["1.21".to_string(), "1.22".to_string()][..]),
+                (
+                    "os",
+                    &["ubuntu-22.04".to_string(), "ubuntu-24.04".to_string()][..]
+                ),
+            ]
+        );
+        assert_eq!(
+            matrix.exclude[0].values,
+            map(&[("go", "1.21"), ("os", "ubuntu-22.04")])
+        );
+        assert_eq!(
+            matrix.include[0].values,
+            map(&[
+                ("experimental", "true"),
+                ("go", "1.23"),
+                ("os", "ubuntu-24.04")
+            ])
+        );
+        assert!(!matrix.fail_fast);
+        assert_eq!(matrix.max_parallel, 2);
+
+        let computed = parse(
+            "jobs:\n  test:\n    runs-on: ubuntu-latest\n    strategy:\n      matrix: ${{ fromJSON(needs.setup.outputs.matrix) }}\n    steps:\n      - run: make\n",
+            "ci",
+        )
+        .unwrap();
+        let matrix = computed.jobs[0].matrix.as_ref().unwrap();
+        assert_eq!(
+            matrix.expression,
+            "${{ fromJSON(needs.setup.outputs.matrix) }}"
+        );
+        assert!(matrix.fail_fast);
+    }
+
+    #[test]
+    fn test_triggers() {
+        let workflow = parse(BUILD, "build").unwrap();
+        let events: Vec<&str> = workflow
+            .triggers
+            .iter()
+            .map(|trigger| trigger.event.as_str())
+            .collect();
+        assert_eq!(events, vec!["push", "pull_request", "schedule"]);
+        assert_eq!(workflow.triggers[0].branches, vec!["main", "release/*"]);
+        assert_eq!(workflow.triggers[0].paths_ignore, vec!["docs/**"]);
+        assert_eq!(workflow.triggers[1].types, vec!["opened", "synchronize"]);
+        assert_eq!(workflow.triggers[2].schedules, vec!["0 3 * * 1"]);
+    }
+
+    #[test]
+    fn test_unsupported_keys() {
+        // A misspelt job key.
+        let source = "jobs:\n  build:\n    run-on: ubuntu-latest\n    steps:\n      - run: make\n";
+        let error = parse(source, "ci").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 5));
+        assert!(
+            error
+                .message
+                .starts_with("jobs.build: unknown field `run-on`"),
+            "{}",
+            error.message
+        );
+
+        // An unknown step key.
+        let source = "jobs:\n  build:\n    runs-on: ubuntu-latest\n    steps:\n      - run: make\n        retries: 2\n";
+        let error = parse(source, "ci").unwrap_err();
+        assert_eq!((error.line, error.column), (6, 9));
+        assert!(
+            error.message.contains("unknown field `retries`"),
+            "{}",
+            error.message
+        );
+
+        // An unknown top-level key.
+        let source = "name: CI\nworkflow: ci\njobs: {}\n";
+        let error = parse(source, "ci").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 1));
+        assert!(
+            error.message.starts_with("unknown field `workflow`"),
+            "{}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn test_invalid_jobs() {
+        let error = |source: &str| {
+            let error = parse(source, "ci").unwrap_err();
+            (error.line, error.column, error.message)
+        };
+        assert_eq!(
+            error("jobs:\n  deploy:\n    needs: build\n    runs-on: ubuntu-latest\n    steps:\n      - run: make\n"),
+            (3, 5, "job 'deploy' needs unknown job 'build'".to_string())
+        );
+        assert_eq!(
+            error("jobs:\n  build:\n    runs-on: ubuntu-latest\n    steps:\n      - name: Both\n        run: make\n        uses: actions/checkout@v4\n"),
+            (
+                5,
+                7,
+                "step 1 of job 'build' must have exactly one of 'run' and 'uses'".to_string()
+            )
+        );
+        assert_eq!(
+            error("jobs:\n  build:\n    steps:\n      - run: make\n"),
+            (2, 3, "job 'build' is missing 'runs-on'".to_string())
+        );
+    }
+}
@@ -0,0 +1,1028 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/gitlab.rs - GitLab CI front end of the workflow parser.
+*
+* A `.gitlab-ci.yml` is read in three passes, the way GitLab evaluates it:
+* 1. The YAML is loaded as a whole, with anchors and `<<` merge keys
+*    resolved. For a CI/CD component, the `spec:` header document is skipped.
+* 2. Every visible job (a top-level mapping that is not a keyword and whose
+*    name does not start with '.') gets the keys of the jobs it `extends`,
+*    merged deeply, and the `default:` keys it does not set (unless
+*    `inherit: default` says otherwise).
+* 3. The merged job is deserialized into `Job` and mapped into the model
+*    structs mirroring `rpc_data.proto`. `before_script`, `script` and
+*    `after_script` become steps; `rules` and `only`/`except` become the job
+*    condition; a job without `needs` needs every job of the earlier stages.
+*
+* Files pulled in with `include` are not read; they are listed in the
+* workflow so callers can report them. A job or rule key GitLab does not
+* define is an error, so a typo does not silently change the pipeline.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use crate::common::{value_text, Scalar, StringList};
+use crate::error::{Key, ParseError};
+use crate::model;
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Top-level keys that are not jobs.
+const KEYWORDS: &[&str] = &[
+    "default",
+    "include",
+    "stages",
+    "variables",
+    "workflow",
+    "image",
+    "services",
+    "cache",
+    "before_script",
+    "after_script",
+    "spec",
+];
+/// The stages of a pipeline that does not declare `stages`.
+const DEFAULT_STAGES: &[&str] = &[".pre", "build", "test", "deploy", ".post"];
+/// The deepest chain of `extends` GitLab accepts.
+const MAX_EXTENDS_DEPTH: usize = 11;
+/// The keys `default:` can set for every job.
+const DEFAULT_KEYS: &[&str] = &[
+    "after_script",
+    "artifacts",
+    "before_script",
+    "cache",
+    "hooks",
+    "id_tokens",
+    "image",
+    "interruptible",
+    "retry",
+    "services",
+    "tags",
+    "timeout",
+];
+
+/// A job after `extends` and `default:` were applied.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    #[serde(default)]
+    pub stage: Option<String>,
+    #[serde(default)]
+    pub image: Option<Image>,
+    #[serde(default)]
+    pub services: Vec<Service>,
+    #[serde(default)]
+    pub before_script: Option<Script>,
+    #[serde(default)]
+    pub script: Option<Script>,
+    #[serde(default)]
+    pub after_script: Option<Script>,
+    #[serde(default)]
+    pub variables: BTreeMap<String, Variable>,
+    #[serde(default)]
+    pub needs: Option<Vec<Need>>,
+    #[serde(default)]
+    pub dependencies: Option<Vec<String>>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    #[serde(default)]
+    pub only: Option<Refs>,
+    #[serde(default)]
+    pub except: Option<Refs>,
+    #[serde(default)]
+    pub when: Option<String>,
+    #[serde(default)]
+    pub allow_failure: Option<AllowFailure>,
+    #[serde(default)]
+    pub timeout: Option<String>,
+    #[serde(default)]
+    pub parallel: Option<Parallel>,
+    #[serde(default)]
+    pub tags: StringList,
+    #[serde(default)]
+    pub environment: Option<Environment>,
+    #[serde(default)]
+    pub trigger: Option<Trigger>,
+    #[serde(default)]
+    pub artifacts: Option<Value>,
+    #[serde(default)]
+    pub cache: Option<Value>,
+    #[serde(default)]
+    pub retry: Option<Value>,
+    #[serde(default)]
+    pub interruptible: Option<bool>,
+    #[serde(default)]
+    pub resource_group: Option<String>,
+    #[serde(default)]
+    pub coverage: Option<String>,
+    #[serde(default)]
+    pub start_in: Option<String>,
+    // The keys below are accepted but have no counterpart in the model.
+    #[serde(default)]
+    pub inherit: Option<Value>,
+    #[serde(default)]
+    pub hooks: Option<Value>,
+    #[serde(default)]
+    pub id_tokens: Option<Value>,
+    #[serde(default)]
+    pub secrets: Option<Value>,
+    #[serde(default)]
+    pub release: Option<Value>,
+    #[serde(default)]
+    pub pages: Option<Value>,
+    #[serde(default)]
+    pub identity: Option<Value>,
+    #[serde(default)]
+    pub manual_confirmation: Option<String>,
+    #[serde(default)]
+    pub dast_configuration: Option<Value>,
+}
+
+/// `image: ruby:3.3` or `image: { name, entrypoint }`.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Image {
+    Name(String),
+    Configured {
+        name: String,
+        #[serde(default)]
+        entrypoint: StringList,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Service {
+    Name(String),
+    Configured {
+        name: String,
+        #[serde(default)]
+        alias: Option<String>,
+        #[serde(default)]
+        entrypoint: StringList,
+        #[serde(default)]
+        command: StringList,
+        #[serde(default)]
+        variables: BTreeMap<String, Variable>,
+    },
+}
+
+/// A script line, or a list of lines that may nest one level.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Script {
+    Line(Scalar),
+    Lines(Vec<Script>),
+}
+
+impl Script {
+    fn lines(&self) -> Vec<String> {
+        match self {
+            Script::Line(Scalar(line)) => vec![line.clone()],
+            Script::Lines(lines) => lines.iter().flat_map(Script::lines).collect(),
+        }
+    }
+}
+
+/// A variable value, or its full settings.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Variable {
+    Value(Scalar),
+    Configured {
+        #[serde(default)]
+        value: Scalar,
+        #[serde(default)]
+        description: Option<String>,
+    },
+}
+
+impl Variable {
+    fn value(&self) -> String {
+        match self {
+            Variable::Value(Scalar(value))
+            | Variable::Configured {
+                value: Scalar(value),
+                ..
+            } => value.clone(),
+        }
+    }
+}
+
+/// A job name, or a need with options.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Need {
+    Job(String),
+    Configured {
+        #[serde(default)]
+        job: Option<String>,
+        #[serde(default)]
+        optional: bool,
+        /// Set for needs on other projects or pipelines.
+        #[serde(default)]
+        project: Option<String>,
+        #[serde(default)]
+        pipeline: Option<String>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    #[serde(default, rename = "if")]
+    pub condition: Option<String>,
+    #[serde(default)]
+    pub when: Option<String>,
+    #[serde(default)]
+    pub changes: Option<Value>,
+    #[serde(default)]
+    pub exists: Option<Value>,
+    #[serde(default)]
+    pub allow_failure: Option<bool>,
+    #[serde(default)]
+    pub variables: BTreeMap<String, Scalar>,
+    #[serde(default)]
+    pub needs: Option<Value>,
+    #[serde(default)]
+    pub interruptible: Option<bool>,
+}
+
+/// `only`/`except`: a list of refs, or `{ refs, variables, changes }`.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Refs {
+    Refs(StringList),
+    Configured {
+        #[serde(default)]
+        refs: StringList,
+        #[serde(default)]
+        variables: Vec<String>,
+        #[serde(default)]
+        changes: Option<Value>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum AllowFailure {
+    Flag(bool),
+    ExitCodes { exit_codes: Value },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Parallel {
+    Count(u32),
+    Matrix {
+        matrix: Vec<BTreeMap<String, StringList>>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Environment {
+    Name(String),
+    Configured { name: String },
+}
+
+/// A downstream pipeline: a project path, or `{ project | include, ... }`.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Trigger {
+    Project(String),
+    Configured {
+        #[serde(default)]
+        project: Option<String>,
+        #[serde(default)]
+        include: Option<Value>,
+    },
+}
+
+/// Parses a GitLab CI file. `default_name` names the workflow unless it sets
+/// `workflow: name`.
+pub fn parse(source: &str, default_name: &str) -> Result<model::Workflow, ParseError> {
+    let mut document = Value::Null;
+    // A CI/CD component starts with a `spec:` header document.
+    for next in serde_yaml::Deserializer::from_str(source) {
+        document = Value::deserialize(next).map_err(|e| ParseError::from_yaml(source, &[], e))?;
+    }
+    document
+        .apply_merge()
+        .map_err(|e| ParseError::from_yaml(source, &[], e))?;
+    let top = match document {
+        Value::Mapping(top) => top,
+        Value::Null => Mapping::new(),
+        _ => {
+            return Err(ParseError::at(
+                1,
+                1,
+                "a GitLab CI file must be a mapping of keywords and jobs",
+            ))
+        }
+    };
+    let field = |key: &str| top.get(key).cloned().unwrap_or(Value::Null);
+    let typed = |key: &'static str| {
+        move |e: serde_yaml::Error| ParseError::from_yaml(source, &[Key::Name(key)], e)
+    };
+
+    let stages: Vec<String> = match field("stages") {
+        Value::Null => DEFAULT_STAGES
+            .iter()
+            .map(|stage| stage.to_string())
+            .collect(),
+        stages => {
+            // `.pre` and `.post` always exist, first and last.
+            let declared: Vec<String> = serde_yaml::from_value(stages).map_err(typed("stages"))?;
+            let mut stages = vec![".pre".to_string()];
+            stages.extend(
+                declared
+                    .into_iter()
+                    .filter(|stage| stage != ".pre" && stage != ".post"),
+            );
+            stages.push(".post".to_string());
+            stages
+        }
+    };
+    let variables: BTreeMap<String, Variable> =
+        serde_yaml::from_value(field("variables")).map_err(typed("variables"))?;
+    let name = field("workflow")
+        .get("name")
+        .map(value_text)
+        .unwrap_or_else(|| default_name.to_string());
+
+    // The keys every job starts from: `default:`, then the deprecated globals.
+    let mut defaults = match field("default") {
+        Value::Mapping(defaults) => defaults,
+        _ => Mapping::new(),
+    };
+    for key in [
+        "image",
+        "services",
+        "cache",
+        "before_script",
+        "after_script",
+    ] {
+        if let Some(value) = top.get(key) {
+            defaults
+                .entry(Value::from(key))
+                .or_insert_with(|| value.clone());
+        }
+    }
+    defaults.retain(|key, _| key.as_str().is_some_and(|key| DEFAULT_KEYS.contains(&key)));
+
+    let templates: HashMap<&str, &Mapping> = top
+        .iter()
+        .filter_map(|(key, value)| Some((key.as_str()?, value.as_mapping()?)))
+        .collect();
+    let mut jobs: Vec<(String, Job)> = Vec::new();
+    for (key, value) in &top {
+        let Some(name) = key.as_str() else { continue };
+        if KEYWORDS.contains(&name) || name.starts_with('.') || !value.is_mapping() {
+            continue;
+        }
+        let mut job = extend(source, name, &templates, 0)?;
+        inherit_defaults(&mut job, &defaults);
+        let job: Job = serde_yaml::from_value(Value::Mapping(job.clone()))
+            .map_err(|e| job_error(source, name, &job, e))?;
+        if job.script.is_none() && job.trigger.is_none() {
+            return Err(ParseError::at_path(
+                source,
+                &[Key::Name(name)],
+                format!("job '{}' needs a 'script' or a 'trigger'", name),
+            ));
+        }
+        let stage = job.stage.as_deref().unwrap_or("test");
+        if !stages.iter().any(|known| known == stage) {
+            return Err(ParseError::at_path(
+                source,
+                &[Key::Name(name), Key::Name("stage")],
+                format!(
+                    "job '{}' uses stage '{}', which is not in 'stages'",
+                    name, stage
+                ),
+            ));
+        }
+        jobs.push((name.to_string(), job));
+    }
+    if jobs.is_empty() {
+        return Err(ParseError::at(1, 1, "the file defines no visible job"));
+    }
+
+    let stage_of: HashMap<&str, usize> = jobs
+        .iter()
+        .map(|(name, job)| {
+            let stage = job.stage.as_deref().unwrap_or("test");
+            (
+                name.as_str(),
+                stages.iter().position(|known| known == stage).unwrap_or(0),
+            )
+        })
+        .collect();
+    let mut converted = Vec::with_capacity(jobs.len());
+    for (name, job) in &jobs {
+        let needs = match &job.needs {
+            // Without `needs`, a job waits for all jobs of the earlier stages.
+            None => jobs
+                .iter()
+                .filter(|(other, _)| stage_of[other.as_str()] < stage_of[name.as_str()])
+                .map(|(other, _)| other.clone())
+                .collect(),
+            Some(needs) => {
+                let mut names = Vec::with_capacity(needs.len());
+                for need in needs {
+                    let (job_name, optional) = match need {
+                        Need::Job(job_name) => (job_name.as_str(), false),
+                        // Needs on other projects or pipelines are not jobs of this file.
+                        Need::Configured {
+                            project: Some(_), ..
+                        }
+                        | Need::Configured {
+                            pipeline: Some(_), ..
+                        } => continue,
+                        Need::Configured {
+                            job: Some(job_name),
+                            optional,
+                            ..
+                        } => (job_name.as_str(), *optional),
+                        Need::Configured { job: None, .. } => continue,
+                    };
+                    if stage_of.contains_key(job_name) {
+                        names.push(job_name.to_string());
+                    } else if !optional {
+                        return Err(ParseError::at_path(
+                            source,
+                            &[Key::Name(name), Key::Name("needs")],
+                            format!("job '{}' needs unknown job '{}'", name, job_name),
+                        ));
+                    }
+                }
+                names
+            }
+        };
+        converted.push(convert_job(name, job, needs));
+    }
+
+    Ok(model::Workflow {
+        name,
+        jobs: converted,
+        source: model::WorkflowSource::GitlabCi,
+        env: variables
+            .iter()
+            .map(|(key, value)| (key.clone(), value.value()))
+            .collect(),
+        stages,
+        includes: includes(&field("include")),
+        ..Default::default()
+    })
+}
+
+/// Places an error about the merged job `name`. Values carry no position,
+/// so an unknown key is looked up in the source, else the job is pointed at.
+fn job_error(source: &str, name: &str, job: &Mapping, error: serde_yaml::Error) -> ParseError {
+    let message = error.to_string();
+    let unknown = message
+        .strip_prefix("unknown field `")
+        .and_then(|rest| rest.split('`').next())
+        .filter(|key| job.contains_key(*key))
+        .map(str::to_string);
+    match unknown {
+        Some(key) => ParseError::at_path(source, &[Key::Name(name), Key::Name(&key)], message),
+        None => ParseError::from_yaml(source, &[Key::Name(name)], error),
+    }
+}
+
+/// Returns the keys of job `name` merged over the jobs it extends.
+fn extend(
+    source: &str,
+    name: &str,
+    templates: &HashMap<&str, &Mapping>,
+    depth: usize,
+) -> Result<Mapping, ParseError> {
+    let error = |message: String| {
+        ParseError::at_path(source, &[Key::Name(name), Key::Name("extends")], message)
+    };
+    if depth > MAX_EXTENDS_DEPTH {
+        return Err(error(format!(
+            "'extends' of job '{}' is nested more than {} levels deep, or forms a cycle",
+            name, MAX_EXTENDS_DEPTH
+        )));
+    }
+    let own = templates[name];
+    let parents: StringList = match own.get("extends") {
+        Some(extends) => {
+            serde_yaml::from_value(extends.clone()).map_err(|e| error(e.to_string()))?
+        }
+        None => return Ok(own.clone()),
+    };
+    let mut merged = Mapping::new();
+    for parent in &parents.0 {
+        if !templates.contains_key(parent.as_str()) {
+            return Err(error(format!(
+                "job '{}' extends unknown job '{}'",
+                name, parent
+            )));
+        }
+        let base = extend(source, parent, templates, depth + 1)?;
+        merge(&mut merged, base);
+    }
+    merge(&mut merged, own.clone());
+    merged.remove("extends");
+    Ok(merged)
+}
+
+/// Merges `overlay` into `base`: mappings are merged key by key, any other
+/// value replaces the one in `base`.
+fn merge(base: &mut Mapping, overlay: Mapping) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Mapping(existing)), Value::Mapping(value)) => merge(existing, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Adds the `default:` keys that `job` neither sets nor opts out of.
+fn inherit_defaults(job: &mut Mapping, defaults: &Mapping) {
+    let inherited = job
+        .get("inherit")
+        .and_then(|inherit| inherit.get("default"))
+        .cloned();
+    for (key, value) in defaults {
+        let wanted = match &inherited {
+            Some(Value::Bool(inherit)) => *inherit,
+            Some(Value::Sequence(keys)) => keys.contains(key),
+            _ => true,
+        };
+        if wanted && !job.contains_key(key) {
+            job.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+/// The files listed in `include`.
+fn includes(include: &Value) -> Vec<String> {
+    let entries = match include {
+        Value::Sequence(entries) => entries.iter().collect(),
+        Value::Null => Vec::new(),
+        entry => vec![entry],
+    };
+    entries
+        .into_iter()
+        .flat_map(|entry| match entry {
+            Value::Mapping(entry) => ["local", "file", "remote", "template", "component"]
+                .iter()
+                .filter_map(|key| entry.get(*key))
+                .flat_map(|value| match value {
+                    Value::Sequence(files) => files.iter().map(value_text).collect(),
+                    value => vec![value_text(value)],
+                })
+                .map(|file| match entry.get("project") {
+                    Some(project) => format!("{}:{}", value_text(project), file),
+                    None => file,
+                })
+                .collect(),
+            entry => vec![value_text(entry)],
+        })
+        .collect()
+}
+
+fn convert_job(name: &str, job: &Job, needs: Vec<String>) -> model::Job {
+    let script_step = |step: &str, script: &Option<Script>, condition: &str| {
+        let lines = script.as_ref().map(Script::lines).unwrap_or_default();
+        (!lines.is_empty()).then(|| model::Step {
+            name: step.to_string(),
+            run_command: lines.join("\n"),
+            condition: condition.to_string(),
+            ..Default::default()
+        })
+    };
+    // `after_script` runs even when the script failed.
+    let steps = [
+        script_step("before_script", &job.before_script, ""),
+        script_step("script", &job.script, ""),
+        script_step("after_script", &job.after_script, "always()"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    // A rule may make the job manual or delayed; the model keeps one `when`
+    // for the job, so the first such rule sets it.
+    let when = job
+        .when
+        .clone()
+        .or_else(|| {
+            job.rules
+                .iter()
+                .filter_map(|rule| rule.when.clone())
+                .find(|when| when == "manual" || when == "delayed")
+        })
+        .filter(|when| when != "on_success")
+        .unwrap_or_default();
+    let rule_allows_failure = job
+        .rules
+        .iter()
+        .any(|rule| rule.allow_failure == Some(true));
+    let continue_on_error = match &job.allow_failure {
+        Some(AllowFailure::Flag(allowed)) => *allowed || rule_allows_failure,
+        Some(AllowFailure::ExitCodes { .. }) => true,
+        None if rule_allows_failure => true,
+        // Manual jobs may fail unless they say otherwise.
+        None => when == "manual",
+    };
+    let (parallel, matrix) = match &job.parallel {
+        Some(Parallel::Count(count)) => (*count, None),
+        Some(Parallel::Matrix { matrix }) => (0, Some(parallel_matrix(matrix))),
+        None => (0, None),
+    };
+    model::Job {
+        name: name.to_string(),
+        runs_on: job.tags.0.join(", "),
+        steps,
+        needs,
+        env: job
+            .variables
+            .iter()
+            .map(|(key, value)| (key.clone(), value.value()))
+            .collect(),
+        condition: condition(job),
+        matrix,
+        container: job.image.as_ref().map(|image| match image {
+            Image::Name(image) => model::Container {
+                image: image.clone(),
+                ..Default::default()
+            },
+            Image::Configured { name, entrypoint } => model::Container {
+                image: name.clone(),
+                entrypoint: entrypoint.0.clone(),
+                ..Default::default()
+            },
+        }),
+        services: job.services.iter().map(service).collect(),
+        timeout_minutes: job.timeout.as_deref().map(timeout_minutes).unwrap_or(0),
+        continue_on_error,
+        environment: match &job.environment {
+            Some(Environment::Name(name)) | Some(Environment::Configured { name }) => name.clone(),
+            None => String::new(),
+        },
+        uses: match &job.trigger {
+            Some(Trigger::Project(project))
+            | Some(Trigger::Configured {
+                project: Some(project),
+                ..
+            }) => project.clone(),
+            Some(Trigger::Configured {
+                include: Some(include),
+                ..
+            }) => includes(include).join(", "),
+            _ => String::new(),
+        },
+        stage: job.stage.clone().unwrap_or_else(|| "test".to_string()),
+        parallel,
+        runner_labels: job.tags.0.clone(),
+        when,
+        ..Default::default()
+    }
+}
+
+fn service(service: &Service) -> model::Container {
+    // GitLab reaches a service by its alias, or by the image name without
+    // registry and tag.
+    let derived_name = |image: &str| {
+        let image = image.rsplit('/').next().unwrap_or(image);
+        image.split([':', '@']).next().unwrap_or(image).to_string()
+    };
+    match service {
+        Service::Name(image) => model::Container {
+            name: derived_name(image),
+            image: image.clone(),
+            ..Default::default()
+        },
+        Service::Configured {
+            name,
+            alias,
+            entrypoint,
+            command,
+            variables,
+        } => model::Container {
+            name: alias.clone().unwrap_or_else(|| derived_name(name)),
+            image: name.clone(),
+            env: variables
+                .iter()
+                .map(|(key, value)| (key.clone(), value.value()))
+                .collect(),
+            entrypoint: entrypoint.0.clone(),
+            command: command.0.clone(),
+            ..Default::default()
+        },
+    }
+}
+
+/// Expands `parallel: matrix:`: every entry is the cross product of its
+/// values, and the job runs for all of them.
+fn parallel_matrix(entries: &[BTreeMap<String, StringList>]) -> model::Matrix {
+    let mut include = Vec::new();
+    for entry in entries {
+        let mut product = vec![BTreeMap::new()];
+        for (name, values) in entry {
+            product = product
+                .into_iter()
+                .flat_map(|combination: BTreeMap<String, String>| {
+                    values.0.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.insert(name.clone(), value.clone());
+                        combination
+                    })
+                })
+                .collect();
+        }
+        include.extend(
+            product
+                .into_iter()
+                .map(|values| model::MatrixCombination { values }),
+        );
+    }
+    model::Matrix {
+        include,
+        ..Default::default()
+    }
+}
+
+/// The condition of a job, from its `rules` or its `only`/`except`, as a
+/// GitLab expression. Empty when the job always runs.
+fn condition(job: &Job) -> String {
+    if !job.rules.is_empty() {
+        // The first matching rule decides; a rule without `if` always matches.
+        let mut expression = "false".to_string();
+        for rule in job.rules.iter().rev() {
+            let runs = rule.when.as_deref() != Some("never");
+            expression = match (&rule.condition, runs) {
+                (None, runs) => runs.to_string(),
+                (Some(_), true) if expression == "true" => expression,
+                (Some(test), true) if expression == "false" => format!("({})", test),
+                (Some(test), true) => format!("({}) || ({})", test, expression),
+                (Some(_), false) if expression == "false" => expression,
+                (Some(test), false) if expression == "true" => format!("!({})", test),
+                (Some(test), false) => format!("!({}) && ({})", test, expression),
+            };
+        }
+        return if expression == "true" {
+            String::new()
+        } else {
+            expression
+        };
+    }
+    let refs = |refs: &Option<Refs>| -> Vec<String> {
+        let (refs, variables) = match refs {
+            Some(Refs::Refs(refs)) => (refs.0.as_slice(), &[][..]),
+            Some(Refs::Configured {
+                refs, variables, ..
+            }) => (refs.0.as_slice(), variables.as_slice()),
+            None => (&[][..], &[][..]),
+        };
+        refs.iter()
+            .map(|name| ref_test(name))
+            .chain(variables.iter().cloned())
+            .collect()
+    };
+    let only = refs(&job.only);
+    let except = refs(&job.except);
+    let mut parts = Vec::new();
+    if !only.is_empty() {
+        parts.push(format!("({})", only.join(" || ")));
+    }
+    if !except.is_empty() {
+        parts.push(format!("!({})", except.join(" || ")));
+    }
+    parts.join(" && ")
+}
+
+/// The expression for one `only`/`except` ref.
+fn ref_test(name: &str) -> String {
+    let source = match name {
+        "branches" => return "$CI_COMMIT_BRANCH".to_string(),
+        "tags" => return "$CI_COMMIT_TAG".to_string(),
+        "merge_requests" => "merge_request_event",
+        "schedules" => "schedule",
+        "api" | "chat" | "external" | "pipelines" | "pushes" | "triggers" | "web" => {
+            return format!("$CI_PIPELINE_SOURCE == \"{}\"", name.trim_end_matches('s'))
+        }
+        _ if name.len() > 1 && name.starts_with('/') && name.ends_with('/') => {
+            return format!("$CI_COMMIT_REF_NAME =~ {}", name)
+        }
+        _ => return format!("$CI_COMMIT_REF_NAME == \"{}\"", name),
+    };
+    format!("$CI_PIPELINE_SOURCE == \"{}\"", source)
+}
+
+/// Parses a GitLab duration such as "1h 30m", "90 minutes" or "2 hours" into
+/// whole minutes, rounded up.
+fn timeout_minutes(timeout: &str) -> u32 {
+    let mut seconds = 0u64;
+    let mut number = String::new();
+    let mut unit = String::new();
+    let mut add = |number: &mut String, unit: &mut String| {
+        if let Ok(value) = number.parse::<u64>() {
+            let scale = match unit.chars().next() {
+                Some('d') => 86_400,
+                Some('h') => 3_600,
+                Some('s') => 1,
+                // Minutes are the default unit.
+                _ => 60,
+            };
+            seconds += value * scale;
+        }
+        number.clear();
+        unit.clear();
+    };
+    for c in timeout.chars() {
+        if c.is_ascii_digit() {
+            if !unit.is_empty() {
+                add(&mut number, &mut unit);
+            }
+            number.push(c);
+        } else if c.is_alphabetic() {
+            unit.push(c.to_ascii_lowercase());
+        }
+    }
+    add(&mut number, &mut unit);
+    seconds.div_ceil(60) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIPELINE: &str = include_str!("../tests/fixtures/gitlab/pipeline.yml");
+
+    fn job<'a>(workflow: &'a model::Workflow, name: &str) -> &'a model::Job {
+        workflow.jobs.iter().find(|job| job.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_stages_and_needs() {
+        let workflow = parse(PIPELINE, ".gitlab-ci").unwrap();
+        assert_eq!(workflow.source, model::WorkflowSource::GitlabCi);
+        assert_eq!(
+            workflow.stages,
+            vec![".pre", "build", "test", "deploy", ".post"]
+        );
+        let jobs: Vec<(&str, &str, &[String])> = workflow
+            .jobs
+            .iter()
+            .map(|job| (job.name.as_str(), job.stage.as_str(), job.needs.as_slice()))
+            .collect();
+        let names =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|n| n.to_string()).collect() };
+        assert_eq!(
+            jobs,
+            vec![
+                ("build", "build", &[][..]),
+                // Without `needs`, a job needs every job of the earlier stages.
+                ("unit", "test", &names(&["build"])[..]),
+                ("integration", "test", &names(&["build"])[..]),
+                (
+                    "deploy",
+                    "deploy",
+                    &names(&["build", "unit", "integration"])[..]
+                ),
+            ]
+        );
+        assert_eq!(
+            workflow.env,
+            BTreeMap::from([
+                ("DOCKER_DRIVER".to_string(), "overlay2".to_string()),
+                ("RELEASE".to_string(), "false".to_string()),
+            ])
+        );
+
+        let error = parse("build:\n  stage: compile\n  script: make\n", ".gitlab-ci").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(
+            error.message,
+            "job 'build' uses stage 'compile', which is not in 'stages'"
+        );
+        let error = parse(
+            "test:\n  needs: [build]\n  script: make test\n",
+            ".gitlab-ci",
+        )
+        .unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(error.message, "job 'test' needs unknown job 'build'");
+    }
+
+    #[test]
+    fn test_extends_and_defaults() {
+        let workflow = parse(PIPELINE, ".gitlab-ci").unwrap();
+        let unit = job(&workflow, "unit");
+        let steps: Vec<(&str, &str)> = unit
+            .steps
+            .iter()
+            .map(|step| (step.name.as_str(), step.run_command.as_str()))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![("before_script", "go version"), ("script", "make test")]
+        );
+        assert_eq!(unit.container.as_ref().unwrap().image, "golang:1.22");
+        assert_eq!(unit.runner_labels, vec!["docker"]);
+        assert_eq!(unit.timeout_minutes, 90);
+
+        // `inherit: default: false` drops the default image and scripts.
+        let integration = job(&workflow, "integration");
+        assert_eq!(integration.steps.len(), 1);
+        assert_eq!(integration.container.as_ref().unwrap().image, "alpine");
+        assert_eq!(integration.timeout_minutes, 90);
+    }
+
+    #[test]
+    fn test_parallel_matrix() {
+        let workflow = parse(PIPELINE, ".gitlab-ci").unwrap();
+        let matrix = job(&workflow, "unit").matrix.as_ref().unwrap();
+        let combinations: Vec<Vec<(&str, &str)>> = matrix
+            .include
+            .iter()
+            .map(|combination| {
+                combination
+                    .values
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str()))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            combinations,
+            vec![
+                vec![("DB", "postgres"), ("GO", "1.21")],
+                vec![("DB", "postgres"), ("GO", "1.22")],
+            ]
+        );
+        assert!(matrix.axes.is_empty());
+    }
+
+    #[test]
+    fn test_rules_and_only() {
+        let workflow = parse(PIPELINE, ".gitlab-ci").unwrap();
+        let integration = job(&workflow, "integration");
+        assert_eq!(
+            integration.condition,
+            "($CI_COMMIT_BRANCH == \"main\") || (!($CI_PIPELINE_SOURCE == \"schedule\"))"
+        );
+        assert_eq!(integration.when, "manual");
+        assert!(integration.continue_on_error);
+
+        let deploy = job(&workflow, "deploy");
+        assert_eq!(
+            deploy.condition,
+            "($CI_COMMIT_REF_NAME == \"main\" || $CI_COMMIT_TAG) && !($SKIP_DEPLOY)"
+        );
+        assert_eq!(deploy.environment, "production");
+        assert!(!deploy.continue_on_error);
+        assert_eq!(job(&workflow, "build").condition, "");
+    }
+
+    #[test]
+    fn test_includes() {
+        let workflow = parse(PIPELINE, ".gitlab-ci").unwrap();
+        assert_eq!(
+            workflow.includes,
+            vec![
+                "ci/common.yml",
+                "group/templates:/deploy.yml",
+                "Security/SAST.gitlab-ci.yml",
+            ]
+        );
+        let single = parse("include: ci/build.yml\nbuild:\n  script: make\n", "ci").unwrap();
+        assert_eq!(single.includes, vec!["ci/build.yml"]);
+    }
+
+    #[test]
+    fn test_unsupported_keys() {
+        let error = parse(
+            "build:\n  script: make\n  artefacts:\n    paths: [bin/]\n",
+            ".gitlab-ci",
+        )
+        .unwrap_err();
+        assert_eq!((error.line, error.column), (3, 3));
+        assert!(
+            error.message.starts_with("unknown field `artefacts`"),
+            "{}",
+            error.message
+        );
+
+        let error = parse(
+            "build:\n  script: make\n  rules:\n    - iff: $CI_COMMIT_TAG\n",
+            ".gitlab-ci",
+        )
+        .unwrap_err();
+        // Keys below the job are not looked up; the job is pointed at.
+        assert_eq!((error.line, error.column), (1, 1));
+        assert!(
+            error.message.starts_with("unknown field `iff`"),
+            "{}",
+            error.message
+        );
+    }
+}
@@ -3,9 +3,18 @@
 *
 * This Rust library is a drop-in replacement for the original Go parser.
 * It exposes a single C-compatible function, `ParseWorkflowToJSON`, which
-* reads a workflow file (e.g., from GitHub Actions), parses its YAML content,
+* reads a GitHub Actions workflow or a GitLab CI file, parses its YAML
+* content into the `Workflow` model of `rpc_data.proto` (see `model.rs`),
 * and serializes it into a JSON string.
 *
+* The front ends live in `github.rs` and `gitlab.rs`; `parse_workflow`
+* picks one from the file name and the top-level keys. Errors carry the
+* line and column they refer to (see `error.rs`).
+*
+* The library is also a module of the `ph_core_api.h` contract: it registers
+* the `ci` command, whose subcommands (`ph ci convert`, `ph ci run`) are
+* implemented in `commands.rs`.
+*
 * The returned JSON string is allocated by Rust and its ownership is
 * transferred to the C/C++ caller. A corresponding `FreeJSONString` function
 * is provided to allow the caller to safely release the memory, which is a
@@ -13,90 +22,71 @@
 *
 * SPDX-License-Identifier: Apache-2.0 */
 
-use libc::c_char;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+mod commands;
+mod common;
+pub mod convert;
+pub mod error;
+pub mod github;
+pub mod gitlab;
+pub mod model;
+pub mod pipeline;
+pub mod runner;
+
+use error::ParseError;
+use libc::{c_char, c_int};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
 use std::ffi::{CStr, CString};
 use std::fs;
+use std::path::Path;
 use std::ptr;
 
-// --- Data Structures for YAML Parsing and JSON Serialization ---
-
-/// Represents a single step within a CI/CD job.
-/// Derives `Serialize` for JSON output and `Deserialize` for YAML input.
-#[derive(Serialize, Deserialize, Debug)]
-struct WorkflowStep {
-    name: String,
-    run: String,
-}
-
-/// Represents the structure of a job as defined in the YAML file.
-/// Note that this struct does not contain the job's name, as the name
-/// is the key in the `jobs` map in the source YAML.
-#[derive(Deserialize, Debug)]
-struct YamlWorkflowJob {
-    #[serde(rename = "runs-on")]
-    runs_on: String,
-    steps: Vec<WorkflowStep>,
-}
-
-/// Represents the top-level structure of the YAML workflow file.
-#[derive(Deserialize, Debug)]
-struct YamlWorkflow {
-    name: String,
-    jobs: HashMap<String, YamlWorkflowJob>,
-}
-
-/// Represents a job for the final JSON output.
-/// This struct *includes* the job's name, which we will populate
-/// after parsing the YAML. This makes the final JSON more convenient to consume.
-#[derive(Serialize, Debug)]
-struct JsonWorkflowJob {
-    name: String,
-    #[serde(rename = "runs_on")]
-    runs_on: String,
-    steps: Vec<WorkflowStep>,
-}
-
-/// Represents the final, top-level structure to be serialized to JSON.
-#[derive(Serialize, Debug)]
-struct JsonWorkflow {
-    name: String,
-    // We convert the map of jobs into a simple array for easier iteration
-    // on the consumer side (e.g., in C++ or JavaScript).
-    jobs: Vec<JsonWorkflowJob>,
+/// Parses the content of a workflow file. GitLab CI is recognized by the
+/// `.gitlab-ci.yml` file name, or by the absence of a top-level `jobs` key;
+/// anything else is read as GitHub Actions.
+pub fn parse_workflow(file_name: &str, source: &str) -> Result<model::Workflow, ParseError> {
+    let base_name = Path::new(file_name)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(file_name);
+    let default_name = base_name
+        .trim_end_matches(".yml")
+        .trim_end_matches(".yaml")
+        .to_string();
+    let is_gitlab =
+        if base_name.ends_with(".gitlab-ci.yml") || base_name.ends_with(".gitlab-ci.yaml") {
+            true
+        } else {
+            // The last document holds the pipeline; a leading one may be a header.
+            let mut has_jobs = false;
+            for document in serde_yaml::Deserializer::from_str(source) {
+                let document = serde_yaml::Value::deserialize(document)
+                    .map_err(|e| ParseError::from_yaml(source, &[], e))?;
+                has_jobs = document
+                    .get("jobs")
+                    .is_some_and(serde_yaml::Value::is_mapping);
+            }
+            !has_jobs
+        };
+    if is_gitlab {
+        gitlab::parse(source, &default_name)
+    } else {
+        github::parse(source, &default_name)
+    }
 }
 
 /// Internal function to handle the core logic, separating it from unsafe FFI code.
 /// This promotes testability and clarity.
 fn parse_and_serialize(filepath: &str) -> Result<String, Box<dyn std::error::Error>> {
     // 1. Read the YAML file content into a string.
-    let yaml_content = fs::read_to_string(filepath)?;
-
-    // 2. Deserialize the YAML string into our intermediate `YamlWorkflow` struct.
-    // `serde_yaml` handles the parsing efficiently.
-    let yaml_workflow: YamlWorkflow = serde_yaml::from_str(&yaml_content)?;
-
-    // 3. Transform the parsed YAML structure into the target JSON structure.
-    // This is where we handle the business logic of moving the job name from
-    // the map key into the job object itself.
-    let json_jobs: Vec<JsonWorkflowJob> = yaml_workflow
-        .jobs
-        .into_iter()
-        .map(|(job_name, yaml_job)| JsonWorkflowJob {
-            name: job_name,
-            runs_on: yaml_job.runs_on,
-            steps: yaml_job.steps,
-        })
-        .collect();
-
-    let json_workflow = JsonWorkflow {
-        name: yaml_workflow.name,
-        jobs: json_jobs,
-    };
+    let yaml_content =
+        fs::read_to_string(filepath).map_err(|e| format!("cannot read '{}': {}", filepath, e))?;
+
+    // 2. Parse it with the front end matching the file.
+    let workflow = parse_workflow(filepath, &yaml_content).map_err(|e| e.in_file(filepath))?;
 
-    // 4. Serialize the final `JsonWorkflow` struct into a pretty-printed JSON string.
-    let json_string = serde_json::to_string_pretty(&json_workflow)?;
+    // 3. Serialize the model into a pretty-printed JSON string.
+    let json_string = serde_json::to_string_pretty(&workflow)?;
 
     Ok(json_string)
 }
@@ -140,8 +130,9 @@ pub unsafe extern "C" fn ParseWorkflowToJSON(filepath: *const c_char) -> *mut c_
             }
         }
         Err(e) => {
-            // As in the Go version, print the error and return null.
-            eprintln!("Error processing workflow file '{}': {}", rust_filepath, e);
+            // As in the Go version, print the error and return null. Parse
+            // errors already start with the file, line and column.
+            eprintln!("Error processing workflow file: {}", e);
             ptr::null_mut()
         }
     }
@@ -166,4 +157,114 @@ pub unsafe extern "C" fn FreeJSONString(s: *mut c_char) {
         // across the FFI boundary.
         let _ = CString::from_raw(s);
     }
-}
\ No newline at end of file
+}
+
+// --- ph Module API ---
+// The C ABI definitions below are identical to the other modules.
+
+#[repr(C)]
+pub enum Status {
+    Success = 0,
+    ErrorInitFailed = 1,
+    ErrorInvalidArgs = 2,
+    ErrorExecFailed = 3,
+}
+
+#[repr(C)]
+pub struct ModuleInfo {
+    name: *const c_char,
+    version: *const c_char,
+    description: *const c_char,
+    commands: *const *const c_char,
+}
+
+static MODULE_NAME: Lazy<CString> = Lazy::new(|| CString::new("ci_cd_manager").unwrap());
+static MODULE_VERSION: Lazy<CString> =
+    Lazy::new(|| CString::new(env!("CARGO_PKG_VERSION")).unwrap());
+static MODULE_DESCRIPTION: Lazy<CString> = Lazy::new(|| {
+    CString::new("CI workflow tools for GitHub Actions and GitLab CI [Rust]").unwrap()
+});
+
+static CI_CMD: Lazy<CString> = Lazy::new(|| CString::new("ci").unwrap());
+
+/// The `ModuleInfo` and the command array it points to, kept together so
+/// both live for the whole program.
+struct StaticApiData {
+    info: ModuleInfo,
+    commands: [*const c_char; 2],
+}
+
+/// # Safety
+/// The pointers only refer to the `Lazy<CString>` statics above, which are
+/// initialized once and never freed, so sharing them across threads is safe.
+unsafe impl Send for StaticApiData {}
+unsafe impl Sync for StaticApiData {}
+
+static STATIC_API_DATA: Lazy<StaticApiData> = Lazy::new(|| {
+    let mut data = StaticApiData {
+        info: ModuleInfo {
+            name: MODULE_NAME.as_ptr(),
+            version: MODULE_VERSION.as_ptr(),
+            description: MODULE_DESCRIPTION.as_ptr(),
+            commands: ptr::null(),
+        },
+        commands: [CI_CMD.as_ptr(), ptr::null()],
+    };
+    data.info.commands = data.commands.as_ptr();
+    data
+});
+
+#[no_mangle]
+pub extern "C" fn module_get_info() -> *const ModuleInfo {
+    &STATIC_API_DATA.info
+}
+
+#[no_mangle]
+pub extern "C" fn module_init(_context: *const std::ffi::c_void) -> Status {
+    // The parser keeps no state, so the core context is not needed.
+    Status::Success
+}
+
+/// Runs a command of this module; `argv[0]` is the command (`ci`).
+///
+/// # Safety
+/// `argv` must point to `argc` valid, null-terminated C strings.
+#[no_mangle]
+pub unsafe extern "C" fn module_exec(argc: c_int, argv: *const *const c_char) -> Status {
+    let args = c_args_to_vec(argc, argv);
+    if args.is_empty() {
+        eprintln!("[CI_CD_MANAGER ERROR] Execution called with no command.");
+        return Status::ErrorInvalidArgs;
+    }
+
+    let result = match args[0].as_str() {
+        "ci" => commands::handle_ci(&args[1..]),
+        command => Err(format!("Unknown command '{}' for ci_cd_manager module", command).into()),
+    };
+
+    match result {
+        Ok(()) => Status::Success,
+        Err(e) => {
+            eprintln!("\n[ph ERROR] Command failed: {}", e);
+            Status::ErrorExecFailed
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn module_cleanup() {
+    // All CString memory is managed by `Lazy` and released at program exit.
+}
+
+/// # Safety
+/// `argv` must point to `argc` valid, null-terminated C strings that stay
+/// valid for the duration of the call.
+unsafe fn c_args_to_vec(argc: c_int, argv: *const *const c_char) -> Vec<String> {
+    if argc <= 0 || argv.is_null() {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(argv, argc as usize)
+        .iter()
+        .map(|&p| CStr::from_ptr(p).to_string_lossy().into_owned())
+        .collect()
+}
@@ -0,0 +1,187 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/model.rs - The parsed workflow, as sent to the other modules.
+*
+* These structs mirror the `Workflow`, `Job`, `Step` and related messages of
+* `src/ipc/schemas/rpc_data.proto`, field for field. They are serialized to
+* JSON with the proto field names (e.g. `runs_on`, `run_command`), which is
+* what the C++ visualizer reads. Like the proto3 JSON mapping, empty fields
+* are left out, except the ones every consumer relies on: the names, `runs_on`
+* and the `jobs` and `steps` lists.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+/// Mirrors the `Step` message.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Step {
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub run_command: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub uses: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub with: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub condition: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub shell: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub working_directory: String,
+    #[serde(skip_serializing_if = "is_false")]
+    pub continue_on_error: bool,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub timeout_minutes: u32,
+}
+
+/// Mirrors the `Container` message.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Container {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub image: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub options: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entrypoint: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+}
+
+/// Mirrors the `MatrixAxis` message.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct MatrixAxis {
+    pub values: Vec<String>,
+}
+
+/// Mirrors the `MatrixCombination` message.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct MatrixCombination {
+    pub values: BTreeMap<String, String>,
+}
+
+/// Mirrors the `Matrix` message.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Matrix {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub axes: BTreeMap<String, MatrixAxis>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<MatrixCombination>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<MatrixCombination>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub expression: String,
+    #[serde(skip_serializing_if = "is_false")]
+    pub fail_fast: bool,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub max_parallel: u32,
+}
+
+/// Mirrors the `Trigger` message.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Trigger {
+    pub event: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub branches_ignore: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags_ignore: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths_ignore: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<String>,
+}
+
+/// Mirrors the `Job` message.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Job {
+    pub name: String,
+    pub runs_on: String,
+    pub steps: Vec<Step>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub needs: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub condition: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<Matrix>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<Container>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<Container>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub outputs: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub timeout_minutes: u32,
+    #[serde(skip_serializing_if = "is_false")]
+    pub continue_on_error: bool,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub environment: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub uses: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub with: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub stage: String,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub parallel: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub runner_labels: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub display_name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub when: String,
+}
+
+/// Mirrors the `WorkflowSource` enum; serialized by value name.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WorkflowSource {
+    #[default]
+    WorkflowSourceUnspecified,
+    GithubActions,
+    GitlabCi,
+}
+
+/// Mirrors the `Workflow` message.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Workflow {
+    pub name: String,
+    pub jobs: Vec<Job>,
+    pub source: WorkflowSource,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<Trigger>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<String>,
+}
@@ -0,0 +1,162 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/pipeline.rs - The phPipeline manifest written by `ph ci convert`.
+*
+* These structs mirror the parts of the operator's phPipeline custom
+* resource (`k8s/operators/ph_operator/src/crds.rs`) that a converted
+* workflow can fill: stages of steps with an image, a command, environment
+* variables, `needs`, a timeout, `continueOnError` and a `matrix`, plus a
+* volume workspace shared by all steps. They are serialized with the CRD's
+* camelCase field names, so the output can be applied as is.
+*
+* `ph ci run` also reads phPipeline manifests into these structs, so they
+* keep the step fields a hand-written manifest may use on top of those
+* (`retries`, `outputs`, `when`, `cache`, `finally` steps, ...). Fields
+* with no local meaning, such as the size of the workspace claim, are
+* ignored.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// The matrix and `when` types are the operator's own, from `pipeline_rules`.
+pub use pipeline_rules::{StepMatrix, WhenCondition, WhenOperator};
+
+pub const API_VERSION: &str = "ph.io/v1alpha1";
+pub const KIND: &str = "phPipeline";
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub api_version: String,
+    pub kind: String,
+    pub metadata: Metadata,
+    pub spec: Spec,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// Mirrors `phPipelineSpec`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Spec {
+    pub stages: Vec<Stage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<Workspace>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub finally: Vec<Step>,
+}
+
+/// Mirrors `PipelineWorkspace`, limited to a volume the operator creates.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Workspace {
+    #[serde(default)]
+    pub volume: WorkspaceVolume,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct WorkspaceVolume {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+}
+
+/// Mirrors `PipelineStage`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Stage {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+/// Mirrors `PipelineStep`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Step {
+    pub name: String,
+    #[serde(rename = "stepType", default, skip_serializing_if = "Option::is_none")]
+    pub step_type: Option<String>,
+    pub image: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<EnvVar>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<StepOutput>,
+    #[serde(default, alias = "runAfter", skip_serializing_if = "Vec::is_empty")]
+    pub needs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_backoff: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub continue_on_error: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<StepCache>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<StepMatrix>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub when: Vec<WhenCondition>,
+}
+
+/// Mirrors `PipelineEnvVar`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvVar {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_from: Option<EnvVarSource>,
+}
+
+/// Mirrors `EnvVarSource`: exactly one of the fields is set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvVarSource {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_key_ref: Option<KeySelector>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_map_key_ref: Option<KeySelector>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeySelector {
+    pub name: String,
+    pub key: String,
+}
+
+/// Mirrors `PipelineStepOutput`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StepOutput {
+    pub name: String,
+    pub path: String,
+}
+
+/// Mirrors `StepCache`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StepCache {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+}
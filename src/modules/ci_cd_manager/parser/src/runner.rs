@@ -0,0 +1,1160 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/runner.rs - Runs a phPipeline on the local Docker engine.
+*
+* This is the logic behind `ph ci run`. It follows the rules the operator
+* applies in `k8s/operators/ph_operator/src/controllers/pipeline_*.rs`, and
+* takes matrix expansion, `when` conditions, durations and retries from the
+* same `pipeline_rules` crate, so a pipeline behaves the same on a laptop as
+* in the cluster:
+* - Matrix steps are expanded into one step per combination, named after its
+*   values, and `$(matrix.<name>)` is replaced everywhere.
+* - A step waits for the steps in its `needs` or, without them, for the
+*   whole previous stage, and for the steps its `when` conditions refer to.
+*   Steps run one at a time in that order, so their logs do not interleave.
+* - Every step runs in a container (`docker run`) with the workspace
+*   mounted at `/workspace`: one volume for the whole run, a host directory
+*   given with `--workspace`, or a fresh volume per step when the pipeline
+*   declares no workspace. `$PH_OUTPUTS` is created before the step and the
+*   first line of every declared output is read once it succeeded, for
+*   `$(steps.<step>.outputs.<name>)`.
+* - The exit code of the container decides the outcome. Timeouts, retries
+*   with backoff, `continueOnError`, `when` conditions and `finally` steps
+*   (told the outcome in `PH_PIPELINE_RESULT`) work as in the cluster. Once
+*   a step failed, the remaining ones are skipped, except those whose
+*   conditions check the status of a step they wait for.
+* - `$(NAME)` references to variables are expanded as Kubernetes does, and
+*   Secret and ConfigMap keys are read from `Options::values` or from the
+*   environment. Step caches are not used: the step always runs.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use crate::pipeline::{Manifest, Spec, Step};
+use devops_automation::process_wrapper::{self, Exit};
+use pipeline_rules::duration::{parse_duration, retry_delay};
+use pipeline_rules::matrix;
+use pipeline_rules::references::substitute_outputs;
+use pipeline_rules::when::{self, WhenContext};
+pub use pipeline_rules::StepPhase;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A flexible error type for command logic failures.
+type CommandResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const DOCKER: &str = "docker";
+/// Where the workspace is mounted in every container.
+const WORKSPACE_MOUNT: &str = "/workspace";
+/// The image of the containers that prepare and collect step outputs.
+const HELPER_IMAGE: &str = "busybox:1.36";
+/// The image running `generate-sbom` steps.
+const SBOM_IMAGE: &str = "anchore/syft:latest";
+/// Output values are cut to this many bytes, as in the cluster.
+const MAX_VALUE_LEN: usize = 1024;
+/// The stage name reported for `finally` steps.
+const FINALLY_STAGE: &str = "finally";
+
+/// Settings of a local run.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// A host directory mounted as the workspace, e.g. a checkout of the
+    /// repository. Without it, the run gets a fresh volume.
+    pub workspace: Option<PathBuf>,
+    /// Keeps the volumes of the run instead of removing them at the end.
+    pub keep_workspace: bool,
+    /// The values of the Secret and ConfigMap keys read by the steps, by
+    /// key. Keys missing here are read from the environment.
+    pub values: BTreeMap<String, String>,
+}
+
+/// What happened to one step.
+#[derive(Debug, Clone)]
+pub struct StepReport {
+    pub name: String,
+    pub stage: String,
+    pub phase: StepPhase,
+    pub attempts: u32,
+    pub duration: Duration,
+    /// Why the step failed or was skipped; empty when it succeeded.
+    pub message: String,
+}
+
+/// What happened to the whole run.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub pipeline: String,
+    /// `Succeeded` or `Failed`.
+    pub phase: StepPhase,
+    pub duration: Duration,
+    pub steps: Vec<StepReport>,
+    /// The first step that failed the run, if any.
+    pub failed_step: Option<String>,
+}
+
+/// A step of the expanded pipeline together with the steps it waits for.
+#[derive(Debug)]
+struct Node {
+    stage: String,
+    step: Step,
+    /// Positions of the steps this one needs.
+    needs: Vec<usize>,
+    /// Positions of the steps whose status its conditions check: they only
+    /// have to finish.
+    watches: Vec<usize>,
+    finally: bool,
+}
+
+/// Runs `manifest` and reports the outcome of every step. Failing steps are
+/// part of the report; an error means the pipeline is invalid or Docker
+/// cannot be used.
+pub fn run(manifest: &Manifest, options: &Options) -> CommandResult<Report> {
+    let name = &manifest.metadata.name;
+    let (spec, groups) = expand(&manifest.spec)?;
+    let nodes = build(&spec, &groups)?;
+    let order = order(&nodes)?;
+
+    process_wrapper::run_command_and_capture(
+        DOCKER,
+        &strings(&["version", "--format", "{{.Server.Version}}"]),
+    )
+    .map_err(|e| format!("cannot reach the Docker engine: {}", e))?;
+
+    let mut runner = Runner {
+        options,
+        run_id: format!("ph-{}-{}", sanitize(name), std::process::id()),
+        shared: None,
+        volumes: Vec::new(),
+        outputs: HashMap::new(),
+    };
+    runner.shared = match &options.workspace {
+        Some(directory) => {
+            let directory = directory
+                .canonicalize()
+                .map_err(|e| format!("cannot use workspace '{}': {}", directory.display(), e))?;
+            Some(directory.to_string_lossy().into_owned())
+        }
+        None if spec.workspace.is_some() => {
+            let volume = runner.create_volume("workspace")?;
+            Some(volume)
+        }
+        None => None,
+    };
+    match &runner.shared {
+        Some(source) => println!("Workspace: {} mounted at {}", source, WORKSPACE_MOUNT),
+        None => println!("The pipeline declares no workspace; every step gets an empty one."),
+    }
+
+    let started = Instant::now();
+    let (steps, failure) = execute(&nodes, &order, &groups, &mut runner)?;
+    Ok(Report {
+        pipeline: name.clone(),
+        phase: if failure.is_some() {
+            StepPhase::Failed
+        } else {
+            StepPhase::Succeeded
+        },
+        duration: started.elapsed(),
+        steps,
+        failed_step: failure,
+    })
+}
+
+/// Runs the steps in `order` and reports them, along with the first step
+/// that failed the run. Once a step failed, the remaining ones are skipped
+/// except the `finally` steps and those whose conditions check the status of
+/// a step, unless a step they wait for without checking it failed. `groups`
+/// holds the steps expanded from every matrix step.
+fn execute(
+    nodes: &[Node],
+    order: &[usize],
+    groups: &HashMap<String, Vec<String>>,
+    executor: &mut impl Executor,
+) -> CommandResult<(Vec<StepReport>, Option<String>)> {
+    let mut phases = vec![StepPhase::Pending; nodes.len()];
+    let mut reports: Vec<Option<StepReport>> = vec![None; nodes.len()];
+    let mut failure: Option<String> = None;
+    for &index in order {
+        let node = &nodes[index];
+        let step_started = Instant::now();
+        let report = |phase: StepPhase, attempts: u32, message: String| StepReport {
+            name: node.step.name.clone(),
+            stage: node.stage.clone(),
+            phase,
+            attempts,
+            duration: step_started.elapsed(),
+            message,
+        };
+
+        let blocked = node.watches.is_empty()
+            || node.needs.iter().any(|&need| {
+                !node.watches.contains(&need)
+                    && phases[need] == StepPhase::Failed
+                    && !nodes[need].step.continue_on_error
+            });
+        if let (Some(failed), false, true) = (&failure, node.finally, blocked) {
+            let message = format!("not run because step '{}' failed", failed);
+            phases[index] = StepPhase::Skipped;
+            reports[index] = Some(report(StepPhase::Skipped, 0, message));
+            continue;
+        }
+        let context = WhenContext {
+            phases: nodes
+                .iter()
+                .zip(&phases)
+                .map(|(node, &phase)| (node.step.name.as_str(), phase))
+                .collect(),
+            groups,
+            outputs: executor.outputs(),
+            // The changed files of a commit are not known locally, so
+            // `changedPaths` conditions hold, as in the cluster when a run
+            // has none.
+            changed_paths: None,
+        };
+        match when::evaluate(&node.step.name, &node.step.when, &context) {
+            Ok(None) => {}
+            Ok(Some(reason)) => {
+                println!("\n==> {}: {}", node.step.name, reason);
+                phases[index] = StepPhase::Skipped;
+                reports[index] = Some(report(StepPhase::Skipped, 0, reason));
+                continue;
+            }
+            Err(e) => {
+                let message = e.to_string();
+                eprintln!("\n==> {}: {}", node.step.name, message);
+                phases[index] = StepPhase::Failed;
+                reports[index] = Some(report(StepPhase::Failed, 0, message));
+                failure.get_or_insert_with(|| node.step.name.clone());
+                continue;
+            }
+        }
+
+        let outcome = if failure.is_some() {
+            StepPhase::Failed
+        } else {
+            StepPhase::Succeeded
+        };
+        let (phase, attempts, mut message) = executor.run_step(node, outcome)?;
+        if phase == StepPhase::Failed {
+            if node.step.continue_on_error {
+                message.push_str(" (ignored)");
+            } else {
+                failure.get_or_insert_with(|| node.step.name.clone());
+            }
+        }
+        phases[index] = phase;
+        reports[index] = Some(report(phase, attempts, message));
+    }
+
+    Ok((reports.into_iter().flatten().collect(), failure))
+}
+
+/// Runs single steps; `Runner` does it with Docker.
+trait Executor {
+    /// The output values of the finished steps, by step name.
+    fn outputs(&self) -> &HashMap<String, BTreeMap<String, String>>;
+    /// Runs every attempt of a step. Returns its phase, the number of
+    /// attempts and, when it failed, why.
+    fn run_step(
+        &mut self,
+        node: &Node,
+        outcome: StepPhase,
+    ) -> CommandResult<(StepPhase, u32, String)>;
+}
+
+/// The state of a run while its steps execute.
+struct Runner<'a> {
+    options: &'a Options,
+    /// Names the containers and volumes of this run.
+    run_id: String,
+    /// What is mounted at `/workspace` for every step, if shared.
+    shared: Option<String>,
+    /// The volumes created for this run, removed when it ends.
+    volumes: Vec<String>,
+    /// The output values of the finished steps, by step name.
+    outputs: HashMap<String, BTreeMap<String, String>>,
+}
+
+impl Drop for Runner<'_> {
+    fn drop(&mut self) {
+        for volume in std::mem::take(&mut self.volumes) {
+            self.remove_volume(volume);
+        }
+    }
+}
+
+impl Executor for Runner<'_> {
+    fn outputs(&self) -> &HashMap<String, BTreeMap<String, String>> {
+        &self.outputs
+    }
+
+    fn run_step(
+        &mut self,
+        node: &Node,
+        outcome: StepPhase,
+    ) -> CommandResult<(StepPhase, u32, String)> {
+        let step = &node.step;
+        println!("\n==> {} (stage '{}')", step.name, node.stage);
+        if step.cache.is_some() {
+            println!("note: the step cache is not used locally; the step always runs");
+        }
+        let source = match &self.shared {
+            Some(source) => source.clone(),
+            None => self.create_volume(&step.name)?,
+        };
+        let result = self.attempts(node, &source, outcome);
+        if self.shared.is_none() {
+            self.volumes.retain(|volume| *volume != source);
+            self.remove_volume(source);
+        }
+        result
+    }
+}
+
+impl Runner<'_> {
+    fn create_volume(&mut self, suffix: &str) -> CommandResult<String> {
+        let name = format!("{}-{}", self.run_id, sanitize(suffix));
+        process_wrapper::run_command_and_capture(DOCKER, &strings(&["volume", "create", &name]))?;
+        self.volumes.push(name.clone());
+        Ok(name)
+    }
+
+    fn remove_volume(&self, volume: String) {
+        if self.options.keep_workspace {
+            println!("Kept volume '{}'.", volume);
+            return;
+        }
+        let args = strings(&["volume", "rm", "--force", &volume]);
+        if let Err(e) = process_wrapper::run_command_and_capture(DOCKER, &args) {
+            eprintln!("warning: cannot remove volume '{}': {}", volume, e);
+        }
+    }
+
+    fn attempts(
+        &mut self,
+        node: &Node,
+        source: &str,
+        outcome: StepPhase,
+    ) -> CommandResult<(StepPhase, u32, String)> {
+        let step = &node.step;
+        let (image, command, args, env) = match self.resolve(node, outcome) {
+            Ok(resolved) => resolved,
+            Err(message) => {
+                eprintln!("[{}] {}", step.name, message);
+                return Ok((StepPhase::Failed, 0, message));
+            }
+        };
+        let timeout = step.timeout.as_deref().and_then(parse_duration);
+        let output_dir = step_output_dir(&step.name);
+        let container = format!("{}-{}", self.run_id, sanitize(&step.name));
+        let attempts = step.retries + 1;
+        let mut message = String::new();
+        for attempt in 1..=attempts {
+            if attempt > 1 {
+                let delay = retry_delay(step.retry_backoff.as_deref(), attempt - 1);
+                println!(
+                    "[{}] retrying in {} (attempt {} of {})",
+                    step.name,
+                    format_duration(delay),
+                    attempt,
+                    attempts
+                );
+                thread::sleep(delay);
+            }
+            self.helper(source, &format!("mkdir -p {}", shell_quote(&output_dir)))?;
+
+            let mut docker_args = strings(&["run", "--rm", "--name", &container]);
+            docker_args.push("--volume".to_string());
+            docker_args.push(format!("{}:{}", source, WORKSPACE_MOUNT));
+            for (name, value) in &env {
+                docker_args.push("--env".to_string());
+                docker_args.push(format!("{}={}", name, value));
+            }
+            if let Some(entrypoint) = command.first() {
+                docker_args.push("--entrypoint".to_string());
+                docker_args.push(entrypoint.clone());
+            }
+            docker_args.push(image.clone());
+            docker_args.extend(command.iter().skip(1).cloned());
+            docker_args.extend(args.iter().cloned());
+
+            let prefix = format!("[{}] ", step.name);
+            let exit =
+                process_wrapper::run_command_with_prefix(DOCKER, &docker_args, &prefix, timeout)?;
+            message = match exit {
+                Exit::Code(0) => {
+                    let values = self.collect(step, source)?;
+                    self.outputs.insert(step.name.clone(), values);
+                    return Ok((StepPhase::Succeeded, attempt, String::new()));
+                }
+                // 125 to 127 come from Docker itself, not from the step.
+                Exit::Code(code @ 125..=127) => {
+                    format!("docker could not run the container (exit code {})", code)
+                }
+                Exit::Code(code) => format!("exited with code {}", code),
+                Exit::TimedOut => {
+                    let args = strings(&["rm", "--force", &container]);
+                    let _ = process_wrapper::run_command_and_capture(DOCKER, &args);
+                    format!(
+                        "timed out after {}",
+                        step.timeout.as_deref().unwrap_or_default()
+                    )
+                }
+            };
+            eprintln!(
+                "[{}] attempt {} of {} {}",
+                step.name, attempt, attempts, message
+            );
+        }
+        Ok((StepPhase::Failed, attempts, message))
+    }
+
+    /// The image, command, arguments and variables of a step, with the
+    /// outputs of earlier steps and the variable references substituted.
+    #[allow(clippy::type_complexity)]
+    fn resolve(
+        &self,
+        node: &Node,
+        outcome: StepPhase,
+    ) -> Result<(String, Vec<String>, Vec<String>, Vec<(String, String)>), String> {
+        let step = &node.step;
+        let mut env: Vec<(String, String)> = vec![
+            ("PH_WORKSPACE".to_string(), WORKSPACE_MOUNT.to_string()),
+            ("PH_OUTPUTS".to_string(), step_output_dir(&step.name)),
+        ];
+        for var in &step.env {
+            let value = match &var.value_from {
+                None => substitute_outputs(&var.value, &self.outputs).map_err(|e| e.to_string())?,
+                Some(source) => {
+                    let (kind, selector) = match (
+                        &source.secret_key_ref,
+                        &source.config_map_key_ref,
+                    ) {
+                        (Some(secret), None) => ("Secret", secret),
+                        (None, Some(config_map)) => ("ConfigMap", config_map),
+                        _ => return Err(format!(
+                            "env '{}' must set exactly one of 'secretKeyRef' and 'configMapKeyRef'",
+                            var.name
+                        )),
+                    };
+                    match self.options.values.get(&selector.key) {
+                        Some(value) => value.clone(),
+                        None => std::env::var(&selector.key).map_err(|_| {
+                            format!(
+                                "env '{}' reads the key '{}' of the {} '{}'; pass it with --set {}=<value> or export {}",
+                                var.name, selector.key, kind, selector.name, selector.key, selector.key
+                            )
+                        })?,
+                    }
+                }
+            };
+            // A variable sees the ones defined before it.
+            let value = expand_variables(&value, &env);
+            match env.iter_mut().find(|(name, _)| *name == var.name) {
+                Some(existing) => existing.1 = value,
+                None => env.push((var.name.clone(), value)),
+            }
+        }
+        if node.finally {
+            env.push(("PH_PIPELINE_RESULT".to_string(), outcome.to_string()));
+        }
+
+        let resolve = |text: &String| {
+            substitute_outputs(text, &self.outputs)
+                .map(|text| expand_variables(&text, &env))
+                .map_err(|e| e.to_string())
+        };
+        let command = step
+            .command
+            .iter()
+            .map(resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+        let args = step
+            .args
+            .iter()
+            .map(resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+        if step.step_type.as_deref() == Some("generate-sbom") {
+            // The image to scan is the first argument, usually the output of
+            // the step that built it.
+            let Some(image_to_scan) = args.first() else {
+                return Err(
+                    "a 'generate-sbom' step needs the image to scan as its first argument"
+                        .to_string(),
+                );
+            };
+            let output = format!("{}/sbom.json", step_output_dir(&step.name));
+            let command = strings(&[
+                "syft",
+                "packages",
+                image_to_scan,
+                "-o",
+                "spdx-json",
+                "--file",
+                &output,
+            ]);
+            return Ok((SBOM_IMAGE.to_string(), command, Vec::new(), env));
+        }
+        Ok((step.image.clone(), command, args, env))
+    }
+
+    /// Reads the declared outputs of a step that succeeded.
+    fn collect(&self, step: &Step, source: &str) -> CommandResult<BTreeMap<String, String>> {
+        let mut script = Vec::new();
+        for output in &step.outputs {
+            let file = match output_file(&step.name, &output.path) {
+                Some(file) => shell_quote(&file),
+                None => {
+                    eprintln!(
+                        "[{}] output '{}' is outside {} and is not read",
+                        step.name, output.name, WORKSPACE_MOUNT
+                    );
+                    continue;
+                }
+            };
+            script.push(format!(
+                "if [ -f {file} ]; then printf '%s=' {name}; head -n 1 {file} | head -c {max}; echo; fi",
+                name = shell_quote(&output.name),
+                max = MAX_VALUE_LEN,
+            ));
+        }
+        if script.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+        let results = self.helper(source, &script.join("\n"))?;
+        Ok(results
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .filter(|(name, _)| !name.is_empty())
+            .map(|(name, value)| (name.to_string(), value.trim_end().to_string()))
+            .collect())
+    }
+
+    /// Runs `script` in a helper container with the workspace mounted.
+    fn helper(&self, source: &str, script: &str) -> CommandResult<String> {
+        let args = vec![
+            "run".to_string(),
+            "--rm".to_string(),
+            "--volume".to_string(),
+            format!("{}:{}", source, WORKSPACE_MOUNT),
+            HELPER_IMAGE.to_string(),
+            "sh".to_string(),
+            "-c".to_string(),
+            format!("set -e\n{}", script),
+        ];
+        process_wrapper::run_command_and_capture(DOCKER, &args)
+    }
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+/// Reduces `text` to the characters Docker accepts in container and volume
+/// names.
+fn sanitize(text: &str) -> String {
+    let name: String = text
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    match name.trim_start_matches(['_', '.', '-']) {
+        "" => "pipeline".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// Quotes `value` for a POSIX shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// The directory where a step writes its outputs.
+fn step_output_dir(step: &str) -> String {
+    format!("{}/steps/{}", WORKSPACE_MOUNT, step)
+}
+
+/// Resolves the path of an output against the step's output directory;
+/// `None` when it points outside the workspace.
+fn output_file(step: &str, path: &str) -> Option<String> {
+    if !path.starts_with('/') {
+        return Some(format!("{}/{}", step_output_dir(step), path));
+    }
+    let inside = path
+        .strip_prefix(WORKSPACE_MOUNT)
+        .is_some_and(|rest| rest.starts_with('/'));
+    (inside && !path.split('/').any(|part| part == "..")).then(|| path.to_string())
+}
+
+/// Formats a duration as e.g. "4s" or "2m05s".
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        _ => format!("{}m{:02}s", seconds / 60, seconds % 60),
+    }
+}
+
+/// Expands `$(NAME)` with the variables of `env` the way Kubernetes does:
+/// `$$` stands for `$` and unknown references are kept as written.
+fn expand_variables(text: &str, env: &[(String, String)]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = after;
+            continue;
+        }
+        let value = rest
+            .strip_prefix("$(")
+            .and_then(|inner| inner.find(')').map(|end| &inner[..end]))
+            .and_then(|name| {
+                env.iter()
+                    .rev()
+                    .find(|(var, _)| var == name)
+                    .map(|(_, value)| (name.len() + 3, value))
+            });
+        match value {
+            Some((length, value)) => {
+                result.push_str(value);
+                rest = &rest[length..];
+            }
+            None => {
+                result.push('$');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Replaces the matrix steps of `spec` with one step per combination.
+/// Returns the expanded spec and the steps of every matrix step, by name.
+fn expand(spec: &Spec) -> CommandResult<(Spec, HashMap<String, Vec<String>>)> {
+    let mut spec = spec.clone();
+    let mut expanded_names: HashMap<String, Vec<String>> = HashMap::new();
+    let stages = spec.stages.iter_mut().map(|stage| &mut stage.steps);
+    for steps in stages.chain(std::iter::once(&mut spec.finally)) {
+        let mut result = Vec::with_capacity(steps.len());
+        for step in steps.drain(..) {
+            let Some(matrix) = &step.matrix else {
+                result.push(step);
+                continue;
+            };
+            let mut template = step.clone();
+            template.matrix = None;
+            let instances = matrix::instances(&step.name, matrix)?;
+            let mut names = Vec::with_capacity(instances.len());
+            for (name, combination) in instances {
+                let mut instance = matrix::instantiate(&template, &step.name, &combination)?;
+                instance.name = name;
+                names.push(instance.name.clone());
+                result.push(instance);
+            }
+            expanded_names.insert(step.name.clone(), names);
+        }
+        *steps = result;
+    }
+
+    if !expanded_names.is_empty() {
+        let stages = spec.stages.iter_mut().flat_map(|stage| &mut stage.steps);
+        for step in stages.chain(&mut spec.finally) {
+            step.needs = step
+                .needs
+                .iter()
+                .flat_map(|need| {
+                    expanded_names
+                        .get(need)
+                        .cloned()
+                        .unwrap_or_else(|| vec![need.clone()])
+                })
+                .collect();
+        }
+    }
+    Ok((spec, expanded_names))
+}
+
+/// Flattens the stages into nodes and checks the graph: step names are
+/// unique, dependencies exist, and conditions and durations are valid. A
+/// condition on a matrix step (a key of `groups`) refers to all of its
+/// steps.
+fn build(spec: &Spec, groups: &HashMap<String, Vec<String>>) -> CommandResult<Vec<Node>> {
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    let mut stage_members: Vec<Vec<usize>> = vec![Vec::new(); spec.stages.len()];
+    let steps = spec
+        .stages
+        .iter()
+        .enumerate()
+        .flat_map(|(index, stage)| stage.steps.iter().map(move |step| (Some(index), step)))
+        .chain(spec.finally.iter().map(|step| (None, step)));
+    for (position, (stage, step)) in steps.enumerate() {
+        if positions.insert(step.name.as_str(), position).is_some() {
+            return Err(format!("step name '{}' is used more than once", step.name).into());
+        }
+        if let Some(stage) = stage {
+            stage_members[stage].push(position);
+        }
+        when::validate(&step.name, &step.when)?;
+        for value in step.timeout.iter().chain(&step.retry_backoff) {
+            if parse_duration(value).is_none() {
+                return Err(format!(
+                    "step '{}' has an invalid duration '{}' (expected e.g. \"30s\", \"10m\" or \"1h\")",
+                    step.name, value
+                )
+                .into());
+            }
+        }
+    }
+    let first_finally = positions.len() - spec.finally.len();
+
+    let mut nodes = Vec::with_capacity(positions.len());
+    let stages = spec
+        .stages
+        .iter()
+        .enumerate()
+        .flat_map(|(index, stage)| {
+            stage
+                .steps
+                .iter()
+                .map(move |step| (Some(index), stage.name.as_str(), step))
+        })
+        .chain(spec.finally.iter().map(|step| (None, FINALLY_STAGE, step)));
+    for (stage_index, stage_name, step) in stages {
+        let positions_of = |name: &str| -> CommandResult<Vec<usize>> {
+            let members = match groups.get(name) {
+                Some(members) => members.iter().map(String::as_str).collect(),
+                None => vec![name],
+            };
+            let mut result = Vec::with_capacity(members.len());
+            for member in members {
+                let position = *positions
+                    .get(member)
+                    .ok_or_else(|| format!("step '{}' needs unknown step '{}'", step.name, name))?;
+                if stage_index.is_some() && position >= first_finally {
+                    return Err(format!(
+                        "step '{}' cannot need finally step '{}'",
+                        step.name, name
+                    )
+                    .into());
+                }
+                result.push(position);
+            }
+            Ok(result)
+        };
+        let mut needs = Vec::new();
+        let named = step
+            .needs
+            .iter()
+            .map(String::as_str)
+            .chain(when::references(&step.when));
+        for need in named {
+            for position in positions_of(need)? {
+                if !needs.contains(&position) {
+                    needs.push(position);
+                }
+            }
+        }
+        if step.needs.is_empty() {
+            if let Some(previous) = stage_index.and_then(|index| index.checked_sub(1)) {
+                needs.extend(&stage_members[previous]);
+            }
+        }
+        let mut watches = Vec::new();
+        for name in when::status_references(&step.when) {
+            watches.extend(positions_of(name)?);
+        }
+        nodes.push(Node {
+            stage: stage_name.to_string(),
+            step: step.clone(),
+            needs,
+            watches,
+            finally: stage_index.is_none(),
+        });
+    }
+    Ok(nodes)
+}
+
+/// The order the steps run in: every step after the steps it needs, the
+/// `finally` steps last, and otherwise as declared.
+fn order(nodes: &[Node]) -> CommandResult<Vec<usize>> {
+    let mut done = vec![false; nodes.len()];
+    let mut order = Vec::with_capacity(nodes.len());
+    for finally in [false, true] {
+        loop {
+            let next = (0..nodes.len()).find(|&i| {
+                !done[i]
+                    && nodes[i].finally == finally
+                    && nodes[i].needs.iter().all(|&need| done[need])
+            });
+            let Some(next) = next else {
+                break;
+            };
+            done[next] = true;
+            order.push(next);
+        }
+    }
+    let blocked: Vec<&str> = nodes
+        .iter()
+        .zip(&done)
+        .filter(|(_, done)| !**done)
+        .map(|(node, _)| node.step.name.as_str())
+        .collect();
+    if blocked.is_empty() {
+        Ok(order)
+    } else {
+        Err(format!("steps form a dependency cycle: {}", blocked.join(", ")).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn spec(value: serde_json::Value) -> Spec {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn names(nodes: &[Node], positions: &[usize]) -> Vec<String> {
+        positions
+            .iter()
+            .map(|&position| nodes[position].step.name.clone())
+            .collect()
+    }
+
+    /// Runs nothing: the listed steps fail, the others succeed.
+    struct Scripted {
+        failing: Vec<&'static str>,
+        /// The steps run, with the outcome they were told.
+        ran: Vec<(String, StepPhase)>,
+        outputs: HashMap<String, BTreeMap<String, String>>,
+    }
+
+    impl Scripted {
+        fn new(failing: Vec<&'static str>) -> Self {
+            Scripted {
+                failing,
+                ran: Vec::new(),
+                outputs: HashMap::new(),
+            }
+        }
+    }
+
+    impl Executor for Scripted {
+        fn outputs(&self) -> &HashMap<String, BTreeMap<String, String>> {
+            &self.outputs
+        }
+
+        fn run_step(
+            &mut self,
+            node: &Node,
+            outcome: StepPhase,
+        ) -> CommandResult<(StepPhase, u32, String)> {
+            self.ran.push((node.step.name.clone(), outcome));
+            if self.failing.contains(&node.step.name.as_str()) {
+                Ok((StepPhase::Failed, 1, "exited with code 1".to_string()))
+            } else {
+                Ok((StepPhase::Succeeded, 1, String::new()))
+            }
+        }
+    }
+
+    fn execute_spec(spec: &Spec, executor: &mut Scripted) -> (Vec<StepReport>, Option<String>) {
+        let (spec, groups) = expand(spec).unwrap();
+        let nodes = build(&spec, &groups).unwrap();
+        let order = order(&nodes).unwrap();
+        execute(&nodes, &order, &groups, executor).unwrap()
+    }
+
+    #[test]
+    fn test_step_order() {
+        let spec = spec(json!({
+            "stages": [
+                { "name": "build", "steps": [
+                    { "name": "package", "image": "alpine", "needs": ["compile"] },
+                    { "name": "compile", "image": "alpine" },
+                    { "name": "lint", "image": "alpine" }
+                ] },
+                { "name": "test", "steps": [
+                    { "name": "unit", "image": "alpine" },
+                    { "name": "e2e", "image": "alpine", "needs": ["package"] }
+                ] }
+            ],
+            "finally": [{ "name": "cleanup", "image": "alpine" }]
+        }));
+        let nodes = build(&spec, &HashMap::new()).unwrap();
+        // Without `needs`, a step waits for the whole previous stage.
+        assert_eq!(
+            names(&nodes, &nodes[3].needs),
+            vec!["package", "compile", "lint"]
+        );
+        assert_eq!(names(&nodes, &nodes[4].needs), vec!["package"]);
+        assert!(nodes[5].finally);
+        assert_eq!(nodes[5].stage, FINALLY_STAGE);
+        assert_eq!(
+            names(&nodes, &order(&nodes).unwrap()),
+            vec!["compile", "package", "lint", "unit", "e2e", "cleanup"]
+        );
+
+        let cycle = self::spec(json!({ "stages": [{ "name": "build", "steps": [
+            { "name": "a", "image": "alpine", "needs": ["b"] },
+            { "name": "b", "image": "alpine", "needs": ["a"] }
+        ] }] }));
+        let error = order(&build(&cycle, &HashMap::new()).unwrap()).unwrap_err();
+        assert_eq!(error.to_string(), "steps form a dependency cycle: a, b");
+    }
+
+    #[test]
+    fn test_needs() {
+        let spec = spec(json!({
+            "stages": [
+                { "name": "build", "steps": [{
+                    "name": "build",
+                    "image": "golang:$(matrix.go)",
+                    "matrix": { "params": { "go": ["1.21", "1.22"] } }
+                }] },
+                { "name": "release", "steps": [
+                    { "name": "publish", "image": "alpine", "needs": ["build"] },
+                    {
+                        "name": "notify",
+                        "image": "alpine",
+                        "needs": ["publish"],
+                        "when": [{ "input": "$(steps.build-1-21.status)", "values": ["Failed"] }]
+                    }
+                ] }
+            ]
+        }));
+        let (expanded, groups) = expand(&spec).unwrap();
+        let nodes = build(&expanded, &groups).unwrap();
+        assert_eq!(
+            names(&nodes, &(0..nodes.len()).collect::<Vec<_>>()),
+            vec!["build-1-21", "build-1-22", "publish", "notify"]
+        );
+        assert_eq!(nodes[1].step.image, "golang:1.22");
+        // Needing a matrix step needs all of its combinations.
+        assert_eq!(
+            names(&nodes, &nodes[2].needs),
+            vec!["build-1-21", "build-1-22"]
+        );
+        // A `when` reference is waited for like a need.
+        assert_eq!(
+            names(&nodes, &nodes[3].needs),
+            vec!["publish", "build-1-21"]
+        );
+
+        let unknown = self::spec(json!({ "stages": [{ "name": "build", "steps": [
+            { "name": "a", "image": "alpine", "needs": ["missing"] }
+        ] }] }));
+        assert_eq!(
+            build(&unknown, &HashMap::new()).unwrap_err().to_string(),
+            "step 'a' needs unknown step 'missing'"
+        );
+        let needs_finally = self::spec(json!({
+            "stages": [{ "name": "build", "steps": [
+                { "name": "a", "image": "alpine", "needs": ["cleanup"] }
+            ] }],
+            "finally": [{ "name": "cleanup", "image": "alpine" }]
+        }));
+        assert_eq!(
+            build(&needs_finally, &HashMap::new())
+                .unwrap_err()
+                .to_string(),
+            "step 'a' cannot need finally step 'cleanup'"
+        );
+    }
+
+    #[test]
+    fn test_failure_propagation() {
+        let spec = spec(json!({
+            "stages": [
+                { "name": "build", "steps": [
+                    { "name": "lint", "image": "alpine", "continueOnError": true },
+                    { "name": "compile", "image": "alpine" }
+                ] },
+                { "name": "test", "steps": [{ "name": "unit", "image": "alpine" }] }
+            ],
+            "finally": [{ "name": "report", "image": "alpine" }]
+        }));
+
+        let mut executor = Scripted::new(vec!["lint"]);
+        let (reports, failure) = execute_spec(&spec, &mut executor);
+        // A failure with `continueOnError` does not fail the run.
+        assert_eq!(failure, None);
+        assert_eq!(reports[0].phase, StepPhase::Failed);
+        assert_eq!(reports[0].message, "exited with code 1 (ignored)");
+        assert_eq!(executor.ran.len(), 4);
+        assert_eq!(
+            executor.ran[3],
+            ("report".to_string(), StepPhase::Succeeded)
+        );
+
+        let mut executor = Scripted::new(vec!["compile"]);
+        let (reports, failure) = execute_spec(&spec, &mut executor);
+        assert_eq!(failure.as_deref(), Some("compile"));
+        assert_eq!(reports[2].name, "unit");
+        assert_eq!(reports[2].phase, StepPhase::Skipped);
+        assert_eq!(reports[2].message, "not run because step 'compile' failed");
+        // The finally steps still run, told that the run failed.
+        let ran: Vec<&str> = executor.ran.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(ran, vec!["lint", "compile", "report"]);
+        assert_eq!(executor.ran[2].1, StepPhase::Failed);
+
+        // A condition that cannot be evaluated fails its step.
+        let mut invalid = spec.clone();
+        invalid.stages[1].steps[0].when = vec![crate::pipeline::WhenCondition {
+            input: Some("$(steps.compile.outputs.version)".to_string()),
+            values: vec!["1.*".to_string()],
+            ..Default::default()
+        }];
+        let mut executor = Scripted::new(Vec::new());
+        let (reports, failure) = execute_spec(&invalid, &mut executor);
+        assert_eq!(failure.as_deref(), Some("unit"));
+        assert_eq!(reports[2].phase, StepPhase::Failed);
+        assert_eq!(reports[2].attempts, 0);
+    }
+
+    #[test]
+    fn test_when_conditions() {
+        let spec = spec(json!({
+            "stages": [{ "name": "build", "steps": [
+                { "name": "lint", "image": "alpine", "continueOnError": true },
+                {
+                    "name": "fix",
+                    "image": "alpine",
+                    "needs": [],
+                    "when": [{ "input": "$(steps.lint.status)", "values": ["Failed"] }]
+                },
+                {
+                    "name": "deploy",
+                    "image": "alpine",
+                    "when": [{ "input": "$(steps.lint.status)", "operator": "notIn", "values": ["Failed"] }]
+                }
+            ] }]
+        }));
+        let mut executor = Scripted::new(vec!["lint"]);
+        let (reports, failure) = execute_spec(&spec, &mut executor);
+        assert_eq!(failure, None);
+        assert_eq!(reports[1].phase, StepPhase::Succeeded);
+        assert_eq!(reports[2].phase, StepPhase::Skipped);
+        assert_eq!(
+            reports[2].message,
+            "Skipped because '$(steps.lint.status)' (Failed) is in [Failed]."
+        );
+    }
+
+    #[test]
+    fn test_status_conditions() {
+        let spec = spec(json!({
+            "stages": [
+                { "name": "build", "steps": [
+                    {
+                        "name": "compile",
+                        "image": "alpine",
+                        "matrix": { "params": { "os": ["linux", "macos"] } }
+                    },
+                    { "name": "lint", "image": "alpine" }
+                ] },
+                { "name": "test", "steps": [
+                    {
+                        "name": "rollback",
+                        "image": "alpine",
+                        "when": [{ "input": "$(steps.compile.status)", "values": ["Failed"] }]
+                    },
+                    { "name": "unit", "image": "alpine" },
+                    {
+                        "name": "publish",
+                        "image": "alpine",
+                        "needs": ["compile"],
+                        "when": [{ "input": "$(steps.lint.status)", "values": ["Succeeded"] }]
+                    }
+                ] }
+            ]
+        }));
+        let mut executor = Scripted::new(vec!["compile-macos"]);
+        let (reports, failure) = execute_spec(&spec, &mut executor);
+        assert_eq!(failure.as_deref(), Some("compile-macos"));
+        // A step checking the status of the failed step still runs.
+        let ran: Vec<&str> = executor.ran.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(ran, vec!["compile-linux", "compile-macos", "rollback"]);
+        let report = |name: &str| reports.iter().find(|report| report.name == name).unwrap();
+        assert_eq!(report("rollback").phase, StepPhase::Succeeded);
+        assert_eq!(report("lint").phase, StepPhase::Skipped);
+        assert_eq!(report("unit").phase, StepPhase::Skipped);
+        // Unless a step it waits for without checking its status failed.
+        assert_eq!(report("publish").phase, StepPhase::Skipped);
+        assert_eq!(
+            report("publish").message,
+            "not run because step 'compile-macos' failed"
+        );
+    }
+
+    #[test]
+    fn test_resolve_env() {
+        let spec = spec(json!({
+            "stages": [{ "name": "deploy", "steps": [{
+                "name": "deploy",
+                "image": "alpine",
+                "command": ["sh", "-c"],
+                "args": ["deploy $(TARGET) --token $$TOKEN $(UNKNOWN)"],
+                "env": [
+                    { "name": "VERSION", "value": "$(steps.build.outputs.version)" },
+                    { "name": "TARGET", "value": "registry/app:$(VERSION)" },
+                    { "name": "TOKEN", "valueFrom": { "secretKeyRef": { "name": "ci", "key": "token" } } }
+                ]
+            }] }],
+            "finally": [{ "name": "report", "image": "alpine" }]
+        }));
+        let nodes = build(&spec, &HashMap::new()).unwrap();
+        let options = Options {
+            values: BTreeMap::from([("token".to_string(), "s3cr3t".to_string())]),
+            ..Default::default()
+        };
+        let mut runner = Runner {
+            options: &options,
+            run_id: "ph-test".to_string(),
+            shared: None,
+            volumes: Vec::new(),
+            outputs: HashMap::from([(
+                "build".to_string(),
+                BTreeMap::from([("version".to_string(), "1.4.0".to_string())]),
+            )]),
+        };
+
+        let (image, command, args, env) = runner.resolve(&nodes[0], StepPhase::Succeeded).unwrap();
+        assert_eq!(image, "alpine");
+        assert_eq!(command, vec!["sh", "-c"]);
+        // `$$` escapes a reference and unknown references are kept.
+        assert_eq!(
+            args,
+            vec!["deploy registry/app:1.4.0 --token $TOKEN $(UNKNOWN)"]
+        );
+        let value = |name: &str| {
+            env.iter()
+                .find(|(var, _)| var == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(value("PH_WORKSPACE"), Some(WORKSPACE_MOUNT));
+        assert_eq!(value("TARGET"), Some("registry/app:1.4.0"));
+        assert_eq!(value("TOKEN"), Some("s3cr3t"));
+        assert_eq!(value("PH_PIPELINE_RESULT"), None);
+
+        let (_, _, _, env) = runner.resolve(&nodes[1], StepPhase::Failed).unwrap();
+        assert!(env.contains(&("PH_PIPELINE_RESULT".to_string(), "Failed".to_string())));
+
+        runner.outputs.clear();
+        let error = runner.resolve(&nodes[0], StepPhase::Succeeded).unwrap_err();
+        assert_eq!(
+            error,
+            "'$(steps.build.outputs.version)' does not refer to a captured output"
+        );
+    }
+}
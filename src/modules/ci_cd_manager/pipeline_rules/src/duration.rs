@@ -0,0 +1,70 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/duration.rs - The durations of pipeline specs.
+*
+* Step timeouts and retry backoffs, preview TTLs and the like are written
+* as a number and a unit, e.g. "30s" or "7d" (`parse_duration`). A failed
+* step waits longer before every new attempt (`retry_delay`).
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use std::time::Duration;
+
+/// The wait before the first retry of a step without `retryBackoff`.
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_secs(10);
+/// The longest wait between two attempts.
+pub const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(600);
+
+/// Parses a duration such as "30s", "10m", "1h" or "7d". Fails on durations
+/// too long to represent.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(value.len() - value.chars().last()?.len_utf8());
+    let number: u64 = number.parse().ok()?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    number.checked_mul(unit_secs).map(Duration::from_secs)
+}
+
+/// The wait before attempt `attempt + 1` of a step whose `retryBackoff` is
+/// `backoff`, after `attempt` failed ones: the backoff doubles with every
+/// retry.
+pub fn retry_delay(backoff: Option<&str>, attempt: u32) -> Duration {
+    let base = backoff
+        .and_then(parse_duration)
+        .unwrap_or(DEFAULT_RETRY_BACKOFF);
+    let factor = 1u32 << attempt.saturating_sub(1).min(16);
+    base.saturating_mul(factor).min(MAX_RETRY_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10m"), Some(Duration::from_secs(600)));
+        assert_eq!(parse_duration(" 2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("3d"), Some(Duration::from_secs(259200)));
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("1w"), None);
+        assert_eq!(parse_duration(""), None);
+        // Too long: the number of seconds overflows.
+        assert_eq!(parse_duration(&format!("{}d", u64::MAX / 86400 + 1)), None);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(None, 1), Duration::from_secs(10));
+        assert_eq!(retry_delay(None, 3), Duration::from_secs(40));
+        assert_eq!(retry_delay(Some("1m"), 2), Duration::from_secs(120));
+        assert_eq!(retry_delay(Some("5m"), 2), MAX_RETRY_BACKOFF);
+        // An invalid backoff falls back to the default.
+        assert_eq!(retry_delay(Some("soon"), 1), DEFAULT_RETRY_BACKOFF);
+    }
+}
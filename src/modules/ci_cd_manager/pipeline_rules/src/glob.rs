@@ -0,0 +1,50 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/glob.rs - The globs of `when` conditions and trigger filters.
+*
+* Branches, tags and changed files are matched with the same globs in
+* `when` conditions and in the filters of phTriggers and phPreviewTriggers.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+/// Matches `text` against a glob: `*` matches within a path segment, `**`
+/// across segments and `?` a single character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(pattern: &[u8], text: &[u8]) -> bool {
+        match pattern {
+            [] => text.is_empty(),
+            [b'*', b'*', rest @ ..] => match rest.strip_prefix(b"/") {
+                // `**/` stands for zero or more whole segments.
+                Some(rest) => (0..=text.len())
+                    .filter(|&i| i == 0 || text[i - 1] == b'/')
+                    .any(|i| matches(rest, &text[i..])),
+                None => (0..=text.len()).any(|i| matches(rest, &text[i..])),
+            },
+            [b'*', rest @ ..] => (0..=text.len())
+                .take_while(|&i| i == 0 || text[i - 1] != b'/')
+                .any(|i| matches(rest, &text[i..])),
+            [b'?', rest @ ..] => {
+                text.first().is_some_and(|&c| c != b'/') && matches(rest, &text[1..])
+            }
+            [c, rest @ ..] => text.first() == Some(c) && matches(rest, &text[1..]),
+        }
+    }
+    matches(pattern.as_bytes(), text.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("main", "main"));
+        assert!(glob_match("release/*", "release/1.2"));
+        assert!(!glob_match("release/*", "release/1.2/hotfix"));
+        assert!(glob_match("services/api/**", "services/api/src/main.rs"));
+        assert!(glob_match("**/*.md", "docs/guide/setup.md"));
+        assert!(glob_match("**/*.md", "README.md"));
+        assert!(!glob_match("docs/**/setup.md", "docs/guide/mysetup.md"));
+        assert!(glob_match("v?.*", "v1.4"));
+        assert!(!glob_match("v?.*", "v10.4"));
+    }
+}
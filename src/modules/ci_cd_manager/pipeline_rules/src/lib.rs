@@ -0,0 +1,26 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/lib.rs - The rules phPipelines run by.
+*
+* The operator (`k8s/operators/ph_operator`) runs phPipelines as Jobs and
+* `ph ci run` (the `workflow_parser` crate) runs them on the local Docker
+* engine. Both take the rules below from this crate, so a pipeline behaves
+* the same on a laptop as in the cluster:
+* - `model`: the types of the pipeline spec and status the rules work on.
+* - `duration`: the durations of specs and the backoff between retries.
+* - `glob`: the globs of `when` conditions and trigger filters.
+* - `names`: names that fit Kubernetes' limits.
+* - `references`: `$(<prefix>.<name>)` references and step outputs.
+* - `matrix`: the combinations of matrix steps and their names.
+* - `when`: the conditions that decide whether a step runs.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+pub mod duration;
+pub mod glob;
+pub mod matrix;
+pub mod model;
+pub mod names;
+pub mod references;
+pub mod when;
+
+pub use model::{StepMatrix, StepPhase, WhenCondition, WhenOperator};
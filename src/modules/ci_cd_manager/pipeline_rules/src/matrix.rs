@@ -0,0 +1,246 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/matrix.rs - The combinations of matrix steps.
+*
+* A matrix step runs once for every combination of its parameters:
+* `combinations` builds the cross product of the values, drops the
+* combinations matched by an `exclude` entry and appends the `include`
+* ones. `instances` names the step of every combination after the step and
+* its values, shortened to fit a container name, and `instantiate` copies
+* the step for a combination with `$(matrix.<name>)` replaced everywhere.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use crate::model::StepMatrix;
+use crate::names::bounded_name;
+use crate::references::substitute_references_in;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// The largest number of combinations a single step may expand to.
+pub const MAX_COMBINATIONS: usize = 256;
+
+/// The values of a combination, by parameter name.
+pub type Combination = BTreeMap<String, String>;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MatrixError {
+    #[error("matrix of step '{0}' has no combinations")]
+    Empty(String),
+    #[error("matrix of step '{step}' has {count} combinations, more than {max}")]
+    TooLarge {
+        step: String,
+        count: usize,
+        max: usize,
+    },
+    #[error("'$(matrix.{name})' in step '{step}' is not a parameter of the combination")]
+    UnresolvedReference { step: String, name: String },
+    #[error("matrix step '{step}' is invalid: {message}")]
+    InvalidStep { step: String, message: String },
+    #[error("two combinations of matrix step '{step}' are both named '{name}'")]
+    DuplicateName { step: String, name: String },
+}
+
+/// Returns the combinations a matrix runs for, in a stable order.
+pub fn combinations(matrix: &StepMatrix) -> Vec<Combination> {
+    let mut product = vec![BTreeMap::new()];
+    if matrix.params.is_empty() {
+        product.clear();
+    }
+    for (name, values) in &matrix.params {
+        product = product
+            .into_iter()
+            .flat_map(|combination| {
+                values.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.insert(name.clone(), value.clone());
+                    combination
+                })
+            })
+            .collect();
+    }
+    product.retain(|combination| {
+        !matrix.exclude.iter().any(|exclude| {
+            exclude
+                .iter()
+                .all(|(name, value)| combination.get(name) == Some(value))
+        })
+    });
+    product.extend(matrix.include.iter().cloned());
+    product
+}
+
+/// Returns the combinations of the matrix of `step`, each with the name of
+/// the step that runs it. Fails when there are none or too many, or when
+/// two of them get the same name.
+pub fn instances(
+    step: &str,
+    matrix: &StepMatrix,
+) -> Result<Vec<(String, Combination)>, MatrixError> {
+    let combinations = combinations(matrix);
+    if combinations.is_empty() {
+        return Err(MatrixError::Empty(step.to_string()));
+    }
+    if combinations.len() > MAX_COMBINATIONS {
+        return Err(MatrixError::TooLarge {
+            step: step.to_string(),
+            count: combinations.len(),
+            max: MAX_COMBINATIONS,
+        });
+    }
+    let mut instances: Vec<(String, Combination)> = Vec::with_capacity(combinations.len());
+    for combination in combinations {
+        let name = combination_name(step, &combination);
+        if instances.iter().any(|(other, _)| *other == name) {
+            return Err(MatrixError::DuplicateName {
+                step: step.to_string(),
+                name,
+            });
+        }
+        instances.push((name, combination));
+    }
+    Ok(instances)
+}
+
+/// The name of the step running `combination` of `step`: the step's name
+/// followed by the values, reduced to characters valid in a Job name and
+/// shortened to fit a container name.
+fn combination_name(step: &str, combination: &Combination) -> String {
+    let mut name = step.to_string();
+    for value in combination.values() {
+        let value: String = value
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let value = value.trim_matches('-');
+        if !value.is_empty() {
+            name.push('-');
+            name.push_str(value);
+        }
+    }
+    bounded_name(&name)
+}
+
+/// Copies `template`, a step of matrix step `step` without its matrix, for
+/// one combination, substituting `$(matrix.<name>)`.
+pub fn instantiate<T: Serialize + DeserializeOwned>(
+    template: &T,
+    step: &str,
+    combination: &Combination,
+) -> Result<T, MatrixError> {
+    let invalid = |e: serde_json::Error| MatrixError::InvalidStep {
+        step: step.to_string(),
+        message: e.to_string(),
+    };
+    let mut value = serde_json::to_value(template).map_err(invalid)?;
+    substitute_references_in(&mut value, "matrix.", &mut |name| {
+        combination
+            .get(name)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| MatrixError::UnresolvedReference {
+                step: step.to_string(),
+                name: name.to_string(),
+            })
+    })?;
+    serde_json::from_value(value).map_err(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn combination(values: &[(&str, &str)]) -> Combination {
+        values
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn matrix(value: serde_json::Value) -> StepMatrix {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_combinations() {
+        let matrix = matrix(json!({
+            "params": { "os": ["linux", "windows"], "toolchain": ["stable", "nightly"] },
+            "exclude": [{ "os": "windows", "toolchain": "nightly" }],
+            "include": [{ "os": "macos", "toolchain": "stable" }]
+        }));
+        assert_eq!(
+            combinations(&matrix),
+            vec![
+                combination(&[("os", "linux"), ("toolchain", "stable")]),
+                combination(&[("os", "linux"), ("toolchain", "nightly")]),
+                combination(&[("os", "windows"), ("toolchain", "stable")]),
+                combination(&[("os", "macos"), ("toolchain", "stable")]),
+            ]
+        );
+        assert!(combinations(&StepMatrix::default()).is_empty());
+    }
+
+    #[test]
+    fn test_instances() {
+        let names = |value| {
+            instances("build", &matrix(value)).map(|instances| {
+                instances
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            names(json!({ "params": { "target": ["x86_64/Linux", "_wasm_"] } })).unwrap(),
+            vec!["build-x86-64-linux", "build-wasm"]
+        );
+        assert_eq!(
+            names(json!({ "params": {} })),
+            Err(MatrixError::Empty("build".to_string()))
+        );
+        let values: Vec<String> = (0..300).map(|value| value.to_string()).collect();
+        assert!(matches!(
+            names(json!({ "params": { "shard": values } })),
+            Err(MatrixError::TooLarge { count: 300, .. })
+        ));
+
+        // Values reduced to the same name are rejected.
+        assert_eq!(
+            names(json!({ "params": { "target": ["a.b", "a-b"] } })),
+            Err(MatrixError::DuplicateName {
+                step: "build".to_string(),
+                name: "build-a-b".to_string()
+            })
+        );
+
+        // Long names are shortened, and stay distinct.
+        let long = "x".repeat(80);
+        let long =
+            names(json!({ "params": { "target": [format!("{}1", long), format!("{}2", long)] } }))
+                .unwrap();
+        assert!(long
+            .iter()
+            .all(|name| name.len() == 63 && name.starts_with("build-xxx")));
+        assert_ne!(long[0], long[1]);
+    }
+
+    #[test]
+    fn test_instantiate() {
+        let step = json!({ "name": "test", "image": "rust:$(matrix.toolchain)" });
+        let values = combination(&[("toolchain", "nightly")]);
+        assert_eq!(
+            instantiate(&step, "test", &values).unwrap(),
+            json!({ "name": "test", "image": "rust:nightly" })
+        );
+        assert_eq!(
+            instantiate(&json!(["$(matrix.os)"]), "test", &values),
+            Err(MatrixError::UnresolvedReference {
+                step: "test".to_string(),
+                name: "os".to_string()
+            })
+        );
+    }
+}
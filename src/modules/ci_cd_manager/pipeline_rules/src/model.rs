@@ -0,0 +1,88 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/model.rs - The pipeline types the rules work on.
+*
+* These are part of the phPipeline CRD (`k8s/operators/ph_operator/src/crds.rs`
+* re-exports them), and of the manifests `ph ci convert` writes and
+* `ph ci run` reads. With the `schema` feature, they derive the JSON schema
+* the CRD is generated from.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The combinations a step runs for. Each combination becomes a step named
+/// after the step and its values (e.g. `test-linux-stable`), which sees the
+/// values as `$(matrix.<name>)`. Steps that need the step wait for all of
+/// its combinations.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct StepMatrix {
+    /// The values of every matrix parameter.
+    pub params: BTreeMap<String, Vec<String>>,
+    /// Combinations to leave out. An entry removes every combination that
+    /// has all of its values.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<BTreeMap<String, String>>,
+    /// Extra combinations, run in addition to the generated ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<BTreeMap<String, String>>,
+}
+
+/// A condition of a step. Set either `input` with `values`, or
+/// `changedPaths`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct WhenCondition {
+    /// The value to test, e.g. "$(params.branch)" in a template,
+    /// "$(matrix.os)", "$(steps.<step>.status)" (the step's phase) or
+    /// "$(steps.<step>.outputs.<name>)". Referenced steps run first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    #[serde(default)]
+    pub operator: WhenOperator,
+    /// Globs the input is matched against, e.g. "release/*".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+    /// Holds when at least one changed file matches one of these globs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changed_paths: Vec<String>,
+}
+
+/// How the input of a condition is compared with its values.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum WhenOperator {
+    /// The input matches one of the values.
+    #[default]
+    In,
+    /// The input matches none of the values.
+    NotIn,
+}
+
+/// The phases of a single pipeline step.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "PascalCase")]
+pub enum StepPhase {
+    /// Waiting for its dependencies or for a free slot.
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    /// Not run because the pipeline failed or was cancelled first.
+    Skipped,
+    /// Killed because the pipeline was cancelled.
+    Cancelled,
+}
+
+impl fmt::Display for StepPhase {
+    /// Writes the phase as it appears in the status, e.g. "Failed".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
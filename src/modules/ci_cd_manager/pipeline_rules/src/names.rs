@@ -0,0 +1,59 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/names.rs - Names that fit Kubernetes' limits.
+*
+* Step names become container names, and with the pipeline's name Job
+* names; both are limited to 63 characters. Longer names are shortened
+* with a hash of the whole name, so that they stay distinct.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+/// The longest name of a container, and of most Kubernetes objects.
+pub const MAX_NAME_LENGTH: usize = 63;
+
+/// Returns the 64-bit FNV-1a hash of `data` as 16 hex digits.
+///
+/// Unlike `std::hash::DefaultHasher`, the result is stable across Rust
+/// releases, so it can be persisted in resource status for change detection.
+/// It is not a cryptographic hash.
+pub fn content_hash(data: &[u8]) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let hash = data.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Shortens `name` to `MAX_NAME_LENGTH` characters. A longer name keeps its
+/// beginning and ends with a hash of the whole name, so that two long names
+/// with the same beginning stay distinct.
+pub fn bounded_name(name: &str) -> String {
+    if name.len() <= MAX_NAME_LENGTH {
+        return name.to_string();
+    }
+    let hash = &content_hash(name.as_bytes())[..8];
+    let mut end = MAX_NAME_LENGTH - hash.len() - 1;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}-{}", name[..end].trim_end_matches('-'), hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded_name() {
+        assert_eq!(bounded_name("build-s0-test"), "build-s0-test");
+        let long = format!("{}-a", "x".repeat(70));
+        let other = format!("{}-b", "x".repeat(70));
+        assert_eq!(bounded_name(&long).len(), MAX_NAME_LENGTH);
+        assert!(bounded_name(&long).starts_with(&"x".repeat(54)));
+        assert_ne!(bounded_name(&long), bounded_name(&other));
+        assert_eq!(
+            bounded_name(&"x".repeat(MAX_NAME_LENGTH)),
+            "x".repeat(MAX_NAME_LENGTH)
+        );
+    }
+}
@@ -0,0 +1,156 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/references.rs - `$(<prefix>.<name>)` references in pipeline specs.
+*
+* Template parameters (`$(params.<name>)`), matrix values
+* (`$(matrix.<name>)`) and step outputs (`$(steps.<step>.outputs.<name>)`)
+* are all written as references, which are replaced in a string
+* (`substitute_references`) or in every string of a step
+* (`substitute_references_in`). Other `$(...)` expressions, such as
+* Kubernetes variable references, are left alone.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+/// A `$(steps.<step>.outputs.<name>)` reference to an output that was not
+/// captured, because the step did not run yet or does not declare it.
+#[derive(Debug, Error, PartialEq)]
+#[error("'$(steps.{step}.outputs.{output})' does not refer to a captured output")]
+pub struct UnresolvedOutput {
+    pub step: String,
+    pub output: String,
+}
+
+/// Replaces every `$(<prefix><reference>)` in `text` with the value `resolve`
+/// returns for the reference. References resolved to `None`, and all other
+/// `$(...)` expressions, are left as written.
+pub fn substitute_references<E>(
+    text: &str,
+    prefix: &str,
+    mut resolve: impl FnMut(&str) -> Result<Option<String>, E>,
+) -> Result<String, E> {
+    let open = format!("$({}", prefix);
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(&open) {
+        let Some(len) = rest[start..].find(')') else {
+            break;
+        };
+        let reference = &rest[start + open.len()..start + len];
+        result.push_str(&rest[..start]);
+        match resolve(reference)? {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[start..start + len + 1]),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Applies `substitute_references` to every string in `value`.
+pub fn substitute_references_in<E>(
+    value: &mut Value,
+    prefix: &str,
+    resolve: &mut impl FnMut(&str) -> Result<Option<String>, E>,
+) -> Result<(), E> {
+    match value {
+        Value::String(text) => *text = substitute_references(text, prefix, &mut *resolve)?,
+        Value::Array(items) => {
+            for item in items {
+                substitute_references_in(item, prefix, resolve)?;
+            }
+        }
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                substitute_references_in(field, prefix, resolve)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replaces every `$(steps.<step>.outputs.<name>)` in `text` with the
+/// captured value.
+pub fn substitute_outputs(
+    text: &str,
+    outputs: &HashMap<String, BTreeMap<String, String>>,
+) -> Result<String, UnresolvedOutput> {
+    substitute_references(text, "steps.", |reference| {
+        resolve_output(reference, outputs)
+    })
+}
+
+/// Resolves a `<step>.outputs.<name>` reference to the captured value. Other
+/// step references resolve to `None`.
+pub fn resolve_output(
+    reference: &str,
+    outputs: &HashMap<String, BTreeMap<String, String>>,
+) -> Result<Option<String>, UnresolvedOutput> {
+    let Some((step, output)) = reference.split_once(".outputs.") else {
+        return Ok(None);
+    };
+    outputs
+        .get(step)
+        .and_then(|values| values.get(output))
+        .cloned()
+        .map(Some)
+        .ok_or_else(|| UnresolvedOutput {
+            step: step.to_string(),
+            output: output.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_substitute_references() {
+        let mut step = json!({
+            "image": "rust:$(matrix.toolchain)",
+            "args": ["--target", "$(matrix.target)", "$(HOME)", "$(matrix.os"],
+            "retries": 2
+        });
+        let values = HashMap::from([("toolchain", "1.79"), ("target", "wasm32")]);
+        substitute_references_in(&mut step, "matrix.", &mut |name| {
+            Ok::<_, ()>(values.get(name).map(|value| value.to_string()))
+        })
+        .unwrap();
+        assert_eq!(
+            step,
+            json!({
+                "image": "rust:1.79",
+                "args": ["--target", "wasm32", "$(HOME)", "$(matrix.os"],
+                "retries": 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_substitute_outputs() {
+        let outputs = HashMap::from([(
+            "build".to_string(),
+            BTreeMap::from([("image".to_string(), "app:1f2e3d".to_string())]),
+        )]);
+        assert_eq!(
+            substitute_outputs(
+                "scan $(steps.build.outputs.image) $(steps.build.status)",
+                &outputs
+            )
+            .unwrap(),
+            "scan app:1f2e3d $(steps.build.status)"
+        );
+        assert_eq!(
+            substitute_outputs("$(steps.test.outputs.report)", &outputs),
+            Err(UnresolvedOutput {
+                step: "test".to_string(),
+                output: "report".to_string()
+            })
+        );
+    }
+}
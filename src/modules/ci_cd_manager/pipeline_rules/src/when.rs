@@ -0,0 +1,303 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* src/when.rs - The `when` conditions that decide whether a step runs.
+*
+* - A condition either matches its `input` against glob `values` (`in` or
+*   `notIn`), or checks that a changed file matches one of its
+*   `changedPaths`. Template parameters and matrix values are already
+*   substituted in the input; `$(steps.<step>.status)` and
+*   `$(steps.<step>.outputs.<name>)` are resolved here. The status of a
+*   matrix step sums up the steps expanded from it: it failed if one of them
+*   failed, and succeeded once all of them succeeded or were skipped.
+* - `references` lists the steps the conditions refer to, so the step waits
+*   for them. A step whose status a condition checks only has to finish, so
+*   that e.g. a step can run because another one failed
+*   (`status_references`).
+* - `evaluate` is called once the step may start and returns why it is
+*   skipped, if a condition does not hold.
+*
+* SPDX-License-Identifier: Apache-2.0 */
+
+use crate::glob::glob_match;
+use crate::model::{StepPhase, WhenCondition, WhenOperator};
+use crate::references::{resolve_output, substitute_references, UnresolvedOutput};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum WhenError {
+    #[error(
+        "condition {index} of step '{step}' must set exactly one of 'input' and 'changedPaths'"
+    )]
+    InvalidCondition { step: String, index: usize },
+    #[error("condition of step '{step}': {source}")]
+    Unresolved {
+        step: String,
+        source: UnresolvedOutput,
+    },
+}
+
+/// What the conditions of a step are evaluated against.
+pub struct WhenContext<'a> {
+    /// The phase of every step, by name.
+    pub phases: HashMap<&'a str, StepPhase>,
+    /// The steps expanded from every matrix step, by the matrix step's name.
+    pub groups: &'a HashMap<String, Vec<String>>,
+    /// The output values of the finished steps, by step name.
+    pub outputs: &'a HashMap<String, BTreeMap<String, String>>,
+    /// The files changed by the commit, when known.
+    pub changed_paths: Option<&'a [String]>,
+}
+
+/// Checks that every condition of `step` sets exactly one of its two forms.
+pub fn validate(step: &str, conditions: &[WhenCondition]) -> Result<(), WhenError> {
+    for (index, condition) in conditions.iter().enumerate() {
+        if condition.input.is_some() != condition.changed_paths.is_empty() {
+            return Err(WhenError::InvalidCondition {
+                step: step.to_string(),
+                index,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The steps whose status or outputs `conditions` refer to.
+pub fn references(conditions: &[WhenCondition]) -> Vec<&str> {
+    step_references(conditions, false)
+}
+
+/// The steps whose status `conditions` check.
+pub fn status_references(conditions: &[WhenCondition]) -> Vec<&str> {
+    step_references(conditions, true)
+}
+
+fn step_references(conditions: &[WhenCondition], status_only: bool) -> Vec<&str> {
+    const OPEN: &str = "$(steps.";
+    let mut names = Vec::new();
+    for input in conditions
+        .iter()
+        .filter_map(|condition| condition.input.as_deref())
+    {
+        let mut rest = input;
+        while let Some(start) = rest.find(OPEN) {
+            rest = &rest[start + OPEN.len()..];
+            let Some(end) = rest.find(['.', ')']) else {
+                break;
+            };
+            let name = &rest[..end];
+            if (!status_only || rest[end..].starts_with(".status)")) && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Returns why `step` is skipped, or `None` when all its `conditions` hold.
+/// Without the list of changed files, `changedPaths` conditions hold.
+pub fn evaluate(
+    step: &str,
+    conditions: &[WhenCondition],
+    context: &WhenContext<'_>,
+) -> Result<Option<String>, WhenError> {
+    for condition in conditions {
+        if let Some(input) = &condition.input {
+            let value = resolve(input, context).map_err(|source| WhenError::Unresolved {
+                step: step.to_string(),
+                source,
+            })?;
+            let matched = condition.values.iter().any(|glob| glob_match(glob, &value));
+            if matched != (condition.operator == WhenOperator::In) {
+                let relation = if matched { "is in" } else { "is not in" };
+                return Ok(Some(format!(
+                    "Skipped because '{}' ({}) {} [{}].",
+                    input,
+                    value,
+                    relation,
+                    condition.values.join(", ")
+                )));
+            }
+        } else if let Some(changed) = context.changed_paths {
+            let touched = changed.iter().any(|path| {
+                condition
+                    .changed_paths
+                    .iter()
+                    .any(|glob| glob_match(glob, path))
+            });
+            if !touched {
+                return Ok(Some(format!(
+                    "Skipped because no changed file matches [{}].",
+                    condition.changed_paths.join(", ")
+                )));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Resolves the step references in the input of a condition.
+fn resolve(input: &str, context: &WhenContext<'_>) -> Result<String, UnresolvedOutput> {
+    substitute_references(input, "steps.", |reference| {
+        match reference.strip_suffix(".status") {
+            Some(name) => Ok(phase_of(name, context).map(|phase| phase.to_string())),
+            None => resolve_output(reference, context.outputs),
+        }
+    })
+}
+
+/// The phase of a step, or the combined phase of the steps expanded from a
+/// matrix step.
+fn phase_of(name: &str, context: &WhenContext<'_>) -> Option<StepPhase> {
+    if let Some(phase) = context.phases.get(name) {
+        return Some(*phase);
+    }
+    let phases: Vec<StepPhase> = context
+        .groups
+        .get(name)?
+        .iter()
+        .filter_map(|member| context.phases.get(member.as_str()).copied())
+        .collect();
+    [
+        StepPhase::Failed,
+        StepPhase::Cancelled,
+        StepPhase::Running,
+        StepPhase::Pending,
+    ]
+    .into_iter()
+    .find(|phase| phases.contains(phase))
+    .or_else(|| {
+        let skipped = phases.iter().all(|phase| *phase == StepPhase::Skipped);
+        Some(if skipped {
+            StepPhase::Skipped
+        } else {
+            StepPhase::Succeeded
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(input: &str, operator: WhenOperator, values: &[&str]) -> WhenCondition {
+        WhenCondition {
+            input: Some(input.to_string()),
+            operator,
+            values: values.iter().map(|v| v.to_string()).collect(),
+            changed_paths: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let outputs = HashMap::from([(
+            "plan".to_string(),
+            BTreeMap::from([("changes".to_string(), "true".to_string())]),
+        )]);
+        let changed = vec!["services/api/main.go".to_string()];
+        let groups = HashMap::new();
+        let context = WhenContext {
+            phases: HashMap::from([("test", StepPhase::Failed)]),
+            groups: &groups,
+            outputs: &outputs,
+            changed_paths: Some(&changed),
+        };
+
+        let runs = vec![
+            input("release/1.2", WhenOperator::In, &["main", "release/*"]),
+            input("$(steps.plan.outputs.changes)", WhenOperator::In, &["true"]),
+            input("$(steps.test.status)", WhenOperator::NotIn, &["Succeeded"]),
+            WhenCondition {
+                changed_paths: vec!["services/api/**".to_string()],
+                ..Default::default()
+            },
+        ];
+        assert_eq!(evaluate("deploy", &runs, &context).unwrap(), None);
+        assert_eq!(references(&runs), vec!["plan", "test"]);
+        assert_eq!(status_references(&runs), vec!["test"]);
+
+        let skipped = vec![input("feature/x", WhenOperator::In, &["main"])];
+        assert_eq!(
+            evaluate("deploy", &skipped, &context).unwrap().as_deref(),
+            Some("Skipped because 'feature/x' (feature/x) is not in [main].")
+        );
+        let untouched = vec![WhenCondition {
+            changed_paths: vec!["docs/**".to_string()],
+            ..Default::default()
+        }];
+        assert!(evaluate("deploy", &untouched, &context).unwrap().is_some());
+        // Without the list of changed files, path conditions hold.
+        let unknown = WhenContext {
+            changed_paths: None,
+            ..context
+        };
+        assert_eq!(evaluate("deploy", &untouched, &unknown).unwrap(), None);
+        let missing = vec![input(
+            "$(steps.lint.outputs.report)",
+            WhenOperator::In,
+            &["ok"],
+        )];
+        assert!(matches!(
+            evaluate("deploy", &missing, &unknown),
+            Err(WhenError::Unresolved { .. })
+        ));
+    }
+
+    #[test]
+    fn test_matrix_step_status() {
+        let outputs = HashMap::new();
+        let groups = HashMap::from([(
+            "build".to_string(),
+            vec!["build-amd64".to_string(), "build-arm64".to_string()],
+        )]);
+        let context = |amd64, arm64| WhenContext {
+            phases: HashMap::from([("build-amd64", amd64), ("build-arm64", arm64)]),
+            groups: &groups,
+            outputs: &outputs,
+            changed_paths: None,
+        };
+        let notify = vec![input(
+            "$(steps.build.status)",
+            WhenOperator::In,
+            &["Failed"],
+        )];
+        assert_eq!(references(&notify), vec!["build"]);
+
+        let failed = context(StepPhase::Succeeded, StepPhase::Failed);
+        assert_eq!(evaluate("notify", &notify, &failed).unwrap(), None);
+        let succeeded = context(StepPhase::Succeeded, StepPhase::Skipped);
+        assert_eq!(
+            evaluate("notify", &notify, &succeeded).unwrap().as_deref(),
+            Some("Skipped because '$(steps.build.status)' (Succeeded) is not in [Failed].")
+        );
+        let skipped = context(StepPhase::Skipped, StepPhase::Skipped);
+        assert_eq!(
+            resolve("$(steps.build.status)", &skipped).unwrap(),
+            "Skipped"
+        );
+        // The status of an expanded step remains available on its own.
+        assert_eq!(
+            resolve("$(steps.build-arm64.status)", &failed).unwrap(),
+            "Failed"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let both = WhenCondition {
+            changed_paths: vec!["src/**".to_string()],
+            ..input("$(params.branch)", WhenOperator::In, &["main"])
+        };
+        assert_eq!(
+            validate(
+                "deploy",
+                &[input("main", WhenOperator::In, &["main"]), both]
+            ),
+            Err(WhenError::InvalidCondition {
+                step: "deploy".to_string(),
+                index: 1
+            })
+        );
+        assert!(validate("deploy", &[WhenCondition::default()]).is_err());
+    }
+}
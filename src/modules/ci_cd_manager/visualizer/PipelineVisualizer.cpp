@@ -51,12 +51,15 @@ bool PipelineVisualizer::loadFromJSON(const std::string& json_data) {
             Job current_job;
             val.at("name").get_to(current_job.name);
             val.at("runs_on").get_to(current_job.runs_on);
+            current_job.needs = val.value("needs", std::vector<std::string>{});
 
             const json& steps_json = val.at("steps");
             for (const auto& step_val : steps_json) {
                 Step current_step;
                 step_val.at("name").get_to(current_step.name);
-                step_val.at("run").get_to(current_step.run_command);
+                // A step runs either a command or an action, so both are optional.
+                current_step.run_command = step_val.value("run_command", "");
+                current_step.uses = step_val.value("uses", "");
                 current_job.steps.push_back(current_step);
             }
             m_pipeline.jobs.push_back(current_job);
@@ -77,10 +80,21 @@ void PipelineVisualizer::display() const {
 
     for (const auto& job : m_pipeline.jobs) {
         std::cout << "\n[JOB] " << job.name << " (Runs on: " << job.runs_on << ")" << std::endl;
+        if (!job.needs.empty()) {
+            std::cout << "  Needs:";
+            for (const auto& need : job.needs) {
+                std::cout << " " << need;
+            }
+            std::cout << std::endl;
+        }
         std::cout << "  `-------------------------------------------" << std::endl;
         for (const auto& step : job.steps) {
             std::cout << "    [STEP] Name: " << step.name << std::endl;
-            std::cout << "      -> Run: " << step.run_command << std::endl;
+            if (!step.uses.empty()) {
+                std::cout << "      -> Uses: " << step.uses << std::endl;
+            } else {
+                std::cout << "      -> Run: " << step.run_command << std::endl;
+            }
         }
     }
     std::cout << "\n==================================================" << std::endl;
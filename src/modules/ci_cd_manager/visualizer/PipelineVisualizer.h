@@ -2,8 +2,8 @@
  * PipelineVisualizer.h - Interface for the C++ CI/CD Pipeline Visualizer.
  *
  * This header defines the public interface for the PipelineVisualizer class.
- * This class is responsible for taking a JSON string (produced by the Go
- * parser), deserializing it into a rich C++ object model, and then rendering
+ * This class is responsible for taking a JSON string (produced by the Rust
+ * workflow parser), deserializing it into a rich C++ object model, and then rendering
  * a textual representation of that model to the console.
  *
  * The use of C++ allows for a strong, object-oriented representation of the
@@ -53,12 +53,14 @@ private:
 
     struct Step {
         std::string name;
-        std::string run_command;
+        std::string run_command; // Empty for steps that use an action.
+        std::string uses;
     };
 
     struct Job {
         std::string name;
         std::string runs_on;
+        std::vector<std::string> needs;
         std::vector<Step> steps;
     };
 
@@ -14,7 +14,7 @@
 * SPDX-License-Identifier: Apache-2.0 */
 
 mod commands;
-mod process_wrapper;
+pub mod process_wrapper;
 
 use libc::{c_char, c_int};
 use once_cell::sync::Lazy;
@@ -115,21 +115,23 @@ static STATIC_API_DATA: Lazy<StaticApiData> = Lazy::new(|| {
 
 
 // --- C-compatible API Implementation ---
+// The symbols are only exported with the `ffi` feature, so that a module
+// using this crate as a Rust library can export its own.
 
-#[no_mangle]
+#[cfg_attr(feature = "ffi", no_mangle)]
 pub extern "C" fn module_get_info() -> *const ModuleInfo {
     // Return a stable pointer to the `info` field of our static data.
     &STATIC_API_DATA.info
 }
 
-#[no_mangle]
+#[cfg_attr(feature = "ffi", no_mangle)]
 pub extern "C" fn module_init(_context: *const std::ffi::c_void) -> Status {
     // In this module, we don't need the context, but we keep the signature for consistency.
     println!("[DEVOPS_AUTOMATION] devops_automation (Rust) module initialized.");
     Status::Success
 }
 
-#[no_mangle]
+#[cfg_attr(feature = "ffi", no_mangle)]
 pub extern "C" fn module_exec(argc: c_int, argv: *const *const c_char) -> Status {
     // This unsafe block is necessary to interact with C pointers, but the logic
     // is contained within the safe `c_args_to_vec` helper function.
@@ -158,7 +160,7 @@ pub extern "C" fn module_exec(argc: c_int, argv: *const *const c_char) -> Status
     }
 }
 
-#[no_mangle]
+#[cfg_attr(feature = "ffi", no_mangle)]
 pub extern "C" fn module_cleanup() {
     println!("[DEVOPS_AUTOMATION] devops_automation (Rust) module cleaned up.");
     // All CString memory is managed by `Lazy` and cleaned up automatically at program exit.
@@ -2,24 +2,43 @@
 * src/process_wrapper.rs - Safe wrappers for executing external processes.
 *
 * This file provides a toolbox of helper functions for running external
-* command-line tools like `terraform` and `vault`. It abstracts the details
-* of `std::process::Command` to offer two distinct execution strategies:
+* command-line tools like `terraform`, `vault` or `docker`. It abstracts the
+* details of `std::process::Command` to offer three execution strategies:
 *
 * 1. Streaming: For long-running or interactive commands where real-time
 *    output is desired. The child process inherits the parent's stdio.
 * 2. Capturing: For commands that produce structured data (like JSON) on
 *    stdout, which needs to be captured and parsed by the application.
+* 3. Prefixed streaming: For commands running next to each other, like the
+*    steps of `ph ci run` (see the `workflow_parser` crate). Every line of
+*    output is printed after a prefix naming its command, and an optional
+*    timeout kills the command once it expires.
 *
 * Error handling is designed to be robust, returning detailed messages if a
 * command is not found or if it exits with a non-zero status code.
 *
 * SPDX-License-Identifier: Apache-2.0 */
 
+use std::io::{BufRead, BufReader, Read, Write};
 use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// A flexible error type for command execution failures.
 type CommandResult<T> = Result<T, Box<dyn std::error::Error>>;
 
+/// How often a command run with a prefix is checked for its exit or its timeout.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How a command run with a prefix ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// It exited with this code; -1 when it was killed by a signal.
+    Code(i32),
+    /// It was killed because it ran longer than its timeout.
+    TimedOut,
+}
+
 /// Executes a command and streams its stdout/stderr directly to the parent process.
 /// This is ideal for long-running, interactive commands like `terraform plan`.
 pub fn run_command_with_streaming(command_name: &str, args: &[String]) -> CommandResult<()> {
@@ -65,4 +84,78 @@ pub fn run_command_and_capture(command_name: &str, args: &[String]) -> CommandRe
     // If successful, we return its stdout as a String.
     let stdout = String::from_utf8(output.stdout)?;
     Ok(stdout)
-}
\ No newline at end of file
+}
+
+/// Executes a command, printing every line of its stdout and stderr after
+/// `prefix`. Returns how it ended; a non-zero exit code is not an error.
+pub fn run_command_with_prefix(
+    command_name: &str,
+    args: &[String],
+    prefix: &str,
+    timeout: Option<Duration>,
+) -> CommandResult<Exit> {
+    let mut child = Command::new(command_name)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            format!(
+                "Failed to execute '{}'. Is it in your PATH? Error: {}",
+                command_name, e
+            )
+        })?;
+
+    let readers = [
+        child.stdout.take().map(|out| forward(out, prefix, false)),
+        child.stderr.take().map(|err| forward(err, prefix, true)),
+    ];
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let exit = loop {
+        if let Some(status) = child.try_wait()? {
+            break Exit::Code(status.code().unwrap_or(-1));
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            child.kill()?;
+            child.wait()?;
+            break Exit::TimedOut;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    for reader in readers.into_iter().flatten() {
+        // A reader only fails if the terminal went away; nothing to report.
+        let _ = reader.join();
+    }
+    Ok(exit)
+}
+
+/// Copies the lines of `stream` to stdout (or stderr) after `prefix`.
+fn forward(
+    stream: impl Read + Send + 'static,
+    prefix: &str,
+    to_stderr: bool,
+) -> thread::JoinHandle<()> {
+    let prefix = prefix.to_string();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(['\n', '\r']);
+            // Each line is written at once, so the two streams never mix
+            // within a line.
+            let _ = if to_stderr {
+                writeln!(std::io::stderr().lock(), "{}{}", prefix, text)
+            } else {
+                writeln!(std::io::stdout().lock(), "{}{}", prefix, text)
+            };
+        }
+    })
+}
@@ -159,20 +159,23 @@ pub async fn handle_exec_action(config: &PreviewConfig) -> Result<()> {
     kube_client::exec_in_pod(&client, &namespace, &pod_name, command).await
 }
 
-/// Patches the `phPreview` custom resource to extend its TTL.
+/// Patches the `phPreview` custom resource so that it expires `--ttl` hours
+/// from now.
 pub async fn handle_extend_action(config: &PreviewConfig) -> Result<()> {
     let pr_number = config.pr_number.context("Missing --pr argument")?;
     let new_ttl = config.new_ttl.context("Missing --ttl argument")?;
     let resource_name = format!("pr-{}", pr_number);
 
-    println!("🚀 Extending TTL for phPreview resource '{}' to {} hours...", resource_name, new_ttl);
+    println!("🚀 Extending phPreview resource '{}' to expire in {} hours...", resource_name, new_ttl);
 
     let client = Client::try_default().await?;
     let previews: Api<phPreview> = Api::namespaced(client, PREVIEW_RESOURCE_NAMESPACE);
 
+    // `expiresAt` takes precedence over `ttl` and `ttlHours` in the operator.
+    let expires_at = Utc::now() + Duration::hours(i64::from(new_ttl));
     let patch = json!({
         "spec": {
-            "ttl_hours": new_ttl
+            "expiresAt": expires_at.to_rfc3339()
         }
     });
 
@@ -37,12 +37,10 @@ use k8s_openapi::api::{
 };
 use kube::{
     api::{
-        Api, AttachParams, DeleteParams, ListParams, LogParams, ObjectMeta, Patch, PatchParams,
-        PostParams,
+        Api, AttachParams, DeleteParams, ListParams, LogParams, ObjectMeta, PostParams,
     },
     Client, Config,
 };
-use serde_json::json;
 use std::collections::BTreeMap;
 use std::path::Path;
 /* BEGIN CHANGE: Add necessary imports for subprocess and directory walking. */
@@ -73,18 +71,18 @@ pub async fn initialize_client(kubeconfig_path: Option<&str>) -> Result<Client>
 }
 
 /// Creates a new namespace in the Kubernetes cluster.
-pub async fn create_namespace(client: &Client, name: &str, ttl_hours: u32) -> Result<()> {
+///
+/// The expiry of a preview is the `expiresAt` or `ttl` of its `phPreview`,
+/// which the operator enforces; the namespace does not record it.
+pub async fn create_namespace(client: &Client, name: &str) -> Result<()> {
     let ns_api: Api<Namespace> = Api::all(client.clone());
     let mut labels = BTreeMap::new();
     labels.insert("managed-by".to_string(), "peitch".to_string());
-    let mut annotations = BTreeMap::new();
-    annotations.insert("peitch.io/ttl-hours".to_string(), ttl_hours.to_string());
 
     let namespace = Namespace {
         metadata: ObjectMeta {
             name: Some(name.to_string()),
             labels: Some(labels),
-            annotations: Some(annotations),
             ..ObjectMeta::default()
         },
         ..Namespace::default()
@@ -311,25 +309,7 @@ pub async fn exec_in_pod(
     Ok(())
 }
 
-/// Updates the TTL annotation on a namespace.
-pub async fn update_namespace_ttl(client: &Client, name: &str, new_ttl_hours: u32) -> Result<()> {
-    let ns_api: Api<Namespace> = Api::all(client.clone());
-    let patch = json!({
-        "metadata": {
-            "annotations": {
-                "peitch.io/ttl-hours": new_ttl_hours.to_string()
-            }
-        }
-    });
-
-    ns_api
-        .patch(name, &PatchParams::default(), &Patch::Merge(&patch))
-        .await
-        .with_context(|| format!("Failed to patch namespace '{}'", name))?;
-    Ok(())
-}
-
-/// Lists all preview namespaces that have expired based on their creation time and TTL.
+/// Lists all preview namespaces older than `max_age_hours`.
 pub async fn list_expired_preview_namespaces(
     client: &Client,
     max_age_hours: u32,
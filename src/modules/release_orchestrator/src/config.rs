@@ -17,6 +17,10 @@ pub enum RolloutPayload {
     Status(StatusConfig),
     #[serde(rename = "promote")]
     Promote(IdConfig),
+    #[serde(rename = "resume")]
+    Resume(IdConfig),
+    #[serde(rename = "abort")]
+    Abort(IdConfig),
     #[serde(rename = "rollback")]
     Rollback(IdConfig),
     #[serde(rename = "plan")]
@@ -32,6 +36,8 @@ pub struct StartConfig {
     pub steps: Option<String>,
     pub metric: Option<String>,
     pub analysis_window: Option<String>,
+    /// Comma-separated weights after which the rollout waits for an approval.
+    pub approve_after: Option<String>,
     #[serde(default)]
     pub skip_sig_check: bool,
     pub public_key: Option<String>,
@@ -50,6 +56,8 @@ pub struct IdConfig {
     pub id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub to_revision: Option<i32>,
+    /// Who requests a promote/resume/abort. Defaults to the local user.
+    pub approver: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
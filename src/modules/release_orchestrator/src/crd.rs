@@ -41,30 +41,67 @@ pub struct Strategy {
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CanaryStrategy {
-    // These fields are now for the controller to parse.
-    // The CLI will populate the raw string fields below.
+    /// The ordered plan the operator walks through. Mirrors the operator's
+    /// `CanaryStep`; exactly one field of each step must be set.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub steps: Vec<CanaryStep>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub analysis: Option<Analysis>,
     #[serde(default)]
     pub auto_promote: bool,
+}
 
-    // Raw strings from CLI
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CanaryStep {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set_weight: Option<u8>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub steps_str: Option<String>,
+    pub pause: Option<PauseStep>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub metric_str: Option<String>,
+    pub analysis: Option<AnalysisStep>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub analysis_window_str: Option<String>,
+    pub set_header_route: Option<SetHeaderRoute>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approval: Option<ApprovalStep>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
 #[serde(rename_all = "camelCase")]
-pub struct CanaryStep {
-    pub set_weight: i32,
+pub struct PauseStep {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub analysis_window: Option<String>,
+    pub duration: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalStep {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisStep {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub metrics: Vec<String>,
+}
+
+/// Routes requests carrying the given headers to the canary. An empty
+/// `match` removes the route of the same name.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SetHeaderRoute {
+    pub name: String,
+    #[serde(default, rename = "match", skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<HeaderMatch>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderMatch {
+    pub header_name: String,
+    pub header_value: String,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
@@ -109,5 +146,20 @@ pub struct PhgitReleaseStatus {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub current_step: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub rollback_to: Option<i32>,
+    pub rollback_to_revision: Option<i32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revisions: Vec<ReleaseRevision>,
+}
+
+/// One entry of the release's revision history, maintained by the operator.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseRevision {
+    pub revision: i32,
+    pub version: String,
+    pub spec_hash: String,
+    pub started_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    pub outcome: String,
 }
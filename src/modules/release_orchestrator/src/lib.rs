@@ -13,11 +13,11 @@ pub mod crd;
 use anyhow::{anyhow, Context, Result};
 use config::{IdConfig, PlanConfig, RolloutPayload, StartConfig, StatusConfig};
 use crd::{
-    Analysis, CanaryStep, CanaryStrategy, Metric, PhgitRelease, PhgitReleaseSpec, PhgitReleaseStatus,
-    Strategy,
+    Analysis, AnalysisStep, ApprovalStep, CanaryStep, CanaryStrategy, Metric, PhgitRelease,
+    PhgitReleaseSpec, PhgitReleaseStatus, Strategy,
 };
 use kube::{
-    api::{Api, ObjectMeta, Patch, PatchParams, PostParams},
+    api::{Api, ObjectMeta, Patch, PatchParams},
     Client,
 };
 use prost::Message;
@@ -38,6 +38,10 @@ pub struct ErrorPayload {
 }
 
 const RELEASE_NAMESPACE: &str = "ph-releases";
+/// Annotations read by the operator to apply a manual rollout action.
+const ROLLOUT_ACTION_ANNOTATION: &str = "ph.io/rollout-action";
+const ROLLOUT_ACTOR_ANNOTATION: &str = "ph.io/rollout-actor";
+const ROLLOUT_REVISION_ANNOTATION: &str = "ph.io/rollout-revision";
 
 async fn handle_start(config: StartConfig) -> Result<()> {
     println!("🚀 Starting new release for app: {}", config.app);
@@ -47,16 +51,8 @@ async fn handle_start(config: StartConfig) -> Result<()> {
         .context("Failed to create Kubernetes client")?;
     let releases: Api<PhgitRelease> = Api::namespaced(client, RELEASE_NAMESPACE);
 
-    // --- Build a "dumb" Canary Strategy ---
-    // All parsing logic is moved to the operator. This module just passes the raw strings.
-    let canary_strategy = CanaryStrategy {
-        steps_str: config.steps,
-        metric_str: config.metric,
-        analysis_window_str: config.analysis_window,
-        auto_promote: true, // Defaulting to true for now
-        steps: vec![], // This will be populated by the controller
-        analysis: None, // This will be populated by the controller
-    };
+    // --- Build the typed Canary Strategy ---
+    let canary_strategy = build_canary_strategy(&config)?;
 
     let strategy = Strategy {
         type_: "Canary".to_string(),
@@ -81,63 +77,191 @@ async fn handle_start(config: StartConfig) -> Result<()> {
         }),
     };
 
-    // --- Create the Resource in Kubernetes ---
+    // --- Create or update the Resource in Kubernetes ---
+    // Applying over an existing release starts a new revision of it.
     releases
-        .create(&PostParams::default(), &release)
+        .patch(&config.app, &PatchParams::apply("ph-cli").force(), &Patch::Apply(&release))
         .await
-        .context(format!("Failed to create PhgitRelease resource for app '{}'", config.app))?;
+        .context(format!("Failed to apply PhgitRelease resource for app '{}'", config.app))?;
 
-    println!("✅ PhgitRelease resource '{}' created successfully. The operator will now process the rollout.", config.app);
+    println!("✅ PhgitRelease resource '{}' applied successfully. The operator will now process the rollout.", config.app);
     Ok(())
 }
 
+/// Translates the CLI flags into a typed canary plan.
+///
+/// `--steps 5,20,50,100` becomes one `setWeight` step per entry. When a
+/// `--metric` is given, an `analysis` step is inserted after every weight
+/// below 100, with `--analysis-window` as the analysis interval. Weights listed
+/// in `--approve-after` are followed by an `approval` step.
+fn build_canary_strategy(config: &StartConfig) -> Result<CanaryStrategy> {
+    let analysis = match &config.metric {
+        Some(metric_str) => {
+            let metric: Metric = serde_json::from_str(metric_str)
+                .context("Failed to parse --metric flag. It must be a valid JSON string.")?;
+            Some(Analysis {
+                interval: config
+                    .analysis_window
+                    .clone()
+                    .unwrap_or_else(|| "1m".to_string()),
+                threshold: 5,
+                max_failures: 2,
+                metrics: vec![metric],
+            })
+        }
+        None => None,
+    };
+
+    let weights = match &config.steps {
+        Some(steps_str) => steps_str
+            .split(',')
+            .map(|s| {
+                s.trim()
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|w| *w <= 100)
+                    .ok_or_else(|| anyhow!("Invalid step value: '{}'. Expected a weight between 0 and 100.", s))
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => vec![100],
+    };
+    // Analysis runs after each weight below 100%; without one, all traffic
+    // would go to the canary before any metric is checked.
+    if analysis.is_some() && !weights.iter().any(|weight| *weight < 100) {
+        return Err(anyhow!(
+            "--metric requires --steps with at least one weight below 100, e.g. --steps 10,50,100."
+        ));
+    }
+
+    let approve_after = match &config.approve_after {
+        Some(weights_str) => weights_str
+            .split(',')
+            .map(|s| {
+                s.trim()
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|w| weights.contains(w))
+                    .ok_or_else(|| anyhow!("Invalid --approve-after value: '{}'. Expected one of the --steps weights.", s))
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+
+    let mut steps = Vec::new();
+    for weight in weights {
+        steps.push(CanaryStep {
+            set_weight: Some(weight),
+            ..Default::default()
+        });
+        if analysis.is_some() && weight < 100 {
+            steps.push(CanaryStep {
+                analysis: Some(AnalysisStep::default()),
+                ..Default::default()
+            });
+        }
+        if approve_after.contains(&weight) {
+            steps.push(CanaryStep {
+                approval: Some(ApprovalStep {
+                    message: Some(format!("Approve to continue past {}% canary traffic.", weight)),
+                }),
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(CanaryStrategy {
+        steps,
+        analysis,
+        auto_promote: true, // Defaulting to true for now
+    })
+}
+
 async fn handle_status(config: StatusConfig) -> Result<()> {
     println!("🔎 Getting status for release: {}", config.id);
-    // Implementation would fetch the PhgitRelease resource and print its status
+    let client = Client::try_default().await.context("Failed to create Kubernetes client")?;
+    let releases: Api<PhgitRelease> = Api::namespaced(client, RELEASE_NAMESPACE);
+    let release = releases
+        .get(&config.id)
+        .await
+        .context(format!("Failed to get PhgitRelease '{}'", config.id))?;
+
+    let status = release.status.unwrap_or_default();
+    println!("Phase: {}", status.phase.as_deref().unwrap_or("Pending"));
+    if let Some(step) = status.current_step {
+        println!("Current step: {}", step);
+    }
+    if !status.revisions.is_empty() {
+        println!("{:<10} {:<24} {:<12} {}", "REVISION", "VERSION", "OUTCOME", "STARTED");
+        for revision in &status.revisions {
+            println!(
+                "{:<10} {:<24} {:<12} {}",
+                revision.revision, revision.version, revision.outcome, revision.started_at
+            );
+        }
+    }
     Ok(())
 }
 
 async fn handle_promote(config: IdConfig) -> Result<()> {
     println!("👍 Promoting release: {}", config.id);
-    let client = Client::try_default().await?;
-    let releases: Api<PhgitRelease> = Api::namespaced(client, RELEASE_NAMESPACE);
-    let patch = serde_json::json!({
-        "status": {
-            "state": "Promoted"
-        }
-    });
-    releases.patch_status(&config.id, &PatchParams::default(), &Patch::Merge(&patch)).await?;
-    println!("✅ Release '{}' promoted.", config.id);
+    request_rollout_action(&config, "promote").await?;
+    println!("✅ Promotion of release '{}' requested.", config.id);
     Ok(())
 }
 
-async fn handle_rollback(config: IdConfig) -> Result<()> {
-    println!("⏪ Rolling back release: {}", config.id);
+async fn handle_resume(config: IdConfig) -> Result<()> {
+    println!("▶️ Resuming release: {}", config.id);
+    request_rollout_action(&config, "resume").await?;
+    println!("✅ Release '{}' approved to continue.", config.id);
+    Ok(())
+}
+
+async fn handle_abort(config: IdConfig) -> Result<()> {
+    println!("🛑 Aborting release: {}", config.id);
+    request_rollout_action(&config, "abort").await?;
+    println!("✅ Abort of release '{}' requested.", config.id);
+    Ok(())
+}
+
+/// Asks the operator to apply a manual action by annotating the release. The
+/// operator records the approver in the release status and clears the annotations.
+async fn request_rollout_action(config: &IdConfig, action: &str) -> Result<()> {
     let client = Client::try_default().await.context("Failed to create Kubernetes client")?;
     let releases: Api<PhgitRelease> = Api::namespaced(client, RELEASE_NAMESPACE);
 
-    let patch = if let Some(revision) = config.to_revision {
-        println!("... to revision {}", revision);
-        serde_json::json!({
-            "status": {
-                "phase": "RollingBack",
-                "rollbackTo": revision
-            }
-        })
-    } else {
-        println!("... to previous version");
-        serde_json::json!({
-            "status": {
-                "phase": "RollingBack"
-            }
-        })
-    };
+    let approver = config
+        .approver
+        .clone()
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string());
+    let mut annotations = serde_json::json!({
+        ROLLOUT_ACTION_ANNOTATION: action,
+        ROLLOUT_ACTOR_ANNOTATION: approver
+    });
+    if let Some(revision) = config.to_revision {
+        annotations[ROLLOUT_REVISION_ANNOTATION] = revision.to_string().into();
+    }
+    let patch = serde_json::json!({ "metadata": { "annotations": annotations } });
 
-    releases.patch_status(&config.id, &PatchParams::default(), &Patch::Merge(&patch))
+    releases
+        .patch(&config.id, &PatchParams::default(), &Patch::Merge(&patch))
         .await
-        .context(format!("Failed to patch status for rollback on PhgitRelease '{}'", config.id))?;
+        .context(format!("Failed to request '{}' on PhgitRelease '{}'", action, config.id))?;
+    Ok(())
+}
+
+async fn handle_rollback(config: IdConfig) -> Result<()> {
+    println!("⏪ Rolling back release: {}", config.id);
+    match config.to_revision {
+        Some(revision) if revision < 1 => return Err(anyhow!("Invalid revision {}: revisions start at 1", revision)),
+        Some(revision) => println!("... to revision {}", revision),
+        None => println!("... to previous version"),
+    }
 
-    println!("✅ PhgitRelease '{}' status patched for rollback.", config.id);
+    // The operator validates the target against the release's revision history.
+    request_rollout_action(&config, "rollback").await?;
+    println!("✅ Rollback of release '{}' requested. Check 'ph rollout status {}' for the outcome.", config.id, config.id);
     Ok(())
 }
 
@@ -168,6 +292,8 @@ pub extern "C" fn run_release_orchestrator(
                 RolloutPayload::Start(cfg) => handle_start(cfg).await,
                 RolloutPayload::Status(cfg) => handle_status(cfg).await,
                 RolloutPayload::Promote(cfg) => handle_promote(cfg).await,
+                RolloutPayload::Resume(cfg) => handle_resume(cfg).await,
+                RolloutPayload::Abort(cfg) => handle_abort(cfg).await,
                 RolloutPayload::Rollback(cfg) => handle_rollback(cfg).await,
                 RolloutPayload::Plan(cfg) => handle_plan(cfg).await,
             }
@@ -201,4 +327,54 @@ pub extern "C" fn run_release_orchestrator(
     });
 
     result.unwrap_or(-5)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METRIC: &str = r#"{"name": "error-rate", "query": "rate(errors[1m])", "onSuccess": "result < 0.01"}"#;
+
+    fn config(steps: Option<&str>, metric: Option<&str>) -> StartConfig {
+        StartConfig {
+            strategy: "canary".to_string(),
+            app: "shop".to_string(),
+            image: "shop:2.0".to_string(),
+            steps: steps.map(str::to_string),
+            metric: metric.map(str::to_string),
+            analysis_window: None,
+            approve_after: None,
+            skip_sig_check: true,
+            public_key: None,
+        }
+    }
+
+    #[test]
+    fn test_canary_strategy_with_metric() {
+        let strategy = build_canary_strategy(&config(Some("10,50,100"), Some(METRIC))).unwrap();
+        let kinds: Vec<&str> = strategy
+            .steps
+            .iter()
+            .map(|step| match (step.set_weight, &step.analysis) {
+                (Some(_), _) => "weight",
+                (None, Some(_)) => "analysis",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(kinds, ["weight", "analysis", "weight", "analysis", "weight"]);
+        assert_eq!(strategy.analysis.unwrap().metrics[0].name, "error-rate");
+    }
+
+    #[test]
+    fn test_metric_without_steps() {
+        // A single 100% step would send all traffic to the canary unanalysed.
+        let error = build_canary_strategy(&config(None, Some(METRIC))).unwrap_err();
+        assert!(error.to_string().contains("--metric requires --steps"));
+        assert!(build_canary_strategy(&config(Some("100"), Some(METRIC))).is_err());
+
+        // Without a metric, a direct promotion is fine.
+        let strategy = build_canary_strategy(&config(None, None)).unwrap();
+        assert_eq!(strategy.steps.len(), 1);
+        assert_eq!(strategy.steps[0].set_weight, Some(100));
+    }
+}
@@ -36,6 +36,14 @@ impl TrafficManagerClient for ArgoRolloutsClient {
         println!("Updating Argo Rollout '{}' in namespace '{}'", split.app_name, ns);
         
         let rollouts: Api<serde_json::Value> = Api::namespaced(self.client.clone(), ns);
+
+        if !split.matches.is_empty() {
+            println!(
+                "Match-based routing is managed by the Rollout's own traffic router. Ignoring {} match rule(s) for '{}'.",
+                split.matches.len(),
+                split.app_name
+            );
+        }
         
         // Argo Rollouts uses a `setCanaryScale` action. The exact weight is determined
         // by the steps defined in the Rollout spec. Here, we'll just set the weight
@@ -0,0 +1,356 @@
+/* Copyright (C) 2025 Pedro Henrique / phkaiser13
+* File: src/modules/release_orchestrator/src/mesh/gateway.rs
+* This file provides the concrete implementation of the `TrafficManagerClient`
+* trait for the Kubernetes Gateway API. It manages an `HTTPRoute` whose single
+* rule carries weighted `backendRefs` pointing at the versioned Services of an
+* application (`<app>-stable`, `<app>-canary`), preceded by a rule that sends
+* requests matching the split's match rules to the canary. This works with any Gateway API
+* implementation (Envoy Gateway, Contour, Cilium, ...) and does not require a
+* service mesh.
+* SPDX-License-Identifier: Apache-2.0 */
+
+use super::{RouteMatch, StringMatch, TrafficManagerClient, TrafficSplit};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use kube::{
+    api::{Api, Patch, PatchParams},
+    Client, CustomResource,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// --- Custom Resource Definition for the Gateway API ---
+
+/// Defines the Rust struct for the Gateway API `HTTPRoute` CRD.
+/// We only define the fields relevant to weighted and match-based traffic splitting.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(
+    group = "gateway.networking.k8s.io",
+    version = "v1",
+    kind = "HTTPRoute",
+    plural = "httproutes"
+)]
+#[kube(namespaced)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPRouteSpec {
+    /// The Gateways (or, for east-west traffic, the Service) the route attaches to.
+    pub parent_refs: Vec<ParentReference>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hostnames: Vec<String>,
+    pub rules: Vec<HTTPRouteRule>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParentReference {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section_name: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPRouteRule {
+    /// A request matching any entry is handled by this rule. Rules with
+    /// matches take precedence over a rule without any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<HTTPRouteMatch>,
+    pub backend_refs: Vec<HTTPBackendRef>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPRouteMatch {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<HTTPValueMatch>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query_params: Vec<HTTPValueMatch>,
+}
+
+/// A header or query parameter match.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+pub struct HTTPValueMatch {
+    #[serde(rename = "type")]
+    pub match_type: HTTPMatchType,
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+pub enum HTTPMatchType {
+    Exact,
+    RegularExpression,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPBackendRef {
+    /// The name of the backend Service (the versioned service).
+    pub name: String,
+    pub port: u16,
+    /// The relative weight of this backend. A weight of 0 sends it no traffic.
+    pub weight: u16,
+}
+
+/// Describes where the managed `HTTPRoute` attaches and which port it targets.
+#[derive(Clone, Debug, Default)]
+pub struct GatewayRouteConfig {
+    /// The parents of the route. When empty, the route attaches to the root
+    /// Service of the application (GAMMA-style east-west routing).
+    pub parent_refs: Vec<ParentReference>,
+    pub hostnames: Vec<String>,
+    /// The port of the versioned Services.
+    pub port: u16,
+}
+
+// --- Gateway API Client Implementation ---
+
+/// A client for interacting with the Kubernetes Gateway API.
+pub struct GatewayApiClient {
+    client: Client,
+    config: GatewayRouteConfig,
+}
+
+impl GatewayApiClient {
+    /// Creates a new `GatewayApiClient` that manages routes described by `config`.
+    pub fn new(client: Client, config: GatewayRouteConfig) -> Self {
+        Self { client, config }
+    }
+}
+
+/// Builds the `HTTPRoute` that distributes traffic according to the weights.
+/// The route is named after the application, like the Istio and SMI resources.
+pub fn build_http_route(split: &TrafficSplit, config: &GatewayRouteConfig) -> HTTPRoute {
+    let parent_refs = if config.parent_refs.is_empty() {
+        vec![ParentReference {
+            group: Some(String::new()),
+            kind: Some("Service".to_string()),
+            name: split.app_name.clone(),
+            ..Default::default()
+        }]
+    } else {
+        config.parent_refs.clone()
+    };
+
+    let backend_refs = split
+        .weights
+        .iter()
+        .map(|(version, weight)| HTTPBackendRef {
+            name: format!("{}-{}", split.app_name, version),
+            port: config.port,
+            weight: *weight as u16,
+        })
+        .collect();
+
+    let mut rules = Vec::new();
+    if !split.matches.is_empty() {
+        rules.push(HTTPRouteRule {
+            matches: split.matches.iter().map(to_http_route_match).collect(),
+            backend_refs: vec![HTTPBackendRef {
+                name: format!("{}-canary", split.app_name),
+                port: config.port,
+                weight: 1,
+            }],
+        });
+    }
+    rules.push(HTTPRouteRule {
+        matches: Vec::new(),
+        backend_refs,
+    });
+
+    HTTPRoute::new(
+        &split.app_name,
+        HTTPRouteSpec {
+            parent_refs,
+            hostnames: config.hostnames.clone(),
+            rules,
+        },
+    )
+}
+
+/// Converts a generic match rule into its Gateway API representation.
+fn to_http_route_match(route_match: &RouteMatch) -> HTTPRouteMatch {
+    let convert = |(name, value): &(String, StringMatch)| {
+        let (match_type, value) = match value {
+            StringMatch::Exact(v) => (HTTPMatchType::Exact, v.clone()),
+            StringMatch::Regex(v) => (HTTPMatchType::RegularExpression, v.clone()),
+        };
+        HTTPValueMatch {
+            match_type,
+            name: name.clone(),
+            value,
+        }
+    };
+    HTTPRouteMatch {
+        headers: route_match.headers.iter().map(convert).collect(),
+        query_params: route_match.query_params.iter().map(convert).collect(),
+    }
+}
+
+#[async_trait]
+impl TrafficManagerClient for GatewayApiClient {
+    /// Updates the weighted backendRefs of the application's HTTPRoute.
+    async fn update_traffic_split(&self, namespace: &str, split: TrafficSplit) -> Result<()> {
+        println!(
+            "Updating Gateway API HTTPRoute for '{}' in namespace '{}'...",
+            split.app_name, namespace
+        );
+
+        let route_api: Api<HTTPRoute> = Api::namespaced(self.client.clone(), namespace);
+        let route = build_http_route(&split, &self.config);
+
+        // Use Server-Side Apply to create or update the HTTPRoute.
+        let ssapply = PatchParams::apply("peitch.release_orchestrator");
+        route_api
+            .patch(&split.app_name, &ssapply, &Patch::Apply(&route))
+            .await
+            .with_context(|| format!("Failed to apply HTTPRoute for '{}'", split.app_name))?;
+
+        println!("Successfully applied HTTPRoute for '{}'.", split.app_name);
+        Ok(())
+    }
+
+    /// Promotes a release by shifting 100% of traffic to the "canary" backend.
+    async fn promote(&self, ns: &str, app_name: &str) -> Result<()> {
+        println!("Promoting release for '{}' in namespace '{}' via Gateway API", app_name, ns);
+        let split = TrafficSplit {
+            app_name: app_name.to_string(),
+            weights: vec![
+                ("stable".to_string(), 0),
+                ("canary".to_string(), 100),
+            ],
+            matches: Vec::new(),
+        };
+        self.update_traffic_split(ns, split).await
+    }
+
+    /// Rolls back a release by shifting 100% of traffic to the "stable" backend.
+    async fn rollback(&self, ns: &str, app_name: &str) -> Result<()> {
+        println!("Rolling back release for '{}' in namespace '{}' via Gateway API", app_name, ns);
+        let split = TrafficSplit {
+            app_name: app_name.to_string(),
+            weights: vec![
+                ("stable".to_string(), 100),
+                ("canary".to_string(), 0),
+            ],
+            matches: Vec::new(),
+        };
+        self.update_traffic_split(ns, split).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(weights: &[(&str, u8)], matches: Vec<RouteMatch>) -> TrafficSplit {
+        TrafficSplit {
+            app_name: "test-app".to_string(),
+            weights: weights.iter().map(|(version, weight)| (version.to_string(), *weight)).collect(),
+            matches,
+        }
+    }
+
+    fn backends(rule: &HTTPRouteRule) -> Vec<(String, u16, u16)> {
+        rule.backend_refs.iter().map(|b| (b.name.clone(), b.port, b.weight)).collect()
+    }
+
+    #[test]
+    fn test_build_http_route_weights() {
+        let config = GatewayRouteConfig { port: 8080, ..Default::default() };
+        let route = build_http_route(&split(&[("stable", 90), ("canary", 10)], Vec::new()), &config);
+
+        assert_eq!(route.metadata.name.as_deref(), Some("test-app"));
+        assert_eq!(route.spec.rules.len(), 1);
+        assert!(route.spec.rules[0].matches.is_empty());
+        assert_eq!(
+            backends(&route.spec.rules[0]),
+            vec![("test-app-stable".to_string(), 8080, 90), ("test-app-canary".to_string(), 8080, 10)]
+        );
+
+        // Without explicit parents the route attaches to the root Service.
+        assert_eq!(
+            route.spec.parent_refs,
+            vec![ParentReference {
+                group: Some(String::new()),
+                kind: Some("Service".to_string()),
+                name: "test-app".to_string(),
+                ..Default::default()
+            }]
+        );
+        assert!(route.spec.hostnames.is_empty());
+    }
+
+    #[test]
+    fn test_build_http_route_matches() {
+        let route_match = RouteMatch {
+            headers: vec![("x-canary".to_string(), StringMatch::Exact("always".to_string()))],
+            query_params: vec![("preview".to_string(), StringMatch::Exact("true".to_string()))],
+        }
+        .with_cookie("beta", "1");
+        let config = GatewayRouteConfig {
+            parent_refs: vec![ParentReference {
+                name: "eg".to_string(),
+                namespace: Some("envoy-gateway-system".to_string()),
+                ..Default::default()
+            }],
+            hostnames: vec!["shop.example.com".to_string()],
+            port: 80,
+        };
+        let route = build_http_route(&split(&[("stable", 100), ("canary", 0)], vec![route_match]), &config);
+
+        assert_eq!(route.spec.parent_refs, config.parent_refs);
+        assert_eq!(route.spec.hostnames, vec!["shop.example.com".to_string()]);
+        assert_eq!(route.spec.rules.len(), 2);
+
+        // Matching requests go to the canary, whatever the weights.
+        let canary_rule = &route.spec.rules[0];
+        assert_eq!(backends(canary_rule), vec![("test-app-canary".to_string(), 80, 1)]);
+        let headers = &canary_rule.matches[0].headers;
+        assert_eq!(headers[0].match_type, HTTPMatchType::Exact);
+        assert_eq!((headers[0].name.as_str(), headers[0].value.as_str()), ("x-canary", "always"));
+        assert_eq!(headers[1].match_type, HTTPMatchType::RegularExpression);
+        assert_eq!(headers[1].name, "cookie");
+        assert_eq!(canary_rule.matches[0].query_params[0].name, "preview");
+
+        let default_rule = &route.spec.rules[1];
+        assert!(default_rule.matches.is_empty());
+        assert_eq!(
+            backends(default_rule),
+            vec![("test-app-stable".to_string(), 80, 100), ("test-app-canary".to_string(), 80, 0)]
+        );
+    }
+
+    #[test]
+    fn test_rendered_backend_refs() {
+        let route = build_http_route(
+            &split(&[("stable", 75), ("canary", 25)], Vec::new()),
+            &GatewayRouteConfig { port: 80, ..Default::default() },
+        );
+        let rendered = serde_json::to_value(&route).unwrap();
+
+        assert_eq!(rendered["apiVersion"], "gateway.networking.k8s.io/v1");
+        assert_eq!(rendered["kind"], "HTTPRoute");
+        assert_eq!(
+            rendered["spec"]["parentRefs"],
+            serde_json::json!([{ "group": "", "kind": "Service", "name": "test-app" }])
+        );
+        // Empty matches and hostnames are left out of the applied manifest.
+        assert!(rendered["spec"].get("hostnames").is_none());
+        assert_eq!(
+            rendered["spec"]["rules"],
+            serde_json::json!([{
+                "backendRefs": [
+                    { "name": "test-app-stable", "port": 80, "weight": 75 },
+                    { "name": "test-app-canary", "port": 80, "weight": 25 }
+                ]
+            }])
+        );
+    }
+}
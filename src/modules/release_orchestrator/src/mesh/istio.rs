@@ -4,10 +4,10 @@
 * trait for the Istio service mesh. It defines the Rust structs corresponding
 * to Istio's `VirtualService` and `DestinationRule` Custom Resources (CRs) and
 * contains the logic to manipulate these resources to control traffic routing
-* for canary and blue-green deployments.
+* for canary, blue-green and shadow (traffic mirroring) deployments.
 * SPDX-License-Identifier: Apache-2.0 */
 
-use super::{TrafficManagerClient, TrafficSplit};
+use super::{RouteMatch, StringMatch, TrafficManagerClient, TrafficMirror, TrafficSplit};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use kube::{
@@ -21,7 +21,7 @@ use std::collections::BTreeMap;
 // --- Custom Resource Definitions for Istio ---
 
 /// Defines the Rust struct for Istio's `VirtualService` CRD.
-/// We only define the fields relevant to traffic splitting.
+/// We only define the fields relevant to weighted and match-based traffic splitting.
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[kube(
     group = "networking.istio.io",
@@ -37,12 +37,68 @@ pub struct VirtualServiceSpec {
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
 pub struct HTTPRoute {
+    /// A request matching any entry is handled by this route. Routes are
+    /// evaluated in order, so match routes come before the weighted route.
+    #[serde(rename = "match", default, skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<HTTPMatchRequest>,
     pub route: Vec<HTTPRouteDestination>,
+    /// A destination that receives a copy of the requests. Its responses are discarded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<Destination>,
+    #[serde(rename = "mirrorPercentage", default, skip_serializing_if = "Option::is_none")]
+    pub mirror_percentage: Option<Percent>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+pub struct Percent {
+    pub value: f64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPMatchRequest {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, IstioStringMatch>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub query_params: BTreeMap<String, IstioStringMatch>,
+}
+
+/// Istio's `StringMatch`: exactly one of the fields is set.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IstioStringMatch {
+    Exact(String),
+    Regex(String),
+}
+
+impl From<&StringMatch> for IstioStringMatch {
+    fn from(value: &StringMatch) -> Self {
+        match value {
+            StringMatch::Exact(v) => IstioStringMatch::Exact(v.clone()),
+            StringMatch::Regex(v) => IstioStringMatch::Regex(v.clone()),
+        }
+    }
+}
+
+impl From<&RouteMatch> for HTTPMatchRequest {
+    fn from(route_match: &RouteMatch) -> Self {
+        let convert = |conditions: &[(String, StringMatch)]| {
+            conditions
+                .iter()
+                .map(|(name, value)| (name.clone(), IstioStringMatch::from(value)))
+                .collect()
+        };
+        HTTPMatchRequest {
+            headers: convert(&route_match.headers),
+            query_params: convert(&route_match.query_params),
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
 pub struct HTTPRouteDestination {
     pub destination: Destination,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub weight: Option<u8>,
 }
 
@@ -136,11 +192,33 @@ impl IstioClient {
             })
             .collect();
 
+        let mut http = Vec::new();
+        if !split.matches.is_empty() {
+            http.push(HTTPRoute {
+                matches: split.matches.iter().map(HTTPMatchRequest::from).collect(),
+                route: vec![HTTPRouteDestination {
+                    destination: Destination {
+                        host: split.app_name.clone(),
+                        subset: "canary".to_string(),
+                    },
+                    weight: None,
+                }],
+                mirror: None,
+                mirror_percentage: None,
+            });
+        }
+        http.push(HTTPRoute {
+            matches: Vec::new(),
+            route: routes,
+            mirror: None,
+            mirror_percentage: None,
+        });
+
         let vs = VirtualService::new(
             &split.app_name,
             VirtualServiceSpec {
                 hosts: vec![split.app_name.clone()],
-                http: vec![HTTPRoute { route: routes }],
+                http,
             },
         );
 
@@ -153,6 +231,44 @@ impl IstioClient {
 
         Ok(())
     }
+
+    /// Applies a `VirtualService` that serves all traffic from the stable subset
+    /// and mirrors a share of it to another subset.
+    async fn apply_mirror_virtual_service(&self, namespace: &str, mirror: &TrafficMirror) -> Result<()> {
+        let vs_api: Api<VirtualService> = Api::namespaced(self.client.clone(), namespace);
+        let vs = VirtualService::new(
+            &mirror.app_name,
+            VirtualServiceSpec {
+                hosts: vec![mirror.app_name.clone()],
+                http: vec![HTTPRoute {
+                    matches: Vec::new(),
+                    route: vec![HTTPRouteDestination {
+                        destination: Destination {
+                            host: mirror.app_name.clone(),
+                            subset: "stable".to_string(),
+                        },
+                        weight: Some(100),
+                    }],
+                    mirror: Some(Destination {
+                        host: mirror.app_name.clone(),
+                        subset: mirror.mirror_to.clone(),
+                    }),
+                    mirror_percentage: Some(Percent {
+                        value: mirror.percent as f64,
+                    }),
+                }],
+            },
+        );
+
+        // Use Server-Side Apply to create or update the VirtualService.
+        let ssapply = PatchParams::apply("peitch.release_orchestrator");
+        vs_api
+            .patch(&mirror.app_name, &ssapply, &Patch::Apply(&vs))
+            .await
+            .with_context(|| format!("Failed to apply mirroring VirtualService for '{}'", mirror.app_name))?;
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -184,6 +300,7 @@ impl TrafficManagerClient for IstioClient {
                 ("stable".to_string(), 0),
                 ("canary".to_string(), 100),
             ],
+            matches: Vec::new(),
         };
         self.update_traffic_split(ns, split).await
     }
@@ -197,7 +314,31 @@ impl TrafficManagerClient for IstioClient {
                 ("stable".to_string(), 100),
                 ("canary".to_string(), 0),
             ],
+            matches: Vec::new(),
         };
         self.update_traffic_split(ns, split).await
     }
+
+    /// Serves all traffic from the "stable" subset and mirrors a share of it.
+    async fn update_mirror(&self, ns: &str, mirror: TrafficMirror) -> Result<()> {
+        println!(
+            "Mirroring {}% of traffic for '{}' to '{}' in namespace '{}' via Istio",
+            mirror.percent, mirror.app_name, mirror.mirror_to, ns
+        );
+
+        // The DestinationRule only needs the subset names, not the weights.
+        let subsets = TrafficSplit {
+            app_name: mirror.app_name.clone(),
+            weights: vec![
+                ("stable".to_string(), 100),
+                (mirror.mirror_to.clone(), 0),
+            ],
+            matches: Vec::new(),
+        };
+        self.ensure_destination_rule(ns, &subsets).await?;
+        self.apply_mirror_virtual_service(ns, &mirror).await?;
+        println!("Successfully applied mirroring VirtualService for '{}'.", mirror.app_name);
+
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -71,6 +71,14 @@ impl TrafficManagerClient for LinkerdClient {
             split.app_name, namespace
         );
 
+        if !split.matches.is_empty() {
+            println!(
+                "SMI TrafficSplit does not support match-based routing. Ignoring {} match rule(s) for '{}'.",
+                split.matches.len(),
+                split.app_name
+            );
+        }
+
         let ts_api: Api<TrafficSplit> = Api::namespaced(self.client.clone(), namespace);
 
         // The name of the TrafficSplit resource is typically the same as the root service.
@@ -119,6 +127,7 @@ impl TrafficManagerClient for LinkerdClient {
                 ("stable".to_string(), 0),
                 ("canary".to_string(), 100),
             ],
+            matches: Vec::new(),
         };
         self.update_traffic_split(ns, split).await
     }
@@ -132,6 +141,7 @@ impl TrafficManagerClient for LinkerdClient {
                 ("stable".to_string(), 100),
                 ("canary".to_string(), 0),
             ],
+            matches: Vec::new(),
         };
         self.update_traffic_split(ns, split).await
     }
@@ -4,8 +4,8 @@
 * File: src/modules/release_orchestrator/src/mesh/mod.rs
 *
 * This module provides a unified interface for interacting with different
-* traffic management systems, including service meshes (like Istio, Linkerd)
-* and dedicated controllers like Argo Rollouts. It abstracts away the specific
+* traffic management systems, including service meshes (like Istio, Linkerd),
+* the Kubernetes Gateway API and dedicated controllers like Argo Rollouts. It abstracts away the specific
 * details of how each system handles traffic splitting, promotion, and rollbacks,
 * allowing the release_controller to work with a generic `TrafficManagerClient`.
 *
@@ -15,10 +15,11 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 
 pub mod argo;
+pub mod gateway;
 pub mod istio;
 pub mod linkerd;
 
@@ -29,6 +30,64 @@ pub struct TrafficSplit {
     /// A list of service versions and their corresponding traffic weights.
     /// e.g., `[("stable", 90), ("canary", 10)]`
     pub weights: Vec<(String, u8)>,
+    /// Requests matching any of these rules are sent to the `canary` version,
+    /// regardless of the weights.
+    pub matches: Vec<RouteMatch>,
+}
+
+/// Represents a desired traffic mirroring (shadow) configuration. All live
+/// traffic is served by the `stable` version while a copy of `percent` percent
+/// of it is sent to the `mirror_to` version, whose responses are discarded.
+pub struct TrafficMirror {
+    /// The name of the application or service being targeted.
+    pub app_name: String,
+    /// The version receiving the mirrored requests, e.g. `canary`.
+    pub mirror_to: String,
+    /// The percentage of live traffic to mirror.
+    pub percent: u8,
+}
+
+/// How a request attribute is compared against a value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StringMatch {
+    Exact(String),
+    /// An RE2 regular expression that must match the whole value.
+    Regex(String),
+}
+
+/// A set of request conditions that must all hold for a request to match.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RouteMatch {
+    /// Header name and value conditions.
+    pub headers: Vec<(String, StringMatch)>,
+    /// Query parameter name and value conditions.
+    pub query_params: Vec<(String, StringMatch)>,
+}
+
+impl RouteMatch {
+    /// Adds a condition on a cookie. Neither Istio nor the Gateway API match
+    /// cookies natively, so this becomes a regex over the `cookie` header.
+    pub fn with_cookie(mut self, name: &str, value: &str) -> Self {
+        let pattern = format!(
+            "^(.*?;\\s*)?{}={}(;.*)?$",
+            escape_regex(name),
+            escape_regex(value)
+        );
+        self.headers.push(("cookie".to_string(), StringMatch::Regex(pattern)));
+        self
+    }
+}
+
+/// Escapes the RE2 metacharacters in `literal`.
+fn escape_regex(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
 }
 
 /// A generic trait for clients that can manage progressive delivery.
@@ -42,4 +101,13 @@ pub trait TrafficManagerClient {
 
     /// Rolls back a release, shifting 100% of traffic to the stable version.
     async fn rollback(&self, ns: &str, app_name: &str) -> Result<()>;
-}
\ No newline at end of file
+
+    /// Routes all traffic to the stable version and mirrors a share of it to
+    /// another version. Any later traffic split, promotion or rollback ends the mirroring.
+    async fn update_mirror(&self, _ns: &str, mirror: TrafficMirror) -> Result<()> {
+        Err(anyhow!(
+            "Traffic mirroring for '{}' is not supported by this traffic manager",
+            mirror.app_name
+        ))
+    }
+}
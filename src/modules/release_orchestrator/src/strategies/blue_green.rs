@@ -39,6 +39,7 @@ impl Strategy for BlueGreenStrategy {
                 (config.new_version.clone(), 100),
                 (config.stable_version.clone(), 0),
             ],
+            matches: Vec::new(),
         };
 
         // Delegate the traffic switch to the underlying service mesh client.
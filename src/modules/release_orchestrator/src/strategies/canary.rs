@@ -52,6 +52,7 @@ impl Strategy for CanaryStrategy {
                 (config.stable_version.clone(), stable_weight),
                 (config.new_version.clone(), canary_weight),
             ],
+            matches: Vec::new(),
         };
 
         // 4. Delegate the actual implementation to the service mesh client.
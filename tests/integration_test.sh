@@ -273,6 +273,182 @@ fi
 kubectl delete phgitpreviews.ph.io/test-preview-failure -n ph-releases
 
 
+# Test Case 8: Pull request lifecycle of a preview
+print_header "Testing the preview lifecycle driven by pull request webhooks"
+kubectl create secret generic preview-webhook -n ph-releases --from-literal=secret=test-secret
+cat <<EOF | kubectl apply -f -
+apiVersion: ph.io/v1alpha1
+kind: phPreviewTrigger
+metadata:
+  name: example-app
+  namespace: ph-releases
+spec:
+  secretRef:
+    name: preview-webhook
+    key: secret
+  repository: phkaiser13/peitch-example-app
+  template:
+    manifestPath: "k8s"
+    appName: "example-app"
+EOF
+
+# tests/send_pr_webhook.sh stands in for the Git server.
+kubectl port-forward -n ph-operator-system deployment/ph-operator-controller-manager 8082:8082 &
+PORT_FORWARD_PID=$!
+sleep 3
+WEBHOOK_URL="http://127.0.0.1:8082/previews/ph-releases/example-app"
+function send_pr() {
+    tests/send_pr_webhook.sh --url "${WEBHOOK_URL}" --secret test-secret \
+        --repo phkaiser13/peitch-example-app --number 1 "$@"
+}
+
+if tests/send_pr_webhook.sh --url "${WEBHOOK_URL}" --secret wrong-secret --action opened --number 1 --sha 1111111; then
+    echo "FAIL: A delivery with an invalid signature was accepted."
+    exit 1
+fi
+
+send_pr --action opened --sha 1111111
+kubectl get phpreviews.ph.io/example-app-pr-1 -n ph-releases -o jsonpath='{.spec.commitSha}' | grep "1111111"
+send_pr --action synchronize --sha 2222222
+kubectl get phpreviews.ph.io/example-app-pr-1 -n ph-releases -o jsonpath='{.spec.commitSha}' | grep "2222222"
+send_pr --action closed --merged --sha 2222222
+if ! kubectl wait --for=delete phpreviews.ph.io/example-app-pr-1 -n ph-releases --timeout=60s; then
+    echo "FAIL: The preview of a merged pull request was not deleted."
+    exit 1
+fi
+echo "PASS: Pull request webhooks created, updated and deleted the preview."
+kill "${PORT_FORWARD_PID}"
+kubectl delete phpreviewtriggers.ph.io/example-app -n ph-releases
+kubectl delete secret preview-webhook -n ph-releases
+
+# Test Case 9: Guardrails and concurrency cap of a preview size class
+print_header "Testing preview size class guardrails and concurrency caps"
+cat <<EOF | kubectl apply -f -
+apiVersion: ph.io/v1alpha1
+kind: phPreviewClass
+metadata:
+  name: small
+spec:
+  quota:
+    pods: "10"
+  containerLimits:
+    default:
+      cpu: 200m
+      memory: 256Mi
+  concurrency:
+    maxPerRepository: 1
+    whenExceeded: Queue
+EOF
+for name in capped-a capped-b; do
+cat <<EOF | kubectl apply -f -
+apiVersion: ph.io/v1alpha1
+kind: phPreview
+metadata:
+  name: ${name}
+  namespace: ph-releases
+spec:
+  repoUrl: "https://github.com/phkaiser13/peitch-example-app"
+  branch: "main"
+  manifestPath: "k8s"
+  appName: "${name}"
+  sizeClass: small
+EOF
+sleep 5
+done
+
+ns=""
+for _ in {1..15}; do
+  ns=$(kubectl get phpreviews.ph.io/capped-a -n ph-releases -o jsonpath='{.status.namespace}')
+  [[ -n "$ns" ]] && break
+  sleep 2
+done
+if [[ -z "$ns" ]]; then
+  echo "FAIL: The first preview of the repository was not admitted."
+  exit 1
+fi
+kubectl get resourcequota/ph-preview-quota limitrange/ph-preview-limits networkpolicy/ph-preview-default-deny -n "$ns"
+
+reason=$(kubectl get phpreviews.ph.io/capped-b -n ph-releases -o jsonpath='{.status.conditions[?(@.type=="Ready")].reason}')
+if [[ "$reason" != "Queued" ]]; then
+  echo "FAIL: The second preview of the repository was not queued (reason: '${reason}')."
+  exit 1
+fi
+
+# Deleting the running preview frees the slot for the queued one.
+kubectl delete phpreviews.ph.io/capped-a -n ph-releases
+ns=""
+for _ in {1..30}; do
+  ns=$(kubectl get phpreviews.ph.io/capped-b -n ph-releases -o jsonpath='{.status.namespace}')
+  [[ -n "$ns" ]] && break
+  sleep 2
+done
+if [[ -z "$ns" ]]; then
+  echo "FAIL: The queued preview was not admitted once a slot was freed."
+  exit 1
+fi
+echo "PASS: The size class stamped its guardrails and queued the preview over the cap."
+kubectl delete phpreviews.ph.io/capped-b -n ph-releases
+kubectl delete phpreviewclasses.ph.io/small
+
+# Test Case 10: TTL expiry and sleep/wake scheduling of a preview
+print_header "Testing preview expiry and working hours"
+# Working hours that start in twelve hours: the preview sleeps once deployed.
+start=$(date -u -d '+12 hours' +%H:%M)
+end=$(date -u -d '+13 hours' +%H:%M)
+cat <<EOF | kubectl apply -f -
+apiVersion: ph.io/v1alpha1
+kind: phPreview
+metadata:
+  name: sleepy
+  namespace: ph-releases
+spec:
+  repoUrl: "https://github.com/phkaiser13/peitch-example-app"
+  branch: "main"
+  manifestPath: "k8s"
+  appName: "sleepy"
+  ttl: "5m"
+  idleSchedule:
+    days: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+    start: "${start}"
+    end: "${end}"
+    wakeFor: "30m"
+EOF
+kubectl wait --for=jsonpath='{.status.deployedCommit}' phpreviews.ph.io/sleepy -n ph-releases --timeout=120s
+# Any change makes the controller look at the preview again.
+kubectl annotate phpreviews.ph.io/sleepy -n ph-releases ph.io/test=sleep --overwrite
+if ! kubectl wait --for=jsonpath='{.status.conditions[?(@.type=="Ready")].reason}'=Sleeping phpreviews.ph.io/sleepy -n ph-releases --timeout=60s; then
+    echo "FAIL: The preview did not go to sleep outside its working hours."
+    exit 1
+fi
+ns=$(kubectl get phpreviews.ph.io/sleepy -n ph-releases -o jsonpath='{.status.namespace}')
+if kubectl get deployments -n "$ns" -o jsonpath='{.items[*].spec.replicas}' | grep -q '[1-9]'; then
+    echo "FAIL: The workloads of the sleeping preview were not scaled to zero."
+    exit 1
+fi
+
+kubectl port-forward -n ph-operator-system deployment/ph-operator-controller-manager 8083:8083 &
+PORT_FORWARD_PID=$!
+sleep 5
+code=$(curl -s -o /dev/null -w '%{http_code}' http://127.0.0.1:8083/wake/ph-releases/sleepy)
+kill "${PORT_FORWARD_PID}"
+if [[ "$code" != "503" ]]; then
+    echo "FAIL: The waker answered ${code} instead of the waking page."
+    exit 1
+fi
+if ! kubectl wait --for=jsonpath='{.status.awakeUntil}' phpreviews.ph.io/sleepy -n ph-releases --timeout=60s; then
+    echo "FAIL: The preview was not woken up."
+    exit 1
+fi
+kubectl wait --for=jsonpath='{.status.conditions[?(@.type=="Ready")].reason}'=Deployed phpreviews.ph.io/sleepy -n ph-releases --timeout=120s
+echo "PASS: The preview slept outside its working hours and was woken up."
+
+# The TTL of five minutes deletes the preview.
+if ! kubectl wait --for=delete phpreviews.ph.io/sleepy -n ph-releases --timeout=420s; then
+    echo "FAIL: The preview was not deleted once expired."
+    exit 1
+fi
+echo "PASS: The preview was deleted once expired."
+
 # --- Final success message ---
 print_header "All integration tests passed!"
 exit 0
@@ -0,0 +1,69 @@
+#!/bin/bash
+# send_pr_webhook.sh - Stand-in for a Git server sending pull request webhooks.
+#
+# Builds a GitHub-style 'pull_request' payload, signs it with the webhook
+# secret (X-Hub-Signature-256, an HMAC-SHA256 of the body) and posts it to
+# the operator, the way GitHub would deliver it to a phPreviewTrigger.
+#
+# Usage:
+#   send_pr_webhook.sh --url URL --secret SECRET --action ACTION \
+#       --number N --sha SHA [--repo OWNER/NAME] [--clone-url URL] \
+#       [--head BRANCH] [--base BRANCH] [--merged] [--delivery ID]
+#
+# ACTION is a GitHub pull request action: opened, reopened, synchronize or
+# closed. Prints the operator's reply and exits non-zero unless it accepted
+# the delivery.
+
+set -e
+set -o pipefail
+
+REPO="acme/shop"
+CLONE_URL=""
+HEAD_BRANCH="feature/preview"
+BASE_BRANCH="main"
+MERGED="false"
+DELIVERY="$(cat /proc/sys/kernel/random/uuid 2>/dev/null || date +%s%N)"
+
+function usage() {
+    sed -n '8,12p' "$0" | sed 's/^# \{0,1\}//'
+    exit 2
+}
+
+while [[ $# -gt 0 ]]; do
+    case "$1" in
+        --url) URL="$2"; shift 2 ;;
+        --secret) SECRET="$2"; shift 2 ;;
+        --action) ACTION="$2"; shift 2 ;;
+        --number) NUMBER="$2"; shift 2 ;;
+        --sha) SHA="$2"; shift 2 ;;
+        --repo) REPO="$2"; shift 2 ;;
+        --clone-url) CLONE_URL="$2"; shift 2 ;;
+        --head) HEAD_BRANCH="$2"; shift 2 ;;
+        --base) BASE_BRANCH="$2"; shift 2 ;;
+        --merged) MERGED="true"; shift ;;
+        --delivery) DELIVERY="$2"; shift 2 ;;
+        *) usage ;;
+    esac
+done
+
+if [[ -z "$URL" || -z "$SECRET" || -z "$ACTION" || -z "$NUMBER" || -z "$SHA" ]]; then
+    usage
+fi
+CLONE_URL="${CLONE_URL:-https://github.com/${REPO}.git}"
+
+BODY=$(cat <<EOF
+{"action":"${ACTION}","number":${NUMBER},"pull_request":{"head":{"ref":"${HEAD_BRANCH}","sha":"${SHA}"},"base":{"ref":"${BASE_BRANCH}","sha":"0000000000000000000000000000000000000000"},"merged":${MERGED}},"repository":{"full_name":"${REPO}","clone_url":"${CLONE_URL}"}}
+EOF
+)
+SIGNATURE=$(printf '%s' "$BODY" | openssl dgst -sha256 -hmac "$SECRET" | sed 's/^.*= //')
+
+STATUS=$(curl --silent --show-error --output /dev/stderr --write-out '%{http_code}' \
+    -X POST "$URL" \
+    -H "Content-Type: application/json" \
+    -H "X-GitHub-Event: pull_request" \
+    -H "X-GitHub-Delivery: ${DELIVERY}" \
+    -H "X-Hub-Signature-256: sha256=${SIGNATURE}" \
+    --data-binary "$BODY")
+echo ""
+echo "HTTP ${STATUS}"
+[[ "$STATUS" == "202" ]]